@@ -168,6 +168,9 @@ pub struct OptionalENConfig {
         default = "OptionalENConfig::default_max_l1_batches_per_tree_iter"
     )]
     pub max_l1_batches_per_tree_iter: usize,
+    /// If set, the Merkle tree is reported as not ready until it catches up to within this number of L1 batches
+    /// of the last sealed L1 batch (e.g., after the tree is recovered from a snapshot).
+    pub merkle_tree_ready_lag_batches: Option<u32>,
     /// Chunk size for multi-get operations. Can speed up loading data for the Merkle tree on some environments,
     /// but the effects vary wildly depending on the setup (e.g., the filesystem used).
     #[serde(default = "OptionalENConfig::default_merkle_tree_multi_get_chunk_size")]
@@ -180,9 +183,267 @@ pub struct OptionalENConfig {
     /// large value (order of 512 MiB) is helpful for large DBs that experience write stalls.
     #[serde(default = "OptionalENConfig::default_merkle_tree_memtable_capacity_mb")]
     merkle_tree_memtable_capacity_mb: usize,
+    /// Limit on the total capacity of memtables across all column families of the Merkle tree RocksDB
+    /// (in megabytes). If not set, only the capacity of memtables for each column family is limited.
+    #[serde(default)]
+    merkle_tree_total_write_buffer_size_mb: Option<usize>,
+    /// Maximum number of files kept open by the Merkle tree RocksDB. If not set, the number is not limited.
+    #[serde(default)]
+    pub merkle_tree_max_open_files: Option<usize>,
     /// Timeout to wait for the Merkle tree database to run compaction on stalled writes.
     #[serde(default = "OptionalENConfig::default_merkle_tree_stalled_writes_timeout_sec")]
     merkle_tree_stalled_writes_timeout_sec: u64,
+    /// Approximate number of entries recovered in a single chunk during Merkle tree recovery from a snapshot.
+    /// The value is persisted in the tree when recovery starts, so changing it mid-recovery has no effect.
+    #[serde(default = "OptionalENConfig::default_merkle_tree_recovery_chunk_size")]
+    pub merkle_tree_recovery_chunk_size: u64,
+    /// Maximum number of attempts to recover a single chunk during Merkle tree recovery. Only transient errors
+    /// (e.g., Postgres connection resets or statement timeouts) are retried.
+    #[serde(default = "OptionalENConfig::default_merkle_tree_recovery_max_chunk_attempts")]
+    pub merkle_tree_recovery_max_chunk_attempts: usize,
+    /// Delay before the first retry of a failed chunk during Merkle tree recovery. The delay is doubled
+    /// for each following retry.
+    #[serde(
+        default = "OptionalENConfig::default_merkle_tree_recovery_chunk_retry_initial_delay_ms"
+    )]
+    merkle_tree_recovery_chunk_retry_initial_delay_ms: u64,
+    /// Maximum delay between retries of a failed chunk during Merkle tree recovery.
+    #[serde(default = "OptionalENConfig::default_merkle_tree_recovery_chunk_retry_max_delay_ms")]
+    merkle_tree_recovery_chunk_retry_max_delay_ms: u64,
+    /// Maximum number of chunks recovered concurrently during Merkle tree recovery. Must not exceed the size
+    /// of the Postgres connection pool used by the Merkle tree; if not set, the pool size is used.
+    pub merkle_tree_recovery_concurrency: Option<usize>,
+    /// If set, Merkle tree recovery concurrency is adaptive: it is decreased down to this value if loading
+    /// chunk entries becomes slow, and increased back once it speeds up.
+    pub merkle_tree_recovery_min_concurrency: Option<usize>,
+    /// If set, the maximum Merkle tree recovery concurrency can be changed without restarting the node
+    /// by writing the new value to the file at this path and sending `SIGHUP` to the node process.
+    pub merkle_tree_recovery_concurrency_override_path: Option<String>,
+    /// Average latency of loading entries for a chunk during Merkle tree recovery, above which adaptive
+    /// concurrency is decreased. Only used if `merkle_tree_recovery_min_concurrency` is set.
+    #[serde(default = "OptionalENConfig::default_merkle_tree_recovery_slow_chunk_threshold_ms")]
+    merkle_tree_recovery_slow_chunk_threshold_ms: u64,
+    /// If set, each chunk is applied to the Merkle tree in sub-chunks of this size during recovery,
+    /// so that recovery of a large chunk can be resumed mid-way after a restart.
+    pub merkle_tree_recovery_sub_chunk_size: Option<usize>,
+    /// If set, entries of each chunk are streamed from Postgres in batches of this size during Merkle tree
+    /// recovery, bounding peak memory usage. Takes precedence over `merkle_tree_recovery_sub_chunk_size`.
+    pub merkle_tree_recovery_streaming_batch_size: Option<usize>,
+    /// If set, Merkle tree nodes are hashed during recovery using a dedicated thread pool with the specified
+    /// number of threads.
+    pub merkle_tree_recovery_hashing_threads: Option<usize>,
+    /// If set, soft cap (in megabytes) on the total size of chunk entries loaded from Postgres but not yet applied
+    /// to the Merkle tree during recovery. Once the cap is exceeded, chunks don't start loading their entries
+    /// until some of the loaded entries are applied.
+    merkle_tree_recovery_loaded_entries_soft_cap_mb: Option<usize>,
+    /// If set, the Merkle tree is recovered to the specified L1 batch instead of the snapshot the node
+    /// was recovered from. Postgres must contain metadata and storage logs for this batch.
+    pub merkle_tree_recovery_target_l1_batch: Option<u32>,
+    /// If set, chunks with the largest estimated number of entries are recovered first during Merkle tree recovery.
+    #[serde(default)]
+    pub merkle_tree_recovery_prioritize_large_chunks: bool,
+    /// If set, the Merkle tree is wiped and its recovery is restarted from scratch if the persisted recovery
+    /// chunk plan differs from the current one. Otherwise, such a mismatch results in an error.
+    #[serde(default)]
+    pub merkle_tree_recovery_force_replan: bool,
+    /// If set, the Merkle tree is wiped and its recovery is restarted from scratch if Postgres genesis differs
+    /// from the one the recovery was started for. Otherwise, such a mismatch results in an error.
+    #[serde(default)]
+    pub merkle_tree_recovery_allow_tree_reset_on_regenesis: bool,
+    /// If set, the Merkle tree is removed and initialized from scratch if it cannot be used as is (e.g., it's being
+    /// recovered to a different L1 batch, or it cannot be upgraded to the full mode). Otherwise, such a tree results
+    /// in an error.
+    #[serde(default)]
+    pub merkle_tree_recovery_allow_tree_reset: bool,
+    /// Estimated number of bytes occupied by a single Merkle tree entry in RocksDB. Used to check that there is
+    /// enough disk space before starting Merkle tree recovery. If set to 0, the check is skipped.
+    #[serde(default = "OptionalENConfig::default_merkle_tree_recovery_estimated_bytes_per_entry")]
+    pub merkle_tree_recovery_estimated_bytes_per_entry: u64,
+    /// Disk space (in megabytes) that should remain available after Merkle tree recovery according to the estimate.
+    #[serde(default = "OptionalENConfig::default_merkle_tree_recovery_disk_space_margin_mb")]
+    merkle_tree_recovery_disk_space_margin_mb: usize,
+    /// If set, Merkle tree recovery refuses to start if there isn't enough disk space for it according
+    /// to the estimate. Otherwise, only a warning is logged.
+    #[serde(default)]
+    pub merkle_tree_recovery_strict_disk_space_check: bool,
+    /// If set, the recovered Merkle tree is verified by sampling the specified number of entries from each
+    /// recovery chunk and comparing them with Postgres.
+    pub merkle_tree_recovery_verification_samples_per_chunk: Option<usize>,
+    /// If set, Merkle proofs for the specified number of entries sampled from the recovered Merkle tree
+    /// are verified against the recovered root hash; an invalid proof fails the node startup.
+    pub merkle_tree_recovery_proof_verification_samples: Option<usize>,
+    /// If set, the Merkle tree root hash is recorded after each recovered chunk in an append-only log
+    /// next to the tree RocksDB directory. The log is archived when tree recovery is finalized.
+    #[serde(default)]
+    pub merkle_tree_recovery_fingerprint_log: bool,
+    /// Maximum number of chunks processed by a single Postgres query and a single tree lookup when filtering out
+    /// recovered chunks before Merkle tree recovery starts or resumes.
+    #[serde(default = "OptionalENConfig::default_merkle_tree_recovery_chunk_filter_batch_size")]
+    pub merkle_tree_recovery_chunk_filter_batch_size: usize,
+    /// If set, snapshot chunks are loaded during Merkle tree recovery using a dedicated Postgres connection pool
+    /// of the specified size. Connections in the pool are identified by the `tree_recovery` application name.
+    pub merkle_tree_recovery_pool_size: Option<u32>,
+    /// Statement timeout (in seconds) for the dedicated Merkle tree recovery connection pool.
+    pub merkle_tree_recovery_statement_timeout_sec: Option<u64>,
+    /// URL of a Postgres read replica used for heavy snapshot queries during Merkle tree recovery. Queries fall back
+    /// to the main database if the replica lags behind the snapshot or fails.
+    pub merkle_tree_recovery_replica_url: Option<String>,
+    /// If set, Merkle tree versions older than the specified number of versions behind the latest one are pruned
+    /// after tree recovery and on each node start, and the tree RocksDB is compacted afterwards.
+    pub merkle_tree_recovery_pruning_retained_versions: Option<u64>,
+    /// Whether to backfill L1 batches processed by the Merkle tree in the lightweight mode if the node is configured
+    /// to run the tree in the full mode. If not set, the tree just continues in the full mode without the backfill.
+    #[serde(default)]
+    pub merkle_tree_recovery_allow_lightweight_tree_upgrade: bool,
+    /// If set, a root hash mismatch of the recovered Merkle tree is diagnosed by comparing entries in each recovery
+    /// chunk in Postgres and in the tree, reporting up to the specified number of diverging keys per chunk.
+    pub merkle_tree_recovery_mismatch_diagnostic_keys_per_chunk: Option<usize>,
+    /// If set, the recovered Merkle tree is exported to the specified directory as a RocksDB checkpoint together
+    /// with a JSON descriptor, so that it can be distributed to other nodes.
+    pub merkle_tree_recovery_export_path: Option<String>,
+    /// If set, an empty Merkle tree is initialized by importing the tree exported to the specified directory
+    /// instead of recovering it from Postgres. Setting this to `merkle_tree_recovery_export_path` makes the export
+    /// a local backup, from which the tree is restored if its data directory is lost.
+    pub merkle_tree_recovery_import_path: Option<String>,
+    /// If set, failing to import the Merkle tree results in an error instead of recovering the tree from Postgres.
+    #[serde(default)]
+    pub merkle_tree_recovery_strict_import: bool,
+    /// If set, snapshot entries for Merkle tree recovery are loaded from the specified snapshot file
+    /// instead of Postgres. The file must contain the snapshot the node is recovered from.
+    pub merkle_tree_recovery_file: Option<String>,
+    /// If set, the Postgres snapshot is verified by recovering a temporary Merkle tree before recovering
+    /// the production one.
+    #[serde(default)]
+    pub merkle_tree_recovery_dry_run: bool,
+    /// If set together with `merkle_tree_recovery_dry_run`, the Merkle tree stops after the dry run
+    /// instead of proceeding with recovery.
+    #[serde(default)]
+    pub merkle_tree_recovery_stop_after_dry_run: bool,
+    /// If set, the entire Merkle tree is verified against the Postgres snapshot it was recovered from,
+    /// and the tree stops instead of normal operation.
+    #[serde(default)]
+    pub merkle_tree_recovery_verify_integrity: bool,
+    /// If set, the Merkle tree stops once it's recovered (and its root hash is checked against the snapshot)
+    /// instead of processing L1 batches, and the node shuts down. The node exits with an error if recovery fails.
+    #[serde(default)]
+    pub merkle_tree_recovery_stop_after_recovery: bool,
+    /// If set, the Merkle tree recovered from a snapshot is reported as recovering until it catches up
+    /// with Postgres to within `merkle_tree_ready_lag_batches` L1 batches, rather than as ready right after
+    /// recovery is finalized.
+    #[serde(default)]
+    pub merkle_tree_recovery_catch_up_before_ready: bool,
+    /// **UNSAFE; for debugging corrupted snapshots only.** If set, a root hash mismatch after Merkle tree recovery
+    /// is logged as an error instead of failing recovery, and the tree is marked as tainted. A tainted tree
+    /// is rejected for normal operation unless `merkle_tree_recovery_experimental_allow_tainted_tree` is set.
+    #[serde(default)]
+    pub merkle_tree_recovery_experimental_skip_root_hash_check: bool,
+    /// **UNSAFE; for debugging corrupted snapshots only.** If set, a Merkle tree tainted by skipping the root hash
+    /// check after recovery is used for normal operation, including serving Merkle proofs.
+    #[serde(default)]
+    pub merkle_tree_recovery_experimental_allow_tainted_tree: bool,
+    /// Minimum interval between health updates on recovered chunks during Merkle tree recovery.
+    /// The last recovered chunk is always reported.
+    #[serde(default = "OptionalENConfig::default_merkle_tree_recovery_health_update_interval_ms")]
+    merkle_tree_recovery_health_update_interval_ms: u64,
+    /// Interval between checks of the watchdog reporting Merkle tree recovery stalls. If set to 0,
+    /// the watchdog is disabled.
+    #[serde(default = "OptionalENConfig::default_merkle_tree_recovery_stall_check_interval_ms")]
+    merkle_tree_recovery_stall_check_interval_ms: u64,
+    /// Merkle tree recovery is reported as stalled if no chunk has finished loading within this threshold.
+    #[serde(default = "OptionalENConfig::default_merkle_tree_recovery_stall_threshold_ms")]
+    merkle_tree_recovery_stall_threshold_ms: u64,
+    /// Total duration of retrying to acquire a Postgres connection during Merkle tree recovery before recovery fails.
+    /// If set to 0, connection acquisition is not retried.
+    #[serde(
+        default = "OptionalENConfig::default_merkle_tree_recovery_connection_retry_timeout_ms"
+    )]
+    merkle_tree_recovery_connection_retry_timeout_ms: u64,
+    /// Timeout for a single attempt to acquire a Postgres connection during Merkle tree recovery. If it elapses
+    /// (e.g., because the pool is saturated by other components), a warning with pool stats is logged,
+    /// and acquisition is retried.
+    #[serde(
+        default = "OptionalENConfig::default_merkle_tree_recovery_connection_acquire_timeout_ms"
+    )]
+    merkle_tree_recovery_connection_acquire_timeout_ms: u64,
+    /// If set, entries of snapshot chunks are loaded from Postgres using binary `COPY` during Merkle tree recovery,
+    /// falling back to a conventional query if `COPY` fails (e.g., because of insufficient permissions).
+    #[serde(default)]
+    pub merkle_tree_recovery_use_copy: bool,
+    /// Interval between checks whether the snapshot is fully applied to Postgres. Merkle tree recovery waits
+    /// until the snapshot is applied if it's started concurrently with the snapshot recovery of the node.
+    #[serde(default = "OptionalENConfig::default_merkle_tree_recovery_snapshot_poll_interval_ms")]
+    merkle_tree_recovery_snapshot_poll_interval_ms: u64,
+    /// If set, the Merkle tree RocksDB is tuned for bulk loading during recovery: large column families use memtables
+    /// with the specified capacity (in megabytes), and auto-compaction is disabled until recovery is finalized.
+    #[serde(default)]
+    merkle_tree_recovery_bulk_load_memtable_capacity_mb: Option<usize>,
+    /// Maximum number of memtables for large column families during bulk loading in Merkle tree recovery.
+    #[serde(
+        default = "OptionalENConfig::default_merkle_tree_recovery_bulk_load_max_write_buffers"
+    )]
+    pub merkle_tree_recovery_bulk_load_max_write_buffers: usize,
+    /// Block cache capacity (in megabytes) of the Merkle tree RocksDB during bulk loading in Merkle tree recovery.
+    /// Overrides `merkle_tree_block_cache_size_mb` until recovery is finalized; ignored if bulk loading is disabled.
+    #[serde(default)]
+    merkle_tree_recovery_bulk_load_block_cache_size_mb: Option<usize>,
+    /// Limit on the total memtable capacity (in megabytes) during bulk loading in Merkle tree recovery.
+    /// Overrides `merkle_tree_total_write_buffer_size_mb` until recovery is finalized; ignored if bulk loading
+    /// is disabled.
+    #[serde(default)]
+    merkle_tree_recovery_bulk_load_total_write_buffer_size_mb: Option<usize>,
+    /// Maximum number of open files during bulk loading in Merkle tree recovery. Overrides
+    /// `merkle_tree_max_open_files` until recovery is finalized; ignored if bulk loading is disabled.
+    #[serde(default)]
+    pub merkle_tree_recovery_bulk_load_max_open_files: Option<usize>,
+    /// If set, the write-ahead log of the Merkle tree RocksDB is disabled during recovery, and writes are flushed
+    /// to disk only when recovery is finalized. Speeds up recovery at the cost of re-checking (and possibly
+    /// re-recovering) the last recovered chunks if the node crashes during recovery.
+    #[serde(default)]
+    pub merkle_tree_recovery_relaxed_durability: bool,
+    /// If set, both the start and the end of recovered Merkle tree chunks are checked when recovery is resumed,
+    /// so that partially written chunks are recovered again.
+    #[serde(default)]
+    pub merkle_tree_recovery_check_chunk_ends: bool,
+    /// If set, recovered Merkle tree chunks with entries mismatching Postgres are repaired when recovery is resumed
+    /// instead of failing recovery.
+    #[serde(default)]
+    pub merkle_tree_recovery_repair_mismatched_chunks: bool,
+    /// If set, memtables of the Merkle tree RocksDB are flushed to disk every specified number of chunks recovered
+    /// during Merkle tree recovery. Bounds memtable memory and avoids write stalls in the middle of a chunk.
+    #[serde(default)]
+    pub merkle_tree_recovery_flush_interval_chunks: Option<usize>,
+    /// If set, memtables of the Merkle tree RocksDB are flushed to disk every time approximately this number
+    /// of megabytes of snapshot entries is applied to the tree during recovery.
+    #[serde(default)]
+    merkle_tree_recovery_flush_interval_mb: Option<usize>,
+    /// Limit on the rate of background writes (flushes and compactions) of the Merkle tree RocksDB during recovery,
+    /// in megabytes per second. Prevents recovery from starving other processes sharing the disk (e.g., Postgres).
+    /// If not set, background writes are not limited.
+    #[serde(default)]
+    merkle_tree_recovery_background_write_rate_limit_mb: Option<usize>,
+    /// If set, the background write rate limit during Merkle tree recovery can be changed without restarting the node
+    /// by writing the new limit (in megabytes per second; 0 to remove the limit) to the file at this path
+    /// and sending `SIGHUP` to the node process.
+    #[serde(default)]
+    pub merkle_tree_recovery_background_write_rate_limit_override_path: Option<String>,
+    /// If set, limits the rate at which snapshot entries are loaded from Postgres and applied to the tree during
+    /// Merkle tree recovery (in entries per second). Caps the recovery load on Postgres if it serves other traffic.
+    #[serde(default)]
+    pub merkle_tree_recovery_max_entries_per_second: Option<usize>,
+    /// If set, Merkle tree recovery fails if it doesn't complete within this number of seconds since it was started
+    /// or resumed after a restart. Recovered chunks are persisted, so recovery is resumed from them after a restart.
+    #[serde(default)]
+    merkle_tree_recovery_max_duration_sec: Option<u64>,
+    /// If set and a newer snapshot appears in Postgres while the Merkle tree is recovered from an older one,
+    /// the tree is removed and recovered from the newer snapshot. Cannot be used together with
+    /// `merkle_tree_recovery_target_l1_batch`.
+    #[serde(default)]
+    pub merkle_tree_recovery_prefer_latest_snapshot: bool,
+    /// Interval (in milliseconds) between checks for a newer snapshot during Merkle tree recovery.
+    #[serde(
+        default = "OptionalENConfig::default_merkle_tree_recovery_latest_snapshot_check_interval_ms"
+    )]
+    merkle_tree_recovery_latest_snapshot_check_interval_ms: u64,
 
     // Other config settings
     /// Port on which the Prometheus exporter server is listening.
@@ -277,6 +538,70 @@ impl OptionalENConfig {
         30
     }
 
+    const fn default_merkle_tree_recovery_chunk_size() -> u64 {
+        200_000
+    }
+
+    const fn default_merkle_tree_recovery_max_chunk_attempts() -> usize {
+        5
+    }
+
+    const fn default_merkle_tree_recovery_chunk_retry_initial_delay_ms() -> u64 {
+        500
+    }
+
+    const fn default_merkle_tree_recovery_chunk_retry_max_delay_ms() -> u64 {
+        30_000
+    }
+
+    const fn default_merkle_tree_recovery_slow_chunk_threshold_ms() -> u64 {
+        10_000
+    }
+
+    const fn default_merkle_tree_recovery_estimated_bytes_per_entry() -> u64 {
+        1_500
+    }
+
+    const fn default_merkle_tree_recovery_disk_space_margin_mb() -> usize {
+        10_240
+    }
+
+    const fn default_merkle_tree_recovery_chunk_filter_batch_size() -> usize {
+        1_000
+    }
+
+    const fn default_merkle_tree_recovery_health_update_interval_ms() -> u64 {
+        1_000
+    }
+
+    const fn default_merkle_tree_recovery_stall_check_interval_ms() -> u64 {
+        60_000
+    }
+
+    const fn default_merkle_tree_recovery_stall_threshold_ms() -> u64 {
+        300_000
+    }
+
+    const fn default_merkle_tree_recovery_connection_retry_timeout_ms() -> u64 {
+        60_000
+    }
+
+    const fn default_merkle_tree_recovery_connection_acquire_timeout_ms() -> u64 {
+        30_000
+    }
+
+    const fn default_merkle_tree_recovery_snapshot_poll_interval_ms() -> u64 {
+        1_000
+    }
+
+    const fn default_merkle_tree_recovery_latest_snapshot_check_interval_ms() -> u64 {
+        60_000
+    }
+
+    const fn default_merkle_tree_recovery_bulk_load_max_write_buffers() -> usize {
+        6
+    }
+
     const fn default_fee_history_limit() -> u64 {
         1_024
     }
@@ -330,11 +655,113 @@ impl OptionalENConfig {
         self.merkle_tree_memtable_capacity_mb * BYTES_IN_MEGABYTE
     }
 
+    /// Returns the limit on the total memtable capacity for Merkle tree in bytes.
+    pub fn merkle_tree_total_write_buffer_size(&self) -> Option<usize> {
+        self.merkle_tree_total_write_buffer_size_mb
+            .map(|size_mb| size_mb * BYTES_IN_MEGABYTE)
+    }
+
     /// Returns the timeout to wait for the Merkle tree database to run compaction on stalled writes.
     pub fn merkle_tree_stalled_writes_timeout(&self) -> Duration {
         Duration::from_secs(self.merkle_tree_stalled_writes_timeout_sec)
     }
 
+    pub fn merkle_tree_recovery_chunk_retry_initial_delay(&self) -> Duration {
+        Duration::from_millis(self.merkle_tree_recovery_chunk_retry_initial_delay_ms)
+    }
+
+    pub fn merkle_tree_recovery_chunk_retry_max_delay(&self) -> Duration {
+        Duration::from_millis(self.merkle_tree_recovery_chunk_retry_max_delay_ms)
+    }
+
+    pub fn merkle_tree_recovery_slow_chunk_threshold(&self) -> Duration {
+        Duration::from_millis(self.merkle_tree_recovery_slow_chunk_threshold_ms)
+    }
+
+    pub fn merkle_tree_recovery_statement_timeout(&self) -> Option<Duration> {
+        self.merkle_tree_recovery_statement_timeout_sec
+            .map(Duration::from_secs)
+    }
+
+    pub fn merkle_tree_recovery_health_update_interval(&self) -> Duration {
+        Duration::from_millis(self.merkle_tree_recovery_health_update_interval_ms)
+    }
+
+    pub fn merkle_tree_recovery_stall_check_interval(&self) -> Duration {
+        Duration::from_millis(self.merkle_tree_recovery_stall_check_interval_ms)
+    }
+
+    pub fn merkle_tree_recovery_stall_threshold(&self) -> Duration {
+        Duration::from_millis(self.merkle_tree_recovery_stall_threshold_ms)
+    }
+
+    pub fn merkle_tree_recovery_connection_retry_timeout(&self) -> Duration {
+        Duration::from_millis(self.merkle_tree_recovery_connection_retry_timeout_ms)
+    }
+
+    pub fn merkle_tree_recovery_connection_acquire_timeout(&self) -> Duration {
+        Duration::from_millis(self.merkle_tree_recovery_connection_acquire_timeout_ms)
+    }
+
+    pub fn merkle_tree_recovery_snapshot_poll_interval(&self) -> Duration {
+        Duration::from_millis(self.merkle_tree_recovery_snapshot_poll_interval_ms)
+    }
+
+    /// Returns the disk space (in bytes) that should remain available after Merkle tree recovery.
+    pub fn merkle_tree_recovery_disk_space_margin(&self) -> usize {
+        self.merkle_tree_recovery_disk_space_margin_mb * BYTES_IN_MEGABYTE
+    }
+
+    /// Returns the soft cap (in bytes) on the total size of loaded entries not yet applied to the Merkle tree
+    /// during recovery.
+    pub fn merkle_tree_recovery_loaded_entries_soft_cap(&self) -> Option<usize> {
+        self.merkle_tree_recovery_loaded_entries_soft_cap_mb
+            .map(|cap_mb| cap_mb * BYTES_IN_MEGABYTE)
+    }
+
+    /// Returns the memtable capacity (in bytes) for large column families during bulk loading in Merkle tree
+    /// recovery, or `None` if bulk loading is disabled.
+    pub fn merkle_tree_recovery_bulk_load_memtable_capacity(&self) -> Option<usize> {
+        self.merkle_tree_recovery_bulk_load_memtable_capacity_mb
+            .map(|capacity_mb| capacity_mb * BYTES_IN_MEGABYTE)
+    }
+
+    /// Returns the block cache capacity (in bytes) during bulk loading in Merkle tree recovery.
+    pub fn merkle_tree_recovery_bulk_load_block_cache_size(&self) -> Option<usize> {
+        self.merkle_tree_recovery_bulk_load_block_cache_size_mb
+            .map(|size_mb| size_mb * BYTES_IN_MEGABYTE)
+    }
+
+    /// Returns the limit on the total memtable capacity (in bytes) during bulk loading in Merkle tree recovery.
+    pub fn merkle_tree_recovery_bulk_load_total_write_buffer_size(&self) -> Option<usize> {
+        self.merkle_tree_recovery_bulk_load_total_write_buffer_size_mb
+            .map(|size_mb| size_mb * BYTES_IN_MEGABYTE)
+    }
+
+    /// Returns the approximate number of bytes of applied snapshot entries between memtable flushes
+    /// during Merkle tree recovery.
+    pub fn merkle_tree_recovery_flush_interval_bytes(&self) -> Option<usize> {
+        self.merkle_tree_recovery_flush_interval_mb
+            .map(|size_mb| size_mb * BYTES_IN_MEGABYTE)
+    }
+
+    /// Returns the limit on the rate of background writes (in bytes per second) during Merkle tree recovery.
+    pub fn merkle_tree_recovery_background_write_rate_limit(&self) -> Option<usize> {
+        self.merkle_tree_recovery_background_write_rate_limit_mb
+            .map(|limit_mb| limit_mb * BYTES_IN_MEGABYTE)
+    }
+
+    /// Returns the interval between checks for a newer snapshot during Merkle tree recovery.
+    pub fn merkle_tree_recovery_latest_snapshot_check_interval(&self) -> Duration {
+        Duration::from_millis(self.merkle_tree_recovery_latest_snapshot_check_interval_ms)
+    }
+
+    /// Returns the maximum duration of Merkle tree recovery, if any.
+    pub fn merkle_tree_recovery_max_duration(&self) -> Option<Duration> {
+        self.merkle_tree_recovery_max_duration_sec
+            .map(Duration::from_secs)
+    }
+
     pub fn api_namespaces(&self) -> Vec<Namespace> {
         self.api_namespaces
             .clone()
@@ -1,4 +1,4 @@
-use std::{sync::Arc, time::Duration};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use anyhow::Context as _;
 use clap::Parser;
@@ -6,7 +6,7 @@ use futures::{future::FusedFuture, FutureExt as _};
 use metrics::EN_METRICS;
 use prometheus_exporter::PrometheusExporterConfig;
 use tokio::{sync::watch, task, time::sleep};
-use zksync_basic_types::{Address, L2ChainId};
+use zksync_basic_types::{Address, L1BatchNumber, L2ChainId};
 use zksync_core::{
     api_server::{
         execution_sandbox::VmConcurrencyLimiter,
@@ -19,6 +19,7 @@ use zksync_core::{
     l1_gas_price::MainNodeGasPriceFetcher,
     metadata_calculator::{
         MetadataCalculator, MetadataCalculatorConfig, MetadataCalculatorModeConfig,
+        MetadataCalculatorRecoveryConfig,
     },
     reorg_detector::ReorgDetector,
     setup_sigint_handler,
@@ -188,10 +189,145 @@ async fn init_tasks(
         },
         delay_interval: config.optional.metadata_calculator_delay(),
         max_l1_batches_per_iter: config.optional.max_l1_batches_per_tree_iter,
+        tree_ready_lag_batches: config.optional.merkle_tree_ready_lag_batches,
         multi_get_chunk_size: config.optional.merkle_tree_multi_get_chunk_size,
         block_cache_capacity: config.optional.merkle_tree_block_cache_size(),
         memtable_capacity: config.optional.merkle_tree_memtable_capacity(),
+        total_write_buffer_size: config.optional.merkle_tree_total_write_buffer_size(),
+        max_open_files: config.optional.merkle_tree_max_open_files,
         stalled_writes_timeout: config.optional.merkle_tree_stalled_writes_timeout(),
+        recovery: MetadataCalculatorRecoveryConfig {
+            desired_chunk_size: config.optional.merkle_tree_recovery_chunk_size,
+            max_chunk_attempts: config.optional.merkle_tree_recovery_max_chunk_attempts,
+            chunk_retry_initial_delay: config
+                .optional
+                .merkle_tree_recovery_chunk_retry_initial_delay(),
+            chunk_retry_max_delay: config.optional.merkle_tree_recovery_chunk_retry_max_delay(),
+            concurrency: config.optional.merkle_tree_recovery_concurrency,
+            min_concurrency: config.optional.merkle_tree_recovery_min_concurrency,
+            concurrency_override_path: config
+                .optional
+                .merkle_tree_recovery_concurrency_override_path
+                .as_ref()
+                .map(PathBuf::from),
+            slow_chunk_threshold: config.optional.merkle_tree_recovery_slow_chunk_threshold(),
+            sub_chunk_size: config.optional.merkle_tree_recovery_sub_chunk_size,
+            streaming_batch_size: config.optional.merkle_tree_recovery_streaming_batch_size,
+            hashing_threads: config.optional.merkle_tree_recovery_hashing_threads,
+            loaded_entries_soft_cap: config
+                .optional
+                .merkle_tree_recovery_loaded_entries_soft_cap(),
+            target_l1_batch: config
+                .optional
+                .merkle_tree_recovery_target_l1_batch
+                .map(L1BatchNumber),
+            prioritize_large_chunks: config.optional.merkle_tree_recovery_prioritize_large_chunks,
+            force_replan: config.optional.merkle_tree_recovery_force_replan,
+            allow_tree_reset_on_regenesis: config
+                .optional
+                .merkle_tree_recovery_allow_tree_reset_on_regenesis,
+            allow_tree_reset: config.optional.merkle_tree_recovery_allow_tree_reset,
+            estimated_bytes_per_entry: config
+                .optional
+                .merkle_tree_recovery_estimated_bytes_per_entry,
+            disk_space_margin: config.optional.merkle_tree_recovery_disk_space_margin(),
+            strict_disk_space_check: config.optional.merkle_tree_recovery_strict_disk_space_check,
+            verification_samples_per_chunk: config
+                .optional
+                .merkle_tree_recovery_verification_samples_per_chunk,
+            proof_verification_samples: config
+                .optional
+                .merkle_tree_recovery_proof_verification_samples,
+            fingerprint_log: config.optional.merkle_tree_recovery_fingerprint_log,
+            chunk_filter_batch_size: config.optional.merkle_tree_recovery_chunk_filter_batch_size,
+            pruning_retained_versions: config
+                .optional
+                .merkle_tree_recovery_pruning_retained_versions,
+            allow_lightweight_tree_upgrade: config
+                .optional
+                .merkle_tree_recovery_allow_lightweight_tree_upgrade,
+            mismatch_diagnostic_keys_per_chunk: config
+                .optional
+                .merkle_tree_recovery_mismatch_diagnostic_keys_per_chunk,
+            export_path: config
+                .optional
+                .merkle_tree_recovery_export_path
+                .as_ref()
+                .map(PathBuf::from),
+            import_path: config
+                .optional
+                .merkle_tree_recovery_import_path
+                .as_ref()
+                .map(PathBuf::from),
+            strict_import: config.optional.merkle_tree_recovery_strict_import,
+            recovery_file: config
+                .optional
+                .merkle_tree_recovery_file
+                .as_ref()
+                .map(PathBuf::from),
+            dry_run: config.optional.merkle_tree_recovery_dry_run,
+            stop_after_dry_run: config.optional.merkle_tree_recovery_stop_after_dry_run,
+            verify_integrity: config.optional.merkle_tree_recovery_verify_integrity,
+            stop_after_recovery: config.optional.merkle_tree_recovery_stop_after_recovery,
+            catch_up_before_ready: config.optional.merkle_tree_recovery_catch_up_before_ready,
+            experimental_skip_root_hash_check: config
+                .optional
+                .merkle_tree_recovery_experimental_skip_root_hash_check,
+            experimental_allow_tainted_tree: config
+                .optional
+                .merkle_tree_recovery_experimental_allow_tainted_tree,
+            health_update_interval: config
+                .optional
+                .merkle_tree_recovery_health_update_interval(),
+            stall_check_interval: config.optional.merkle_tree_recovery_stall_check_interval(),
+            stall_threshold: config.optional.merkle_tree_recovery_stall_threshold(),
+            connection_retry_timeout: config
+                .optional
+                .merkle_tree_recovery_connection_retry_timeout(),
+            connection_acquire_timeout: config
+                .optional
+                .merkle_tree_recovery_connection_acquire_timeout(),
+            use_copy: config.optional.merkle_tree_recovery_use_copy,
+            snapshot_poll_interval: config
+                .optional
+                .merkle_tree_recovery_snapshot_poll_interval(),
+            bulk_load_memtable_capacity: config
+                .optional
+                .merkle_tree_recovery_bulk_load_memtable_capacity(),
+            bulk_load_max_write_buffers: config
+                .optional
+                .merkle_tree_recovery_bulk_load_max_write_buffers,
+            bulk_load_block_cache_capacity: config
+                .optional
+                .merkle_tree_recovery_bulk_load_block_cache_size(),
+            bulk_load_total_write_buffer_size: config
+                .optional
+                .merkle_tree_recovery_bulk_load_total_write_buffer_size(),
+            bulk_load_max_open_files: config
+                .optional
+                .merkle_tree_recovery_bulk_load_max_open_files,
+            relaxed_durability: config.optional.merkle_tree_recovery_relaxed_durability,
+            check_chunk_ends: config.optional.merkle_tree_recovery_check_chunk_ends,
+            repair_mismatched_chunks: config
+                .optional
+                .merkle_tree_recovery_repair_mismatched_chunks,
+            flush_interval_chunks: config.optional.merkle_tree_recovery_flush_interval_chunks,
+            flush_interval_bytes: config.optional.merkle_tree_recovery_flush_interval_bytes(),
+            background_write_rate_limit: config
+                .optional
+                .merkle_tree_recovery_background_write_rate_limit(),
+            background_write_rate_limit_override_path: config
+                .optional
+                .merkle_tree_recovery_background_write_rate_limit_override_path
+                .as_ref()
+                .map(PathBuf::from),
+            max_entries_per_second: config.optional.merkle_tree_recovery_max_entries_per_second,
+            max_recovery_duration: config.optional.merkle_tree_recovery_max_duration(),
+            prefer_latest_snapshot: config.optional.merkle_tree_recovery_prefer_latest_snapshot,
+            latest_snapshot_check_interval: config
+                .optional
+                .merkle_tree_recovery_latest_snapshot_check_interval(),
+        },
     })
     .await;
     healthchecks.push(Box::new(metadata_calculator.tree_health_check()));
@@ -206,7 +342,8 @@ async fn init_tasks(
             .build()
             .await
             .context("failed to build connection pool for ConsistencyChecker")?,
-    );
+    )
+    .with_tree_recovery_status(metadata_calculator.recovery_status());
 
     let batch_status_updater = BatchStatusUpdater::new(
         &main_node_url,
@@ -223,6 +360,32 @@ async fn init_tasks(
         .build()
         .await
         .context("failed to build a tree_pool")?;
+    let metadata_calculator = match config.optional.merkle_tree_recovery_pool_size {
+        Some(pool_size) => {
+            let recovery_pool = ConnectionPool::builder(&config.postgres.database_url, pool_size)
+                .set_statement_timeout(config.optional.merkle_tree_recovery_statement_timeout())
+                .set_application_name("tree_recovery")
+                .build()
+                .await
+                .context("failed to build a tree recovery connection pool")?;
+            metadata_calculator.with_recovery_pool(recovery_pool)
+        }
+        None => metadata_calculator,
+    };
+    let metadata_calculator = match &config.optional.merkle_tree_recovery_replica_url {
+        Some(replica_url) => {
+            // The replica pool should be able to serve the same concurrency as the pool used to load chunks.
+            let pool_size = config.optional.merkle_tree_recovery_pool_size.unwrap_or(1);
+            let replica_pool = ConnectionPool::builder(replica_url, pool_size)
+                .set_statement_timeout(config.optional.merkle_tree_recovery_statement_timeout())
+                .set_application_name("tree_recovery")
+                .build()
+                .await
+                .context("failed to build a tree recovery replica connection pool")?;
+            metadata_calculator.with_replica_pool(replica_pool)
+        }
+        None => metadata_calculator,
+    };
     let tree_handle = task::spawn(metadata_calculator.run(tree_pool, tree_stop_receiver));
 
     let consistency_checker_handle = tokio::spawn(consistency_checker.run(stop_receiver.clone()));
@@ -447,10 +610,15 @@ async fn main() -> anyhow::Result<()> {
 
     let particular_crypto_alerts = None;
     let graceful_shutdown = None::<futures::future::Ready<()>>;
-    let tasks_allowed_to_finish = false;
+    // If the Merkle tree is configured to stop after recovery, its task finishing is the expected outcome.
+    let stop_after_recovery = config.optional.merkle_tree_recovery_stop_after_recovery;
+    let tasks_allowed_to_finish = stop_after_recovery;
+    let mut tasks_result = Ok(());
 
     tokio::select! {
-        _ = wait_for_tasks(task_handles, particular_crypto_alerts, graceful_shutdown, tasks_allowed_to_finish) => {},
+        result = wait_for_tasks(task_handles, particular_crypto_alerts, graceful_shutdown, tasks_allowed_to_finish) => {
+            tasks_result = result;
+        },
         _ = sigint_receiver => {
             tracing::info!("Stop signal received, shutting down");
         },
@@ -497,5 +665,9 @@ async fn main() -> anyhow::Result<()> {
         );
     }
 
+    if stop_after_recovery {
+        // Exit with an error code if recovery has failed, so that it's visible to the job running the node.
+        tasks_result.context("Merkle tree recovery failed")?;
+    }
     Ok(())
 }
@@ -1,8 +1,14 @@
-use std::{path::Path, time::Instant};
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use anyhow::Context as _;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use zksync_config::DBConfig;
+use zksync_core::metadata_calculator::{
+    inspect_tree_db, reset_tree_db, TreeDbInspection, TreeDbState,
+};
 use zksync_env_config::FromEnv;
 use zksync_merkle_tree::domain::ZkSyncTree;
 use zksync_storage::RocksDB;
@@ -20,17 +26,57 @@ struct Cli {
     /// applied to it last. If not specified, the latest tree version is checked.
     #[arg(long = "l1-batch")]
     l1_batch: Option<u32>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Inspects the Merkle tree RocksDB without modifying it, e.g. to check progress of tree recovery
+    /// from a snapshot. The tree can be inspected while it's used by a running node.
+    InspectRecovery {
+        /// Path to the tree RocksDB directory. If not specified, the path from the database config is used.
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// Outputs the inspection results as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Removes the Merkle tree RocksDB, so that the tree is recovered from scratch on the next node start.
+    /// This is the way to restart recovery that has failed with a root hash mismatch. The node must be stopped.
+    ResetTree {
+        /// Path to the tree RocksDB directory. If not specified, the path from the database config is used.
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
 }
 
 impl Cli {
-    fn run(self, config: &DBConfig) {
+    fn run(self, config: &DBConfig) -> anyhow::Result<()> {
+        match self.command {
+            None => {
+                Self::check_consistency(config, self.l1_batch);
+                Ok(())
+            }
+            Some(Command::InspectRecovery { path, json }) => {
+                let path = path.unwrap_or_else(|| config.merkle_tree.path.clone().into());
+                Self::inspect_recovery(path, json)
+            }
+            Some(Command::ResetTree { path }) => {
+                let path = path.unwrap_or_else(|| config.merkle_tree.path.clone().into());
+                Self::reset_tree(path)
+            }
+        }
+    }
+
+    fn check_consistency(config: &DBConfig, l1_batch: Option<u32>) {
         let db_path = &config.merkle_tree.path;
         tracing::info!("Verifying consistency of Merkle tree at {db_path}");
         let start = Instant::now();
         let db = RocksDB::new(Path::new(db_path));
         let tree = ZkSyncTree::new_lightweight(db.into());
 
-        let l1_batch_number = if let Some(number) = self.l1_batch {
+        let l1_batch_number = if let Some(number) = l1_batch {
             L1BatchNumber(number)
         } else {
             let next_number = tree.next_l1_batch_number();
@@ -45,6 +91,88 @@ impl Cli {
         tree.verify_consistency(l1_batch_number);
         tracing::info!("Merkle tree verified in {:?}", start.elapsed());
     }
+
+    fn inspect_recovery(path: PathBuf, json: bool) -> anyhow::Result<()> {
+        let runtime = tokio::runtime::Runtime::new().context("failed creating Tokio runtime")?;
+        let inspection = runtime.block_on(inspect_tree_db(path))?;
+        if json {
+            let output = serde_json::to_string_pretty(&inspection)
+                .context("failed serializing inspection results")?;
+            println!("{output}");
+        } else {
+            print_inspection(&inspection);
+        }
+        Ok(())
+    }
+
+    fn reset_tree(path: PathBuf) -> anyhow::Result<()> {
+        let runtime = tokio::runtime::Runtime::new().context("failed creating Tokio runtime")?;
+        runtime.block_on(reset_tree_db(path.clone()))?;
+        tracing::info!("Removed Merkle tree at `{}`", path.display());
+        Ok(())
+    }
+}
+
+fn print_inspection(inspection: &TreeDbInspection) {
+    const BYTES_IN_MEGABYTE: u64 = 1_024 * 1_024;
+
+    println!("Merkle tree at `{}`", inspection.path.display());
+    println!(
+        "Disk usage: {} MiB ({} bytes)",
+        inspection.disk_usage_bytes / BYTES_IN_MEGABYTE,
+        inspection.disk_usage_bytes
+    );
+    match &inspection.state {
+        TreeDbState::Empty => println!("State: empty"),
+        TreeDbState::Ready {
+            next_l1_batch_number,
+            root_hash,
+            leaf_count,
+        } => {
+            println!("State: ready, next L1 batch #{next_l1_batch_number}");
+            println!("Root hash: {root_hash:?}");
+            println!("Leaf count: {leaf_count}");
+        }
+        TreeDbState::Recovering(recovery) => {
+            println!(
+                "State: recovering to L1 batch #{}",
+                recovery.recovered_version
+            );
+            println!("Root hash: {:?}", recovery.root_hash);
+            println!("Leaf count: {}", recovery.leaf_count);
+            if let (Some(least_key), Some(greatest_key)) =
+                (recovery.least_key, recovery.greatest_key)
+            {
+                println!(
+                    "Key range: {least_key:?}..={greatest_key:?} (~{:.2}% of the keyspace)",
+                    recovery.key_span * 100.0
+                );
+            }
+            println!("Incomplete chunks: {}", recovery.incomplete_chunks.len());
+            for chunk in &recovery.incomplete_chunks {
+                match chunk.last_applied_key {
+                    Some(key) => println!(
+                        "  chunk starting at {:?}: applied up to {key:?}",
+                        chunk.start_key
+                    ),
+                    None => println!(
+                        "  chunk starting at {:?}: no entries applied",
+                        chunk.start_key
+                    ),
+                }
+            }
+            if recovery.may_have_lost_writes {
+                println!("Some writes may have been lost; recovered chunks will be re-checked on the next start");
+            }
+        }
+    }
+    if let Some(failure) = &inspection.recovery_failure {
+        println!(
+            "Recovery FAILED at UNIX timestamp {}: expected root hash {:?}, got {:?}",
+            failure.failed_at, failure.expected_root_hash, failure.actual_root_hash
+        );
+        println!("Manual intervention required; use `reset-tree` to restart recovery once the cause is fixed");
+    }
 }
 
 fn main() -> anyhow::Result<()> {
@@ -65,6 +193,5 @@ fn main() -> anyhow::Result<()> {
     let _guard = builder.build();
 
     let db_config = DBConfig::from_env().context("DBConfig::from_env()")?;
-    Cli::parse().run(&db_config);
-    Ok(())
+    Cli::parse().run(&db_config)
 }
@@ -161,9 +161,16 @@ async fn main() -> anyhow::Result<()> {
 
     let particular_crypto_alerts = None::<Vec<String>>;
     let graceful_shutdown = None::<futures::future::Ready<()>>;
-    let tasks_allowed_to_finish = false;
+    // If the Merkle tree is configured to stop after recovery, its task finishing is the expected outcome.
+    let stop_after_recovery = configs.db_config.as_ref().map_or(false, |config| {
+        config.merkle_tree.recovery.stop_after_recovery
+    });
+    let tasks_allowed_to_finish = stop_after_recovery;
+    let mut tasks_result = Ok(());
     tokio::select! {
-        _ = wait_for_tasks(core_task_handles, particular_crypto_alerts, graceful_shutdown, tasks_allowed_to_finish) => {},
+        result = wait_for_tasks(core_task_handles, particular_crypto_alerts, graceful_shutdown, tasks_allowed_to_finish) => {
+            tasks_result = result;
+        },
         _ = sigint_receiver => {
             tracing::info!("Stop signal received, shutting down");
         },
@@ -184,5 +191,9 @@ async fn main() -> anyhow::Result<()> {
     tokio::time::sleep(Duration::from_secs(5)).await;
     health_check_handle.stop().await;
     tracing::info!("Stopped");
+    if stop_after_recovery {
+        // Exit with an error code if recovery has failed, so that it's visible to the job running the server.
+        tasks_result.context("Merkle tree recovery failed")?;
+    }
     Ok(())
 }
@@ -38,12 +38,30 @@ pub struct MerkleTreeConfig {
     /// large value (order of 512 MiB) is helpful for large DBs that experience write stalls.
     #[serde(default = "MerkleTreeConfig::default_memtable_capacity_mb")]
     pub memtable_capacity_mb: usize,
+    /// Limit on the total capacity of memtables across all column families of the Merkle tree RocksDB (in megabytes).
+    /// If not set, only the capacity of memtables for each column family is limited.
+    #[serde(default)]
+    pub total_write_buffer_size_mb: Option<usize>,
+    /// Maximum number of files kept open by the Merkle tree RocksDB. Limiting this number reduces memory usage
+    /// by table readers at the cost of slower reads. If not set, the number of open files is not limited.
+    #[serde(default)]
+    pub max_open_files: Option<usize>,
     /// Timeout to wait for the Merkle tree database to run compaction on stalled writes.
     #[serde(default = "MerkleTreeConfig::default_stalled_writes_timeout_sec")]
     pub stalled_writes_timeout_sec: u64,
     /// Maximum number of L1 batches to be processed by the Merkle tree at a time.
     #[serde(default = "MerkleTreeConfig::default_max_l1_batches_per_iter")]
     pub max_l1_batches_per_iter: usize,
+    /// If set, the Merkle tree is reported as not ready until it catches up to within this number of L1 batches
+    /// of the last sealed L1 batch in Postgres (e.g., after the tree is recovered from a snapshot). Once the tree
+    /// has caught up, it's not reported as not ready again if it falls behind. If not set, the tree is reported
+    /// as ready as soon as it's initialized.
+    #[serde(default)]
+    pub tree_ready_lag_batches: Option<u32>,
+    /// Configuration of the Merkle tree recovery from a Postgres snapshot.
+    #[serde(skip)]
+    // ^ Filled in separately in `DBConfig::from_env()`; see the comment for `DBConfig::merkle_tree`.
+    pub recovery: MerkleTreeRecoveryConfig,
 }
 
 impl Default for MerkleTreeConfig {
@@ -54,8 +72,12 @@ impl Default for MerkleTreeConfig {
             multi_get_chunk_size: Self::default_multi_get_chunk_size(),
             block_cache_size_mb: Self::default_block_cache_size_mb(),
             memtable_capacity_mb: Self::default_memtable_capacity_mb(),
+            total_write_buffer_size_mb: None,
+            max_open_files: None,
             stalled_writes_timeout_sec: Self::default_stalled_writes_timeout_sec(),
             max_l1_batches_per_iter: Self::default_max_l1_batches_per_iter(),
+            tree_ready_lag_batches: None,
+            recovery: MerkleTreeRecoveryConfig::default(),
         }
     }
 }
@@ -95,12 +117,689 @@ impl MerkleTreeConfig {
         self.memtable_capacity_mb * super::BYTES_IN_MEGABYTE
     }
 
+    /// Returns the limit on the total memtable capacity in bytes, or `None` if the capacity is not limited.
+    pub fn total_write_buffer_size(&self) -> Option<usize> {
+        self.total_write_buffer_size_mb
+            .map(|size_mb| size_mb * super::BYTES_IN_MEGABYTE)
+    }
+
     /// Returns the timeout to wait for the Merkle tree database to run compaction on stalled writes.
     pub fn stalled_writes_timeout(&self) -> Duration {
         Duration::from_secs(self.stalled_writes_timeout_sec)
     }
 }
 
+/// Configuration of the Merkle tree recovery from a Postgres snapshot.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MerkleTreeRecoveryConfig {
+    /// Approximate number of entries recovered in a single chunk. Smaller chunks put less load
+    /// on Postgres at the cost of more round trips.
+    ///
+    /// The value is read once when the recovery starts and is persisted in the tree; after that,
+    /// the persisted value is used on node restarts even if this config value changes.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_desired_chunk_size")]
+    pub desired_chunk_size: u64,
+    /// Maximum number of attempts to recover a single chunk. Chunk recovery is retried with exponential backoff
+    /// only if it fails because of a transient error (e.g., a Postgres connection reset or a statement timeout).
+    #[serde(default = "MerkleTreeRecoveryConfig::default_max_chunk_attempts")]
+    pub max_chunk_attempts: usize,
+    /// Delay (in milliseconds) before the first retry of a failed chunk. The delay is doubled for each following
+    /// retry and is randomized to spread retries of concurrently recovered chunks.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_chunk_retry_initial_delay_ms")]
+    pub chunk_retry_initial_delay_ms: u64,
+    /// Maximum delay (in milliseconds) between retries of a failed chunk.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_chunk_retry_max_delay_ms")]
+    pub chunk_retry_max_delay_ms: u64,
+    /// Maximum number of chunks recovered concurrently. Each concurrently recovered chunk holds a Postgres
+    /// connection while loading entries, so the value must not exceed the size of the Merkle tree connection pool.
+    /// If not set, the pool size is used.
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    /// If set, recovery concurrency is adaptive: it is decreased (down to this value) if loading chunk entries
+    /// from Postgres becomes slow, and increased back (up to `concurrency`) once it speeds up.
+    #[serde(default)]
+    pub min_concurrency: Option<usize>,
+    /// If set, the maximum recovery concurrency can be changed without restarting the node by writing the new value
+    /// to the file at this path and sending `SIGHUP` to the node process. The value is capped by the size
+    /// of the connection pool used to load chunks; in-flight chunks are not interrupted if concurrency decreases.
+    #[serde(default)]
+    pub concurrency_override_path: Option<String>,
+    /// Average latency of loading entries for a chunk, above which adaptive concurrency is decreased.
+    /// Only used if `min_concurrency` is set.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_slow_chunk_threshold_ms")]
+    pub slow_chunk_threshold_ms: u64,
+    /// If set, each chunk is applied to the tree in sub-chunks of this size, with progress within the chunk
+    /// persisted in the recovery journal. This allows resuming recovery of large chunks mid-way after a restart.
+    #[serde(default)]
+    pub sub_chunk_size: Option<usize>,
+    /// If set, entries of each chunk are streamed from Postgres in batches of this size instead of being loaded
+    /// at once, so that peak memory usage is bounded by the batch size times recovery concurrency.
+    /// Takes precedence over `sub_chunk_size`. Ignored when recovering from an object store.
+    #[serde(default)]
+    pub streaming_batch_size: Option<usize>,
+    /// If set, tree nodes are hashed when extending the tree during recovery using a dedicated thread pool
+    /// with the specified number of threads. If not set, the global thread pool is used.
+    #[serde(default)]
+    pub hashing_threads: Option<usize>,
+    /// If set, soft cap (in megabytes) on the total size of chunk entries loaded from Postgres but not yet applied
+    /// to the tree. Once the cap is exceeded, chunks don't start loading their entries until some of the loaded
+    /// entries are applied.
+    #[serde(default)]
+    pub loaded_entries_soft_cap_mb: Option<usize>,
+    /// If set, the tree is recovered to the specified L1 batch instead of the snapshot the node was recovered from.
+    /// Postgres must contain metadata for the batch, and storage logs for its last miniblock must contain the full
+    /// state as of the batch (i.e., there must be no storage logs in earlier miniblocks). Useful for debugging
+    /// and controlled rollouts; the tree refuses to continue recovery started with a different L1 batch.
+    #[serde(default)]
+    pub target_l1_batch: Option<u32>,
+    /// If set, chunks with the largest estimated number of entries are recovered first, so that the recovery tail
+    /// isn't dominated by a few large chunks. Only supported when recovering from Postgres.
+    #[serde(default)]
+    pub prioritize_large_chunks: bool,
+    /// If set and the recovery chunk plan persisted in the tree differs from the current one (e.g., because
+    /// the number of snapshot storage logs in Postgres has changed), the tree is wiped and recovery is restarted
+    /// from scratch. Otherwise, such a mismatch results in an error.
+    #[serde(default)]
+    pub force_replan: bool,
+    /// If set and Postgres genesis differs from the one the tree recovery was started for (e.g., because Postgres
+    /// was re-initialized), the tree is wiped and recovery is restarted from scratch. Otherwise, such a mismatch
+    /// results in an error.
+    #[serde(default)]
+    pub allow_tree_reset_on_regenesis: bool,
+    /// If set, the tree is removed and initialized from scratch if it cannot be used as is: it's being recovered
+    /// to an L1 batch different from the recovery target, or it was processed in the lightweight mode and cannot
+    /// be upgraded to the full mode. Otherwise, such a tree results in an error.
+    #[serde(default)]
+    pub allow_tree_reset: bool,
+    /// Estimated number of bytes occupied by a single tree entry in RocksDB. Used to estimate the disk space
+    /// required for recovery before it starts. If set to 0, the disk space check is skipped.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_estimated_bytes_per_entry")]
+    pub estimated_bytes_per_entry: u64,
+    /// Disk space (in megabytes) that should remain available after recovery according to the estimate.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_disk_space_margin_mb")]
+    pub disk_space_margin_mb: usize,
+    /// If set, recovery refuses to start if the estimated disk space required for it exceeds the available space
+    /// (minus the margin). Otherwise, only a warning is logged.
+    #[serde(default)]
+    pub strict_disk_space_check: bool,
+    /// If set, the recovered tree is verified by sampling the specified number of entries from each chunk
+    /// and comparing them with Postgres. Mismatches result in an error listing the offending keys and chunks.
+    #[serde(default)]
+    pub verification_samples_per_chunk: Option<usize>,
+    /// If set, Merkle proofs for the specified number of entries sampled from the recovered tree are verified
+    /// against the recovered root hash after recovery is finalized. An invalid proof results in an error.
+    #[serde(default)]
+    pub proof_verification_samples: Option<usize>,
+    /// If set, the tree root hash is recorded after each recovered chunk together with the chunk key range
+    /// and entry count in an append-only log next to the tree RocksDB directory. The log is archived
+    /// when recovery is finalized.
+    #[serde(default)]
+    pub fingerprint_log: bool,
+    /// Maximum number of chunks processed by a single Postgres query and a single tree lookup when filtering out
+    /// recovered chunks before recovery starts or resumes. Only relevant for very large chunk counts.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_chunk_filter_batch_size")]
+    pub chunk_filter_batch_size: usize,
+    /// If set, snapshot chunks are loaded using a dedicated Postgres connection pool of the specified size
+    /// instead of the main tree pool. Connections in the pool are identified by the `tree_recovery` application name.
+    #[serde(default)]
+    pub pool_size: Option<u32>,
+    /// Statement timeout (in seconds) for the dedicated recovery connection pool (see `pool_size`). Should be
+    /// large enough for queries loading an entire chunk. If not set, the statement timeout is not set.
+    #[serde(default)]
+    pub statement_timeout_sec: Option<u64>,
+    /// If set, heavy snapshot queries during recovery (loading chunk entries, filtering recovered chunks, counting
+    /// snapshot storage logs) are sent to the Postgres read replica, with a fallback to the primary if the replica
+    /// lags behind the snapshot or fails. The replica pool has the same size and statement timeout as the dedicated
+    /// recovery pool (see `pool_size`).
+    #[serde(default)]
+    pub use_replica: bool,
+    /// If set, tree versions older than the specified number of versions behind the latest one are pruned
+    /// after recovery is finalized and on each node start, and the tree RocksDB is compacted afterwards.
+    #[serde(default)]
+    pub pruning_retained_versions: Option<u64>,
+    /// Whether to backfill L1 batches processed by the tree in the lightweight mode if the tree is configured
+    /// to run in the full mode. The backfill reverts the tree to the first L1 batch processed in the lightweight mode,
+    /// so that witness inputs and commitments are produced for the reverted batches. If not set, the tree just
+    /// continues in the full mode, and witness inputs are not produced for such L1 batches.
+    #[serde(default)]
+    pub allow_lightweight_tree_upgrade: bool,
+    /// If set, a root hash mismatch of the recovered tree is diagnosed by comparing fingerprints of entries
+    /// in each chunk in Postgres and in the tree. Up to the specified number of example diverging keys is reported
+    /// for each diverging chunk. The report is logged and written to a JSON file next to the tree RocksDB directory.
+    #[serde(default)]
+    pub mismatch_diagnostic_keys_per_chunk: Option<usize>,
+    /// If set, the recovered tree is exported to the specified directory after recovery is finalized. The export
+    /// contains a RocksDB checkpoint of the tree and a JSON descriptor with the snapshot L1 batch, miniblock,
+    /// root hash and the number of tree entries.
+    #[serde(default)]
+    pub export_path: Option<String>,
+    /// If set, an empty tree is initialized by importing the tree exported to the specified directory
+    /// (see `export_path`) instead of recovering it from Postgres. The imported tree is verified against
+    /// the snapshot; if verification fails, imported data is removed and the tree is recovered from Postgres.
+    /// If `import_path` is the same as `export_path`, the export serves as a local backup of the recovered tree:
+    /// if the tree data directory is lost, the tree is restored from the backup instead of being recovered again.
+    #[serde(default)]
+    pub import_path: Option<String>,
+    /// If set together with `import_path`, failing to import the tree results in an error instead of
+    /// recovering the tree from Postgres.
+    #[serde(default)]
+    pub strict_import: bool,
+    /// If set, snapshot entries are loaded from the specified snapshot file instead of Postgres (e.g., if the tree
+    /// cannot load entries from Postgres directly). The file must contain the snapshot the tree is recovered to;
+    /// it can be produced from Postgres on another machine using `metadata_calculator::write_snapshot_file()`
+    /// in the core crate. Postgres is still used for snapshot metadata and for checking recovered chunks.
+    #[serde(default)]
+    pub recovery_file: Option<String>,
+    /// If set, the Postgres snapshot is verified by recovering a temporary tree before recovering
+    /// the production one. The production tree DB is not touched during the dry run.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// If set together with `dry_run`, the Merkle tree stops after the dry run instead of proceeding
+    /// with the recovery of the production tree.
+    #[serde(default)]
+    pub stop_after_dry_run: bool,
+    /// If set, the entire Merkle tree is verified against the Postgres snapshot it was recovered from,
+    /// and the tree stops instead of normal operation. An interrupted check is resumed on the next start.
+    #[serde(default)]
+    pub verify_integrity: bool,
+    /// If set, the Merkle tree stops once it's prepared for normal operation (e.g., recovered from a snapshot
+    /// and exported if `export_path` is set) instead of processing L1 batches. Before stopping, the tree root hash
+    /// is checked against the snapshot. Useful to produce a tree artifact in CI or snapshot-publishing jobs.
+    #[serde(default)]
+    pub stop_after_recovery: bool,
+    /// If set, a tree recovered from a snapshot keeps being reported as recovering (in the catch-up phase) until
+    /// it catches up with Postgres to within `tree_ready_lag_batches` (or fully, if the lag isn't configured),
+    /// instead of being reported as ready right after recovery is finalized.
+    #[serde(default)]
+    pub catch_up_before_ready: bool,
+    /// **UNSAFE; for debugging corrupted snapshots only.** If set, a root hash mismatch of the recovered tree
+    /// is logged as an error instead of failing recovery, and the tree is marked as tainted in its metadata.
+    /// A tainted tree is never used for normal operation unless `experimental_allow_tainted_tree` is set.
+    #[serde(default)]
+    pub experimental_skip_root_hash_check: bool,
+    /// **UNSAFE; for debugging corrupted snapshots only.** If set, a tree tainted by
+    /// `experimental_skip_root_hash_check` is used for normal operation (including serving Merkle proofs)
+    /// instead of being rejected. The taint is still reported via the health check.
+    #[serde(default)]
+    pub experimental_allow_tainted_tree: bool,
+    /// Minimum interval between health updates on recovered chunks. The last recovered chunk is always reported.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_health_update_interval_ms")]
+    pub health_update_interval_ms: u64,
+    /// Interval between checks of the watchdog reporting recovery stalls (i.e., no chunks finishing loading
+    /// for a while, e.g. because Postgres is locked up). If set to 0, the watchdog is disabled.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_stall_check_interval_ms")]
+    pub stall_check_interval_ms: u64,
+    /// Recovery is reported as stalled if no chunk has finished loading within this threshold.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_stall_threshold_ms")]
+    pub stall_threshold_ms: u64,
+    /// Total duration (in milliseconds) of retrying to acquire a Postgres connection during recovery (e.g., if
+    /// the connection pool is exhausted or Postgres is restarting) before recovery fails. If set to 0,
+    /// connection acquisition is not retried.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_connection_retry_timeout_ms")]
+    pub connection_retry_timeout_ms: u64,
+    /// Timeout (in milliseconds) for a single attempt to acquire a Postgres connection during recovery. If the pool
+    /// is saturated (e.g., by other components sharing it) and a connection isn't acquired within the timeout,
+    /// a warning with pool stats is logged, and acquisition is retried. Unlike connection errors, such retries
+    /// are not limited by `connection_retry_timeout_ms`.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_connection_acquire_timeout_ms")]
+    pub connection_acquire_timeout_ms: u64,
+    /// If set, entries of snapshot chunks are loaded from Postgres using `COPY ... TO STDOUT (FORMAT binary)`,
+    /// which is faster than a conventional query for large chunks. `COPY` may require additional permissions
+    /// in some managed Postgres setups; if it fails, entries are loaded using a conventional query.
+    /// Not used when entries are streamed in batches (see `streaming_batch_size`).
+    #[serde(default)]
+    pub use_copy: bool,
+    /// Interval (in milliseconds) between checks whether the snapshot is fully applied to Postgres. Recovery waits
+    /// until the snapshot is applied if the node is being recovered from a snapshot concurrently with tree recovery.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_snapshot_poll_interval_ms")]
+    pub snapshot_poll_interval_ms: u64,
+    /// If set, RocksDB is tuned for bulk loading during recovery: large column families use memtables
+    /// with the specified capacity (in megabytes), and auto-compaction is disabled. The tree RocksDB is compacted
+    /// manually when recovery is finalized, after which the normal RocksDB options are restored.
+    /// If not set, recovery uses the same RocksDB options as normal tree operation.
+    #[serde(default)]
+    pub bulk_load_memtable_capacity_mb: Option<usize>,
+    /// Maximum number of memtables for large column families during bulk loading (see `bulk_load_memtable_capacity_mb`).
+    /// Larger values allow to absorb write bursts while memtables are flushed. Ignored if bulk loading
+    /// is not enabled.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_bulk_load_max_write_buffers")]
+    pub bulk_load_max_write_buffers: usize,
+    /// Block cache capacity (in megabytes) during bulk loading. Overrides `block_cache_size_mb` from the tree config
+    /// while recovery is in progress; ignored if bulk loading is not enabled (see `bulk_load_memtable_capacity_mb`).
+    /// Since recovery performs few reads, a smaller block cache frees memory for memtables.
+    #[serde(default)]
+    pub bulk_load_block_cache_size_mb: Option<usize>,
+    /// Limit on the total memtable capacity (in megabytes) during bulk loading. Overrides `total_write_buffer_size_mb`
+    /// from the tree config while recovery is in progress; ignored if bulk loading is not enabled.
+    #[serde(default)]
+    pub bulk_load_total_write_buffer_size_mb: Option<usize>,
+    /// Maximum number of open files during bulk loading. Overrides `max_open_files` from the tree config
+    /// while recovery is in progress; ignored if bulk loading is not enabled.
+    #[serde(default)]
+    pub bulk_load_max_open_files: Option<usize>,
+    /// If set, the write-ahead log of the tree RocksDB is disabled during recovery, so that writes are not synced
+    /// to disk one by one. All writes are flushed to disk when recovery is finalized. This speeds up recovery,
+    /// but if the node crashes during recovery, the last recovered chunks may be lost. In this case, recovery
+    /// checks both the start and the end of each chunk after the restart and re-recovers chunks that are
+    /// incomplete, which is slower than normal resumption.
+    #[serde(default)]
+    pub relaxed_durability: bool,
+    /// If set, recovered chunks are always checked by both their start and their end when recovery is resumed,
+    /// and chunks with the start present in the tree, but the end missing are recovered again. This is done
+    /// automatically if tree writes may have been lost in a crash (e.g., with `relaxed_durability`); this option
+    /// additionally guards against other partial chunk writes at the cost of slower resumption.
+    #[serde(default)]
+    pub check_chunk_ends: bool,
+    /// If set, a recovered chunk with an entry in the tree mismatching Postgres (which is detected when recovery
+    /// is resumed) is repaired by overwriting its entries in the tree with ones from Postgres, instead of failing
+    /// recovery. The root hash check after recovery still guarantees the correctness of the recovered tree.
+    #[serde(default)]
+    pub repair_mismatched_chunks: bool,
+    /// If set, memtables of the tree RocksDB are flushed to disk every specified number of recovered chunks.
+    /// Flushing periodically bounds memtable memory and prevents RocksDB from stalling writes unpredictably
+    /// in the middle of a chunk (e.g., if auto-compaction is disabled for bulk loading). If neither this option
+    /// nor `flush_interval_mb` is set, memtables are flushed by RocksDB on its own.
+    #[serde(default)]
+    pub flush_interval_chunks: Option<usize>,
+    /// If set, memtables of the tree RocksDB are flushed to disk every time approximately this number
+    /// of megabytes of snapshot entries is applied to the tree during recovery. Can be combined with
+    /// `flush_interval_chunks`; memtables are flushed once either threshold is reached.
+    #[serde(default)]
+    pub flush_interval_mb: Option<usize>,
+    /// Limit on the rate of background writes (memtable flushes and compactions) of the tree RocksDB during recovery,
+    /// in megabytes per second. Useful if the tree shares a disk with Postgres or other RocksDB instances, so that
+    /// recovery I/O doesn't starve them. If not set, background writes are not limited.
+    #[serde(default)]
+    pub background_write_rate_limit_mb: Option<usize>,
+    /// If set, the background write rate limit can be changed without restarting the node by writing the new limit
+    /// (in megabytes per second; 0 to remove the limit) to the file at this path and sending `SIGHUP` to the node
+    /// process. The tree RocksDB is reopened to apply the new limit, so changing it frequently is not recommended.
+    #[serde(default)]
+    pub background_write_rate_limit_override_path: Option<String>,
+    /// If set, limits the rate at which snapshot entries are loaded from Postgres and applied to the tree during
+    /// recovery (in entries per second). Useful if recovery runs against a Postgres replica serving other traffic,
+    /// so that its impact on the replica is capped. If not set, recovery runs as fast as possible.
+    #[serde(default)]
+    pub max_entries_per_second: Option<usize>,
+    /// If set, recovery fails if it doesn't complete within this number of seconds since it was started or resumed
+    /// after a restart. Once the deadline is exceeded, no new chunks are started, and chunks in flight are recovered
+    /// and persisted, so recovery can be resumed later. Useful to alert on recoveries that won't meet an SLA instead
+    /// of running for days.
+    #[serde(default)]
+    pub max_recovery_duration_sec: Option<u64>,
+    /// If set and a newer snapshot appears in Postgres while the tree is recovered from an older one, the recovering
+    /// tree is removed, and recovery is restarted from the newer snapshot. The check is performed on startup
+    /// and periodically between recovered chunks. Cannot be used together with `target_l1_batch`.
+    #[serde(default)]
+    pub prefer_latest_snapshot: bool,
+    /// Interval (in milliseconds) between checks for a newer snapshot during recovery. Only used
+    /// if `prefer_latest_snapshot` is set.
+    #[serde(default = "MerkleTreeRecoveryConfig::default_latest_snapshot_check_interval_ms")]
+    pub latest_snapshot_check_interval_ms: u64,
+}
+
+impl Default for MerkleTreeRecoveryConfig {
+    fn default() -> Self {
+        Self {
+            desired_chunk_size: Self::default_desired_chunk_size(),
+            max_chunk_attempts: Self::default_max_chunk_attempts(),
+            chunk_retry_initial_delay_ms: Self::default_chunk_retry_initial_delay_ms(),
+            chunk_retry_max_delay_ms: Self::default_chunk_retry_max_delay_ms(),
+            concurrency: None,
+            min_concurrency: None,
+            concurrency_override_path: None,
+            slow_chunk_threshold_ms: Self::default_slow_chunk_threshold_ms(),
+            sub_chunk_size: None,
+            streaming_batch_size: None,
+            hashing_threads: None,
+            loaded_entries_soft_cap_mb: None,
+            target_l1_batch: None,
+            prioritize_large_chunks: false,
+            force_replan: false,
+            allow_tree_reset_on_regenesis: false,
+            allow_tree_reset: false,
+            estimated_bytes_per_entry: Self::default_estimated_bytes_per_entry(),
+            disk_space_margin_mb: Self::default_disk_space_margin_mb(),
+            strict_disk_space_check: false,
+            verification_samples_per_chunk: None,
+            proof_verification_samples: None,
+            fingerprint_log: false,
+            chunk_filter_batch_size: Self::default_chunk_filter_batch_size(),
+            pool_size: None,
+            statement_timeout_sec: None,
+            use_replica: false,
+            pruning_retained_versions: None,
+            allow_lightweight_tree_upgrade: false,
+            mismatch_diagnostic_keys_per_chunk: None,
+            export_path: None,
+            import_path: None,
+            strict_import: false,
+            recovery_file: None,
+            dry_run: false,
+            stop_after_dry_run: false,
+            verify_integrity: false,
+            stop_after_recovery: false,
+            catch_up_before_ready: false,
+            experimental_skip_root_hash_check: false,
+            experimental_allow_tainted_tree: false,
+            health_update_interval_ms: Self::default_health_update_interval_ms(),
+            stall_check_interval_ms: Self::default_stall_check_interval_ms(),
+            stall_threshold_ms: Self::default_stall_threshold_ms(),
+            connection_retry_timeout_ms: Self::default_connection_retry_timeout_ms(),
+            connection_acquire_timeout_ms: Self::default_connection_acquire_timeout_ms(),
+            use_copy: false,
+            snapshot_poll_interval_ms: Self::default_snapshot_poll_interval_ms(),
+            bulk_load_memtable_capacity_mb: None,
+            bulk_load_max_write_buffers: Self::default_bulk_load_max_write_buffers(),
+            bulk_load_block_cache_size_mb: None,
+            bulk_load_total_write_buffer_size_mb: None,
+            bulk_load_max_open_files: None,
+            relaxed_durability: false,
+            check_chunk_ends: false,
+            repair_mismatched_chunks: false,
+            flush_interval_chunks: None,
+            flush_interval_mb: None,
+            background_write_rate_limit_mb: None,
+            background_write_rate_limit_override_path: None,
+            max_entries_per_second: None,
+            max_recovery_duration_sec: None,
+            prefer_latest_snapshot: false,
+            latest_snapshot_check_interval_ms: Self::default_latest_snapshot_check_interval_ms(),
+        }
+    }
+}
+
+impl MerkleTreeRecoveryConfig {
+    const fn default_desired_chunk_size() -> u64 {
+        200_000
+    }
+
+    const fn default_max_chunk_attempts() -> usize {
+        5
+    }
+
+    const fn default_chunk_retry_initial_delay_ms() -> u64 {
+        500
+    }
+
+    const fn default_chunk_retry_max_delay_ms() -> u64 {
+        30_000
+    }
+
+    const fn default_slow_chunk_threshold_ms() -> u64 {
+        10_000
+    }
+
+    const fn default_estimated_bytes_per_entry() -> u64 {
+        1_500
+    }
+
+    const fn default_disk_space_margin_mb() -> usize {
+        10_240
+    }
+
+    const fn default_chunk_filter_batch_size() -> usize {
+        1_000
+    }
+
+    const fn default_health_update_interval_ms() -> u64 {
+        1_000
+    }
+
+    const fn default_stall_check_interval_ms() -> u64 {
+        60_000
+    }
+
+    const fn default_stall_threshold_ms() -> u64 {
+        300_000
+    }
+
+    const fn default_connection_retry_timeout_ms() -> u64 {
+        60_000
+    }
+
+    const fn default_connection_acquire_timeout_ms() -> u64 {
+        30_000
+    }
+
+    const fn default_snapshot_poll_interval_ms() -> u64 {
+        1_000
+    }
+
+    const fn default_latest_snapshot_check_interval_ms() -> u64 {
+        60_000
+    }
+
+    const fn default_bulk_load_max_write_buffers() -> usize {
+        6
+    }
+
+    /// Returns the delay before the first retry of a failed chunk.
+    pub fn chunk_retry_initial_delay(&self) -> Duration {
+        Duration::from_millis(self.chunk_retry_initial_delay_ms)
+    }
+
+    /// Returns the maximum delay between retries of a failed chunk.
+    pub fn chunk_retry_max_delay(&self) -> Duration {
+        Duration::from_millis(self.chunk_retry_max_delay_ms)
+    }
+
+    /// Returns the average latency of loading chunk entries, above which adaptive concurrency is decreased.
+    pub fn slow_chunk_threshold(&self) -> Duration {
+        Duration::from_millis(self.slow_chunk_threshold_ms)
+    }
+
+    /// Returns the minimum interval between health updates on recovered chunks.
+    pub fn health_update_interval(&self) -> Duration {
+        Duration::from_millis(self.health_update_interval_ms)
+    }
+
+    /// Returns the interval between checks of the recovery stall watchdog.
+    pub fn stall_check_interval(&self) -> Duration {
+        Duration::from_millis(self.stall_check_interval_ms)
+    }
+
+    /// Returns the threshold after which recovery is reported as stalled.
+    pub fn stall_threshold(&self) -> Duration {
+        Duration::from_millis(self.stall_threshold_ms)
+    }
+
+    /// Returns the statement timeout for the dedicated recovery connection pool.
+    pub fn statement_timeout(&self) -> Option<Duration> {
+        self.statement_timeout_sec.map(Duration::from_secs)
+    }
+
+    /// Returns the total duration of retrying to acquire a Postgres connection during recovery.
+    pub fn connection_retry_timeout(&self) -> Duration {
+        Duration::from_millis(self.connection_retry_timeout_ms)
+    }
+
+    /// Returns the timeout for a single attempt to acquire a Postgres connection during recovery.
+    pub fn connection_acquire_timeout(&self) -> Duration {
+        Duration::from_millis(self.connection_acquire_timeout_ms)
+    }
+
+    /// Returns the interval between checks whether the snapshot is fully applied to Postgres.
+    pub fn snapshot_poll_interval(&self) -> Duration {
+        Duration::from_millis(self.snapshot_poll_interval_ms)
+    }
+
+    /// Returns the disk space (in bytes) that should remain available after recovery.
+    pub fn disk_space_margin(&self) -> usize {
+        self.disk_space_margin_mb * super::BYTES_IN_MEGABYTE
+    }
+
+    /// Returns the soft cap (in bytes) on the total size of loaded chunk entries not yet applied to the tree.
+    pub fn loaded_entries_soft_cap(&self) -> Option<usize> {
+        self.loaded_entries_soft_cap_mb
+            .map(|cap_mb| cap_mb * super::BYTES_IN_MEGABYTE)
+    }
+
+    /// Returns the memtable capacity (in bytes) for large column families during bulk loading, or `None`
+    /// if bulk loading is disabled.
+    pub fn bulk_load_memtable_capacity(&self) -> Option<usize> {
+        self.bulk_load_memtable_capacity_mb
+            .map(|capacity_mb| capacity_mb * super::BYTES_IN_MEGABYTE)
+    }
+
+    /// Returns the block cache capacity (in bytes) overriding the normal one during bulk loading.
+    pub fn bulk_load_block_cache_size(&self) -> Option<usize> {
+        self.bulk_load_block_cache_size_mb
+            .map(|size_mb| size_mb * super::BYTES_IN_MEGABYTE)
+    }
+
+    /// Returns the limit on the total memtable capacity (in bytes) overriding the normal one during bulk loading.
+    pub fn bulk_load_total_write_buffer_size(&self) -> Option<usize> {
+        self.bulk_load_total_write_buffer_size_mb
+            .map(|size_mb| size_mb * super::BYTES_IN_MEGABYTE)
+    }
+
+    /// Returns the approximate number of bytes of applied snapshot entries between memtable flushes.
+    pub fn flush_interval_bytes(&self) -> Option<usize> {
+        self.flush_interval_mb
+            .map(|size_mb| size_mb * super::BYTES_IN_MEGABYTE)
+    }
+
+    /// Returns the interval between checks for a newer snapshot during recovery.
+    pub fn latest_snapshot_check_interval(&self) -> Duration {
+        Duration::from_millis(self.latest_snapshot_check_interval_ms)
+    }
+
+    /// Returns the maximum duration of recovery, if any.
+    pub fn max_recovery_duration(&self) -> Option<Duration> {
+        self.max_recovery_duration_sec.map(Duration::from_secs)
+    }
+
+    /// Returns the limit on the rate of background writes (in bytes per second) during recovery.
+    pub fn background_write_rate_limit(&self) -> Option<usize> {
+        self.background_write_rate_limit_mb
+            .map(|limit_mb| limit_mb * super::BYTES_IN_MEGABYTE)
+    }
+
+    /// Checks that the config values are consistent. This doesn't check values depending on the environment,
+    /// such as the size of the main Merkle tree connection pool; these are checked when recovery starts.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.desired_chunk_size > 0,
+            "`desired_chunk_size` must be positive"
+        );
+        anyhow::ensure!(
+            self.max_chunk_attempts > 0,
+            "`max_chunk_attempts` must be positive"
+        );
+        anyhow::ensure!(
+            self.chunk_retry_initial_delay_ms <= self.chunk_retry_max_delay_ms,
+            "`chunk_retry_initial_delay_ms` ({}) must not exceed `chunk_retry_max_delay_ms` ({})",
+            self.chunk_retry_initial_delay_ms,
+            self.chunk_retry_max_delay_ms
+        );
+
+        if let Some(concurrency) = self.concurrency {
+            anyhow::ensure!(concurrency > 0, "`concurrency` must be positive");
+            if let Some(pool_size) = self.pool_size {
+                anyhow::ensure!(
+                    concurrency <= pool_size as usize,
+                    "`concurrency` ({concurrency}) must not exceed the size of the recovery connection pool \
+                     `pool_size` ({pool_size})"
+                );
+            }
+        }
+        if let Some(min_concurrency) = self.min_concurrency {
+            anyhow::ensure!(min_concurrency > 0, "`min_concurrency` must be positive");
+            if let Some(concurrency) = self.concurrency {
+                anyhow::ensure!(
+                    min_concurrency <= concurrency,
+                    "`min_concurrency` ({min_concurrency}) must not exceed `concurrency` ({concurrency})"
+                );
+            }
+        }
+        anyhow::ensure!(self.pool_size != Some(0), "`pool_size` must be positive");
+
+        let positive_options = [
+            ("sub_chunk_size", self.sub_chunk_size),
+            ("streaming_batch_size", self.streaming_batch_size),
+            ("hashing_threads", self.hashing_threads),
+            (
+                "loaded_entries_soft_cap_mb",
+                self.loaded_entries_soft_cap_mb,
+            ),
+            (
+                "verification_samples_per_chunk",
+                self.verification_samples_per_chunk,
+            ),
+            (
+                "proof_verification_samples",
+                self.proof_verification_samples,
+            ),
+            (
+                "mismatch_diagnostic_keys_per_chunk",
+                self.mismatch_diagnostic_keys_per_chunk,
+            ),
+            (
+                "bulk_load_memtable_capacity_mb",
+                self.bulk_load_memtable_capacity_mb,
+            ),
+            (
+                "bulk_load_total_write_buffer_size_mb",
+                self.bulk_load_total_write_buffer_size_mb,
+            ),
+            ("bulk_load_max_open_files", self.bulk_load_max_open_files),
+            (
+                "background_write_rate_limit_mb",
+                self.background_write_rate_limit_mb,
+            ),
+            ("flush_interval_chunks", self.flush_interval_chunks),
+            ("flush_interval_mb", self.flush_interval_mb),
+            ("max_entries_per_second", self.max_entries_per_second),
+        ];
+        for (name, value) in positive_options {
+            anyhow::ensure!(value != Some(0), "`{name}` must be positive if set");
+        }
+        anyhow::ensure!(
+            self.max_recovery_duration_sec != Some(0),
+            "`max_recovery_duration_sec` must be positive if set"
+        );
+        anyhow::ensure!(
+            self.chunk_filter_batch_size > 0,
+            "`chunk_filter_batch_size` must be positive"
+        );
+        anyhow::ensure!(
+            self.connection_acquire_timeout_ms > 0,
+            "`connection_acquire_timeout_ms` must be positive"
+        );
+        anyhow::ensure!(
+            self.snapshot_poll_interval_ms > 0,
+            "`snapshot_poll_interval_ms` must be positive"
+        );
+        anyhow::ensure!(
+            self.latest_snapshot_check_interval_ms > 0,
+            "`latest_snapshot_check_interval_ms` must be positive"
+        );
+        anyhow::ensure!(
+            self.bulk_load_max_write_buffers >= 2,
+            "`bulk_load_max_write_buffers` must be at least 2"
+        );
+        anyhow::ensure!(
+            self.stall_check_interval_ms == 0 || self.stall_threshold_ms > 0,
+            "`stall_threshold_ms` must be positive if the recovery watchdog is enabled"
+        );
+
+        anyhow::ensure!(
+            !self.prefer_latest_snapshot || self.target_l1_batch.is_none(),
+            "`prefer_latest_snapshot` cannot be used together with `target_l1_batch`"
+        );
+        anyhow::ensure!(
+            !self.strict_import || self.import_path.is_some(),
+            "`strict_import` requires `import_path` to be set"
+        );
+        anyhow::ensure!(
+            !self.stop_after_dry_run || self.dry_run,
+            "`stop_after_dry_run` requires `dry_run` to be set"
+        );
+        Ok(())
+    }
+}
+
 /// Database configuration.
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct DBConfig {
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS tree_recovery_summary;
+DROP TABLE IF EXISTS tree_recovery_progress;
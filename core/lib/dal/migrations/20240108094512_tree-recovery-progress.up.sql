@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS tree_recovery_progress
+(
+    l1_batch_number BIGINT    NOT NULL,
+    chunk_id        INT       NOT NULL,
+    start_key       BYTEA     NOT NULL,
+    end_key         BYTEA     NOT NULL,
+    -- `NULL` if the chunk was recovered before progress was recorded (e.g., it was inserted on reconciliation).
+    entry_count     BIGINT,
+    completed_at    TIMESTAMP NOT NULL,
+
+    PRIMARY KEY (l1_batch_number, chunk_id)
+);
+
+CREATE TABLE IF NOT EXISTS tree_recovery_summary
+(
+    l1_batch_number       BIGINT    NOT NULL PRIMARY KEY,
+    total_chunk_count     INT       NOT NULL,
+    recovered_chunk_count INT       NOT NULL,
+    recovered_entry_count BIGINT    NOT NULL,
+
+    created_at            TIMESTAMP NOT NULL,
+    updated_at            TIMESTAMP NOT NULL,
+    finished_at           TIMESTAMP
+);
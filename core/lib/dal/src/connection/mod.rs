@@ -1,4 +1,11 @@
-use std::{env, fmt, time::Duration};
+use std::{
+    env, fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use anyhow::Context as _;
 use sqlx::{
@@ -23,6 +30,7 @@ pub struct ConnectionPoolBuilder {
     database_url: String,
     max_size: u32,
     statement_timeout: Option<Duration>,
+    application_name: Option<String>,
 }
 
 impl fmt::Debug for ConnectionPoolBuilder {
@@ -32,6 +40,7 @@ impl fmt::Debug for ConnectionPoolBuilder {
             .debug_struct("ConnectionPoolBuilder")
             .field("max_size", &self.max_size)
             .field("statement_timeout", &self.statement_timeout)
+            .field("application_name", &self.application_name)
             .finish()
     }
 }
@@ -46,6 +55,13 @@ impl ConnectionPoolBuilder {
         self
     }
 
+    /// Sets the application name reported by pool connections (e.g., in `pg_stat_activity`).
+    /// If not specified, the application name will not be set.
+    pub fn set_application_name(&mut self, name: &str) -> &mut Self {
+        self.application_name = Some(name.to_owned());
+        self
+    }
+
     /// Builds a connection pool from this builder.
     pub async fn build(&self) -> anyhow::Result<ConnectionPool> {
         let options = PgPoolOptions::new().max_connections(self.max_size);
@@ -57,6 +73,9 @@ impl ConnectionPoolBuilder {
             let timeout_string = format!("{}s", timeout.as_secs());
             connect_options = connect_options.options([("statement_timeout", timeout_string)]);
         }
+        if let Some(name) = &self.application_name {
+            connect_options = connect_options.application_name(name);
+        }
         let pool = options
             .connect_with(connect_options)
             .await
@@ -70,6 +89,7 @@ impl ConnectionPoolBuilder {
         Ok(ConnectionPool {
             inner: pool,
             max_size: self.max_size,
+            waiter_count: Arc::default(),
         })
     }
 }
@@ -120,10 +140,41 @@ pub(super) async fn create_test_db() -> anyhow::Result<url::Url> {
     Ok(db_url)
 }
 
+/// Statistics of a [`ConnectionPool`] returned by [`ConnectionPool::stats()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionPoolStats {
+    /// Maximum number of connections in the pool.
+    pub max_size: u32,
+    /// Current number of connections in the pool, including idle ones.
+    pub size: u32,
+    /// Current number of idle connections in the pool.
+    pub idle: usize,
+    /// Number of tasks currently waiting to acquire a connection from the pool.
+    pub waiters: usize,
+}
+
+/// Tracks a task waiting to acquire a connection from a [`ConnectionPool`].
+#[derive(Debug)]
+struct WaiterGuard<'a>(&'a AtomicUsize);
+
+impl<'a> WaiterGuard<'a> {
+    fn new(waiter_count: &'a AtomicUsize) -> Self {
+        waiter_count.fetch_add(1, Ordering::Relaxed);
+        Self(waiter_count)
+    }
+}
+
+impl Drop for WaiterGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 #[derive(Clone)]
 pub struct ConnectionPool {
     pub(crate) inner: PgPool,
     max_size: u32,
+    waiter_count: Arc<AtomicUsize>,
 }
 
 impl fmt::Debug for ConnectionPool {
@@ -137,16 +188,18 @@ impl fmt::Debug for ConnectionPool {
 
 impl ConnectionPool {
     pub async fn test_pool() -> ConnectionPool {
+        const TEST_MAX_CONNECTIONS: u32 = 50; // Expected to be enough for any unit test.
+        Self::constrained_test_pool(TEST_MAX_CONNECTIONS).await
+    }
+
+    /// Same as [`Self::test_pool()`], but with a custom pool size. Useful to test behavior
+    /// when the pool is exhausted.
+    pub async fn constrained_test_pool(max_size: u32) -> ConnectionPool {
         let db_url = create_test_db()
             .await
             .expect("Unable to prepare test database")
             .to_string();
-
-        const TEST_MAX_CONNECTIONS: u32 = 50; // Expected to be enough for any unit test.
-        Self::builder(&db_url, TEST_MAX_CONNECTIONS)
-            .build()
-            .await
-            .unwrap()
+        Self::builder(&db_url, max_size).build().await.unwrap()
     }
 
     /// Initializes a builder for connection pools.
@@ -155,6 +208,7 @@ impl ConnectionPool {
             database_url: database_url.to_string(),
             max_size: max_pool_size,
             statement_timeout: None,
+            application_name: None,
         }
     }
 
@@ -171,6 +225,16 @@ impl ConnectionPool {
         self.max_size
     }
 
+    /// Returns current statistics of this pool.
+    pub fn stats(&self) -> ConnectionPoolStats {
+        ConnectionPoolStats {
+            max_size: self.max_size,
+            size: self.inner.size(),
+            idle: self.inner.num_idle(),
+            waiters: self.waiter_count.load(Ordering::Relaxed),
+        }
+    }
+
     /// Creates a `StorageProcessor` entity over a recoverable connection.
     /// Upon a database outage connection will block the thread until
     /// it will be able to recover the connection (or, if connection cannot
@@ -222,7 +286,7 @@ impl ConnectionPool {
                 .observe(self.inner.size() as usize);
             CONNECTION_METRICS.pool_idle.observe(self.inner.num_idle());
 
-            let connection = self.inner.acquire().await;
+            let connection = self.acquire_connection().await;
             let connection_err = match connection {
                 Ok(connection) => return Ok(connection),
                 Err(err) => {
@@ -239,7 +303,7 @@ impl ConnectionPool {
         }
 
         // Attempting to get the pooled connection for the last time
-        match self.inner.acquire().await {
+        match self.acquire_connection().await {
             Ok(conn) => Ok(conn),
             Err(err) => {
                 Self::report_connection_error(&err);
@@ -248,6 +312,11 @@ impl ConnectionPool {
         }
     }
 
+    async fn acquire_connection(&self) -> sqlx::Result<PoolConnection<Postgres>> {
+        let _guard = WaiterGuard::new(&self.waiter_count);
+        self.inner.acquire().await
+    }
+
     fn report_connection_error(err: &sqlx::Error) {
         CONNECTION_METRICS.pool_acquire_error[&err.into()].inc();
     }
@@ -283,4 +352,50 @@ mod tests {
             sqlx::Error::Database(db_err) if db_err.message().contains("statement timeout")
         );
     }
+    #[tokio::test]
+    async fn setting_application_name() {
+        let db_url = create_test_db()
+            .await
+            .expect("Unable to prepare test database")
+            .to_string();
+
+        let pool = ConnectionPool::singleton(&db_url)
+            .set_application_name("tree_recovery")
+            .build()
+            .await
+            .unwrap();
+
+        let mut storage = pool.access_storage().await.unwrap();
+        let application_name: String =
+            sqlx::query_scalar("SELECT current_setting('application_name')")
+                .fetch_one(storage.conn())
+                .await
+                .unwrap();
+        assert_eq!(application_name, "tree_recovery");
+    }
+
+    #[tokio::test]
+    async fn getting_pool_stats() {
+        let pool = ConnectionPool::constrained_test_pool(1).await;
+        let storage = pool.access_storage().await.unwrap();
+        let stats = pool.stats();
+        assert_eq!(stats.max_size, 1);
+        assert_eq!(stats.size, 1);
+        assert_eq!(stats.idle, 0);
+        assert_eq!(stats.waiters, 0);
+
+        let waiting_task = pool.access_storage();
+        tokio::pin!(waiting_task);
+        let timeout_result =
+            tokio::time::timeout(Duration::from_millis(50), &mut waiting_task).await;
+        assert!(
+            timeout_result.is_err(),
+            "connection acquired from exhausted pool"
+        );
+        assert_eq!(pool.stats().waiters, 1);
+
+        drop(storage);
+        waiting_task.await.unwrap();
+        assert_eq!(pool.stats().waiters, 0);
+    }
 }
@@ -3,7 +3,7 @@
 use sqlx::{pool::PoolConnection, postgres::Postgres, Connection, PgConnection, Transaction};
 pub use sqlx::{types::BigDecimal, Error as SqlxError};
 
-pub use crate::connection::ConnectionPool;
+pub use crate::connection::{ConnectionPool, ConnectionPoolStats};
 use crate::{
     accounts_dal::AccountsDal, basic_witness_input_producer_dal::BasicWitnessInputProducerDal,
     blocks_dal::BlocksDal, blocks_web3_dal::BlocksWeb3Dal, connection::holder::ConnectionHolder,
@@ -22,6 +22,7 @@ use crate::{
     sync_dal::SyncDal, system_dal::SystemDal, tokens_dal::TokensDal,
     tokens_web3_dal::TokensWeb3Dal, transactions_dal::TransactionsDal,
     transactions_web3_dal::TransactionsWeb3Dal,
+    tree_recovery_progress_dal::TreeRecoveryProgressDal,
 };
 
 #[macro_use]
@@ -47,6 +48,7 @@ pub mod healthcheck;
 mod instrument;
 mod metrics;
 mod models;
+mod pg_copy;
 pub mod proof_generation_dal;
 pub mod protocol_versions_dal;
 pub mod protocol_versions_web3_dal;
@@ -65,6 +67,7 @@ pub mod tokens_dal;
 pub mod tokens_web3_dal;
 pub mod transactions_dal;
 pub mod transactions_web3_dal;
+pub mod tree_recovery_progress_dal;
 
 #[cfg(test)]
 mod tests;
@@ -106,6 +109,16 @@ impl<'a> StorageProcessor<'a> {
         }
     }
 
+    /// Closes the underlying connection instead of returning it to the pool, so that the pool can open
+    /// a replacement. This should be used if the connection may be left in an inconsistent state, e.g.
+    /// after cancelling a query future. If the processor is within a transaction, the transaction is rolled back.
+    pub async fn close(self) -> sqlx::Result<()> {
+        match self.conn {
+            ConnectionHolder::Pooled(conn) => conn.detach().close().await,
+            ConnectionHolder::Transaction(transaction) => transaction.rollback().await,
+        }
+    }
+
     /// Creates a `StorageProcessor` using a pool of connections.
     /// This method borrows one of the connections from the pool, and releases it
     /// after `drop`.
@@ -256,4 +269,8 @@ impl<'a> StorageProcessor<'a> {
     pub fn snapshot_recovery_dal(&mut self) -> SnapshotRecoveryDal<'_, 'a> {
         SnapshotRecoveryDal { storage: self }
     }
+
+    pub fn tree_recovery_progress_dal(&mut self) -> TreeRecoveryProgressDal<'_, 'a> {
+        TreeRecoveryProgressDal { storage: self }
+    }
 }
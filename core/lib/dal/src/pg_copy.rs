@@ -0,0 +1,281 @@
+//! Minimal parser for the binary format of Postgres `COPY ... TO STDOUT (FORMAT binary)`.
+//!
+//! See [the Postgres docs](https://www.postgresql.org/docs/current/sql-copy.html) for the format description.
+
+use std::ops;
+
+/// Signature starting binary `COPY` output.
+const SIGNATURE: &[u8] = b"PGCOPY\n\xff\r\n\0";
+/// Length of the fixed part of the header: signature, flags and the header extension length.
+const HEADER_LEN: usize = SIGNATURE.len() + 8;
+/// Field count marking the end of `COPY` output.
+const TRAILER: i16 = -1;
+
+#[derive(Debug, thiserror::Error)]
+#[error("malformed binary COPY output: {0}")]
+pub(crate) struct CopyParseError(String);
+
+impl CopyParseError {
+    fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl From<CopyParseError> for sqlx::Error {
+    fn from(err: CopyParseError) -> Self {
+        Self::Decode(err.into())
+    }
+}
+
+/// Incremental parser of binary `COPY` output. Output chunks are supplied using [`Self::extend()`]
+/// and complete tuples are read using [`Self::next_tuple()`].
+#[derive(Debug, Default)]
+pub(crate) struct BinaryCopyParser {
+    buffer: Vec<u8>,
+    /// Position of the first unparsed byte in `buffer`.
+    pos: usize,
+    is_header_read: bool,
+    is_finished: bool,
+    /// Ranges of fields of the last read tuple in `buffer`; `None` for `NULL` fields.
+    fields: Vec<Option<ops::Range<usize>>>,
+}
+
+impl BinaryCopyParser {
+    /// Appends a chunk of `COPY` output to the parser buffer.
+    pub fn extend(&mut self, chunk: &[u8]) {
+        if self.pos > 0 {
+            self.buffer.drain(..self.pos);
+            self.pos = 0;
+        }
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Reads the next tuple if it's fully buffered. Returns `None` if more output is required,
+    /// or if the output has ended.
+    pub fn next_tuple(&mut self) -> Result<Option<CopyTuple<'_>>, CopyParseError> {
+        if self.is_finished {
+            return Ok(None);
+        }
+        if !self.is_header_read && !self.read_header()? {
+            return Ok(None);
+        }
+
+        let mut pos = self.pos;
+        let Some(field_count) = self.read_i16(&mut pos) else {
+            return Ok(None);
+        };
+        if field_count == TRAILER {
+            self.pos = pos;
+            self.is_finished = true;
+            return Ok(None);
+        }
+        let field_count = usize::try_from(field_count)
+            .map_err(|_| CopyParseError::new(format!("invalid field count: {field_count}")))?;
+
+        self.fields.clear();
+        for _ in 0..field_count {
+            let Some(len) = self.read_i32(&mut pos) else {
+                return Ok(None);
+            };
+            if len == -1 {
+                self.fields.push(None);
+                continue;
+            }
+            let len = usize::try_from(len)
+                .map_err(|_| CopyParseError::new(format!("invalid field length: {len}")))?;
+            if self.buffer.len() - pos < len {
+                return Ok(None);
+            }
+            self.fields.push(Some(pos..pos + len));
+            pos += len;
+        }
+        self.pos = pos;
+        Ok(Some(CopyTuple {
+            buffer: &self.buffer,
+            fields: &self.fields,
+        }))
+    }
+
+    /// Checks that the output has ended and was fully parsed.
+    pub fn finish(self) -> Result<(), CopyParseError> {
+        if !self.is_finished {
+            return Err(CopyParseError::new("output ended without trailer"));
+        }
+        if self.pos < self.buffer.len() {
+            let trailing_len = self.buffer.len() - self.pos;
+            return Err(CopyParseError::new(format!(
+                "{trailing_len} trailing bytes after trailer"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns `false` if the header isn't fully buffered yet.
+    fn read_header(&mut self) -> Result<bool, CopyParseError> {
+        if self.buffer.len() - self.pos < HEADER_LEN {
+            return Ok(false);
+        }
+        let mut pos = self.pos;
+        if !self.buffer[pos..].starts_with(SIGNATURE) {
+            return Err(CopyParseError::new("invalid signature"));
+        }
+        pos += SIGNATURE.len() + 4; // skip flags
+        let extension_len = self.read_i32(&mut pos).unwrap();
+        let extension_len = usize::try_from(extension_len).map_err(|_| {
+            CopyParseError::new(format!("invalid header extension length: {extension_len}"))
+        })?;
+        if self.buffer.len() - pos < extension_len {
+            return Ok(false);
+        }
+        self.pos = pos + extension_len;
+        self.is_header_read = true;
+        Ok(true)
+    }
+
+    fn read_i16(&self, pos: &mut usize) -> Option<i16> {
+        let bytes = self.buffer.get(*pos..*pos + 2)?;
+        *pos += 2;
+        Some(i16::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_i32(&self, pos: &mut usize) -> Option<i32> {
+        let bytes = self.buffer.get(*pos..*pos + 4)?;
+        *pos += 4;
+        Some(i32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+/// Tuple read by [`BinaryCopyParser`].
+#[derive(Debug)]
+pub(crate) struct CopyTuple<'a> {
+    buffer: &'a [u8],
+    fields: &'a [Option<ops::Range<usize>>],
+}
+
+impl<'a> CopyTuple<'a> {
+    /// Returns the raw value of a non-null field with the specified index.
+    pub fn bytes(&self, index: usize) -> Result<&'a [u8], CopyParseError> {
+        let field = self.fields.get(index).ok_or_else(|| {
+            CopyParseError::new(format!(
+                "field #{index} is out of bounds for tuple with {} fields",
+                self.fields.len()
+            ))
+        })?;
+        let range = field
+            .clone()
+            .ok_or_else(|| CopyParseError::new(format!("field #{index} is NULL")))?;
+        Ok(&self.buffer[range])
+    }
+
+    /// Returns the value of a non-null `BIGINT` field with the specified index.
+    pub fn i64(&self, index: usize) -> Result<i64, CopyParseError> {
+        let bytes = self.bytes(index)?;
+        let bytes = bytes.try_into().map_err(|_| {
+            CopyParseError::new(format!(
+                "field #{index} has length {}, expected 8 bytes for BIGINT",
+                bytes.len()
+            ))
+        })?;
+        Ok(i64::from_be_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_output(tuples: &[Vec<Option<&[u8]>>]) -> Vec<u8> {
+        let mut output = SIGNATURE.to_vec();
+        output.extend_from_slice(&0_i32.to_be_bytes()); // flags
+        output.extend_from_slice(&3_i32.to_be_bytes()); // header extension length
+        output.extend_from_slice(b"ext");
+        for tuple in tuples {
+            output.extend_from_slice(&(tuple.len() as i16).to_be_bytes());
+            for field in tuple {
+                if let Some(field) = field {
+                    output.extend_from_slice(&(field.len() as i32).to_be_bytes());
+                    output.extend_from_slice(field);
+                } else {
+                    output.extend_from_slice(&(-1_i32).to_be_bytes());
+                }
+            }
+        }
+        output.extend_from_slice(&TRAILER.to_be_bytes());
+        output
+    }
+
+    fn parse_output<'a>(
+        chunks: impl Iterator<Item = &'a [u8]>,
+    ) -> Result<Vec<(Vec<u8>, Option<i64>)>, CopyParseError> {
+        let mut parser = BinaryCopyParser::default();
+        let mut tuples = vec![];
+        for chunk in chunks {
+            parser.extend(chunk);
+            while let Some(tuple) = parser.next_tuple()? {
+                let value = tuple.bytes(0)?.to_vec();
+                let index = tuple.i64(1).ok();
+                tuples.push((value, index));
+            }
+        }
+        parser.finish()?;
+        Ok(tuples)
+    }
+
+    #[test]
+    fn parsing_copy_output() {
+        let index = 42_i64.to_be_bytes();
+        let output = encode_output(&[
+            vec![Some(&b"value"[..]), Some(&index[..])],
+            vec![Some(&b""[..]), None],
+            vec![Some(&[0xff; 32][..]), Some(&index[..])],
+        ]);
+        let expected_tuples = [
+            (b"value".to_vec(), Some(42)),
+            (vec![], None),
+            (vec![0xff; 32], Some(42)),
+        ];
+
+        let tuples = parse_output([output.as_slice()].into_iter()).unwrap();
+        assert_eq!(tuples, expected_tuples);
+        for chunk_size in [1, 2, 3, 7, 16] {
+            let tuples = parse_output(output.chunks(chunk_size)).unwrap();
+            assert_eq!(tuples, expected_tuples, "chunk_size={chunk_size}");
+        }
+    }
+
+    #[test]
+    fn parsing_empty_copy_output() {
+        let output = encode_output(&[]);
+        let tuples = parse_output([output.as_slice()].into_iter()).unwrap();
+        assert!(tuples.is_empty());
+    }
+
+    #[test]
+    fn parsing_malformed_copy_output() {
+        let index = 42_i64.to_be_bytes();
+        let output = encode_output(&[vec![Some(&b"value"[..]), Some(&index[..])]]);
+
+        let mut invalid_signature = output.clone();
+        invalid_signature[0] = b'X';
+        let err = parse_output([invalid_signature.as_slice()].into_iter()).unwrap_err();
+        assert!(err.to_string().contains("invalid signature"), "{err}");
+
+        let truncated_output = &output[..output.len() - 2];
+        let err = parse_output([truncated_output].into_iter()).unwrap_err();
+        assert!(err.to_string().contains("without trailer"), "{err}");
+
+        let mut trailing_output = output.clone();
+        trailing_output.push(0);
+        let err = parse_output([trailing_output.as_slice()].into_iter()).unwrap_err();
+        assert!(err.to_string().contains("1 trailing bytes"), "{err}");
+
+        let short_index = encode_output(&[vec![Some(&b"value"[..]), Some(&[1, 2, 3][..])]]);
+        let mut parser = BinaryCopyParser::default();
+        parser.extend(&short_index);
+        let tuple = parser.next_tuple().unwrap().unwrap();
+        let err = tuple.i64(1).unwrap_err();
+        assert!(err.to_string().contains("expected 8 bytes"), "{err}");
+        let err = tuple.bytes(2).unwrap_err();
+        assert!(err.to_string().contains("out of bounds"), "{err}");
+    }
+}
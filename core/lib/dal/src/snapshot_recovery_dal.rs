@@ -50,6 +50,7 @@ impl SnapshotRecoveryDal<'_, '_> {
         Ok(())
     }
 
+    /// Returns information about the newest snapshot applied to Postgres.
     pub async fn get_applied_snapshot_status(
         &mut self,
     ) -> sqlx::Result<Option<SnapshotRecoveryStatus>> {
@@ -64,6 +65,10 @@ impl SnapshotRecoveryDal<'_, '_> {
                 total_chunk_count
             FROM
                 snapshot_recovery
+            ORDER BY
+                l1_batch_number DESC
+            LIMIT
+                1
             "#,
         )
         .fetch_optional(self.storage.conn())
@@ -78,6 +83,75 @@ impl SnapshotRecoveryDal<'_, '_> {
             total_chunk_count: r.total_chunk_count as u64,
         }))
     }
+
+    /// Returns information about all snapshots applied to Postgres, newest first.
+    pub async fn get_applied_snapshot_statuses(
+        &mut self,
+    ) -> sqlx::Result<Vec<SnapshotRecoveryStatus>> {
+        let records = sqlx::query!(
+            r#"
+            SELECT
+                l1_batch_number,
+                l1_batch_root_hash,
+                miniblock_number,
+                miniblock_root_hash,
+                last_finished_chunk_id,
+                total_chunk_count
+            FROM
+                snapshot_recovery
+            ORDER BY
+                l1_batch_number DESC
+            "#,
+        )
+        .fetch_all(self.storage.conn())
+        .await?;
+
+        Ok(records
+            .into_iter()
+            .map(|r| SnapshotRecoveryStatus {
+                l1_batch_number: L1BatchNumber(r.l1_batch_number as u32),
+                l1_batch_root_hash: H256::from_slice(&r.l1_batch_root_hash),
+                miniblock_number: MiniblockNumber(r.miniblock_number as u32),
+                miniblock_root_hash: H256::from_slice(&r.miniblock_root_hash),
+                last_finished_chunk_id: r.last_finished_chunk_id.map(|v| v as u64),
+                total_chunk_count: r.total_chunk_count as u64,
+            })
+            .collect())
+    }
+
+    /// Checks whether storage logs of the snapshot the node is recovered from are fully applied to Postgres
+    /// (i.e., all snapshot chunks are finished). If several snapshots are applied, the newest one is checked.
+    /// Returns `None` if the node isn't recovered from a snapshot.
+    pub async fn is_storage_logs_recovery_complete(&mut self) -> sqlx::Result<Option<bool>> {
+        let is_complete = sqlx::query_scalar!(
+            r#"
+            SELECT
+                COALESCE(last_finished_chunk_id + 1 >= total_chunk_count, FALSE) AS "is_complete!"
+            FROM
+                snapshot_recovery
+            ORDER BY
+                l1_batch_number DESC
+            LIMIT
+                1
+            "#,
+        )
+        .fetch_optional(self.storage.conn())
+        .await?;
+        Ok(is_complete)
+    }
+
+    /// Removes information about the snapshot the node is recovered from. This is used when the snapshot
+    /// is replaced with a newer one.
+    pub async fn delete_applied_snapshot_status(&mut self) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            DELETE FROM snapshot_recovery
+            "#,
+        )
+        .execute(self.storage.conn())
+        .await?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -132,4 +206,65 @@ mod tests {
             .unwrap();
         assert_eq!(Some(updated_status), updated_status_from_db);
     }
+
+    #[tokio::test]
+    async fn listing_applied_snapshots() {
+        let connection_pool = ConnectionPool::test_pool().await;
+        let mut conn = connection_pool.access_storage().await.unwrap();
+        let mut dal = conn.snapshot_recovery_dal();
+        let statuses = dal.get_applied_snapshot_statuses().await.unwrap();
+        assert_eq!(statuses, []);
+
+        let statuses: Vec<_> = [123, 42, 234]
+            .into_iter()
+            .map(|number| SnapshotRecoveryStatus {
+                l1_batch_number: L1BatchNumber(number),
+                l1_batch_root_hash: H256::random(),
+                miniblock_number: MiniblockNumber(number * 2),
+                miniblock_root_hash: H256::random(),
+                last_finished_chunk_id: Some(0),
+                total_chunk_count: 1,
+            })
+            .collect();
+        for status in &statuses {
+            dal.set_applied_snapshot_status(status).await.unwrap();
+        }
+
+        let statuses_from_db = dal.get_applied_snapshot_statuses().await.unwrap();
+        assert_eq!(
+            statuses_from_db,
+            [
+                statuses[2].clone(),
+                statuses[0].clone(),
+                statuses[1].clone()
+            ]
+        );
+        let newest_status = dal.get_applied_snapshot_status().await.unwrap();
+        assert_eq!(newest_status.as_ref(), Some(&statuses[2]));
+    }
+
+    #[tokio::test]
+    async fn checking_storage_logs_recovery_completeness() {
+        let connection_pool = ConnectionPool::test_pool().await;
+        let mut conn = connection_pool.access_storage().await.unwrap();
+        let mut dal = conn.snapshot_recovery_dal();
+        let is_complete = dal.is_storage_logs_recovery_complete().await.unwrap();
+        assert_eq!(is_complete, None);
+
+        let mut status = SnapshotRecoveryStatus {
+            l1_batch_number: L1BatchNumber(123),
+            l1_batch_root_hash: H256::random(),
+            miniblock_number: MiniblockNumber(234),
+            miniblock_root_hash: H256::random(),
+            last_finished_chunk_id: None,
+            total_chunk_count: 3,
+        };
+        for (last_finished_chunk_id, expected) in [(None, false), (Some(1), false), (Some(2), true)]
+        {
+            status.last_finished_chunk_id = last_finished_chunk_id;
+            dal.set_applied_snapshot_status(&status).await.unwrap();
+            let is_complete = dal.is_storage_logs_recovery_complete().await.unwrap();
+            assert_eq!(is_complete, Some(expected), "{last_finished_chunk_id:?}");
+        }
+    }
 }
@@ -1,12 +1,16 @@
 use std::{collections::HashMap, ops, time::Instant};
 
+use futures::TryStreamExt;
 use sqlx::{types::chrono::Utc, Row};
 use zksync_types::{
     get_code_key, AccountTreeId, Address, L1BatchNumber, MiniblockNumber, StorageKey, StorageLog,
     FAILED_CONTRACT_DEPLOYMENT_BYTECODE_HASH, H256, U256,
 };
 
-use crate::{instrument::InstrumentExt, models::storage_log::StorageTreeEntry, StorageProcessor};
+use crate::{
+    instrument::InstrumentExt, models::storage_log::StorageTreeEntry, pg_copy::BinaryCopyParser,
+    StorageProcessor,
+};
 
 #[derive(Debug)]
 pub struct StorageLogsDal<'a, 'c> {
@@ -525,6 +529,75 @@ impl StorageLogsDal<'_, '_> {
         Ok(count.unwrap_or(0) as u64)
     }
 
+    /// Checks whether there are storage logs in miniblocks preceding the specified one.
+    pub async fn has_storage_logs_before_miniblock(
+        &mut self,
+        miniblock_number: MiniblockNumber,
+    ) -> sqlx::Result<bool> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                miniblock_number
+            FROM
+                storage_logs
+            WHERE
+                miniblock_number < $1
+            LIMIT
+                1
+            "#,
+            miniblock_number.0 as i64
+        )
+        .fetch_optional(self.storage.conn())
+        .await?;
+        Ok(row.is_some())
+    }
+
+    /// Returns the maximum leaf index among storage logs in the specified miniblock, or 0 if there are no logs.
+    /// This is used to sanity-check Merkle tree recovery.
+    pub async fn max_leaf_index_for_miniblock(
+        &mut self,
+        miniblock_number: MiniblockNumber,
+    ) -> sqlx::Result<u64> {
+        let max_index = sqlx::query_scalar!(
+            r#"
+            SELECT
+                MAX(initial_writes.index)
+            FROM
+                storage_logs
+                INNER JOIN initial_writes ON storage_logs.hashed_key = initial_writes.hashed_key
+            WHERE
+                storage_logs.miniblock_number = $1
+            "#,
+            miniblock_number.0 as i64
+        )
+        .fetch_one(self.storage.conn())
+        .await?;
+        Ok(max_index.unwrap_or(0) as u64)
+    }
+
+    /// Counts distinct leaf indices among storage logs in the specified miniblock. This is used to sanity-check
+    /// Merkle tree recovery; for a valid snapshot, the returned count is equal to the number of storage logs.
+    pub async fn count_distinct_leaf_indices_for_miniblock(
+        &mut self,
+        miniblock_number: MiniblockNumber,
+    ) -> sqlx::Result<u64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT
+                COUNT(DISTINCT initial_writes.index)
+            FROM
+                storage_logs
+                INNER JOIN initial_writes ON storage_logs.hashed_key = initial_writes.hashed_key
+            WHERE
+                storage_logs.miniblock_number = $1
+            "#,
+            miniblock_number.0 as i64
+        )
+        .fetch_one(self.storage.conn())
+        .await?;
+        Ok(count.unwrap_or(0) as u64)
+    }
+
     /// Gets a starting tree entry for each of the supplied `key_ranges` for the specified
     /// `miniblock_number`. This method is used during Merkle tree recovery.
     pub async fn get_chunk_starts_for_miniblock(
@@ -583,6 +656,104 @@ impl StorageLogsDal<'_, '_> {
         Ok(rows.collect())
     }
 
+    /// Gets an ending tree entry (i.e., the entry with the greatest hashed key) for each of the supplied `key_ranges`
+    /// for the specified `miniblock_number`. This method is used during Merkle tree recovery.
+    pub async fn get_chunk_ends_for_miniblock(
+        &mut self,
+        miniblock_number: MiniblockNumber,
+        key_ranges: &[ops::RangeInclusive<H256>],
+    ) -> sqlx::Result<Vec<Option<StorageTreeEntry>>> {
+        let (start_keys, end_keys): (Vec<_>, Vec<_>) = key_ranges
+            .iter()
+            .map(|range| (range.start().as_bytes(), range.end().as_bytes()))
+            .unzip();
+        let rows = sqlx::query!(
+            r#"
+            WITH
+                sl AS (
+                    SELECT
+                        (
+                            SELECT
+                                ARRAY[hashed_key, value] AS kv
+                            FROM
+                                storage_logs
+                            WHERE
+                                storage_logs.miniblock_number = $1
+                                AND storage_logs.hashed_key >= u.start_key
+                                AND storage_logs.hashed_key <= u.end_key
+                            ORDER BY
+                                storage_logs.hashed_key DESC
+                            LIMIT
+                                1
+                        )
+                    FROM
+                        UNNEST($2::bytea[], $3::bytea[]) AS u (start_key, end_key)
+                )
+            SELECT
+                sl.kv[1] AS "hashed_key?",
+                sl.kv[2] AS "value?",
+                initial_writes.index
+            FROM
+                sl
+                LEFT OUTER JOIN initial_writes ON initial_writes.hashed_key = sl.kv[1]
+            "#,
+            miniblock_number.0 as i64,
+            &start_keys as &[&[u8]],
+            &end_keys as &[&[u8]],
+        )
+        .fetch_all(self.storage.conn())
+        .await?;
+
+        let rows = rows.into_iter().map(|row| {
+            Some(StorageTreeEntry {
+                key: U256::from_little_endian(row.hashed_key.as_ref()?),
+                value: H256::from_slice(row.value.as_ref()?),
+                leaf_index: row.index? as u64,
+            })
+        });
+        Ok(rows.collect())
+    }
+
+    /// Counts storage logs for the specified `miniblock_number` with hashed keys in each of `key_ranges`.
+    /// The returned vector has the same length as `key_ranges`. All ranges are processed by a single query,
+    /// each with an index range scan. This is used to plan Merkle tree recovery.
+    pub async fn count_storage_logs_in_ranges(
+        &mut self,
+        miniblock_number: MiniblockNumber,
+        key_ranges: &[ops::RangeInclusive<H256>],
+    ) -> sqlx::Result<Vec<u64>> {
+        let (start_keys, end_keys): (Vec<_>, Vec<_>) = key_ranges
+            .iter()
+            .map(|range| (range.start().as_bytes(), range.end().as_bytes()))
+            .unzip();
+        let counts = sqlx::query_scalar!(
+            r#"
+            SELECT
+                (
+                    SELECT
+                        COUNT(*)
+                    FROM
+                        storage_logs
+                    WHERE
+                        storage_logs.miniblock_number = $1
+                        AND storage_logs.hashed_key >= u.start_key
+                        AND storage_logs.hashed_key <= u.end_key
+                ) AS "count!"
+            FROM
+                UNNEST($2::bytea[], $3::bytea[]) WITH ORDINALITY AS u (start_key, end_key, ordinal)
+            ORDER BY
+                u.ordinal
+            "#,
+            miniblock_number.0 as i64,
+            &start_keys as &[&[u8]],
+            &end_keys as &[&[u8]],
+        )
+        .fetch_all(self.storage.conn())
+        .await?;
+
+        Ok(counts.into_iter().map(|count| count as u64).collect())
+    }
+
     /// Fetches tree entries for the specified `miniblock_number` and `key_range`. This is used during
     /// Merkle tree recovery.
     pub async fn get_tree_entries_for_miniblock(
@@ -621,6 +792,173 @@ impl StorageLogsDal<'_, '_> {
         Ok(rows.collect())
     }
 
+    /// Same as [`Self::get_tree_entries_for_miniblock()`], but streams entries using
+    /// `COPY ... TO STDOUT (FORMAT binary)` and decodes them with a minimal binary parser. This avoids
+    /// the overhead of decoding rows one by one, which is noticeable for large key ranges. Note that `COPY`
+    /// may require additional permissions in some managed Postgres setups.
+    pub async fn copy_tree_entries_for_miniblock(
+        &mut self,
+        miniblock_number: MiniblockNumber,
+        key_range: ops::RangeInclusive<H256>,
+    ) -> sqlx::Result<Vec<StorageTreeEntry>> {
+        // `COPY` doesn't support bind parameters, so they are inlined; all of them are hex-encoded or numeric.
+        let statement = format!(
+            r"COPY (
+                SELECT
+                    storage_logs.hashed_key,
+                    storage_logs.value,
+                    initial_writes.index
+                FROM
+                    storage_logs
+                    INNER JOIN initial_writes ON storage_logs.hashed_key = initial_writes.hashed_key
+                WHERE
+                    storage_logs.miniblock_number = {miniblock_number}
+                    AND storage_logs.hashed_key >= '\x{start:x}'::bytea
+                    AND storage_logs.hashed_key <= '\x{end:x}'::bytea
+                ORDER BY
+                    storage_logs.hashed_key
+            ) TO STDOUT (FORMAT binary)",
+            miniblock_number = miniblock_number.0,
+            start = key_range.start(),
+            end = key_range.end()
+        );
+
+        let mut output = self.storage.conn().copy_out_raw(&statement).await?;
+        let mut parser = BinaryCopyParser::default();
+        let mut entries = vec![];
+        while let Some(chunk) = output.try_next().await? {
+            parser.extend(&chunk);
+            while let Some(tuple) = parser.next_tuple()? {
+                entries.push(StorageTreeEntry {
+                    key: U256::from_little_endian(tuple.bytes(0)?),
+                    value: H256::from_slice(tuple.bytes(1)?),
+                    leaf_index: tuple.i64(2)? as u64,
+                });
+            }
+        }
+        parser.finish()?;
+        Ok(entries)
+    }
+
+    /// Fetches up to `limit` tree entries for the specified `miniblock_number` with hashed keys in `key_range`
+    /// strictly greater than `after_key` (if specified), ordered by hashed key. This is used to load entries
+    /// in pages using keyset pagination during Merkle tree recovery, so that a single query doesn't need
+    /// to return the entire key range; the next page should start after the hashed key of the last returned entry.
+    pub async fn get_tree_entries_for_miniblock_paged(
+        &mut self,
+        miniblock_number: MiniblockNumber,
+        key_range: ops::RangeInclusive<H256>,
+        after_key: Option<H256>,
+        limit: usize,
+    ) -> sqlx::Result<Vec<StorageTreeEntry>> {
+        // An empty byte sequence is less than any hashed key, so it doesn't restrict the range.
+        let after_key = after_key.as_ref().map_or(&[][..], H256::as_bytes);
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                storage_logs.hashed_key,
+                storage_logs.value,
+                initial_writes.index
+            FROM
+                storage_logs
+                INNER JOIN initial_writes ON storage_logs.hashed_key = initial_writes.hashed_key
+            WHERE
+                storage_logs.miniblock_number = $1
+                AND storage_logs.hashed_key >= $2::bytea
+                AND storage_logs.hashed_key <= $3::bytea
+                AND storage_logs.hashed_key > $4::bytea
+            ORDER BY
+                storage_logs.hashed_key
+            LIMIT
+                $5
+            "#,
+            miniblock_number.0 as i64,
+            key_range.start().as_bytes(),
+            key_range.end().as_bytes(),
+            after_key,
+            limit as i64
+        )
+        .fetch_all(self.storage.conn())
+        .await?;
+
+        let rows = rows.into_iter().map(|row| StorageTreeEntry {
+            key: U256::from_little_endian(&row.hashed_key),
+            value: H256::from_slice(&row.value),
+            leaf_index: row.index as u64,
+        });
+        Ok(rows.collect())
+    }
+
+    /// Fetches tree entries for the specified `miniblock_number` and `hashed_keys`, ordered by hashed key.
+    /// Keys not present in the miniblock are skipped. This is used to verify a recovered Merkle tree
+    /// against the snapshot.
+    pub async fn get_tree_entries_for_hashed_keys(
+        &mut self,
+        miniblock_number: MiniblockNumber,
+        hashed_keys: &[H256],
+    ) -> sqlx::Result<Vec<StorageTreeEntry>> {
+        let hashed_keys: Vec<_> = hashed_keys.iter().map(H256::as_bytes).collect();
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                storage_logs.hashed_key,
+                storage_logs.value,
+                initial_writes.index
+            FROM
+                storage_logs
+                INNER JOIN initial_writes ON storage_logs.hashed_key = initial_writes.hashed_key
+            WHERE
+                storage_logs.miniblock_number = $1
+                AND storage_logs.hashed_key = ANY ($2::bytea[])
+            ORDER BY
+                storage_logs.hashed_key
+            "#,
+            miniblock_number.0 as i64,
+            &hashed_keys as &[&[u8]]
+        )
+        .fetch_all(self.storage.conn())
+        .await?;
+
+        let rows = rows.into_iter().map(|row| StorageTreeEntry {
+            key: U256::from_little_endian(&row.hashed_key),
+            value: H256::from_slice(&row.value),
+            leaf_index: row.index as u64,
+        });
+        Ok(rows.collect())
+    }
+
+    /// Computes a coarse histogram of hashed keys for the specified `miniblock_number`. Keys are bucketed
+    /// by their first 2 bytes, so the returned vector always has `1 << 16` elements; the element at index `i`
+    /// is the number of hashed keys starting with the big-endian 2-byte prefix `i`. This is used during
+    /// Merkle tree recovery to split the key space into chunks of approximately equal size.
+    pub async fn get_hashed_key_histogram_for_miniblock(
+        &mut self,
+        miniblock_number: MiniblockNumber,
+    ) -> sqlx::Result<Vec<u64>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                GET_BYTE(hashed_key, 0) * 256 + GET_BYTE(hashed_key, 1) AS "bucket!",
+                COUNT(*) AS "count!"
+            FROM
+                storage_logs
+            WHERE
+                miniblock_number = $1
+            GROUP BY
+                1
+            "#,
+            miniblock_number.0 as i64
+        )
+        .fetch_all(self.storage.conn())
+        .await?;
+
+        let mut histogram = vec![0_u64; 1 << 16];
+        for row in rows {
+            histogram[row.bucket as usize] = row.count as u64;
+        }
+        Ok(histogram)
+    }
+
     pub async fn retain_storage_logs(
         &mut self,
         miniblock_number: MiniblockNumber,
@@ -986,6 +1324,43 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn getting_ending_entries_in_chunks() {
+        let pool = ConnectionPool::test_pool().await;
+        let mut conn = pool.access_storage().await.unwrap();
+        let sorted_hashed_keys = prepare_tree_entries(&mut conn, 100).await;
+
+        let key_ranges = [
+            H256::zero()..=H256::repeat_byte(0xff),
+            H256::repeat_byte(0x40)..=H256::repeat_byte(0x80),
+            H256::repeat_byte(0x50)..=H256::repeat_byte(0x51),
+            H256::repeat_byte(0x11)..=H256::repeat_byte(0x11),
+        ];
+
+        let chunk_ends = conn
+            .storage_logs_dal()
+            .get_chunk_ends_for_miniblock(MiniblockNumber(1), &key_ranges)
+            .await
+            .unwrap();
+
+        for (chunk_end, key_range) in chunk_ends.into_iter().zip(key_ranges) {
+            let expected_end_key = sorted_hashed_keys
+                .iter()
+                .rev()
+                .find(|&key| key_range.contains(key));
+            if let Some(chunk_end) = chunk_end {
+                assert_eq!(
+                    u256_to_h256_reversed(chunk_end.key),
+                    *expected_end_key.unwrap()
+                );
+                assert_ne!(chunk_end.value, H256::zero());
+                assert_ne!(chunk_end.leaf_index, 0);
+            } else {
+                assert_eq!(expected_end_key, None);
+            }
+        }
+    }
+
     async fn prepare_tree_entries(conn: &mut StorageProcessor<'_>, count: u8) -> Vec<H256> {
         conn.protocol_versions_dal()
             .save_protocol_version_with_tx(ProtocolVersion::default())
@@ -1043,4 +1418,375 @@ mod tests {
             assert!(key_range.contains(&u256_to_h256_reversed(entry.key)));
         }
     }
+
+    fn assert_equal_entries(lhs: &[StorageTreeEntry], rhs: &[StorageTreeEntry]) {
+        assert_eq!(lhs.len(), rhs.len());
+        for (lhs, rhs) in lhs.iter().zip(rhs) {
+            assert_eq!(
+                (lhs.key, lhs.value, lhs.leaf_index),
+                (rhs.key, rhs.value, rhs.leaf_index)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn copying_tree_entries() {
+        let pool = ConnectionPool::test_pool().await;
+        let mut conn = pool.access_storage().await.unwrap();
+        prepare_tree_entries(&mut conn, 10).await;
+
+        let key_ranges = [
+            H256::zero()..=H256::repeat_byte(0xff),
+            H256::repeat_byte(0x80)..=H256::repeat_byte(0xbf),
+            H256::repeat_byte(0xbf)..=H256::repeat_byte(0x80),
+        ];
+        for key_range in key_ranges {
+            let copied_entries = conn
+                .storage_logs_dal()
+                .copy_tree_entries_for_miniblock(MiniblockNumber(1), key_range.clone())
+                .await
+                .unwrap();
+            let queried_entries = conn
+                .storage_logs_dal()
+                .get_tree_entries_for_miniblock(MiniblockNumber(1), key_range.clone())
+                .await
+                .unwrap();
+            assert_equal_entries(&copied_entries, &queried_entries);
+        }
+
+        let copied_entries = conn
+            .storage_logs_dal()
+            .copy_tree_entries_for_miniblock(
+                MiniblockNumber(2),
+                H256::zero()..=H256::repeat_byte(0xff),
+            )
+            .await
+            .unwrap();
+        assert!(copied_entries.is_empty());
+    }
+
+    /// Rough benchmark comparing loading a large chunk of tree entries using `COPY` and using a conventional query.
+    #[tokio::test]
+    #[ignore] // takes a while; run manually
+    async fn copying_tree_entries_is_faster_than_query() {
+        const ENTRY_COUNT: u64 = 1_000_000;
+
+        let pool = ConnectionPool::test_pool().await;
+        let mut conn = pool.access_storage().await.unwrap();
+        conn.protocol_versions_dal()
+            .save_protocol_version_with_tx(ProtocolVersion::default())
+            .await;
+        let account = AccountTreeId::new(Address::repeat_byte(1));
+        let logs: Vec<_> = (0..ENTRY_COUNT)
+            .map(|i| {
+                let key = StorageKey::new(account, H256::from_low_u64_be(i));
+                StorageLog::new_write_log(key, H256::from_low_u64_be(i + 1))
+            })
+            .collect();
+        conn.storage_logs_dal()
+            .insert_storage_logs(MiniblockNumber(1), &[(H256::zero(), logs.clone())])
+            .await;
+        let keys: Vec<_> = logs.iter().map(|log| log.key).collect();
+        conn.storage_logs_dedup_dal()
+            .insert_initial_writes(L1BatchNumber(1), &keys)
+            .await;
+
+        let key_range = H256::zero()..=H256::repeat_byte(0xff);
+        let started_at = Instant::now();
+        let queried_entries = conn
+            .storage_logs_dal()
+            .get_tree_entries_for_miniblock(MiniblockNumber(1), key_range.clone())
+            .await
+            .unwrap();
+        let query_latency = started_at.elapsed();
+        let started_at = Instant::now();
+        let copied_entries = conn
+            .storage_logs_dal()
+            .copy_tree_entries_for_miniblock(MiniblockNumber(1), key_range)
+            .await
+            .unwrap();
+        let copy_latency = started_at.elapsed();
+
+        assert_eq!(copied_entries.len() as u64, ENTRY_COUNT);
+        assert_equal_entries(&copied_entries, &queried_entries);
+        assert!(
+            copy_latency < query_latency,
+            "COPY ({copy_latency:?}) is slower than query ({query_latency:?})"
+        );
+    }
+
+    async fn load_tree_entries_in_pages(
+        conn: &mut StorageProcessor<'_>,
+        key_range: ops::RangeInclusive<H256>,
+        page_size: usize,
+    ) -> Vec<H256> {
+        let mut loaded_keys = vec![];
+        loop {
+            let page = conn
+                .storage_logs_dal()
+                .get_tree_entries_for_miniblock_paged(
+                    MiniblockNumber(1),
+                    key_range.clone(),
+                    loaded_keys.last().copied(),
+                    page_size,
+                )
+                .await
+                .unwrap();
+            assert!(page.len() <= page_size);
+            let page_keys = page.iter().map(|entry| u256_to_h256_reversed(entry.key));
+            loaded_keys.extend(page_keys);
+            if page.len() < page_size {
+                break;
+            }
+        }
+        loaded_keys
+    }
+
+    #[tokio::test]
+    async fn getting_tree_entries_in_pages() {
+        let pool = ConnectionPool::test_pool().await;
+        let mut conn = pool.access_storage().await.unwrap();
+        let sorted_hashed_keys = prepare_tree_entries(&mut conn, 10).await;
+
+        let full_range = H256::zero()..=H256::repeat_byte(0xff);
+        for page_size in [1, 3, 5, 10, 20] {
+            let loaded_keys =
+                load_tree_entries_in_pages(&mut conn, full_range.clone(), page_size).await;
+            assert_eq!(loaded_keys, sorted_hashed_keys, "page_size={page_size}");
+        }
+
+        let key_range = sorted_hashed_keys[2]..=sorted_hashed_keys[7];
+        for page_size in [1, 2, 3, 6] {
+            let loaded_keys =
+                load_tree_entries_in_pages(&mut conn, key_range.clone(), page_size).await;
+            assert_eq!(
+                loaded_keys,
+                sorted_hashed_keys[2..=7],
+                "page_size={page_size}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn getting_tree_entries_in_pages_at_range_boundaries() {
+        let pool = ConnectionPool::test_pool().await;
+        let mut conn = pool.access_storage().await.unwrap();
+        let sorted_hashed_keys = prepare_tree_entries(&mut conn, 10).await;
+
+        let key_range = sorted_hashed_keys[2]..=sorted_hashed_keys[7];
+        // `after_key` preceding the range doesn't extend it.
+        let page = conn
+            .storage_logs_dal()
+            .get_tree_entries_for_miniblock_paged(
+                MiniblockNumber(1),
+                key_range.clone(),
+                Some(sorted_hashed_keys[0]),
+                10,
+            )
+            .await
+            .unwrap();
+        let page_keys: Vec<_> = page
+            .iter()
+            .map(|entry| u256_to_h256_reversed(entry.key))
+            .collect();
+        assert_eq!(page_keys, sorted_hashed_keys[2..=7]);
+
+        // `after_key` equal to the range end results in an empty page.
+        let page = conn
+            .storage_logs_dal()
+            .get_tree_entries_for_miniblock_paged(
+                MiniblockNumber(1),
+                key_range.clone(),
+                Some(sorted_hashed_keys[7]),
+                10,
+            )
+            .await
+            .unwrap();
+        assert!(page.is_empty());
+
+        // Same for `after_key` equal to the range end not present in the snapshot.
+        let page = conn
+            .storage_logs_dal()
+            .get_tree_entries_for_miniblock_paged(
+                MiniblockNumber(1),
+                H256::zero()..=H256::repeat_byte(0xff),
+                Some(H256::repeat_byte(0xff)),
+                10,
+            )
+            .await
+            .unwrap();
+        assert!(page.is_empty());
+
+        // `after_key` preceding the range end by a single entry results in a single-entry page.
+        let page = conn
+            .storage_logs_dal()
+            .get_tree_entries_for_miniblock_paged(
+                MiniblockNumber(1),
+                key_range,
+                Some(sorted_hashed_keys[6]),
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(u256_to_h256_reversed(page[0].key), sorted_hashed_keys[7]);
+    }
+
+    async fn count_storage_logs_in_range(
+        conn: &mut StorageProcessor<'_>,
+        miniblock_number: MiniblockNumber,
+        key_range: &ops::RangeInclusive<H256>,
+    ) -> u64 {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM storage_logs \
+             WHERE miniblock_number = $1 AND hashed_key >= $2 AND hashed_key <= $3",
+        )
+        .bind(miniblock_number.0 as i64)
+        .bind(key_range.start().as_bytes())
+        .bind(key_range.end().as_bytes())
+        .fetch_one(conn.conn())
+        .await
+        .unwrap();
+        count as u64
+    }
+
+    #[tokio::test]
+    async fn counting_storage_logs_in_ranges() {
+        let pool = ConnectionPool::test_pool().await;
+        let mut conn = pool.access_storage().await.unwrap();
+        let sorted_hashed_keys = prepare_tree_entries(&mut conn, 50).await;
+
+        let mut key_ranges: Vec<_> = (0_u8..=255)
+            .map(|i| {
+                let mut start = H256::zero();
+                start.0[0] = i;
+                let mut end = H256::repeat_byte(0xff);
+                end.0[0] = i;
+                start..=end
+            })
+            .collect();
+        // Ranges can overlap and be empty, and they don't need to be sorted.
+        key_ranges.extend([
+            H256::zero()..=H256::repeat_byte(0xff),
+            sorted_hashed_keys[10]..=sorted_hashed_keys[20],
+            sorted_hashed_keys[5]..=sorted_hashed_keys[5],
+            sorted_hashed_keys[7]..=sorted_hashed_keys[6],
+            H256::zero()..=H256::repeat_byte(0x7f),
+        ]);
+
+        let counts = conn
+            .storage_logs_dal()
+            .count_storage_logs_in_ranges(MiniblockNumber(1), &key_ranges)
+            .await
+            .unwrap();
+        assert_eq!(counts.len(), key_ranges.len());
+        for (range, &count) in key_ranges.iter().zip(&counts) {
+            let expected_count =
+                count_storage_logs_in_range(&mut conn, MiniblockNumber(1), range).await;
+            assert_eq!(count, expected_count, "{range:?}");
+        }
+        assert_eq!(counts[..256].iter().sum::<u64>(), 50);
+        assert_eq!(counts[256..260], [50, 11, 1, 0]);
+
+        let counts = conn
+            .storage_logs_dal()
+            .count_storage_logs_in_ranges(MiniblockNumber(2), &key_ranges)
+            .await
+            .unwrap();
+        assert_eq!(counts.len(), key_ranges.len());
+        assert!(counts.iter().all(|&count| count == 0));
+
+        let counts = conn
+            .storage_logs_dal()
+            .count_storage_logs_in_ranges(MiniblockNumber(1), &[])
+            .await
+            .unwrap();
+        assert!(counts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn getting_hashed_key_histogram() {
+        let pool = ConnectionPool::test_pool().await;
+        let mut conn = pool.access_storage().await.unwrap();
+        let sorted_hashed_keys = prepare_tree_entries(&mut conn, 50).await;
+
+        let histogram = conn
+            .storage_logs_dal()
+            .get_hashed_key_histogram_for_miniblock(MiniblockNumber(1))
+            .await
+            .unwrap();
+        assert_eq!(histogram.len(), 1 << 16);
+        assert_eq!(histogram.iter().sum::<u64>(), 50);
+
+        let mut expected_histogram = vec![0_u64; 1 << 16];
+        for key in &sorted_hashed_keys {
+            let prefix = [key.as_bytes()[0], key.as_bytes()[1]];
+            expected_histogram[usize::from(u16::from_be_bytes(prefix))] += 1;
+        }
+        assert_eq!(histogram, expected_histogram);
+
+        let histogram = conn
+            .storage_logs_dal()
+            .get_hashed_key_histogram_for_miniblock(MiniblockNumber(2))
+            .await
+            .unwrap();
+        assert!(histogram.iter().all(|&count| count == 0));
+    }
+
+    #[tokio::test]
+    async fn getting_leaf_index_stats() {
+        let pool = ConnectionPool::test_pool().await;
+        let mut conn = pool.access_storage().await.unwrap();
+        let sorted_hashed_keys = prepare_tree_entries(&mut conn, 10).await;
+
+        let max_leaf_index = conn
+            .storage_logs_dal()
+            .max_leaf_index_for_miniblock(MiniblockNumber(1))
+            .await
+            .unwrap();
+        assert_eq!(max_leaf_index, 10);
+        let distinct_count = conn
+            .storage_logs_dal()
+            .count_distinct_leaf_indices_for_miniblock(MiniblockNumber(1))
+            .await
+            .unwrap();
+        assert_eq!(distinct_count, 10);
+
+        // Artificially duplicate a leaf index.
+        sqlx::query("DROP INDEX initial_writes_index_index")
+            .execute(conn.conn())
+            .await
+            .unwrap();
+        sqlx::query("UPDATE initial_writes SET index = 1 WHERE hashed_key = $1")
+            .bind(sorted_hashed_keys[5].as_bytes())
+            .execute(conn.conn())
+            .await
+            .unwrap();
+
+        let distinct_count = conn
+            .storage_logs_dal()
+            .count_distinct_leaf_indices_for_miniblock(MiniblockNumber(1))
+            .await
+            .unwrap();
+        assert_eq!(distinct_count, 9);
+        let log_count = conn
+            .storage_logs_dal()
+            .count_miniblock_storage_logs(MiniblockNumber(1))
+            .await
+            .unwrap();
+        assert_eq!(log_count, 10);
+
+        let max_leaf_index = conn
+            .storage_logs_dal()
+            .max_leaf_index_for_miniblock(MiniblockNumber(2))
+            .await
+            .unwrap();
+        assert_eq!(max_leaf_index, 0);
+        let distinct_count = conn
+            .storage_logs_dal()
+            .count_distinct_leaf_indices_for_miniblock(MiniblockNumber(2))
+            .await
+            .unwrap();
+        assert_eq!(distinct_count, 0);
+    }
 }
@@ -27,4 +27,60 @@ impl SystemDal<'_, '_> {
             _ => 0,
         }
     }
+
+    /// Returns the process ID of the Postgres backend serving this connection.
+    pub async fn get_backend_pid(&mut self) -> sqlx::Result<i32> {
+        sqlx::query_scalar("SELECT pg_backend_pid()")
+            .fetch_one(self.storage.conn())
+            .await
+    }
+
+    /// Cancels the query currently executed by the Postgres backend with the specified process ID.
+    /// Returns `false` if the query cannot be cancelled (e.g., there's no such backend).
+    pub async fn cancel_backend_query(&mut self, pid: i32) -> sqlx::Result<bool> {
+        sqlx::query_scalar("SELECT pg_cancel_backend($1)")
+            .bind(pid)
+            .fetch_one(self.storage.conn())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::ConnectionPool;
+
+    #[tokio::test]
+    async fn cancelling_query_after_closing_connection() {
+        let pool = ConnectionPool::test_pool().await;
+        let mut conn = pool.access_storage().await.unwrap();
+        let pid = conn.system_dal().get_backend_pid().await.unwrap();
+
+        let query = sqlx::query("SELECT pg_sleep(60)").execute(conn.conn());
+        tokio::time::timeout(Duration::from_millis(200), query)
+            .await
+            .unwrap_err();
+        conn.close().await.unwrap();
+
+        let mut conn = pool.access_storage().await.unwrap();
+        assert!(conn.system_dal().cancel_backend_query(pid).await.unwrap());
+        // The backend should terminate shortly after the query is cancelled.
+        let mut backend_count = 1;
+        for _ in 0..100 {
+            backend_count = sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM pg_stat_activity WHERE pid = $1",
+            )
+            .bind(pid)
+            .fetch_one(conn.conn())
+            .await
+            .unwrap();
+            if backend_count == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(backend_count, 0);
+    }
 }
@@ -0,0 +1,413 @@
+use std::ops;
+
+use zksync_types::{L1BatchNumber, H256};
+
+use crate::StorageProcessor;
+
+/// Chunk of snapshot storage logs recovered by the Merkle tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveredTreeChunk {
+    /// 0-based index of the chunk.
+    pub chunk_id: u64,
+    /// Range of hashed keys covered by the chunk.
+    pub key_range: ops::RangeInclusive<H256>,
+    /// Number of entries inserted into the tree for the chunk, or `None` if it's unknown (e.g., the chunk
+    /// was recovered before progress was recorded).
+    pub entry_count: Option<u64>,
+}
+
+/// Summary of the Merkle tree recovery progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeRecoverySummary {
+    /// Number of the snapshot L1 batch the tree is recovered to.
+    pub l1_batch_number: L1BatchNumber,
+    pub total_chunk_count: u64,
+    pub recovered_chunk_count: u64,
+    /// Number of recovered entries. Doesn't include entries of chunks with unknown entry count.
+    pub recovered_entry_count: u64,
+    pub is_finished: bool,
+}
+
+/// DAL for Merkle tree recovery progress. Progress is recorded so that it can be monitored using SQL;
+/// it is not used by the recovery logic, which relies on the tree data.
+#[derive(Debug)]
+pub struct TreeRecoveryProgressDal<'a, 'c> {
+    pub(crate) storage: &'a mut StorageProcessor<'c>,
+}
+
+impl TreeRecoveryProgressDal<'_, '_> {
+    /// Inserts or updates recovered chunks for the specified snapshot L1 batch and updates the recovery summary.
+    pub async fn insert_recovered_chunks(
+        &mut self,
+        l1_batch_number: L1BatchNumber,
+        total_chunk_count: u64,
+        chunks: &[RecoveredTreeChunk],
+    ) -> sqlx::Result<()> {
+        let mut chunk_ids = Vec::with_capacity(chunks.len());
+        let mut start_keys = Vec::with_capacity(chunks.len());
+        let mut end_keys = Vec::with_capacity(chunks.len());
+        let mut entry_counts = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            chunk_ids.push(chunk.chunk_id as i32);
+            start_keys.push(chunk.key_range.start().as_bytes());
+            end_keys.push(chunk.key_range.end().as_bytes());
+            entry_counts.push(chunk.entry_count.map(|count| count as i64));
+        }
+
+        let mut transaction = self.storage.start_transaction().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO
+                tree_recovery_progress (
+                    l1_batch_number,
+                    chunk_id,
+                    start_key,
+                    end_key,
+                    entry_count,
+                    completed_at
+                )
+            SELECT
+                $1,
+                u.chunk_id,
+                u.start_key,
+                u.end_key,
+                u.entry_count,
+                NOW()
+            FROM
+                UNNEST($2::INT[], $3::BYTEA[], $4::BYTEA[], $5::BIGINT[]) AS u (chunk_id, start_key, end_key, entry_count)
+            ON CONFLICT (l1_batch_number, chunk_id) DO
+            UPDATE
+            SET
+                start_key = excluded.start_key,
+                end_key = excluded.end_key,
+                entry_count = excluded.entry_count,
+                completed_at = excluded.completed_at
+            "#,
+            l1_batch_number.0 as i64,
+            &chunk_ids,
+            &start_keys as &[&[u8]],
+            &end_keys as &[&[u8]],
+            &entry_counts as &[Option<i64>],
+        )
+        .execute(transaction.conn())
+        .await?;
+
+        transaction
+            .tree_recovery_progress_dal()
+            .update_summary(l1_batch_number, total_chunk_count)
+            .await?;
+        transaction.commit().await
+    }
+
+    /// Reconciles recorded progress with the chunks actually recovered by the tree (e.g., after a restart):
+    ///
+    /// - Progress for other snapshot L1 batches is removed.
+    /// - Chunks not present in `recovered_chunks` are removed.
+    /// - Chunks from `recovered_chunks` missing in the table are inserted with an unknown entry count.
+    pub async fn reconcile_recovered_chunks(
+        &mut self,
+        l1_batch_number: L1BatchNumber,
+        total_chunk_count: u64,
+        recovered_chunks: &[(u64, ops::RangeInclusive<H256>)],
+    ) -> sqlx::Result<()> {
+        let mut chunk_ids = Vec::with_capacity(recovered_chunks.len());
+        let mut start_keys = Vec::with_capacity(recovered_chunks.len());
+        let mut end_keys = Vec::with_capacity(recovered_chunks.len());
+        for (chunk_id, key_range) in recovered_chunks {
+            chunk_ids.push(*chunk_id as i32);
+            start_keys.push(key_range.start().as_bytes());
+            end_keys.push(key_range.end().as_bytes());
+        }
+
+        let mut transaction = self.storage.start_transaction().await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM tree_recovery_progress
+            WHERE
+                l1_batch_number <> $1
+                OR chunk_id <> ALL ($2)
+            "#,
+            l1_batch_number.0 as i64,
+            &chunk_ids,
+        )
+        .execute(transaction.conn())
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM tree_recovery_summary
+            WHERE
+                l1_batch_number <> $1
+            "#,
+            l1_batch_number.0 as i64,
+        )
+        .execute(transaction.conn())
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO
+                tree_recovery_progress (
+                    l1_batch_number,
+                    chunk_id,
+                    start_key,
+                    end_key,
+                    entry_count,
+                    completed_at
+                )
+            SELECT
+                $1,
+                u.chunk_id,
+                u.start_key,
+                u.end_key,
+                NULL,
+                NOW()
+            FROM
+                UNNEST($2::INT[], $3::BYTEA[], $4::BYTEA[]) AS u (chunk_id, start_key, end_key)
+            ON CONFLICT (l1_batch_number, chunk_id) DO NOTHING
+            "#,
+            l1_batch_number.0 as i64,
+            &chunk_ids,
+            &start_keys as &[&[u8]],
+            &end_keys as &[&[u8]],
+        )
+        .execute(transaction.conn())
+        .await?;
+
+        transaction
+            .tree_recovery_progress_dal()
+            .update_summary(l1_batch_number, total_chunk_count)
+            .await?;
+        transaction.commit().await
+    }
+
+    async fn update_summary(
+        &mut self,
+        l1_batch_number: L1BatchNumber,
+        total_chunk_count: u64,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO
+                tree_recovery_summary (
+                    l1_batch_number,
+                    total_chunk_count,
+                    recovered_chunk_count,
+                    recovered_entry_count,
+                    created_at,
+                    updated_at
+                )
+            SELECT
+                $1,
+                $2,
+                COUNT(*),
+                COALESCE(SUM(entry_count), 0),
+                NOW(),
+                NOW()
+            FROM
+                tree_recovery_progress
+            WHERE
+                l1_batch_number = $1
+            ON CONFLICT (l1_batch_number) DO
+            UPDATE
+            SET
+                total_chunk_count = excluded.total_chunk_count,
+                recovered_chunk_count = excluded.recovered_chunk_count,
+                recovered_entry_count = excluded.recovered_entry_count,
+                updated_at = excluded.updated_at
+            "#,
+            l1_batch_number.0 as i64,
+            total_chunk_count as i32,
+        )
+        .execute(self.storage.conn())
+        .await?;
+        Ok(())
+    }
+
+    /// Marks recovery to the specified snapshot L1 batch as finished.
+    pub async fn mark_recovery_finished(
+        &mut self,
+        l1_batch_number: L1BatchNumber,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE tree_recovery_summary
+            SET
+                finished_at = NOW(),
+                updated_at = NOW()
+            WHERE
+                l1_batch_number = $1
+            "#,
+            l1_batch_number.0 as i64,
+        )
+        .execute(self.storage.conn())
+        .await?;
+        Ok(())
+    }
+
+    /// Returns recovered chunks for the specified snapshot L1 batch ordered by chunk ID.
+    pub async fn get_recovered_chunks(
+        &mut self,
+        l1_batch_number: L1BatchNumber,
+    ) -> sqlx::Result<Vec<RecoveredTreeChunk>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                chunk_id,
+                start_key,
+                end_key,
+                entry_count
+            FROM
+                tree_recovery_progress
+            WHERE
+                l1_batch_number = $1
+            ORDER BY
+                chunk_id
+            "#,
+            l1_batch_number.0 as i64,
+        )
+        .fetch_all(self.storage.conn())
+        .await?;
+
+        let chunks = rows.into_iter().map(|row| RecoveredTreeChunk {
+            chunk_id: row.chunk_id as u64,
+            key_range: H256::from_slice(&row.start_key)..=H256::from_slice(&row.end_key),
+            entry_count: row.entry_count.map(|count| count as u64),
+        });
+        Ok(chunks.collect())
+    }
+
+    /// Returns the recovery summary for the specified snapshot L1 batch.
+    pub async fn get_summary(
+        &mut self,
+        l1_batch_number: L1BatchNumber,
+    ) -> sqlx::Result<Option<TreeRecoverySummary>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                total_chunk_count,
+                recovered_chunk_count,
+                recovered_entry_count,
+                finished_at IS NOT NULL AS "is_finished!"
+            FROM
+                tree_recovery_summary
+            WHERE
+                l1_batch_number = $1
+            "#,
+            l1_batch_number.0 as i64,
+        )
+        .fetch_optional(self.storage.conn())
+        .await?;
+
+        Ok(row.map(|row| TreeRecoverySummary {
+            l1_batch_number,
+            total_chunk_count: row.total_chunk_count as u64,
+            recovered_chunk_count: row.recovered_chunk_count as u64,
+            recovered_entry_count: row.recovered_entry_count as u64,
+            is_finished: row.is_finished,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConnectionPool;
+
+    fn key_range(chunk_id: u8) -> ops::RangeInclusive<H256> {
+        H256::repeat_byte(chunk_id)..=H256::repeat_byte(chunk_id + 1)
+    }
+
+    #[tokio::test]
+    async fn recording_tree_recovery_progress() {
+        let pool = ConnectionPool::test_pool().await;
+        let mut conn = pool.access_storage().await.unwrap();
+        let mut dal = conn.tree_recovery_progress_dal();
+        let l1_batch_number = L1BatchNumber(5);
+        assert_eq!(dal.get_summary(l1_batch_number).await.unwrap(), None);
+
+        let chunks: Vec<_> = (0..3)
+            .map(|i| RecoveredTreeChunk {
+                chunk_id: i.into(),
+                key_range: key_range(i),
+                entry_count: Some(u64::from(i) * 10 + 1),
+            })
+            .collect();
+        dal.insert_recovered_chunks(l1_batch_number, 4, &chunks[..2])
+            .await
+            .unwrap();
+        dal.insert_recovered_chunks(l1_batch_number, 4, &chunks[2..])
+            .await
+            .unwrap();
+        assert_eq!(
+            dal.get_recovered_chunks(l1_batch_number).await.unwrap(),
+            chunks
+        );
+        let summary = dal.get_summary(l1_batch_number).await.unwrap().unwrap();
+        assert_eq!(
+            summary,
+            TreeRecoverySummary {
+                l1_batch_number,
+                total_chunk_count: 4,
+                recovered_chunk_count: 3,
+                recovered_entry_count: 1 + 11 + 21,
+                is_finished: false,
+            }
+        );
+
+        dal.mark_recovery_finished(l1_batch_number).await.unwrap();
+        let summary = dal.get_summary(l1_batch_number).await.unwrap().unwrap();
+        assert!(summary.is_finished);
+    }
+
+    #[tokio::test]
+    async fn reconciling_tree_recovery_progress() {
+        let pool = ConnectionPool::test_pool().await;
+        let mut conn = pool.access_storage().await.unwrap();
+        let mut dal = conn.tree_recovery_progress_dal();
+        let stale_chunk = RecoveredTreeChunk {
+            chunk_id: 0,
+            key_range: key_range(0),
+            entry_count: Some(100),
+        };
+        dal.insert_recovered_chunks(L1BatchNumber(1), 2, &[stale_chunk])
+            .await
+            .unwrap();
+
+        let l1_batch_number = L1BatchNumber(5);
+        let chunks: Vec<_> = (0..2)
+            .map(|i| RecoveredTreeChunk {
+                chunk_id: i.into(),
+                key_range: key_range(i),
+                entry_count: Some(10),
+            })
+            .collect();
+        dal.insert_recovered_chunks(l1_batch_number, 4, &chunks)
+            .await
+            .unwrap();
+
+        // Chunk #0 turned out not to be recovered; chunk #3 was recovered without being recorded.
+        let recovered_chunks = [(1, key_range(1)), (3, key_range(3))];
+        dal.reconcile_recovered_chunks(l1_batch_number, 4, &recovered_chunks)
+            .await
+            .unwrap();
+        assert_eq!(dal.get_summary(L1BatchNumber(1)).await.unwrap(), None);
+        assert!(dal
+            .get_recovered_chunks(L1BatchNumber(1))
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            dal.get_recovered_chunks(l1_batch_number).await.unwrap(),
+            [
+                chunks[1].clone(),
+                RecoveredTreeChunk {
+                    chunk_id: 3,
+                    key_range: key_range(3),
+                    entry_count: None,
+                }
+            ]
+        );
+        let summary = dal.get_summary(l1_batch_number).await.unwrap().unwrap();
+        assert_eq!(summary.recovered_chunk_count, 2);
+        assert_eq!(summary.recovered_entry_count, 10);
+    }
+}
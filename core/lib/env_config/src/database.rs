@@ -1,14 +1,25 @@
 use std::env;
 
 use anyhow::Context as _;
-use zksync_config::{DBConfig, PostgresConfig};
+use zksync_config::{configs::database::MerkleTreeConfig, DBConfig, PostgresConfig};
 
 use crate::{envy_load, FromEnv};
 
 impl FromEnv for DBConfig {
     fn from_env() -> anyhow::Result<Self> {
+        let merkle_tree = MerkleTreeConfig {
+            recovery: envy_load(
+                "database_merkle_tree_recovery",
+                "DATABASE_MERKLE_TREE_RECOVERY_",
+            )?,
+            ..envy_load("database_merkle_tree", "DATABASE_MERKLE_TREE_")?
+        };
+        merkle_tree
+            .recovery
+            .validate()
+            .context("invalid Merkle tree recovery config")?;
         Ok(Self {
-            merkle_tree: envy_load("database_merkle_tree", "DATABASE_MERKLE_TREE_")?,
+            merkle_tree,
             ..envy_load("database", "DATABASE_")?
         })
     }
@@ -63,8 +74,73 @@ mod tests {
             DATABASE_MERKLE_TREE_MODE=lightweight
             DATABASE_MERKLE_TREE_MULTI_GET_CHUNK_SIZE=250
             DATABASE_MERKLE_TREE_MEMTABLE_CAPACITY_MB=512
+            DATABASE_MERKLE_TREE_TOTAL_WRITE_BUFFER_SIZE_MB=2048
+            DATABASE_MERKLE_TREE_MAX_OPEN_FILES=1000
             DATABASE_MERKLE_TREE_STALLED_WRITES_TIMEOUT_SEC=60
             DATABASE_MERKLE_TREE_MAX_L1_BATCHES_PER_ITER=50
+            DATABASE_MERKLE_TREE_TREE_READY_LAG_BATCHES=10
+            DATABASE_MERKLE_TREE_RECOVERY_DESIRED_CHUNK_SIZE=50000
+            DATABASE_MERKLE_TREE_RECOVERY_MAX_CHUNK_ATTEMPTS=3
+            DATABASE_MERKLE_TREE_RECOVERY_CHUNK_RETRY_INITIAL_DELAY_MS=100
+            DATABASE_MERKLE_TREE_RECOVERY_CHUNK_RETRY_MAX_DELAY_MS=10000
+            DATABASE_MERKLE_TREE_RECOVERY_CONCURRENCY=4
+            DATABASE_MERKLE_TREE_RECOVERY_MIN_CONCURRENCY=2
+            DATABASE_MERKLE_TREE_RECOVERY_CONCURRENCY_OVERRIDE_PATH="/db/tree_concurrency"
+            DATABASE_MERKLE_TREE_RECOVERY_SLOW_CHUNK_THRESHOLD_MS=5000
+            DATABASE_MERKLE_TREE_RECOVERY_SUB_CHUNK_SIZE=10000
+            DATABASE_MERKLE_TREE_RECOVERY_STREAMING_BATCH_SIZE=5000
+            DATABASE_MERKLE_TREE_RECOVERY_HASHING_THREADS=4
+            DATABASE_MERKLE_TREE_RECOVERY_LOADED_ENTRIES_SOFT_CAP_MB=512
+            DATABASE_MERKLE_TREE_RECOVERY_TARGET_L1_BATCH=123
+            DATABASE_MERKLE_TREE_RECOVERY_PRIORITIZE_LARGE_CHUNKS=true
+            DATABASE_MERKLE_TREE_RECOVERY_FORCE_REPLAN=true
+            DATABASE_MERKLE_TREE_RECOVERY_ALLOW_TREE_RESET_ON_REGENESIS=true
+            DATABASE_MERKLE_TREE_RECOVERY_ALLOW_TREE_RESET=true
+            DATABASE_MERKLE_TREE_RECOVERY_ESTIMATED_BYTES_PER_ENTRY=2000
+            DATABASE_MERKLE_TREE_RECOVERY_DISK_SPACE_MARGIN_MB=1024
+            DATABASE_MERKLE_TREE_RECOVERY_STRICT_DISK_SPACE_CHECK=true
+            DATABASE_MERKLE_TREE_RECOVERY_VERIFICATION_SAMPLES_PER_CHUNK=10
+            DATABASE_MERKLE_TREE_RECOVERY_PROOF_VERIFICATION_SAMPLES=100
+            DATABASE_MERKLE_TREE_RECOVERY_FINGERPRINT_LOG=true
+            DATABASE_MERKLE_TREE_RECOVERY_CHUNK_FILTER_BATCH_SIZE=500
+            DATABASE_MERKLE_TREE_RECOVERY_POOL_SIZE=4
+            DATABASE_MERKLE_TREE_RECOVERY_STATEMENT_TIMEOUT_SEC=3600
+            DATABASE_MERKLE_TREE_RECOVERY_USE_REPLICA=true
+            DATABASE_MERKLE_TREE_RECOVERY_PRUNING_RETAINED_VERSIONS=1000
+            DATABASE_MERKLE_TREE_RECOVERY_ALLOW_LIGHTWEIGHT_TREE_UPGRADE=true
+            DATABASE_MERKLE_TREE_RECOVERY_MISMATCH_DIAGNOSTIC_KEYS_PER_CHUNK=5
+            DATABASE_MERKLE_TREE_RECOVERY_EXPORT_PATH="/db/tree_export"
+            DATABASE_MERKLE_TREE_RECOVERY_IMPORT_PATH="/db/tree_import"
+            DATABASE_MERKLE_TREE_RECOVERY_STRICT_IMPORT=true
+            DATABASE_MERKLE_TREE_RECOVERY_RECOVERY_FILE="/db/tree_snapshot.bin"
+            DATABASE_MERKLE_TREE_RECOVERY_DRY_RUN=true
+            DATABASE_MERKLE_TREE_RECOVERY_VERIFY_INTEGRITY=true
+            DATABASE_MERKLE_TREE_RECOVERY_STOP_AFTER_RECOVERY=true
+            DATABASE_MERKLE_TREE_RECOVERY_CATCH_UP_BEFORE_READY=true
+            DATABASE_MERKLE_TREE_RECOVERY_EXPERIMENTAL_SKIP_ROOT_HASH_CHECK=true
+            DATABASE_MERKLE_TREE_RECOVERY_EXPERIMENTAL_ALLOW_TAINTED_TREE=true
+            DATABASE_MERKLE_TREE_RECOVERY_HEALTH_UPDATE_INTERVAL_MS=500
+            DATABASE_MERKLE_TREE_RECOVERY_STALL_CHECK_INTERVAL_MS=10000
+            DATABASE_MERKLE_TREE_RECOVERY_STALL_THRESHOLD_MS=120000
+            DATABASE_MERKLE_TREE_RECOVERY_CONNECTION_RETRY_TIMEOUT_MS=30000
+            DATABASE_MERKLE_TREE_RECOVERY_CONNECTION_ACQUIRE_TIMEOUT_MS=10000
+            DATABASE_MERKLE_TREE_RECOVERY_USE_COPY=true
+            DATABASE_MERKLE_TREE_RECOVERY_SNAPSHOT_POLL_INTERVAL_MS=5000
+            DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_MEMTABLE_CAPACITY_MB=1024
+            DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_MAX_WRITE_BUFFERS=8
+            DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_BLOCK_CACHE_SIZE_MB=32
+            DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_TOTAL_WRITE_BUFFER_SIZE_MB=8192
+            DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_MAX_OPEN_FILES=5000
+            DATABASE_MERKLE_TREE_RECOVERY_RELAXED_DURABILITY=true
+            DATABASE_MERKLE_TREE_RECOVERY_CHECK_CHUNK_ENDS=true
+            DATABASE_MERKLE_TREE_RECOVERY_REPAIR_MISMATCHED_CHUNKS=true
+            DATABASE_MERKLE_TREE_RECOVERY_FLUSH_INTERVAL_CHUNKS=10
+            DATABASE_MERKLE_TREE_RECOVERY_FLUSH_INTERVAL_MB=256
+            DATABASE_MERKLE_TREE_RECOVERY_BACKGROUND_WRITE_RATE_LIMIT_MB=50
+            DATABASE_MERKLE_TREE_RECOVERY_BACKGROUND_WRITE_RATE_LIMIT_OVERRIDE_PATH="/db/tree_rate_limit"
+            DATABASE_MERKLE_TREE_RECOVERY_MAX_ENTRIES_PER_SECOND=100000
+            DATABASE_MERKLE_TREE_RECOVERY_MAX_RECOVERY_DURATION_SEC=86400
+            DATABASE_MERKLE_TREE_RECOVERY_LATEST_SNAPSHOT_CHECK_INTERVAL_MS=30000
         "#;
         lock.set_env(config);
 
@@ -74,8 +150,221 @@ mod tests {
         assert_eq!(db_config.merkle_tree.mode, MerkleTreeMode::Lightweight);
         assert_eq!(db_config.merkle_tree.multi_get_chunk_size, 250);
         assert_eq!(db_config.merkle_tree.max_l1_batches_per_iter, 50);
+        assert_eq!(db_config.merkle_tree.tree_ready_lag_batches, Some(10));
         assert_eq!(db_config.merkle_tree.memtable_capacity_mb, 512);
+        assert_eq!(
+            db_config.merkle_tree.total_write_buffer_size_mb,
+            Some(2_048)
+        );
+        assert_eq!(
+            db_config.merkle_tree.total_write_buffer_size(),
+            Some(2 << 30)
+        );
+        assert_eq!(db_config.merkle_tree.max_open_files, Some(1_000));
         assert_eq!(db_config.merkle_tree.stalled_writes_timeout_sec, 60);
+        assert_eq!(db_config.merkle_tree.recovery.desired_chunk_size, 50_000);
+        assert_eq!(db_config.merkle_tree.recovery.max_chunk_attempts, 3);
+        assert_eq!(
+            db_config.merkle_tree.recovery.chunk_retry_initial_delay_ms,
+            100
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.chunk_retry_max_delay_ms,
+            10_000
+        );
+        assert_eq!(db_config.merkle_tree.recovery.concurrency, Some(4));
+        assert_eq!(db_config.merkle_tree.recovery.min_concurrency, Some(2));
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .concurrency_override_path
+                .as_deref(),
+            Some("/db/tree_concurrency")
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.slow_chunk_threshold_ms,
+            5_000
+        );
+        assert_eq!(db_config.merkle_tree.recovery.sub_chunk_size, Some(10_000));
+        assert_eq!(
+            db_config.merkle_tree.recovery.streaming_batch_size,
+            Some(5_000)
+        );
+        assert_eq!(db_config.merkle_tree.recovery.hashing_threads, Some(4));
+        assert_eq!(
+            db_config.merkle_tree.recovery.loaded_entries_soft_cap(),
+            Some(512 << 20)
+        );
+        assert_eq!(db_config.merkle_tree.recovery.target_l1_batch, Some(123));
+        assert!(db_config.merkle_tree.recovery.prioritize_large_chunks);
+        assert!(db_config.merkle_tree.recovery.force_replan);
+        assert!(db_config.merkle_tree.recovery.allow_tree_reset_on_regenesis);
+        assert!(db_config.merkle_tree.recovery.allow_tree_reset);
+        assert_eq!(
+            db_config.merkle_tree.recovery.estimated_bytes_per_entry,
+            2_000
+        );
+        assert_eq!(db_config.merkle_tree.recovery.disk_space_margin_mb, 1_024);
+        assert!(db_config.merkle_tree.recovery.strict_disk_space_check);
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .verification_samples_per_chunk,
+            Some(10)
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.proof_verification_samples,
+            Some(100)
+        );
+        assert!(db_config.merkle_tree.recovery.fingerprint_log);
+        assert_eq!(db_config.merkle_tree.recovery.chunk_filter_batch_size, 500);
+        assert_eq!(db_config.merkle_tree.recovery.pool_size, Some(4));
+        assert_eq!(
+            db_config.merkle_tree.recovery.statement_timeout_sec,
+            Some(3_600)
+        );
+        assert!(db_config.merkle_tree.recovery.use_replica);
+        assert_eq!(
+            db_config.merkle_tree.recovery.pruning_retained_versions,
+            Some(1_000)
+        );
+        assert!(
+            db_config
+                .merkle_tree
+                .recovery
+                .allow_lightweight_tree_upgrade
+        );
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .mismatch_diagnostic_keys_per_chunk,
+            Some(5)
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.export_path.as_deref(),
+            Some("/db/tree_export")
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.import_path.as_deref(),
+            Some("/db/tree_import")
+        );
+        assert!(db_config.merkle_tree.recovery.strict_import);
+        assert_eq!(
+            db_config.merkle_tree.recovery.recovery_file.as_deref(),
+            Some("/db/tree_snapshot.bin")
+        );
+        assert!(db_config.merkle_tree.recovery.dry_run);
+        assert!(!db_config.merkle_tree.recovery.stop_after_dry_run);
+        assert!(db_config.merkle_tree.recovery.verify_integrity);
+        assert!(db_config.merkle_tree.recovery.stop_after_recovery);
+        assert!(db_config.merkle_tree.recovery.catch_up_before_ready);
+        assert!(
+            db_config
+                .merkle_tree
+                .recovery
+                .experimental_skip_root_hash_check
+        );
+        assert!(
+            db_config
+                .merkle_tree
+                .recovery
+                .experimental_allow_tainted_tree
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.health_update_interval_ms,
+            500
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.stall_check_interval_ms,
+            10_000
+        );
+        assert_eq!(db_config.merkle_tree.recovery.stall_threshold_ms, 120_000);
+        assert_eq!(
+            db_config.merkle_tree.recovery.connection_retry_timeout_ms,
+            30_000
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.connection_acquire_timeout_ms,
+            10_000
+        );
+        assert!(db_config.merkle_tree.recovery.use_copy);
+        assert_eq!(
+            db_config.merkle_tree.recovery.snapshot_poll_interval_ms,
+            5_000
+        );
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .bulk_load_memtable_capacity_mb,
+            Some(1_024)
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.bulk_load_memtable_capacity(),
+            Some(1 << 30)
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.bulk_load_max_write_buffers,
+            8
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.bulk_load_block_cache_size(),
+            Some(32 << 20)
+        );
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .bulk_load_total_write_buffer_size(),
+            Some(8 << 30)
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.bulk_load_max_open_files,
+            Some(5_000)
+        );
+        assert!(db_config.merkle_tree.recovery.relaxed_durability);
+        assert!(db_config.merkle_tree.recovery.check_chunk_ends);
+        assert!(db_config.merkle_tree.recovery.repair_mismatched_chunks);
+        assert_eq!(
+            db_config.merkle_tree.recovery.flush_interval_chunks,
+            Some(10)
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.flush_interval_bytes(),
+            Some(256 << 20)
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.background_write_rate_limit(),
+            Some(50 << 20)
+        );
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .background_write_rate_limit_override_path
+                .as_deref(),
+            Some("/db/tree_rate_limit")
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.max_entries_per_second,
+            Some(100_000)
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.max_recovery_duration_sec,
+            Some(86_400)
+        );
+        // `prefer_latest_snapshot` cannot be set together with the target L1 batch.
+        assert!(!db_config.merkle_tree.recovery.prefer_latest_snapshot);
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .latest_snapshot_check_interval_ms,
+            30_000
+        );
     }
 
     #[test]
@@ -89,8 +378,75 @@ mod tests {
             "DATABASE_MERKLE_TREE_MULTI_GET_CHUNK_SIZE",
             "DATABASE_MERKLE_TREE_BLOCK_CACHE_SIZE_MB",
             "DATABASE_MERKLE_TREE_MEMTABLE_CAPACITY_MB",
+            "DATABASE_MERKLE_TREE_TOTAL_WRITE_BUFFER_SIZE_MB",
+            "DATABASE_MERKLE_TREE_MAX_OPEN_FILES",
             "DATABASE_MERKLE_TREE_STALLED_WRITES_TIMEOUT_SEC",
             "DATABASE_MERKLE_TREE_MAX_L1_BATCHES_PER_ITER",
+            "DATABASE_MERKLE_TREE_TREE_READY_LAG_BATCHES",
+            "DATABASE_MERKLE_TREE_RECOVERY_DESIRED_CHUNK_SIZE",
+            "DATABASE_MERKLE_TREE_RECOVERY_MAX_CHUNK_ATTEMPTS",
+            "DATABASE_MERKLE_TREE_RECOVERY_CHUNK_RETRY_INITIAL_DELAY_MS",
+            "DATABASE_MERKLE_TREE_RECOVERY_CHUNK_RETRY_MAX_DELAY_MS",
+            "DATABASE_MERKLE_TREE_RECOVERY_CONCURRENCY",
+            "DATABASE_MERKLE_TREE_RECOVERY_MIN_CONCURRENCY",
+            "DATABASE_MERKLE_TREE_RECOVERY_CONCURRENCY_OVERRIDE_PATH",
+            "DATABASE_MERKLE_TREE_RECOVERY_SLOW_CHUNK_THRESHOLD_MS",
+            "DATABASE_MERKLE_TREE_RECOVERY_SUB_CHUNK_SIZE",
+            "DATABASE_MERKLE_TREE_RECOVERY_STREAMING_BATCH_SIZE",
+            "DATABASE_MERKLE_TREE_RECOVERY_HASHING_THREADS",
+            "DATABASE_MERKLE_TREE_RECOVERY_LOADED_ENTRIES_SOFT_CAP_MB",
+            "DATABASE_MERKLE_TREE_RECOVERY_TARGET_L1_BATCH",
+            "DATABASE_MERKLE_TREE_RECOVERY_PRIORITIZE_LARGE_CHUNKS",
+            "DATABASE_MERKLE_TREE_RECOVERY_FORCE_REPLAN",
+            "DATABASE_MERKLE_TREE_RECOVERY_ALLOW_TREE_RESET_ON_REGENESIS",
+            "DATABASE_MERKLE_TREE_RECOVERY_ALLOW_TREE_RESET",
+            "DATABASE_MERKLE_TREE_RECOVERY_ESTIMATED_BYTES_PER_ENTRY",
+            "DATABASE_MERKLE_TREE_RECOVERY_DISK_SPACE_MARGIN_MB",
+            "DATABASE_MERKLE_TREE_RECOVERY_STRICT_DISK_SPACE_CHECK",
+            "DATABASE_MERKLE_TREE_RECOVERY_VERIFICATION_SAMPLES_PER_CHUNK",
+            "DATABASE_MERKLE_TREE_RECOVERY_PROOF_VERIFICATION_SAMPLES",
+            "DATABASE_MERKLE_TREE_RECOVERY_FINGERPRINT_LOG",
+            "DATABASE_MERKLE_TREE_RECOVERY_CHUNK_FILTER_BATCH_SIZE",
+            "DATABASE_MERKLE_TREE_RECOVERY_POOL_SIZE",
+            "DATABASE_MERKLE_TREE_RECOVERY_STATEMENT_TIMEOUT_SEC",
+            "DATABASE_MERKLE_TREE_RECOVERY_USE_REPLICA",
+            "DATABASE_MERKLE_TREE_RECOVERY_PRUNING_RETAINED_VERSIONS",
+            "DATABASE_MERKLE_TREE_RECOVERY_ALLOW_LIGHTWEIGHT_TREE_UPGRADE",
+            "DATABASE_MERKLE_TREE_RECOVERY_MISMATCH_DIAGNOSTIC_KEYS_PER_CHUNK",
+            "DATABASE_MERKLE_TREE_RECOVERY_EXPORT_PATH",
+            "DATABASE_MERKLE_TREE_RECOVERY_IMPORT_PATH",
+            "DATABASE_MERKLE_TREE_RECOVERY_STRICT_IMPORT",
+            "DATABASE_MERKLE_TREE_RECOVERY_RECOVERY_FILE",
+            "DATABASE_MERKLE_TREE_RECOVERY_DRY_RUN",
+            "DATABASE_MERKLE_TREE_RECOVERY_STOP_AFTER_DRY_RUN",
+            "DATABASE_MERKLE_TREE_RECOVERY_VERIFY_INTEGRITY",
+            "DATABASE_MERKLE_TREE_RECOVERY_STOP_AFTER_RECOVERY",
+            "DATABASE_MERKLE_TREE_RECOVERY_CATCH_UP_BEFORE_READY",
+            "DATABASE_MERKLE_TREE_RECOVERY_EXPERIMENTAL_SKIP_ROOT_HASH_CHECK",
+            "DATABASE_MERKLE_TREE_RECOVERY_EXPERIMENTAL_ALLOW_TAINTED_TREE",
+            "DATABASE_MERKLE_TREE_RECOVERY_HEALTH_UPDATE_INTERVAL_MS",
+            "DATABASE_MERKLE_TREE_RECOVERY_STALL_CHECK_INTERVAL_MS",
+            "DATABASE_MERKLE_TREE_RECOVERY_STALL_THRESHOLD_MS",
+            "DATABASE_MERKLE_TREE_RECOVERY_CONNECTION_RETRY_TIMEOUT_MS",
+            "DATABASE_MERKLE_TREE_RECOVERY_CONNECTION_ACQUIRE_TIMEOUT_MS",
+            "DATABASE_MERKLE_TREE_RECOVERY_USE_COPY",
+            "DATABASE_MERKLE_TREE_RECOVERY_SNAPSHOT_POLL_INTERVAL_MS",
+            "DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_MEMTABLE_CAPACITY_MB",
+            "DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_MAX_WRITE_BUFFERS",
+            "DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_BLOCK_CACHE_SIZE_MB",
+            "DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_TOTAL_WRITE_BUFFER_SIZE_MB",
+            "DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_MAX_OPEN_FILES",
+            "DATABASE_MERKLE_TREE_RECOVERY_RELAXED_DURABILITY",
+            "DATABASE_MERKLE_TREE_RECOVERY_CHECK_CHUNK_ENDS",
+            "DATABASE_MERKLE_TREE_RECOVERY_REPAIR_MISMATCHED_CHUNKS",
+            "DATABASE_MERKLE_TREE_RECOVERY_FLUSH_INTERVAL_CHUNKS",
+            "DATABASE_MERKLE_TREE_RECOVERY_FLUSH_INTERVAL_MB",
+            "DATABASE_MERKLE_TREE_RECOVERY_BACKGROUND_WRITE_RATE_LIMIT_MB",
+            "DATABASE_MERKLE_TREE_RECOVERY_BACKGROUND_WRITE_RATE_LIMIT_OVERRIDE_PATH",
+            "DATABASE_MERKLE_TREE_RECOVERY_MAX_ENTRIES_PER_SECOND",
+            "DATABASE_MERKLE_TREE_RECOVERY_MAX_RECOVERY_DURATION_SEC",
+            "DATABASE_MERKLE_TREE_RECOVERY_PREFER_LATEST_SNAPSHOT",
+            "DATABASE_MERKLE_TREE_RECOVERY_LATEST_SNAPSHOT_CHECK_INTERVAL_MS",
         ]);
 
         let db_config = DBConfig::from_env().unwrap();
@@ -99,9 +455,186 @@ mod tests {
         assert_eq!(db_config.merkle_tree.mode, MerkleTreeMode::Full);
         assert_eq!(db_config.merkle_tree.multi_get_chunk_size, 500);
         assert_eq!(db_config.merkle_tree.max_l1_batches_per_iter, 20);
+        assert_eq!(db_config.merkle_tree.tree_ready_lag_batches, None);
         assert_eq!(db_config.merkle_tree.block_cache_size_mb, 128);
         assert_eq!(db_config.merkle_tree.memtable_capacity_mb, 256);
+        assert_eq!(db_config.merkle_tree.total_write_buffer_size_mb, None);
+        assert_eq!(db_config.merkle_tree.max_open_files, None);
         assert_eq!(db_config.merkle_tree.stalled_writes_timeout_sec, 30);
+        assert_eq!(db_config.merkle_tree.recovery.desired_chunk_size, 200_000);
+        assert_eq!(db_config.merkle_tree.recovery.max_chunk_attempts, 5);
+        assert_eq!(
+            db_config.merkle_tree.recovery.chunk_retry_initial_delay_ms,
+            500
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.chunk_retry_max_delay_ms,
+            30_000
+        );
+        assert_eq!(db_config.merkle_tree.recovery.concurrency, None);
+        assert_eq!(db_config.merkle_tree.recovery.min_concurrency, None);
+        assert_eq!(
+            db_config.merkle_tree.recovery.concurrency_override_path,
+            None
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.slow_chunk_threshold_ms,
+            10_000
+        );
+        assert_eq!(db_config.merkle_tree.recovery.sub_chunk_size, None);
+        assert_eq!(db_config.merkle_tree.recovery.streaming_batch_size, None);
+        assert_eq!(db_config.merkle_tree.recovery.hashing_threads, None);
+        assert_eq!(
+            db_config.merkle_tree.recovery.loaded_entries_soft_cap_mb,
+            None
+        );
+        assert_eq!(db_config.merkle_tree.recovery.target_l1_batch, None);
+        assert!(!db_config.merkle_tree.recovery.prioritize_large_chunks);
+        assert!(!db_config.merkle_tree.recovery.force_replan);
+        assert!(!db_config.merkle_tree.recovery.allow_tree_reset_on_regenesis);
+        assert!(!db_config.merkle_tree.recovery.allow_tree_reset);
+        assert_eq!(
+            db_config.merkle_tree.recovery.estimated_bytes_per_entry,
+            1_500
+        );
+        assert_eq!(db_config.merkle_tree.recovery.disk_space_margin_mb, 10_240);
+        assert!(!db_config.merkle_tree.recovery.strict_disk_space_check);
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .verification_samples_per_chunk,
+            None
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.proof_verification_samples,
+            None
+        );
+        assert!(!db_config.merkle_tree.recovery.fingerprint_log);
+        assert_eq!(
+            db_config.merkle_tree.recovery.chunk_filter_batch_size,
+            1_000
+        );
+        assert_eq!(db_config.merkle_tree.recovery.pool_size, None);
+        assert_eq!(db_config.merkle_tree.recovery.statement_timeout_sec, None);
+        assert!(!db_config.merkle_tree.recovery.use_replica);
+        assert_eq!(
+            db_config.merkle_tree.recovery.pruning_retained_versions,
+            None
+        );
+        assert!(
+            !db_config
+                .merkle_tree
+                .recovery
+                .allow_lightweight_tree_upgrade
+        );
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .mismatch_diagnostic_keys_per_chunk,
+            None
+        );
+        assert_eq!(db_config.merkle_tree.recovery.export_path, None);
+        assert_eq!(db_config.merkle_tree.recovery.import_path, None);
+        assert!(!db_config.merkle_tree.recovery.strict_import);
+        assert_eq!(db_config.merkle_tree.recovery.recovery_file, None);
+        assert!(!db_config.merkle_tree.recovery.dry_run);
+        assert!(!db_config.merkle_tree.recovery.verify_integrity);
+        assert!(!db_config.merkle_tree.recovery.stop_after_recovery);
+        assert!(!db_config.merkle_tree.recovery.catch_up_before_ready);
+        assert!(
+            !db_config
+                .merkle_tree
+                .recovery
+                .experimental_skip_root_hash_check
+        );
+        assert!(
+            !db_config
+                .merkle_tree
+                .recovery
+                .experimental_allow_tainted_tree
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.health_update_interval_ms,
+            1_000
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.stall_check_interval_ms,
+            60_000
+        );
+        assert_eq!(db_config.merkle_tree.recovery.stall_threshold_ms, 300_000);
+        assert_eq!(
+            db_config.merkle_tree.recovery.connection_retry_timeout_ms,
+            60_000
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.connection_acquire_timeout_ms,
+            30_000
+        );
+        assert!(!db_config.merkle_tree.recovery.use_copy);
+        assert_eq!(
+            db_config.merkle_tree.recovery.snapshot_poll_interval_ms,
+            1_000
+        );
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .bulk_load_memtable_capacity_mb,
+            None
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.bulk_load_max_write_buffers,
+            6
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.bulk_load_block_cache_size_mb,
+            None
+        );
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .bulk_load_total_write_buffer_size_mb,
+            None
+        );
+        assert_eq!(
+            db_config.merkle_tree.recovery.bulk_load_max_open_files,
+            None
+        );
+        assert!(!db_config.merkle_tree.recovery.relaxed_durability);
+        assert!(!db_config.merkle_tree.recovery.check_chunk_ends);
+        assert!(!db_config.merkle_tree.recovery.repair_mismatched_chunks);
+        assert_eq!(db_config.merkle_tree.recovery.flush_interval_chunks, None);
+        assert_eq!(db_config.merkle_tree.recovery.flush_interval_mb, None);
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .background_write_rate_limit_mb,
+            None
+        );
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .background_write_rate_limit_override_path,
+            None
+        );
+        assert_eq!(db_config.merkle_tree.recovery.max_entries_per_second, None);
+        assert_eq!(
+            db_config.merkle_tree.recovery.max_recovery_duration_sec,
+            None
+        );
+        assert!(!db_config.merkle_tree.recovery.prefer_latest_snapshot);
+        assert_eq!(
+            db_config
+                .merkle_tree
+                .recovery
+                .latest_snapshot_check_interval_ms,
+            60_000
+        );
 
         // Check that new env variable for Merkle tree path is supported
         lock.set_env("DATABASE_MERKLE_TREE_PATH=/db/tree/main");
@@ -120,4 +653,111 @@ mod tests {
         let db_config = DBConfig::from_env().unwrap();
         assert_eq!(db_config.merkle_tree.max_l1_batches_per_iter, 50);
     }
+
+    #[test]
+    fn from_env_with_invalid_recovery_config() {
+        let invalid_configs = [
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_DESIRED_CHUNK_SIZE=0",
+                "`desired_chunk_size` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_MAX_CHUNK_ATTEMPTS=0",
+                "`max_chunk_attempts` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_CHUNK_RETRY_INITIAL_DELAY_MS=60000",
+                "must not exceed `chunk_retry_max_delay_ms`",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_CONCURRENCY=0",
+                "`concurrency` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_CONCURRENCY=8\n\
+                 DATABASE_MERKLE_TREE_RECOVERY_POOL_SIZE=4",
+                "must not exceed the size of the recovery connection pool",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_CONCURRENCY=2\n\
+                 DATABASE_MERKLE_TREE_RECOVERY_MIN_CONCURRENCY=4",
+                "`min_concurrency` (4) must not exceed `concurrency` (2)",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_STREAMING_BATCH_SIZE=0",
+                "`streaming_batch_size` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_CONNECTION_ACQUIRE_TIMEOUT_MS=0",
+                "`connection_acquire_timeout_ms` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_SNAPSHOT_POLL_INTERVAL_MS=0",
+                "`snapshot_poll_interval_ms` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_MEMTABLE_CAPACITY_MB=0",
+                "`bulk_load_memtable_capacity_mb` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_MAX_WRITE_BUFFERS=1",
+                "`bulk_load_max_write_buffers` must be at least 2",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_TOTAL_WRITE_BUFFER_SIZE_MB=0",
+                "`bulk_load_total_write_buffer_size_mb` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_BULK_LOAD_MAX_OPEN_FILES=0",
+                "`bulk_load_max_open_files` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_BACKGROUND_WRITE_RATE_LIMIT_MB=0",
+                "`background_write_rate_limit_mb` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_FLUSH_INTERVAL_CHUNKS=0",
+                "`flush_interval_chunks` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_FLUSH_INTERVAL_MB=0",
+                "`flush_interval_mb` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_MAX_ENTRIES_PER_SECOND=0",
+                "`max_entries_per_second` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_MAX_RECOVERY_DURATION_SEC=0",
+                "`max_recovery_duration_sec` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_LATEST_SNAPSHOT_CHECK_INTERVAL_MS=0",
+                "`latest_snapshot_check_interval_ms` must be positive",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_PREFER_LATEST_SNAPSHOT=true\n\
+                 DATABASE_MERKLE_TREE_RECOVERY_TARGET_L1_BATCH=5",
+                "`prefer_latest_snapshot` cannot be used together with `target_l1_batch`",
+            ),
+            (
+                "DATABASE_MERKLE_TREE_RECOVERY_STOP_AFTER_DRY_RUN=true",
+                "`stop_after_dry_run` requires `dry_run`",
+            ),
+        ];
+
+        for (config, expected_message) in invalid_configs {
+            let mut lock = MUTEX.lock();
+            lock.set_env(config);
+            let err = DBConfig::from_env().unwrap_err();
+            let err = format!("{err:#}");
+            assert!(err.contains("invalid Merkle tree recovery config"), "{err}");
+            assert!(err.contains(expected_message), "{err}");
+        }
+
+        // Check that a negative value is rejected when parsing.
+        let mut lock = MUTEX.lock();
+        lock.set_env("DATABASE_MERKLE_TREE_RECOVERY_STALL_THRESHOLD_MS=-1");
+        DBConfig::from_env().unwrap_err();
+    }
 }
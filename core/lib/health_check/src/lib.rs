@@ -15,6 +15,12 @@ pub enum HealthStatus {
     NotReady,
     /// Component is ready for operations.
     Ready,
+    /// Component is recovering its state (e.g., from a snapshot) and cannot serve requests yet. Unlike
+    /// [`Self::NotReady`], this is an expected long-running state rather than an error.
+    Recovering,
+    /// Component is operational, but is affected by non-fatal issues (e.g., some of its tasks have failed
+    /// and will be retried).
+    Affected,
     /// Component is shut down.
     ShutDown,
     /// Component has been abnormally interrupted by a panic.
@@ -22,17 +28,20 @@ pub enum HealthStatus {
 }
 
 impl HealthStatus {
-    /// Checks whether a component is ready according to this status.
+    /// Checks whether a component is ready according to this status. Affected components are considered ready;
+    /// recovering components are not.
     pub fn is_ready(self) -> bool {
-        matches!(self, Self::Ready)
+        matches!(self, Self::Ready | Self::Affected)
     }
 
     fn priority_for_aggregation(self) -> usize {
         match self {
             Self::Ready => 0,
-            Self::ShutDown => 1,
-            Self::NotReady => 2,
-            Self::Panicked => 3,
+            Self::Affected => 1,
+            Self::Recovering => 2,
+            Self::ShutDown => 3,
+            Self::NotReady => 4,
+            Self::Panicked => 5,
         }
     }
 }
@@ -59,6 +68,11 @@ impl Health {
     pub fn status(&self) -> HealthStatus {
         self.status
     }
+
+    /// Returns health details. Mostly useful for testing.
+    pub fn details(&self) -> Option<&serde_json::Value> {
+        self.details.as_ref()
+    }
 }
 
 impl From<HealthStatus> for Health {
@@ -256,4 +270,45 @@ mod tests {
         let updated = health_updater.update(health);
         assert!(updated);
     }
+
+    #[tokio::test]
+    async fn aggregating_affected_health_status() {
+        let (first_check, first_updater) = ReactiveHealthCheck::new("first");
+        let (second_check, second_updater) = ReactiveHealthCheck::new("second");
+        first_updater.update(HealthStatus::Ready.into());
+        second_updater.update(HealthStatus::Affected.into());
+
+        let checks: Vec<Box<dyn CheckHealth>> = vec![Box::new(first_check), Box::new(second_check)];
+        let app_health = AppHealth::new(&checks).await;
+        assert_matches!(app_health.inner.status(), HealthStatus::Affected);
+        assert!(app_health.is_ready());
+
+        second_updater.update(HealthStatus::NotReady.into());
+        let app_health = AppHealth::new(&checks).await;
+        assert_matches!(app_health.inner.status(), HealthStatus::NotReady);
+        assert!(!app_health.is_ready());
+    }
+
+    #[tokio::test]
+    async fn aggregating_recovering_health_status() {
+        let (first_check, first_updater) = ReactiveHealthCheck::new("first");
+        let (second_check, second_updater) = ReactiveHealthCheck::new("second");
+        first_updater.update(HealthStatus::Affected.into());
+        second_updater.update(HealthStatus::Recovering.into());
+
+        let checks: Vec<Box<dyn CheckHealth>> = vec![Box::new(first_check), Box::new(second_check)];
+        let app_health = AppHealth::new(&checks).await;
+        assert_matches!(app_health.inner.status(), HealthStatus::Recovering);
+        assert!(!app_health.is_ready());
+
+        first_updater.update(HealthStatus::NotReady.into());
+        let app_health = AppHealth::new(&checks).await;
+        assert_matches!(app_health.inner.status(), HealthStatus::NotReady);
+
+        first_updater.update(HealthStatus::Ready.into());
+        second_updater.update(HealthStatus::Ready.into());
+        let app_health = AppHealth::new(&checks).await;
+        assert_matches!(app_health.inner.status(), HealthStatus::Ready);
+        assert!(app_health.is_ready());
+    }
 }
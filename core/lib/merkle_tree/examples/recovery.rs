@@ -34,6 +34,10 @@ struct Cli {
     /// Block cache capacity for RocksDB in bytes.
     #[arg(long = "block-cache", conflicts_with = "in_memory")]
     block_cache: Option<usize>,
+    /// Number of threads in a dedicated thread pool used to hash tree nodes. If not specified,
+    /// the global `rayon` thread pool is used.
+    #[arg(long = "threads")]
+    thread_count: Option<usize>,
     /// Seed to use in the RNG for reproducibility.
     #[arg(long = "rng-seed", default_value = "0")]
     rng_seed: u64,
@@ -86,6 +90,11 @@ impl Cli {
         let mut last_key = Key::zero();
         let mut last_leaf_index = 0;
         let mut recovery = MerkleTreeRecovery::with_hasher(db, recovered_version, hasher);
+        if let Some(thread_count) = self.thread_count {
+            recovery
+                .use_dedicated_thread_pool(thread_count)
+                .expect("failed initializing `rayon` thread pool");
+        }
         let recovery_started_at = Instant::now();
         for updated_idx in 0..self.update_count {
             let started_at = Instant::now();
@@ -0,0 +1,120 @@
+//! Benchmark comparing tree recovery with a single hashing thread and with a dedicated thread pool
+//! (see [`MerkleTreeRecovery::use_dedicated_thread_pool()`]).
+//!
+//! Should be compiled with the release profile, otherwise hashing would be prohibitively slow.
+
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tempfile::TempDir;
+use tracing_subscriber::EnvFilter;
+use zksync_merkle_tree::{
+    recovery::MerkleTreeRecovery, Key, PatchSet, PruneDatabase, RocksDBWrapper, TreeEntry,
+    ValueHash,
+};
+use zksync_storage::RocksDB;
+
+/// CLI for benchmarking Merkle tree recovery with a dedicated hashing thread pool.
+#[derive(Debug, Parser)]
+struct Cli {
+    /// Total number of recovered entries.
+    #[arg(long = "entries", default_value = "1000000")]
+    entry_count: u64,
+    /// Number of entries per recovery chunk.
+    #[arg(long = "chunk-size", default_value = "100000")]
+    chunk_size: u64,
+    /// Number of threads in the dedicated thread pool compared with a single thread.
+    #[arg(long = "threads", default_value = "4")]
+    thread_count: usize,
+    /// Perform benchmarking on in-memory DB rather than RocksDB (i.e., with focus on hashing logic).
+    #[arg(long = "in-memory", short = 'M')]
+    in_memory: bool,
+    /// Seed to use in the RNG for reproducibility.
+    #[arg(long = "rng-seed", default_value = "0")]
+    rng_seed: u64,
+}
+
+impl Cli {
+    fn init_logging() {
+        tracing_subscriber::fmt()
+            .pretty()
+            .with_env_filter(EnvFilter::from_default_env())
+            .init();
+    }
+
+    fn run(self) {
+        Self::init_logging();
+        tracing::info!("Launched with options: {self:?}");
+
+        let entries = self.generate_entries();
+        let (single_thread_hash, single_thread_latency) = self.recover(&entries, 1);
+        tracing::info!("Recovered tree with 1 hashing thread in {single_thread_latency:?}");
+        let (root_hash, latency) = self.recover(&entries, self.thread_count);
+        tracing::info!(
+            "Recovered tree with {} hashing threads in {latency:?}",
+            self.thread_count
+        );
+        assert_eq!(root_hash, single_thread_hash, "root hashes differ");
+
+        let speedup = single_thread_latency.as_secs_f64() / latency.as_secs_f64();
+        tracing::info!(
+            "Speedup with {} hashing threads: {speedup:.2}x",
+            self.thread_count
+        );
+    }
+
+    /// Generates entries with keys increasing by random increments, so that they can be recovered linearly.
+    fn generate_entries(&self) -> Vec<TreeEntry> {
+        let mut rng = StdRng::seed_from_u64(self.rng_seed);
+        let key_step = Key::MAX / Key::from(self.entry_count);
+        assert!(key_step > Key::from(u64::MAX));
+
+        let mut last_key = Key::zero();
+        (1..=self.entry_count)
+            .map(|leaf_index| {
+                last_key += key_step - Key::from(rng.gen::<u64>());
+                TreeEntry {
+                    key: last_key,
+                    value: ValueHash::from_low_u64_be(leaf_index),
+                    leaf_index,
+                }
+            })
+            .collect()
+    }
+
+    fn recover(&self, entries: &[TreeEntry], thread_count: usize) -> (ValueHash, Duration) {
+        let (mut mock_db, mut rocksdb);
+        let mut _temp_dir = None;
+        let db: &mut dyn PruneDatabase = if self.in_memory {
+            mock_db = PatchSet::default();
+            &mut mock_db
+        } else {
+            let dir = TempDir::new().expect("failed creating temp dir for RocksDB");
+            rocksdb = RocksDBWrapper::from(RocksDB::new(dir.path()));
+            _temp_dir = Some(dir);
+            &mut rocksdb
+        };
+
+        let mut recovery = MerkleTreeRecovery::new(db, 123);
+        recovery
+            .use_dedicated_thread_pool(thread_count)
+            .expect("failed initializing `rayon` thread pool");
+        let chunk_size = usize::try_from(self.chunk_size).expect("chunk size is too large");
+        let started_at = Instant::now();
+        for (chunk_idx, chunk) in entries.chunks(chunk_size).enumerate() {
+            let chunk_started_at = Instant::now();
+            recovery.extend_linear(chunk.to_vec());
+            tracing::debug!(
+                "Recovered chunk #{chunk_idx} with {thread_count} threads in {:?}",
+                chunk_started_at.elapsed()
+            );
+        }
+        let root_hash = recovery.root_hash();
+        (root_hash, started_at.elapsed())
+    }
+}
+
+fn main() {
+    Cli::parse().run();
+}
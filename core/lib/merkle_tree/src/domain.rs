@@ -1,7 +1,10 @@
 //! Tying the Merkle tree implementation to the problem domain.
 
+use std::{collections::BTreeMap, ops::RangeInclusive, path::Path};
+
 use rayon::{ThreadPool, ThreadPoolBuilder};
 use zksync_crypto::hasher::blake2::Blake2Hasher;
+use zksync_storage::rocksdb;
 use zksync_types::{
     proofs::{PrepareBasicCircuitsJob, StorageLogMetadata},
     writes::{InitialStorageWrite, RepeatedStorageWrite, StateDiffRecord},
@@ -12,10 +15,11 @@ use zksync_utils::h256_to_u256;
 use crate::{
     storage::{PatchSet, Patched, RocksDBWrapper},
     types::{
-        Key, Root, TreeEntry, TreeEntryWithProof, TreeInstruction, TreeLogEntry, ValueHash,
-        TREE_DEPTH,
+        Key, Root, TreeEntry, TreeEntryWithProof, TreeInstruction, TreeLogEntry, TreeTags,
+        ValueHash, TREE_DEPTH,
     },
-    BlockOutput, HashTree, MerkleTree, NoVersionError,
+    BlockOutput, ConsistencyError, Database, HashTree, MerkleTree, MerkleTreePruner,
+    MerkleTreePrunerHandle, NoVersionError, PruneDatabase,
 };
 
 /// Metadata for the current tree state.
@@ -163,6 +167,83 @@ impl ZkSyncTree {
         L1BatchNumber(number)
     }
 
+    /// Returns the root hash of the tree after processing the specified L1 batch, including changes not yet
+    /// saved to RocksDB. Returns `None` if the tree doesn't contain the corresponding version (e.g., because
+    /// the L1 batch wasn't processed yet, or because the version was pruned).
+    pub fn l1_batch_root_hash(&self, l1_batch_number: L1BatchNumber) -> Option<ValueHash> {
+        self.tree.root_hash(u64::from(l1_batch_number.0))
+    }
+
+    /// Checks whether the tree version for the specified L1 batch is retained in full, i.e., wasn't removed
+    /// (even partially) by the tree pruner. Unlike [`Self::l1_batch_root_hash()`], this detects versions
+    /// with a retained root node, but pruned descendant nodes.
+    pub fn is_l1_batch_retained(&self, l1_batch_number: L1BatchNumber) -> bool {
+        let version = u64::from(l1_batch_number.0);
+        if self.tree.root(version).is_none() {
+            return false;
+        }
+        // The pruner removes stale keys in the order of versions that made them stale. Thus, if there are no stale keys
+        // obsoleted by the next version, nodes of `version` could have been pruned.
+        let db = self.tree.db.inner();
+        db.min_stale_key_version()
+            .map_or(true, |min_version| min_version <= version + 1)
+    }
+
+    /// Returns custom tags persisted in the tree manifest, including changes not yet saved to RocksDB.
+    /// Custom tags set during tree recovery are retained after recovery is finalized.
+    pub fn custom_tags(&self) -> BTreeMap<String, String> {
+        let tags = self.tree.db.manifest().and_then(|manifest| manifest.tags);
+        tags.map(|tags| tags.custom).unwrap_or_default()
+    }
+
+    /// Updates custom tags in the tree manifest using the provided closure. Like other changes,
+    /// updated tags are persisted to RocksDB on [`Self::save()`].
+    pub fn update_custom_tags<R>(
+        &mut self,
+        update: impl FnOnce(&mut BTreeMap<String, String>) -> R,
+    ) -> R {
+        let mut manifest = self.tree.db.manifest().unwrap_or_default();
+        let tags = manifest
+            .tags
+            .get_or_insert_with(|| TreeTags::new(&Blake2Hasher));
+        let output = update(&mut tags.custom);
+        self.tree.db.apply_patch(PatchSet::from_manifest(manifest));
+        output
+    }
+
+    /// Reads at most `limit` entries with hashed keys in the specified `key_range` from the latest tree version,
+    /// including changes not yet saved to RocksDB. The entries are returned in the ascending key order.
+    ///
+    /// Leaf indices are stored in tree leaves regardless of the processing mode, so returned entries have
+    /// valid leaf indices both for full and lightweight trees.
+    pub fn entries_in_range(
+        &self,
+        key_range: &RangeInclusive<Key>,
+        limit: usize,
+    ) -> Vec<TreeEntry> {
+        let Some(version) = self.tree.latest_version() else {
+            return vec![];
+        };
+        self.tree
+            .entries_in_range_with_limit(version, key_range, limit)
+            .unwrap_or_default()
+    }
+
+    /// Reads entries together with Merkle proofs with the specified keys from the tree, including changes
+    /// not yet saved to RocksDB. The entries are returned in the same order as requested.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn entries_with_proofs(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        keys: &[Key],
+    ) -> Result<Vec<TreeEntryWithProof>, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        self.tree.entries_with_proofs(version, keys)
+    }
+
     /// Verifies tree consistency. `l1_batch_number` specifies the version of the tree
     /// to be checked, expressed as the number of latest L1 batch applied to the tree.
     ///
@@ -425,6 +506,12 @@ impl ZkSyncTree {
     pub fn reset(&mut self) {
         self.tree.db.reset();
     }
+
+    /// Discards unsaved changes in the tree and returns the underlying database.
+    pub fn into_db(mut self) -> RocksDBWrapper {
+        self.tree.db.reset();
+        self.tree.db.into_inner()
+    }
 }
 
 /// Readonly handle to a [`ZkSyncTree`].
@@ -458,6 +545,33 @@ impl ZkSyncTreeReader {
         self.0.latest_root().leaf_count()
     }
 
+    /// Verifies consistency of the tree at the specified L1 batch, similarly to [`ZkSyncTree::verify_consistency()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an inconsistency is detected.
+    pub fn verify_consistency(
+        &self,
+        l1_batch_number: L1BatchNumber,
+    ) -> Result<(), ConsistencyError> {
+        let version = u64::from(l1_batch_number.0);
+        self.0.verify_consistency(version, true)
+    }
+
+    /// Reads entries with the specified keys from the tree. The entries are returned in the same order as requested.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn entries(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        keys: &[Key],
+    ) -> Result<Vec<TreeEntry>, NoVersionError> {
+        let version = u64::from(l1_batch_number.0);
+        self.0.entries(version, keys)
+    }
+
     /// Reads entries together with Merkle proofs with the specified keys from the tree. The entries are returned
     /// in the same order as requested.
     ///
@@ -472,4 +586,30 @@ impl ZkSyncTreeReader {
         let version = u64::from(l1_batch_number.0);
         self.0.entries_with_proofs(version, keys)
     }
+
+    /// Creates a checkpoint of the tree RocksDB at the specified `path`, which must not exist. The checkpoint
+    /// contains the tree state persisted at the time of the call and can be opened as a separate tree.
+    ///
+    /// # Errors
+    ///
+    /// Propagates RocksDB I/O errors.
+    pub fn create_checkpoint(&self, path: &Path) -> Result<(), rocksdb::Error> {
+        self.0.db.create_checkpoint(path)
+    }
+
+    /// Creates a pruner removing tree versions older than `past_versions_to_keep` behind the latest version
+    /// persisted in RocksDB. Unlike other reader methods, running the pruner modifies the database;
+    /// the tree may be updated concurrently with pruning, but no more than one pruner should run at a time.
+    pub fn pruner(
+        &self,
+        past_versions_to_keep: u64,
+    ) -> (MerkleTreePruner<RocksDBWrapper>, MerkleTreePrunerHandle) {
+        MerkleTreePruner::new(self.0.db.clone(), past_versions_to_keep)
+    }
+
+    /// Runs manual compaction of the tree RocksDB, e.g. to reclaim disk space after pruning. Returns
+    /// the number of reclaimed bytes. Compaction blocks until it's complete and doesn't change tree contents.
+    pub fn compact_db(&self) -> u64 {
+        self.0.db.compact()
+    }
 }
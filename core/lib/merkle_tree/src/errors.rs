@@ -2,7 +2,7 @@
 
 use std::{error, fmt, str::Utf8Error};
 
-use crate::types::NodeKey;
+use crate::types::{NodeKey, ValueHash};
 
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
@@ -164,6 +164,25 @@ impl fmt::Display for NoVersionError {
 
 impl error::Error for NoVersionError {}
 
+/// Error verifying a Merkle proof of a tree entry (see [`TreeEntryWithProof::try_verify()`]).
+///
+/// [`TreeEntryWithProof::try_verify()`]: crate::TreeEntryWithProof::try_verify()
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum ProofVerificationError {
+    /// Entry has zero leaf index (i.e., is missing from the tree), but a non-default value.
+    #[error("invalid missing value specification: leaf index is zero, but value is non-default")]
+    InvalidMissingValue,
+    /// Root hash computed from the Merkle proof differs from the trusted one.
+    #[error("root hash mismatch: trusted {trusted:?}, computed from proof {computed:?}")]
+    RootHashMismatch {
+        /// Trusted root hash.
+        trusted: ValueHash,
+        /// Root hash computed from the proof.
+        computed: ValueHash,
+    },
+}
+
 #[cfg(test)]
 mod tests {
     use zksync_types::U256;
@@ -1,10 +1,12 @@
 //! Getters for the Merkle tree.
 
+use std::ops::RangeInclusive;
+
 use crate::{
     hasher::HasherWithStats,
     recovery::MerkleTreeRecovery,
     storage::{LoadAncestorsResult, SortedKeys, WorkingPatchSet},
-    types::{Nibbles, Node, TreeEntry, TreeEntryWithProof},
+    types::{Nibbles, Node, Root, TreeEntry, TreeEntryWithProof},
     Database, HashTree, Key, MerkleTree, NoVersionError, PruneDatabase, ValueHash,
 };
 
@@ -55,6 +57,46 @@ impl<DB: Database, H: HashTree> MerkleTree<DB, H> {
             },
         )
     }
+
+    /// Reads all entries with keys in the specified `key_range` from the tree. The entries are returned
+    /// in the ascending key order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn entries_in_range(
+        &self,
+        version: u64,
+        key_range: &RangeInclusive<Key>,
+    ) -> Result<Vec<TreeEntry>, NoVersionError> {
+        self.entries_in_range_with_limit(version, key_range, usize::MAX)
+    }
+
+    /// Reads at most `limit` entries with keys in the specified `key_range` from the tree. The entries
+    /// are returned in the ascending key order; i.e., the returned entries have the least keys in the range.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree `version` is missing.
+    pub fn entries_in_range_with_limit(
+        &self,
+        version: u64,
+        key_range: &RangeInclusive<Key>,
+        limit: usize,
+    ) -> Result<Vec<TreeEntry>, NoVersionError> {
+        let root = load_root(&self.db, version)?;
+        Ok(load_entries_in_range(&self.db, root, key_range, limit))
+    }
+}
+
+fn load_root(db: &impl Database, version: u64) -> Result<Root, NoVersionError> {
+    db.root(version).ok_or_else(|| {
+        let manifest = db.manifest().unwrap_or_default();
+        NoVersionError {
+            missing_version: version,
+            version_count: manifest.version_count,
+        }
+    })
 }
 
 fn load_and_transform_entries<T>(
@@ -63,13 +105,7 @@ fn load_and_transform_entries<T>(
     leaf_keys: &[Key],
     mut transform: impl FnMut(&mut WorkingPatchSet, &Key, &Nibbles) -> T,
 ) -> Result<Vec<T>, NoVersionError> {
-    let root = db.root(version).ok_or_else(|| {
-        let manifest = db.manifest().unwrap_or_default();
-        NoVersionError {
-            missing_version: version,
-            version_count: manifest.version_count,
-        }
-    })?;
+    let root = load_root(db, version)?;
     let sorted_keys = SortedKeys::new(leaf_keys.iter().copied());
     let mut patch_set = WorkingPatchSet::new(version, root);
     let LoadAncestorsResult {
@@ -95,6 +131,57 @@ fn extract_entry(
     }
 }
 
+fn load_entries_in_range(
+    db: &impl Database,
+    root: Root,
+    key_range: &RangeInclusive<Key>,
+    limit: usize,
+) -> Vec<TreeEntry> {
+    let mut entries = vec![];
+    if let Root::Filled { node, .. } = root {
+        collect_entries_in_range(db, Nibbles::EMPTY, node, key_range, limit, &mut entries);
+    }
+    entries
+}
+
+/// Recursively traverses the subtree rooted at `node`, skipping child subtrees that cannot contain keys
+/// from `key_range`. Since children of internal nodes are iterated in the nibble order, leaves are collected
+/// in the ascending key order. The traversal stops once `entries` contain `limit` entries.
+fn collect_entries_in_range(
+    db: &impl Database,
+    nibbles: Nibbles,
+    node: Node,
+    key_range: &RangeInclusive<Key>,
+    limit: usize,
+    entries: &mut Vec<TreeEntry>,
+) {
+    match node {
+        Node::Leaf(leaf) => {
+            if entries.len() < limit && key_range.contains(&leaf.full_key) {
+                entries.push(leaf.into());
+            }
+        }
+        Node::Internal(node) => {
+            for (nibble, child_ref) in node.children() {
+                if entries.len() >= limit {
+                    return;
+                }
+                let child_nibbles = nibbles.push(nibble).unwrap();
+                // ^ `unwrap()` is safe; there can be no internal nodes on the bottom-most tree level
+                let child_range = child_nibbles.key_range();
+                if child_range.end() < key_range.start() || child_range.start() > key_range.end() {
+                    continue;
+                }
+
+                let child_key = child_nibbles.with_version(child_ref.version);
+                let child = db.tree_node(&child_key, child_ref.is_leaf).unwrap();
+                // ^ `unwrap()` is safe by construction
+                collect_entries_in_range(db, child_nibbles, child, key_range, limit, entries);
+            }
+        }
+    }
+}
+
 impl<DB: PruneDatabase, H: HashTree> MerkleTreeRecovery<DB, H> {
     /// Reads entries with the specified keys from the tree. The entries are returned in the same order
     /// as requested. If a certain key is not present in the tree, the corresponding returned entry
@@ -107,12 +194,24 @@ impl<DB: PruneDatabase, H: HashTree> MerkleTreeRecovery<DB, H> {
                 leaf_keys.iter().map(|key| TreeEntry::empty(*key)).collect()
             })
     }
+
+    /// Reads all entries with keys in the specified `key_range` from the tree. The entries are returned
+    /// in the ascending key order.
+    pub fn entries_in_range(&self, key_range: &RangeInclusive<Key>) -> Vec<TreeEntry> {
+        // If there's no recovered version, the recovered tree is empty yet.
+        self.db
+            .root(self.recovered_version())
+            .map(|root| load_entries_in_range(&self.db, root, key_range, usize::MAX))
+            .unwrap_or_default()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use assert_matches::assert_matches;
+
     use super::*;
-    use crate::PatchSet;
+    use crate::{PatchSet, ProofVerificationError};
 
     #[test]
     fn entries_in_empty_tree() {
@@ -148,5 +247,76 @@ mod tests {
         entries[0].verify(&tree.hasher, output.root_hash);
         assert!(entries[1].base.is_empty());
         entries[1].verify(&tree.hasher, output.root_hash);
+
+        let mut tampered_entry = entries[0].clone();
+        tampered_entry.base.value = ValueHash::repeat_byte(2);
+        let err = tampered_entry
+            .try_verify(&tree.hasher, output.root_hash)
+            .unwrap_err();
+        assert_matches!(err, ProofVerificationError::RootHashMismatch { .. });
+
+        let mut tampered_entry = entries[1].clone();
+        tampered_entry.base.value = ValueHash::repeat_byte(2);
+        let err = tampered_entry
+            .try_verify(&tree.hasher, output.root_hash)
+            .unwrap_err();
+        assert_eq!(err, ProofVerificationError::InvalidMissingValue);
+    }
+
+    #[test]
+    fn entries_in_range() {
+        let mut tree = MerkleTree::new(PatchSet::default());
+        let entries: Vec<_> = (1..=100_u64)
+            .map(|i| {
+                TreeEntry::new(
+                    (Key::from(i) << 248) | Key::from(i),
+                    i,
+                    ValueHash::from_low_u64_be(i),
+                )
+            })
+            .collect();
+        tree.extend(entries.clone());
+
+        let all_entries = tree.entries_in_range(0, &(Key::zero()..=Key::MAX)).unwrap();
+        assert_eq!(all_entries, entries);
+
+        let range = (Key::from(10) << 248)..=((Key::from(20) << 248) | Key::from(20));
+        let range_entries = tree.entries_in_range(0, &range).unwrap();
+        assert_eq!(range_entries, entries[9..20]);
+
+        let range = (Key::from(10) << 248) + 11..=(Key::from(20) << 248);
+        let range_entries = tree.entries_in_range(0, &range).unwrap();
+        assert_eq!(range_entries, entries[10..19]);
+
+        let missing_range = (Key::from(0xff) << 248)..=Key::MAX;
+        assert!(tree.entries_in_range(0, &missing_range).unwrap().is_empty());
+        assert!(tree.entries_in_range(1, &range).is_err());
+    }
+
+    #[test]
+    fn entries_in_range_with_limit() {
+        let mut tree = MerkleTree::new(PatchSet::default());
+        let entries: Vec<_> = (1..=100_u64)
+            .map(|i| {
+                TreeEntry::new(
+                    (Key::from(i) << 248) | Key::from(i),
+                    i,
+                    ValueHash::from_low_u64_be(i),
+                )
+            })
+            .collect();
+        tree.extend(entries.clone());
+
+        let full_range = Key::zero()..=Key::MAX;
+        for limit in [0, 1, 10, 99, 100, 1_000] {
+            let limited_entries = tree
+                .entries_in_range_with_limit(0, &full_range, limit)
+                .unwrap();
+            assert_eq!(limited_entries, entries[..limit.min(100)]);
+        }
+
+        let range = (Key::from(10) << 248) + 11..=(Key::from(20) << 248);
+        let range_entries = tree.entries_in_range_with_limit(0, &range, 5).unwrap();
+        assert_eq!(range_entries, entries[10..15]);
     }
 }
@@ -3,6 +3,7 @@
 use std::mem;
 
 use crate::{
+    errors::ProofVerificationError,
     hasher::{HashTree, HasherWithStats},
     types::{
         BlockOutputWithProofs, Key, LeafNode, TreeEntry, TreeEntryWithProof, TreeInstruction,
@@ -67,14 +68,31 @@ impl TreeEntryWithProof {
     ///
     /// Panics if the proof doesn't verify.
     pub fn verify(&self, hasher: &dyn HashTree, trusted_root_hash: ValueHash) {
-        if self.base.leaf_index == 0 {
-            assert!(
-                self.base.value.is_zero(),
-                "Invalid missing value specification: leaf index is zero, but value is non-default"
-            );
+        self.try_verify(hasher, trusted_root_hash)
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+
+    /// Verifies this proof, returning an error instead of panicking if the proof doesn't verify.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the proof doesn't verify.
+    pub fn try_verify(
+        &self,
+        hasher: &dyn HashTree,
+        trusted_root_hash: ValueHash,
+    ) -> Result<(), ProofVerificationError> {
+        if self.base.leaf_index == 0 && !self.base.value.is_zero() {
+            return Err(ProofVerificationError::InvalidMissingValue);
         }
         let root_hash = hasher.fold_merkle_path(&self.merkle_path, self.base);
-        assert_eq!(root_hash, trusted_root_hash, "Root hash mismatch");
+        if root_hash != trusted_root_hash {
+            return Err(ProofVerificationError::RootHashMismatch {
+                trusted: trusted_root_hash,
+                computed: root_hash,
+            });
+        }
+        Ok(())
     }
 }
 
@@ -49,9 +49,10 @@
 use zksync_crypto::hasher::blake2::Blake2Hasher;
 
 pub use crate::{
-    errors::NoVersionError,
+    consistency::ConsistencyError,
+    errors::{NoVersionError, ProofVerificationError},
     hasher::{HashTree, TreeRangeDigest},
-    pruning::{MerkleTreePruner, MerkleTreePrunerHandle},
+    pruning::{MerkleTreePruner, MerkleTreePrunerHandle, PruningOutcome},
     storage::{
         Database, MerkleTreeColumnFamily, PatchSet, Patched, PruneDatabase, PrunePatchSet,
         RocksDBWrapper,
@@ -255,6 +256,7 @@ mod tests {
             depth: 256,
             hasher: "blake2s256".to_string(),
             is_recovering: false,
+            custom: Default::default(),
         });
 
         MerkleTree::new(db);
@@ -269,6 +271,7 @@ mod tests {
             depth: 128,
             hasher: "blake2s256".to_string(),
             is_recovering: false,
+            custom: Default::default(),
         });
 
         MerkleTree::new(db);
@@ -283,6 +286,7 @@ mod tests {
             depth: 256,
             hasher: "sha256".to_string(),
             is_recovering: false,
+            custom: Default::default(),
         });
 
         MerkleTree::new(db);
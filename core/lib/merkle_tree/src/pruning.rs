@@ -24,6 +24,15 @@ impl MerkleTreePrunerHandle {
     }
 }
 
+/// Outcome of [`MerkleTreePruner::run_to_completion()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruningOutcome {
+    /// Total number of stale keys removed by the pruner.
+    pub pruned_key_count: usize,
+    /// Whether the pruner was aborted before all stale keys eligible for removal were removed.
+    pub is_aborted: bool,
+}
+
 /// Component responsible for Merkle tree pruning, i.e. removing nodes not referenced by new versions
 /// of the tree. A pruner should be instantiated using a [`Clone`] of the tree database, possibly
 /// configured and then [`run()`](Self::run()) on its own thread. [`MerkleTreePrunerHandle`] provides
@@ -142,6 +151,39 @@ impl<DB: PruneDatabase> MerkleTreePruner<DB> {
         Some(stats)
     }
 
+    /// Runs this pruner until all stale keys eligible for removal per the pruning policy are removed,
+    /// or until the pruner is aborted via its handle (dropping the handle aborts the pruner as well).
+    /// Unlike [`Self::run()`], the pruner doesn't wait for the tree to produce new stale keys.
+    ///
+    /// Pruning progress is persisted after each iteration, so aborted pruning can be resumed by running
+    /// a new pruner for the same database.
+    pub fn run_to_completion(mut self) -> PruningOutcome {
+        let mut outcome = PruningOutcome::default();
+        loop {
+            if !matches!(
+                self.aborted_receiver.try_recv(),
+                Err(mpsc::TryRecvError::Empty)
+            ) {
+                tracing::info!(
+                    "Pruner was aborted after removing {} stale keys",
+                    outcome.pruned_key_count
+                );
+                outcome.is_aborted = true;
+                return outcome;
+            }
+
+            let Some(stats) = self.run_once() else {
+                return outcome;
+            };
+            let has_more_work = stats.has_more_work();
+            outcome.pruned_key_count += stats.pruned_key_count;
+            stats.report();
+            if !has_more_work {
+                return outcome;
+            }
+        }
+    }
+
     /// Runs this pruner indefinitely until it is aborted by dropping its handle.
     pub fn run(mut self) {
         tracing::info!("Started Merkle tree pruner {self:?}");
@@ -245,6 +287,45 @@ mod tests {
         assert!(start.elapsed() < Duration::from_secs(10));
     }
 
+    #[test]
+    fn running_pruner_to_completion() {
+        let mut db = create_db();
+        let (mut pruner, _handle) = MerkleTreePruner::new(&mut db, 1);
+        pruner.set_target_pruned_key_count(1);
+
+        let outcome = pruner.run_to_completion();
+        assert!(outcome.pruned_key_count > 0);
+        assert!(!outcome.is_aborted);
+        for version in 0..3 {
+            assert!(db.root_mut(version).is_none());
+        }
+        assert!(db.root_mut(3).is_some());
+
+        // Repeated pruning has nothing to do.
+        let (pruner, _handle) = MerkleTreePruner::new(&mut db, 1);
+        assert_eq!(pruner.run_to_completion(), PruningOutcome::default());
+    }
+
+    #[test]
+    fn aborted_pruning_can_be_resumed() {
+        let mut db = create_db();
+        let (mut pruner, pruner_handle) = MerkleTreePruner::new(&mut db, 0);
+        pruner.set_target_pruned_key_count(1);
+        pruner_handle.abort();
+        let outcome = pruner.run_to_completion();
+        assert_eq!(outcome.pruned_key_count, 0);
+        assert!(outcome.is_aborted);
+        assert!(db.root_mut(0).is_some());
+
+        let (pruner, _handle) = MerkleTreePruner::new(&mut db, 0);
+        let outcome = pruner.run_to_completion();
+        assert!(outcome.pruned_key_count > 0);
+        assert!(!outcome.is_aborted);
+        for version in 0..4 {
+            assert!(db.root_mut(version).is_none());
+        }
+    }
+
     fn generate_key_value_pairs(indexes: impl Iterator<Item = u64>) -> Vec<TreeEntry> {
         indexes
             .map(|i| TreeEntry::new(Key::from(i), i + 1, ValueHash::from_low_u64_be(i)))
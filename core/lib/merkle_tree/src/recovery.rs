@@ -35,13 +35,14 @@
 //! before extending the tree; these nodes are guaranteed to be the *only* DB reads necessary
 //! to insert new entries.
 
-use std::time::Instant;
+use std::{collections::BTreeMap, time::Instant};
 
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
 use zksync_crypto::hasher::blake2::Blake2Hasher;
 
 use crate::{
     hasher::{HashTree, HasherWithStats},
-    storage::{PatchSet, PruneDatabase, PrunePatchSet, Storage},
+    storage::{PatchSet, PruneDatabase, PrunePatchSet, RocksDBWrapper, Storage},
     types::{Key, Manifest, Root, TreeEntry, TreeTags, ValueHash},
 };
 
@@ -51,6 +52,7 @@ pub struct MerkleTreeRecovery<DB, H = Blake2Hasher> {
     pub(crate) db: DB,
     hasher: H,
     recovered_version: u64,
+    thread_pool: Option<ThreadPool>,
 }
 
 impl<DB: PruneDatabase> MerkleTreeRecovery<DB> {
@@ -75,7 +77,7 @@ impl<DB: PruneDatabase, H: HashTree> MerkleTreeRecovery<DB, H> {
     ///   do not match those of the tree loaded from the database.
     pub fn with_hasher(mut db: DB, recovered_version: u64, hasher: H) -> Self {
         let manifest = db.manifest();
-        let mut manifest = if let Some(manifest) = manifest {
+        let (mut manifest, mut is_manifest_updated) = if let Some(manifest) = manifest {
             if manifest.version_count > 0 {
                 let expected_version = manifest.version_count - 1;
                 assert_eq!(
@@ -85,31 +87,63 @@ impl<DB: PruneDatabase, H: HashTree> MerkleTreeRecovery<DB, H> {
                     for version {expected_version}"
                 );
             }
-            manifest
+            (manifest, false)
         } else {
-            Manifest {
+            let manifest = Manifest {
                 version_count: recovered_version + 1,
                 tags: None,
-            }
+            };
+            (manifest, true)
         };
 
-        manifest.version_count = recovered_version + 1;
+        if manifest.version_count != recovered_version + 1 {
+            manifest.version_count = recovered_version + 1;
+            is_manifest_updated = true;
+        }
         if let Some(tags) = &manifest.tags {
             tags.assert_consistency(&hasher, true);
         } else {
             let mut tags = TreeTags::new(&hasher);
             tags.is_recovering = true;
             manifest.tags = Some(tags);
+            is_manifest_updated = true;
+        }
+        // The manifest isn't rewritten if it's unchanged, so that recovery can be resumed for a database
+        // opened in the read-only mode (e.g., to inspect recovery progress).
+        if is_manifest_updated {
+            db.apply_patch(PatchSet::from_manifest(manifest));
         }
-        db.apply_patch(PatchSet::from_manifest(manifest));
 
         Self {
             db,
             hasher,
             recovered_version,
+            thread_pool: None,
         }
     }
 
+    /// Signals that the recovery should use a dedicated `rayon` thread pool with the specified number
+    /// of threads for hashing tree nodes when extending the tree. By default, the global `rayon`
+    /// thread pool is used.
+    ///
+    /// If `thread_count` is 0, the default number of threads will be used; see `rayon` docs
+    /// for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the thread pool cannot be created, e.g. because OS threads cannot be spawned.
+    pub fn use_dedicated_thread_pool(
+        &mut self,
+        thread_count: usize,
+    ) -> Result<(), ThreadPoolBuildError> {
+        let thread_pool = ThreadPoolBuilder::new()
+            .thread_name(|idx| format!("merkle-tree-recovery-{idx}"))
+            .num_threads(thread_count)
+            .build()?;
+        self.thread_pool = Some(thread_pool);
+        Ok(())
+    }
+
     /// Returns the version of the tree being recovered.
     pub fn recovered_version(&self) -> u64 {
         self.recovered_version
@@ -124,12 +158,51 @@ impl<DB: PruneDatabase, H: HashTree> MerkleTreeRecovery<DB, H> {
         node.hash(&mut HasherWithStats::new(&self.hasher), 0)
     }
 
+    /// Returns the number of entries (i.e., leaves) in the recovered tree at this point.
+    pub fn leaf_count(&self) -> u64 {
+        self.db
+            .root(self.recovered_version)
+            .map_or(0, |root| root.leaf_count())
+    }
+
+    /// Returns custom tags persisted in the tree manifest. Tags can be used by the caller
+    /// to store arbitrary metadata about the recovery process (e.g., recovery parameters).
+    #[allow(clippy::missing_panics_doc)]
+    pub fn custom_tags(&self) -> BTreeMap<String, String> {
+        let manifest = self.db.manifest().unwrap();
+        // ^ `unwrap()` is safe: manifest is inserted into the DB on creation
+        manifest.tags.map(|tags| tags.custom).unwrap_or_default()
+    }
+
+    /// Updates custom tags in the tree manifest using the provided closure and persists
+    /// the updated manifest. Custom tags are retained after the recovery is finalized.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn update_custom_tags<R>(
+        &mut self,
+        update: impl FnOnce(&mut BTreeMap<String, String>) -> R,
+    ) -> R {
+        let mut manifest = self.db.manifest().unwrap();
+        // ^ `unwrap()` is safe: manifest is inserted into the DB on creation
+        let tags = manifest
+            .tags
+            .get_or_insert_with(|| TreeTags::new(&self.hasher));
+        let output = update(&mut tags.custom);
+        self.db.apply_patch(PatchSet::from_manifest(manifest));
+        output
+    }
+
     /// Returns the last key processed during the recovery process.
     pub fn last_processed_key(&self) -> Option<Key> {
         let storage = Storage::new(&self.db, &self.hasher, self.recovered_version, false);
         storage.greatest_key()
     }
 
+    /// Returns the least key inserted into the tree so far, or `None` if the tree is empty.
+    pub fn least_key(&self) -> Option<Key> {
+        let storage = Storage::new(&self.db, &self.hasher, self.recovered_version, false);
+        storage.least_key()
+    }
+
     /// Extends a tree with a chunk of linearly ordered entries.
     ///
     /// Entries must be ordered by increasing `key`, and the key of the first entry must be greater
@@ -152,7 +225,11 @@ impl<DB: PruneDatabase, H: HashTree> MerkleTreeRecovery<DB, H> {
 
         let started_at = Instant::now();
         let storage = Storage::new(&self.db, &self.hasher, self.recovered_version, false);
-        let patch = storage.extend_during_linear_recovery(entries);
+        let patch = if let Some(thread_pool) = &self.thread_pool {
+            thread_pool.install(|| storage.extend_during_linear_recovery(entries))
+        } else {
+            storage.extend_during_linear_recovery(entries)
+        };
         tracing::debug!("Finished processing keys; took {:?}", started_at.elapsed());
 
         let started_at = Instant::now();
@@ -161,7 +238,7 @@ impl<DB: PruneDatabase, H: HashTree> MerkleTreeRecovery<DB, H> {
     }
 
     /// Extends a tree with a chunk of entries. Unlike [`Self::extend_linear()`], entries may be
-    /// ordered in any way you like.
+    /// ordered in any way you like. Entries with keys already present in the tree overwrite existing entries.
     #[tracing::instrument(
         level = "debug",
         skip_all,
@@ -175,7 +252,11 @@ impl<DB: PruneDatabase, H: HashTree> MerkleTreeRecovery<DB, H> {
 
         let started_at = Instant::now();
         let storage = Storage::new(&self.db, &self.hasher, self.recovered_version, false);
-        let patch = storage.extend_during_random_recovery(entries);
+        let patch = if let Some(thread_pool) = &self.thread_pool {
+            thread_pool.install(|| storage.extend_during_random_recovery(entries))
+        } else {
+            storage.extend_during_random_recovery(entries)
+        };
         tracing::debug!("Finished processing keys; took {:?}", started_at.elapsed());
 
         let started_at = Instant::now();
@@ -183,13 +264,33 @@ impl<DB: PruneDatabase, H: HashTree> MerkleTreeRecovery<DB, H> {
         tracing::debug!("Finished persisting to DB; took {:?}", started_at.elapsed());
     }
 
+    /// Prunes stale keys accumulated during recovery. This is performed by [`Self::finalize()`]
+    /// automatically; calling this method beforehand allows to separate pruning (which can take a while
+    /// for large trees) from marking recovery as complete.
+    #[allow(clippy::range_plus_one)]
+    pub fn prune_stale_keys(&mut self) {
+        let started_at = Instant::now();
+        let stale_keys = self.db.stale_keys(self.recovered_version);
+        let stale_keys_len = stale_keys.len();
+        tracing::debug!("Pruning {stale_keys_len} accumulated stale keys");
+        let prune_patch = PrunePatchSet::new(
+            stale_keys,
+            self.recovered_version..self.recovered_version + 1,
+        );
+        self.db.prune(prune_patch);
+        tracing::debug!(
+            "Pruned {stale_keys_len} stale keys in {:?}",
+            started_at.elapsed()
+        );
+    }
+
     /// Finalizes the recovery process marking it as complete in the tree manifest.
     #[tracing::instrument(
         level = "debug",
         skip_all,
         fields(recovered_version = self.recovered_version),
     )]
-    #[allow(clippy::missing_panics_doc, clippy::range_plus_one)]
+    #[allow(clippy::missing_panics_doc)]
     pub fn finalize(mut self) -> DB {
         let mut manifest = self.db.manifest().unwrap();
         // ^ `unwrap()` is safe: manifest is inserted into the DB on creation
@@ -205,20 +306,7 @@ impl<DB: PruneDatabase, H: HashTree> MerkleTreeRecovery<DB, H> {
         tracing::debug!(
             "Finalizing recovery of the Merkle tree with {leaf_count} key–value entries"
         );
-
-        let started_at = Instant::now();
-        let stale_keys = self.db.stale_keys(self.recovered_version);
-        let stale_keys_len = stale_keys.len();
-        tracing::debug!("Pruning {stale_keys_len} accumulated stale keys");
-        let prune_patch = PrunePatchSet::new(
-            stale_keys,
-            self.recovered_version..self.recovered_version + 1,
-        );
-        self.db.prune(prune_patch);
-        tracing::debug!(
-            "Pruned {stale_keys_len} stale keys in {:?}",
-            started_at.elapsed()
-        );
+        self.prune_stale_keys();
 
         manifest
             .tags
@@ -231,6 +319,61 @@ impl<DB: PruneDatabase, H: HashTree> MerkleTreeRecovery<DB, H> {
     }
 }
 
+/// Recovery journal operations. See [`RocksDBWrapper::recovery_journal_entry()`] etc. for details.
+impl<H> MerkleTreeRecovery<RocksDBWrapper, H> {
+    /// Returns the value for the specified `key` in the recovery journal.
+    pub fn recovery_journal_entry(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.db.recovery_journal_entry(key)
+    }
+
+    /// Returns all entries in the recovery journal in the lexical key order.
+    pub fn recovery_journal_entries(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.db.recovery_journal_entries()
+    }
+
+    /// Sets the value for the specified `key` in the recovery journal, or removes the entry
+    /// if `value` is `None`.
+    pub fn set_recovery_journal_entry(&mut self, key: &[u8], value: Option<&[u8]>) {
+        self.db.set_recovery_journal_entry(key, value);
+    }
+
+    /// Removes all entries from the recovery journal. The journal is not cleared automatically
+    /// when recovery is finalized.
+    pub fn clear_recovery_journal(&mut self) {
+        self.db.clear_recovery_journal();
+    }
+
+    /// Runs manual RocksDB compaction and returns the number of bytes reclaimed
+    /// (see [`RocksDBWrapper::compact()`]). Useful if the database is opened with auto-compaction disabled
+    /// to speed up recovery.
+    pub fn compact_db(&self) -> u64 {
+        self.db.compact()
+    }
+
+    /// Flushes memtables of the underlying RocksDB to disk (see [`RocksDBWrapper::flush()`]). Useful to bound
+    /// memtable memory during recovery if the database is opened with auto-compaction disabled.
+    ///
+    /// # Errors
+    ///
+    /// Propagates RocksDB I/O errors.
+    pub fn flush_db(&self) -> Result<(), zksync_storage::rocksdb::Error> {
+        self.db.flush()
+    }
+
+    /// Returns the underlying database without finalizing recovery. Recovery can be resumed by creating
+    /// a new recovery instance for the returned database.
+    pub fn into_db(self) -> RocksDBWrapper {
+        self.db
+    }
+
+    /// Discards all recovery progress, including custom tags in the tree manifest and the recovery journal.
+    /// Returns the emptied database, which can be used to start recovery from scratch.
+    pub fn reset(mut self) -> RocksDBWrapper {
+        self.db.clear();
+        self.db
+    }
+}
+
 fn entries_key_range(entries: &[TreeEntry]) -> String {
     let (Some(first), Some(last)) = (entries.first(), entries.last()) else {
         return "(empty)".to_owned();
@@ -241,7 +384,7 @@ fn entries_key_range(entries: &[TreeEntry]) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{hasher::HasherWithStats, types::LeafNode, MerkleTree};
+    use crate::{hasher::HasherWithStats, types::LeafNode, Database, MerkleTree};
 
     #[test]
     #[should_panic(expected = "Tree is expected to be in the process of recovery")]
@@ -267,6 +410,23 @@ mod tests {
         assert_eq!(tree.root(42), Some(Root::Empty));
     }
 
+    #[test]
+    fn persisting_custom_tags() {
+        let mut db = PatchSet::default();
+        let mut recovery = MerkleTreeRecovery::new(&mut db, 42);
+        assert!(recovery.custom_tags().is_empty());
+        recovery.update_custom_tags(|tags| tags.insert("test".to_owned(), "value".to_owned()));
+
+        let recovery = MerkleTreeRecovery::new(&mut db, 42);
+        let custom_tags = recovery.custom_tags();
+        assert_eq!(custom_tags.len(), 1);
+        assert_eq!(custom_tags["test"], "value");
+
+        recovery.finalize();
+        let custom_tags = db.manifest().unwrap().custom_tags().cloned().unwrap();
+        assert_eq!(custom_tags["test"], "value");
+    }
+
     #[test]
     fn recovering_tree_with_single_node() {
         let mut recovery = MerkleTreeRecovery::new(PatchSet::default(), 42);
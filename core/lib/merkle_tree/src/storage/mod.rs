@@ -106,6 +106,14 @@ impl TreeUpdater {
         Some((leaf, load_result.longest_prefixes[0]))
     }
 
+    /// Loads the least key from the database.
+    fn load_least_key<DB: Database + ?Sized>(&mut self, db: &DB) -> Option<(LeafNode, Nibbles)> {
+        let (leaf, load_result) = self.patch_set.load_least_key(db)?;
+        self.metrics.db_reads += load_result.db_reads;
+        assert_eq!(load_result.longest_prefixes.len(), 1);
+        Some((leaf, load_result.longest_prefixes[0]))
+    }
+
     /// Inserts or updates a value hash for the specified `key`. This implementation
     /// is almost verbatim the algorithm described in the Jellyfish Merkle tree white paper.
     /// The algorithm from the paper is as follows:
@@ -315,6 +323,10 @@ impl<'a, DB: Database + ?Sized> Storage<'a, DB> {
         Some(self.updater.load_greatest_key(self.db)?.0.full_key)
     }
 
+    pub fn least_key(mut self) -> Option<Key> {
+        Some(self.updater.load_least_key(self.db)?.0.full_key)
+    }
+
     pub fn extend_during_linear_recovery(mut self, recovery_entries: Vec<TreeEntry>) -> PatchSet {
         let (mut prev_key, mut prev_nibbles) = match self.updater.load_greatest_key(self.db) {
             Some((leaf, nibbles)) => (Some(leaf.full_key), nibbles),
@@ -354,8 +366,12 @@ impl<'a, DB: Database + ?Sized> Storage<'a, DB> {
 
         let extend_patch_latency = BLOCK_TIMINGS.extend_patch.start();
         for (entry, parent_nibbles) in recovery_entries.into_iter().zip(parent_nibbles) {
-            self.updater.insert(entry, &parent_nibbles);
-            self.leaf_count += 1;
+            // Entries may overwrite existing ones (e.g., to repair a corrupted entry);
+            // this doesn't change the leaf count.
+            let (log, _) = self.updater.insert(entry, &parent_nibbles);
+            if matches!(log, TreeLogEntry::Inserted) {
+                self.leaf_count += 1;
+            }
         }
         let extend_patch_latency = extend_patch_latency.observe();
         tracing::debug!("Tree traversal stage took {extend_patch_latency:?}");
@@ -587,15 +587,31 @@ impl WorkingPatchSet {
     pub fn load_greatest_key<DB: Database + ?Sized>(
         &mut self,
         db: &DB,
+    ) -> Option<(LeafNode, LoadAncestorsResult)> {
+        self.load_boundary_key(db, InternalNode::last_child_ref)
+    }
+
+    pub fn load_least_key<DB: Database + ?Sized>(
+        &mut self,
+        db: &DB,
+    ) -> Option<(LeafNode, LoadAncestorsResult)> {
+        self.load_boundary_key(db, InternalNode::first_child_ref)
+    }
+
+    /// Descends from the root to a leaf, choosing a child of each internal node using `select_child`.
+    fn load_boundary_key<DB: Database + ?Sized>(
+        &mut self,
+        db: &DB,
+        select_child: fn(&InternalNode) -> (u8, &ChildRef),
     ) -> Option<(LeafNode, LoadAncestorsResult)> {
         let mut nibbles = Nibbles::EMPTY;
         let mut db_reads = 0;
-        let greatest_leaf = loop {
+        let boundary_leaf = loop {
             match self.get(&nibbles) {
                 None => return None,
                 Some(Node::Leaf(leaf)) => break *leaf,
                 Some(Node::Internal(node)) => {
-                    let (next_nibble, child_ref) = node.last_child_ref();
+                    let (next_nibble, child_ref) = select_child(node);
                     nibbles = nibbles.push(next_nibble).unwrap();
                     // ^ `unwrap()` is safe; there can be no internal nodes on the bottom-most tree level
                     let child_key = nibbles.with_version(child_ref.version);
@@ -611,7 +627,7 @@ impl WorkingPatchSet {
             longest_prefixes: vec![nibbles],
             db_reads,
         };
-        Some((greatest_leaf, result))
+        Some((boundary_leaf, result))
     }
 
     /// Creates a Merkle proof for the specified `key`, which has given `parent_nibbles`
@@ -784,4 +800,42 @@ mod tests {
         assert_eq!(load_result.longest_prefixes[0].nibble_count(), 2);
         assert_eq!(load_result.db_reads, 2);
     }
+
+    #[test]
+    fn loading_least_key() {
+        let mut patch = WorkingPatchSet::new(0, Root::Empty);
+        let load_result = patch.load_least_key(&PatchSet::default());
+        assert!(load_result.is_none());
+
+        let mut db = PatchSet::default();
+        let key = Key::from_little_endian(&[0xa0; 32]);
+        let (_, patch) =
+            Storage::new(&db, &(), 0, true).extend(vec![TreeEntry::new(key, 1, ValueHash::zero())]);
+        db.apply_patch(patch);
+
+        let mut patch = WorkingPatchSet::new(1, db.root(0).unwrap());
+        let (least_leaf, load_result) = patch.load_least_key(&db).unwrap();
+        assert_eq!(least_leaf.full_key, key);
+        assert_eq!(load_result.longest_prefixes[0].nibble_count(), 0);
+        assert_eq!(load_result.db_reads, 0);
+
+        let lesser_key = Key::from(1234_u64);
+        let (_, patch) = Storage::new(&db, &(), 1, true).extend(vec![TreeEntry::new(
+            lesser_key,
+            2,
+            ValueHash::zero(),
+        )]);
+        db.apply_patch(patch);
+
+        let mut patch = WorkingPatchSet::new(2, db.root(1).unwrap());
+        let (least_leaf, load_result) = patch.load_least_key(&db).unwrap();
+        assert_eq!(least_leaf.full_key, lesser_key);
+        assert_eq!(load_result.longest_prefixes.len(), 1);
+        assert_eq!(load_result.longest_prefixes[0].nibble_count(), 1);
+        assert_eq!(load_result.db_reads, 1);
+
+        // The greatest key must not be influenced by loading the least one.
+        let (greatest_leaf, _) = patch.load_greatest_key(&db).unwrap();
+        assert_eq!(greatest_leaf.full_key, key);
+    }
 }
@@ -1,9 +1,13 @@
 //! RocksDB implementation of [`Database`].
 
-use std::path::Path;
+use std::{mem, path::Path};
 
 use rayon::prelude::*;
-use zksync_storage::{db::NamedColumnFamily, rocksdb::DBPinnableSlice, RocksDB};
+use zksync_storage::{
+    db::NamedColumnFamily,
+    rocksdb::{self, DBPinnableSlice},
+    RocksDB,
+};
 
 use crate::{
     errors::{DeserializeError, ErrorContext},
@@ -23,16 +27,20 @@ pub enum MerkleTreeColumnFamily {
     Tree,
     /// Column family containing stale node keys that are eventually removed by the pruning logic.
     StaleKeys,
+    /// Column family containing the recovery journal. The journal is an arbitrary key–value mapping
+    /// that can be used to track tree recovery progress with finer granularity than recovered chunks.
+    RecoveryJournal,
 }
 
 impl NamedColumnFamily for MerkleTreeColumnFamily {
     const DB_NAME: &'static str = "merkle_tree";
-    const ALL: &'static [Self] = &[Self::Tree, Self::StaleKeys];
+    const ALL: &'static [Self] = &[Self::Tree, Self::StaleKeys, Self::RecoveryJournal];
 
     fn name(&self) -> &'static str {
         match self {
             Self::Tree => "default",
             Self::StaleKeys => "stale_keys",
+            Self::RecoveryJournal => "recovery_journal",
         }
     }
 
@@ -70,6 +78,16 @@ impl RocksDBWrapper {
         Self::from(RocksDB::new(path))
     }
 
+    /// Opens an existing tree database at the specified directory in the read-only mode
+    /// (see [`RocksDB::open_read_only()`]). Operations writing to the database will panic.
+    ///
+    /// # Errors
+    ///
+    /// Propagates RocksDB I/O errors, e.g. if there's no database at `path`.
+    pub fn open_read_only(path: &Path) -> Result<Self, rocksdb::Error> {
+        RocksDB::open_read_only(path).map(Self::from)
+    }
+
     /// Sets the chunk size for multi-get operations. The requested keys will be split
     /// into chunks of this size and requested in parallel using `rayon`. Setting chunk size
     /// to a large value (e.g., `usize::MAX`) will effectively disable parallelism.
@@ -87,6 +105,11 @@ impl RocksDBWrapper {
         self.multi_get_chunk_size = chunk_size;
     }
 
+    /// Returns the chunk size for multi-get operations set via [`Self::set_multi_get_chunk_size()`].
+    pub fn multi_get_chunk_size(&self) -> usize {
+        self.multi_get_chunk_size
+    }
+
     fn raw_node(&self, key: &[u8]) -> Option<Vec<u8>> {
         self.db
             .get_cf(MerkleTreeColumnFamily::Tree, key)
@@ -129,6 +152,151 @@ impl RocksDBWrapper {
         })
     }
 
+    /// Returns the value for the specified `key` in the recovery journal.
+    ///
+    /// # Panics
+    ///
+    /// Panics on RocksDB I/O errors.
+    pub fn recovery_journal_entry(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.db
+            .get_cf(MerkleTreeColumnFamily::RecoveryJournal, key)
+            .expect("Failed reading from RocksDB")
+    }
+
+    /// Returns all entries in the recovery journal in the lexical key order.
+    ///
+    /// # Panics
+    ///
+    /// Panics on RocksDB I/O errors.
+    pub fn recovery_journal_entries(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.db
+            .prefix_iterator_cf(MerkleTreeColumnFamily::RecoveryJournal, &[])
+            .map(|(key, value)| (key.into_vec(), value.into_vec()))
+            .collect()
+    }
+
+    /// Sets the value for the specified `key` in the recovery journal, or removes the entry
+    /// if `value` is `None`.
+    ///
+    /// # Panics
+    ///
+    /// Panics on RocksDB I/O errors.
+    pub fn set_recovery_journal_entry(&mut self, key: &[u8], value: Option<&[u8]>) {
+        let journal_cf = MerkleTreeColumnFamily::RecoveryJournal;
+        let mut write_batch = self.db.new_write_batch();
+        if let Some(value) = value {
+            write_batch.put_cf(journal_cf, key, value);
+        } else {
+            write_batch.delete_cf(journal_cf, key);
+        }
+        self.db
+            .write(write_batch)
+            .expect("Failed writing a batch to RocksDB");
+    }
+
+    /// Removes all entries from the recovery journal.
+    ///
+    /// # Panics
+    ///
+    /// Panics on RocksDB I/O errors.
+    pub fn clear_recovery_journal(&mut self) {
+        let journal_cf = MerkleTreeColumnFamily::RecoveryJournal;
+        let mut write_batch = self.db.new_write_batch();
+        for (key, _) in self.db.prefix_iterator_cf(journal_cf, &[]) {
+            write_batch.delete_cf(journal_cf, &key);
+        }
+        self.db
+            .write(write_batch)
+            .expect("Failed writing a batch to RocksDB");
+    }
+
+    /// Removes all data from the database, including the tree manifest and the recovery journal.
+    ///
+    /// # Panics
+    ///
+    /// Panics on RocksDB I/O errors.
+    pub fn clear(&mut self) {
+        // All keys in the tree and stale keys column families are shorter than 64 bytes
+        // and start with a version, so they are covered by this range.
+        const KEYS_END: &[u8] = &[u8::MAX; 64];
+
+        let mut write_batch = self.db.new_write_batch();
+        for cf in [
+            MerkleTreeColumnFamily::Tree,
+            MerkleTreeColumnFamily::StaleKeys,
+        ] {
+            write_batch.delete_range_cf(cf, &[] as &[_]..KEYS_END);
+        }
+        self.db
+            .write(write_batch)
+            .expect("Failed writing a batch to RocksDB");
+        self.clear_recovery_journal();
+    }
+
+    /// Copies all data from the `source` database, including the tree manifest and the recovery journal.
+    /// This database should be empty; otherwise, its data is mixed with the copied data.
+    ///
+    /// # Panics
+    ///
+    /// Panics on RocksDB I/O errors.
+    pub fn copy_from(&mut self, source: &Self) {
+        /// Maximum number of key-value pairs in a single write batch.
+        const BATCH_SIZE: usize = 10_000;
+
+        for &cf in MerkleTreeColumnFamily::ALL {
+            let mut write_batch = self.db.new_write_batch();
+            let mut batch_len = 0;
+            for (key, value) in source.db.prefix_iterator_cf(cf, &[]) {
+                write_batch.put_cf(cf, &key, &value);
+                batch_len += 1;
+                if batch_len == BATCH_SIZE {
+                    let full_batch = mem::replace(&mut write_batch, self.db.new_write_batch());
+                    self.db
+                        .write(full_batch)
+                        .expect("Failed writing a batch to RocksDB");
+                    batch_len = 0;
+                }
+            }
+            self.db
+                .write(write_batch)
+                .expect("Failed writing a batch to RocksDB");
+        }
+    }
+
+    /// Returns the path to the RocksDB directory.
+    pub fn path(&self) -> &Path {
+        self.db.path()
+    }
+
+    /// Creates a checkpoint of the database at the specified `path`, which must not exist.
+    /// The checkpoint can be opened as a separate tree instance.
+    ///
+    /// # Errors
+    ///
+    /// Propagates RocksDB I/O errors.
+    pub fn create_checkpoint(&self, path: &Path) -> Result<(), rocksdb::Error> {
+        self.db.create_checkpoint(path)
+    }
+
+    /// Runs manual RocksDB compaction for all column families and returns the number of bytes reclaimed
+    /// (based on total SST file sizes before and after compaction). Compaction is mostly useful after
+    /// [pruning](crate::MerkleTreePruner) removes a large number of nodes.
+    pub fn compact(&self) -> u64 {
+        let size_before = self.db.total_sst_files_size();
+        self.db.compact();
+        let size_after = self.db.total_sst_files_size();
+        size_before.saturating_sub(size_after)
+    }
+
+    /// Flushes all writes to disk (see [`RocksDB::flush()`]).
+    ///
+    /// # Errors
+    ///
+    /// Propagates RocksDB I/O errors.
+    pub fn flush(&self) -> Result<(), rocksdb::Error> {
+        self.db.flush()
+    }
+
     /// Returns the wrapped RocksDB instance.
     pub fn into_inner(self) -> RocksDB<MerkleTreeColumnFamily> {
         self.db
@@ -336,6 +504,52 @@ mod tests {
         assert_contains_exactly_keys(&db, &expected_keys);
     }
 
+    #[test]
+    fn copying_db() {
+        let dir = TempDir::new().expect("failed creating temporary dir for RocksDB");
+        let mut source = RocksDBWrapper::new(&dir.path().join("source"));
+        let root = Root::new(2, Node::Internal(InternalNode::default()));
+        let nodes = generate_nodes(0, &[1, 2]);
+        let mut expected_keys: HashSet<_> = nodes.keys().copied().collect();
+        expected_keys.insert(NodeKey::empty(0));
+        source.apply_patch(create_patch(0, root, nodes));
+        source.set_recovery_journal_entry(b"chunk", Some(b"progress"));
+
+        let mut target = RocksDBWrapper::new(&dir.path().join("target"));
+        target.copy_from(&source);
+        assert_contains_exactly_keys(&target, &expected_keys);
+        assert_eq!(target.manifest(), source.manifest());
+        assert_eq!(
+            target.recovery_journal_entry(b"chunk").unwrap(),
+            b"progress"
+        );
+    }
+
+    #[test]
+    fn reading_db_opened_read_only() {
+        let dir = TempDir::new().expect("failed creating temporary dir for RocksDB");
+        let mut db = RocksDBWrapper::new(dir.path());
+        let root = Root::new(2, Node::Internal(InternalNode::default()));
+        let nodes = generate_nodes(0, &[1, 2]);
+        let mut expected_keys: HashSet<_> = nodes.keys().copied().collect();
+        expected_keys.insert(NodeKey::empty(0));
+        db.apply_patch(create_patch(0, root, nodes));
+        db.set_recovery_journal_entry(b"chunk1", Some(b"progress"));
+        db.set_recovery_journal_entry(b"chunk0", Some(b""));
+        db.db.flush().unwrap();
+
+        let read_only_db = RocksDBWrapper::open_read_only(dir.path()).unwrap();
+        assert_contains_exactly_keys(&read_only_db, &expected_keys);
+        assert_eq!(read_only_db.manifest(), db.manifest());
+        assert_eq!(
+            read_only_db.recovery_journal_entries(),
+            [
+                (b"chunk0".to_vec(), vec![]),
+                (b"chunk1".to_vec(), b"progress".to_vec())
+            ]
+        );
+    }
+
     fn assert_contains_exactly_keys(db: &RocksDBWrapper, expected_keys: &HashSet<NodeKey>) {
         let cf = MerkleTreeColumnFamily::Tree;
         let actual_keys: HashSet<_> = db
@@ -1,6 +1,6 @@
 //! Serialization of node types in the database.
 
-use std::str;
+use std::{collections::BTreeMap, str};
 
 use crate::{
     errors::{DeserializeError, DeserializeErrorKind, ErrorContext},
@@ -197,6 +197,8 @@ impl Node {
 }
 
 impl TreeTags {
+    const CUSTOM_TAG_PREFIX: &'static str = "custom.";
+
     /// Tags are serialized as a length-prefixed list of `(&str, &str)` tuples, where each
     /// `&str` is length-prefixed as well. All lengths are encoded using LEB128.
     fn deserialize(bytes: &mut &[u8]) -> Result<Self, DeserializeError> {
@@ -205,6 +207,7 @@ impl TreeTags {
         let mut hasher = None;
         let mut depth = None;
         let mut is_recovering = false;
+        let mut custom = BTreeMap::new();
 
         for _ in 0..tag_count {
             let key = Self::deserialize_str(bytes)?;
@@ -230,7 +233,12 @@ impl TreeTags {
                     })?;
                     is_recovering = parsed;
                 }
-                _ => return Err(DeserializeErrorKind::UnknownTag(key.to_owned()).into()),
+                _ => {
+                    let Some(custom_key) = key.strip_prefix(Self::CUSTOM_TAG_PREFIX) else {
+                        return Err(DeserializeErrorKind::UnknownTag(key.to_owned()).into());
+                    };
+                    custom.insert(custom_key.to_owned(), value.to_owned());
+                }
             }
         }
         Ok(Self {
@@ -238,6 +246,7 @@ impl TreeTags {
             hasher: hasher.ok_or(DeserializeErrorKind::MissingTag("hasher"))?,
             depth: depth.ok_or(DeserializeErrorKind::MissingTag("depth"))?,
             is_recovering,
+            custom,
         })
     }
 
@@ -259,7 +268,7 @@ impl TreeTags {
     }
 
     fn serialize(&self, buffer: &mut Vec<u8>) {
-        let entry_count = 3 + u64::from(self.is_recovering);
+        let entry_count = 3 + u64::from(self.is_recovering) + self.custom.len() as u64;
         leb128::write::unsigned(buffer, entry_count).unwrap();
         Self::serialize_str(buffer, "architecture");
         Self::serialize_str(buffer, &self.architecture);
@@ -271,6 +280,11 @@ impl TreeTags {
             Self::serialize_str(buffer, "is_recovering");
             Self::serialize_str(buffer, "true");
         }
+        for (custom_key, value) in &self.custom {
+            let key = format!("{}{custom_key}", Self::CUSTOM_TAG_PREFIX);
+            Self::serialize_str(buffer, &key);
+            Self::serialize_str(buffer, value);
+        }
     }
 }
 
@@ -340,6 +354,25 @@ mod tests {
         assert_eq!(manifest_copy, manifest);
     }
 
+    #[test]
+    fn serializing_manifest_with_custom_tags() {
+        let mut manifest = Manifest::new(42, &());
+        let custom_tags = &mut manifest.tags.as_mut().unwrap().custom;
+        custom_tags.insert("chunk_size".to_owned(), "1000".to_owned());
+        let mut buffer = vec![];
+        manifest.serialize(&mut buffer);
+        assert_eq!(buffer[0], 42); // version count
+        assert_eq!(buffer[1], 4); // number of tags
+        assert_eq!(
+            buffer[2..],
+            *b"\x0Carchitecture\x06AR16MT\x05depth\x03256\x06hasher\x08no_op256\x11custom.chunk_size\x041000"
+        );
+        // ^ length-prefixed tag names and values
+
+        let manifest_copy = Manifest::deserialize(&buffer).unwrap();
+        assert_eq!(manifest_copy, manifest);
+    }
+
     #[test]
     fn manifest_serialization_errors() {
         let manifest = Manifest::new(42, &());
@@ -2,7 +2,7 @@
 //! some of these types are declared as public and can be even exported using the `unstable` module.
 //! Still, logically these types are private, so adding them to new public APIs etc. is a logical error.
 
-use std::{fmt, num::NonZeroU64};
+use std::{collections::BTreeMap, fmt, num::NonZeroU64, ops::RangeInclusive};
 
 use crate::{
     hasher::{HashTree, InternalNodeCache},
@@ -25,6 +25,8 @@ pub(crate) struct TreeTags {
     pub depth: usize,
     pub hasher: String,
     pub is_recovering: bool,
+    /// Custom tags set by the tree user. Serialized with the `custom.` prefix.
+    pub custom: BTreeMap<String, String>,
 }
 
 impl TreeTags {
@@ -36,6 +38,7 @@ impl TreeTags {
             hasher: hasher.name().to_owned(),
             depth: TREE_DEPTH,
             is_recovering: false,
+            custom: BTreeMap::new(),
         }
     }
 
@@ -94,6 +97,11 @@ impl Manifest {
         }
     }
 
+    /// Returns custom tags set for the tree, or `None` if the manifest has no tags.
+    pub fn custom_tags(&self) -> Option<&BTreeMap<String, String>> {
+        Some(&self.tags.as_ref()?.custom)
+    }
+
     #[cfg(test)]
     pub(crate) fn new(version_count: u64, hasher: &dyn HashTree) -> Self {
         Self {
@@ -182,6 +190,18 @@ impl Nibbles {
         &self.bytes
     }
 
+    /// Returns the range of keys having these nibbles as a prefix.
+    pub fn key_range(&self) -> RangeInclusive<Key> {
+        let start = Key::from_big_endian(&self.bytes);
+        let prefix_bits = self.nibble_count * 4;
+        let end = if prefix_bits == TREE_DEPTH {
+            start
+        } else {
+            start | (Key::MAX >> prefix_bits)
+        };
+        start..=end
+    }
+
     /// Extracts the last nibble and the parent sequence of nibbles
     /// (i.e., one with the last nibble truncated). If this sequence of nibbles is empty,
     /// returns `None`.
@@ -456,6 +476,11 @@ impl InternalNode {
         self.children.values_mut()
     }
 
+    pub(crate) fn first_child_ref(&self) -> (u8, &ChildRef) {
+        self.children.iter().next().unwrap()
+        // ^ `unwrap()` is safe by construction; all persisted internal nodes are not empty
+    }
+
     pub(crate) fn last_child_ref(&self) -> (u8, &ChildRef) {
         self.children.last().unwrap()
         // ^ `unwrap()` is safe by construction; all persisted internal nodes are not empty
@@ -688,4 +713,22 @@ mod tests {
         assert_ne!(nibbles, other_nibbles);
         assert!(nibbles > other_nibbles);
     }
+
+    #[test]
+    fn nibbles_key_range() {
+        let range = Nibbles::EMPTY.key_range();
+        assert_eq!(range, U256::zero()..=U256::MAX);
+
+        let nibbles = Nibbles::new(&TEST_KEY, 1);
+        let range = nibbles.key_range();
+        assert_eq!(*range.start(), U256([0, 0, 0, 0x_d000_0000_0000_0000]));
+        assert_eq!(
+            *range.end(),
+            U256([u64::MAX, u64::MAX, u64::MAX, 0x_dfff_ffff_ffff_ffff])
+        );
+        assert!(range.contains(&TEST_KEY));
+
+        let nibbles = Nibbles::new(&TEST_KEY, 64);
+        assert_eq!(nibbles.key_range(), TEST_KEY..=TEST_KEY);
+    }
 }
@@ -111,6 +111,42 @@ fn basic_workflow_multiblock() {
     assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(12));
 }
 
+#[test]
+fn checking_retained_versions_after_pruning() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let logs = gen_storage_logs();
+    let db = RocksDB::new(temp_dir.as_ref());
+    let mut tree = ZkSyncTree::new_lightweight(db.into());
+    for block in logs.chunks(9) {
+        tree.process_l1_batch(block);
+    }
+    tree.save();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(12));
+    for l1_batch_number in 0..12 {
+        assert!(tree.is_l1_batch_retained(L1BatchNumber(l1_batch_number)));
+    }
+    assert!(!tree.is_l1_batch_retained(L1BatchNumber(12)));
+
+    let (pruner, _handle) = tree.reader().pruner(2);
+    let outcome = pruner.run_to_completion();
+    assert!(outcome.pruned_key_count > 0);
+
+    for l1_batch_number in 0..9 {
+        let l1_batch_number = L1BatchNumber(l1_batch_number);
+        assert!(
+            !tree.is_l1_batch_retained(l1_batch_number),
+            "{l1_batch_number}"
+        );
+    }
+    for l1_batch_number in 9..12 {
+        let l1_batch_number = L1BatchNumber(l1_batch_number);
+        assert!(
+            tree.is_l1_batch_retained(l1_batch_number),
+            "{l1_batch_number}"
+        );
+    }
+}
+
 #[test]
 fn filtering_out_no_op_writes() {
     let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
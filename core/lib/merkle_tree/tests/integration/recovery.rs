@@ -24,14 +24,82 @@ fn recovery_basics() {
     let (kvs, expected_hash) = &*ENTRIES_AND_HASH;
     let mut recovery_entries: Vec<_> = kvs.clone();
     recovery_entries.sort_unstable_by_key(|entry| entry.key);
+    let least_key = recovery_entries[0].key;
     let greatest_key = recovery_entries[99].key;
 
     let recovered_version = 123;
     let mut recovery = MerkleTreeRecovery::new(PatchSet::default(), recovered_version);
+    assert_eq!(recovery.leaf_count(), 0);
     recovery.extend_linear(recovery_entries);
 
+    assert_eq!(recovery.least_key(), Some(least_key));
     assert_eq!(recovery.last_processed_key(), Some(greatest_key));
     assert_eq!(recovery.root_hash(), *expected_hash);
+    assert_eq!(recovery.leaf_count(), kvs.len() as u64);
+
+    let tree = MerkleTree::new(recovery.finalize());
+    tree.verify_consistency(recovered_version, true).unwrap();
+}
+
+#[test]
+fn overwriting_entries_during_recovery() {
+    let (kvs, expected_hash) = &*ENTRIES_AND_HASH;
+    let recovered_version = 123;
+    let mut recovery = MerkleTreeRecovery::new(PatchSet::default(), recovered_version);
+    let mut corrupted_entries = kvs.clone();
+    for entry in &mut corrupted_entries[..10] {
+        entry.value = ValueHash::repeat_byte(0xff);
+    }
+    recovery.extend_random(corrupted_entries);
+    assert_ne!(recovery.root_hash(), *expected_hash);
+
+    // Overwriting entries must not change the leaf count.
+    recovery.extend_random(kvs[..10].to_vec());
+    assert_eq!(recovery.leaf_count(), kvs.len() as u64);
+    assert_eq!(recovery.root_hash(), *expected_hash);
+
+    let mut tree = MerkleTree::new(recovery.finalize());
+    tree.verify_consistency(recovered_version, true).unwrap();
+    test_tree_after_recovery(&mut tree, recovered_version, *expected_hash);
+}
+
+#[test]
+fn pruning_stale_keys_before_finalization() {
+    let (kvs, expected_hash) = &*ENTRIES_AND_HASH;
+    let recovered_version = 123;
+    let mut recovery = MerkleTreeRecovery::new(PatchSet::default(), recovered_version);
+    for chunk in kvs.chunks(10) {
+        recovery.extend_random(chunk.to_vec());
+    }
+    recovery.prune_stale_keys();
+    assert_eq!(recovery.root_hash(), *expected_hash);
+    // Repeated pruning should be a no-op.
+    recovery.prune_stale_keys();
+    assert_eq!(recovery.root_hash(), *expected_hash);
+
+    let mut tree = MerkleTree::new(recovery.finalize());
+    tree.verify_consistency(recovered_version, true).unwrap();
+    test_tree_after_recovery(&mut tree, recovered_version, *expected_hash);
+}
+
+#[test_casing(2, RecoveryKind::ALL)]
+fn recovery_with_dedicated_thread_pool(kind: RecoveryKind) {
+    let (kvs, expected_hash) = &*ENTRIES_AND_HASH;
+    let mut recovery_entries = kvs.clone();
+    if matches!(kind, RecoveryKind::Linear) {
+        recovery_entries.sort_unstable_by_key(|entry| entry.key);
+    }
+
+    let recovered_version = 123;
+    let mut recovery = MerkleTreeRecovery::new(PatchSet::default(), recovered_version);
+    recovery.use_dedicated_thread_pool(4).unwrap();
+    for chunk in recovery_entries.chunks(10) {
+        match kind {
+            RecoveryKind::Linear => recovery.extend_linear(chunk.to_vec()),
+            RecoveryKind::Random => recovery.extend_random(chunk.to_vec()),
+        }
+    }
+    assert_eq!(recovery.root_hash(), *expected_hash);
 
     let tree = MerkleTree::new(recovery.finalize());
     tree.verify_consistency(recovered_version, true).unwrap();
@@ -134,4 +202,75 @@ mod rocksdb {
         let db = RocksDBWrapper::new(temp_dir.path());
         test_recovery_in_chunks(db, kind, chunk_size);
     }
+
+    #[test]
+    fn persisting_recovery_journal() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = RocksDBWrapper::new(temp_dir.path());
+        let mut recovery = MerkleTreeRecovery::new(db, 42);
+        assert_eq!(recovery.recovery_journal_entry(b"chunk"), None);
+        recovery.set_recovery_journal_entry(b"chunk", Some(b"progress"));
+        recovery.set_recovery_journal_entry(b"other_chunk", Some(b"other_progress"));
+        recovery.set_recovery_journal_entry(b"other_chunk", None);
+        drop(recovery);
+
+        let db = RocksDBWrapper::new(temp_dir.path());
+        let mut recovery = MerkleTreeRecovery::new(db, 42);
+        let entry = recovery.recovery_journal_entry(b"chunk");
+        assert_eq!(entry.as_deref(), Some(b"progress" as &[_]));
+        assert_eq!(recovery.recovery_journal_entry(b"other_chunk"), None);
+
+        recovery.clear_recovery_journal();
+        assert_eq!(recovery.recovery_journal_entry(b"chunk"), None);
+    }
+
+    #[test]
+    fn resuming_recovery_for_read_only_db() {
+        let (kvs, _) = &*ENTRIES_AND_HASH;
+        let temp_dir = TempDir::new().unwrap();
+        let db = RocksDBWrapper::new(temp_dir.path());
+        let mut recovery = MerkleTreeRecovery::new(db, 42);
+        recovery.extend_random(kvs[..50].to_vec());
+        recovery.set_recovery_journal_entry(b"chunk", Some(b"progress"));
+        let root_hash = recovery.root_hash();
+        drop(recovery);
+
+        let db = RocksDBWrapper::open_read_only(temp_dir.path()).unwrap();
+        let recovery = MerkleTreeRecovery::new(db, 42);
+        assert_eq!(recovery.root_hash(), root_hash);
+        assert_eq!(recovery.leaf_count(), 50);
+        let least_key = kvs[..50].iter().map(|entry| entry.key).min();
+        assert_eq!(recovery.least_key(), least_key);
+        let greatest_key = kvs[..50].iter().map(|entry| entry.key).max();
+        assert_eq!(recovery.last_processed_key(), greatest_key);
+        assert_eq!(
+            recovery.recovery_journal_entries(),
+            [(b"chunk".to_vec(), b"progress".to_vec())]
+        );
+    }
+
+    #[test]
+    fn resetting_recovery() {
+        let (kvs, expected_hash) = &*ENTRIES_AND_HASH;
+        let temp_dir = TempDir::new().unwrap();
+        let db = RocksDBWrapper::new(temp_dir.path());
+        let mut recovery = MerkleTreeRecovery::new(db, 42);
+        recovery.update_custom_tags(|tags| {
+            tags.insert("test".to_owned(), "value".to_owned());
+        });
+        recovery.extend_random(kvs[..50].to_vec());
+        recovery.set_recovery_journal_entry(b"chunk", Some(b"progress"));
+
+        let db = recovery.reset();
+        assert!(db.manifest().is_none());
+        let mut recovery = MerkleTreeRecovery::new(db, 42);
+        assert!(recovery.custom_tags().is_empty());
+        assert_eq!(recovery.recovery_journal_entry(b"chunk"), None);
+        assert_eq!(recovery.last_processed_key(), None);
+
+        recovery.extend_random(kvs.clone());
+        assert_eq!(recovery.root_hash(), *expected_hash);
+        let tree = MerkleTree::new(recovery.finalize());
+        tree.verify_consistency(42, true).unwrap();
+    }
 }
@@ -11,8 +11,9 @@ use std::{
 };
 
 use rocksdb::{
-    properties, BlockBasedOptions, Cache, ColumnFamily, ColumnFamilyDescriptor, DBPinnableSlice,
-    Direction, IteratorMode, Options, PrefixRange, ReadOptions, WriteOptions, DB,
+    checkpoint::Checkpoint, properties, BlockBasedOptions, Cache, ColumnFamily,
+    ColumnFamilyDescriptor, DBPinnableSlice, Direction, IteratorMode, Options, PrefixRange,
+    ReadOptions, WriteOptions, DB,
 };
 
 use crate::metrics::{RocksdbLabels, RocksdbSizeMetrics, METRICS};
@@ -96,6 +97,9 @@ pub(crate) struct RocksDBInner {
     db_name: &'static str,
     cf_names: HashSet<&'static str>,
     _registry_entry: RegistryEntry,
+    /// Time when writes were first observed to be stalled (i.e., stopped or delayed) by [`Self::collect_metrics()`],
+    /// or `None` if writes were not stalled during the last metrics collection.
+    write_stall_started_at: Mutex<Option<Instant>>,
     // Importantly, `Cache`s must be dropped after `DB`, so we place them as the last field
     // (fields in a struct are dropped in the declaration order).
     _caches: RocksDBCaches,
@@ -103,6 +107,7 @@ pub(crate) struct RocksDBInner {
 
 impl RocksDBInner {
     pub(crate) fn collect_metrics(&self, metrics: &RocksdbSizeMetrics) {
+        let mut any_writes_stopped = false;
         for &cf_name in &self.cf_names {
             let cf = self.db.cf_handle(cf_name).unwrap();
             // ^ `unwrap()` is safe (CF existence is checked during DB initialization)
@@ -110,6 +115,7 @@ impl RocksDBInner {
 
             let writes_stopped = self.int_property(cf, properties::IS_WRITE_STOPPED);
             let writes_stopped = writes_stopped == Some(1);
+            any_writes_stopped |= writes_stopped;
             metrics.writes_stopped[&labels].set(writes_stopped.into());
 
             let num_immutable_memtables =
@@ -157,6 +163,26 @@ impl RocksDBInner {
                 metrics.index_and_filters_size[&labels].set(size);
             }
         }
+
+        let delayed_write_rate = self
+            .db
+            .property_int_value(properties::ACTUAL_DELAYED_WRITE_RATE)
+            .ok()
+            .flatten()
+            .unwrap_or(0);
+        metrics.delayed_write_rate[&self.db_name.into()].set(delayed_write_rate);
+        let is_stalled = any_writes_stopped || delayed_write_rate > 0;
+        let mut stall_started_at = self
+            .write_stall_started_at
+            .lock()
+            .expect("write stall timestamp is poisoned");
+        let stall_duration = if is_stalled {
+            stall_started_at.get_or_insert_with(Instant::now).elapsed()
+        } else {
+            *stall_started_at = None;
+            Duration::ZERO
+        };
+        metrics.write_stall_duration[&self.db_name.into()].set(stall_duration);
     }
 
     fn int_property(&self, cf: &ColumnFamily, name: &CStr) -> Option<u64> {
@@ -271,6 +297,25 @@ pub struct RocksDBOptions {
     /// Setting this to a reasonably large value (order of 512 MiB) is helpful for large DBs that experience
     /// write stalls. If not set, large CFs will not be configured specially.
     pub large_memtable_capacity: Option<usize>,
+    /// Maximum number of memtables (including ones being flushed) for large CFs. Larger values allow to absorb
+    /// write bursts during bulk loading. If not set, the default number is used.
+    pub large_max_write_buffer_number: Option<usize>,
+    /// Limit on the total byte size of memtables across all CFs. If not set, the total size is not limited
+    /// (memtables are still limited per CF).
+    pub total_write_buffer_size: Option<usize>,
+    /// Maximum number of files that can be kept open by RocksDB. If not set, the number is not limited.
+    pub max_open_files: Option<usize>,
+    /// Limit on the rate of background writes (memtable flushes and compactions) in bytes per second. Limiting
+    /// background I/O prevents RocksDB from starving other processes sharing the disk, at the cost of more frequent
+    /// write stalls. If not set, background writes are not limited.
+    pub background_write_rate_limit: Option<usize>,
+    /// Disables automatic compaction for all CFs. This speeds up bulk loading, but the database must be
+    /// compacted manually (see [`RocksDB::compact()`]) afterwards; otherwise, reads will degrade.
+    pub disable_auto_compactions: bool,
+    /// Disables the write-ahead log (WAL) for all writes, which speeds up bulk loading. Writes that are not flushed
+    /// to SST files are lost if the process crashes; use [`RocksDB::flush()`] to make writes durable.
+    /// Sync writes (see [`RocksDB::with_sync_writes()`]) are ignored if this option is set.
+    pub disable_wal: bool,
     /// Timeout to wait for the database to run compaction on stalled writes during startup or
     /// when the corresponding RocksDB error is encountered.
     pub stalled_writes_retries: StalledWritesRetries,
@@ -281,6 +326,12 @@ impl Default for RocksDBOptions {
         Self {
             block_cache_capacity: None,
             large_memtable_capacity: None,
+            large_max_write_buffer_number: None,
+            total_write_buffer_size: None,
+            max_open_files: None,
+            background_write_rate_limit: None,
+            disable_auto_compactions: false,
+            disable_wal: false,
             stalled_writes_retries: StalledWritesRetries::new(Duration::from_secs(10)),
         }
     }
@@ -293,7 +344,7 @@ impl Default for RocksDBOptions {
 pub struct RocksDB<CF> {
     inner: Arc<RocksDBInner>,
     sync_writes: bool,
-    stalled_writes_retries: StalledWritesRetries,
+    options: RocksDBOptions,
     _cf: PhantomData<CF>,
 }
 
@@ -304,7 +355,18 @@ impl<CF: NamedColumnFamily> RocksDB<CF> {
 
     pub fn with_options(path: &Path, options: RocksDBOptions) -> Self {
         let caches = RocksDBCaches::new(options.block_cache_capacity);
-        let db_options = Self::rocksdb_options(None, None);
+        let mut db_options = Self::rocksdb_options(None, None);
+        if let Some(size) = options.total_write_buffer_size {
+            db_options.set_db_write_buffer_size(size);
+        }
+        if let Some(count) = options.max_open_files {
+            db_options.set_max_open_files(i32::try_from(count).unwrap_or(i32::MAX));
+        }
+        if let Some(rate_limit) = options.background_write_rate_limit {
+            // Refill period and fairness are the RocksDB defaults.
+            let rate_limit = i64::try_from(rate_limit).unwrap_or(i64::MAX);
+            db_options.set_ratelimiter(rate_limit, 100_000, 10);
+        }
         let existing_cfs = DB::list_cf(&db_options, path).unwrap_or_else(|err| {
             tracing::warn!(
                 "Failed getting column families for RocksDB `{}` at `{}`, assuming CFs are empty; {err}",
@@ -350,7 +412,16 @@ impl<CF: NamedColumnFamily> RocksDB<CF> {
                 block_based_options.set_block_cache(cache);
             }
             let memtable_capacity = options.large_memtable_capacity.filter(|_| requires_tuning);
-            let cf_options = Self::rocksdb_options(memtable_capacity, Some(block_based_options));
+            let mut cf_options =
+                Self::rocksdb_options(memtable_capacity, Some(block_based_options));
+            // Must be set after `rocksdb_options()` since the latter overwrites the number of memtables.
+            if let Some(count) = options
+                .large_max_write_buffer_number
+                .filter(|_| requires_tuning)
+            {
+                cf_options.set_max_write_buffer_number(count as i32);
+            }
+            cf_options.set_disable_auto_compactions(options.disable_auto_compactions);
             ColumnFamilyDescriptor::new(cf_name, cf_options)
         });
 
@@ -360,6 +431,7 @@ impl<CF: NamedColumnFamily> RocksDB<CF> {
             db_name: CF::DB_NAME,
             cf_names,
             _registry_entry: RegistryEntry::new(),
+            write_stall_started_at: Mutex::new(None),
             _caches: caches,
         });
         RocksdbSizeMetrics::register(CF::DB_NAME, Arc::downgrade(&inner));
@@ -374,11 +446,50 @@ impl<CF: NamedColumnFamily> RocksDB<CF> {
         Self {
             inner,
             sync_writes: false,
-            stalled_writes_retries: options.stalled_writes_retries,
+            options,
             _cf: PhantomData,
         }
     }
 
+    /// Opens an existing database at `path` in the read-only mode, e.g. to inspect it with an offline tool.
+    /// Unlike [`Self::with_options()`], missing column families are not created; accessing such a column family
+    /// will panic. Writes to the returned instance fail. The read-only instance doesn't report metrics,
+    /// so that it doesn't shadow metrics of the instance opened by the node.
+    ///
+    /// # Errors
+    ///
+    /// Propagates RocksDB I/O errors, e.g. if there's no database at `path`.
+    pub fn open_read_only(path: &Path) -> Result<Self, rocksdb::Error> {
+        let db_options = Self::rocksdb_options(None, None);
+        let existing_cfs = DB::list_cf(&db_options, path)?;
+        let cf_names = CF::ALL
+            .iter()
+            .map(NamedColumnFamily::name)
+            .filter(|&name| existing_cfs.iter().any(|cf_name| cf_name == name))
+            .collect();
+        let db = DB::open_cf_for_read_only(&db_options, path, &existing_cfs, false)?;
+        let inner = Arc::new(RocksDBInner {
+            db,
+            db_name: CF::DB_NAME,
+            cf_names,
+            _registry_entry: RegistryEntry::new(),
+            write_stall_started_at: Mutex::new(None),
+            _caches: RocksDBCaches::new(None),
+        });
+
+        tracing::info!(
+            "Opened RocksDB `{}` at `{}` in the read-only mode",
+            CF::DB_NAME,
+            path.display()
+        );
+        Ok(Self {
+            inner,
+            sync_writes: false,
+            options: RocksDBOptions::default(),
+            _cf: PhantomData,
+        })
+    }
+
     /// Switches on sync writes in [`Self::write()`] and [`Self::put()`]. This has a performance
     /// penalty and is mostly useful for tests.
     #[must_use]
@@ -387,6 +498,76 @@ impl<CF: NamedColumnFamily> RocksDB<CF> {
         self
     }
 
+    /// Returns the path to the database directory.
+    pub fn path(&self) -> &Path {
+        self.inner.db.path()
+    }
+
+    /// Returns the options this database was opened with.
+    pub fn options(&self) -> RocksDBOptions {
+        self.options
+    }
+
+    /// Creates a checkpoint of the database at the specified `path`. A checkpoint is a consistent snapshot
+    /// of the database that can be opened as a separate RocksDB instance; SST files are hard-linked if `path`
+    /// is on the same filesystem as the database and are copied otherwise. The `path` directory must not exist.
+    ///
+    /// # Errors
+    ///
+    /// Propagates RocksDB I/O errors.
+    pub fn create_checkpoint(&self, path: &Path) -> Result<(), rocksdb::Error> {
+        let checkpoint = Checkpoint::new(&self.inner.db)?;
+        checkpoint.create_checkpoint(path)
+    }
+
+    /// Runs manual compaction for the entire key range of all column families. This method blocks
+    /// until compaction is complete and thus can take a long time for large databases.
+    pub fn compact(&self) {
+        for cf in CF::ALL {
+            let cf = self.inner.db.cf_handle(cf.name()).unwrap();
+            // ^ `unwrap()` is safe (CF existence is checked during DB initialization)
+            self.inner
+                .db
+                .compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+        }
+    }
+
+    /// Flushes memtables of all column families to SST files and syncs the write-ahead log. After this method
+    /// returns, all preceding writes are durable, including ones performed with the WAL disabled
+    /// (see [`RocksDBOptions::disable_wal`]).
+    ///
+    /// # Errors
+    ///
+    /// Propagates RocksDB I/O errors.
+    pub fn flush(&self) -> Result<(), rocksdb::Error> {
+        for cf in CF::ALL {
+            let cf = self.inner.db.cf_handle(cf.name()).unwrap();
+            // ^ `unwrap()` is safe (CF existence is checked during DB initialization)
+            self.inner.db.flush_cf(cf)?;
+        }
+        self.inner.db.flush_wal(true)
+    }
+
+    /// Returns the value of an integer RocksDB property (e.g., one of [`rocksdb::properties`]) for the specified
+    /// column family, or `None` if the property is not defined or cannot be read.
+    pub fn int_property(&self, cf: CF, name: &CStr) -> Option<u64> {
+        self.inner.int_property(self.column_family(cf), name)
+    }
+
+    /// Returns the total size of SST files for all column families in bytes. Column families
+    /// for which the size cannot be obtained are skipped.
+    pub fn total_sst_files_size(&self) -> u64 {
+        CF::ALL
+            .iter()
+            .filter_map(|cf| {
+                let cf = self.inner.db.cf_handle(cf.name()).unwrap();
+                // ^ `unwrap()` is safe (CF existence is checked during DB initialization)
+                self.inner
+                    .int_property(cf, properties::TOTAL_SST_FILES_SIZE)
+            })
+            .sum()
+    }
+
     fn rocksdb_options(
         memtable_capacity: Option<usize>,
         block_based_options: Option<BlockBasedOptions>,
@@ -447,7 +628,7 @@ impl<CF: NamedColumnFamily> RocksDB<CF> {
     }
 
     pub fn write<'a>(&'a self, batch: WriteBatch<'a, CF>) -> Result<(), rocksdb::Error> {
-        let retries = &self.stalled_writes_retries;
+        let retries = &self.options.stalled_writes_retries;
         let mut raw_batch = batch.inner;
         METRICS.report_batch_size(CF::DB_NAME, raw_batch.size_in_bytes());
 
@@ -457,7 +638,7 @@ impl<CF: NamedColumnFamily> RocksDB<CF> {
         }
 
         let raw_batch_bytes = raw_batch.data().to_vec();
-        let mut retries = self.stalled_writes_retries.intervals();
+        let mut retries = self.options.stalled_writes_retries.intervals();
         let mut stalled_write_reported = false;
         let started_at = Instant::now();
         loop {
@@ -493,7 +674,12 @@ impl<CF: NamedColumnFamily> RocksDB<CF> {
     }
 
     fn write_inner(&self, raw_batch: rocksdb::WriteBatch) -> Result<(), rocksdb::Error> {
-        if self.sync_writes {
+        if self.options.disable_wal {
+            // Sync writes require WAL, so they are not used.
+            let mut options = WriteOptions::new();
+            options.disable_wal(true);
+            self.inner.db.write_opt(raw_batch, &options)
+        } else if self.sync_writes {
             let mut options = WriteOptions::new();
             options.set_sync(true);
             self.inner.db.write_opt(raw_batch, &options)
@@ -702,6 +888,131 @@ mod tests {
         assert_eq!(value.unwrap(), b"value");
     }
 
+    #[test]
+    fn creating_checkpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = RocksDB::<NewColumnFamilies>::new(&temp_dir.path().join("db")).with_sync_writes();
+        let mut batch = db.new_write_batch();
+        batch.put_cf(NewColumnFamilies::Other, b"test", b"value");
+        db.write(batch).unwrap();
+
+        let checkpoint_path = temp_dir.path().join("checkpoint");
+        db.create_checkpoint(&checkpoint_path).unwrap();
+        // Changes after the checkpoint is created must not influence it.
+        let mut batch = db.new_write_batch();
+        batch.put_cf(NewColumnFamilies::Other, b"test", b"new_value");
+        db.write(batch).unwrap();
+
+        let checkpoint = RocksDB::<NewColumnFamilies>::new(&checkpoint_path);
+        let value = checkpoint
+            .get_cf(NewColumnFamilies::Other, b"test")
+            .unwrap();
+        assert_eq!(value.unwrap(), b"value");
+        // The checkpoint directory must not exist.
+        assert!(db.create_checkpoint(&checkpoint_path).is_err());
+    }
+
+    #[test]
+    fn opening_read_only_db() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("db");
+        assert!(RocksDB::<NewColumnFamilies>::open_read_only(&db_path).is_err());
+
+        let db = RocksDB::<NewColumnFamilies>::new(&db_path).with_sync_writes();
+        let mut batch = db.new_write_batch();
+        batch.put_cf(NewColumnFamilies::Other, b"test", b"value");
+        db.write(batch).unwrap();
+
+        // The database can be opened read-only while it's still open for writes.
+        let read_only_db = RocksDB::<NewColumnFamilies>::open_read_only(&db_path).unwrap();
+        let value = read_only_db
+            .get_cf(NewColumnFamilies::Other, b"test")
+            .unwrap();
+        assert_eq!(value.unwrap(), b"value");
+
+        let mut batch = read_only_db.new_write_batch();
+        batch.put_cf(NewColumnFamilies::Other, b"test", b"new_value");
+        read_only_db.write(batch).unwrap_err();
+    }
+
+    #[test]
+    fn flushing_writes_without_wal() {
+        let temp_dir = TempDir::new().unwrap();
+        let options = RocksDBOptions {
+            disable_wal: true,
+            ..RocksDBOptions::default()
+        };
+        // Sync writes must be ignored; RocksDB rejects them if WAL is disabled.
+        let db =
+            RocksDB::<NewColumnFamilies>::with_options(temp_dir.path(), options).with_sync_writes();
+        let mut batch = db.new_write_batch();
+        batch.put_cf(NewColumnFamilies::Other, b"test", b"value");
+        db.write(batch).unwrap();
+        db.flush().unwrap();
+        drop(db);
+
+        let db = RocksDB::<NewColumnFamilies>::new(temp_dir.path());
+        let value = db.get_cf(NewColumnFamilies::Other, b"test").unwrap();
+        assert_eq!(value.unwrap(), b"value");
+    }
+
+    /// Reads a DB option from the latest options file persisted by RocksDB.
+    fn persisted_db_option(db_path: &Path, name: &str) -> Option<String> {
+        let options_file = std::fs::read_dir(db_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                let file_name = path.file_name().unwrap().to_string_lossy();
+                file_name.starts_with("OPTIONS-") && !file_name.ends_with(".dbtmp")
+            })
+            .max()?;
+        // ^ Options file names contain a zero-padded sequence number, so the latest file is the greatest one.
+        let options = std::fs::read_to_string(options_file).unwrap();
+        let prefix = format!("{name}=");
+        options
+            .lines()
+            .find_map(|line| Some(line.trim().strip_prefix(&prefix)?.to_owned()))
+    }
+
+    #[test]
+    fn applying_size_options() {
+        let temp_dir = TempDir::new().unwrap();
+        let options = RocksDBOptions {
+            block_cache_capacity: Some(32 << 20),
+            total_write_buffer_size: Some(64 << 20),
+            max_open_files: Some(128),
+            ..RocksDBOptions::default()
+        };
+        let db = RocksDB::<NewColumnFamilies>::with_options(temp_dir.path(), options);
+        let block_cache_capacity =
+            db.int_property(NewColumnFamilies::Other, properties::BLOCK_CACHE_CAPACITY);
+        assert_eq!(block_cache_capacity, Some(32 << 20));
+        let total_write_buffer_size = persisted_db_option(temp_dir.path(), "db_write_buffer_size");
+        assert_eq!(total_write_buffer_size.unwrap(), (64 << 20).to_string());
+        let max_open_files = persisted_db_option(temp_dir.path(), "max_open_files");
+        assert_eq!(max_open_files.unwrap(), "128");
+    }
+
+    #[test]
+    fn flushing_with_background_write_rate_limit() {
+        let temp_dir = TempDir::new().unwrap();
+        let options = RocksDBOptions {
+            background_write_rate_limit: Some(64 << 10),
+            ..RocksDBOptions::default()
+        };
+        let db = RocksDB::<NewColumnFamilies>::with_options(temp_dir.path(), options);
+        let mut batch = db.new_write_batch();
+        batch.put_cf(NewColumnFamilies::Default, b"test", &[1; 1_024]);
+        db.write(batch).unwrap();
+        db.flush().unwrap();
+        assert!(db.total_sst_files_size() > 0);
+        drop(db);
+
+        let db = RocksDB::<NewColumnFamilies>::new(temp_dir.path());
+        let value = db.get_cf(NewColumnFamilies::Default, b"test").unwrap();
+        assert_eq!(value.unwrap(), [1; 1_024]);
+    }
+
     #[test]
     fn write_batch_can_be_restored_from_bytes() {
         let temp_dir = TempDir::new().unwrap();
@@ -12,7 +12,7 @@ use vise::{Buckets, Collector, Counter, EncodeLabelSet, Family, Gauge, Histogram
 use crate::db::RocksDBInner;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelSet)]
-struct DbLabel {
+pub(crate) struct DbLabel {
     db: &'static str,
 }
 
@@ -102,6 +102,15 @@ pub(crate) struct RocksdbSizeMetrics {
     pub block_cache_size: Family<RocksdbLabels, Gauge<u64>>,
     /// Total size of index and Bloom filters in the column family of a RocksDB instance.
     pub index_and_filters_size: Family<RocksdbLabels, Gauge<u64>>,
+
+    /// Rate (bytes per second) to which writes to a RocksDB instance are currently slowed down; 0 if writes
+    /// are not delayed.
+    pub delayed_write_rate: Family<DbLabel, Gauge<u64>>,
+    /// Time elapsed since writes to a RocksDB instance were first observed to be stopped or delayed
+    /// (e.g., because background flushes and compactions don't keep up); 0 if writes are not stalled.
+    /// Since the duration is measured between metric scrapes, it's approximate.
+    #[metrics(unit = Unit::Seconds)]
+    pub write_stall_duration: Family<DbLabel, Gauge<Duration>>,
 }
 
 /// Weak refs to DB instances registered using [`RocksdbSizeMetrics::register()`].
@@ -3,18 +3,23 @@ use tokio::task::JoinHandle;
 
 use crate::panic_extractor::try_extract_panic_message;
 
+/// Waits until one of the tasks finishes. Returns `Ok(())` if the task finished successfully and tasks
+/// are allowed to finish, and an error otherwise (i.e., if the task returned an error or panicked, or finished
+/// while it wasn't expected to).
 pub async fn wait_for_tasks<Fut>(
     task_futures: Vec<JoinHandle<anyhow::Result<()>>>,
     particular_crypto_alerts: Option<Vec<String>>,
     graceful_shutdown: Option<Fut>,
     tasks_allowed_to_finish: bool,
-) where
+) -> anyhow::Result<()>
+where
     Fut: Future<Output = ()>,
 {
     match future::select_all(task_futures).await.0 {
         Ok(Ok(())) => {
             if tasks_allowed_to_finish {
                 tracing::info!("One of the actors finished its run. Finishing execution.");
+                Ok(())
             } else {
                 let err = "One of the actors finished its run, while it wasn't expected to do it";
                 tracing::error!("{err}");
@@ -22,15 +27,18 @@ pub async fn wait_for_tasks<Fut>(
                 if let Some(graceful_shutdown) = graceful_shutdown {
                     graceful_shutdown.await;
                 }
+                Err(anyhow::anyhow!(err))
             }
         }
         Ok(Err(err)) => {
-            let err = format!("One of the tokio actors unexpectedly finished with error: {err}");
-            tracing::error!("{err}");
-            vlog::capture_message(&err, vlog::AlertLevel::Warning);
+            let message =
+                format!("One of the tokio actors unexpectedly finished with error: {err}");
+            tracing::error!("{message}");
+            vlog::capture_message(&message, vlog::AlertLevel::Warning);
             if let Some(graceful_shutdown) = graceful_shutdown {
                 graceful_shutdown.await;
             }
+            Err(err)
         }
         Err(error) => {
             let is_panic = error.is_panic();
@@ -63,6 +71,9 @@ pub async fn wait_for_tasks<Fut>(
             if let Some(graceful_shutdown) = graceful_shutdown {
                 graceful_shutdown.await;
             }
+            Err(anyhow::anyhow!(
+                "One of the tokio actors unexpectedly finished with error: {panic_message}"
+            ))
         }
     }
 }
@@ -1,4 +1,6 @@
-//! Primitive Merkle tree API used internally to fetch proofs.
+//! Primitive Merkle tree API used internally to fetch proofs. While the tree is being recovered from a snapshot,
+//! the API reports recovery progress and allows pausing and resuming recovery; entry lookups (but not proofs)
+//! for the snapshot L1 batch are served from Postgres and are marked as degraded.
 
 use std::{fmt, future::Future, net::SocketAddr, pin::Pin};
 
@@ -16,7 +18,10 @@ use zksync_merkle_tree::NoVersionError;
 use zksync_types::{L1BatchNumber, H256, U256};
 
 use self::metrics::{MerkleTreeApiMethod, API_METRICS};
-use crate::metadata_calculator::{AsyncTreeReader, MerkleTreeInfo};
+use crate::metadata_calculator::{
+    AsyncTreeReader, MerkleTreeInfo, RecoveryControlHandle, RecoveryStatus, TreeLookupError,
+    TreeReaderHandle,
+};
 
 mod metrics;
 #[cfg(test)]
@@ -60,22 +65,98 @@ impl TreeEntryWithProof {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct TreeEntriesRequest {
+    l1_batch_number: L1BatchNumber,
+    hashed_keys: Vec<U256>,
+}
+
+/// Response to tree entry lookups.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TreeEntriesResponse {
+    /// Whether entries were served from the snapshot in Postgres because the tree is not ready.
+    pub degraded: bool,
+    pub entries: Vec<TreeEntryValue>,
+}
+
+/// Value and leaf index of a tree entry. Both are zero if the entry is missing.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TreeEntryValue {
+    #[serde(default, skip_serializing_if = "H256::is_zero")]
+    pub value: H256,
+    #[serde(default, skip_serializing_if = "TreeEntryWithProof::is_zero")]
+    pub index: u64,
+}
+
+impl From<zksync_merkle_tree::TreeEntry> for TreeEntryValue {
+    fn from(entry: zksync_merkle_tree::TreeEntry) -> Self {
+        Self {
+            value: entry.value,
+            index: entry.leaf_index,
+        }
+    }
+}
+
+/// Response to recovery control requests.
+#[derive(Debug, Serialize, Deserialize)]
+struct RecoveryControlResponse {
+    paused: bool,
+}
+
 #[derive(Debug)]
 enum TreeApiError {
     NoTreeVersion(NoVersionError),
+    /// The tree is not ready yet; contains recovery progress if the tree is being recovered from a snapshot.
+    NotReady(Option<RecoveryStatus>),
+    /// The tree is not ready yet, and the requested L1 batch cannot be served from the snapshot in Postgres.
+    NotSnapshotL1Batch(TreeLookupError),
+    Internal(anyhow::Error),
 }
 
 impl IntoResponse for TreeApiError {
     fn into_response(self) -> Response {
-        let (status, title, detail) = match self {
-            Self::NoTreeVersion(err) => {
-                (StatusCode::NOT_FOUND, "L1 batch not found", err.to_string())
+        let (status, error_type, title, detail) = match self {
+            Self::NoTreeVersion(err) => (
+                StatusCode::NOT_FOUND,
+                "l1-batch-not-found",
+                "L1 batch not found",
+                err.to_string(),
+            ),
+            Self::NotReady(Some(status)) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "tree-recovering",
+                "Merkle tree is recovering",
+                format!(
+                    "Merkle tree is being recovered from the snapshot for L1 batch #{}; recovered {} / {} chunks",
+                    status.snapshot_l1_batch, status.recovered_chunk_count, status.chunk_count
+                ),
+            ),
+            Self::NotReady(None) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "tree-not-ready",
+                "Merkle tree is not ready",
+                "Merkle tree is being initialized".to_owned(),
+            ),
+            Self::NotSnapshotL1Batch(err) => (
+                StatusCode::NOT_FOUND,
+                "l1-batch-not-found",
+                "L1 batch not found",
+                err.to_string(),
+            ),
+            Self::Internal(err) => {
+                tracing::error!("Internal error in Merkle tree API: {err:#}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "internal",
+                    "Internal error",
+                    format!("{err:#}"),
+                )
             }
         };
 
         // Loosely conforms to HTTP Problem Details RFC: https://datatracker.ietf.org/doc/html/rfc7807
         let body = serde_json::json!({
-            "type": "/errors#l1-batch-not-found",
+            "type": format!("/errors#{error_type}"),
             "title": title,
             "detail": detail,
         });
@@ -96,6 +177,9 @@ pub(crate) trait TreeApiClient {
         l1_batch_number: L1BatchNumber,
         hashed_keys: Vec<U256>,
     ) -> anyhow::Result<Vec<TreeEntryWithProof>>;
+
+    /// Obtains the recovery progress, or `None` if the tree is not being recovered.
+    async fn get_recovery_status(&self) -> anyhow::Result<Option<RecoveryStatus>>;
 }
 
 /// In-memory client implementation.
@@ -114,6 +198,10 @@ impl TreeApiClient for AsyncTreeReader {
             .await
             .map_err(Into::into)
     }
+
+    async fn get_recovery_status(&self) -> anyhow::Result<Option<RecoveryStatus>> {
+        Ok(None) // the reader is only available once the tree is ready
+    }
 }
 
 /// [`TreeApiClient`] implementation requesting data from a Merkle tree API server.
@@ -122,6 +210,8 @@ pub struct TreeApiHttpClient {
     inner: reqwest::Client,
     info_url: String,
     proofs_url: String,
+    entries_url: String,
+    recovery_status_url: String,
 }
 
 impl TreeApiHttpClient {
@@ -130,8 +220,38 @@ impl TreeApiHttpClient {
             inner: reqwest::Client::new(),
             info_url: url_base.to_owned(),
             proofs_url: format!("{url_base}/proofs"),
+            entries_url: format!("{url_base}/entries"),
+            recovery_status_url: format!("{url_base}/recovery"),
         }
     }
+
+    /// Looks up values and leaf indices for the specified `hashed_keys` at the specified tree version
+    /// (= L1 batch number). While the tree is recovering, lookups for the snapshot L1 batch are served
+    /// from Postgres; such responses are marked as degraded.
+    pub async fn get_entries(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        hashed_keys: Vec<U256>,
+    ) -> anyhow::Result<TreeEntriesResponse> {
+        let response = self
+            .inner
+            .post(&self.entries_url)
+            .json(&TreeEntriesRequest {
+                l1_batch_number,
+                hashed_keys,
+            })
+            .send()
+            .await
+            .with_context(|| {
+                format!("Failed requesting entries for L1 batch #{l1_batch_number}")
+            })?;
+        let response = response.error_for_status().with_context(|| {
+            format!("Requesting entries for L1 batch #{l1_batch_number} returned non-OK response")
+        })?;
+        response.json().await.with_context(|| {
+            format!("Failed deserializing entries for L1 batch #{l1_batch_number}")
+        })
+    }
 }
 
 #[async_trait]
@@ -175,16 +295,25 @@ impl TreeApiClient for TreeApiHttpClient {
         })?;
         Ok(response.entries)
     }
-}
 
-impl AsyncTreeReader {
-    async fn info_handler(State(this): State<Self>) -> Json<MerkleTreeInfo> {
-        let latency = API_METRICS.latency[&MerkleTreeApiMethod::Info].start();
-        let info = this.info().await;
-        latency.observe();
-        Json(info)
+    async fn get_recovery_status(&self) -> anyhow::Result<Option<RecoveryStatus>> {
+        let response = self
+            .inner
+            .get(&self.recovery_status_url)
+            .send()
+            .await
+            .context("Failed requesting tree recovery status")?;
+        let response = response
+            .error_for_status()
+            .context("Requesting tree recovery status returned non-OK response")?;
+        response
+            .json()
+            .await
+            .context("Failed deserializing tree recovery status")
     }
+}
 
+impl AsyncTreeReader {
     async fn get_proofs_inner(
         &self,
         l1_batch_number: L1BatchNumber,
@@ -196,6 +325,57 @@ impl AsyncTreeReader {
             .await?;
         Ok(proofs.into_iter().map(TreeEntryWithProof::new).collect())
     }
+}
+
+/// State of the Merkle tree API server. The tree reader becomes available once the tree is ready
+/// (i.e., after it's recovered from a snapshot if necessary); until then, the server reports recovery progress
+/// and serves degraded entry lookups if the tree reader handle allows it.
+#[derive(Debug, Clone)]
+pub(crate) struct TreeApiState {
+    tree_reader: watch::Receiver<Option<TreeReaderHandle>>,
+    recovery_status: watch::Receiver<Option<RecoveryStatus>>,
+    recovery_control: RecoveryControlHandle,
+}
+
+impl TreeApiState {
+    pub fn new(
+        tree_reader: watch::Receiver<Option<TreeReaderHandle>>,
+        recovery_status: watch::Receiver<Option<RecoveryStatus>>,
+        recovery_control: RecoveryControlHandle,
+    ) -> Self {
+        Self {
+            tree_reader,
+            recovery_status,
+            recovery_control,
+        }
+    }
+
+    fn not_ready_error(&self) -> TreeApiError {
+        let recovery_status = self.recovery_status.borrow().clone();
+        TreeApiError::NotReady(recovery_status)
+    }
+
+    fn tree_reader(&self) -> Result<AsyncTreeReader, TreeApiError> {
+        let handle = self.tree_reader.borrow();
+        if let Some(reader) = handle.as_ref().and_then(TreeReaderHandle::ready) {
+            return Ok(reader.clone());
+        }
+        drop(handle);
+        Err(self.not_ready_error())
+    }
+
+    /// Returns the tree reader handle, which may serve degraded lookups if the tree is not ready.
+    fn tree_reader_handle(&self) -> Result<TreeReaderHandle, TreeApiError> {
+        let handle = self.tree_reader.borrow().clone();
+        handle.ok_or_else(|| self.not_ready_error())
+    }
+
+    async fn info_handler(State(this): State<Self>) -> Result<Json<MerkleTreeInfo>, TreeApiError> {
+        let latency = API_METRICS.latency[&MerkleTreeApiMethod::Info].start();
+        let info = this.tree_reader()?.info().await;
+        latency.observe();
+        Ok(Json(info))
+    }
 
     async fn get_proofs_handler(
         State(this): State<Self>,
@@ -203,6 +383,7 @@ impl AsyncTreeReader {
     ) -> Result<Json<TreeProofsResponse>, TreeApiError> {
         let latency = API_METRICS.latency[&MerkleTreeApiMethod::GetProofs].start();
         let entries = this
+            .tree_reader()?
             .get_proofs_inner(request.l1_batch_number, request.hashed_keys)
             .await
             .map_err(TreeApiError::NoTreeVersion)?;
@@ -211,6 +392,54 @@ impl AsyncTreeReader {
         Ok(Json(response))
     }
 
+    async fn get_entries_handler(
+        State(this): State<Self>,
+        Json(request): Json<TreeEntriesRequest>,
+    ) -> Result<Json<TreeEntriesResponse>, TreeApiError> {
+        let latency = API_METRICS.latency[&MerkleTreeApiMethod::GetEntries].start();
+        let handle = this.tree_reader_handle()?;
+        let entries = handle
+            .entries(request.l1_batch_number, request.hashed_keys)
+            .await
+            .map_err(|err| match err {
+                TreeLookupError::NoVersion(err) => TreeApiError::NoTreeVersion(err),
+                TreeLookupError::NoSnapshot => this.not_ready_error(),
+                err @ TreeLookupError::NotSnapshotL1Batch { .. } => {
+                    TreeApiError::NotSnapshotL1Batch(err)
+                }
+                TreeLookupError::Internal(err) => TreeApiError::Internal(err),
+            })?;
+        let response = TreeEntriesResponse {
+            degraded: handle.is_degraded(),
+            entries: entries.into_iter().map(TreeEntryValue::from).collect(),
+        };
+        latency.observe();
+        Ok(Json(response))
+    }
+
+    async fn recovery_status_handler(State(this): State<Self>) -> Json<Option<RecoveryStatus>> {
+        let latency = API_METRICS.latency[&MerkleTreeApiMethod::RecoveryStatus].start();
+        let recovery_status = this.recovery_status.borrow().clone();
+        latency.observe();
+        Json(recovery_status)
+    }
+
+    async fn pause_recovery_handler(State(this): State<Self>) -> Json<RecoveryControlResponse> {
+        let latency = API_METRICS.latency[&MerkleTreeApiMethod::RecoveryControl].start();
+        this.recovery_control.pause();
+        tracing::info!("Tree recovery pause was requested via Merkle tree API");
+        latency.observe();
+        Json(RecoveryControlResponse { paused: true })
+    }
+
+    async fn resume_recovery_handler(State(this): State<Self>) -> Json<RecoveryControlResponse> {
+        let latency = API_METRICS.latency[&MerkleTreeApiMethod::RecoveryControl].start();
+        this.recovery_control.resume();
+        tracing::info!("Tree recovery resume was requested via Merkle tree API");
+        latency.observe();
+        Json(RecoveryControlResponse { paused: false })
+    }
+
     fn create_api_server(
         self,
         bind_address: &SocketAddr,
@@ -221,6 +450,16 @@ impl AsyncTreeReader {
         let app = Router::new()
             .route("/", routing::get(Self::info_handler))
             .route("/proofs", routing::post(Self::get_proofs_handler))
+            .route("/entries", routing::post(Self::get_entries_handler))
+            .route("/recovery", routing::get(Self::recovery_status_handler))
+            .route(
+                "/recovery/pause",
+                routing::post(Self::pause_recovery_handler),
+            )
+            .route(
+                "/recovery/resume",
+                routing::post(Self::resume_recovery_handler),
+            )
             .with_state(self);
 
         let server = axum::Server::try_bind(bind_address)
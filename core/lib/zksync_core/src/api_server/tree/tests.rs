@@ -4,10 +4,15 @@ use std::net::Ipv4Addr;
 
 use tempfile::TempDir;
 use zksync_dal::ConnectionPool;
+use zksync_types::{snapshots::SnapshotRecoveryStatus, L2ChainId, MiniblockNumber};
 
 use super::*;
-use crate::metadata_calculator::tests::{
-    gen_storage_logs, reset_db_state, run_calculator, setup_calculator,
+use crate::{
+    genesis::{ensure_genesis_state, GenesisParams},
+    metadata_calculator::{
+        tests::{gen_storage_logs, reset_db_state, run_calculator, setup_calculator},
+        SnapshotStateReader,
+    },
 };
 
 #[tokio::test]
@@ -18,12 +23,11 @@ async fn merkle_tree_api() {
     let api_addr = (Ipv4Addr::LOCALHOST, 0).into();
 
     reset_db_state(&pool, 5).await;
-    let tree_reader = calculator.tree_reader();
+    let tree_api_state = calculator.tree_api_state();
     let calculator_task = tokio::spawn(run_calculator(calculator, pool));
 
     let (stop_sender, stop_receiver) = watch::channel(false);
-    let api_server = tree_reader
-        .await
+    let api_server = tree_api_state
         .create_api_server(&api_addr, stop_receiver.clone())
         .unwrap();
     let local_addr = *api_server.local_addr();
@@ -34,6 +38,7 @@ async fn merkle_tree_api() {
     calculator_task.await.unwrap();
 
     // Query the API.
+    assert_eq!(api_client.get_recovery_status().await.unwrap(), None);
     let tree_info = api_client.get_info().await.unwrap();
     assert!(tree_info.leaf_count > 20);
     assert_eq!(tree_info.next_l1_batch_number, L1BatchNumber(6));
@@ -78,3 +83,190 @@ async fn merkle_tree_api() {
     stop_sender.send_replace(true);
     api_server_task.await.unwrap().unwrap();
 }
+
+#[tokio::test]
+async fn merkle_tree_api_for_recovering_tree() {
+    let api_addr = (Ipv4Addr::LOCALHOST, 0).into();
+    let recovery_status = RecoveryStatus {
+        snapshot_l1_batch: L1BatchNumber(23),
+        chunk_count: 10,
+        recovered_chunk_count: 3,
+        total_entry_count: 1_000,
+        processed_entry_count: 300,
+        entries_per_second: Some(100.0),
+        estimated_time_remaining_secs: Some(7.0),
+    };
+    let (_tree_reader_sender, tree_reader) = watch::channel(None);
+    let (recovery_status_sender, recovery_status_receiver) =
+        watch::channel(Some(recovery_status.clone()));
+    let recovery_control = RecoveryControlHandle::default();
+    let tree_api_state = TreeApiState::new(
+        tree_reader,
+        recovery_status_receiver,
+        recovery_control.clone(),
+    );
+
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let api_server = tree_api_state
+        .create_api_server(&api_addr, stop_receiver)
+        .unwrap();
+    let local_addr = *api_server.local_addr();
+    let api_server_task = tokio::spawn(api_server.run());
+    let api_client = TreeApiHttpClient::new(&format!("http://{local_addr}"));
+
+    let status = api_client.get_recovery_status().await.unwrap();
+    assert_eq!(status, Some(recovery_status));
+
+    let err = api_client
+        .get_proofs(L1BatchNumber(23), vec![U256::zero()])
+        .await
+        .unwrap_err();
+    let err = format!("{err:?}");
+    assert!(err.contains("503 Service Unavailable"), "{err}");
+    let err = api_client.get_info().await.unwrap_err();
+    let err = format!("{err:?}");
+    assert!(err.contains("503 Service Unavailable"), "{err}");
+
+    // Check the error returned for proof queries.
+    let response = reqwest::Client::new()
+        .post(format!("http://{local_addr}/proofs"))
+        .json(&TreeProofsRequest {
+            l1_batch_number: L1BatchNumber(23),
+            hashed_keys: vec![],
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["type"], "/errors#tree-recovering");
+    let detail = body["detail"].as_str().unwrap();
+    assert!(detail.contains("L1 batch #23"), "{detail}");
+    assert!(detail.contains("3 / 10 chunks"), "{detail}");
+
+    // Emulate recovery progress.
+    recovery_status_sender.send_modify(|status| {
+        status.as_mut().unwrap().recovered_chunk_count = 10;
+    });
+    let status = api_client.get_recovery_status().await.unwrap().unwrap();
+    assert_eq!(status.recovered_chunk_count, 10);
+
+    // Pause and resume recovery.
+    let http_client = reqwest::Client::new();
+    let response = http_client
+        .post(format!("http://{local_addr}/recovery/pause"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response: RecoveryControlResponse = response.json().await.unwrap();
+    assert!(response.paused);
+    assert!(recovery_control.is_paused());
+
+    let response = http_client
+        .post(format!("http://{local_addr}/recovery/resume"))
+        .send()
+        .await
+        .unwrap();
+    let response: RecoveryControlResponse = response.json().await.unwrap();
+    assert!(!response.paused);
+    assert!(!recovery_control.is_paused());
+
+    stop_sender.send_replace(true);
+    api_server_task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn merkle_tree_api_serves_degraded_entries_for_recovering_tree() {
+    let pool = ConnectionPool::test_pool().await;
+    let mut storage = pool.access_storage().await.unwrap();
+    ensure_genesis_state(&mut storage, L2ChainId::from(270), &GenesisParams::mock())
+        .await
+        .unwrap();
+    drop(storage);
+    reset_db_state(&pool, 1).await;
+    let snapshot_recovery = SnapshotRecoveryStatus {
+        l1_batch_number: L1BatchNumber(1),
+        l1_batch_root_hash: H256::repeat_byte(1),
+        miniblock_number: MiniblockNumber(1),
+        miniblock_root_hash: H256::zero(),
+        last_finished_chunk_id: Some(0),
+        total_chunk_count: 1,
+    };
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&snapshot_recovery)
+        .await
+        .unwrap();
+
+    let api_addr = (Ipv4Addr::LOCALHOST, 0).into();
+    let degraded_reader = SnapshotStateReader::new(pool.clone());
+    let (_tree_reader_sender, tree_reader) =
+        watch::channel(Some(TreeReaderHandle::Degraded(degraded_reader)));
+    let (_recovery_status_sender, recovery_status) = watch::channel(None);
+    let tree_api_state = TreeApiState::new(
+        tree_reader,
+        recovery_status,
+        RecoveryControlHandle::default(),
+    );
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let api_server = tree_api_state
+        .create_api_server(&api_addr, stop_receiver)
+        .unwrap();
+    let local_addr = *api_server.local_addr();
+    let api_server_task = tokio::spawn(api_server.run());
+    let api_client = TreeApiHttpClient::new(&format!("http://{local_addr}"));
+
+    let logs = gen_storage_logs(0..100, 1).pop().unwrap();
+    let mut hashed_keys: Vec<_> = logs
+        .iter()
+        .take(10)
+        .map(|log| log.key.hashed_key_u256())
+        .collect();
+    // Extend with some non-existing keys.
+    hashed_keys.extend((0_u8..5).map(|byte| U256::from_big_endian(&[byte; 32])));
+
+    let response = api_client
+        .get_entries(L1BatchNumber(1), hashed_keys.clone())
+        .await
+        .unwrap();
+    assert!(response.degraded);
+    assert_eq!(response.entries.len(), 15);
+    for (i, entry) in response.entries.iter().enumerate() {
+        let should_be_present = i < 10;
+        if should_be_present {
+            assert_eq!(entry.value, logs[i].value);
+            assert_ne!(entry.index, 0);
+        } else {
+            assert_eq!(
+                *entry,
+                TreeEntryValue {
+                    value: H256::zero(),
+                    index: 0
+                }
+            );
+        }
+    }
+
+    let err = api_client
+        .get_entries(L1BatchNumber(2), hashed_keys.clone())
+        .await
+        .unwrap_err();
+    let err = format!("{err:?}");
+    assert!(err.contains("404 Not Found"), "{err}");
+    // Proofs are never served in the degraded mode.
+    let err = api_client
+        .get_proofs(L1BatchNumber(1), hashed_keys)
+        .await
+        .unwrap_err();
+    let err = format!("{err:?}");
+    assert!(err.contains("503 Service Unavailable"), "{err}");
+    let err = api_client.get_info().await.unwrap_err();
+    let err = format!("{err:?}");
+    assert!(err.contains("503 Service Unavailable"), "{err}");
+
+    stop_sender.send_replace(true);
+    api_server_task.await.unwrap().unwrap();
+}
@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use tokio::sync::watch;
 use zksync_contracts::PRE_BOOJUM_COMMIT_FUNCTION;
 use zksync_dal::ConnectionPool;
 use zksync_types::{
@@ -7,7 +8,10 @@ use zksync_types::{
     L1BatchNumber,
 };
 
-use crate::metrics::{CheckerComponent, EN_METRICS};
+use crate::{
+    metadata_calculator::RecoveryStatus,
+    metrics::{CheckerComponent, EN_METRICS},
+};
 
 #[derive(Debug)]
 pub struct ConsistencyChecker {
@@ -17,6 +21,7 @@ pub struct ConsistencyChecker {
     max_batches_to_recheck: u32,
     web3: Web3<Http>,
     db: ConnectionPool,
+    tree_recovery_status: Option<watch::Receiver<Option<RecoveryStatus>>>,
 }
 
 const SLEEP_DELAY: Duration = Duration::from_secs(5);
@@ -30,9 +35,30 @@ impl ConsistencyChecker {
             contract,
             max_batches_to_recheck,
             db,
+            tree_recovery_status: None,
         }
     }
 
+    /// Makes the checker consult the recovery progress of the Merkle tree (see
+    /// [`MetadataCalculator::recovery_status()`](crate::metadata_calculator::MetadataCalculator::recovery_status())).
+    /// While the tree is recovering from a snapshot, L1 batches before the snapshot one never get metadata
+    /// in Postgres, so the checker skips them instead of waiting for their metadata indefinitely.
+    #[must_use]
+    pub fn with_tree_recovery_status(
+        mut self,
+        recovery_status: watch::Receiver<Option<RecoveryStatus>>,
+    ) -> Self {
+        self.tree_recovery_status = Some(recovery_status);
+        self
+    }
+
+    /// Returns the L1 batch of the snapshot the tree is being recovered from, if any.
+    fn tree_recovery_l1_batch(&self) -> Option<L1BatchNumber> {
+        let recovery_status = self.tree_recovery_status.as_ref()?;
+        let snapshot_l1_batch = recovery_status.borrow().as_ref()?.snapshot_l1_batch;
+        Some(snapshot_l1_batch)
+    }
+
     async fn check_commitments(&self, batch_number: L1BatchNumber) -> Result<bool, error::Error> {
         let mut storage = self.db.access_storage().await.unwrap();
 
@@ -162,6 +188,16 @@ impl ConsistencyChecker {
                 break;
             }
 
+            if let Some(snapshot_l1_batch) = self.tree_recovery_l1_batch() {
+                if batch_number < snapshot_l1_batch {
+                    tracing::info!(
+                        "Merkle tree is being recovered from the snapshot for L1 batch #{snapshot_l1_batch}; \
+                         skipping checks for earlier L1 batches starting from #{batch_number}"
+                    );
+                    batch_number = snapshot_l1_batch;
+                }
+            }
+
             let metadata = self
                 .db
                 .access_storage()
@@ -814,17 +814,35 @@ async fn run_tree(
 
     let config =
         MetadataCalculatorConfig::for_main_node(&db_config.merkle_tree, operation_manager, mode);
-    let metadata_calculator = MetadataCalculator::new(&config).await;
+    let mut metadata_calculator = MetadataCalculator::new(&config).await;
+    let recovery_config = &db_config.merkle_tree.recovery;
+    if let Some(pool_size) = recovery_config.pool_size {
+        let recovery_pool = ConnectionPool::builder(postgres_config.master_url()?, pool_size)
+            .set_statement_timeout(recovery_config.statement_timeout())
+            .set_application_name("tree_recovery")
+            .build()
+            .await
+            .context("failed to build tree recovery connection pool")?;
+        metadata_calculator = metadata_calculator.with_recovery_pool(recovery_pool);
+    }
+    if recovery_config.use_replica {
+        // The replica pool should be able to serve the same concurrency as the pool used to load chunks.
+        let pool_size = recovery_config.pool_size.unwrap_or(1);
+        let replica_pool = ConnectionPool::builder(postgres_config.replica_url()?, pool_size)
+            .set_statement_timeout(recovery_config.statement_timeout())
+            .set_application_name("tree_recovery")
+            .build()
+            .await
+            .context("failed to build tree recovery replica connection pool")?;
+        metadata_calculator = metadata_calculator.with_replica_pool(replica_pool);
+    }
     if let Some(api_config) = api_config {
         let address = (Ipv4Addr::UNSPECIFIED, api_config.port).into();
-        let tree_reader = metadata_calculator.tree_reader();
+        let tree_api_state = metadata_calculator.tree_api_state();
         let stop_receiver = stop_receiver.clone();
-        task_futures.push(tokio::spawn(async move {
-            tree_reader
-                .await
-                .run_api_server(address, stop_receiver)
-                .await
-        }));
+        task_futures.push(tokio::spawn(
+            tree_api_state.run_api_server(address, stop_receiver),
+        ));
     }
 
     let tree_health_check = metadata_calculator.tree_health_check();
@@ -1,13 +1,18 @@
 //! Various helpers for the metadata calculator.
 
 use std::{
+    any::Any,
     collections::BTreeMap,
+    fmt, fs,
     future::Future,
+    ops,
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
     time::Duration,
 };
 
-use serde::{Deserialize, Serialize};
+use anyhow::Context as _;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 #[cfg(test)]
 use tokio::sync::mpsc;
 use zksync_config::configs::database::MerkleTreeMode;
@@ -16,12 +21,14 @@ use zksync_health_check::{Health, HealthStatus};
 use zksync_merkle_tree::{
     domain::{TreeMetadata, ZkSyncTree, ZkSyncTreeReader},
     recovery::MerkleTreeRecovery,
-    Database, Key, NoVersionError, RocksDBWrapper, TreeEntry, TreeEntryWithProof, TreeInstruction,
+    Database, Key, NoVersionError, PruningOutcome, RocksDBWrapper, TreeEntry, TreeEntryWithProof,
+    TreeInstruction,
 };
 use zksync_storage::{RocksDB, RocksDBOptions, StalledWritesRetries};
 use zksync_types::{block::L1BatchHeader, L1BatchNumber, StorageKey, H256};
+use zksync_utils::{h256_to_u256, u256_to_h256};
 
-use super::metrics::{LoadChangesStage, TreeUpdateStage, METRICS};
+use super::metrics::{LoadChangesStage, TreeUpdateStage, METRICS, RECOVERY_METRICS};
 
 /// General information about the Merkle tree.
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,49 +45,213 @@ impl From<MerkleTreeInfo> for Health {
     }
 }
 
-/// Creates a RocksDB wrapper with the specified params.
+/// Taint of a tree whose recovery was finalized despite a root hash mismatch, which is only possible
+/// with the unsafe `experimental_skip_root_hash_check` recovery option. The taint is persisted
+/// in the tree manifest and is never removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct TreeTaint {
+    /// Root hash of the snapshot L1 batch.
+    pub expected_root_hash: H256,
+    /// Root hash of the recovered tree.
+    pub actual_root_hash: H256,
+}
+
+impl TreeTaint {
+    /// Custom tag in the tree manifest storing the taint.
+    const TAG: &'static str = "recovery.tainted";
+}
+
+/// Marker of a Merkle tree recovery that has failed because of a root hash mismatch. The marker is persisted
+/// in the tree manifest, and the tree is left in the recovery state so that it can be inspected, but is never
+/// used to serve requests. Recovery is not retried on the following node starts until the tree is reset
+/// (see [`reset_tree_db()`](super::reset_tree_db())).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryFailure {
+    /// Root hash of the snapshot L1 batch.
+    pub expected_root_hash: H256,
+    /// Root hash of the recovered tree.
+    pub actual_root_hash: H256,
+    /// UNIX timestamp (in seconds) when recovery has failed.
+    pub failed_at: u64,
+}
+
+impl RecoveryFailure {
+    /// Custom tag in the tree manifest storing the marker.
+    const TAG: &'static str = "recovery.failed";
+}
+
+/// Parses a JSON value of the custom `tag` in the tree manifest, or returns `None` if the tag is not set.
+fn parse_custom_tag<T: DeserializeOwned>(
+    tags: &BTreeMap<String, String>,
+    tag: &str,
+) -> anyhow::Result<Option<T>> {
+    let Some(value) = tags.get(tag) else {
+        return Ok(None);
+    };
+    let value = serde_json::from_str(value).with_context(|| {
+        format!("failed parsing `{tag}` tag in Merkle tree manifest: {value:?}")
+    })?;
+    Ok(Some(value))
+}
+
+/// RocksDB tuning profile for the bulk-load workload of tree recovery. Compared to normal tree operation,
+/// recovery performs large writes and almost no reads, so it benefits from larger memtables and deferred compaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct RecoveryDbProfile {
+    /// Byte capacity of memtables for large column families.
+    pub memtable_capacity: usize,
+    /// Maximum number of memtables for large column families.
+    pub max_write_buffer_number: usize,
+    /// Block cache capacity overriding [`TreeDbParams::block_cache_capacity`].
+    pub block_cache_capacity: Option<usize>,
+    /// Limit on the total memtable capacity overriding [`TreeDbParams::total_write_buffer_size`].
+    pub total_write_buffer_size: Option<usize>,
+    /// Maximum number of open files overriding [`TreeDbParams::max_open_files`].
+    pub max_open_files: Option<usize>,
+}
+
+impl RecoveryDbProfile {
+    /// Adjusts the normal RocksDB `options` for bulk loading. Auto-compaction is disabled; the database
+    /// is compacted manually when recovery is finalized. Sizes not overridden by the profile are retained
+    /// from the normal options.
+    fn apply(self, options: RocksDBOptions) -> RocksDBOptions {
+        RocksDBOptions {
+            block_cache_capacity: self.block_cache_capacity.or(options.block_cache_capacity),
+            large_memtable_capacity: Some(self.memtable_capacity),
+            large_max_write_buffer_number: Some(self.max_write_buffer_number),
+            total_write_buffer_size: self
+                .total_write_buffer_size
+                .or(options.total_write_buffer_size),
+            max_open_files: self.max_open_files.or(options.max_open_files),
+            disable_auto_compactions: true,
+            ..options
+        }
+    }
+}
+
+/// Parameters of the Merkle tree RocksDB used during normal tree operation.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct TreeDbParams {
+    /// Byte capacity of the block cache.
+    pub block_cache_capacity: usize,
+    /// Byte capacity of memtables for large column families.
+    pub memtable_capacity: usize,
+    /// Limit on the total byte capacity of memtables across all column families.
+    pub total_write_buffer_size: Option<usize>,
+    /// Maximum number of files kept open by RocksDB.
+    pub max_open_files: Option<usize>,
+    pub stalled_writes_timeout: Duration,
+    pub multi_get_chunk_size: usize,
+}
+
+impl TreeDbParams {
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        Self {
+            block_cache_capacity: 0,
+            memtable_capacity: 16 << 20, // 16 MiB
+            total_write_buffer_size: None,
+            max_open_files: None,
+            stalled_writes_timeout: Duration::ZERO, // writes should never be stalled in tests
+            multi_get_chunk_size: 500,
+        }
+    }
+
+    fn rocksdb_options(&self) -> RocksDBOptions {
+        RocksDBOptions {
+            block_cache_capacity: Some(self.block_cache_capacity),
+            large_memtable_capacity: Some(self.memtable_capacity),
+            total_write_buffer_size: self.total_write_buffer_size,
+            max_open_files: self.max_open_files,
+            stalled_writes_retries: StalledWritesRetries::new(self.stalled_writes_timeout),
+            ..RocksDBOptions::default()
+        }
+    }
+}
+
+/// Creates a RocksDB wrapper with the specified params. If `recovery_profile` is specified, the database
+/// is tuned for bulk loading during recovery; the profile takes precedence over `params`.
 pub(super) async fn create_db(
     path: PathBuf,
-    block_cache_capacity: usize,
-    memtable_capacity: usize,
-    stalled_writes_timeout: Duration,
-    multi_get_chunk_size: usize,
+    params: TreeDbParams,
+    recovery_profile: Option<RecoveryDbProfile>,
 ) -> RocksDBWrapper {
-    tokio::task::spawn_blocking(move || {
-        create_db_sync(
-            &path,
-            block_cache_capacity,
-            memtable_capacity,
-            stalled_writes_timeout,
-            multi_get_chunk_size,
-        )
-    })
-    .await
-    .unwrap()
+    tokio::task::spawn_blocking(move || create_db_sync(&path, params, recovery_profile))
+        .await
+        .unwrap()
 }
 
 fn create_db_sync(
     path: &Path,
-    block_cache_capacity: usize,
-    memtable_capacity: usize,
-    stalled_writes_timeout: Duration,
-    multi_get_chunk_size: usize,
+    params: TreeDbParams,
+    recovery_profile: Option<RecoveryDbProfile>,
 ) -> RocksDBWrapper {
+    let options = params.rocksdb_options();
+    let options = match recovery_profile {
+        Some(profile) => profile.apply(options),
+        None => options,
+    };
+    // Log effective RocksDB options, so that it's clear which of `params` are overridden by the recovery profile.
     tracing::info!(
         "Initializing Merkle tree database at `{path}` with {multi_get_chunk_size} multi-get chunk size, \
-         {block_cache_capacity}B block cache, {memtable_capacity}B memtable capacity, \
-         {stalled_writes_timeout:?} stalled writes timeout",
-        path = path.display()
+         recovery profile {recovery_profile:?} and effective options {options:?}",
+        path = path.display(),
+        multi_get_chunk_size = params.multi_get_chunk_size
     );
+    open_db_sync(path, options, params.multi_get_chunk_size)
+}
 
-    let mut db = RocksDB::with_options(
-        path,
-        RocksDBOptions {
-            block_cache_capacity: Some(block_cache_capacity),
-            large_memtable_capacity: Some(memtable_capacity),
-            stalled_writes_retries: StalledWritesRetries::new(stalled_writes_timeout),
-        },
+/// Closes the tree RocksDB and reopens it with options obtained by applying `map_options` to the current ones.
+/// Returns the reopened database and the options it was previously opened with.
+///
+/// `db` must be the only handle to the database; otherwise, it cannot be reopened.
+fn reopen_db_sync(
+    db: RocksDBWrapper,
+    map_options: impl FnOnce(RocksDBOptions) -> RocksDBOptions,
+) -> (RocksDBWrapper, RocksDBOptions) {
+    let path = db.path().to_owned();
+    let multi_get_chunk_size = db.multi_get_chunk_size();
+    let db = db.into_inner();
+    let prev_options = db.options();
+    drop(db); // closes RocksDB
+
+    let options = map_options(prev_options);
+    tracing::info!(
+        "Reopening Merkle tree database at `{}` with {options:?}",
+        path.display()
     );
+    let db = open_db_sync(&path, options, multi_get_chunk_size);
+    (db, prev_options)
+}
+
+/// Creates an empty marker file and syncs it to disk.
+fn create_marker_file(path: &Path) -> anyhow::Result<()> {
+    let file = fs::File::create(path)
+        .with_context(|| format!("failed creating marker file `{}`", path.display()))?;
+    file.sync_all()
+        .with_context(|| format!("failed syncing marker file `{}`", path.display()))
+}
+
+fn open_db_sync(
+    path: &Path,
+    options: RocksDBOptions,
+    multi_get_chunk_size: usize,
+) -> RocksDBWrapper {
+    let wipe_path = wipe_path(path);
+    if wipe_path.exists() {
+        tracing::warn!(
+            "Found Merkle tree data at `{}` left after an interrupted wipe; removing it",
+            wipe_path.display()
+        );
+        if let Err(err) = fs::remove_dir_all(&wipe_path) {
+            tracing::warn!(
+                "Failed removing Merkle tree data at `{}` left after an interrupted wipe: {err}",
+                wipe_path.display()
+            );
+        }
+    }
+
+    let mut db = RocksDB::with_options(path, options);
     if cfg!(test) {
         // We need sync writes for the unit tests to execute reliably. With the default config,
         // some writes to RocksDB may occur, but not be visible to the test code.
@@ -91,6 +262,129 @@ fn create_db_sync(
     db
 }
 
+/// Opens an existing tree RocksDB at `path` in the read-only mode, e.g. to inspect the tree while it's used
+/// by another process. Unlike [`create_db()`], the database isn't created if it doesn't exist.
+pub(super) async fn open_db_read_only(path: PathBuf) -> anyhow::Result<RocksDBWrapper> {
+    tokio::task::spawn_blocking(move || {
+        RocksDBWrapper::open_read_only(&path).with_context(|| {
+            format!(
+                "failed opening Merkle tree RocksDB at `{}` in the read-only mode",
+                path.display()
+            )
+        })
+    })
+    .await
+    .unwrap()
+}
+
+/// Returns the path the tree RocksDB directory at `path` is moved to before it's removed. If this path exists,
+/// the process was terminated while wiping the tree; the remaining data is removed when the DB is opened.
+pub(super) fn wipe_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".wipe");
+    path.with_file_name(file_name)
+}
+
+/// Closes the tree RocksDB and removes its directory. The directory is atomically renamed before removal,
+/// so that the tree is never observed partially removed. Returns an empty RocksDB at the same path opened
+/// with the same options.
+///
+/// `db` must be the only handle to the database; otherwise, the database isn't closed before its directory
+/// is removed.
+pub(super) async fn destroy_db(db: RocksDBWrapper) -> anyhow::Result<RocksDBWrapper> {
+    tokio::task::spawn_blocking(|| destroy_db_sync(db))
+        .await
+        .context("panicked destroying Merkle tree RocksDB")?
+}
+
+fn destroy_db_sync(db: RocksDBWrapper) -> anyhow::Result<RocksDBWrapper> {
+    let path = db.path().to_owned();
+    let multi_get_chunk_size = db.multi_get_chunk_size();
+    let db = db.into_inner();
+    let options = db.options();
+    drop(db); // closes RocksDB
+    remove_db_dir_sync(&path)?;
+    Ok(open_db_sync(&path, options, multi_get_chunk_size))
+}
+
+/// Removes the tree RocksDB directory at `path`, which must not be opened. The directory is atomically renamed
+/// before removal, so that the tree is never observed partially removed.
+pub(super) fn remove_db_dir_sync(path: &Path) -> anyhow::Result<()> {
+    let wipe_path = wipe_path(path);
+    tracing::info!(
+        "Removing Merkle tree at `{}` via `{}`",
+        path.display(),
+        wipe_path.display()
+    );
+    if wipe_path.exists() {
+        fs::remove_dir_all(&wipe_path).with_context(|| {
+            format!(
+                "failed removing Merkle tree data at `{}` left after an interrupted wipe",
+                wipe_path.display()
+            )
+        })?;
+    }
+    fs::rename(path, &wipe_path).with_context(|| {
+        format!(
+            "failed moving Merkle tree at `{}` to `{}`",
+            path.display(),
+            wipe_path.display()
+        )
+    })?;
+    fs::remove_dir_all(&wipe_path)
+        .with_context(|| format!("failed removing Merkle tree at `{}`", wipe_path.display()))
+}
+
+/// Closes the tree RocksDB and replaces its directory with the RocksDB directory at `source_path` (e.g., a checkpoint).
+/// Returns the replaced database opened with the same options.
+///
+/// `db` must be the only handle to the database, and it must not contain any data worth preserving. If the process
+/// is terminated after `db` is removed, but before `source_path` is moved in its place, an empty database
+/// is created at the tree path on the next start.
+pub(super) async fn replace_db(
+    db: RocksDBWrapper,
+    source_path: PathBuf,
+) -> anyhow::Result<RocksDBWrapper> {
+    tokio::task::spawn_blocking(move || replace_db_sync(db, &source_path))
+        .await
+        .unwrap()
+}
+
+fn replace_db_sync(db: RocksDBWrapper, source_path: &Path) -> anyhow::Result<RocksDBWrapper> {
+    let path = db.path().to_owned();
+    let multi_get_chunk_size = db.multi_get_chunk_size();
+    let db = db.into_inner();
+    let options = db.options();
+    drop(db); // closes RocksDB
+
+    tracing::info!(
+        "Replacing Merkle tree at `{}` with `{}`",
+        path.display(),
+        source_path.display()
+    );
+    fs::remove_dir_all(&path)
+        .with_context(|| format!("failed removing Merkle tree at `{}`", path.display()))?;
+    fs::rename(source_path, &path).with_context(|| {
+        format!(
+            "failed moving `{}` to `{}`",
+            source_path.display(),
+            path.display()
+        )
+    })?;
+    Ok(open_db_sync(&path, options, multi_get_chunk_size))
+}
+
+/// Extracts a message from a caught panic payload.
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "(unknown panic payload)"
+    }
+}
+
 /// Wrapper around the "main" tree implementation used by [`MetadataCalculator`].
 ///
 /// Async methods provided by this wrapper are not cancel-safe! This is probably not an issue;
@@ -108,6 +402,10 @@ pub(super) struct AsyncTree {
 impl AsyncTree {
     const INCONSISTENT_MSG: &'static str =
         "`AsyncTree` is in inconsistent state, which could occur after one of its async methods was cancelled";
+    /// Custom manifest tag storing the first L1 batch processed by the tree in the lightweight mode.
+    /// Witness inputs and commitments are not produced for this batch and all batches after it, so they need
+    /// to be backfilled if the tree is switched to the full mode.
+    const LIGHTWEIGHT_SINCE_TAG: &'static str = "mode.lightweight_since";
 
     pub fn new(db: RocksDBWrapper, mode: MerkleTreeMode) -> Self {
         let tree = match mode {
@@ -132,6 +430,13 @@ impl AsyncTree {
         self.mode
     }
 
+    /// Closes the tree and removes its RocksDB directory (see [`destroy_db()`]); unsaved changes are discarded.
+    /// Returns an empty database at the same path.
+    pub async fn destroy(self) -> anyhow::Result<RocksDBWrapper> {
+        let tree = self.inner.expect(Self::INCONSISTENT_MSG);
+        destroy_db(tree.into_db()).await
+    }
+
     pub fn reader(&self) -> AsyncTreeReader {
         AsyncTreeReader {
             inner: self.inner.as_ref().expect(Self::INCONSISTENT_MSG).reader(),
@@ -151,13 +456,80 @@ impl AsyncTree {
         self.as_ref().root_hash()
     }
 
+    /// Returns the first L1 batch processed by the tree in the lightweight mode, unless the tree was switched
+    /// back to the full mode afterwards (see [`Self::clear_lightweight_since()`]). The genesis L1 batch
+    /// and the L1 batch the tree was recovered to don't count since they are processed identically in both modes.
+    pub fn lightweight_since(&self) -> anyhow::Result<Option<L1BatchNumber>> {
+        let tags = self.as_ref().custom_tags();
+        let Some(tag) = tags.get(Self::LIGHTWEIGHT_SINCE_TAG) else {
+            return Ok(None);
+        };
+        let l1_batch_number = tag.parse::<u32>().with_context(|| {
+            format!(
+                "failed parsing `{}` tag in Merkle tree manifest: {tag:?}",
+                Self::LIGHTWEIGHT_SINCE_TAG
+            )
+        })?;
+        Ok(Some(L1BatchNumber(l1_batch_number)))
+    }
+
+    /// Removes the tag set by the tree in the lightweight mode. Like other changes, this is persisted on [`Self::save()`].
+    pub fn clear_lightweight_since(&mut self) {
+        self.as_mut().update_custom_tags(|tags| {
+            tags.remove(Self::LIGHTWEIGHT_SINCE_TAG);
+        });
+    }
+
+    /// Returns the taint of the tree if its recovery was finalized despite a root hash mismatch.
+    pub fn taint(&self) -> anyhow::Result<Option<TreeTaint>> {
+        parse_custom_tag(&self.as_ref().custom_tags(), TreeTaint::TAG)
+    }
+
+    /// Returns the marker of the failed recovery if it's persisted in the tree manifest.
+    pub fn recovery_failure(&self) -> anyhow::Result<Option<RecoveryFailure>> {
+        parse_custom_tag(&self.as_ref().custom_tags(), RecoveryFailure::TAG)
+    }
+
+    /// Returns custom tags persisted in the tree manifest, including changes not yet saved to RocksDB.
+    pub fn custom_tags(&self) -> BTreeMap<String, String> {
+        self.as_ref().custom_tags()
+    }
+
+    /// Updates custom tags in the tree manifest. Like other changes, this is persisted on [`Self::save()`].
+    pub fn update_custom_tags<R>(
+        &mut self,
+        update: impl FnOnce(&mut BTreeMap<String, String>) -> R,
+    ) -> R {
+        self.as_mut().update_custom_tags(update)
+    }
+
+    /// Returns the root hash of the tree after processing the specified L1 batch, or `None` if the tree
+    /// doesn't contain the corresponding version (e.g., because it was pruned).
+    pub fn l1_batch_root_hash(&self, l1_batch_number: L1BatchNumber) -> Option<H256> {
+        self.as_ref().l1_batch_root_hash(l1_batch_number)
+    }
+
+    /// Checks whether the tree version for the specified L1 batch wasn't (even partially) pruned.
+    pub fn is_l1_batch_retained(&self, l1_batch_number: L1BatchNumber) -> bool {
+        self.as_ref().is_l1_batch_retained(l1_batch_number)
+    }
+
     pub async fn process_l1_batch(
         &mut self,
         storage_logs: Vec<TreeInstruction<StorageKey>>,
     ) -> TreeMetadata {
         let mut tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        // The genesis L1 batch doesn't need to be marked; it's processed in the same way in both tree modes.
+        let mark_lightweight = self.mode == MerkleTreeMode::Lightweight && !tree.is_empty();
         let (tree, metadata) = tokio::task::spawn_blocking(move || {
+            let l1_batch_number = tree.next_l1_batch_number();
             let metadata = tree.process_l1_batch(&storage_logs);
+            if mark_lightweight {
+                tree.update_custom_tags(|tags| {
+                    tags.entry(Self::LIGHTWEIGHT_SINCE_TAG.to_owned())
+                        .or_insert_with(|| l1_batch_number.0.to_string());
+                });
+            }
             (tree, metadata)
         })
         .await
@@ -182,6 +554,76 @@ impl AsyncTree {
     pub fn revert_logs(&mut self, last_l1_batch_to_keep: L1BatchNumber) {
         self.as_mut().revert_logs(last_l1_batch_to_keep);
     }
+
+    /// Returns at most `limit` entries with hashed keys in `key_range` in the ascending key order, together with
+    /// the continuation for the remaining entries. Entries have valid leaf indices both in the full
+    /// and lightweight tree modes.
+    pub async fn entries_range(
+        &mut self,
+        key_range: ops::RangeInclusive<H256>,
+        limit: usize,
+    ) -> TreeEntriesPage {
+        let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let (tree, page) = tokio::task::spawn_blocking(move || {
+            let range_end = *key_range.end();
+            let u256_range = h256_to_u256(*key_range.start())..=h256_to_u256(range_end);
+            // Load an extra entry to determine whether there are more entries in the range.
+            let mut entries = tree.entries_in_range(&u256_range, limit.saturating_add(1));
+            let continuation = if entries.len() > limit {
+                let next_entry = entries.pop().unwrap();
+                // ^ `unwrap()` is safe; `entries` are non-empty
+                Some(u256_to_h256(next_entry.key)..=range_end)
+            } else {
+                None
+            };
+            (
+                tree,
+                TreeEntriesPage {
+                    entries,
+                    continuation,
+                },
+            )
+        })
+        .await
+        .unwrap();
+
+        self.inner = Some(tree);
+        page
+    }
+
+    /// Returns entries together with Merkle proofs for the specified keys at the latest tree version
+    /// in the same order as `keys`. All entries are read in a single batch on the blocking thread pool.
+    pub async fn entries_with_proofs(
+        &mut self,
+        keys: Vec<Key>,
+    ) -> anyhow::Result<Vec<TreeEntryWithProof>> {
+        let Some(latest_l1_batch) = self.next_l1_batch_number().0.checked_sub(1) else {
+            anyhow::bail!("Merkle tree has no versions");
+        };
+        let l1_batch_number = L1BatchNumber(latest_l1_batch);
+        let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let (tree, entries) = tokio::task::spawn_blocking(move || {
+            let entries = tree.entries_with_proofs(l1_batch_number, &keys);
+            (tree, entries)
+        })
+        .await
+        .unwrap();
+
+        self.inner = Some(tree);
+        entries.with_context(|| {
+            format!("failed getting entries with proofs for L1 batch #{l1_batch_number}")
+        })
+    }
+}
+
+/// Page of tree entries returned by [`AsyncTree::entries_range()`].
+#[derive(Debug)]
+pub(crate) struct TreeEntriesPage {
+    /// Entries in the ascending key order.
+    pub entries: Vec<TreeEntry>,
+    /// Continuation token: key range to request the next page with. `None` if there are no more entries
+    /// in the requested range.
+    pub continuation: Option<ops::RangeInclusive<H256>>,
 }
 
 /// Async version of [`ZkSyncTreeReader`].
@@ -203,6 +645,16 @@ impl AsyncTreeReader {
         .unwrap()
     }
 
+    pub async fn entries(
+        self,
+        l1_batch_number: L1BatchNumber,
+        keys: Vec<Key>,
+    ) -> Result<Vec<TreeEntry>, NoVersionError> {
+        tokio::task::spawn_blocking(move || self.inner.entries(l1_batch_number, &keys))
+            .await
+            .unwrap()
+    }
+
     pub async fn entries_with_proofs(
         self,
         l1_batch_number: L1BatchNumber,
@@ -212,26 +664,229 @@ impl AsyncTreeReader {
             .await
             .unwrap()
     }
+
+    /// Verifies consistency of the tree at the specified L1 batch.
+    pub async fn verify_consistency(self, l1_batch_number: L1BatchNumber) -> anyhow::Result<()> {
+        tokio::task::spawn_blocking(move || {
+            self.inner
+                .verify_consistency(l1_batch_number)
+                .with_context(|| {
+                    format!("Merkle tree at L1 batch #{l1_batch_number} is inconsistent")
+                })
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Creates a RocksDB checkpoint of the tree at the specified `path`, which must not exist.
+    pub async fn create_checkpoint(self, path: PathBuf) -> anyhow::Result<()> {
+        tokio::task::spawn_blocking(move || {
+            self.inner.create_checkpoint(&path).with_context(|| {
+                format!(
+                    "failed creating Merkle tree checkpoint at `{}`",
+                    path.display()
+                )
+            })
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Prunes tree versions older than `past_versions_to_keep` behind the latest persisted version until
+    /// all eligible versions are pruned or `abort` resolves.
+    pub async fn prune(
+        self,
+        past_versions_to_keep: u64,
+        abort: impl Future<Output = ()>,
+    ) -> PruningOutcome {
+        let (pruner, pruner_handle) = self.inner.pruner(past_versions_to_keep);
+        let mut pruning_task = tokio::task::spawn_blocking(|| pruner.run_to_completion());
+        tokio::select! {
+            biased;
+
+            () = abort => {
+                pruner_handle.abort();
+                pruning_task.await.unwrap()
+            }
+            outcome = &mut pruning_task => outcome.unwrap(),
+        }
+    }
+
+    /// Runs manual compaction of the tree RocksDB and returns the number of reclaimed bytes.
+    pub async fn compact_db(self) -> u64 {
+        tokio::task::spawn_blocking(move || self.inner.compact_db())
+            .await
+            .unwrap()
+    }
 }
 
+/// Name of the marker file created in the tree RocksDB directory while writes to the database are not durable
+/// (see [`AsyncTreeRecovery::relax_durability()`]).
+const UNSYNCED_WRITES_MARKER: &str = "UNSYNCED_RECOVERY";
+
 /// Async wrapper for [`MerkleTreeRecovery`].
 #[derive(Debug, Default)]
 pub(super) struct AsyncTreeRecovery {
     inner: Option<MerkleTreeRecovery<RocksDBWrapper>>,
     mode: MerkleTreeMode,
+    db_path: PathBuf,
+    /// RocksDB options restored when recovery is finalized. Set if the database was reopened
+    /// with a [`RecoveryDbProfile`] or with relaxed durability.
+    normal_db_options: Option<RocksDBOptions>,
+    uses_db_profile: bool,
+    relaxed_durability: bool,
+    background_write_rate_limit: Option<usize>,
+    /// Number of threads in the dedicated thread pool for hashing (see [`Self::use_dedicated_thread_pool()`]).
+    /// Retained so that the pool can be recreated when the database is reopened.
+    hashing_threads: Option<usize>,
+    /// Set if the tree has panicked while being extended. In this case, the tree may be partially updated,
+    /// so it cannot be extended further.
+    is_poisoned: bool,
+    /// If set, [`Self::extend()`] panics when extending the tree with an entry with this key.
+    #[cfg(test)]
+    panic_on_key: Option<Key>,
 }
 
 impl AsyncTreeRecovery {
     const INCONSISTENT_MSG: &'static str =
         "`AsyncTreeRecovery` is in inconsistent state, which could occur after one of its async methods was cancelled";
 
+    /// Creates a recovery wrapper. Recovered tree data doesn't depend on `mode`; it only determines the mode
+    /// of the tree after recovery is finalized. Thus, a tree recovered in the lightweight mode can be upgraded
+    /// to the full mode later on.
     pub fn new(db: RocksDBWrapper, recovered_version: u64, mode: MerkleTreeMode) -> Self {
+        let db_path = db.path().to_owned();
         Self {
             inner: Some(MerkleTreeRecovery::new(db, recovered_version)),
             mode,
+            db_path,
+            normal_db_options: None,
+            uses_db_profile: false,
+            relaxed_durability: false,
+            background_write_rate_limit: None,
+            hashing_threads: None,
+            is_poisoned: false,
+            #[cfg(test)]
+            panic_on_key: None,
         }
     }
 
+    /// Makes [`Self::extend()`] panic when extending the tree with an entry with the specified key.
+    #[cfg(test)]
+    pub fn panic_on_key(&mut self, key: Key) {
+        self.panic_on_key = Some(key);
+    }
+
+    /// Closes the tree RocksDB and reopens it with options obtained by applying `map_options` to the current ones.
+    async fn reopen_db(
+        &mut self,
+        map_options: impl FnOnce(RocksDBOptions) -> RocksDBOptions + Send + 'static,
+    ) -> anyhow::Result<()> {
+        let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let recovered_version = tree.recovered_version();
+        let (db, prev_options) =
+            tokio::task::spawn_blocking(move || reopen_db_sync(tree.into_db(), map_options))
+                .await
+                .unwrap();
+        self.inner = Some(MerkleTreeRecovery::new(db, recovered_version));
+        self.normal_db_options.get_or_insert(prev_options);
+        if let Some(thread_count) = self.hashing_threads {
+            self.use_dedicated_thread_pool(thread_count)?;
+        }
+        Ok(())
+    }
+
+    /// Reopens the tree RocksDB tuned according to the recovery `profile`. The normal RocksDB options are restored
+    /// by [`Self::finalize()`]; before that, the database should be compacted using [`Self::compact_db()`].
+    pub async fn use_db_profile(&mut self, profile: RecoveryDbProfile) -> anyhow::Result<()> {
+        if self.uses_db_profile {
+            return Ok(()); // The profile is already applied
+        }
+        self.reopen_db(move |options| profile.apply(options))
+            .await?;
+        self.uses_db_profile = true;
+        Ok(())
+    }
+
+    /// Checks whether the tree RocksDB is tuned according to a [`RecoveryDbProfile`].
+    pub fn uses_db_profile(&self) -> bool {
+        self.uses_db_profile
+    }
+
+    /// Reopens the tree RocksDB with the write-ahead log disabled. Writes become durable only after an explicit flush,
+    /// which is performed by [`Self::finalize()`]. Until then, a marker file is kept in the database directory,
+    /// so that after a crash, [`Self::may_have_lost_writes()`] returns `true` on the next startup.
+    pub async fn relax_durability(&mut self) -> anyhow::Result<()> {
+        if self.relaxed_durability {
+            return Ok(());
+        }
+        let marker_path = self.db_path.join(UNSYNCED_WRITES_MARKER);
+        tokio::task::spawn_blocking(move || create_marker_file(&marker_path))
+            .await
+            .unwrap()?;
+        self.reopen_db(|options| RocksDBOptions {
+            disable_wal: true,
+            ..options
+        })
+        .await?;
+        self.relaxed_durability = true;
+        Ok(())
+    }
+
+    /// Reopens the tree RocksDB with the specified limit on the rate of background writes (memtable flushes
+    /// and compactions) in bytes per second, or without a limit if `rate_limit` is `None`. Does nothing
+    /// if the limit doesn't change. The normal RocksDB options are restored by [`Self::finalize()`]; compaction
+    /// performed when finalizing recovery is still limited.
+    pub async fn set_background_write_rate_limit(
+        &mut self,
+        rate_limit: Option<usize>,
+    ) -> anyhow::Result<()> {
+        if self.background_write_rate_limit == rate_limit {
+            return Ok(());
+        }
+        self.reopen_db(move |options| RocksDBOptions {
+            background_write_rate_limit: rate_limit,
+            ..options
+        })
+        .await?;
+        self.background_write_rate_limit = rate_limit;
+        RECOVERY_METRICS
+            .background_write_rate_limit
+            .set(rate_limit.unwrap_or(0));
+        Ok(())
+    }
+
+    /// Returns the current limit on the rate of background writes (see [`Self::set_background_write_rate_limit()`]).
+    pub fn background_write_rate_limit(&self) -> Option<usize> {
+        self.background_write_rate_limit
+    }
+
+    /// Checks whether some tree writes may have been lost, i.e., whether the tree RocksDB was written to
+    /// with relaxed durability (see [`Self::relax_durability()`]) and the writes weren't flushed afterwards.
+    /// In this case, chunks that look recovered based on their first key may be only partially persisted.
+    pub fn may_have_lost_writes(&self) -> bool {
+        self.db_path.join(UNSYNCED_WRITES_MARKER).exists()
+    }
+
+    /// Returns the path to the tree RocksDB directory.
+    pub fn db_path(&self) -> &Path {
+        &self.db_path
+    }
+
+    /// Signals that the tree should use a dedicated thread pool with the specified number of threads
+    /// to hash tree nodes when extending the tree.
+    pub fn use_dedicated_thread_pool(&mut self, thread_count: usize) -> anyhow::Result<()> {
+        self.inner
+            .as_mut()
+            .expect(Self::INCONSISTENT_MSG)
+            .use_dedicated_thread_pool(thread_count)
+            .with_context(|| {
+                format!("failed initializing hashing thread pool ({thread_count} threads)")
+            })?;
+        self.hashing_threads = Some(thread_count);
+        Ok(())
+    }
+
     pub fn recovered_version(&self) -> u64 {
         self.inner
             .as_ref()
@@ -239,16 +894,150 @@ impl AsyncTreeRecovery {
             .recovered_version()
     }
 
-    /// Returns an entry for the specified key.
-    pub async fn entries(&mut self, keys: Vec<Key>) -> Vec<TreeEntry> {
+    /// Returns the mode of the tree after recovery is finalized.
+    pub fn mode(&self) -> MerkleTreeMode {
+        self.mode
+    }
+
+    /// Returns custom tags persisted in the tree manifest.
+    pub async fn custom_tags(&mut self) -> BTreeMap<String, String> {
         let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
-        let (entry, tree) = tokio::task::spawn_blocking(move || (tree.entries(&keys), tree))
+        let (tags, tree) = tokio::task::spawn_blocking(move || (tree.custom_tags(), tree))
             .await
             .unwrap();
         self.inner = Some(tree);
+        tags
+    }
+
+    /// Marks the recovering tree as tainted. The taint is retained after recovery is finalized.
+    pub async fn mark_tainted(&mut self, taint: TreeTaint) -> anyhow::Result<()> {
+        let taint = serde_json::to_string(&taint).context("failed serializing tree taint")?;
+        self.update_custom_tags(move |tags| {
+            tags.insert(TreeTaint::TAG.to_owned(), taint);
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Returns the marker of the failed recovery if it was persisted.
+    pub async fn recovery_failure(&mut self) -> anyhow::Result<Option<RecoveryFailure>> {
+        parse_custom_tag(&self.custom_tags().await, RecoveryFailure::TAG)
+    }
+
+    /// Persists the marker of the failed recovery.
+    pub async fn mark_recovery_failed(&mut self, failure: RecoveryFailure) -> anyhow::Result<()> {
+        let failure =
+            serde_json::to_string(&failure).context("failed serializing recovery failure")?;
+        self.update_custom_tags(move |tags| {
+            tags.insert(RecoveryFailure::TAG.to_owned(), failure);
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Updates custom tags persisted in the tree manifest.
+    pub async fn update_custom_tags<F, R>(&mut self, update: F) -> R
+    where
+        F: FnOnce(&mut BTreeMap<String, String>) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let mut tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let (output, tree) = tokio::task::spawn_blocking(move || {
+            let output = tree.update_custom_tags(update);
+            (output, tree)
+        })
+        .await
+        .unwrap();
+        self.inner = Some(tree);
+        output
+    }
+
+    /// Returns the recovery journal entry for the specified key.
+    pub async fn journal_entry(&mut self, key: Vec<u8>) -> Option<Vec<u8>> {
+        let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let (entry, tree) =
+            tokio::task::spawn_blocking(move || (tree.recovery_journal_entry(&key), tree))
+                .await
+                .unwrap();
+        self.inner = Some(tree);
         entry
     }
 
+    /// Returns all recovery journal entries in the lexical key order.
+    pub async fn journal_entries(&mut self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let (entries, tree) =
+            tokio::task::spawn_blocking(move || (tree.recovery_journal_entries(), tree))
+                .await
+                .unwrap();
+        self.inner = Some(tree);
+        entries
+    }
+
+    /// Sets the recovery journal entry for the specified key, or removes it if `value` is `None`.
+    pub async fn set_journal_entry(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+        let mut tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let tree = tokio::task::spawn_blocking(move || {
+            tree.set_recovery_journal_entry(&key, value.as_deref());
+            tree
+        })
+        .await
+        .unwrap();
+        self.inner = Some(tree);
+    }
+
+    /// Returns entries for the specified keys in the same order as `keys`. Keys are looked up in batches,
+    /// so that a huge number of keys doesn't result in a single enormous RocksDB multi-get.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from RocksDB fails, e.g. because of an I/O error or corrupted data.
+    pub async fn entries(&mut self, keys: Vec<Key>) -> anyhow::Result<Vec<TreeEntry>> {
+        /// Maximum number of keys looked up in the tree at once.
+        const BATCH_SIZE: usize = 500;
+
+        let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let (entries, tree) = tokio::task::spawn_blocking(move || {
+            let mut entries = Vec::with_capacity(keys.len());
+            for batch in keys.chunks(BATCH_SIZE) {
+                // The tree panics on RocksDB errors; reads don't modify the tree, so it's safe to use it
+                // after a caught panic.
+                let batch_entries = panic::catch_unwind(AssertUnwindSafe(|| tree.entries(batch)));
+                match batch_entries {
+                    Ok(batch_entries) => entries.extend(batch_entries),
+                    Err(panic) => {
+                        let err = anyhow::anyhow!(
+                            "Failed reading tree entries for keys {:0>64x}..={:0>64x}: {}",
+                            batch[0],
+                            batch[batch.len() - 1],
+                            panic_message(&*panic)
+                        );
+                        return (Err(err), tree);
+                    }
+                }
+            }
+            (Ok(entries), tree)
+        })
+        .await
+        .unwrap();
+        self.inner = Some(tree);
+        entries
+    }
+
+    /// Returns all entries with keys in the specified range, in the ascending key order.
+    pub async fn entries_in_range(
+        &mut self,
+        key_range: ops::RangeInclusive<Key>,
+    ) -> Vec<TreeEntry> {
+        let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let (entries, tree) =
+            tokio::task::spawn_blocking(move || (tree.entries_in_range(&key_range), tree))
+                .await
+                .unwrap();
+        self.inner = Some(tree);
+        entries
+    }
+
     /// Returns the current hash of the tree.
     pub async fn root_hash(&mut self) -> H256 {
         let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
@@ -259,28 +1048,239 @@ impl AsyncTreeRecovery {
         root_hash
     }
 
+    /// Returns the number of entries inserted into the tree so far.
+    pub async fn leaf_count(&mut self) -> u64 {
+        let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let (leaf_count, tree) = tokio::task::spawn_blocking(move || (tree.leaf_count(), tree))
+            .await
+            .unwrap();
+        self.inner = Some(tree);
+        leaf_count
+    }
+
+    /// Returns the least and the greatest keys inserted into the tree so far, or `None` if the tree is empty.
+    pub async fn key_range(&mut self) -> Option<ops::RangeInclusive<Key>> {
+        let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let (key_range, tree) = tokio::task::spawn_blocking(move || {
+            let key_range = tree
+                .least_key()
+                .zip(tree.last_processed_key())
+                .map(|(least_key, greatest_key)| least_key..=greatest_key);
+            (key_range, tree)
+        })
+        .await
+        .unwrap();
+        self.inner = Some(tree);
+        key_range
+    }
+
     /// Extends the tree with a chunk of recovery entries.
-    pub async fn extend(&mut self, entries: Vec<TreeEntry>) {
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the tree panics while being extended (e.g., on an internal invariant violation).
+    /// In this case, the tree is left in an inconsistent state; all following calls will return an error as well.
+    pub async fn extend(&mut self, entries: Vec<TreeEntry>) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !self.is_poisoned,
+            "Merkle tree has panicked while being extended previously and cannot be extended further"
+        );
         let mut tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
-        let tree = tokio::task::spawn_blocking(move || {
+        let entry_count = entries.len();
+        #[cfg(test)]
+        let panic_on_key = self.panic_on_key;
+        let result = tokio::task::spawn_blocking(move || {
+            #[cfg(test)]
+            if let Some(key) = panic_on_key {
+                assert!(
+                    entries.iter().all(|entry| entry.key != key),
+                    "Test panic on key {key:0>64x}"
+                );
+            }
             tree.extend_random(entries);
             tree
         })
+        .await;
+
+        match result {
+            Ok(tree) => {
+                self.inner = Some(tree);
+                Ok(())
+            }
+            Err(err) if err.is_panic() => {
+                // The tree may be partially updated after a panic, so it's not restored.
+                self.is_poisoned = true;
+                Err(anyhow::anyhow!(
+                    "Merkle tree panicked extending with {entry_count} entries: {}",
+                    panic_message(&*err.into_panic())
+                ))
+            }
+            Err(err) => {
+                self.is_poisoned = true;
+                Err(anyhow::Error::from(err).context(format!(
+                    "failed extending Merkle tree with {entry_count} entries"
+                )))
+            }
+        }
+    }
+
+    /// Discards all recovery progress by removing the tree (see [`Self::destroy()`]) and restarts recovery
+    /// for the same tree version from scratch.
+    pub async fn reset(self) -> anyhow::Result<Self> {
+        let recovered_version = self.recovered_version();
+        self.reset_to_version(recovered_version).await
+    }
+
+    /// Same as [`Self::reset()`], but restarts recovery for the specified tree version.
+    pub async fn reset_to_version(self, recovered_version: u64) -> anyhow::Result<Self> {
+        let mode = self.mode;
+        let normal_db_options = self.normal_db_options;
+        let uses_db_profile = self.uses_db_profile;
+        let relaxed_durability = self.relaxed_durability;
+        let background_write_rate_limit = self.background_write_rate_limit;
+        let db_path = self.db_path.clone();
+        let db = self.destroy().await?;
+        if relaxed_durability {
+            // The marker is removed together with the database directory.
+            let marker_path = db_path.join(UNSYNCED_WRITES_MARKER);
+            tokio::task::spawn_blocking(move || create_marker_file(&marker_path))
+                .await
+                .unwrap()?;
+        }
+        // The database is reopened with the same options, so the recovery profile, relaxed durability
+        // and the background write rate limit are retained.
+        Ok(Self {
+            normal_db_options,
+            uses_db_profile,
+            relaxed_durability,
+            background_write_rate_limit,
+            ..Self::new(db, recovered_version, mode)
+        })
+    }
+
+    /// Closes the tree and removes its RocksDB directory (see [`destroy_db()`]). Returns an empty database
+    /// at the same path, which can be used to initialize the tree from scratch.
+    pub async fn destroy(self) -> anyhow::Result<RocksDBWrapper> {
+        let tree = self.inner.expect(Self::INCONSISTENT_MSG);
+        destroy_db(tree.into_db()).await
+    }
+
+    /// Prunes stale keys accumulated during recovery. This is done by [`Self::finalize()`] as well,
+    /// but calling this method beforehand allows to measure pruning separately.
+    pub async fn prune_stale_keys(&mut self) {
+        let mut tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let tree = tokio::task::spawn_blocking(move || {
+            tree.prune_stale_keys();
+            tree
+        })
         .await
         .unwrap();
-
         self.inner = Some(tree);
     }
 
-    pub async fn finalize(self) -> AsyncTree {
-        let tree = self.inner.expect(Self::INCONSISTENT_MSG);
-        let db = tokio::task::spawn_blocking(|| tree.finalize())
+    /// Flushes memtables of the tree RocksDB to disk.
+    ///
+    /// # Errors
+    ///
+    /// Propagates RocksDB I/O errors.
+    pub async fn flush_db(&mut self) -> anyhow::Result<()> {
+        let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let (result, tree) = tokio::task::spawn_blocking(move || (tree.flush_db(), tree))
             .await
             .unwrap();
+        self.inner = Some(tree);
+        result.context("failed flushing Merkle tree RocksDB")
+    }
+
+    /// Runs manual RocksDB compaction and returns the number of reclaimed bytes. This is necessary after bulk loading
+    /// with a [`RecoveryDbProfile`], which disables auto-compaction.
+    pub async fn compact_db(&mut self) -> u64 {
+        let tree = self.inner.take().expect(Self::INCONSISTENT_MSG);
+        let (reclaimed_bytes, tree) = tokio::task::spawn_blocking(move || {
+            let reclaimed_bytes = tree.compact_db();
+            (reclaimed_bytes, tree)
+        })
+        .await
+        .unwrap();
+        self.inner = Some(tree);
+        reclaimed_bytes
+    }
+
+    /// Finalizes recovery. The recovery journal is cleared before finalizing. If the tree RocksDB was reopened
+    /// with a [`RecoveryDbProfile`] or relaxed durability, it's reopened with the normal options.
+    ///
+    /// If writes may have been lost (see [`Self::may_have_lost_writes()`]), all writes are flushed and synced
+    /// to disk before removing the unsynced writes marker.
+    pub async fn finalize(self) -> AsyncTree {
+        let mut tree = self.inner.expect(Self::INCONSISTENT_MSG);
+        let normal_db_options = self.normal_db_options;
+        let marker_path = self.db_path.join(UNSYNCED_WRITES_MARKER);
+        let db = tokio::task::spawn_blocking(move || {
+            tree.clear_recovery_journal();
+            let db = tree.finalize();
+            if marker_path.exists() {
+                db.flush().expect("Failed flushing Merkle tree RocksDB");
+                if let Err(err) = fs::remove_file(&marker_path) {
+                    tracing::warn!(
+                        "Failed removing unsynced writes marker `{}`: {err}",
+                        marker_path.display()
+                    );
+                }
+            }
+            if let Some(options) = normal_db_options {
+                reopen_db_sync(db, |_| options).0
+            } else {
+                db
+            }
+        })
+        .await
+        .unwrap();
         AsyncTree::new(db, self.mode)
     }
 }
 
+/// State of the Merkle tree maintained by [`MetadataCalculator`](super::MetadataCalculator) returned by
+/// [`MetadataCalculator::tree_state()`](super::MetadataCalculator::tree_state()). The state is also published
+/// in the tree health details when the calculator starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum TreeState {
+    /// Tree is not initialized. It will be either recovered from a snapshot or built from the genesis L1 batch.
+    Empty { mode: MerkleTreeMode },
+    /// Tree is being recovered to the specified version (i.e., L1 batch number).
+    Recovering {
+        recovered_version: u64,
+        mode: MerkleTreeMode,
+    },
+    /// Tree is ready for normal operation.
+    Ready {
+        next_l1_batch_number: L1BatchNumber,
+        mode: MerkleTreeMode,
+    },
+}
+
+impl fmt::Display for TreeState {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty { mode } => write!(formatter, "empty ({mode:?} mode)"),
+            Self::Recovering {
+                recovered_version,
+                mode,
+            } => write!(
+                formatter,
+                "recovering to L1 batch #{recovered_version} ({mode:?} mode)"
+            ),
+            Self::Ready {
+                next_l1_batch_number,
+                mode,
+            } => write!(
+                formatter,
+                "ready with next L1 batch #{next_l1_batch_number} ({mode:?} mode)"
+            ),
+        }
+    }
+}
+
 /// Tree at any stage of its life cycle.
 #[derive(Debug)]
 pub(super) enum GenericAsyncTree {
@@ -310,6 +1310,47 @@ impl GenericAsyncTree {
         .await
         .unwrap()
     }
+
+    /// Destroys the tree, removing its RocksDB directory (see [`destroy_db()`]), and returns an empty tree
+    /// that can be recovered or built from genesis.
+    pub async fn reset(self) -> anyhow::Result<Self> {
+        let (db, mode) = match self {
+            Self::Empty { db, mode } => (destroy_db(db).await?, mode),
+            Self::Recovering(tree) => {
+                let mode = tree.mode();
+                (tree.destroy().await?, mode)
+            }
+            Self::Ready(tree) => {
+                let mode = tree.mode();
+                (tree.destroy().await?, mode)
+            }
+        };
+        Ok(Self::Empty { db, mode })
+    }
+
+    /// Returns the marker of the failed recovery if it's persisted in the tree (see [`RecoveryFailure`]).
+    pub async fn recovery_failure(&mut self) -> anyhow::Result<Option<RecoveryFailure>> {
+        match self {
+            Self::Empty { .. } => Ok(None),
+            Self::Recovering(tree) => tree.recovery_failure().await,
+            Self::Ready(tree) => tree.recovery_failure(),
+        }
+    }
+
+    /// Returns the current state of the tree.
+    pub fn state(&self) -> TreeState {
+        match self {
+            Self::Empty { mode, .. } => TreeState::Empty { mode: *mode },
+            Self::Recovering(tree) => TreeState::Recovering {
+                recovered_version: tree.recovered_version(),
+                mode: tree.mode(),
+            },
+            Self::Ready(tree) => TreeState::Ready {
+                next_l1_batch_number: tree.next_l1_batch_number(),
+                mode: tree.mode(),
+            },
+        }
+    }
 }
 
 /// Component implementing the delay policy in [`MetadataCalculator`] when there are no
@@ -434,6 +1475,8 @@ impl L1BatchWithLogs {
 mod tests {
     use tempfile::TempDir;
     use zksync_dal::ConnectionPool;
+    use zksync_merkle_tree::MerkleTreeColumnFamily;
+    use zksync_storage::rocksdb::properties;
     use zksync_types::{proofs::PrepareBasicCircuitsJob, L2ChainId, StorageKey, StorageLog};
 
     use super::*;
@@ -559,15 +1602,124 @@ mod tests {
     }
 
     async fn create_tree(temp_dir: &TempDir) -> AsyncTree {
-        let db = create_db(
-            temp_dir.path().to_owned(),
-            0,
-            16 << 20,       // 16 MiB,
-            Duration::ZERO, // writes should never be stalled in tests
-            500,
-        )
-        .await;
-        AsyncTree::new(db, MerkleTreeMode::Full)
+        create_tree_with_mode(temp_dir, MerkleTreeMode::Full).await
+    }
+
+    async fn create_tree_with_mode(temp_dir: &TempDir, mode: MerkleTreeMode) -> AsyncTree {
+        let db = create_db(temp_dir.path().to_owned(), TreeDbParams::for_tests(), None).await;
+        AsyncTree::new(db, mode)
+    }
+
+    #[tokio::test]
+    async fn creating_db_with_custom_sizes() {
+        let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+        let params = TreeDbParams {
+            block_cache_capacity: 32 << 20,
+            total_write_buffer_size: Some(64 << 20),
+            max_open_files: Some(256),
+            ..TreeDbParams::for_tests()
+        };
+        let db = create_db(temp_dir.path().join("normal"), params, None).await;
+        assert_db_sizes(&db, 32 << 20, 64 << 20, 256);
+
+        // Recovery profile overrides are applied on top of normal params.
+        let profile = RecoveryDbProfile {
+            memtable_capacity: 32 << 20,
+            max_write_buffer_number: 4,
+            block_cache_capacity: Some(8 << 20),
+            total_write_buffer_size: Some(128 << 20),
+            max_open_files: None,
+        };
+        let db = create_db(temp_dir.path().join("recovery"), params, Some(profile)).await;
+        assert_db_sizes(&db, 8 << 20, 128 << 20, 256);
+    }
+
+    fn assert_db_sizes(
+        db: &RocksDBWrapper,
+        block_cache_capacity: u64,
+        total_write_buffer_size: u64,
+        max_open_files: usize,
+    ) {
+        let db = db.clone().into_inner();
+        let actual_block_cache_capacity = db.int_property(
+            MerkleTreeColumnFamily::Tree,
+            properties::BLOCK_CACHE_CAPACITY,
+        );
+        assert_eq!(actual_block_cache_capacity, Some(block_cache_capacity));
+
+        let options = db.options();
+        assert_eq!(
+            options.total_write_buffer_size,
+            Some(total_write_buffer_size as usize)
+        );
+        assert_eq!(options.max_open_files, Some(max_open_files));
+    }
+
+    #[tokio::test]
+    async fn reading_entries_range() {
+        for mode in [MerkleTreeMode::Full, MerkleTreeMode::Lightweight] {
+            test_reading_entries_range(mode).await;
+        }
+    }
+
+    async fn test_reading_entries_range(mode: MerkleTreeMode) {
+        let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+        let mut tree = create_tree_with_mode(&temp_dir, mode).await;
+        let logs = gen_storage_logs(100..200, 2);
+        let mut leaf_index = 0;
+        for batch_logs in &logs {
+            let instructions = batch_logs
+                .iter()
+                .map(|log| {
+                    leaf_index += 1;
+                    TreeInstruction::write(log.key, leaf_index, log.value)
+                })
+                .collect();
+            tree.process_l1_batch(instructions).await;
+        }
+
+        // Brute-force expected entries from the written logs.
+        let mut expected_entries: Vec<_> = logs
+            .iter()
+            .flatten()
+            .zip(1..)
+            .map(|(log, leaf_index)| {
+                TreeEntry::new(log.key.hashed_key_u256(), leaf_index, log.value)
+            })
+            .collect();
+        expected_entries.sort_unstable_by_key(|entry| entry.key);
+
+        let full_range = H256::zero()..=H256::repeat_byte(0xff);
+        let page = tree.entries_range(full_range.clone(), usize::MAX).await;
+        assert_eq!(page.entries, expected_entries, "mode={mode:?}");
+        assert_eq!(page.continuation, None);
+
+        let key_range = H256::repeat_byte(0x40)..=H256::repeat_byte(0xa0);
+        let expected_range_entries: Vec<_> = expected_entries
+            .iter()
+            .filter(|entry| key_range.contains(&u256_to_h256(entry.key)))
+            .copied()
+            .collect();
+        assert!(!expected_range_entries.is_empty());
+
+        // Page through the range.
+        let mut range_entries = vec![];
+        let mut continuation = Some(key_range);
+        while let Some(range) = continuation {
+            let page = tree.entries_range(range, 7).await;
+            assert!(page.entries.len() <= 7);
+            if page.continuation.is_some() {
+                assert_eq!(page.entries.len(), 7);
+            }
+            range_entries.extend(page.entries);
+            continuation = page.continuation;
+        }
+        assert_eq!(range_entries, expected_range_entries, "mode={mode:?}");
+
+        let empty_range = H256::repeat_byte(0xff)..=H256::repeat_byte(0xff);
+        let page = tree.entries_range(empty_range, 10).await;
+        assert!(page.entries.is_empty());
+        assert_eq!(page.continuation, None);
     }
 
     async fn assert_log_equivalence(
@@ -3,13 +3,13 @@
 use std::time::{Duration, Instant};
 
 use vise::{
-    Buckets, EncodeLabelSet, EncodeLabelValue, Family, Gauge, Histogram, LatencyObserver, Metrics,
-    Unit,
+    Buckets, Counter, EncodeLabelSet, EncodeLabelValue, Family, Gauge, Histogram, LatencyObserver,
+    Metrics, Unit,
 };
 use zksync_types::block::L1BatchHeader;
 use zksync_utils::time::seconds_since_epoch;
 
-use super::MetadataCalculator;
+use super::{MetadataCalculator, RecoveryFinalizeStage};
 use crate::metrics::{BlockStage, APP_METRICS};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
@@ -90,6 +90,8 @@ pub(super) struct MetadataCalculatorMetrics {
     /// The lag can only be positive if Postgres was restored from a backup truncating some
     /// of the batches already processed by the tree.
     pub backup_lag: Gauge<u64>,
+    /// Number of L1 batches sealed in Postgres, but not yet processed by the Merkle tree.
+    pub tree_lag_batches: Gauge<u64>,
     /// Number of zero values that need to be checked for L1 batch of the initial write in the process
     /// of updating the Merkle tree.
     #[metrics(buckets = COUNTS_BUCKETS)]
@@ -179,8 +181,17 @@ impl MetadataCalculator {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
 #[metrics(label = "stage", rename_all = "snake_case")]
 pub(super) enum RecoveryStage {
+    LoadKeyHistogram,
     LoadChunkStarts,
+    FilterChunks,
+    CountChunkEntries,
     Finalize,
+    Verify,
+    VerifyProofs,
+    Diagnose,
+    Export,
+    Import,
+    IntegrityCheck,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
@@ -188,25 +199,198 @@ pub(super) enum RecoveryStage {
 pub(super) enum ChunkRecoveryStage {
     AcquireConnection,
     LoadEntries,
-    LockTree,
+    /// Time the tree spends idle waiting for loaded entries.
+    WaitForEntries,
     ExtendTree,
+    /// Manual flush of the tree RocksDB memtables performed after applying entries.
+    Flush,
 }
 
+/// Buckets for the number of entries in a recovery chunk (from 1k to 1M).
+const CHUNK_ENTRIES_BUCKETS: Buckets = Buckets::values(&[
+    1_000.0,
+    2_000.0,
+    5_000.0,
+    10_000.0,
+    20_000.0,
+    50_000.0,
+    100_000.0,
+    200_000.0,
+    500_000.0,
+    1_000_000.0,
+]);
+/// Buckets for the total duration of recovering a chunk (from 100ms to 10min).
+const CHUNK_DURATION_BUCKETS: Buckets = Buckets::values(&[
+    0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0,
+]);
+/// Buckets for the per-chunk throughput in entries per second (from 100 to 1M).
+const CHUNK_THROUGHPUT_BUCKETS: Buckets = Buckets::values(&[
+    100.0,
+    200.0,
+    500.0,
+    1_000.0,
+    2_000.0,
+    5_000.0,
+    10_000.0,
+    20_000.0,
+    50_000.0,
+    100_000.0,
+    200_000.0,
+    500_000.0,
+    1_000_000.0,
+]);
+
+/// Buckets for the time spent acquiring a Postgres connection (from 1ms to 10min). Compared to the default
+/// latency buckets, these make long waits on a saturated connection pool visible.
+const CONNECTION_WAIT_BUCKETS: Buckets = Buckets::values(&[
+    0.001, 0.005, 0.025, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0,
+]);
+
 /// Metrics for Merkle tree recovery driven by the metadata calculator.
 #[derive(Debug, Metrics)]
 #[metrics(prefix = "server_metadata_calculator_recovery")]
 pub(super) struct MetadataCalculatorRecoveryMetrics {
-    /// Number of chunks recovered.
+    /// Total number of chunks in the recovery.
+    pub chunk_count: Gauge<usize>,
+    /// Number of chunks recovered, including ones recovered before the recovery was resumed after a restart.
     pub recovered_chunk_count: Gauge<usize>,
+    /// Number of chunks remaining to be recovered.
+    pub remaining_chunks: Gauge<usize>,
+    /// Number of chunks checked for being recovered before recovery starts or resumes
+    /// (see [`RecoveryStage::FilterChunks`]). Equals `chunk_count` once all chunks are checked.
+    pub filtered_chunk_count: Gauge<usize>,
+    /// Total number of entries inserted into the tree, including ones inserted before the recovery was resumed
+    /// after a restart (the latter are taken from the tree leaf count when recovery is resumed).
+    pub inserted_entries: Counter,
+    /// Number of entries inserted into the tree per second over a sliding window of recently recovered chunks.
+    pub entries_per_second: Gauge<f64>,
+    /// Number of chunk recovery retries caused by transient errors.
+    pub chunk_retries: Counter,
+    /// Number of recovered chunks repaired because their tree entries mismatched Postgres when recovery was resumed.
+    pub repaired_chunks: Counter,
+    /// Number of snapshot queries that fell back from the Postgres read replica to the primary because the replica
+    /// lagged behind the snapshot or a replica query failed.
+    pub replica_fallbacks: Counter,
+    /// Number of retries acquiring a Postgres connection during recovery.
+    pub connection_retries: Counter,
+    /// Number of times acquiring a Postgres connection during recovery timed out because the connection pool
+    /// was saturated. Such acquisitions are retried.
+    pub connection_starvations: Counter,
+    /// Time spent acquiring a Postgres connection during recovery, including retries after timeouts and errors.
+    #[metrics(buckets = CONNECTION_WAIT_BUCKETS, unit = Unit::Seconds)]
+    pub connection_wait: Histogram<Duration>,
+    /// Number of chunks for which loading entries using binary `COPY` failed, so that a conventional query was used.
+    pub copy_fallbacks: Counter,
+    /// Effective maximum number of concurrently recovered chunks.
+    pub concurrency_limit: Gauge<usize>,
+    /// Limit on the rate of background writes (flushes and compactions) of the tree RocksDB during recovery
+    /// in bytes per second; 0 if background writes are not limited.
+    pub background_write_rate_limit: Gauge<usize>,
+    /// Effective number of entries applied to the tree per second since recovery was started or resumed
+    /// if recovery is throttled (see `max_entries_per_second` in the recovery config); 0 otherwise.
+    pub throttled_entries_per_second: Gauge<f64>,
+    /// Number of loaded chunks (or batches of chunk entries, if entries are streamed) waiting to be applied
+    /// to the tree.
+    pub loaded_entries_queue_depth: Gauge<usize>,
+    /// Total size of chunk entries loaded by chunk tasks, but not yet applied to the tree.
+    #[metrics(unit = Unit::Bytes)]
+    pub loaded_entries_bytes: Gauge<usize>,
+    /// Time a chunk task waits for a concurrency permit (i.e., for a Postgres connection slot) before loading
+    /// chunk entries. Since tasks for all chunks are created at once, waits up to the recovery duration
+    /// are expected for chunks recovered last; if waits are short while `tree_wait` is long, increasing
+    /// concurrency won't speed up recovery.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    pub permit_wait: Histogram<Duration>,
+    /// Time a chunk task waits for the tree applier to accept loaded entries. Healthy values are well below
+    /// `chunk_latency{stage="extend_tree"}`; values close to it mean that recovery is bottlenecked on the tree
+    /// rather than on Postgres.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    pub tree_wait: Histogram<Duration>,
+    /// Number of chunk tasks blocked waiting for the tree applier to accept loaded entries. Healthy values
+    /// are 0 or 1; values consistently close to `concurrency_limit` mean that recovery is bottlenecked
+    /// on the tree rather than on Postgres.
+    pub tree_wait_queue_depth: Gauge<usize>,
+    /// Number of entries sampled during the verification of the recovered tree against Postgres.
+    pub verified_entry_count: Gauge<usize>,
+    /// Number of sampled entries that differ between the recovered tree and Postgres.
+    pub mismatched_entry_count: Gauge<usize>,
+    /// Number of entries inserted into the tree by the last finished recovery since it was started
+    /// or resumed after a restart.
+    pub recovered_entry_count: Gauge<u64>,
+    /// Wall-clock duration of the last finished recovery since it was started or resumed after a restart.
+    #[metrics(unit = Unit::Seconds)]
+    pub duration: Gauge<Duration>,
+    /// Time elapsed since a chunk has last finished loading if recovery is considered stalled by the watchdog;
+    /// 0 otherwise.
+    #[metrics(unit = Unit::Seconds)]
+    pub stalled: Gauge<Duration>,
     /// Latency of a tree recovery stage (not related to the recovery of a particular chunk;
     /// those metrics are tracked in the `chunk_latency` histogram).
     #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
     pub latency: Family<RecoveryStage, Histogram<Duration>>,
+    /// Latency of a sub-stage of the [`RecoveryStage::Finalize`] stage.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    pub finalize_latency: Family<RecoveryFinalizeStage, Histogram<Duration>>,
     /// Latency of a chunk recovery stage.
     #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
     pub chunk_latency: Family<ChunkRecoveryStage, Histogram<Duration>>,
+    /// Number of entries inserted into the tree for a recovered chunk.
+    #[metrics(buckets = CHUNK_ENTRIES_BUCKETS)]
+    pub chunk_entries: Histogram<usize>,
+    /// Number of snapshot entries in the chunks remaining to be recovered according to the recovery plan.
+    pub planned_entries: Gauge<u64>,
+    /// Number of snapshot entries in a chunk remaining to be recovered according to the recovery plan.
+    #[metrics(buckets = CHUNK_ENTRIES_BUCKETS)]
+    pub planned_chunk_entries: Histogram<u64>,
+    /// Total duration of recovering a chunk, from starting to load its entries to extending the tree.
+    #[metrics(buckets = CHUNK_DURATION_BUCKETS, unit = Unit::Seconds)]
+    pub chunk_duration: Histogram<Duration>,
+    /// Per-chunk throughput (entries per second) of a chunk recovery stage. Only observed for stages
+    /// processing all chunk entries at once, i.e. loading non-streamed entries and extending the tree.
+    #[metrics(buckets = CHUNK_THROUGHPUT_BUCKETS)]
+    pub chunk_throughput: Family<ChunkRecoveryStage, Histogram<f64>>,
+}
+
+impl MetadataCalculatorRecoveryMetrics {
+    /// Observes the throughput of processing `entry_count` entries of a single chunk at the specified stage.
+    pub fn observe_chunk_throughput(
+        &self,
+        stage: ChunkRecoveryStage,
+        entry_count: usize,
+        latency: Duration,
+    ) {
+        let latency_secs = latency.as_secs_f64();
+        if entry_count > 0 && latency_secs > 0.0 {
+            self.chunk_throughput[&stage].observe(entry_count as f64 / latency_secs);
+        }
+    }
 }
 
 #[vise::register]
 pub(super) static RECOVERY_METRICS: vise::Global<MetadataCalculatorRecoveryMetrics> =
     vise::Global::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EncodeLabelValue, EncodeLabelSet)]
+#[metrics(label = "stage", rename_all = "snake_case")]
+pub(super) enum PruningStage {
+    Prune,
+    Compact,
+}
+
+/// Metrics for pruning old tree versions and compacting the tree RocksDB.
+#[derive(Debug, Metrics)]
+#[metrics(prefix = "server_metadata_calculator_pruning")]
+pub(super) struct MetadataCalculatorPruningMetrics {
+    /// Number of stale keys removed from the tree by pruning.
+    pub pruned_keys: Counter,
+    /// Number of bytes reclaimed by RocksDB compaction after pruning.
+    #[metrics(unit = Unit::Bytes)]
+    pub reclaimed_bytes: Counter,
+    /// Latency of a pruning stage.
+    #[metrics(buckets = Buckets::LATENCIES, unit = Unit::Seconds)]
+    pub latency: Family<PruningStage, Histogram<Duration>>,
+}
+
+#[vise::register]
+pub(super) static PRUNING_METRICS: vise::Global<MetadataCalculatorPruningMetrics> =
+    vise::Global::new();
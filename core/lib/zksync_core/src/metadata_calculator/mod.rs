@@ -1,10 +1,7 @@
 //! This module applies updates to the ZkSyncTree, calculates metadata for sealed blocks, and
 //! stores them in the DB.
 
-use std::{
-    future::{self, Future},
-    time::Duration,
-};
+use std::{path::PathBuf, time::Duration};
 
 use tokio::sync::watch;
 use zksync_config::configs::{
@@ -18,19 +15,43 @@ use zksync_object_store::{ObjectStore, ObjectStoreFactory};
 use zksync_types::{
     block::L1BatchHeader,
     commitment::{L1BatchCommitment, L1BatchMetadata},
-    H256,
+    L1BatchNumber, H256,
 };
 
-pub(crate) use self::helpers::{AsyncTreeReader, L1BatchWithLogs, MerkleTreeInfo};
 use self::{
-    helpers::{create_db, Delayer, GenericAsyncTree},
+    helpers::{create_db, Delayer, GenericAsyncTree, TreeDbParams},
     metrics::{TreeUpdateStage, METRICS},
-    updater::TreeUpdater,
+    recovery::{
+        check_tree_taint, finish_recovery_run, run_integrity_check, EnsureReadyContext,
+        RecoveryOverrides, RecoveryPools,
+    },
+    updater::{TreeReadiness, TreeUpdater},
 };
-use crate::gas_tracker::commit_gas_count_for_l1_batch;
+pub(crate) use self::{
+    helpers::{AsyncTreeReader, L1BatchWithLogs, MerkleTreeInfo},
+    reader::{SnapshotStateReader, TreeLookupError, TreeReaderHandle},
+};
+pub use self::{
+    helpers::{RecoveryFailure, TreeState},
+    pruning::{TreePruner, TreePruningStats},
+    recovery::{
+        inspect_tree_db, recover_tree, recovery_chunk_ranges, reset_tree_db, verify_proofs,
+        write_snapshot_file, ChunkDescriptor, ChunkFingerprint, ChunkTimingsSummary,
+        DiscrepancyKind, DiskSpaceEstimate, EntryDiscrepancy, FailedChunks,
+        HandleIntegrityCheckEvent, HandleRecoveryEvent, IncompleteChunk, IntegrityCheckPhase,
+        IntegrityCheckStats, LeafIndexStats, PlannedChunk, RecoveryControl, RecoveryControlHandle,
+        RecoveryError, RecoveryErrorKind, RecoveryFinalizeStage, RecoveryFingerprintLog,
+        RecoveryInspection, RecoveryOptions, RecoveryPlan, RecoveryReport, RecoveryStallReport,
+        RecoveryStats, RecoveryStatus, SlowestChunk, SnapshotFileHeader, SnapshotParameters,
+        StartupAction, StartupDecision, TreeDbInspection, TreeDbState,
+    },
+};
+use crate::{api_server::tree::TreeApiState, gas_tracker::commit_gas_count_for_l1_batch};
 
 mod helpers;
 mod metrics;
+mod pruning;
+mod reader;
 mod recovery;
 #[cfg(test)]
 pub(crate) mod tests;
@@ -70,6 +91,9 @@ pub struct MetadataCalculatorConfig<'a> {
     pub delay_interval: Duration,
     /// Maximum number of L1 batches to get from Postgres on a single update iteration.
     pub max_l1_batches_per_iter: usize,
+    /// If set, the tree is reported as not ready until it catches up to within this number of L1 batches
+    /// of the last sealed L1 batch in Postgres.
+    pub tree_ready_lag_batches: Option<u32>,
     /// Chunk size for multi-get operations. Can speed up loading data for the Merkle tree on some environments,
     /// but the effects vary wildly depending on the setup (e.g., the filesystem used).
     pub multi_get_chunk_size: usize,
@@ -78,11 +102,29 @@ pub struct MetadataCalculatorConfig<'a> {
     /// Capacity of RocksDB memtables. Can be set to a reasonably large value (order of 512 MiB)
     /// to mitigate write stalls.
     pub memtable_capacity: usize,
+    /// Limit on the total byte capacity of RocksDB memtables across all column families. If not set, the capacity
+    /// is only limited for each column family separately.
+    pub total_write_buffer_size: Option<usize>,
+    /// Maximum number of files kept open by RocksDB. If not set, the number is not limited.
+    pub max_open_files: Option<usize>,
     /// Timeout to wait for the Merkle tree database to run compaction on stalled writes.
     pub stalled_writes_timeout: Duration,
+    /// Configuration specific to the Merkle tree recovery.
+    pub recovery: MetadataCalculatorRecoveryConfig,
 }
 
 impl<'a> MetadataCalculatorConfig<'a> {
+    fn db_params(&self) -> TreeDbParams {
+        TreeDbParams {
+            block_cache_capacity: self.block_cache_capacity,
+            memtable_capacity: self.memtable_capacity,
+            total_write_buffer_size: self.total_write_buffer_size,
+            max_open_files: self.max_open_files,
+            stalled_writes_timeout: self.stalled_writes_timeout,
+            multi_get_chunk_size: self.multi_get_chunk_size,
+        }
+    }
+
     pub(crate) fn for_main_node(
         merkle_tree_config: &'a MerkleTreeConfig,
         operation_config: &'a OperationsManagerConfig,
@@ -93,10 +135,364 @@ impl<'a> MetadataCalculatorConfig<'a> {
             mode,
             delay_interval: operation_config.delay_interval(),
             max_l1_batches_per_iter: merkle_tree_config.max_l1_batches_per_iter,
+            tree_ready_lag_batches: merkle_tree_config.tree_ready_lag_batches,
             multi_get_chunk_size: merkle_tree_config.multi_get_chunk_size,
             block_cache_capacity: merkle_tree_config.block_cache_size(),
             memtable_capacity: merkle_tree_config.memtable_capacity(),
+            total_write_buffer_size: merkle_tree_config.total_write_buffer_size(),
+            max_open_files: merkle_tree_config.max_open_files,
             stalled_writes_timeout: merkle_tree_config.stalled_writes_timeout(),
+            recovery: MetadataCalculatorRecoveryConfig {
+                desired_chunk_size: merkle_tree_config.recovery.desired_chunk_size,
+                max_chunk_attempts: merkle_tree_config.recovery.max_chunk_attempts,
+                chunk_retry_initial_delay: merkle_tree_config.recovery.chunk_retry_initial_delay(),
+                chunk_retry_max_delay: merkle_tree_config.recovery.chunk_retry_max_delay(),
+                concurrency: merkle_tree_config.recovery.concurrency,
+                min_concurrency: merkle_tree_config.recovery.min_concurrency,
+                concurrency_override_path: merkle_tree_config
+                    .recovery
+                    .concurrency_override_path
+                    .as_ref()
+                    .map(PathBuf::from),
+                slow_chunk_threshold: merkle_tree_config.recovery.slow_chunk_threshold(),
+                sub_chunk_size: merkle_tree_config.recovery.sub_chunk_size,
+                streaming_batch_size: merkle_tree_config.recovery.streaming_batch_size,
+                hashing_threads: merkle_tree_config.recovery.hashing_threads,
+                loaded_entries_soft_cap: merkle_tree_config.recovery.loaded_entries_soft_cap(),
+                target_l1_batch: merkle_tree_config
+                    .recovery
+                    .target_l1_batch
+                    .map(L1BatchNumber),
+                prioritize_large_chunks: merkle_tree_config.recovery.prioritize_large_chunks,
+                force_replan: merkle_tree_config.recovery.force_replan,
+                allow_tree_reset_on_regenesis: merkle_tree_config
+                    .recovery
+                    .allow_tree_reset_on_regenesis,
+                allow_tree_reset: merkle_tree_config.recovery.allow_tree_reset,
+                estimated_bytes_per_entry: merkle_tree_config.recovery.estimated_bytes_per_entry,
+                disk_space_margin: merkle_tree_config.recovery.disk_space_margin(),
+                strict_disk_space_check: merkle_tree_config.recovery.strict_disk_space_check,
+                verification_samples_per_chunk: merkle_tree_config
+                    .recovery
+                    .verification_samples_per_chunk,
+                proof_verification_samples: merkle_tree_config.recovery.proof_verification_samples,
+                fingerprint_log: merkle_tree_config.recovery.fingerprint_log,
+                chunk_filter_batch_size: merkle_tree_config.recovery.chunk_filter_batch_size,
+                pruning_retained_versions: merkle_tree_config.recovery.pruning_retained_versions,
+                allow_lightweight_tree_upgrade: merkle_tree_config
+                    .recovery
+                    .allow_lightweight_tree_upgrade,
+                mismatch_diagnostic_keys_per_chunk: merkle_tree_config
+                    .recovery
+                    .mismatch_diagnostic_keys_per_chunk,
+                export_path: merkle_tree_config
+                    .recovery
+                    .export_path
+                    .as_ref()
+                    .map(PathBuf::from),
+                import_path: merkle_tree_config
+                    .recovery
+                    .import_path
+                    .as_ref()
+                    .map(PathBuf::from),
+                strict_import: merkle_tree_config.recovery.strict_import,
+                recovery_file: merkle_tree_config
+                    .recovery
+                    .recovery_file
+                    .as_ref()
+                    .map(PathBuf::from),
+                dry_run: merkle_tree_config.recovery.dry_run,
+                stop_after_dry_run: merkle_tree_config.recovery.stop_after_dry_run,
+                verify_integrity: merkle_tree_config.recovery.verify_integrity,
+                stop_after_recovery: merkle_tree_config.recovery.stop_after_recovery,
+                catch_up_before_ready: merkle_tree_config.recovery.catch_up_before_ready,
+                experimental_skip_root_hash_check: merkle_tree_config
+                    .recovery
+                    .experimental_skip_root_hash_check,
+                experimental_allow_tainted_tree: merkle_tree_config
+                    .recovery
+                    .experimental_allow_tainted_tree,
+                health_update_interval: merkle_tree_config.recovery.health_update_interval(),
+                stall_check_interval: merkle_tree_config.recovery.stall_check_interval(),
+                stall_threshold: merkle_tree_config.recovery.stall_threshold(),
+                connection_retry_timeout: merkle_tree_config.recovery.connection_retry_timeout(),
+                connection_acquire_timeout: merkle_tree_config
+                    .recovery
+                    .connection_acquire_timeout(),
+                use_copy: merkle_tree_config.recovery.use_copy,
+                snapshot_poll_interval: merkle_tree_config.recovery.snapshot_poll_interval(),
+                bulk_load_memtable_capacity: merkle_tree_config
+                    .recovery
+                    .bulk_load_memtable_capacity(),
+                bulk_load_max_write_buffers: merkle_tree_config
+                    .recovery
+                    .bulk_load_max_write_buffers,
+                bulk_load_block_cache_capacity: merkle_tree_config
+                    .recovery
+                    .bulk_load_block_cache_size(),
+                bulk_load_total_write_buffer_size: merkle_tree_config
+                    .recovery
+                    .bulk_load_total_write_buffer_size(),
+                bulk_load_max_open_files: merkle_tree_config.recovery.bulk_load_max_open_files,
+                relaxed_durability: merkle_tree_config.recovery.relaxed_durability,
+                check_chunk_ends: merkle_tree_config.recovery.check_chunk_ends,
+                repair_mismatched_chunks: merkle_tree_config.recovery.repair_mismatched_chunks,
+                flush_interval_chunks: merkle_tree_config.recovery.flush_interval_chunks,
+                flush_interval_bytes: merkle_tree_config.recovery.flush_interval_bytes(),
+                background_write_rate_limit: merkle_tree_config
+                    .recovery
+                    .background_write_rate_limit(),
+                background_write_rate_limit_override_path: merkle_tree_config
+                    .recovery
+                    .background_write_rate_limit_override_path
+                    .as_ref()
+                    .map(PathBuf::from),
+                max_entries_per_second: merkle_tree_config.recovery.max_entries_per_second,
+                max_recovery_duration: merkle_tree_config.recovery.max_recovery_duration(),
+                prefer_latest_snapshot: merkle_tree_config.recovery.prefer_latest_snapshot,
+                latest_snapshot_check_interval: merkle_tree_config
+                    .recovery
+                    .latest_snapshot_check_interval(),
+            },
+        }
+    }
+}
+
+/// Configuration of the Merkle tree recovery performed by [`MetadataCalculator`].
+#[derive(Debug, Clone)]
+pub struct MetadataCalculatorRecoveryConfig {
+    /// Approximate number of entries recovered in a single chunk. The value is persisted in the tree
+    /// when recovery starts; if the tree is restarted with another value, the persisted one is used.
+    pub desired_chunk_size: u64,
+    /// Maximum number of attempts to recover a single chunk. Only transient errors are retried.
+    pub max_chunk_attempts: usize,
+    /// Delay before the first retry of a failed chunk. The delay is doubled for each following retry.
+    pub chunk_retry_initial_delay: Duration,
+    /// Maximum delay between retries of a failed chunk.
+    pub chunk_retry_max_delay: Duration,
+    /// Maximum number of concurrently recovered chunks. Must not exceed the size of the connection pool
+    /// supplied to the calculator; if not set, the pool size is used.
+    pub concurrency: Option<usize>,
+    /// Minimum number of concurrently recovered chunks. If set, concurrency is adjusted between this value
+    /// and the maximum concurrency based on the latency of loading chunk entries.
+    pub min_concurrency: Option<usize>,
+    /// If set, the maximum number of concurrently recovered chunks is reloaded from this file on `SIGHUP`.
+    pub concurrency_override_path: Option<PathBuf>,
+    /// Average latency of loading chunk entries, above which adaptive concurrency is decreased.
+    pub slow_chunk_threshold: Duration,
+    /// If set, each chunk is applied to the tree in sub-chunks of this size, with progress within the chunk
+    /// persisted in the recovery journal.
+    pub sub_chunk_size: Option<usize>,
+    /// If set, entries of each chunk are streamed from Postgres in batches of this size instead of being loaded
+    /// at once. Takes precedence over `sub_chunk_size`.
+    pub streaming_batch_size: Option<usize>,
+    /// If set, tree nodes are hashed when extending the tree using a dedicated thread pool with the specified
+    /// number of threads. If not set, the global thread pool is used.
+    pub hashing_threads: Option<usize>,
+    /// If set, soft cap (in bytes) on the total size of chunk entries loaded but not yet applied to the tree.
+    /// Once the cap is exceeded, chunks don't start loading their entries until some of the loaded entries
+    /// are applied.
+    pub loaded_entries_soft_cap: Option<usize>,
+    /// L1 batch to recover the tree to. If not set, the tree is recovered to the snapshot the node was recovered from.
+    pub target_l1_batch: Option<L1BatchNumber>,
+    /// Whether to recover chunks with the largest estimated number of entries first.
+    pub prioritize_large_chunks: bool,
+    /// Whether to wipe the tree and restart recovery from scratch if the persisted recovery chunk plan differs
+    /// from the current one. If not set, such a mismatch results in an error.
+    pub force_replan: bool,
+    /// Whether to wipe the tree and restart recovery from scratch if Postgres genesis differs from the one
+    /// the recovery was started for (e.g., because Postgres was re-initialized). If not set, such a mismatch
+    /// results in an error.
+    pub allow_tree_reset_on_regenesis: bool,
+    /// Whether to remove the tree and initialize it from scratch if it cannot be used as is: it's being recovered
+    /// to an L1 batch different from the recovery target, or it was processed in the lightweight mode and cannot
+    /// be upgraded to the full mode. If not set, such a tree results in an error.
+    pub allow_tree_reset: bool,
+    /// Estimated number of bytes occupied by a single tree entry in RocksDB. Used to check that there is enough
+    /// disk space before starting recovery; if set to 0, the check is skipped.
+    pub estimated_bytes_per_entry: u64,
+    /// Disk space (in bytes) that should remain available after recovery according to the estimate.
+    pub disk_space_margin: usize,
+    /// Whether to refuse to start recovery if there isn't enough disk space for it. If not set, only a warning is logged.
+    pub strict_disk_space_check: bool,
+    /// If set, the recovered tree is verified by comparing the specified number of entries sampled from each chunk
+    /// with Postgres.
+    pub verification_samples_per_chunk: Option<usize>,
+    /// If set, Merkle proofs for the specified number of entries sampled from the recovered tree are verified
+    /// against the recovered root hash.
+    pub proof_verification_samples: Option<usize>,
+    /// Whether to record the tree root hash after each recovered chunk in an append-only log
+    /// (see [`RecoveryFingerprintLog`]).
+    pub fingerprint_log: bool,
+    /// Maximum number of chunks processed by a single Postgres query and a single tree lookup when filtering out
+    /// recovered chunks.
+    pub chunk_filter_batch_size: usize,
+    /// If set, tree versions older than the specified number of versions behind the latest one are pruned
+    /// and the tree RocksDB is compacted after recovery and on each start. See [`TreePruner`].
+    pub pruning_retained_versions: Option<u64>,
+    /// Whether to backfill L1 batches processed by the tree in the lightweight mode if the calculator runs
+    /// in the full mode. If not set, the tree continues in the full mode without the backfill.
+    pub allow_lightweight_tree_upgrade: bool,
+    /// If set, a root hash mismatch of the recovered tree is diagnosed, reporting diverging chunks together with
+    /// up to the specified number of example diverging keys per chunk.
+    pub mismatch_diagnostic_keys_per_chunk: Option<usize>,
+    /// If set, the recovered tree is exported to the specified directory as a RocksDB checkpoint together with
+    /// a JSON descriptor after recovery is finalized.
+    pub export_path: Option<PathBuf>,
+    /// If set, an empty tree is initialized by importing the tree exported to the specified directory instead of
+    /// recovering it from Postgres. If the imported tree fails verification, it is recovered from Postgres.
+    pub import_path: Option<PathBuf>,
+    /// Whether to return an error if importing the tree fails instead of recovering it from Postgres.
+    pub strict_import: bool,
+    /// If set, snapshot entries are loaded from the specified snapshot file instead of Postgres.
+    pub recovery_file: Option<PathBuf>,
+    /// Whether to verify the snapshot by recovering a temporary tree before recovering the production one.
+    pub dry_run: bool,
+    /// Whether to stop after the dry run instead of proceeding with recovery. Only used if `dry_run` is set.
+    pub stop_after_dry_run: bool,
+    /// Whether to verify the entire tree against the Postgres snapshot it was recovered from and stop instead
+    /// of normal operation. An interrupted check is resumed on the next start.
+    pub verify_integrity: bool,
+    /// Whether to stop once the tree is prepared for normal operation (e.g., recovered from a snapshot) and its
+    /// root hash is checked against the snapshot, instead of processing L1 batches.
+    pub stop_after_recovery: bool,
+    /// Whether to keep a recovered tree in the recovering health status (in the catch-up phase) until it catches up
+    /// with Postgres to within [`MetadataCalculatorConfig::tree_ready_lag_batches`] L1 batches (or fully,
+    /// if the lag isn't set), rather than reporting it as ready once recovery is finalized.
+    pub catch_up_before_ready: bool,
+    /// **UNSAFE; for debugging corrupted snapshots only.** Whether to log a root hash mismatch of the recovered tree
+    /// as an error instead of failing recovery. The recovered tree is marked as tainted in its manifest.
+    pub experimental_skip_root_hash_check: bool,
+    /// **UNSAFE; for debugging corrupted snapshots only.** Whether to use a tainted tree for normal operation,
+    /// including serving Merkle proofs. If not set, the calculator fails on a tainted tree.
+    pub experimental_allow_tainted_tree: bool,
+    /// Minimum interval between health updates on recovered chunks. The last recovered chunk is always reported.
+    pub health_update_interval: Duration,
+    /// Interval between checks of the recovery watchdog reporting recovery stalls. If set to 0, the watchdog
+    /// is disabled.
+    pub stall_check_interval: Duration,
+    /// Recovery is reported as stalled if no chunk has finished loading within this threshold.
+    pub stall_threshold: Duration,
+    /// Total duration of retrying to acquire a Postgres connection during recovery before recovery fails.
+    pub connection_retry_timeout: Duration,
+    /// Timeout for a single attempt to acquire a Postgres connection during recovery. Acquisition is retried
+    /// after a warning with pool stats if the timeout elapses.
+    pub connection_acquire_timeout: Duration,
+    /// Whether to load chunk entries from Postgres using binary `COPY`, falling back to a conventional query
+    /// if `COPY` fails.
+    pub use_copy: bool,
+    /// Interval between checks whether the snapshot is fully applied to Postgres.
+    pub snapshot_poll_interval: Duration,
+    /// If set, the tree RocksDB is tuned for bulk loading during recovery, with the specified byte capacity
+    /// of memtables for large column families and disabled auto-compaction. The database is compacted
+    /// and reopened with the normal options when recovery is finalized.
+    pub bulk_load_memtable_capacity: Option<usize>,
+    /// Maximum number of memtables for large column families during bulk loading.
+    pub bulk_load_max_write_buffers: usize,
+    /// Block cache capacity during bulk loading. If set and bulk loading is enabled, overrides
+    /// [`MetadataCalculatorConfig::block_cache_capacity`] until recovery is finalized.
+    pub bulk_load_block_cache_capacity: Option<usize>,
+    /// Limit on the total memtable capacity during bulk loading. If set and bulk loading is enabled, overrides
+    /// [`MetadataCalculatorConfig::total_write_buffer_size`] until recovery is finalized.
+    pub bulk_load_total_write_buffer_size: Option<usize>,
+    /// Maximum number of open files during bulk loading. If set and bulk loading is enabled, overrides
+    /// [`MetadataCalculatorConfig::max_open_files`] until recovery is finalized.
+    pub bulk_load_max_open_files: Option<usize>,
+    /// Whether to disable the write-ahead log of the tree RocksDB during recovery. Writes are flushed to disk
+    /// when recovery is finalized; if the node crashes before that, recovered chunks are checked pessimistically
+    /// on restart.
+    pub relaxed_durability: bool,
+    /// Whether to always check both the start and the end of recovered chunks when recovery is resumed.
+    /// Chunk ends are checked regardless of this option if tree writes may have been lost.
+    pub check_chunk_ends: bool,
+    /// Whether to repair recovered chunks with tree entries mismatching Postgres when recovery is resumed,
+    /// instead of failing recovery.
+    pub repair_mismatched_chunks: bool,
+    /// If set, memtables of the tree RocksDB are flushed every specified number of recovered chunks.
+    pub flush_interval_chunks: Option<usize>,
+    /// If set, memtables of the tree RocksDB are flushed every time approximately this number of bytes
+    /// of snapshot entries is applied to the tree.
+    pub flush_interval_bytes: Option<usize>,
+    /// Limit on the rate of background writes of the tree RocksDB (in bytes per second) during recovery.
+    pub background_write_rate_limit: Option<usize>,
+    /// If set, the background write rate limit is reloaded from this file (in megabytes per second) on `SIGHUP`.
+    pub background_write_rate_limit_override_path: Option<PathBuf>,
+    /// If set, limits the rate at which snapshot entries are loaded and applied to the tree during recovery.
+    pub max_entries_per_second: Option<usize>,
+    /// If set, recovery fails with [`RecoveryError::DeadlineExceeded`] if it doesn't complete within this duration
+    /// since it was started or resumed after a restart.
+    pub max_recovery_duration: Option<Duration>,
+    /// If set, the recovering tree is removed and recovery is restarted from a newer snapshot if one appears
+    /// in Postgres (see [`RecoveryError::SnapshotSuperseded`]).
+    pub prefer_latest_snapshot: bool,
+    /// Interval between checks for a newer snapshot during recovery if `prefer_latest_snapshot` is set.
+    pub latest_snapshot_check_interval: Duration,
+}
+
+impl Default for MetadataCalculatorRecoveryConfig {
+    fn default() -> Self {
+        Self {
+            desired_chunk_size: 200_000,
+            max_chunk_attempts: 5,
+            chunk_retry_initial_delay: Duration::from_millis(500),
+            chunk_retry_max_delay: Duration::from_secs(30),
+            concurrency: None,
+            min_concurrency: None,
+            concurrency_override_path: None,
+            slow_chunk_threshold: Duration::from_secs(10),
+            sub_chunk_size: None,
+            streaming_batch_size: None,
+            hashing_threads: None,
+            loaded_entries_soft_cap: None,
+            target_l1_batch: None,
+            prioritize_large_chunks: false,
+            force_replan: false,
+            allow_tree_reset_on_regenesis: false,
+            allow_tree_reset: false,
+            estimated_bytes_per_entry: 1_500,
+            disk_space_margin: 10 << 30, // 10 GiB
+            strict_disk_space_check: false,
+            verification_samples_per_chunk: None,
+            proof_verification_samples: None,
+            fingerprint_log: false,
+            chunk_filter_batch_size: 1_000,
+            pruning_retained_versions: None,
+            allow_lightweight_tree_upgrade: false,
+            mismatch_diagnostic_keys_per_chunk: None,
+            export_path: None,
+            import_path: None,
+            strict_import: false,
+            recovery_file: None,
+            dry_run: false,
+            stop_after_dry_run: false,
+            verify_integrity: false,
+            stop_after_recovery: false,
+            catch_up_before_ready: false,
+            experimental_skip_root_hash_check: false,
+            experimental_allow_tainted_tree: false,
+            health_update_interval: Duration::from_secs(1),
+            stall_check_interval: Duration::from_secs(60),
+            stall_threshold: Duration::from_secs(300),
+            connection_retry_timeout: Duration::from_secs(60),
+            connection_acquire_timeout: Duration::from_secs(30),
+            use_copy: false,
+            snapshot_poll_interval: Duration::from_secs(1),
+            bulk_load_memtable_capacity: None,
+            bulk_load_max_write_buffers: 6,
+            bulk_load_block_cache_capacity: None,
+            bulk_load_total_write_buffer_size: None,
+            bulk_load_max_open_files: None,
+            relaxed_durability: false,
+            check_chunk_ends: false,
+            repair_mismatched_chunks: false,
+            flush_interval_chunks: None,
+            flush_interval_bytes: None,
+            background_write_rate_limit: None,
+            background_write_rate_limit_override_path: None,
+            max_entries_per_second: None,
+            max_recovery_duration: None,
+            prefer_latest_snapshot: false,
+            latest_snapshot_check_interval: Duration::from_secs(60),
         }
     }
 }
@@ -104,11 +500,20 @@ impl<'a> MetadataCalculatorConfig<'a> {
 #[derive(Debug)]
 pub struct MetadataCalculator {
     tree: GenericAsyncTree,
-    tree_reader: watch::Sender<Option<AsyncTreeReader>>,
+    tree_reader: watch::Sender<Option<TreeReaderHandle>>,
+    recovery_status: watch::Sender<Option<RecoveryStatus>>,
+    recovery_control: RecoveryControlHandle,
+    recovery_listeners: Vec<Box<dyn HandleRecoveryEvent>>,
+    integrity_check_listeners: Vec<Box<dyn HandleIntegrityCheckEvent>>,
     object_store: Option<Box<dyn ObjectStore>>,
+    snapshot_object_store: Option<Box<dyn ObjectStore>>,
+    recovery_pool: Option<ConnectionPool>,
+    replica_pool: Option<ConnectionPool>,
     delayer: Delayer,
     health_updater: HealthUpdater,
     max_l1_batches_per_iter: usize,
+    tree_ready_lag_batches: Option<u32>,
+    recovery_config: MetadataCalculatorRecoveryConfig,
 }
 
 impl MetadataCalculator {
@@ -128,46 +533,112 @@ impl MetadataCalculator {
             MetadataCalculatorModeConfig::Lightweight => None,
         };
 
-        let db = create_db(
-            config.db_path.into(),
-            config.block_cache_capacity,
-            config.memtable_capacity,
-            config.stalled_writes_timeout,
-            config.multi_get_chunk_size,
-        )
-        .await;
+        let db = create_db(config.db_path.into(), config.db_params(), None).await;
         let tree = GenericAsyncTree::new(db, mode).await;
 
         let (_, health_updater) = ReactiveHealthCheck::new("tree");
         Self {
             tree,
             tree_reader: watch::channel(None).0,
+            recovery_status: watch::channel(None).0,
+            recovery_control: RecoveryControlHandle::default(),
+            recovery_listeners: Vec::new(),
+            integrity_check_listeners: Vec::new(),
             object_store,
+            snapshot_object_store: None,
+            recovery_pool: None,
+            replica_pool: None,
             delayer: Delayer::new(config.delay_interval),
             health_updater,
             max_l1_batches_per_iter: config.max_l1_batches_per_iter,
+            tree_ready_lag_batches: config.tree_ready_lag_batches,
+            recovery_config: config.recovery.clone(),
         }
     }
 
+    /// Sets the object store containing snapshot storage log chunks. If set, the tree is recovered from these chunks
+    /// instead of loading snapshot entries from Postgres.
+    #[must_use]
+    pub fn with_snapshot_object_store(mut self, object_store: Box<dyn ObjectStore>) -> Self {
+        self.snapshot_object_store = Some(object_store);
+        self
+    }
+
+    /// Sets a dedicated connection pool used to load snapshot chunks from Postgres during tree recovery.
+    /// The pool passed to [`Self::run()`] is still used for all other queries.
+    #[must_use]
+    pub fn with_recovery_pool(mut self, pool: ConnectionPool) -> Self {
+        self.recovery_pool = Some(pool);
+        self
+    }
+
+    /// Sets a connection pool for a Postgres read replica preferred for heavy snapshot queries during tree recovery.
+    /// Queries fall back to the primary if the replica lags behind the snapshot or fails.
+    #[must_use]
+    pub fn with_replica_pool(mut self, pool: ConnectionPool) -> Self {
+        self.replica_pool = Some(pool);
+        self
+    }
+
+    /// Registers a listener for Merkle tree recovery events (e.g., to report recovery progress to external systems).
+    /// See [`HandleRecoveryEvent`] docs for the guarantees provided to listeners.
+    pub fn register_recovery_listener(&mut self, listener: Box<dyn HandleRecoveryEvent>) {
+        self.recovery_listeners.push(listener);
+    }
+
+    /// Registers a listener for Merkle tree integrity check events. Events are only emitted if the integrity check
+    /// is enabled in the recovery config.
+    pub fn register_integrity_check_listener(
+        &mut self,
+        listener: Box<dyn HandleIntegrityCheckEvent>,
+    ) {
+        self.integrity_check_listeners.push(listener);
+    }
+
     /// Returns a health check for this calculator.
     pub fn tree_health_check(&self) -> ReactiveHealthCheck {
         self.health_updater.subscribe()
     }
 
-    /// Returns a reference to the tree reader.
-    pub(crate) fn tree_reader(&self) -> impl Future<Output = AsyncTreeReader> {
-        let mut receiver = self.tree_reader.subscribe();
-        async move {
-            loop {
-                if let Some(reader) = receiver.borrow().clone() {
-                    break reader;
-                }
-                if receiver.changed().await.is_err() {
-                    tracing::info!("Tree dropped without getting ready; not resolving tree reader");
-                    future::pending::<()>().await;
-                }
-            }
-        }
+    /// Returns the state for the Merkle tree API server. The state is available immediately, so that the server
+    /// can report recovery progress while the tree is being recovered.
+    pub(crate) fn tree_api_state(&self) -> TreeApiState {
+        TreeApiState::new(
+            self.tree_reader.subscribe(),
+            self.recovery_status.subscribe(),
+            self.recovery_control.clone(),
+        )
+    }
+
+    #[cfg(test)]
+    pub(crate) fn tree_reader(&self) -> watch::Receiver<Option<TreeReaderHandle>> {
+        self.tree_reader.subscribe()
+    }
+
+    /// Returns the state of the Merkle tree as of calculator initialization. The state may change once the calculator
+    /// is [run](Self::run()) (e.g., the tree is recovered from a snapshot).
+    pub fn tree_state(&self) -> TreeState {
+        self.tree.state()
+    }
+
+    /// Returns a receiver of the Merkle tree recovery progress. The progress is published once recovery from
+    /// a snapshot is started, updated on each recovered chunk, and reset to `None` once the recovered tree
+    /// is finalized. Components comparing the tree or Postgres with other data sources can use the snapshot
+    /// L1 batch from the progress to skip checks for earlier L1 batches, which are never processed by the tree.
+    pub fn recovery_status(&self) -> watch::Receiver<Option<RecoveryStatus>> {
+        self.recovery_status.subscribe()
+    }
+
+    /// Returns a handle to pause and resume Merkle tree recovery from a snapshot. The handle is available immediately;
+    /// it has no effect if the tree doesn't need recovery or once recovery is finished.
+    pub fn recovery_control(&self) -> RecoveryControlHandle {
+        self.recovery_control.clone()
+    }
+
+    /// Returns a pruner for the Merkle tree maintained by this calculator. The pruner is available immediately
+    /// and waits for the tree to be initialized (e.g., recovered) before pruning it.
+    pub fn tree_pruner(&self) -> TreePruner {
+        TreePruner::new(self.tree_reader.subscribe())
     }
 
     pub async fn run(
@@ -175,16 +646,96 @@ impl MetadataCalculator {
         pool: ConnectionPool,
         stop_receiver: watch::Receiver<bool>,
     ) -> anyhow::Result<()> {
-        let tree = self
-            .tree
-            .ensure_ready(&pool, &stop_receiver, &self.health_updater)
-            .await?;
-        let Some(tree) = tree else {
-            return Ok(()); // recovery was aborted because a stop signal was received
+        let recovery_pools = RecoveryPools {
+            recovery: self.recovery_pool.as_ref(),
+            replica: self.replica_pool.as_ref(),
         };
-        self.tree_reader.send_replace(Some(tree.reader()));
+        if !matches!(self.tree, GenericAsyncTree::Ready(_)) {
+            // Serve snapshot lookups from Postgres until the tree is ready.
+            let degraded_reader = SnapshotStateReader::new(pool.clone());
+            self.tree_reader
+                .send_replace(Some(TreeReaderHandle::Degraded(degraded_reader)));
+        }
+        let (mut overrides, overrides_reloader) =
+            RecoveryOverrides::from_config(&self.recovery_config);
+        overrides.control = Some(self.recovery_control.subscribe());
+        let ensure_ready = self.tree.ensure_ready(
+            &self.recovery_config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: recovery_pools,
+                snapshot_object_store: self.snapshot_object_store.as_deref(),
+                stop_receiver: &stop_receiver,
+                health_updater: &self.health_updater,
+                recovery_status: &self.recovery_status,
+                recovery_listeners: self.recovery_listeners,
+                overrides,
+            },
+        );
+        tokio::pin!(ensure_ready);
+        // Overrides are reloaded until the tree is ready; the reloader only returns on an error.
+        let result = tokio::select! {
+            result = &mut ensure_ready => result,
+            reload_result = overrides_reloader.run() => {
+                reload_result?;
+                ensure_ready.await
+            }
+        };
+        let (tree, report) = result.map_err(|err| {
+            // Do not leave the degraded reader published if the tree cannot be made ready; e.g., after a failed
+            // recovery, the snapshot in Postgres cannot be trusted.
+            self.tree_reader.send_replace(None);
+            err
+        })?;
+        tracing::info!("Finished preparing Merkle tree: {report:?}");
+        report.attach_to_health(&self.health_updater).await;
+        let Some(mut tree) = tree else {
+            return Ok(()); // recovery was stopped before completion
+        };
+        // Close connections in the dedicated recovery pools; they aren't used after recovery.
+        drop(self.recovery_pool);
+        drop(self.replica_pool);
+        self.recovery_status.send_replace(None);
+        if self.recovery_config.verify_integrity {
+            return run_integrity_check(
+                &mut tree,
+                &self.recovery_config,
+                &pool,
+                &stop_receiver,
+                &self.health_updater,
+                self.integrity_check_listeners,
+            )
+            .await;
+        }
+        if self.recovery_config.stop_after_recovery {
+            finish_recovery_run(
+                &tree,
+                &report,
+                &self.recovery_config,
+                &pool,
+                &self.health_updater,
+            )
+            .await?;
+            return Ok(());
+        }
+        // Must be checked before the tree reader is published, so that a tainted tree doesn't serve proofs.
+        let taint = check_tree_taint(&tree, &self.recovery_config, &self.health_updater)?;
+        self.tree_reader
+            .send_replace(Some(TreeReaderHandle::Ready(tree.reader())));
 
-        let updater = TreeUpdater::new(tree, self.max_l1_batches_per_iter, self.object_store);
+        let readiness = match report {
+            RecoveryReport::Recovered { .. } if self.recovery_config.catch_up_before_ready => {
+                TreeReadiness::after_recovery(self.tree_ready_lag_batches, report)
+            }
+            _ => TreeReadiness::new(self.tree_ready_lag_batches),
+        };
+        let readiness = readiness.with_taint(taint);
+        let updater = TreeUpdater::new(
+            tree,
+            self.max_l1_batches_per_iter,
+            readiness,
+            self.object_store,
+        );
         updater
             .loop_updating_tree(self.delayer, &pool, stop_receiver, self.health_updater)
             .await
@@ -0,0 +1,117 @@
+//! Pruning of old Merkle tree versions followed by the compaction of the tree RocksDB.
+//!
+//! After recovery, a tree contains a single version, but catching up with the chain head creates a version
+//! per L1 batch. Most nodes never query old versions, so they can be pruned to reclaim disk space.
+
+use anyhow::Context as _;
+use tokio::sync::watch;
+
+use super::{
+    helpers::AsyncTreeReader,
+    metrics::{PruningStage, PRUNING_METRICS},
+    reader::TreeReaderHandle,
+    recovery::wait_for_stop,
+};
+
+/// Statistics of pruning the Merkle tree returned by [`TreePruner::prune_and_compact()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TreePruningStats {
+    /// Number of stale keys removed from the tree.
+    pub pruned_key_count: usize,
+    /// Number of bytes reclaimed by RocksDB compaction.
+    pub reclaimed_bytes: u64,
+}
+
+/// Prunes old versions of the Merkle tree maintained by [`MetadataCalculator`](super::MetadataCalculator)
+/// and compacts the tree RocksDB. Can be used to periodically prune the tree once the node has caught up
+/// with the chain head; pruning can run concurrently with the calculator updating the tree.
+#[derive(Debug, Clone)]
+pub struct TreePruner {
+    tree_reader: watch::Receiver<Option<TreeReaderHandle>>,
+}
+
+impl TreePruner {
+    pub(super) fn new(tree_reader: watch::Receiver<Option<TreeReaderHandle>>) -> Self {
+        Self { tree_reader }
+    }
+
+    /// Waits until the tree is initialized, prunes tree versions older than `past_versions_to_keep`
+    /// behind the latest version and compacts the tree RocksDB if any stale keys were removed.
+    ///
+    /// Returns `Ok(None)` if a stop signal was received before pruning was completed. Pruning progress
+    /// is persisted, so interrupted pruning is resumed by the next call (including after a node restart).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the metadata calculator has stopped without initializing the tree.
+    pub async fn prune_and_compact(
+        &self,
+        past_versions_to_keep: u64,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<TreePruningStats>> {
+        let mut tree_reader = self.tree_reader.clone();
+        let tree_reader = tokio::select! {
+            reader = tree_reader.wait_for(|reader| reader.as_ref().and_then(TreeReaderHandle::ready).is_some()) => {
+                let reader = reader.context("metadata calculator stopped before initializing Merkle tree")?;
+                reader.as_ref().and_then(TreeReaderHandle::ready).unwrap().clone()
+                // ^ `unwrap()` is safe by construction
+            }
+            () = wait_for_stop(stop_receiver.clone()) => return Ok(None),
+        };
+        Ok(prune_and_compact(tree_reader, past_versions_to_keep, false, stop_receiver).await)
+    }
+}
+
+/// Prunes tree versions older than `past_versions_to_keep` behind the latest version persisted in RocksDB,
+/// and compacts the tree RocksDB if any stale keys were removed or `force_compaction` is set.
+/// Returns `None` if pruning was interrupted by a stop signal. Compaction cannot be interrupted.
+pub(super) async fn prune_and_compact(
+    tree_reader: AsyncTreeReader,
+    past_versions_to_keep: u64,
+    force_compaction: bool,
+    stop_receiver: &watch::Receiver<bool>,
+) -> Option<TreePruningStats> {
+    tracing::info!(
+        "Pruning Merkle tree versions older than {past_versions_to_keep} versions behind the latest one"
+    );
+    let latency = PRUNING_METRICS.latency[&PruningStage::Prune].start();
+    let outcome = tree_reader
+        .clone()
+        .prune(past_versions_to_keep, wait_for_stop(stop_receiver.clone()))
+        .await;
+    let latency = latency.observe();
+    PRUNING_METRICS
+        .pruned_keys
+        .inc_by(outcome.pruned_key_count as u64);
+
+    if outcome.is_aborted {
+        tracing::info!(
+            "Merkle tree pruning was interrupted after removing {} stale keys in {latency:?}; \
+             it will be resumed on the next run",
+            outcome.pruned_key_count
+        );
+        return None;
+    }
+    tracing::info!(
+        "Removed {} stale keys from Merkle tree in {latency:?}",
+        outcome.pruned_key_count
+    );
+
+    let mut stats = TreePruningStats {
+        pruned_key_count: outcome.pruned_key_count,
+        reclaimed_bytes: 0,
+    };
+    if stats.pruned_key_count > 0 || force_compaction {
+        let latency = PRUNING_METRICS.latency[&PruningStage::Compact].start();
+        stats.reclaimed_bytes = tree_reader.compact_db().await;
+        let latency = latency.observe();
+        PRUNING_METRICS
+            .reclaimed_bytes
+            .inc_by(stats.reclaimed_bytes);
+        tracing::info!(
+            "Compacted Merkle tree RocksDB in {latency:?}, reclaiming {}B",
+            stats.reclaimed_bytes
+        );
+    }
+    Some(stats)
+}
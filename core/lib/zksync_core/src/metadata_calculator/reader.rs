@@ -0,0 +1,134 @@
+//! Handle to the Merkle tree reader shared by [`MetadataCalculator`](super::MetadataCalculator) with the tree API
+//! server and the pruner.
+//!
+//! Recovering the tree from a snapshot can take hours, but Postgres contains the exact snapshot state all along.
+//! Thus, while the tree is not ready, the handle serves value and leaf index lookups (but not proofs) for
+//! the snapshot L1 batch directly from Postgres; such lookups are flagged as degraded. Once the tree is ready,
+//! the calculator replaces the handle with the real tree reader in a single watch channel update, so that readers
+//! observe either the degraded handle or the real tree, but never a mix of the two.
+
+use std::collections::HashMap;
+
+use anyhow::Context as _;
+use zksync_dal::ConnectionPool;
+use zksync_merkle_tree::{Key, NoVersionError, TreeEntry};
+use zksync_types::{L1BatchNumber, H256};
+
+use super::{helpers::AsyncTreeReader, recovery::hashed_key};
+
+/// Error looking up tree entries via [`TreeReaderHandle::entries()`].
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum TreeLookupError {
+    /// The requested tree version is missing.
+    #[error(transparent)]
+    NoVersion(#[from] NoVersionError),
+    /// The tree is not ready, and Postgres doesn't contain a fully applied snapshot to serve lookups from.
+    #[error("Merkle tree is not ready, and Postgres doesn't contain a fully applied snapshot")]
+    NoSnapshot,
+    /// The tree is not ready, and the requested L1 batch is not the snapshot one.
+    #[error(
+        "Only the snapshot L1 batch #{snapshot_l1_batch} can be queried while Merkle tree is recovering; \
+         requested L1 batch #{requested}"
+    )]
+    NotSnapshotL1Batch {
+        requested: L1BatchNumber,
+        snapshot_l1_batch: L1BatchNumber,
+    },
+    /// Internal error, e.g. a failed Postgres query.
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+/// Serves tree entry lookups for the snapshot L1 batch from Postgres. The applied snapshot is queried
+/// on each lookup, so that the reader stays correct if the snapshot is replaced during recovery.
+#[derive(Debug, Clone)]
+pub(crate) struct SnapshotStateReader {
+    pool: ConnectionPool,
+}
+
+impl SnapshotStateReader {
+    pub fn new(pool: ConnectionPool) -> Self {
+        Self { pool }
+    }
+
+    async fn entries(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        keys: &[Key],
+    ) -> Result<Vec<TreeEntry>, TreeLookupError> {
+        let mut storage = self.pool.access_storage_tagged("tree_api").await?;
+        let snapshot_recovery = storage
+            .snapshot_recovery_dal()
+            .get_applied_snapshot_status()
+            .await
+            .context("Failed getting snapshot recovery info")?;
+        let snapshot_recovery = snapshot_recovery.filter(|snapshot_recovery| {
+            snapshot_recovery
+                .last_finished_chunk_id
+                .map_or(false, |chunk_id| {
+                    chunk_id + 1 >= snapshot_recovery.total_chunk_count
+                })
+        });
+        let snapshot_recovery = snapshot_recovery.ok_or(TreeLookupError::NoSnapshot)?;
+        if snapshot_recovery.l1_batch_number != l1_batch_number {
+            return Err(TreeLookupError::NotSnapshotL1Batch {
+                requested: l1_batch_number,
+                snapshot_l1_batch: snapshot_recovery.l1_batch_number,
+            });
+        }
+
+        let hashed_keys: Vec<_> = keys.iter().map(hashed_key).collect();
+        let postgres_entries = storage
+            .storage_logs_dal()
+            .get_tree_entries_for_hashed_keys(snapshot_recovery.miniblock_number, &hashed_keys)
+            .await
+            .context("Failed getting snapshot entries from Postgres")?;
+        let postgres_entries: HashMap<_, _> = postgres_entries
+            .into_iter()
+            .map(|entry| (entry.key, entry))
+            .collect();
+
+        let entries = keys.iter().map(|key| match postgres_entries.get(key) {
+            Some(entry) => TreeEntry::new(entry.key, entry.leaf_index, entry.value),
+            None => TreeEntry::new(*key, 0, H256::zero()),
+        });
+        Ok(entries.collect())
+    }
+}
+
+/// Handle to the Merkle tree reader. See the [module docs](self) for details.
+#[derive(Debug, Clone)]
+pub(crate) enum TreeReaderHandle {
+    /// The tree is not ready yet; lookups are served from the snapshot in Postgres.
+    Degraded(SnapshotStateReader),
+    /// The tree is ready.
+    Ready(AsyncTreeReader),
+}
+
+impl TreeReaderHandle {
+    /// Checks whether lookups are served from Postgres rather than the tree.
+    pub fn is_degraded(&self) -> bool {
+        matches!(self, Self::Degraded(_))
+    }
+
+    /// Returns the reader of the real tree, or `None` if the tree is not ready.
+    pub fn ready(&self) -> Option<&AsyncTreeReader> {
+        match self {
+            Self::Ready(reader) => Some(reader),
+            Self::Degraded(_) => None,
+        }
+    }
+
+    /// Looks up entries with the specified keys at the specified tree version (= L1 batch number). The entries
+    /// are returned in the same order as requested; missing entries have zero value and leaf index.
+    pub async fn entries(
+        &self,
+        l1_batch_number: L1BatchNumber,
+        keys: Vec<Key>,
+    ) -> Result<Vec<TreeEntry>, TreeLookupError> {
+        match self {
+            Self::Degraded(reader) => reader.entries(l1_batch_number, &keys).await,
+            Self::Ready(reader) => Ok(reader.clone().entries(l1_batch_number, keys).await?),
+        }
+    }
+}
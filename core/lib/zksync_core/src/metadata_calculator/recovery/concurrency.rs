@@ -0,0 +1,391 @@
+//! Adaptive concurrency control for Merkle tree recovery.
+
+use std::{sync::Mutex, time::Duration};
+
+use anyhow::Context as _;
+use tokio::sync::{watch, Semaphore, SemaphorePermit};
+
+use crate::metadata_calculator::metrics::RECOVERY_METRICS;
+
+/// Limits on the number of concurrently recovered chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ConcurrencyLimits {
+    /// Minimum concurrency that adaptive control can back off to.
+    pub min: usize,
+    /// Maximum concurrency; recovery starts with this concurrency.
+    pub max: usize,
+    /// Average latency of loading chunk entries above which concurrency is decreased.
+    pub latency_threshold: Duration,
+    /// Upper bound on the maximum concurrency set at runtime (see [`AdaptiveConcurrency::set_max()`]).
+    pub ceiling: usize,
+}
+
+impl ConcurrencyLimits {
+    /// Creates limits with a fixed concurrency.
+    pub fn fixed(concurrency: usize) -> Self {
+        Self {
+            min: concurrency,
+            max: concurrency,
+            latency_threshold: Duration::MAX,
+            ceiling: concurrency,
+        }
+    }
+
+    /// Sets the upper bound on the maximum concurrency set at runtime.
+    pub fn with_ceiling(mut self, ceiling: usize) -> Self {
+        self.ceiling = ceiling;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct ConcurrencyState {
+    /// Current limits; can be changed at runtime.
+    limits: ConcurrencyLimits,
+    current: usize,
+    /// Number of permits that should be forgotten once they are released by the chunks holding them.
+    pending_decrease: usize,
+    /// Latencies observed since the last adjustment.
+    latencies: Vec<Duration>,
+}
+
+impl ConcurrencyState {
+    fn set_current(&mut self, semaphore: &Semaphore, new: usize) {
+        let prev = self.current;
+        if new < prev {
+            let decrease = prev - new;
+            let forgotten = semaphore.forget_permits(decrease);
+            self.pending_decrease += decrease - forgotten;
+        } else if new > prev {
+            let increase = new - prev;
+            // Permits held by chunks that are yet to be forgotten are reused first.
+            let reused = increase.min(self.pending_decrease);
+            self.pending_decrease -= reused;
+            semaphore.add_permits(increase - reused);
+        }
+        self.current = new;
+    }
+}
+
+/// AIMD-style (additive increase, multiplicative decrease) controller of the number of concurrently recovered chunks.
+///
+/// The controller observes latencies of loading chunk entries. Once the number of observations reaches
+/// the current concurrency (i.e., roughly after each "round" of concurrently processed chunks), the average latency
+/// is compared to the threshold. If it exceeds the threshold, concurrency is halved; otherwise, it is increased by 1.
+/// Concurrency always stays within the configured [`ConcurrencyLimits`]. The maximum concurrency can be changed
+/// at runtime (see [`Self::set_max()`]).
+#[derive(Debug)]
+pub(super) struct AdaptiveConcurrency {
+    /// Limits as configured when recovery was started.
+    configured_limits: ConcurrencyLimits,
+    semaphore: Semaphore,
+    state: Mutex<ConcurrencyState>,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(limits: ConcurrencyLimits) -> Self {
+        assert!(
+            limits.min > 0 && limits.min <= limits.max && limits.max <= limits.ceiling,
+            "{limits:?}"
+        );
+        RECOVERY_METRICS.concurrency_limit.set(limits.max);
+        Self {
+            configured_limits: limits,
+            semaphore: Semaphore::new(limits.max),
+            state: Mutex::new(ConcurrencyState {
+                limits,
+                current: limits.max,
+                pending_decrease: 0,
+                latencies: Vec::with_capacity(limits.max),
+            }),
+        }
+    }
+
+    /// Returns the current concurrency.
+    pub fn current(&self) -> usize {
+        self.state
+            .lock()
+            .expect("concurrency state is poisoned")
+            .current
+    }
+
+    pub async fn acquire(&self) -> anyhow::Result<ConcurrencyPermit<'_>> {
+        let wait_latency = RECOVERY_METRICS.permit_wait.start();
+        let permit = self
+            .semaphore
+            .acquire()
+            .await
+            .context("semaphore is never closed")?;
+        wait_latency.observe();
+        Ok(ConcurrencyPermit {
+            permit: Some(permit),
+            controller: self,
+        })
+    }
+
+    /// Observes latency of loading entries for a chunk, potentially adjusting concurrency.
+    pub fn observe_latency(&self, latency: Duration) {
+        let mut state = self.state.lock().expect("concurrency state is poisoned");
+        let limits = state.limits;
+        if limits.min == limits.max {
+            return; // concurrency is fixed
+        }
+
+        state.latencies.push(latency);
+        if state.latencies.len() < state.current {
+            return;
+        }
+        let observation_count = state.latencies.len() as u32;
+        let average_latency = state.latencies.drain(..).sum::<Duration>() / observation_count;
+
+        let prev = state.current;
+        if average_latency > limits.latency_threshold {
+            state.set_current(&self.semaphore, (prev / 2).max(limits.min));
+        } else if prev < limits.max {
+            state.set_current(&self.semaphore, prev + 1);
+        }
+
+        if state.current != prev {
+            tracing::info!(
+                "Adjusted recovery concurrency from {prev} to {} based on average latency \
+                 {average_latency:?} of loading chunk entries (threshold: {:?})",
+                state.current,
+                limits.latency_threshold
+            );
+            RECOVERY_METRICS.concurrency_limit.set(state.current);
+        }
+    }
+
+    /// Sets the maximum concurrency at runtime (e.g., to reduce load on Postgres without restarting recovery).
+    /// The value is capped by [`ConcurrencyLimits::ceiling`]. Fixed concurrency is set to the new value; adaptive
+    /// concurrency is clamped to it, lowering the minimum concurrency if necessary, and grows back gradually
+    /// if the value increases. If concurrency decreases, permits held by in-flight chunks are forgotten
+    /// once they are released.
+    pub fn set_max(&self, max: usize) {
+        let mut state = self.state.lock().expect("concurrency state is poisoned");
+        let max = max.min(state.limits.ceiling);
+        if max == 0 {
+            tracing::warn!("Ignoring request to set maximum recovery concurrency to 0");
+            return;
+        }
+        let configured_limits = self.configured_limits;
+        state.limits.max = max;
+        state.limits.min = if configured_limits.min == configured_limits.max {
+            max
+        } else {
+            configured_limits.min.min(max)
+        };
+        state.latencies.clear();
+
+        let prev = state.current;
+        let new = prev.clamp(state.limits.min, max);
+        state.set_current(&self.semaphore, new);
+        tracing::info!(
+            "Set maximum recovery concurrency to {max} (ceiling: {}); adjusted concurrency from {prev} to {new}",
+            state.limits.ceiling
+        );
+        RECOVERY_METRICS.concurrency_limit.set(new);
+    }
+
+    /// Applies maximum concurrency values received from `receiver` using [`Self::set_max()`], including the current
+    /// value if it has changed since the receiver was created. Returns once the sender is dropped.
+    pub async fn follow_max_overrides(&self, mut receiver: watch::Receiver<usize>) {
+        let mut has_changed = receiver.borrow().has_changed();
+        loop {
+            if has_changed {
+                let max = *receiver.borrow_and_update();
+                self.set_max(max);
+            }
+            if receiver.changed().await.is_err() {
+                return;
+            }
+            has_changed = true;
+        }
+    }
+}
+
+/// Permit to recover a chunk issued by [`AdaptiveConcurrency`].
+#[derive(Debug)]
+pub(super) struct ConcurrencyPermit<'a> {
+    permit: Option<SemaphorePermit<'a>>,
+    controller: &'a AdaptiveConcurrency,
+}
+
+impl Drop for ConcurrencyPermit<'_> {
+    fn drop(&mut self) {
+        let mut state = self
+            .controller
+            .state
+            .lock()
+            .expect("concurrency state is poisoned");
+        if state.pending_decrease > 0 {
+            state.pending_decrease -= 1;
+            if let Some(permit) = self.permit.take() {
+                permit.forget();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD: Duration = Duration::from_millis(100);
+
+    fn adaptive_limits(min: usize, max: usize) -> ConcurrencyLimits {
+        ConcurrencyLimits {
+            min,
+            max,
+            latency_threshold: THRESHOLD,
+            ceiling: max,
+        }
+    }
+
+    #[test]
+    fn fixed_concurrency_is_not_adjusted() {
+        let concurrency = AdaptiveConcurrency::new(ConcurrencyLimits::fixed(4));
+        for _ in 0..10 {
+            concurrency.observe_latency(Duration::from_secs(10));
+        }
+        assert_eq!(concurrency.current(), 4);
+        assert_eq!(concurrency.semaphore.available_permits(), 4);
+    }
+
+    #[test]
+    fn concurrency_converges_down_on_slow_chunks() {
+        let concurrency = AdaptiveConcurrency::new(adaptive_limits(2, 16));
+        let mut observed_concurrency = vec![];
+        for _ in 0..30 {
+            concurrency.observe_latency(THRESHOLD * 3);
+            observed_concurrency.push(concurrency.current());
+        }
+
+        assert!(
+            observed_concurrency.windows(2).all(|w| w[0] >= w[1]),
+            "{observed_concurrency:?}"
+        );
+        assert_eq!(concurrency.current(), 2);
+        assert_eq!(concurrency.semaphore.available_permits(), 2);
+    }
+
+    #[test]
+    fn concurrency_grows_back_on_fast_chunks() {
+        let concurrency = AdaptiveConcurrency::new(adaptive_limits(1, 4));
+        for _ in 0..(4 + 2 + 1) {
+            concurrency.observe_latency(THRESHOLD * 2);
+        }
+        assert_eq!(concurrency.current(), 1);
+
+        for _ in 0..20 {
+            concurrency.observe_latency(THRESHOLD / 2);
+        }
+        assert_eq!(concurrency.current(), 4);
+        assert_eq!(concurrency.semaphore.available_permits(), 4);
+    }
+
+    #[tokio::test]
+    async fn held_permits_are_forgotten_on_release_after_decrease() {
+        let concurrency = AdaptiveConcurrency::new(adaptive_limits(1, 4));
+        let permits = [
+            concurrency.acquire().await.unwrap(),
+            concurrency.acquire().await.unwrap(),
+            concurrency.acquire().await.unwrap(),
+        ];
+        for _ in 0..4 {
+            concurrency.observe_latency(THRESHOLD * 2);
+        }
+        assert_eq!(concurrency.current(), 2);
+        // The only available permit is forgotten immediately, and the remaining one once a permit is released.
+        assert_eq!(concurrency.semaphore.available_permits(), 0);
+
+        drop(permits);
+        assert_eq!(concurrency.semaphore.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn decreasing_max_concurrency_at_runtime() {
+        let concurrency = AdaptiveConcurrency::new(ConcurrencyLimits::fixed(4));
+        let permits = [
+            concurrency.acquire().await.unwrap(),
+            concurrency.acquire().await.unwrap(),
+            concurrency.acquire().await.unwrap(),
+        ];
+        concurrency.set_max(1);
+        assert_eq!(concurrency.current(), 1);
+        assert_eq!(concurrency.semaphore.available_permits(), 0);
+
+        drop(permits);
+        assert_eq!(concurrency.semaphore.available_permits(), 1);
+        // Concurrency must remain fixed.
+        concurrency.observe_latency(Duration::from_secs(10));
+        assert_eq!(concurrency.current(), 1);
+    }
+
+    #[tokio::test]
+    async fn increasing_max_concurrency_at_runtime() {
+        let concurrency = AdaptiveConcurrency::new(ConcurrencyLimits::fixed(4).with_ceiling(8));
+        let permits = [
+            concurrency.acquire().await.unwrap(),
+            concurrency.acquire().await.unwrap(),
+            concurrency.acquire().await.unwrap(),
+        ];
+        concurrency.set_max(1);
+        assert_eq!(concurrency.semaphore.available_permits(), 0);
+        // A held permit pending to be forgotten is reused.
+        concurrency.set_max(2);
+        assert_eq!(concurrency.current(), 2);
+        assert_eq!(concurrency.semaphore.available_permits(), 0);
+        drop(permits);
+        assert_eq!(concurrency.semaphore.available_permits(), 2);
+
+        concurrency.set_max(100);
+        assert_eq!(concurrency.current(), 8);
+        assert_eq!(concurrency.semaphore.available_permits(), 8);
+        concurrency.set_max(0);
+        assert_eq!(concurrency.current(), 8);
+    }
+
+    #[test]
+    fn adaptive_concurrency_is_clamped_at_runtime() {
+        let concurrency = AdaptiveConcurrency::new(adaptive_limits(4, 8));
+        concurrency.set_max(2);
+        assert_eq!(concurrency.current(), 2);
+        for _ in 0..20 {
+            concurrency.observe_latency(THRESHOLD / 2);
+        }
+        assert_eq!(concurrency.current(), 2);
+
+        concurrency.set_max(6);
+        // Adaptive concurrency grows back gradually starting from the configured minimum.
+        assert_eq!(concurrency.current(), 4);
+        for _ in 0..20 {
+            concurrency.observe_latency(THRESHOLD / 2);
+        }
+        assert_eq!(concurrency.current(), 6);
+        assert_eq!(concurrency.semaphore.available_permits(), 6);
+    }
+
+    #[tokio::test]
+    async fn following_max_concurrency_overrides() {
+        let concurrency = AdaptiveConcurrency::new(ConcurrencyLimits::fixed(4));
+        // The value sent before following overrides should be applied.
+        let (override_sender, override_receiver) = watch::channel(4);
+        override_sender.send_replace(3);
+        drop(override_sender);
+        concurrency.follow_max_overrides(override_receiver).await;
+        assert_eq!(concurrency.current(), 3);
+
+        let (override_sender, override_receiver) = watch::channel(3);
+        let follow_task = concurrency.follow_max_overrides(override_receiver);
+        let send_task = async {
+            tokio::task::yield_now().await;
+            override_sender.send_replace(2);
+            tokio::task::yield_now().await;
+            drop(override_sender);
+        };
+        tokio::join!(follow_task, send_task);
+        assert_eq!(concurrency.current(), 2);
+        assert_eq!(concurrency.semaphore.available_permits(), 2);
+    }
+}
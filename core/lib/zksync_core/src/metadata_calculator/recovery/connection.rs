@@ -0,0 +1,211 @@
+//! Retrying Postgres connection acquisition during Merkle tree recovery.
+
+use std::time::{Duration, Instant};
+
+use futures::Future;
+use tokio::sync::watch;
+use zksync_dal::{ConnectionPool, StorageProcessor};
+
+use super::run_until_stopped;
+use crate::metadata_calculator::metrics::RECOVERY_METRICS;
+
+/// Delay before the first retry of acquiring a connection.
+const INITIAL_DELAY: Duration = Duration::from_millis(100);
+/// Maximum delay between retries.
+const MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Acquires a connection from `pool`, retrying failures (e.g., if the pool is exhausted or Postgres is restarting)
+/// with capped exponential backoff. `target` describes what the connection is acquired for (e.g., a chunk
+/// key range); it's used in logs and errors. Gives up once retries would take longer than `retry_timeout`
+/// in total, returning the last error. Returns `None` if a stop signal is received.
+///
+/// Each acquisition attempt is bounded by `acquire_timeout`. If it elapses, the pool is considered starved;
+/// this is logged together with the pool stats, and the acquisition is retried.
+pub(super) async fn access_storage_with_retries<'a>(
+    pool: &'a ConnectionPool,
+    target: &str,
+    retry_timeout: Duration,
+    acquire_timeout: Duration,
+    stop_receiver: &watch::Receiver<bool>,
+) -> anyhow::Result<Option<StorageProcessor<'a>>> {
+    let latency = RECOVERY_METRICS.connection_wait.start();
+    let result = retry_with_backoff(target, retry_timeout, stop_receiver, || {
+        access_storage_watching_starvation(pool, target, acquire_timeout)
+    })
+    .await;
+    latency.observe();
+    result
+}
+
+async fn access_storage_watching_starvation<'a>(
+    pool: &'a ConnectionPool,
+    target: &str,
+    acquire_timeout: Duration,
+) -> anyhow::Result<StorageProcessor<'a>> {
+    loop {
+        match tokio::time::timeout(acquire_timeout, pool.access_storage()).await {
+            Ok(result) => return result,
+            Err(_) => {
+                let stats = pool.stats();
+                tracing::warn!(
+                    "Timed out acquiring Postgres connection for {target} after {acquire_timeout:?}; \
+                     pool size: {}/{}, idle: {}, waiters: {}. Retrying",
+                    stats.size,
+                    stats.max_size,
+                    stats.idle,
+                    stats.waiters
+                );
+                RECOVERY_METRICS.connection_starvations.inc();
+            }
+        }
+    }
+}
+
+async fn retry_with_backoff<T, Fut>(
+    target: &str,
+    retry_timeout: Duration,
+    stop_receiver: &watch::Receiver<bool>,
+    mut acquire: impl FnMut() -> Fut,
+) -> anyhow::Result<Option<T>>
+where
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let started_at = Instant::now();
+    let mut attempt = 1;
+    loop {
+        let Some(result) = run_until_stopped(acquire(), stop_receiver).await else {
+            return Ok(None);
+        };
+        let err = match result {
+            Ok(output) => return Ok(Some(output)),
+            Err(err) => err,
+        };
+
+        let delay = retry_delay(attempt);
+        let elapsed = started_at.elapsed();
+        if elapsed + delay > retry_timeout {
+            return Err(err.context(format!(
+                "failed acquiring Postgres connection for {target} in {attempt} attempt(s) over {elapsed:?}"
+            )));
+        }
+        tracing::warn!(
+            "Failed acquiring Postgres connection for {target} (attempt {attempt}); retrying in {delay:?}: {err:#}"
+        );
+        RECOVERY_METRICS.connection_retries.inc();
+        if run_until_stopped(tokio::time::sleep(delay), stop_receiver)
+            .await
+            .is_none()
+        {
+            return Ok(None);
+        }
+        attempt += 1;
+    }
+}
+
+/// Returns the delay before retrying after a failed `attempt` (1-based).
+fn retry_delay(attempt: u32) -> Duration {
+    let exponent = (attempt - 1).min(16);
+    INITIAL_DELAY.saturating_mul(1 << exponent).min(MAX_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use futures::future;
+
+    use super::*;
+
+    /// Returns a closure failing the first `failure_count` calls.
+    fn flaky_acquire(
+        failure_count: u32,
+        call_count: &AtomicU32,
+    ) -> impl FnMut() -> future::Ready<anyhow::Result<u32>> + '_ {
+        move || {
+            let call = call_count.fetch_add(1, Ordering::Relaxed);
+            future::ready(if call < failure_count {
+                Err(anyhow::anyhow!("pool timed out"))
+            } else {
+                Ok(call)
+            })
+        }
+    }
+
+    #[test]
+    fn retry_delays_are_capped() {
+        assert_eq!(retry_delay(1), INITIAL_DELAY);
+        assert_eq!(retry_delay(2), INITIAL_DELAY * 2);
+        assert_eq!(retry_delay(10), MAX_DELAY);
+        assert_eq!(retry_delay(u32::MAX), MAX_DELAY);
+    }
+
+    #[tokio::test]
+    async fn acquiring_connection_after_failures() {
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        let call_count = AtomicU32::new(0);
+        let acquire = flaky_acquire(3, &call_count);
+        let output = retry_with_backoff("test", Duration::from_secs(10), &stop_receiver, acquire)
+            .await
+            .unwrap();
+        assert_eq!(output, Some(3));
+        assert_eq!(call_count.into_inner(), 4);
+    }
+
+    #[tokio::test]
+    async fn giving_up_after_retry_timeout() {
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        let call_count = AtomicU32::new(0);
+        let acquire = flaky_acquire(u32::MAX, &call_count);
+        let err = retry_with_backoff("test", Duration::from_millis(500), &stop_receiver, acquire)
+            .await
+            .unwrap_err();
+        let err = format!("{err:#}");
+        assert!(err.contains("pool timed out"), "{err}");
+        // Delays are 100ms, 200ms, 400ms; the last one doesn't fit into the timeout.
+        assert_eq!(call_count.into_inner(), 3);
+    }
+
+    #[tokio::test]
+    async fn acquiring_connection_from_starved_pool() {
+        let pool = ConnectionPool::constrained_test_pool(1).await;
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        let starvations_before = RECOVERY_METRICS.connection_starvations.get();
+
+        let held_storage = pool.access_storage().await.unwrap();
+        let acquire_task = access_storage_with_retries(
+            &pool,
+            "test",
+            Duration::from_secs(60),
+            Duration::from_millis(50),
+            &stop_receiver,
+        );
+        let release_task = async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            drop(held_storage);
+        };
+        let (storage, ()) = tokio::join!(acquire_task, release_task);
+        assert!(storage.unwrap().is_some());
+
+        // Metrics are global and may be updated by other tests, so we only check that the counter has increased.
+        let starvations_after = RECOVERY_METRICS.connection_starvations.get();
+        assert!(
+            starvations_after > starvations_before,
+            "{starvations_after} <= {starvations_before}"
+        );
+    }
+
+    #[tokio::test]
+    async fn stop_signal_interrupts_retries() {
+        let (stop_sender, stop_receiver) = watch::channel(false);
+        let call_count = AtomicU32::new(0);
+        let acquire = flaky_acquire(u32::MAX, &call_count);
+        let retry_task =
+            retry_with_backoff("test", Duration::from_secs(60), &stop_receiver, acquire);
+        let stop_task = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            stop_sender.send_replace(true);
+        };
+        let (output, ()) = tokio::join!(retry_task, stop_task);
+        assert_eq!(output.unwrap(), None);
+    }
+}
@@ -0,0 +1,194 @@
+//! Pausing and resuming Merkle tree recovery at runtime.
+
+use std::{future, sync::Arc};
+
+use serde::Serialize;
+use tokio::sync::watch;
+
+use super::{wait_for_stop, HandleRecoveryEvent, RecoveryWatchdog};
+
+/// Command controlling Merkle tree recovery sent via [`RecoveryControlHandle`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryControl {
+    /// No new chunks are started; chunks in flight are still recovered.
+    Pause,
+    /// Chunks are recovered as usual.
+    #[default]
+    Resume,
+}
+
+/// Handle allowing to pause and resume Merkle tree recovery. Pausing doesn't interrupt chunks in flight; it only
+/// prevents chunk tasks from starting new chunks until recovery is resumed. A stop signal takes precedence
+/// over pausing, i.e., paused recovery can be stopped as usual.
+///
+/// Clones of the handle control the same recovery. Commands sent before recovery is started are applied once
+/// it starts; commands sent after recovery has finished have no effect.
+#[derive(Debug, Clone, Default)]
+pub struct RecoveryControlHandle {
+    sender: Arc<watch::Sender<RecoveryControl>>,
+}
+
+impl RecoveryControlHandle {
+    /// Pauses recovery.
+    pub fn pause(&self) {
+        self.sender.send_replace(RecoveryControl::Pause);
+    }
+
+    /// Resumes recovery if it was paused.
+    pub fn resume(&self) {
+        self.sender.send_replace(RecoveryControl::Resume);
+    }
+
+    /// Checks whether recovery is paused.
+    pub fn is_paused(&self) -> bool {
+        *self.sender.borrow() == RecoveryControl::Pause
+    }
+
+    /// Subscribes to commands sent via this handle. The receiver can be passed to
+    /// [`RecoveryOptions::control()`](super::RecoveryOptions::control()).
+    pub fn subscribe(&self) -> watch::Receiver<RecoveryControl> {
+        self.sender.subscribe()
+    }
+}
+
+/// Gate blocking chunk tasks while recovery is paused. If the control channel is not supplied or its sender
+/// is dropped, recovery is never paused.
+#[derive(Debug)]
+pub(super) struct PauseGate {
+    control: Option<watch::Receiver<RecoveryControl>>,
+}
+
+impl PauseGate {
+    pub fn new(control: Option<watch::Receiver<RecoveryControl>>) -> Self {
+        Self { control }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.control.as_ref().is_some_and(|control| {
+            // `has_changed()` only errors if the sender is dropped.
+            *control.borrow() == RecoveryControl::Pause && control.has_changed().is_ok()
+        })
+    }
+
+    /// Waits until recovery is not paused. Returns `false` if a stop signal is received before or during the wait.
+    pub async fn wait_until_resumed(&self, stop_receiver: &watch::Receiver<bool>) -> bool {
+        let Some(control) = &self.control else {
+            return !*stop_receiver.borrow();
+        };
+        let mut control = control.clone();
+        tokio::select! {
+            biased;
+
+            () = wait_for_stop(stop_receiver.clone()) => false,
+            // If the sender is dropped, recovery cannot be resumed, so it's treated as not paused.
+            _ = control.wait_for(|&command| command == RecoveryControl::Resume) => true,
+        }
+    }
+
+    /// Reports transitions between paused and resumed recovery to `events` and `watchdog` until the sender
+    /// of the control channel is dropped. Recovery is assumed to be resumed initially.
+    pub async fn report_transitions(
+        &self,
+        events: &dyn HandleRecoveryEvent,
+        watchdog: &RecoveryWatchdog,
+    ) {
+        let Some(control) = &self.control else {
+            future::pending::<()>().await;
+            return;
+        };
+        let mut control = control.clone();
+        let mut is_paused = false;
+        loop {
+            let should_pause = *control.borrow_and_update() == RecoveryControl::Pause;
+            if should_pause != is_paused {
+                is_paused = should_pause;
+                Self::report_transition(is_paused, events, watchdog);
+            }
+            if control.changed().await.is_err() {
+                if is_paused {
+                    Self::report_transition(false, events, watchdog);
+                }
+                return;
+            }
+        }
+    }
+
+    fn report_transition(
+        is_paused: bool,
+        events: &dyn HandleRecoveryEvent,
+        watchdog: &RecoveryWatchdog,
+    ) {
+        watchdog.set_paused(is_paused);
+        if is_paused {
+            tracing::info!(
+                "Tree recovery is paused; chunks in flight will be recovered, but no new chunks will be started"
+            );
+            events.recovery_paused();
+        } else {
+            tracing::info!("Tree recovery is resumed");
+            events.recovery_resumed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn pause_gate_waits_for_resume() {
+        let handle = RecoveryControlHandle::default();
+        let gate = PauseGate::new(Some(handle.subscribe()));
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        assert!(!gate.is_paused());
+        assert!(gate.wait_until_resumed(&stop_receiver).await);
+
+        handle.pause();
+        assert!(gate.is_paused());
+        let wait = tokio::time::timeout(
+            Duration::from_millis(50),
+            gate.wait_until_resumed(&stop_receiver),
+        );
+        wait.await.unwrap_err();
+
+        let resume = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            handle.resume();
+        };
+        let (resumed, ()) = tokio::join!(gate.wait_until_resumed(&stop_receiver), resume);
+        assert!(resumed);
+        assert!(!gate.is_paused());
+    }
+
+    #[tokio::test]
+    async fn stop_signal_takes_precedence_over_pause() {
+        let handle = RecoveryControlHandle::default();
+        let gate = PauseGate::new(Some(handle.subscribe()));
+        let (stop_sender, stop_receiver) = watch::channel(false);
+        handle.pause();
+
+        let stop = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            stop_sender.send_replace(true);
+        };
+        let (resumed, ()) = tokio::join!(gate.wait_until_resumed(&stop_receiver), stop);
+        assert!(!resumed);
+
+        handle.resume();
+        assert!(!gate.wait_until_resumed(&stop_receiver).await);
+    }
+
+    #[tokio::test]
+    async fn dropped_handle_resumes_recovery() {
+        let handle = RecoveryControlHandle::default();
+        let gate = PauseGate::new(Some(handle.subscribe()));
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        handle.pause();
+        drop(handle);
+        assert!(!gate.is_paused());
+        assert!(gate.wait_until_resumed(&stop_receiver).await);
+    }
+}
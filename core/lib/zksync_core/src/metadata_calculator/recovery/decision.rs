@@ -0,0 +1,229 @@
+//! Decision made by `GenericAsyncTree::ensure_ready()` on startup.
+
+use std::{fmt, path::PathBuf};
+
+use serde::Serialize;
+use zksync_types::L1BatchNumber;
+
+use super::super::{helpers::TreeState, MetadataCalculatorRecoveryConfig};
+
+/// Action taken by `GenericAsyncTree::ensure_ready()` on startup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StartupAction {
+    /// Tree is ready; normal operation is resumed.
+    ResumeOperation,
+    /// Recovery of the tree is resumed.
+    ResumeRecovery,
+    /// Tree is imported from the export at the specified path. If the import fails, the tree may be recovered
+    /// from Postgres instead, depending on the config.
+    ImportTree { path: PathBuf },
+    /// Recovery of the tree from the Postgres snapshot is started.
+    StartRecovery,
+    /// Tree is built from the genesis L1 batch.
+    BuildFromGenesis,
+    /// Startup fails because Merkle tree and Postgres states are inconsistent.
+    Fail { reason: String },
+}
+
+impl fmt::Display for StartupAction {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ResumeOperation => formatter.write_str("resume normal operation"),
+            Self::ResumeRecovery => formatter.write_str("resume recovery"),
+            Self::ImportTree { path } => {
+                write!(formatter, "import tree exported to `{}`", path.display())
+            }
+            Self::StartRecovery => formatter.write_str("start recovery"),
+            Self::BuildFromGenesis => formatter.write_str("build tree from genesis"),
+            Self::Fail { reason } => write!(formatter, "fail ({reason})"),
+        }
+    }
+}
+
+/// Decision made by `GenericAsyncTree::ensure_ready()` on startup together with all its inputs. The decision
+/// is logged and published in the tree health details, so that it's possible to find out why the tree
+/// is recovered or built from scratch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StartupDecision {
+    /// Detected tree state.
+    pub tree_state: TreeState,
+    /// L1 batch of the snapshot in Postgres (or of the configured recovery target), if any.
+    pub snapshot_l1_batch: Option<L1BatchNumber>,
+    /// Recovered tree version if the tree is recovering.
+    pub recovered_version: Option<u64>,
+    /// Chosen action.
+    pub action: StartupAction,
+}
+
+impl fmt::Display for StartupDecision {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "Merkle tree is {}", self.tree_state)?;
+        match self.snapshot_l1_batch {
+            Some(l1_batch) => write!(formatter, ", snapshot L1 batch: #{l1_batch}")?,
+            None => formatter.write_str(", no snapshot in Postgres")?,
+        }
+        write!(formatter, "; action: {}", self.action)
+    }
+}
+
+impl StartupDecision {
+    /// Makes a decision based on the tree state and the snapshot L1 batch. The decision must be consistent
+    /// with branching in `GenericAsyncTree::ensure_ready()`.
+    pub(super) fn new(
+        tree_state: TreeState,
+        snapshot_l1_batch: Option<L1BatchNumber>,
+        config: &MetadataCalculatorRecoveryConfig,
+    ) -> Self {
+        let recovered_version = match tree_state {
+            TreeState::Recovering {
+                recovered_version, ..
+            } => Some(recovered_version),
+            TreeState::Empty { .. } | TreeState::Ready { .. } => None,
+        };
+        let action = match (tree_state, snapshot_l1_batch) {
+            (TreeState::Ready { .. }, _) => StartupAction::ResumeOperation,
+            (TreeState::Recovering { .. }, None) => StartupAction::Fail {
+                reason: "tree is recovering, but Postgres doesn't contain snapshot recovery information"
+                    .to_owned(),
+            },
+            (
+                TreeState::Recovering {
+                    recovered_version, ..
+                },
+                Some(l1_batch),
+            ) if u64::from(l1_batch.0) != recovered_version => StartupAction::Fail {
+                reason: format!(
+                    "snapshot L1 batch #{l1_batch} differs from the recovered tree version {recovered_version}"
+                ),
+            },
+            (TreeState::Recovering { .. }, Some(_)) => StartupAction::ResumeRecovery,
+            (TreeState::Empty { .. }, Some(_)) => match &config.import_path {
+                Some(path) => StartupAction::ImportTree { path: path.clone() },
+                None => StartupAction::StartRecovery,
+            },
+            (TreeState::Empty { .. }, None) => StartupAction::BuildFromGenesis,
+        };
+        Self {
+            tree_state,
+            snapshot_l1_batch,
+            recovered_version,
+            action,
+        }
+    }
+
+    /// Logs this decision as a single structured log line.
+    pub(super) fn log(&self) {
+        if matches!(self.action, StartupAction::Fail { .. }) {
+            tracing::warn!(
+                tree_state = %self.tree_state,
+                snapshot_l1_batch = self.snapshot_l1_batch.map(|l1_batch| l1_batch.0),
+                recovered_version = self.recovered_version,
+                action = %self.action,
+                "Merkle tree startup decision: {self}"
+            );
+        } else {
+            tracing::info!(
+                tree_state = %self.tree_state,
+                snapshot_l1_batch = self.snapshot_l1_batch.map(|l1_batch| l1_batch.0),
+                recovered_version = self.recovered_version,
+                action = %self.action,
+                "Merkle tree startup decision: {self}"
+            );
+        }
+    }
+}
+
+/// Health details published by `GenericAsyncTree::ensure_ready()` on startup. The tree state is flattened
+/// for backward compatibility.
+#[derive(Debug, Serialize)]
+pub(super) struct StartupHealthDetails<'a> {
+    #[serde(flatten)]
+    pub state: TreeState,
+    pub decision: &'a StartupDecision,
+}
+
+#[cfg(test)]
+mod tests {
+    use zksync_config::configs::database::MerkleTreeMode;
+
+    use super::*;
+
+    const MODE: MerkleTreeMode = MerkleTreeMode::Full;
+
+    fn decide(
+        tree_state: TreeState,
+        snapshot_l1_batch: Option<u32>,
+        config: &MetadataCalculatorRecoveryConfig,
+    ) -> String {
+        StartupDecision::new(tree_state, snapshot_l1_batch.map(L1BatchNumber), config).to_string()
+    }
+
+    #[test]
+    fn startup_decision_for_recovering_tree() {
+        let config = MetadataCalculatorRecoveryConfig::default();
+        let state = TreeState::Recovering {
+            recovered_version: 5,
+            mode: MODE,
+        };
+        assert_eq!(
+            decide(state, Some(5), &config),
+            "Merkle tree is recovering to L1 batch #5 (Full mode), snapshot L1 batch: #5; action: resume recovery"
+        );
+        assert_eq!(
+            decide(state, Some(6), &config),
+            "Merkle tree is recovering to L1 batch #5 (Full mode), snapshot L1 batch: #6; action: fail \
+             (snapshot L1 batch #6 differs from the recovered tree version 5)"
+        );
+        assert_eq!(
+            decide(state, None, &config),
+            "Merkle tree is recovering to L1 batch #5 (Full mode), no snapshot in Postgres; action: fail \
+             (tree is recovering, but Postgres doesn't contain snapshot recovery information)"
+        );
+    }
+
+    #[test]
+    fn startup_decision_for_empty_tree_with_snapshot() {
+        let mut config = MetadataCalculatorRecoveryConfig::default();
+        let state = TreeState::Empty { mode: MODE };
+        assert_eq!(
+            decide(state, Some(5), &config),
+            "Merkle tree is empty (Full mode), snapshot L1 batch: #5; action: start recovery"
+        );
+
+        config.import_path = Some("/exports/tree".into());
+        assert_eq!(
+            decide(state, Some(5), &config),
+            "Merkle tree is empty (Full mode), snapshot L1 batch: #5; action: import tree exported to `/exports/tree`"
+        );
+    }
+
+    #[test]
+    fn startup_decision_for_empty_tree_without_snapshot() {
+        let config = MetadataCalculatorRecoveryConfig::default();
+        let state = TreeState::Empty { mode: MODE };
+        assert_eq!(
+            decide(state, None, &config),
+            "Merkle tree is empty (Full mode), no snapshot in Postgres; action: build tree from genesis"
+        );
+    }
+
+    #[test]
+    fn startup_decision_for_ready_tree() {
+        let config = MetadataCalculatorRecoveryConfig::default();
+        let state = TreeState::Ready {
+            next_l1_batch_number: L1BatchNumber(10),
+            mode: MODE,
+        };
+        assert_eq!(
+            decide(state, None, &config),
+            "Merkle tree is ready with next L1 batch #10 (Full mode), no snapshot in Postgres; \
+             action: resume normal operation"
+        );
+        assert_eq!(
+            decide(state, Some(5), &config),
+            "Merkle tree is ready with next L1 batch #10 (Full mode), snapshot L1 batch: #5; \
+             action: resume normal operation"
+        );
+    }
+}
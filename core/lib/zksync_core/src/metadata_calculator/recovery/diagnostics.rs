@@ -0,0 +1,405 @@
+//! Diagnostics for a root hash mismatch of the recovered Merkle tree.
+//!
+//! Tree keys are little-endian representations of hashed keys, so a chunk (i.e., a hashed key range)
+//! doesn't correspond to a contiguous key range in the tree. Hence, the tree side of a chunk is obtained
+//! by reading the entire tree in equal-width key ranges and distributing the read entries among chunks.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, ops,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use zksync_dal::StorageProcessor;
+use zksync_merkle_tree::TreeEntry;
+use zksync_types::{web3::signing::keccak256, MiniblockNumber, H256};
+use zksync_utils::h256_to_u256;
+
+use super::hashed_key;
+use crate::metadata_calculator::{
+    helpers::AsyncTreeRecovery,
+    metrics::{RecoveryStage, RECOVERY_METRICS},
+};
+
+/// Order-independent fingerprint of a set of tree entries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct EntriesFingerprint {
+    pub entry_count: u64,
+    /// XOR of hashes of all entries.
+    pub hash: H256,
+}
+
+impl EntriesFingerprint {
+    fn push(&mut self, entry: &TreeEntry) {
+        let mut buffer = [0_u8; 72];
+        entry.key.to_big_endian(&mut buffer[..32]);
+        buffer[32..64].copy_from_slice(entry.value.as_bytes());
+        buffer[64..].copy_from_slice(&entry.leaf_index.to_be_bytes());
+        let entry_hash = keccak256(&buffer);
+        for (byte, entry_byte) in self.hash.0.iter_mut().zip(entry_hash) {
+            *byte ^= entry_byte;
+        }
+        self.entry_count += 1;
+    }
+}
+
+/// Value of a tree entry together with its leaf index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct EntryValue {
+    pub value: H256,
+    pub leaf_index: u64,
+}
+
+impl From<&TreeEntry> for EntryValue {
+    fn from(entry: &TreeEntry) -> Self {
+        Self {
+            value: entry.value,
+            leaf_index: entry.leaf_index,
+        }
+    }
+}
+
+/// Key diverging between Postgres and the recovered tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct DivergentKey {
+    pub hashed_key: H256,
+    /// Entry in Postgres, or `None` if the key is missing from the snapshot.
+    pub postgres: Option<EntryValue>,
+    /// Entry in the tree, or `None` if the key is missing from the tree.
+    pub tree: Option<EntryValue>,
+}
+
+/// Chunk with entries diverging between Postgres and the recovered tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct DivergentChunk {
+    pub chunk_id: usize,
+    pub key_range: ops::RangeInclusive<H256>,
+    pub postgres: EntriesFingerprint,
+    pub tree: EntriesFingerprint,
+    /// Example diverging keys in the ascending order.
+    pub divergent_keys: Vec<DivergentKey>,
+}
+
+/// Report produced by [`diagnose_root_hash_mismatch()`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct RootHashMismatchReport {
+    pub snapshot_miniblock: MiniblockNumber,
+    pub expected_root_hash: H256,
+    pub actual_root_hash: H256,
+    pub chunk_count: usize,
+    /// Number of tree entries not belonging to any of the chunks.
+    pub unassigned_tree_entry_count: u64,
+    pub divergent_chunks: Vec<DivergentChunk>,
+}
+
+impl fmt::Display for RootHashMismatchReport {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{} of {} chunks diverge",
+            self.divergent_chunks.len(),
+            self.chunk_count
+        )?;
+        for chunk in &self.divergent_chunks {
+            write!(
+                formatter,
+                "; chunk {} ({:?}): {} entries in Postgres, {} entries in tree, {} example diverging keys",
+                chunk.chunk_id,
+                chunk.key_range,
+                chunk.postgres.entry_count,
+                chunk.tree.entry_count,
+                chunk.divergent_keys.len()
+            )?;
+        }
+        if self.unassigned_tree_entry_count > 0 {
+            write!(
+                formatter,
+                "; {} tree entries don't belong to any chunk",
+                self.unassigned_tree_entry_count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl RootHashMismatchReport {
+    /// Returns the path to the report file for the tree located at `db_path`.
+    pub fn path(db_path: &Path) -> PathBuf {
+        db_path.with_extension("root_hash_mismatch.json")
+    }
+
+    async fn write(&self, path: PathBuf) -> anyhow::Result<()> {
+        let contents = serde_json::to_vec_pretty(self).context("failed serializing report")?;
+        tokio::task::spawn_blocking(move || {
+            std::fs::write(&path, contents)
+                .with_context(|| format!("failed writing report to `{}`", path.display()))
+        })
+        .await
+        .unwrap()
+    }
+}
+
+fn find_chunk(key_chunks: &[ops::RangeInclusive<H256>], hashed_key: &H256) -> Option<usize> {
+    let chunk_id = key_chunks.partition_point(|chunk| chunk.end() < hashed_key);
+    let chunk = key_chunks.get(chunk_id)?;
+    chunk.contains(hashed_key).then_some(chunk_id)
+}
+
+/// Reads all entries from the `tree` in `read_count` equal-width key ranges and passes them to `handle_entry`.
+async fn for_each_tree_entry(
+    tree: &mut AsyncTreeRecovery,
+    read_count: usize,
+    mut handle_entry: impl FnMut(&TreeEntry),
+) {
+    for key_range in AsyncTreeRecovery::hashed_key_ranges(read_count) {
+        let key_range = h256_to_u256(*key_range.start())..=h256_to_u256(*key_range.end());
+        for entry in tree.entries_in_range(key_range).await {
+            handle_entry(&entry);
+        }
+    }
+}
+
+async fn load_postgres_entries(
+    storage: &mut StorageProcessor<'_>,
+    snapshot_miniblock: MiniblockNumber,
+    key_chunk: &ops::RangeInclusive<H256>,
+) -> anyhow::Result<Vec<TreeEntry>> {
+    let entries = storage
+        .storage_logs_dal()
+        .get_tree_entries_for_miniblock(snapshot_miniblock, key_chunk.clone())
+        .await
+        .with_context(|| {
+            format!("Failed getting entries for chunk {key_chunk:?} in snapshot for miniblock #{snapshot_miniblock}")
+        })?;
+    let entries = entries
+        .into_iter()
+        .map(|entry| TreeEntry::new(entry.key, entry.leaf_index, entry.value));
+    Ok(entries.collect())
+}
+
+/// Finds up to `max_count` keys diverging between Postgres and tree entries, both keyed by the hashed key.
+fn divergent_keys(
+    postgres_entries: &BTreeMap<H256, EntryValue>,
+    tree_entries: &BTreeMap<H256, EntryValue>,
+    max_count: usize,
+) -> Vec<DivergentKey> {
+    let all_keys: BTreeSet<_> = postgres_entries.keys().chain(tree_entries.keys()).collect();
+    let divergent_keys = all_keys.into_iter().filter_map(|&hashed_key| {
+        let postgres = postgres_entries.get(&hashed_key).copied();
+        let tree = tree_entries.get(&hashed_key).copied();
+        (postgres != tree).then_some(DivergentKey {
+            hashed_key,
+            postgres,
+            tree,
+        })
+    });
+    divergent_keys.take(max_count).collect()
+}
+
+/// Diagnoses a root hash mismatch of the recovered `tree` by comparing fingerprints of entries in each
+/// of `key_chunks` in Postgres and in the tree. For diverging chunks, up to `max_divergent_keys_per_chunk`
+/// example diverging keys are collected. The report is logged and written next to the tree RocksDB directory.
+pub(super) async fn diagnose_root_hash_mismatch(
+    tree: &mut AsyncTreeRecovery,
+    storage: &mut StorageProcessor<'_>,
+    snapshot_miniblock: MiniblockNumber,
+    expected_root_hash: H256,
+    actual_root_hash: H256,
+    key_chunks: &[ops::RangeInclusive<H256>],
+    max_divergent_keys_per_chunk: usize,
+) -> anyhow::Result<RootHashMismatchReport> {
+    tracing::info!(
+        "Diagnosing root hash mismatch for the recovered Merkle tree in {} chunks",
+        key_chunks.len()
+    );
+    let diagnostics_latency = RECOVERY_METRICS.latency[&RecoveryStage::Diagnose].start();
+
+    let mut postgres_fingerprints = Vec::with_capacity(key_chunks.len());
+    for key_chunk in key_chunks {
+        let mut fingerprint = EntriesFingerprint::default();
+        for entry in load_postgres_entries(storage, snapshot_miniblock, key_chunk).await? {
+            fingerprint.push(&entry);
+        }
+        postgres_fingerprints.push(fingerprint);
+    }
+
+    let mut tree_fingerprints = vec![EntriesFingerprint::default(); key_chunks.len()];
+    let mut unassigned_tree_entry_count = 0;
+    for_each_tree_entry(tree, key_chunks.len(), |entry| {
+        match find_chunk(key_chunks, &hashed_key(&entry.key)) {
+            Some(chunk_id) => tree_fingerprints[chunk_id].push(entry),
+            None => unassigned_tree_entry_count += 1,
+        }
+    })
+    .await;
+
+    let divergent_chunk_ids: Vec<_> = (0..key_chunks.len())
+        .filter(|&chunk_id| postgres_fingerprints[chunk_id] != tree_fingerprints[chunk_id])
+        .collect();
+    let mut divergent_tree_entries: BTreeMap<_, BTreeMap<_, _>> = divergent_chunk_ids
+        .iter()
+        .map(|&chunk_id| (chunk_id, BTreeMap::new()))
+        .collect();
+    if max_divergent_keys_per_chunk > 0 && !divergent_chunk_ids.is_empty() {
+        for_each_tree_entry(tree, key_chunks.len(), |entry| {
+            let hashed_key = hashed_key(&entry.key);
+            let chunk_entries = find_chunk(key_chunks, &hashed_key)
+                .and_then(|chunk_id| divergent_tree_entries.get_mut(&chunk_id));
+            if let Some(chunk_entries) = chunk_entries {
+                chunk_entries.insert(hashed_key, EntryValue::from(entry));
+            }
+        })
+        .await;
+    }
+
+    let mut divergent_chunks = Vec::with_capacity(divergent_chunk_ids.len());
+    for (chunk_id, tree_entries) in divergent_tree_entries {
+        let key_chunk = &key_chunks[chunk_id];
+        let divergent_keys = if max_divergent_keys_per_chunk > 0 {
+            let postgres_entries: BTreeMap<_, _> =
+                load_postgres_entries(storage, snapshot_miniblock, key_chunk)
+                    .await?
+                    .iter()
+                    .map(|entry| (hashed_key(&entry.key), EntryValue::from(entry)))
+                    .collect();
+            divergent_keys(
+                &postgres_entries,
+                &tree_entries,
+                max_divergent_keys_per_chunk,
+            )
+        } else {
+            vec![]
+        };
+        divergent_chunks.push(DivergentChunk {
+            chunk_id,
+            key_range: key_chunk.clone(),
+            postgres: postgres_fingerprints[chunk_id],
+            tree: tree_fingerprints[chunk_id],
+            divergent_keys,
+        });
+    }
+
+    let report = RootHashMismatchReport {
+        snapshot_miniblock,
+        expected_root_hash,
+        actual_root_hash,
+        chunk_count: key_chunks.len(),
+        unassigned_tree_entry_count,
+        divergent_chunks,
+    };
+    let diagnostics_latency = diagnostics_latency.observe();
+    tracing::error!(
+        "Diagnosed root hash mismatch for the recovered Merkle tree in {diagnostics_latency:?}: {report}"
+    );
+    for chunk in &report.divergent_chunks {
+        for key in &chunk.divergent_keys {
+            tracing::error!(
+                "Chunk {}: key {:?} diverges (Postgres: {:?}, tree: {:?})",
+                chunk.chunk_id,
+                key.hashed_key,
+                key.postgres,
+                key.tree
+            );
+        }
+    }
+
+    let report_path = RootHashMismatchReport::path(tree.db_path());
+    match report.write(report_path.clone()).await {
+        Ok(()) => tracing::info!(
+            "Wrote root hash mismatch report to `{}`",
+            report_path.display()
+        ),
+        Err(err) => tracing::warn!("Failed writing root hash mismatch report: {err:#}"),
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use zksync_types::U256;
+
+    use super::*;
+
+    fn entry_value(value: u8, leaf_index: u64) -> EntryValue {
+        EntryValue {
+            value: H256::repeat_byte(value),
+            leaf_index,
+        }
+    }
+
+    #[test]
+    fn fingerprint_is_order_independent() {
+        let entries: Vec<_> = (1..=10_u64)
+            .map(|i| TreeEntry::new(U256::from(i), i, H256::from_low_u64_be(i)))
+            .collect();
+        let mut fingerprint = EntriesFingerprint::default();
+        for entry in &entries {
+            fingerprint.push(entry);
+        }
+        let mut reversed_fingerprint = EntriesFingerprint::default();
+        for entry in entries.iter().rev() {
+            reversed_fingerprint.push(entry);
+        }
+        assert_eq!(fingerprint, reversed_fingerprint);
+        assert_eq!(fingerprint.entry_count, 10);
+
+        let mut other_fingerprint = EntriesFingerprint::default();
+        for entry in &entries[1..] {
+            other_fingerprint.push(entry);
+        }
+        other_fingerprint.push(&TreeEntry::new(U256::from(1), 1, H256::zero()));
+        assert_ne!(fingerprint, other_fingerprint);
+    }
+
+    #[test]
+    fn finding_chunks() {
+        let key_chunks: Vec<_> = AsyncTreeRecovery::hashed_key_ranges(4).collect();
+        assert_eq!(find_chunk(&key_chunks, &H256::zero()), Some(0));
+        assert_eq!(find_chunk(&key_chunks, &H256::repeat_byte(0x3f)), Some(0));
+        assert_eq!(find_chunk(&key_chunks, &H256::repeat_byte(0x40)), Some(1));
+        assert_eq!(find_chunk(&key_chunks, &H256::repeat_byte(0xff)), Some(3));
+        assert_eq!(find_chunk(&key_chunks[..2], &H256::repeat_byte(0xff)), None);
+    }
+
+    #[test]
+    fn finding_divergent_keys() {
+        let postgres_entries = BTreeMap::from([
+            (H256::repeat_byte(1), entry_value(1, 1)),
+            (H256::repeat_byte(2), entry_value(2, 2)),
+            (H256::repeat_byte(3), entry_value(3, 3)),
+        ]);
+        let tree_entries = BTreeMap::from([
+            (H256::repeat_byte(1), entry_value(1, 1)),
+            (H256::repeat_byte(3), entry_value(0xff, 3)),
+            (H256::repeat_byte(4), entry_value(4, 4)),
+        ]);
+
+        let keys = divergent_keys(&postgres_entries, &tree_entries, 10);
+        assert_eq!(
+            keys,
+            [
+                DivergentKey {
+                    hashed_key: H256::repeat_byte(2),
+                    postgres: Some(entry_value(2, 2)),
+                    tree: None,
+                },
+                DivergentKey {
+                    hashed_key: H256::repeat_byte(3),
+                    postgres: Some(entry_value(3, 3)),
+                    tree: Some(entry_value(0xff, 3)),
+                },
+                DivergentKey {
+                    hashed_key: H256::repeat_byte(4),
+                    postgres: None,
+                    tree: Some(entry_value(4, 4)),
+                },
+            ]
+        );
+
+        let keys = divergent_keys(&postgres_entries, &tree_entries, 1);
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].hashed_key, H256::repeat_byte(2));
+    }
+}
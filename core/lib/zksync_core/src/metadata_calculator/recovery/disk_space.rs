@@ -0,0 +1,145 @@
+//! Checking that there is enough disk space for Merkle tree recovery.
+
+use std::{fmt, path::Path};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+
+/// Provider of filesystem stats. Encapsulated in a trait to be able to mock it in tests.
+pub(super) trait FsStatsProvider: fmt::Debug + Send + Sync {
+    /// Returns the number of bytes available to the current process on the filesystem containing `path`.
+    fn available_space(&self, path: &Path) -> anyhow::Result<u64>;
+}
+
+/// [`FsStatsProvider`] querying filesystem stats from the OS.
+#[derive(Debug)]
+pub(super) struct OsFsStats;
+
+impl FsStatsProvider for OsFsStats {
+    #[allow(clippy::useless_conversion)] // `statvfs` field types are platform-dependent
+    fn available_space(&self, path: &Path) -> anyhow::Result<u64> {
+        let stats = nix::sys::statvfs::statvfs(path)
+            .with_context(|| format!("failed getting filesystem stats for `{}`", path.display()))?;
+        Ok(u64::from(stats.blocks_available()).saturating_mul(u64::from(stats.fragment_size())))
+    }
+}
+
+/// Estimate of the disk space required for recovery together with the available space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskSpaceEstimate {
+    /// Estimated number of bytes that will be written to the tree during the remaining recovery.
+    pub required_bytes: u64,
+    /// Number of bytes available on the filesystem containing the tree.
+    pub available_bytes: u64,
+    /// Number of bytes that should remain available after recovery.
+    pub margin_bytes: u64,
+}
+
+impl DiskSpaceEstimate {
+    pub fn is_sufficient(&self) -> bool {
+        self.required_bytes.saturating_add(self.margin_bytes) <= self.available_bytes
+    }
+}
+
+/// Disk space check performed before recovering chunks.
+#[derive(Debug)]
+pub(super) struct DiskSpaceCheck<'a> {
+    pub bytes_per_entry: u64,
+    pub margin_bytes: u64,
+    /// Whether to return an error if there isn't enough disk space. Otherwise, only a warning is logged.
+    pub strict: bool,
+    pub fs_stats: &'a dyn FsStatsProvider,
+}
+
+impl DiskSpaceCheck<'_> {
+    /// Estimates the disk space required to insert `entry_count` entries into the tree located at `path`
+    /// and compares it with the available space.
+    pub fn run(&self, path: &Path, entry_count: u64) -> anyhow::Result<DiskSpaceEstimate> {
+        let available_bytes = self.fs_stats.available_space(path)?;
+        let estimate = DiskSpaceEstimate {
+            required_bytes: entry_count.saturating_mul(self.bytes_per_entry),
+            available_bytes,
+            margin_bytes: self.margin_bytes,
+        };
+        if estimate.is_sufficient() {
+            tracing::info!(
+                "Checked disk space for Merkle tree recovery of {entry_count} entries at `{}`: {estimate:?}",
+                path.display()
+            );
+            return Ok(estimate);
+        }
+
+        let message = format!(
+            "Merkle tree recovery of {entry_count} entries is estimated to require {} bytes of disk space, \
+             but only {} bytes are available at `{}` (the margin is {} bytes)",
+            estimate.required_bytes,
+            estimate.available_bytes,
+            path.display(),
+            estimate.margin_bytes
+        );
+        anyhow::ensure!(
+            !self.strict,
+            "{message}; free up disk space or disable the strict disk space check"
+        );
+        tracing::warn!("{message}; proceeding since the disk space check is not strict");
+        Ok(estimate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct MockFsStats(u64);
+
+    impl FsStatsProvider for MockFsStats {
+        fn available_space(&self, _path: &Path) -> anyhow::Result<u64> {
+            Ok(self.0)
+        }
+    }
+
+    fn check(available_bytes: u64, strict: bool) -> anyhow::Result<DiskSpaceEstimate> {
+        let fs_stats = MockFsStats(available_bytes);
+        let check = DiskSpaceCheck {
+            bytes_per_entry: 100,
+            margin_bytes: 1_000,
+            strict,
+            fs_stats: &fs_stats,
+        };
+        check.run(Path::new("/db/tree"), 50)
+    }
+
+    #[test]
+    fn disk_space_check_with_sufficient_space() {
+        for strict in [false, true] {
+            let estimate = check(6_000, strict).unwrap();
+            assert_eq!(
+                estimate,
+                DiskSpaceEstimate {
+                    required_bytes: 5_000,
+                    available_bytes: 6_000,
+                    margin_bytes: 1_000,
+                }
+            );
+            assert!(estimate.is_sufficient());
+        }
+    }
+
+    #[test]
+    fn disk_space_check_with_insufficient_space() {
+        let estimate = check(5_999, false).unwrap();
+        assert!(!estimate.is_sufficient());
+
+        let err = check(5_999, true).unwrap_err().to_string();
+        assert!(err.contains("5000 bytes"), "{err}");
+        assert!(err.contains("5999 bytes"), "{err}");
+    }
+
+    #[test]
+    fn querying_available_space_from_os() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let available_bytes = OsFsStats.available_space(temp_dir.path()).unwrap();
+        assert!(available_bytes > 0);
+    }
+}
@@ -0,0 +1,233 @@
+//! Structured errors returned by Merkle tree recovery.
+
+use std::time::Duration;
+
+use serde::Serialize;
+use zksync_types::{L1BatchNumber, MiniblockNumber, H256};
+
+use super::{super::helpers::RecoveryFailure, verification::LeafIndexStats, FailedChunks};
+
+/// Machine-readable kind of a [`RecoveryError`]. Reported in health check details when recovery fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryErrorKind {
+    SnapshotMissing,
+    SnapshotIncomplete,
+    InvalidSnapshotParameters,
+    RootHashMismatch,
+    CorruptedSnapshot,
+    LeafIndexMismatch,
+    ChunksFailed,
+    Interrupted,
+    DeadlineExceeded,
+    SnapshotSuperseded,
+    SnapshotChanged,
+    PreviouslyFailed,
+    Other,
+}
+
+/// Error returned by Merkle tree recovery.
+///
+/// Internally, recovery logic uses [`anyhow::Error`]s; typed errors are wrapped into them where they originate
+/// and are extracted back on conversion (see the `From<anyhow::Error>` implementation). Thus, context added
+/// to a wrapped typed error is discarded on conversion.
+#[derive(Debug, thiserror::Error)]
+pub enum RecoveryError {
+    /// Postgres doesn't contain snapshot data required for recovery (e.g., the tree is recovering, but there's
+    /// no snapshot recovery information, or the configured target L1 batch is not in Postgres).
+    #[error("{0}")]
+    SnapshotMissing(String),
+    /// Snapshot is not fully applied to Postgres yet.
+    #[error(
+        "Snapshot for L1 batch #{l1_batch_number} is not fully applied to Postgres (last finished chunk: \
+         {last_finished_chunk_id:?}, total chunk count: {total_chunk_count}); Merkle tree cannot be recovered from it"
+    )]
+    SnapshotIncomplete {
+        l1_batch_number: L1BatchNumber,
+        last_finished_chunk_id: Option<u64>,
+        total_chunk_count: u64,
+    },
+    /// Snapshot recovery information is inconsistent with Postgres data (e.g., the snapshot miniblock
+    /// is not the last miniblock of the snapshot L1 batch) or is invalid by itself (e.g., the expected root hash is zero).
+    #[error("Invalid snapshot parameters for L1 batch #{l1_batch_number}: {details}")]
+    InvalidSnapshotParameters {
+        l1_batch_number: L1BatchNumber,
+        details: String,
+    },
+    /// Root hash of the recovered tree differs from the one in the snapshot.
+    #[error(
+        "Root hash of recovered tree {actual:?} differs from expected root hash {expected:?}{}",
+        .diagnostics.as_ref().map(|report| format!("; {report}")).unwrap_or_default()
+    )]
+    RootHashMismatch {
+        expected: H256,
+        actual: H256,
+        /// Summary of the mismatch diagnostics, if they are enabled.
+        diagnostics: Option<String>,
+    },
+    /// Snapshot data is corrupted at the specified hashed key.
+    #[error("node snapshot is corrupted at hashed key {key:?}: {details}")]
+    CorruptedSnapshot { key: H256, details: String },
+    /// Leaf indices in the snapshot are inconsistent with the number of snapshot entries or with the recovered tree
+    /// (e.g., some leaf indices are duplicated or skipped).
+    #[error("leaf indices in snapshot for miniblock #{miniblock} are inconsistent: {stats}")]
+    LeafIndexMismatch {
+        miniblock: MiniblockNumber,
+        stats: LeafIndexStats,
+    },
+    /// Leaf indices of entries in chunks recovered by the tree don't form the range `1..=log_count`. Unlike
+    /// with [`Self::LeafIndexMismatch`], anomalous leaf indices are attributed to specific chunks.
+    #[error("leaf indices in snapshot for miniblock #{miniblock} are inconsistent: {details}")]
+    ChunkLeafIndexMismatch {
+        miniblock: MiniblockNumber,
+        details: String,
+    },
+    /// Some chunks could not be recovered.
+    #[error(transparent)]
+    ChunksFailed(FailedChunks),
+    /// Recovery was stopped before completion, either by a stop signal or after a dry run as configured.
+    #[error("Merkle tree recovery was interrupted")]
+    Interrupted,
+    /// Recovery didn't complete within the configured maximum duration. No chunks are started after the deadline,
+    /// but chunks in flight are recovered and persisted, so recovery is resumed from them after a restart.
+    #[error(
+        "Merkle tree recovery exceeded its deadline after {elapsed:?} with {recovered} / {total} chunks recovered; \
+         recovery will be resumed from recovered chunks after a restart. If recovery cannot complete in time, \
+         increase `max_recovery_duration_sec` or recovery concurrency in the config"
+    )]
+    DeadlineExceeded {
+        /// Number of recovered chunks, including ones recovered before a restart.
+        recovered: usize,
+        /// Total number of chunks in the snapshot.
+        total: usize,
+        /// Time elapsed since recovery was started or resumed after a restart.
+        elapsed: Duration,
+    },
+    /// A newer snapshot has appeared in Postgres while the tree was recovered from an older one. Only returned
+    /// if newer snapshots are preferred in the config; `GenericAsyncTree::ensure_ready()` handles this error
+    /// by removing the tree and restarting recovery from the newer snapshot.
+    #[error(
+        "Merkle tree recovery to L1 batch #{recovered_version} was aborted because Postgres contains \
+         a newer snapshot for L1 batch #{latest_l1_batch}"
+    )]
+    SnapshotSuperseded {
+        /// L1 batch the tree was recovered to.
+        recovered_version: u64,
+        /// L1 batch of the newer snapshot in Postgres.
+        latest_l1_batch: L1BatchNumber,
+    },
+    /// Snapshot data in Postgres has changed while the tree was recovered, so the recovered tree cannot be checked
+    /// against the snapshot. Unlike [`Self::SnapshotSuperseded`], this is not expected during normal operation.
+    #[error(
+        "Snapshot for L1 batch #{l1_batch_number} has changed in Postgres during Merkle tree recovery: {details}. \
+         This may be caused by a Postgres rollback or a reorg re-executing the snapshot L1 batch; \
+         check Postgres and restart recovery"
+    )]
+    SnapshotChanged {
+        l1_batch_number: L1BatchNumber,
+        details: String,
+    },
+    /// Recovery has previously failed with a root hash mismatch, and the tree was kept for inspection. Recovery
+    /// is not retried since it would fail in the same way; the tree must be reset manually
+    /// (see [`reset_tree_db()`](crate::metadata_calculator::reset_tree_db())).
+    #[error(
+        "Merkle tree recovery has previously failed at UNIX timestamp {}: root hash of recovered tree {:?} differs \
+         from expected root hash {:?}. Manual intervention required: the tree is kept for inspection \
+         (e.g., via `merkle_tree_consistency_checker inspect-recovery`); once the cause is fixed, reset the tree \
+         via `merkle_tree_consistency_checker reset-tree` to restart recovery from scratch",
+        .0.failed_at,
+        .0.actual_root_hash,
+        .0.expected_root_hash
+    )]
+    PreviouslyFailed(RecoveryFailure),
+    /// Other error (e.g., a Postgres or RocksDB error, or a misconfiguration).
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for RecoveryError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<Self>() {
+            Ok(err) => err,
+            Err(err) => Self::Other(err),
+        }
+    }
+}
+
+impl RecoveryError {
+    /// Returns the kind of this error.
+    pub fn kind(&self) -> RecoveryErrorKind {
+        match self {
+            Self::SnapshotMissing(_) => RecoveryErrorKind::SnapshotMissing,
+            Self::SnapshotIncomplete { .. } => RecoveryErrorKind::SnapshotIncomplete,
+            Self::InvalidSnapshotParameters { .. } => RecoveryErrorKind::InvalidSnapshotParameters,
+            Self::RootHashMismatch { .. } => RecoveryErrorKind::RootHashMismatch,
+            Self::CorruptedSnapshot { .. } => RecoveryErrorKind::CorruptedSnapshot,
+            Self::LeafIndexMismatch { .. } | Self::ChunkLeafIndexMismatch { .. } => {
+                RecoveryErrorKind::LeafIndexMismatch
+            }
+            Self::ChunksFailed(_) => RecoveryErrorKind::ChunksFailed,
+            Self::Interrupted => RecoveryErrorKind::Interrupted,
+            Self::DeadlineExceeded { .. } => RecoveryErrorKind::DeadlineExceeded,
+            Self::SnapshotSuperseded { .. } => RecoveryErrorKind::SnapshotSuperseded,
+            Self::SnapshotChanged { .. } => RecoveryErrorKind::SnapshotChanged,
+            Self::PreviouslyFailed(_) => RecoveryErrorKind::PreviouslyFailed,
+            Self::Other(_) => RecoveryErrorKind::Other,
+        }
+    }
+
+    /// Returns the kind of a typed error wrapped into `err`, or [`RecoveryErrorKind::Other`] if `err`
+    /// doesn't wrap a typed error.
+    pub(super) fn kind_of(err: &anyhow::Error) -> RecoveryErrorKind {
+        err.downcast_ref::<Self>()
+            .map_or(RecoveryErrorKind::Other, Self::kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed_errors_are_extracted_from_anyhow() {
+        let err = anyhow::Error::from(RecoveryError::CorruptedSnapshot {
+            key: H256::repeat_byte(1),
+            details: "test".to_owned(),
+        });
+        assert_eq!(
+            RecoveryError::kind_of(&err),
+            RecoveryErrorKind::CorruptedSnapshot
+        );
+        let err = RecoveryError::from(err);
+        assert!(
+            matches!(err, RecoveryError::CorruptedSnapshot { key, .. } if key == H256::repeat_byte(1)),
+            "{err:?}"
+        );
+
+        let err = RecoveryError::from(anyhow::anyhow!("test"));
+        assert_eq!(err.kind(), RecoveryErrorKind::Other);
+        assert_eq!(err.to_string(), "test");
+    }
+
+    #[test]
+    fn deadline_exceeded_error_is_actionable() {
+        let err = RecoveryError::DeadlineExceeded {
+            recovered: 3,
+            total: 10,
+            elapsed: Duration::from_secs(5),
+        };
+        assert_eq!(err.kind(), RecoveryErrorKind::DeadlineExceeded);
+        let message = err.to_string();
+        assert!(message.contains("3 / 10 chunks recovered"), "{message}");
+        assert!(message.contains("max_recovery_duration_sec"), "{message}");
+
+        let kind = serde_json::to_value(err.kind()).unwrap();
+        assert_eq!(kind, "deadline_exceeded");
+    }
+
+    #[test]
+    fn error_kind_serialization() {
+        let kind = serde_json::to_value(RecoveryErrorKind::RootHashMismatch).unwrap();
+        assert_eq!(kind, "root_hash_mismatch");
+    }
+}
@@ -0,0 +1,211 @@
+//! Exporting the recovered Merkle tree as a RocksDB checkpoint, so that it can be distributed to other nodes
+//! instead of recovering the tree on each node.
+//!
+//! An export is a directory containing the tree checkpoint (`db` subdirectory) and a JSON descriptor
+//! (`descriptor.json`) of the exported tree. The export is prepared in a temporary sibling directory, which
+//! is atomically renamed to the export path once the export is complete; thus, an interrupted export
+//! never leaves a partially written export at the export path.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use zksync_health_check::{Health, HealthStatus, HealthUpdater};
+use zksync_types::{L1BatchNumber, MiniblockNumber, H256};
+
+use crate::metadata_calculator::{
+    helpers::AsyncTree,
+    metrics::{RecoveryStage, RECOVERY_METRICS},
+};
+
+/// Descriptor of an exported Merkle tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct TreeExportDescriptor {
+    pub l1_batch_number: L1BatchNumber,
+    pub miniblock_number: MiniblockNumber,
+    pub root_hash: H256,
+    /// Number of entries (i.e., leaves) in the exported tree.
+    pub entry_count: u64,
+}
+
+impl TreeExportDescriptor {
+    const FILE_NAME: &'static str = "descriptor.json";
+
+    /// Returns the path to the descriptor file in the export directory.
+    pub fn path(export_path: &Path) -> PathBuf {
+        export_path.join(Self::FILE_NAME)
+    }
+
+    /// Loads the descriptor from the export directory.
+    pub async fn load(export_path: &Path) -> anyhow::Result<Self> {
+        let path = Self::path(export_path);
+        tokio::task::spawn_blocking(move || {
+            let contents = std::fs::read(&path)
+                .with_context(|| format!("failed reading `{}`", path.display()))?;
+            serde_json::from_slice(&contents)
+                .with_context(|| format!("failed deserializing `{}`", path.display()))
+        })
+        .await
+        .unwrap()
+    }
+
+    async fn write(&self, export_path: &Path) -> anyhow::Result<()> {
+        let path = Self::path(export_path);
+        let contents =
+            serde_json::to_vec_pretty(self).context("failed serializing export descriptor")?;
+        tokio::task::spawn_blocking(move || {
+            std::fs::write(&path, contents)
+                .with_context(|| format!("failed writing `{}`", path.display()))
+        })
+        .await
+        .unwrap()
+    }
+}
+
+/// Stage of the tree export reported via the health check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum TreeExportStage {
+    CreatingCheckpoint,
+    WritingDescriptor,
+    Completed,
+}
+
+/// Information about the tree export reported via the health check.
+#[derive(Debug, Serialize)]
+struct TreeExportInfo<'a> {
+    mode: &'static str, // "recovery_export" to distinguish from other tree health details
+    stage: TreeExportStage,
+    export_path: &'a Path,
+    descriptor: TreeExportDescriptor,
+}
+
+/// Exports the recovered `tree` to `export_path`. If the export path already exists and contains an export
+/// with the same descriptor, does nothing; if the existing export differs, returns an error.
+pub(super) async fn export_recovered_tree(
+    tree: &AsyncTree,
+    miniblock_number: MiniblockNumber,
+    export_path: &Path,
+    health_updater: &HealthUpdater,
+) -> anyhow::Result<TreeExportDescriptor> {
+    let tree_info = tree.reader().info().await;
+    let descriptor = TreeExportDescriptor {
+        l1_batch_number: tree_info.next_l1_batch_number - 1,
+        miniblock_number,
+        root_hash: tree_info.root_hash,
+        entry_count: tree_info.leaf_count,
+    };
+
+    if export_path.exists() {
+        let existing_descriptor =
+            TreeExportDescriptor::load(export_path)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Merkle tree export path `{}` exists, but doesn't contain a valid export",
+                        export_path.display()
+                    )
+                })?;
+        anyhow::ensure!(
+            existing_descriptor == descriptor,
+            "Merkle tree export at `{}` ({existing_descriptor:?}) differs from the recovered tree ({descriptor:?}); \
+             remove the export or change the export path",
+            export_path.display()
+        );
+        tracing::info!(
+            "Merkle tree is already exported to `{}`: {descriptor:?}",
+            export_path.display()
+        );
+        return Ok(descriptor);
+    }
+
+    let export_latency = RECOVERY_METRICS.latency[&RecoveryStage::Export].start();
+    let update_health = |stage| {
+        let health = Health::from(HealthStatus::Ready).with_details(TreeExportInfo {
+            mode: "recovery_export",
+            stage,
+            export_path,
+            descriptor,
+        });
+        health_updater.update(health);
+    };
+
+    let temp_path = partial_export_path(export_path);
+    let temp_path_for_task = temp_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let temp_path = temp_path_for_task;
+        if temp_path.exists() {
+            tracing::info!(
+                "Removing partial Merkle tree export at `{}` left after an interrupted export",
+                temp_path.display()
+            );
+            std::fs::remove_dir_all(&temp_path)
+                .with_context(|| format!("failed removing `{}`", temp_path.display()))?;
+        }
+        std::fs::create_dir_all(&temp_path)
+            .with_context(|| format!("failed creating `{}`", temp_path.display()))
+    })
+    .await
+    .unwrap()?;
+
+    update_health(TreeExportStage::CreatingCheckpoint);
+    tree.reader()
+        .create_checkpoint(temp_path.join("db"))
+        .await?;
+    update_health(TreeExportStage::WritingDescriptor);
+    descriptor.write(&temp_path).await?;
+
+    let export_path_for_task = export_path.to_owned();
+    tokio::task::spawn_blocking(move || {
+        std::fs::rename(&temp_path, &export_path_for_task).with_context(|| {
+            format!(
+                "failed renaming `{}` to `{}`",
+                temp_path.display(),
+                export_path_for_task.display()
+            )
+        })
+    })
+    .await
+    .unwrap()?;
+    update_health(TreeExportStage::Completed);
+
+    let export_latency = export_latency.observe();
+    tracing::info!(
+        "Exported recovered Merkle tree to `{}` in {export_latency:?}: {descriptor:?}",
+        export_path.display()
+    );
+    Ok(descriptor)
+}
+
+/// Returns the path of the temporary directory used to prepare an export.
+fn partial_export_path(export_path: &Path) -> PathBuf {
+    export_path.with_extension("partial")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_descriptor_serialization() {
+        let descriptor = TreeExportDescriptor {
+            l1_batch_number: L1BatchNumber(42),
+            miniblock_number: MiniblockNumber(123),
+            root_hash: H256::repeat_byte(1),
+            entry_count: 1_000,
+        };
+        let json = serde_json::to_value(descriptor).unwrap();
+        assert_eq!(json["l1_batch_number"], 42);
+        assert_eq!(json["miniblock_number"], 123);
+        assert_eq!(json["entry_count"], 1_000);
+
+        let restored: TreeExportDescriptor = serde_json::from_value(json).unwrap();
+        assert_eq!(restored, descriptor);
+    }
+
+    #[test]
+    fn partial_export_path_is_a_sibling() {
+        let path = partial_export_path(Path::new("/exports/tree"));
+        assert_eq!(path, Path::new("/exports/tree.partial"));
+    }
+}
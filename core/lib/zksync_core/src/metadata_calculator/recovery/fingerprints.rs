@@ -0,0 +1,259 @@
+//! Append-only log of Merkle tree fingerprints recorded during recovery.
+//!
+//! After each chunk is recovered, the current tree root hash is recorded together with the chunk key range
+//! and the number of inserted entries. The log is stored as a JSON Lines file next to the tree RocksDB directory
+//! and allows proving which data went into the tree and when; if the recovered tree has an unexpected root hash,
+//! the log bounds the chunks where divergence was introduced. The log is truncated when recovery starts from scratch
+//! and is archived when recovery is finalized, replacing the archive of the previous recovery.
+
+use std::{
+    fs,
+    io::{self, Write as _},
+    ops,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use zksync_types::H256;
+use zksync_utils::time::seconds_since_epoch;
+
+/// Tree fingerprint recorded after a chunk is recovered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkFingerprint {
+    /// Recovered tree version (i.e., the snapshot L1 batch number).
+    pub recovered_version: u64,
+    /// Zero-based index of the recovered chunk.
+    pub chunk_index: usize,
+    /// Start of the hashed key range of the chunk (inclusive).
+    pub key_range_start: H256,
+    /// End of the hashed key range of the chunk (inclusive).
+    pub key_range_end: H256,
+    /// Number of entries inserted into the tree for the chunk.
+    pub entry_count: u64,
+    /// Tree root hash after the chunk was recovered.
+    pub root_hash: H256,
+    /// UNIX timestamp (in seconds) when the fingerprint was recorded.
+    pub recorded_at: u64,
+}
+
+impl ChunkFingerprint {
+    pub(super) fn new(
+        recovered_version: u64,
+        chunk_index: usize,
+        key_range: &ops::RangeInclusive<H256>,
+        entry_count: u64,
+        root_hash: H256,
+    ) -> Self {
+        Self {
+            recovered_version,
+            chunk_index,
+            key_range_start: *key_range.start(),
+            key_range_end: *key_range.end(),
+            entry_count,
+            root_hash,
+            recorded_at: seconds_since_epoch(),
+        }
+    }
+}
+
+/// Contents of [`RecoveryFingerprintLog`] returned by [`RecoveryFingerprintLog::dump_json()`].
+#[derive(Debug, Serialize)]
+struct FingerprintLogDump {
+    /// Fingerprints recorded by the ongoing (or interrupted) recovery.
+    log: Vec<ChunkFingerprint>,
+    /// Fingerprints recorded by the last finalized recovery.
+    archived_log: Vec<ChunkFingerprint>,
+}
+
+/// Append-only log of tree fingerprints recorded during recovery (see [`ChunkFingerprint`]).
+#[derive(Debug, Clone)]
+pub struct RecoveryFingerprintLog {
+    path: PathBuf,
+}
+
+impl RecoveryFingerprintLog {
+    /// Returns the log for the tree with the specified RocksDB directory. The log is stored next to the directory.
+    pub fn for_tree(db_path: &Path) -> Self {
+        Self {
+            path: db_path.with_extension("fingerprints.jsonl"),
+        }
+    }
+
+    /// Returns the path to the log.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path to the log archived when recovery was finalized.
+    pub fn archive_path(&self) -> PathBuf {
+        self.path.with_extension("archived.jsonl")
+    }
+
+    /// Reads fingerprints recorded by the ongoing (or interrupted) recovery. Returns an empty list
+    /// if the log doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns I/O and deserialization errors.
+    pub fn read(&self) -> anyhow::Result<Vec<ChunkFingerprint>> {
+        Self::read_file(&self.path)
+    }
+
+    /// Reads fingerprints recorded by the last finalized recovery. Returns an empty list if there is no archived log.
+    ///
+    /// # Errors
+    ///
+    /// Returns I/O and deserialization errors.
+    pub fn read_archived(&self) -> anyhow::Result<Vec<ChunkFingerprint>> {
+        Self::read_file(&self.archive_path())
+    }
+
+    /// Dumps both the current and the archived logs as a pretty-printed JSON object.
+    ///
+    /// # Errors
+    ///
+    /// Returns I/O and deserialization errors.
+    pub fn dump_json(&self) -> anyhow::Result<String> {
+        let dump = FingerprintLogDump {
+            log: self.read()?,
+            archived_log: self.read_archived()?,
+        };
+        serde_json::to_string_pretty(&dump).context("failed serializing fingerprint log")
+    }
+
+    fn read_file(path: &Path) -> anyhow::Result<Vec<ChunkFingerprint>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => {
+                return Err(anyhow::Error::from(err).context(format!(
+                    "failed reading fingerprint log `{}`",
+                    path.display()
+                )))
+            }
+        };
+        let lines = contents.lines().enumerate();
+        let lines = lines.filter(|(_, line)| !line.is_empty());
+        lines
+            .map(|(i, line)| {
+                serde_json::from_str(line).with_context(|| {
+                    format!(
+                        "malformed line #{} in fingerprint log `{}`",
+                        i + 1,
+                        path.display()
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Removes all fingerprints from the log.
+    pub(super) async fn truncate(&self) -> anyhow::Result<()> {
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(anyhow::Error::from(err)
+                .context(format!(
+                    "failed truncating fingerprint log `{}`",
+                    path.display()
+                ))),
+            _ => Ok(()),
+        })
+        .await
+        .context("panicked truncating fingerprint log")?
+    }
+
+    /// Appends a fingerprint to the log. The fingerprint is synced to disk before returning.
+    pub(super) async fn append(&self, fingerprint: &ChunkFingerprint) -> anyhow::Result<()> {
+        let mut line =
+            serde_json::to_string(fingerprint).context("failed serializing fingerprint")?;
+        line.push('\n');
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?;
+            file.write_all(line.as_bytes())?;
+            file.sync_data()
+        })
+        .await
+        .context("panicked appending to fingerprint log")?
+        .with_context(|| {
+            format!(
+                "failed appending to fingerprint log `{}`",
+                self.path.display()
+            )
+        })
+    }
+
+    /// Archives the log, replacing the previously archived log. Does nothing if the log doesn't exist.
+    pub(super) async fn archive(&self) -> anyhow::Result<()> {
+        let path = self.path.clone();
+        let archive_path = self.archive_path();
+        tokio::task::spawn_blocking(move || {
+            if !path.exists() {
+                return Ok(());
+            }
+            fs::rename(&path, &archive_path).with_context(|| {
+                format!(
+                    "failed archiving fingerprint log `{}` to `{}`",
+                    path.display(),
+                    archive_path.display()
+                )
+            })
+        })
+        .await
+        .context("panicked archiving fingerprint log")?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn fingerprint_log_paths() {
+        let log = RecoveryFingerprintLog::for_tree(Path::new("/db/tree"));
+        assert_eq!(log.path(), Path::new("/db/tree.fingerprints.jsonl"));
+        assert_eq!(
+            log.archive_path(),
+            Path::new("/db/tree.fingerprints.archived.jsonl")
+        );
+    }
+
+    #[tokio::test]
+    async fn fingerprint_log_basics() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = RecoveryFingerprintLog::for_tree(&temp_dir.path().join("tree"));
+        assert!(log.read().unwrap().is_empty());
+
+        let fingerprints: Vec<_> = (0..3)
+            .map(|i| {
+                let key_range = H256::repeat_byte(i)..=H256::repeat_byte(i + 1);
+                ChunkFingerprint::new(42, i.into(), &key_range, 10, H256::repeat_byte(0xff))
+            })
+            .collect();
+        for fingerprint in &fingerprints {
+            log.append(fingerprint).await.unwrap();
+        }
+        assert_eq!(log.read().unwrap(), fingerprints);
+
+        log.archive().await.unwrap();
+        assert!(!log.path().exists());
+        assert!(log.read().unwrap().is_empty());
+        assert_eq!(log.read_archived().unwrap(), fingerprints);
+        let dump: serde_json::Value = serde_json::from_str(&log.dump_json().unwrap()).unwrap();
+        assert_eq!(dump["log"], serde_json::json!([]));
+        assert_eq!(dump["archived_log"].as_array().unwrap().len(), 3);
+
+        log.append(&fingerprints[0]).await.unwrap();
+        log.truncate().await.unwrap();
+        assert!(log.read().unwrap().is_empty());
+        // Truncating a missing log is a no-op.
+        log.truncate().await.unwrap();
+        assert_eq!(log.read_archived().unwrap(), fingerprints);
+    }
+}
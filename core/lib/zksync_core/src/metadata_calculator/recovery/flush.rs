@@ -0,0 +1,115 @@
+//! Periodic flushing of the tree RocksDB memtables during recovery.
+
+/// Interval between manual flushes of the tree RocksDB memtables during recovery. Memtables are flushed once
+/// either of the thresholds is reached; if no thresholds are set, memtables are only flushed by RocksDB itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct FlushInterval {
+    /// Number of recovered chunks.
+    pub chunks: Option<usize>,
+    /// Approximate byte size of entries applied to the tree (see [`LoadedEntriesBudget::entries_bytes()`]).
+    ///
+    /// [`LoadedEntriesBudget::entries_bytes()`]: super::memory::LoadedEntriesBudget::entries_bytes()
+    pub bytes: Option<usize>,
+}
+
+/// Tracks progress since the last flush of the tree RocksDB memtables according to a [`FlushInterval`].
+#[derive(Debug)]
+pub(super) struct FlushTracker {
+    interval: FlushInterval,
+    recovered_chunks: usize,
+    applied_bytes: usize,
+}
+
+impl FlushTracker {
+    pub fn new(interval: FlushInterval) -> Self {
+        Self {
+            interval,
+            recovered_chunks: 0,
+            applied_bytes: 0,
+        }
+    }
+
+    /// Records entries applied to the tree.
+    pub fn observe_applied_bytes(&mut self, bytes: usize) {
+        self.applied_bytes = self.applied_bytes.saturating_add(bytes);
+    }
+
+    /// Records a recovered chunk.
+    pub fn observe_recovered_chunk(&mut self) {
+        self.recovered_chunks += 1;
+    }
+
+    /// Checks whether memtables should be flushed. If so, resets the progress, assuming that the caller
+    /// will flush memtables.
+    pub fn take_flush(&mut self) -> bool {
+        let chunks_reached = self
+            .interval
+            .chunks
+            .is_some_and(|chunks| self.recovered_chunks >= chunks);
+        let bytes_reached = self
+            .interval
+            .bytes
+            .is_some_and(|bytes| self.applied_bytes >= bytes);
+        if chunks_reached || bytes_reached {
+            self.recovered_chunks = 0;
+            self.applied_bytes = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracker_without_thresholds_never_flushes() {
+        let mut tracker = FlushTracker::new(FlushInterval::default());
+        for _ in 0..100 {
+            tracker.observe_applied_bytes(1 << 20);
+            tracker.observe_recovered_chunk();
+            assert!(!tracker.take_flush());
+        }
+    }
+
+    #[test]
+    fn flushing_every_several_chunks() {
+        let mut tracker = FlushTracker::new(FlushInterval {
+            chunks: Some(3),
+            bytes: None,
+        });
+        let flushes: Vec<_> = (0..9)
+            .map(|_| {
+                tracker.observe_applied_bytes(1 << 20);
+                tracker.observe_recovered_chunk();
+                tracker.take_flush()
+            })
+            .collect();
+        assert_eq!(
+            flushes,
+            [false, false, true, false, false, true, false, false, true]
+        );
+    }
+
+    #[test]
+    fn flushing_by_chunks_or_bytes() {
+        let mut tracker = FlushTracker::new(FlushInterval {
+            chunks: Some(2),
+            bytes: Some(1_000),
+        });
+        // Batches of a streamed chunk are accounted before the chunk is recovered.
+        tracker.observe_applied_bytes(600);
+        assert!(!tracker.take_flush());
+        tracker.observe_applied_bytes(600);
+        assert!(tracker.take_flush());
+        tracker.observe_recovered_chunk();
+        assert!(!tracker.take_flush());
+
+        tracker.observe_applied_bytes(100);
+        tracker.observe_recovered_chunk();
+        assert!(tracker.take_flush());
+        assert!(!tracker.take_flush());
+    }
+}
@@ -0,0 +1,151 @@
+//! Versioning of the Merkle tree recovery format.
+//!
+//! Recovery can span several node versions if the node is updated while the tree is recovering. If the tree hasher
+//! or the encoding of recovered entries changes between versions, resuming recovery started by another binary
+//! would mix incompatible tree nodes, which is only detected by the root hash check once all chunks are recovered.
+//! To fail early instead, the recovery format is persisted in the tree manifest when recovery starts and is checked
+//! each time recovery is resumed.
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use zksync_crypto::hasher::blake2::Blake2Hasher;
+use zksync_merkle_tree::HashTree;
+
+/// Migration of a persisted recovery format to a newer format. Returns `None` if the migration doesn't apply
+/// to the format.
+type FormatMigration = fn(&RecoveryFormat) -> Option<RecoveryFormat>;
+
+/// Migrations for compatible changes of the recovery format, i.e. ones that don't influence recovered tree nodes
+/// (e.g., a change in the entry encoding that decodes old entries to the same values). Migrations are applied
+/// in order. When bumping [`RecoveryFormat::ENTRY_ENCODING`] in a compatible way, add a migration from
+/// the previous format here; otherwise, recoveries started by older binaries cannot be resumed.
+const MIGRATIONS: &[FormatMigration] = &[];
+
+/// Format of the Merkle tree recovery.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct RecoveryFormat {
+    /// Name of the hasher used for tree nodes (see [`HashTree::name()`]).
+    pub hasher: String,
+    /// Version of the encoding of recovered tree entries (hashed keys, values and leaf indices).
+    pub entry_encoding: u32,
+}
+
+impl RecoveryFormat {
+    /// Version of the entry encoding used by this binary. Must be bumped whenever the encoding changes.
+    const ENTRY_ENCODING: u32 = 1;
+
+    /// Returns the format used by this binary.
+    pub fn current() -> Self {
+        Self {
+            hasher: Blake2Hasher.name().to_owned(),
+            entry_encoding: Self::ENTRY_ENCODING,
+        }
+    }
+
+    /// Checks that recovery started with the `persisted` format (as stored in the tree manifest) can be resumed
+    /// by this binary. Returns `true` if the format was migrated to the current one and should be persisted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error with upgrade instructions if the persisted format is malformed or incompatible.
+    pub fn check_resume(persisted: &str) -> anyhow::Result<bool> {
+        Self::check_resume_with_migrations(persisted, MIGRATIONS)
+    }
+
+    fn check_resume_with_migrations(
+        persisted: &str,
+        migrations: &[FormatMigration],
+    ) -> anyhow::Result<bool> {
+        const HINT: &str =
+            "Finish recovery using the node version that started it, or remove the tree \
+                            to restart recovery from scratch";
+
+        let current = Self::current();
+        let persisted: Self = serde_json::from_str(persisted).with_context(|| {
+            format!(
+                "Malformed recovery format persisted in Merkle tree: {persisted:?}; it was likely written \
+                 by an incompatible node version. {HINT}"
+            )
+        })?;
+        if persisted == current {
+            return Ok(false);
+        }
+
+        let mut format = persisted.clone();
+        for migration in migrations {
+            if let Some(migrated) = migration(&format) {
+                format = migrated;
+            }
+        }
+        anyhow::ensure!(
+            format == current,
+            "Merkle tree recovery was started with recovery format {persisted:?}, which is incompatible with \
+             the format {current:?} used by this node version; resuming recovery would produce a wrong root hash. \
+             {HINT}"
+        );
+        tracing::info!(
+            "Migrated Merkle tree recovery format {persisted:?} to the current format {current:?}"
+        );
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_format_is_compatible() {
+        let format = serde_json::to_string(&RecoveryFormat::current()).unwrap();
+        assert!(!RecoveryFormat::check_resume(&format).unwrap());
+    }
+
+    #[test]
+    fn incompatible_formats_are_rejected() {
+        let old_format = RecoveryFormat {
+            entry_encoding: 0,
+            ..RecoveryFormat::current()
+        };
+        let old_format = serde_json::to_string(&old_format).unwrap();
+        let err = RecoveryFormat::check_resume(&old_format)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("would produce a wrong root hash"), "{err}");
+
+        let other_hasher = RecoveryFormat {
+            hasher: "keccak256".to_owned(),
+            ..RecoveryFormat::current()
+        };
+        let other_hasher = serde_json::to_string(&other_hasher).unwrap();
+        RecoveryFormat::check_resume(&other_hasher).unwrap_err();
+
+        let err = RecoveryFormat::check_resume("garbage").unwrap_err();
+        assert!(format!("{err:#}").contains("Malformed"), "{err:#}");
+    }
+
+    #[test]
+    fn compatible_formats_are_migrated() {
+        let old_format = RecoveryFormat {
+            entry_encoding: 0,
+            ..RecoveryFormat::current()
+        };
+        let old_format = serde_json::to_string(&old_format).unwrap();
+        let migrations: &[FormatMigration] = &[|format| {
+            (format.entry_encoding == 0).then(|| RecoveryFormat {
+                entry_encoding: 1,
+                ..format.clone()
+            })
+        }];
+        let migrated =
+            RecoveryFormat::check_resume_with_migrations(&old_format, migrations).unwrap();
+        assert!(migrated);
+
+        // A format with another hasher remains incompatible after migrations.
+        let other_hasher = RecoveryFormat {
+            hasher: "keccak256".to_owned(),
+            entry_encoding: 0,
+        };
+        let other_hasher = serde_json::to_string(&other_hasher).unwrap();
+        RecoveryFormat::check_resume_with_migrations(&other_hasher, migrations).unwrap_err();
+    }
+}
@@ -0,0 +1,159 @@
+//! Importing a Merkle tree exported by another node or by this node before its tree was lost
+//! (see [`export_recovered_tree()`]) instead of recovering the tree from Postgres.
+//!
+//! [`export_recovered_tree()`]: super::export::export_recovered_tree
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use zksync_config::configs::database::MerkleTreeMode;
+use zksync_merkle_tree::RocksDBWrapper;
+use zksync_types::snapshots::SnapshotRecoveryStatus;
+
+use super::export::TreeExportDescriptor;
+use crate::metadata_calculator::{
+    helpers::{replace_db, AsyncTree, GenericAsyncTree},
+    metrics::{RecoveryStage, RECOVERY_METRICS},
+};
+
+/// Merkle tree imported into a staging directory next to the tree RocksDB directory and verified
+/// by [`import_exported_tree()`]. The staged tree replaces the (empty) tree using [`Self::install()`].
+#[derive(Debug)]
+#[must_use = "staged tree should be installed"]
+pub(super) struct StagedImport {
+    staging_path: PathBuf,
+    mode: MerkleTreeMode,
+}
+
+impl StagedImport {
+    /// Replaces the empty tree `db` with the staged tree.
+    pub async fn install(self, db: RocksDBWrapper) -> anyhow::Result<AsyncTree> {
+        let db = replace_db(db, self.staging_path)
+            .await
+            .context("failed installing imported Merkle tree")?;
+        Ok(AsyncTree::new(db, self.mode))
+    }
+}
+
+/// Imports the tree exported to `import_path` for the empty tree at `db_path`. The export is verified against
+/// `snapshot_recovery`: the exported L1 batch must match the snapshot L1 batch, and the imported tree must have
+/// the expected root hash and be internally consistent.
+///
+/// The tree is imported by creating a RocksDB checkpoint of the exported tree in a staging directory next to
+/// `db_path`. Checkpoint files are hard-linked if possible, so the import is cheap even for large trees.
+/// The tree at `db_path` is not touched until the staged tree is installed, so the import can be safely
+/// interrupted at any point; a staging directory left after an interrupted or failed import is removed
+/// on the next import.
+pub(super) async fn import_exported_tree(
+    db_path: &Path,
+    mode: MerkleTreeMode,
+    import_path: &Path,
+    snapshot_recovery: &SnapshotRecoveryStatus,
+) -> anyhow::Result<StagedImport> {
+    let l1_batch = snapshot_recovery.l1_batch_number;
+    let expected_root_hash = snapshot_recovery.l1_batch_root_hash;
+    let descriptor = TreeExportDescriptor::load(import_path)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed loading descriptor of the exported Merkle tree at `{}`",
+                import_path.display()
+            )
+        })?;
+    anyhow::ensure!(
+        descriptor.l1_batch_number == l1_batch && descriptor.root_hash == expected_root_hash,
+        "Exported Merkle tree at `{}` ({descriptor:?}) doesn't match the snapshot L1 batch #{l1_batch} \
+         with root hash {expected_root_hash:?}",
+        import_path.display()
+    );
+    let source_path = import_path.join("db");
+    anyhow::ensure!(
+        source_path.is_dir(),
+        "Exported Merkle tree at `{}` doesn't contain RocksDB directory",
+        import_path.display()
+    );
+
+    let import_latency = RECOVERY_METRICS.latency[&RecoveryStage::Import].start();
+    tracing::info!(
+        "Importing Merkle tree exported at `{}`: {descriptor:?}",
+        import_path.display()
+    );
+    let staging_path = import_staging_path(db_path);
+    let staging_path_for_task = staging_path.clone();
+    tokio::task::spawn_blocking(move || stage_checkpoint(&source_path, &staging_path_for_task))
+        .await
+        .unwrap()?;
+
+    let verification_result = async {
+        let db = RocksDBWrapper::new(&staging_path);
+        let tree = match GenericAsyncTree::new(db, mode).await {
+            GenericAsyncTree::Ready(tree) => tree,
+            GenericAsyncTree::Empty { .. } => anyhow::bail!("Imported Merkle tree is empty"),
+            GenericAsyncTree::Recovering(_) => {
+                anyhow::bail!("Imported Merkle tree is not fully recovered")
+            }
+        };
+        let next_l1_batch = tree.next_l1_batch_number();
+        anyhow::ensure!(
+            next_l1_batch == l1_batch + 1,
+            "Imported Merkle tree has next L1 batch #{next_l1_batch}, while the snapshot L1 batch is #{l1_batch}"
+        );
+        let actual_root_hash = tree.root_hash();
+        anyhow::ensure!(
+            actual_root_hash == expected_root_hash,
+            "Root hash of imported tree {actual_root_hash:?} differs from expected root hash {expected_root_hash:?}"
+        );
+        tree.reader().verify_consistency(l1_batch).await
+        // `tree` is dropped here, closing the staged RocksDB, so that it can be moved.
+    };
+
+    match verification_result.await {
+        Ok(()) => {
+            let import_latency = import_latency.observe();
+            tracing::info!("Imported and verified Merkle tree in {import_latency:?}");
+            Ok(StagedImport { staging_path, mode })
+        }
+        Err(err) => {
+            let removal_result = tokio::task::spawn_blocking(move || {
+                fs::remove_dir_all(&staging_path)
+                    .with_context(|| format!("failed removing `{}`", staging_path.display()))
+            })
+            .await
+            .unwrap();
+            if let Err(removal_err) = removal_result {
+                tracing::warn!("Failed removing imported Merkle tree: {removal_err:#}");
+            }
+            Err(err.context("Imported Merkle tree failed verification; imported data was removed"))
+        }
+    }
+}
+
+/// Creates a checkpoint of the exported RocksDB at `source_path` at `staging_path`, removing a staging directory
+/// left after a previous import if necessary.
+fn stage_checkpoint(source_path: &Path, staging_path: &Path) -> anyhow::Result<()> {
+    if staging_path.exists() {
+        tracing::info!(
+            "Removing Merkle tree at `{}` left after an interrupted import",
+            staging_path.display()
+        );
+        fs::remove_dir_all(staging_path)
+            .with_context(|| format!("failed removing `{}`", staging_path.display()))?;
+    }
+    let source_db = RocksDBWrapper::new(source_path);
+    source_db.create_checkpoint(staging_path).with_context(|| {
+        format!(
+            "failed creating checkpoint of exported Merkle tree at `{}`",
+            staging_path.display()
+        )
+    })
+}
+
+/// Returns the path of the staging directory used to import the tree at `db_path`.
+pub(super) fn import_staging_path(db_path: &Path) -> PathBuf {
+    let mut file_name = db_path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".import");
+    db_path.with_file_name(file_name)
+}
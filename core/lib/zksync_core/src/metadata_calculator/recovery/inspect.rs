@@ -0,0 +1,221 @@
+//! Offline inspection of the Merkle tree RocksDB, e.g. to check recovery progress, and resetting the tree
+//! after a failed recovery.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use zksync_config::configs::database::MerkleTreeMode;
+use zksync_merkle_tree::Key;
+use zksync_types::{L1BatchNumber, H256};
+use zksync_utils::u256_to_h256;
+
+use super::{
+    super::helpers::{
+        open_db_read_only, remove_db_dir_sync, AsyncTreeRecovery, GenericAsyncTree, RecoveryFailure,
+    },
+    ChunkJournalEntry,
+};
+
+/// Chunk partially applied to the tree according to the recovery journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IncompleteChunk {
+    /// Start of the chunk key range.
+    pub start_key: H256,
+    /// Greatest key among applied entries of the chunk, or `None` if no entries were applied yet.
+    pub last_applied_key: Option<H256>,
+}
+
+/// Progress of the Merkle tree recovery.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryInspection {
+    /// Recovered tree version, i.e., the number of the snapshot L1 batch.
+    pub recovered_version: u64,
+    /// Current root hash of the tree.
+    pub root_hash: H256,
+    /// Number of entries recovered so far.
+    pub leaf_count: u64,
+    /// Least key present in the tree, or `None` if the tree is empty.
+    pub least_key: Option<H256>,
+    /// Greatest key present in the tree, or `None` if the tree is empty.
+    pub greatest_key: Option<H256>,
+    /// Approximate share of the keyspace between the least and the greatest present keys (from 0 to 1).
+    /// Since chunks may be recovered in any order, this is only an upper bound on the recovered share
+    /// of the keyspace.
+    pub key_span: f64,
+    /// Chunks partially applied to the tree according to the recovery journal.
+    pub incomplete_chunks: Vec<IncompleteChunk>,
+    /// Whether some tree writes may have been lost (see [`AsyncTreeRecovery::may_have_lost_writes()`]).
+    pub may_have_lost_writes: bool,
+}
+
+impl RecoveryInspection {
+    fn key_span(least_key: Key, greatest_key: Key) -> f64 {
+        // The 64 most significant bits provide sufficient precision.
+        let span = (greatest_key - least_key) >> 192;
+        span.low_u64() as f64 / 2.0_f64.powi(64)
+    }
+}
+
+/// State of the Merkle tree RocksDB returned by [`inspect_tree_db()`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum TreeDbState {
+    /// Tree is not initialized.
+    Empty,
+    /// Tree is being recovered from a snapshot.
+    Recovering(RecoveryInspection),
+    /// Tree is ready for normal operation.
+    Ready {
+        next_l1_batch_number: L1BatchNumber,
+        root_hash: H256,
+        leaf_count: u64,
+    },
+}
+
+/// Information about the Merkle tree RocksDB returned by [`inspect_tree_db()`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TreeDbInspection {
+    /// Path to the tree RocksDB directory.
+    pub path: PathBuf,
+    /// Total size of files in the tree RocksDB directory.
+    pub disk_usage_bytes: u64,
+    #[serde(flatten)]
+    pub state: TreeDbState,
+    /// Marker of the failed recovery, if recovery has failed with a root hash mismatch. Such a tree
+    /// must be reset using [`reset_tree_db()`] to restart recovery.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery_failure: Option<RecoveryFailure>,
+}
+
+/// Inspects the Merkle tree RocksDB at `path` without modifying it. The database is opened in the read-only mode,
+/// so it can be inspected while it's used by a running node; in this case, the latest changes may not be visible.
+///
+/// # Errors
+///
+/// Returns an error if the database cannot be opened (e.g., it doesn't exist) or the recovery journal is corrupted.
+pub async fn inspect_tree_db(path: PathBuf) -> anyhow::Result<TreeDbInspection> {
+    let db = open_db_read_only(path.clone()).await?;
+    // The mode doesn't influence inspection; it's only used when the tree is written to.
+    let mut tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let recovery_failure = tree.recovery_failure().await?;
+    let state = match tree {
+        GenericAsyncTree::Empty { .. } => TreeDbState::Empty,
+        GenericAsyncTree::Recovering(mut tree) => TreeDbState::Recovering(tree.inspect().await?),
+        GenericAsyncTree::Ready(tree) => {
+            let info = tree.reader().info().await;
+            TreeDbState::Ready {
+                next_l1_batch_number: info.next_l1_batch_number,
+                root_hash: info.root_hash,
+                leaf_count: info.leaf_count,
+            }
+        }
+    };
+
+    let disk_usage_bytes = tokio::task::spawn_blocking({
+        let path = path.clone();
+        move || dir_size(&path)
+    })
+    .await
+    .unwrap()
+    .with_context(|| format!("failed measuring disk usage of `{}`", path.display()))?;
+    Ok(TreeDbInspection {
+        path,
+        disk_usage_bytes,
+        state,
+        recovery_failure,
+    })
+}
+
+/// Removes the Merkle tree RocksDB at `path`, so that the tree is initialized from scratch (e.g., recovered
+/// from a snapshot) on the next node start. This is the way to restart recovery that has failed with a root hash
+/// mismatch (see [`RecoveryFailure`]) once the cause of the failure is fixed.
+///
+/// The tree must not be used by a running node.
+///
+/// # Errors
+///
+/// Returns an error if the database doesn't exist or cannot be removed.
+pub async fn reset_tree_db(path: PathBuf) -> anyhow::Result<()> {
+    // Check that the path contains a tree RocksDB; this also fails if the path doesn't exist.
+    let db = open_db_read_only(path.clone()).await?;
+    drop(db);
+    tokio::task::spawn_blocking(move || remove_db_dir_sync(&path))
+        .await
+        .context("panicked removing Merkle tree RocksDB")?
+}
+
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+impl AsyncTreeRecovery {
+    /// Inspects recovery progress. This only reads from the tree, so it can be used for a tree
+    /// opened in the read-only mode.
+    pub async fn inspect(&mut self) -> anyhow::Result<RecoveryInspection> {
+        let recovered_version = self.recovered_version();
+        let key_range = self.key_range().await;
+        let key_span = key_range.as_ref().map_or(0.0, |range| {
+            RecoveryInspection::key_span(*range.start(), *range.end())
+        });
+
+        let mut incomplete_chunks = vec![];
+        for (journal_key, raw_entry) in self.journal_entries().await {
+            anyhow::ensure!(
+                journal_key.len() == 32,
+                "unexpected recovery journal key length: {}",
+                journal_key.len()
+            );
+            let start_key = H256::from_slice(&journal_key);
+            let entry = ChunkJournalEntry::deserialize(&raw_entry).with_context(|| {
+                format!("Failed deserializing recovery journal entry for chunk starting at {start_key:?}")
+            })?;
+            // Entries for another recovered version are ignored during recovery as well.
+            if entry.recovered_version == recovered_version {
+                incomplete_chunks.push(IncompleteChunk {
+                    start_key,
+                    last_applied_key: entry.last_applied_key.map(u256_to_h256),
+                });
+            }
+        }
+
+        Ok(RecoveryInspection {
+            recovered_version,
+            root_hash: self.root_hash().await,
+            leaf_count: self.leaf_count().await,
+            least_key: key_range.as_ref().map(|range| u256_to_h256(*range.start())),
+            greatest_key: key_range.map(|range| u256_to_h256(*range.end())),
+            key_span,
+            incomplete_chunks,
+            may_have_lost_writes: self.may_have_lost_writes(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computing_key_span() {
+        assert_eq!(RecoveryInspection::key_span(Key::zero(), Key::zero()), 0.0);
+        assert_eq!(RecoveryInspection::key_span(Key::MAX, Key::MAX), 0.0);
+        let span = RecoveryInspection::key_span(Key::zero(), Key::MAX);
+        assert!((span - 1.0).abs() < 1e-9, "{span}");
+        let span = RecoveryInspection::key_span(Key::MAX / 4, Key::MAX / 4 * 3);
+        assert!((span - 0.5).abs() < 1e-9, "{span}");
+    }
+}
@@ -0,0 +1,665 @@
+//! Full verification of a Merkle tree against the Postgres snapshot it was recovered from.
+//!
+//! Unlike sampled verification performed after recovery, the integrity check covers the entire key space.
+//! It consists of two passes:
+//!
+//! 1. Tree leaves are iterated in the ascending key order in pages. For each page, entries with the same hashed keys
+//!    are loaded from Postgres, which detects extra and mismatched tree entries.
+//! 2. If Postgres contains more snapshot entries than were matched by tree entries, snapshot entries are streamed
+//!    from Postgres in the hashed key order (which differs from the tree key order) and looked up in the tree
+//!    to find entries missing from the tree. The pass stops once all missing entries are found.
+//!
+//! The check progress (the current pass and the last verified hashed key) is persisted in the tree manifest
+//! after each page, so an interrupted check is resumed on the next run. Persisted progress is discarded
+//! if the tree or the snapshot has changed in the meantime.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use zksync_dal::ConnectionPool;
+use zksync_health_check::{Health, HealthStatus, HealthUpdater};
+use zksync_merkle_tree::TreeEntry;
+use zksync_types::{MiniblockNumber, H256, U256};
+use zksync_utils::u256_to_h256;
+
+use super::{get_recovery_target, hashed_key, ready_tree_recovered_version, SnapshotEntryPages};
+use crate::metadata_calculator::{
+    helpers::AsyncTree,
+    metrics::{RecoveryStage, RECOVERY_METRICS},
+    MetadataCalculatorRecoveryConfig,
+};
+
+/// Number of entries verified in a single page.
+const PAGE_SIZE: usize = 10_000;
+/// Maximum number of discrepancies logged during a single integrity check run. All discrepancies are still
+/// reported to [`HandleIntegrityCheckEvent`] handlers.
+const MAX_LOGGED_DISCREPANCIES: u64 = 100;
+
+/// Handler of events emitted by the Merkle tree integrity check. Besides the built-in handler updating the tree
+/// health check, handlers can be registered from outside the module using
+/// [`MetadataCalculator::register_integrity_check_listener()`]. Panics in registered listeners are caught and logged.
+///
+/// [`MetadataCalculator::register_integrity_check_listener()`]: crate::metadata_calculator::MetadataCalculator::register_integrity_check_listener
+pub trait HandleIntegrityCheckEvent: fmt::Debug + Send + Sync {
+    /// Called when the check starts or is resumed. `phase` is the phase the check starts from, and `stats`
+    /// are accumulated before the check was resumed; they are zeroed if the check starts from scratch.
+    fn check_started(
+        &mut self,
+        _snapshot_miniblock: MiniblockNumber,
+        _phase: IntegrityCheckPhase,
+        _stats: IntegrityCheckStats,
+    ) {
+        // Default implementation does nothing
+    }
+
+    /// Called for each discrepancy between the tree and Postgres.
+    fn discrepancy_found(&mut self, _discrepancy: &EntryDiscrepancy) {
+        // Default implementation does nothing
+    }
+
+    /// Called after a page of entries is verified and the check progress is persisted. `last_key` is the last
+    /// verified hashed key.
+    fn page_verified(
+        &mut self,
+        _phase: IntegrityCheckPhase,
+        _last_key: H256,
+        _stats: IntegrityCheckStats,
+    ) {
+        // Default implementation does nothing
+    }
+
+    /// Called when the check is finished. Not called if the check is interrupted.
+    fn check_finished(&mut self, _stats: IntegrityCheckStats) {
+        // Default implementation does nothing
+    }
+}
+
+/// Pass of the Merkle tree integrity check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityCheckPhase {
+    /// Iterating over tree entries and comparing them with Postgres.
+    Tree,
+    /// Iterating over Postgres entries to find entries missing from the tree.
+    Postgres,
+}
+
+/// Kind of [`EntryDiscrepancy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscrepancyKind {
+    /// Snapshot entry is missing from the tree.
+    Missing,
+    /// Tree entry is not present in the snapshot.
+    Extra,
+    /// Tree entry differs from the snapshot entry with the same key (by value or leaf index).
+    Mismatched,
+}
+
+/// Discrepancy between the tree and the Postgres snapshot found by the integrity check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryDiscrepancy {
+    pub kind: DiscrepancyKind,
+    pub hashed_key: H256,
+    /// Snapshot entry; `None` for [`DiscrepancyKind::Extra`].
+    pub postgres_entry: Option<TreeEntry>,
+    /// Tree entry; `None` for [`DiscrepancyKind::Missing`].
+    pub tree_entry: Option<TreeEntry>,
+}
+
+/// Statistics of the Merkle tree integrity check. If the check was resumed after an interruption, statistics
+/// include the work done before the interruption.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntegrityCheckStats {
+    /// Number of tree entries compared with Postgres.
+    pub verified_tree_entries: u64,
+    /// Number of Postgres entries looked up in the tree while searching for missing entries.
+    pub verified_postgres_entries: u64,
+    pub missing_entry_count: u64,
+    pub extra_entry_count: u64,
+    pub mismatched_entry_count: u64,
+}
+
+impl IntegrityCheckStats {
+    /// Returns the total number of found discrepancies.
+    pub fn discrepancy_count(&self) -> u64 {
+        self.missing_entry_count + self.extra_entry_count + self.mismatched_entry_count
+    }
+
+    fn observe(&mut self, kind: DiscrepancyKind) {
+        match kind {
+            DiscrepancyKind::Missing => self.missing_entry_count += 1,
+            DiscrepancyKind::Extra => self.extra_entry_count += 1,
+            DiscrepancyKind::Mismatched => self.mismatched_entry_count += 1,
+        }
+    }
+
+    /// Returns the number of snapshot entries expected to be missing from the tree, given that the snapshot
+    /// contains `log_count` entries.
+    fn expected_missing_entries(&self, log_count: u64) -> u64 {
+        let matched_entries = self.verified_tree_entries - self.extra_entry_count;
+        log_count.saturating_sub(matched_entries)
+    }
+}
+
+/// Options for [`AsyncTree::verify_against_snapshot()`].
+#[derive(Debug)]
+pub(super) struct IntegrityCheckOptions<'a> {
+    /// Number of entries verified in a single page. The check progress is persisted after each page.
+    pub page_size: usize,
+    pub events: Box<dyn HandleIntegrityCheckEvent + 'a>,
+}
+
+/// Progress of the integrity check persisted in the tree manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IntegrityCheckProgress {
+    snapshot_miniblock: MiniblockNumber,
+    /// Root hash of the checked tree. Used to detect tree changes between check runs.
+    root_hash: H256,
+    phase: IntegrityCheckPhase,
+    last_verified_key: Option<H256>,
+    stats: IntegrityCheckStats,
+}
+
+impl IntegrityCheckProgress {
+    /// Custom manifest tag storing the serialized progress.
+    const TAG: &'static str = "integrity_check.progress";
+
+    fn new(snapshot_miniblock: MiniblockNumber, root_hash: H256) -> Self {
+        Self {
+            snapshot_miniblock,
+            root_hash,
+            phase: IntegrityCheckPhase::Tree,
+            last_verified_key: None,
+            stats: IntegrityCheckStats::default(),
+        }
+    }
+}
+
+impl AsyncTree {
+    /// Verifies all tree entries against the Postgres snapshot for `snapshot_miniblock` and finds snapshot entries
+    /// missing from the tree. The tree must not be updated after recovery; otherwise, all updated entries will be
+    /// reported as discrepancies.
+    ///
+    /// Returns `Ok(None)` if the check was interrupted by a stop signal; the check is resumed on the next call.
+    /// Discrepancies are reported via `options.events` and are not considered errors.
+    pub(super) async fn verify_against_snapshot(
+        &mut self,
+        pool: &ConnectionPool,
+        snapshot_miniblock: MiniblockNumber,
+        options: IntegrityCheckOptions<'_>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<IntegrityCheckStats>> {
+        let check_latency = RECOVERY_METRICS.latency[&RecoveryStage::IntegrityCheck].start();
+        let root_hash = self.root_hash();
+        let mut progress = self
+            .integrity_check_progress(snapshot_miniblock, root_hash)
+            .unwrap_or_else(|| IntegrityCheckProgress::new(snapshot_miniblock, root_hash));
+        if let Some(last_key) = progress.last_verified_key {
+            tracing::info!(
+                "Resuming Merkle tree integrity check against snapshot for miniblock #{snapshot_miniblock} \
+                 in {:?} phase after key {last_key:?}",
+                progress.phase
+            );
+        } else {
+            tracing::info!(
+                "Starting Merkle tree integrity check against snapshot for miniblock #{snapshot_miniblock}"
+            );
+        }
+
+        let mut check = IntegrityCheck {
+            pool,
+            snapshot_miniblock,
+            page_size: options.page_size.max(1),
+            events: options.events,
+            logged_discrepancies: 0,
+        };
+        check
+            .events
+            .check_started(snapshot_miniblock, progress.phase, progress.stats);
+
+        if progress.phase == IntegrityCheckPhase::Tree {
+            if !check
+                .verify_tree_entries(self, &mut progress, stop_receiver)
+                .await?
+            {
+                return Ok(None);
+            }
+            progress.phase = IntegrityCheckPhase::Postgres;
+            progress.last_verified_key = None;
+        }
+
+        let mut storage = pool.access_storage().await?;
+        let log_count = storage
+            .storage_logs_dal()
+            .count_miniblock_storage_logs(snapshot_miniblock)
+            .await
+            .with_context(|| {
+                format!("Failed getting number of logs for miniblock #{snapshot_miniblock}")
+            })?;
+        drop(storage);
+        if !check
+            .find_missing_entries(self, &mut progress, log_count, stop_receiver)
+            .await?
+        {
+            return Ok(None);
+        }
+
+        self.save_integrity_check_progress(None).await;
+        let stats = progress.stats;
+        let check_latency = check_latency.observe();
+        tracing::info!(
+            "Finished Merkle tree integrity check against snapshot for miniblock #{snapshot_miniblock} \
+             in {check_latency:?} (latency of the last run): {stats:?}"
+        );
+        check.events.check_finished(stats);
+        Ok(Some(stats))
+    }
+
+    fn integrity_check_progress(
+        &self,
+        snapshot_miniblock: MiniblockNumber,
+        root_hash: H256,
+    ) -> Option<IntegrityCheckProgress> {
+        let tags = self.custom_tags();
+        let raw_progress = tags.get(IntegrityCheckProgress::TAG)?;
+        let progress: IntegrityCheckProgress = match serde_json::from_str(raw_progress) {
+            Ok(progress) => progress,
+            Err(err) => {
+                tracing::warn!(
+                    "Failed parsing persisted integrity check progress {raw_progress:?}: {err}; \
+                     restarting the check from scratch"
+                );
+                return None;
+            }
+        };
+        if progress.snapshot_miniblock != snapshot_miniblock || progress.root_hash != root_hash {
+            tracing::info!(
+                "Persisted integrity check progress {progress:?} is obsolete (snapshot miniblock: \
+                 #{snapshot_miniblock}, tree root hash: {root_hash:?}); restarting the check from scratch"
+            );
+            return None;
+        }
+        Some(progress)
+    }
+
+    async fn save_integrity_check_progress(&mut self, progress: Option<&IntegrityCheckProgress>) {
+        let raw_progress = progress.map(|progress| {
+            serde_json::to_string(progress).expect("failed serializing integrity check progress")
+        });
+        self.update_custom_tags(|tags| {
+            if let Some(raw_progress) = raw_progress {
+                tags.insert(IntegrityCheckProgress::TAG.to_owned(), raw_progress);
+            } else {
+                tags.remove(IntegrityCheckProgress::TAG);
+            }
+        });
+        self.save().await;
+    }
+}
+
+/// State of a single integrity check run.
+#[derive(Debug)]
+struct IntegrityCheck<'a> {
+    pool: &'a ConnectionPool,
+    snapshot_miniblock: MiniblockNumber,
+    page_size: usize,
+    events: Box<dyn HandleIntegrityCheckEvent + 'a>,
+    logged_discrepancies: u64,
+}
+
+impl IntegrityCheck<'_> {
+    fn report_discrepancy(
+        &mut self,
+        stats: &mut IntegrityCheckStats,
+        discrepancy: EntryDiscrepancy,
+    ) {
+        stats.observe(discrepancy.kind);
+        if self.logged_discrepancies < MAX_LOGGED_DISCREPANCIES {
+            tracing::warn!("Merkle tree integrity check found discrepancy: {discrepancy:?}");
+            self.logged_discrepancies += 1;
+        }
+        self.events.discrepancy_found(&discrepancy);
+    }
+
+    async fn page_verified(
+        &mut self,
+        tree: &mut AsyncTree,
+        progress: &mut IntegrityCheckProgress,
+        last_key: H256,
+    ) {
+        progress.last_verified_key = Some(last_key);
+        tree.save_integrity_check_progress(Some(progress)).await;
+        self.events
+            .page_verified(progress.phase, last_key, progress.stats);
+    }
+
+    /// Iterates over tree entries and compares them with Postgres. Returns `false` if interrupted by a stop signal.
+    async fn verify_tree_entries(
+        &mut self,
+        tree: &mut AsyncTree,
+        progress: &mut IntegrityCheckProgress,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<bool> {
+        let start_key = match progress.last_verified_key {
+            Some(last_key) => {
+                let last_key = U256::from_little_endian(last_key.as_bytes());
+                let Some(start_key) = last_key.checked_add(U256::one()) else {
+                    return Ok(true); // the last verified key is the greatest possible key
+                };
+                start_key
+            }
+            None => U256::zero(),
+        };
+        let mut key_range = Some(u256_to_h256(start_key)..=H256::repeat_byte(0xff));
+
+        while let Some(range) = key_range {
+            if *stop_receiver.borrow() {
+                tracing::info!("Stop signal received during Merkle tree integrity check");
+                return Ok(false);
+            }
+            let page = tree.entries_range(range, self.page_size).await;
+            key_range = page.continuation;
+            let Some(last_entry) = page.entries.last() else {
+                break;
+            };
+            let last_key = hashed_key(&last_entry.key);
+
+            let hashed_keys: Vec<_> = page
+                .entries
+                .iter()
+                .map(|entry| hashed_key(&entry.key))
+                .collect();
+            let mut storage = self.pool.access_storage().await?;
+            let postgres_entries = storage
+                .storage_logs_dal()
+                .get_tree_entries_for_hashed_keys(self.snapshot_miniblock, &hashed_keys)
+                .await
+                .context("Failed getting snapshot entries from Postgres")?;
+            drop(storage);
+            let postgres_entries: HashMap<_, _> = postgres_entries
+                .into_iter()
+                .map(|entry| {
+                    let entry = TreeEntry::new(entry.key, entry.leaf_index, entry.value);
+                    (entry.key, entry)
+                })
+                .collect();
+
+            for tree_entry in page.entries {
+                progress.stats.verified_tree_entries += 1;
+                let postgres_entry = postgres_entries.get(&tree_entry.key).copied();
+                let kind = match postgres_entry {
+                    None => DiscrepancyKind::Extra,
+                    Some(entry) if entry != tree_entry => DiscrepancyKind::Mismatched,
+                    Some(_) => continue,
+                };
+                let discrepancy = EntryDiscrepancy {
+                    kind,
+                    hashed_key: hashed_key(&tree_entry.key),
+                    postgres_entry,
+                    tree_entry: Some(tree_entry),
+                };
+                self.report_discrepancy(&mut progress.stats, discrepancy);
+            }
+            self.page_verified(tree, progress, last_key).await;
+        }
+        Ok(true)
+    }
+
+    /// Streams snapshot entries from Postgres and looks them up in the tree until all entries missing
+    /// from the tree are found. Returns `false` if interrupted by a stop signal.
+    async fn find_missing_entries(
+        &mut self,
+        tree: &mut AsyncTree,
+        progress: &mut IntegrityCheckProgress,
+        log_count: u64,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<bool> {
+        let mut pages = SnapshotEntryPages::new(
+            self.snapshot_miniblock,
+            H256::zero()..=H256::repeat_byte(0xff),
+            self.page_size,
+        )
+        .resume_after(progress.last_verified_key);
+        while progress.stats.missing_entry_count
+            < progress.stats.expected_missing_entries(log_count)
+        {
+            if *stop_receiver.borrow() {
+                tracing::info!("Stop signal received during Merkle tree integrity check");
+                return Ok(false);
+            }
+
+            let mut storage = self.pool.access_storage().await?;
+            let entries = pages
+                .next_page(&mut storage)
+                .await
+                .context("Failed getting snapshot entries from Postgres")?;
+            drop(storage);
+            let Some(entries) = entries else {
+                break;
+            };
+            let last_key = hashed_key(&entries[entries.len() - 1].key);
+
+            let keys = entries.iter().map(|entry| entry.key).collect();
+            let tree_entries = tree
+                .entries_with_proofs(keys)
+                .await
+                .context("Failed getting entries from the tree")?;
+            for (entry, tree_entry) in entries.into_iter().zip(tree_entries) {
+                progress.stats.verified_postgres_entries += 1;
+                if tree_entry.base.is_empty() {
+                    let discrepancy = EntryDiscrepancy {
+                        kind: DiscrepancyKind::Missing,
+                        hashed_key: hashed_key(&entry.key),
+                        postgres_entry: Some(entry),
+                        tree_entry: None,
+                    };
+                    self.report_discrepancy(&mut progress.stats, discrepancy);
+                }
+            }
+            self.page_verified(tree, progress, last_key).await;
+        }
+        Ok(true)
+    }
+}
+
+/// [`HandleIntegrityCheckEvent`] implementation forwarding events to the built-in handler and to registered
+/// listeners. The built-in handler is called first; panics in listeners are caught and logged.
+#[derive(Debug)]
+struct IntegrityCheckEventFanOut<'a> {
+    inner: Box<dyn HandleIntegrityCheckEvent + 'a>,
+    listeners: Vec<Box<dyn HandleIntegrityCheckEvent>>,
+}
+
+impl IntegrityCheckEventFanOut<'_> {
+    fn notify_listeners(
+        &mut self,
+        event: &'static str,
+        call: impl Fn(&mut dyn HandleIntegrityCheckEvent),
+    ) {
+        call(self.inner.as_mut());
+        for listener in &mut self.listeners {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| call(listener.as_mut())));
+            if result.is_err() {
+                tracing::error!(
+                    "Integrity check listener {listener:?} panicked handling `{event}` event"
+                );
+            }
+        }
+    }
+}
+
+impl HandleIntegrityCheckEvent for IntegrityCheckEventFanOut<'_> {
+    fn check_started(
+        &mut self,
+        snapshot_miniblock: MiniblockNumber,
+        phase: IntegrityCheckPhase,
+        stats: IntegrityCheckStats,
+    ) {
+        self.notify_listeners("check_started", |listener| {
+            listener.check_started(snapshot_miniblock, phase, stats);
+        });
+    }
+
+    fn discrepancy_found(&mut self, discrepancy: &EntryDiscrepancy) {
+        self.notify_listeners("discrepancy_found", |listener| {
+            listener.discrepancy_found(discrepancy);
+        });
+    }
+
+    fn page_verified(
+        &mut self,
+        phase: IntegrityCheckPhase,
+        last_key: H256,
+        stats: IntegrityCheckStats,
+    ) {
+        self.notify_listeners("page_verified", |listener| {
+            listener.page_verified(phase, last_key, stats);
+        });
+    }
+
+    fn check_finished(&mut self, stats: IntegrityCheckStats) {
+        self.notify_listeners("check_finished", |listener| {
+            listener.check_finished(stats);
+        });
+    }
+}
+
+/// Information about the integrity check reported via the health check.
+#[derive(Debug, Serialize)]
+struct IntegrityCheckInfo {
+    mode: &'static str, // "integrity_check" to distinguish from other tree health details
+    snapshot_miniblock: MiniblockNumber,
+    phase: Option<IntegrityCheckPhase>,
+    last_verified_key: Option<H256>,
+    is_finished: bool,
+    #[serde(flatten)]
+    stats: IntegrityCheckStats,
+}
+
+/// [`HandleIntegrityCheckEvent`] implementation publishing the check progress to the tree health check.
+#[derive(Debug)]
+struct IntegrityCheckHealthUpdater<'a> {
+    inner: &'a HealthUpdater,
+    snapshot_miniblock: MiniblockNumber,
+}
+
+impl IntegrityCheckHealthUpdater<'_> {
+    fn update(
+        &self,
+        phase: Option<IntegrityCheckPhase>,
+        last_verified_key: Option<H256>,
+        stats: IntegrityCheckStats,
+    ) {
+        let is_finished = phase.is_none();
+        let status = if is_finished && stats.discrepancy_count() > 0 {
+            HealthStatus::Affected
+        } else {
+            HealthStatus::NotReady
+        };
+        let health = Health::from(status).with_details(IntegrityCheckInfo {
+            mode: "integrity_check",
+            snapshot_miniblock: self.snapshot_miniblock,
+            phase,
+            last_verified_key,
+            is_finished,
+            stats,
+        });
+        self.inner.update(health);
+    }
+}
+
+impl HandleIntegrityCheckEvent for IntegrityCheckHealthUpdater<'_> {
+    fn check_started(
+        &mut self,
+        _snapshot_miniblock: MiniblockNumber,
+        phase: IntegrityCheckPhase,
+        stats: IntegrityCheckStats,
+    ) {
+        self.update(Some(phase), None, stats);
+    }
+
+    fn page_verified(
+        &mut self,
+        phase: IntegrityCheckPhase,
+        last_key: H256,
+        stats: IntegrityCheckStats,
+    ) {
+        self.update(Some(phase), Some(last_key), stats);
+    }
+
+    fn check_finished(&mut self, stats: IntegrityCheckStats) {
+        self.update(None, None, stats);
+    }
+}
+
+/// Verifies the entire `tree` against the snapshot it was recovered from (see [`AsyncTree::verify_against_snapshot()`]).
+/// This is used instead of the normal tree operation if configured. Returns an error if discrepancies are found
+/// or the tree cannot be checked.
+pub(crate) async fn run_integrity_check(
+    tree: &mut AsyncTree,
+    config: &MetadataCalculatorRecoveryConfig,
+    pool: &ConnectionPool,
+    stop_receiver: &watch::Receiver<bool>,
+    health_updater: &HealthUpdater,
+    listeners: Vec<Box<dyn HandleIntegrityCheckEvent>>,
+) -> anyhow::Result<()> {
+    let target = get_recovery_target(config, pool, ready_tree_recovered_version(tree)).await?.context(
+        "Postgres doesn't contain a snapshot; Merkle tree integrity can only be checked for a recovered tree",
+    )?;
+    let snapshot_recovery = &target.snapshot_recovery;
+    let l1_batch = snapshot_recovery.l1_batch_number;
+    let next_l1_batch = tree.next_l1_batch_number();
+    anyhow::ensure!(
+        next_l1_batch == l1_batch + 1,
+        "Merkle tree integrity can only be checked against the snapshot L1 batch #{l1_batch} before the tree \
+         processes any L1 batches after it, but the next L1 batch for the tree is #{next_l1_batch}"
+    );
+    let root_hash = tree.root_hash();
+    if root_hash != snapshot_recovery.l1_batch_root_hash {
+        tracing::warn!(
+            "Merkle tree root hash {root_hash:?} differs from the snapshot root hash {:?}; the integrity check \
+             will report diverging entries",
+            snapshot_recovery.l1_batch_root_hash
+        );
+    }
+
+    let snapshot_miniblock = snapshot_recovery.miniblock_number;
+    let health_events = IntegrityCheckHealthUpdater {
+        inner: health_updater,
+        snapshot_miniblock,
+    };
+    let options = IntegrityCheckOptions {
+        page_size: PAGE_SIZE,
+        events: Box::new(IntegrityCheckEventFanOut {
+            inner: Box::new(health_events),
+            listeners,
+        }),
+    };
+    let stats = tree
+        .verify_against_snapshot(pool, snapshot_miniblock, options, stop_receiver)
+        .await?;
+    let Some(stats) = stats else {
+        tracing::info!(
+            "Merkle tree integrity check was interrupted; it will be resumed on the next run"
+        );
+        return Ok(());
+    };
+    anyhow::ensure!(
+        stats.discrepancy_count() == 0,
+        "Merkle tree doesn't match Postgres snapshot for miniblock #{snapshot_miniblock}: found {} missing, \
+         {} extra and {} mismatched entries",
+        stats.missing_entry_count,
+        stats.extra_entry_count,
+        stats.mismatched_entry_count
+    );
+    tracing::info!(
+        "Merkle tree matches Postgres snapshot for miniblock #{snapshot_miniblock}; stopping as configured"
+    );
+    Ok(())
+}
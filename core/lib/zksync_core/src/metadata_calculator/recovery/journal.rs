@@ -0,0 +1,82 @@
+//! Recovery journal allowing to resume recovery of a chunk mid-way.
+
+use std::ops;
+
+use anyhow::Context as _;
+use zksync_merkle_tree::Key;
+use zksync_types::H256;
+
+/// Entry in the recovery journal for a single chunk that is recovered in sub-chunks. Entries are keyed
+/// by the start of the chunk key range and are removed once the chunk is fully recovered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ChunkJournalEntry {
+    /// Recovered tree version (i.e., the snapshot L1 batch number) the entry was written for.
+    pub recovered_version: u64,
+    /// Greatest key among applied sub-chunks, or `None` if no sub-chunks were applied yet.
+    pub last_applied_key: Option<Key>,
+}
+
+impl ChunkJournalEntry {
+    pub fn journal_key(key_chunk: &ops::RangeInclusive<H256>) -> Vec<u8> {
+        key_chunk.start().as_bytes().to_vec()
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(40);
+        buffer.extend_from_slice(&self.recovered_version.to_be_bytes());
+        if let Some(key) = self.last_applied_key {
+            let mut key_bytes = [0_u8; 32];
+            key.to_big_endian(&mut key_bytes);
+            buffer.extend_from_slice(&key_bytes);
+        }
+        buffer
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            bytes.len() == 8 || bytes.len() == 40,
+            "unexpected recovery journal entry length: {}",
+            bytes.len()
+        );
+        let (version_bytes, key_bytes) = bytes.split_at(8);
+        let recovered_version = u64::from_be_bytes(
+            version_bytes
+                .try_into()
+                .context("version must have 8 bytes")?,
+        );
+        let last_applied_key = (!key_bytes.is_empty()).then(|| Key::from_big_endian(key_bytes));
+        Ok(Self {
+            recovered_version,
+            last_applied_key,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_entry_serialization() {
+        let entries = [
+            ChunkJournalEntry {
+                recovered_version: 42,
+                last_applied_key: None,
+            },
+            ChunkJournalEntry {
+                recovered_version: 1,
+                last_applied_key: Some(Key::from(0x1234_5678)),
+            },
+            ChunkJournalEntry {
+                recovered_version: u64::MAX,
+                last_applied_key: Some(Key::MAX),
+            },
+        ];
+        for entry in entries {
+            let bytes = entry.serialize();
+            assert_eq!(ChunkJournalEntry::deserialize(&bytes).unwrap(), entry);
+        }
+
+        ChunkJournalEntry::deserialize(&[0; 9]).unwrap_err();
+    }
+}
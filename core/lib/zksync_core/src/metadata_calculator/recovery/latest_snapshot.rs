@@ -0,0 +1,92 @@
+//! Detecting that the snapshot the tree is recovered from is superseded by a newer snapshot in Postgres.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use zksync_dal::ConnectionPool;
+use zksync_types::L1BatchNumber;
+
+use super::get_snapshot_l1_batch;
+
+#[derive(Debug, Default)]
+struct CheckState {
+    last_checked_at: Option<Instant>,
+    superseded_by: Option<L1BatchNumber>,
+}
+
+/// Check whether Postgres contains a snapshot newer than the one the tree is recovered from. Chunk tasks perform
+/// the check before starting a chunk; Postgres is queried at most once per the check interval, and a detected
+/// newer snapshot is cached, so that the check is cheap.
+#[derive(Debug)]
+pub(super) struct LatestSnapshotCheck<'a> {
+    pool: &'a ConnectionPool,
+    recovered_version: u64,
+    interval: Duration,
+    state: Mutex<CheckState>,
+}
+
+impl<'a> LatestSnapshotCheck<'a> {
+    pub fn new(pool: &'a ConnectionPool, recovered_version: u64, interval: Duration) -> Self {
+        Self {
+            pool,
+            recovered_version,
+            interval,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns the L1 batch of a newer snapshot detected by previous checks, if any.
+    pub fn superseded_by(&self) -> Option<L1BatchNumber> {
+        self.state
+            .lock()
+            .expect("latest snapshot check state is poisoned")
+            .superseded_by
+    }
+
+    /// Checks whether a newer snapshot is in Postgres if the check interval has elapsed since the previous check.
+    /// Returns the L1 batch of the newer snapshot, if any. Errors querying Postgres are logged and don't abort
+    /// recovery, since the recovered snapshot is still valid.
+    pub async fn check(&self) -> Option<L1BatchNumber> {
+        {
+            let mut state = self
+                .state
+                .lock()
+                .expect("latest snapshot check state is poisoned");
+            if state.superseded_by.is_some() {
+                return state.superseded_by;
+            }
+            let now = Instant::now();
+            let is_due = state.last_checked_at.map_or(true, |checked_at| {
+                now.duration_since(checked_at) >= self.interval
+            });
+            if !is_due {
+                return None;
+            }
+            // Claim the check, so that concurrent chunk tasks don't query Postgres at the same time.
+            state.last_checked_at = Some(now);
+        }
+
+        let latest_l1_batch = match get_snapshot_l1_batch(self.pool).await {
+            Ok(l1_batch) => l1_batch,
+            Err(err) => {
+                tracing::warn!("Failed checking for a newer snapshot in Postgres: {err:#}");
+                return None;
+            }
+        };
+        let latest_l1_batch =
+            latest_l1_batch.filter(|l1_batch| u64::from(l1_batch.0) > self.recovered_version)?;
+        tracing::warn!(
+            "Postgres contains a snapshot for L1 batch #{latest_l1_batch}, which is newer than the snapshot \
+             for L1 batch #{} the Merkle tree is recovered from; no new chunks will be started",
+            self.recovered_version
+        );
+        let mut state = self
+            .state
+            .lock()
+            .expect("latest snapshot check state is poisoned");
+        state.superseded_by = Some(latest_l1_batch);
+        state.superseded_by
+    }
+}
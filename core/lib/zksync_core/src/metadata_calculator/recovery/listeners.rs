@@ -0,0 +1,258 @@
+//! Fan-out of recovery events to listeners registered from outside the module
+//! (see [`MetadataCalculator::register_recovery_listener()`]).
+//!
+//! [`MetadataCalculator::register_recovery_listener()`]: crate::metadata_calculator::MetadataCalculator::register_recovery_listener
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::{
+    future::{self, BoxFuture},
+    FutureExt as _,
+};
+
+use super::{
+    ChunkDescriptor, DiskSpaceEstimate, HandleRecoveryEvent, RecoveryError, RecoveryFinalizeStage,
+    RecoveryStallReport, RecoveryStats,
+};
+
+/// Default timeout for a single async event handled by a registered listener.
+const DEFAULT_LISTENER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// [`HandleRecoveryEvent`] implementation forwarding events to the built-in handler and to registered listeners.
+/// The built-in handler is called first. Unlike the built-in handler, listeners are isolated from recovery:
+/// a panic in a listener is caught and logged, and async events are awaited for all listeners concurrently,
+/// with each call limited by a timeout.
+#[derive(Debug)]
+pub(super) struct RecoveryEventFanOut<'a> {
+    inner: Box<dyn HandleRecoveryEvent + 'a>,
+    listeners: &'a [Box<dyn HandleRecoveryEvent>],
+    listener_timeout: Duration,
+}
+
+impl<'a> RecoveryEventFanOut<'a> {
+    pub fn new(
+        inner: Box<dyn HandleRecoveryEvent + 'a>,
+        listeners: &'a [Box<dyn HandleRecoveryEvent>],
+    ) -> Self {
+        Self {
+            inner,
+            listeners,
+            listener_timeout: DEFAULT_LISTENER_TIMEOUT,
+        }
+    }
+
+    #[cfg(test)]
+    fn with_listener_timeout(mut self, timeout: Duration) -> Self {
+        self.listener_timeout = timeout;
+        self
+    }
+
+    fn notify_listeners(&self, event: &'static str, call: impl Fn(&dyn HandleRecoveryEvent)) {
+        for listener in self.listeners {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| call(listener.as_ref())));
+            if result.is_err() {
+                tracing::error!("Recovery listener {listener:?} panicked handling `{event}` event");
+            }
+        }
+    }
+
+    async fn notify_listeners_async<'s>(
+        &'s self,
+        event: &'static str,
+        call: impl Fn(&'s dyn HandleRecoveryEvent) -> BoxFuture<'s, ()>,
+    ) {
+        let timeout = self.listener_timeout;
+        let calls = self.listeners.iter().map(|listener| {
+            let call = AssertUnwindSafe(call(listener.as_ref())).catch_unwind();
+            async move {
+                match tokio::time::timeout(timeout, call).await {
+                    Ok(Ok(())) => { /* the listener has handled the event successfully */ }
+                    Ok(Err(_)) => {
+                        tracing::error!(
+                            "Recovery listener {listener:?} panicked handling `{event}` event"
+                        );
+                    }
+                    Err(_) => {
+                        tracing::warn!(
+                            "Recovery listener {listener:?} timed out handling `{event}` event after {timeout:?}"
+                        );
+                    }
+                }
+            }
+        });
+        future::join_all(calls).await;
+    }
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for RecoveryEventFanOut<'_> {
+    fn recovery_started(
+        &self,
+        chunk_count: usize,
+        recovered_chunk_count: usize,
+        recovered_entry_count: u64,
+    ) {
+        self.inner
+            .recovery_started(chunk_count, recovered_chunk_count, recovered_entry_count);
+        self.notify_listeners("recovery_started", |listener| {
+            listener.recovery_started(chunk_count, recovered_chunk_count, recovered_entry_count);
+        });
+    }
+
+    fn disk_space_checked(&self, estimate: DiskSpaceEstimate) {
+        self.inner.disk_space_checked(estimate);
+        self.notify_listeners("disk_space_checked", |listener| {
+            listener.disk_space_checked(estimate);
+        });
+    }
+
+    async fn chunk_started(&self, chunk: &ChunkDescriptor) {
+        self.inner.chunk_started(chunk).await;
+        self.notify_listeners_async("chunk_started", |listener| listener.chunk_started(chunk))
+            .await;
+    }
+
+    async fn chunk_retried(&self, attempt: usize) {
+        self.inner.chunk_retried(attempt).await;
+        self.notify_listeners_async("chunk_retried", |listener| listener.chunk_retried(attempt))
+            .await;
+    }
+
+    async fn chunk_loaded(&self, chunk_id: usize) {
+        self.inner.chunk_loaded(chunk_id).await;
+        self.notify_listeners_async("chunk_loaded", |listener| listener.chunk_loaded(chunk_id))
+            .await;
+    }
+
+    async fn chunk_recovered(&self, chunk: &ChunkDescriptor) {
+        self.inner.chunk_recovered(chunk).await;
+        self.notify_listeners_async("chunk_recovered", |listener| {
+            listener.chunk_recovered(chunk)
+        })
+        .await;
+    }
+
+    async fn chunk_failed(&self, chunk: &ChunkDescriptor, err: &anyhow::Error) {
+        self.inner.chunk_failed(chunk, err).await;
+        self.notify_listeners_async("chunk_failed", |listener| listener.chunk_failed(chunk, err))
+            .await;
+    }
+
+    fn recovery_stalled(&self, report: &RecoveryStallReport) {
+        self.inner.recovery_stalled(report);
+        self.notify_listeners("recovery_stalled", |listener| {
+            listener.recovery_stalled(report);
+        });
+    }
+
+    fn recovery_paused(&self) {
+        self.inner.recovery_paused();
+        self.notify_listeners("recovery_paused", |listener| listener.recovery_paused());
+    }
+
+    fn recovery_resumed(&self) {
+        self.inner.recovery_resumed();
+        self.notify_listeners("recovery_resumed", |listener| listener.recovery_resumed());
+    }
+
+    fn finalize_stage_started(&self, stage: RecoveryFinalizeStage) {
+        self.inner.finalize_stage_started(stage);
+        self.notify_listeners("finalize_stage_started", |listener| {
+            listener.finalize_stage_started(stage);
+        });
+    }
+
+    fn recovery_finished(&self, stats: RecoveryStats) {
+        self.inner.recovery_finished(stats);
+        self.notify_listeners("recovery_finished", |listener| {
+            listener.recovery_finished(stats);
+        });
+    }
+
+    fn recovery_failed(&self, err: &RecoveryError) {
+        self.inner.recovery_failed(err);
+        self.notify_listeners("recovery_failed", |listener| listener.recovery_failed(err));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use zksync_types::H256;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct RecoveredChunkCounter(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl HandleRecoveryEvent for RecoveredChunkCounter {
+        async fn chunk_recovered(&self, _chunk: &ChunkDescriptor) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[derive(Debug)]
+    struct PanickingListener;
+
+    #[async_trait]
+    impl HandleRecoveryEvent for PanickingListener {
+        fn recovery_started(
+            &self,
+            _chunk_count: usize,
+            _recovered_chunk_count: usize,
+            _recovered_entry_count: u64,
+        ) {
+            panic!("recovery_started");
+        }
+
+        async fn chunk_recovered(&self, _chunk: &ChunkDescriptor) {
+            panic!("chunk_recovered");
+        }
+    }
+
+    #[derive(Debug)]
+    struct HangingListener;
+
+    #[async_trait]
+    impl HandleRecoveryEvent for HangingListener {
+        async fn chunk_recovered(&self, _chunk: &ChunkDescriptor) {
+            future::pending::<()>().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn misbehaving_listeners_do_not_break_event_handling() {
+        let inner_counter = RecoveredChunkCounter::default();
+        let inner_count = inner_counter.0.clone();
+        let listener_counter = RecoveredChunkCounter::default();
+        let listener_count = listener_counter.0.clone();
+        let listeners: Vec<Box<dyn HandleRecoveryEvent>> = vec![
+            Box::new(PanickingListener),
+            Box::new(HangingListener),
+            Box::new(listener_counter),
+        ];
+        let events = RecoveryEventFanOut::new(Box::new(inner_counter), &listeners)
+            .with_listener_timeout(Duration::from_millis(10));
+
+        events.recovery_started(3, 0, 0);
+        for index in 0..3 {
+            let chunk = ChunkDescriptor {
+                index,
+                key_range: H256::zero()..=H256::repeat_byte(0xff),
+                entry_count: Some(100),
+            };
+            events.chunk_recovered(&chunk).await;
+        }
+        assert_eq!(inner_count.load(Ordering::SeqCst), 3);
+        assert_eq!(listener_count.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,189 @@
+//! Accounting of memory occupied by chunk entries that are loaded, but not yet applied to the tree.
+
+use std::{mem, sync::Mutex};
+
+use tokio::sync::Notify;
+use zksync_merkle_tree::TreeEntry;
+
+use crate::metadata_calculator::metrics::RECOVERY_METRICS;
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    /// Total size of entries loaded by chunk loaders and not yet applied to the tree.
+    loaded_bytes: usize,
+    /// Memory reserved for chunks whose entries are being loaded.
+    reserved_bytes: usize,
+    /// Set once the tree applier has stopped; loaded entries may never be released after that.
+    is_closed: bool,
+}
+
+impl BudgetState {
+    fn used_bytes(&self) -> usize {
+        self.loaded_bytes + self.reserved_bytes
+    }
+}
+
+/// Tracks the total size of entries loaded by chunk loaders but not yet applied to the tree, and optionally
+/// enforces a soft cap on it. The cap is soft because the size of chunk entries isn't known before they are loaded;
+/// instead, a chunk reserves the estimated chunk size before loading its entries, and waits if the reservation
+/// would exceed the cap. A chunk is never blocked if no memory is used, so that recovery always makes progress.
+#[derive(Debug)]
+pub(super) struct LoadedEntriesBudget {
+    soft_cap: Option<usize>,
+    estimated_chunk_bytes: usize,
+    state: Mutex<BudgetState>,
+    released: Notify,
+}
+
+impl LoadedEntriesBudget {
+    pub fn new(soft_cap: Option<usize>, estimated_chunk_entry_count: u64) -> Self {
+        let estimated_chunk_entry_count = usize::try_from(estimated_chunk_entry_count)
+            .unwrap_or(usize::MAX)
+            .max(1);
+        RECOVERY_METRICS.loaded_entries_bytes.set(0);
+        Self {
+            soft_cap,
+            estimated_chunk_bytes: estimated_chunk_entry_count
+                .saturating_mul(mem::size_of::<TreeEntry>()),
+            state: Mutex::default(),
+            released: Notify::new(),
+        }
+    }
+
+    /// Returns the number of bytes occupied by `entries` for the purposes of accounting.
+    pub fn entries_bytes(entries: &[TreeEntry]) -> usize {
+        mem::size_of_val(entries)
+    }
+
+    /// Waits until the estimated size of a chunk fits into the soft cap and reserves it. The reservation
+    /// is released when the returned guard is dropped. If the soft cap is not set, returns immediately.
+    pub async fn reserve_chunk(&self) -> ChunkReservation<'_> {
+        let Some(soft_cap) = self.soft_cap else {
+            return ChunkReservation {
+                budget: self,
+                bytes: 0,
+            };
+        };
+
+        loop {
+            // Must be created before checking the state, so that releases in between aren't missed.
+            let released = self.released.notified();
+            {
+                let mut state = self.state.lock().expect("budget state is poisoned");
+                if state.is_closed {
+                    return ChunkReservation {
+                        budget: self,
+                        bytes: 0,
+                    };
+                }
+                let used_bytes = state.used_bytes();
+                if used_bytes == 0 || used_bytes + self.estimated_chunk_bytes <= soft_cap {
+                    state.reserved_bytes += self.estimated_chunk_bytes;
+                    return ChunkReservation {
+                        budget: self,
+                        bytes: self.estimated_chunk_bytes,
+                    };
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Accounts for loaded entries of the specified size.
+    pub fn track_loaded(&self, bytes: usize) {
+        let mut state = self.state.lock().expect("budget state is poisoned");
+        state.loaded_bytes += bytes;
+        RECOVERY_METRICS
+            .loaded_entries_bytes
+            .set(state.loaded_bytes);
+    }
+
+    /// Releases loaded entries of the specified size, e.g. after they are applied to the tree.
+    pub fn release_loaded(&self, bytes: usize) {
+        let mut state = self.state.lock().expect("budget state is poisoned");
+        state.loaded_bytes = state.loaded_bytes.saturating_sub(bytes);
+        RECOVERY_METRICS
+            .loaded_entries_bytes
+            .set(state.loaded_bytes);
+        drop(state);
+        self.released.notify_waiters();
+    }
+
+    /// Stops enforcing the soft cap. Must be called once the tree applier has stopped, so that chunk loaders
+    /// don't wait for loaded entries that will never be applied.
+    pub fn close(&self) {
+        self.state
+            .lock()
+            .expect("budget state is poisoned")
+            .is_closed = true;
+        self.released.notify_waiters();
+    }
+
+    fn release_reservation(&self, bytes: usize) {
+        let mut state = self.state.lock().expect("budget state is poisoned");
+        state.reserved_bytes = state.reserved_bytes.saturating_sub(bytes);
+        drop(state);
+        self.released.notify_waiters();
+    }
+}
+
+/// Reservation returned by [`LoadedEntriesBudget::reserve_chunk()`].
+#[derive(Debug)]
+pub(super) struct ChunkReservation<'a> {
+    budget: &'a LoadedEntriesBudget,
+    bytes: usize,
+}
+
+impl Drop for ChunkReservation<'_> {
+    fn drop(&mut self) {
+        if self.bytes > 0 {
+            self.budget.release_reservation(self.bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt as _;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reservations_are_limited_by_soft_cap() {
+        let chunk_bytes = 10 * mem::size_of::<TreeEntry>();
+        let budget = LoadedEntriesBudget::new(Some(chunk_bytes * 2), 10);
+
+        let first = budget.reserve_chunk().now_or_never().unwrap();
+        let second = budget.reserve_chunk().now_or_never().unwrap();
+        assert!(budget.reserve_chunk().now_or_never().is_none());
+
+        drop(first);
+        let _third = budget.reserve_chunk().now_or_never().unwrap();
+        drop(second);
+        budget.track_loaded(chunk_bytes);
+        assert!(budget.reserve_chunk().now_or_never().is_none());
+        budget.release_loaded(chunk_bytes);
+        budget.reserve_chunk().now_or_never().unwrap();
+    }
+
+    #[tokio::test]
+    async fn chunk_is_not_blocked_if_no_memory_is_used() {
+        let budget = LoadedEntriesBudget::new(Some(1), 1_000);
+        let reservation = budget.reserve_chunk().now_or_never().unwrap();
+        assert_eq!(reservation.bytes, budget.estimated_chunk_bytes);
+        assert!(budget.reserve_chunk().now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn closing_budget_unblocks_waiting_chunks() {
+        let budget = LoadedEntriesBudget::new(Some(1), 1_000);
+        budget.track_loaded(1_000);
+        let reservation = budget.reserve_chunk();
+        futures::pin_mut!(reservation);
+        assert!(reservation.as_mut().now_or_never().is_none());
+
+        budget.close();
+        let reservation = reservation.now_or_never().unwrap();
+        assert_eq!(reservation.bytes, 0);
+    }
+}
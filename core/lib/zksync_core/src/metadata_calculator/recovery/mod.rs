@@ -0,0 +1,5677 @@
+//! High-level recovery logic for the Merkle tree.
+//!
+//! # Overview
+//!
+//! Tree recovery works by checking Postgres and Merkle tree state on Metadata calculator initialization.
+//! Depending on these states, we can have one of the following situations:
+//!
+//! - Tree is recovering.
+//! - Tree is empty and should be recovered (i.e., there's a snapshot in Postgres).
+//! - Tree is empty and should be built from scratch.
+//! - Tree is ready for normal operation (i.e., it's not empty and is not recovering).
+//!
+//! The detected situation and the chosen action are logged and published in the tree health details
+//! (see [`StartupDecision`]).
+//!
+//! If the node is recovered from a snapshot concurrently with the tree startup, Postgres may contain a snapshot
+//! that is not fully applied yet. In this case, the tree waits until the snapshot is applied, polling Postgres
+//! and reporting the wait via the health check, before examining Postgres state any further.
+//!
+//! If recovery is necessary, it starts / resumes by loading the snapshot in chunks
+//! and feeding each chunk to the tree. Snapshot entries are loaded either from Postgres or, if an object store
+//! with snapshot storage log chunks is supplied, directly from the object store (see [`RecoveryEntrySource`]).
+//! For Postgres, chunks are hashed key ranges containing approximately
+//! the same number of snapshot entries; ranges are computed from a coarse histogram of hashed keys
+//! loaded from Postgres. For the object store, chunks coincide with the snapshot storage log chunks.
+//! Chunks are loaded concurrently since this is the most
+//! I/O-heavy operation; the concurrency is limited in order to not run into DB timeout errors.
+//! Loading and applying chunks form a pipeline: chunk loaders send loaded entries via a bounded queue
+//! to a single applier owning the tree, which applies them in the order of arrival. Thus, the next chunk
+//! is loaded while the previous one is being applied, even with unit concurrency.
+//! The concurrency limit is configurable and defaults to the number of connections in the supplied
+//! connection pool. Optionally, concurrency is adjusted based on the latency of loading chunk entries
+//! (see [`AdaptiveConcurrency`]). Before starting recovery in chunks, we filter out
+//! chunks that have already been recovered by checking if the first key in a chunk is present
+//! in the tree. (Note that for this to work, chunks **must** always be defined in the same way;
+//! to ensure this, the entry source and the desired chunk size are persisted in the tree manifest
+//! when recovery starts, and the key histogram only depends on the immutable snapshot data.) As an additional
+//! safeguard, the chunk plan (see [`ChunkPlan`]) is persisted as well and is checked when recovery is resumed;
+//! if the plan has changed, recovery fails unless it's configured to wipe the tree and start from scratch.
+//! If tree writes may have been lost in a crash, or if configured, the last key of each chunk is checked as well,
+//! so that partially written chunks are recovered again. A checked tree entry mismatching Postgres fails recovery,
+//! unless it's configured to repair such chunks by overwriting their entries from Postgres.
+//! Similarly, Postgres genesis (see [`PostgresGenesis`]) is persisted when recovery starts, so that the tree
+//! doesn't resume recovery against unrelated data if Postgres was re-initialized in the meantime.
+//! Wiping the tree removes its RocksDB directory; the directory is renamed before removal, so that a wipe
+//! interrupted by a crash is finished when the tree is opened next time. If configured, a tree that cannot be used
+//! as is (e.g., recovered to a different L1 batch) is wiped in the same way instead of failing.
+//!
+//! Optionally, each chunk can be applied to the tree in sub-chunks. In this case, progress within a chunk
+//! is tracked in the recovery journal (a dedicated RocksDB column family; see [`ChunkJournalEntry`]),
+//! so that a partially recovered chunk is resumed after the last applied sub-chunk if the node crashes.
+//! The journal is cleared when recovery is finalized. When recovering from Postgres, chunk entries can also
+//! be streamed in batches using keyset pagination on the hashed key instead of being loaded at once; in this case,
+//! each batch is sent to the applier as soon as it's loaded, which bounds memory usage during recovery.
+//! Progress within a streamed chunk is tracked in the journal in the same way as for sub-chunks.
+//!
+//! The recovery logic is fault-tolerant and supports graceful shutdown. If recovery is interrupted,
+//! recovery of the remaining chunks will continue when Metadata calculator is restarted. On a stop signal,
+//! new chunks are not started and loading chunk entries is aborted (for Postgres, the running query is cancelled),
+//! but chunks with already loaded entries are still applied to the tree. Similarly, an error recovering a chunk
+//! doesn't abort recovery of other chunks; once all chunks are processed, errors for all failed chunks are combined
+//! into a single error (see [`FailedChunks`]), which is also reported via the health check. While recovery
+//! is in progress, failed chunks are reported via the health check as soon as they fail (with the `affected` status).
+//! Failures to acquire a Postgres connection for loading chunks (e.g., because the pool is exhausted or Postgres
+//! is restarting) are retried with capped exponential backoff for a configurable duration before failing the chunk.
+//! Errors returned by recovery are structured (see [`RecoveryError`]); the machine-readable error kind
+//! is included in health check details when recovery fails.
+//! If no chunks finish loading for a while (e.g., because Postgres is locked up), this is reported
+//! by a watchdog running alongside chunk tasks (see [`RecoveryWatchdog`]).
+//!
+//! Optionally, the tree root hash is recorded after each recovered chunk together with the chunk key range
+//! and entry count in an append-only log next to the tree directory (see [`RecoveryFingerprintLog`]).
+//! The log is archived when recovery is finalized, so that it's possible to audit which data went into the tree.
+//! Recovered chunks and the overall recovery progress are also recorded in Postgres, so that recovery can be monitored
+//! using SQL (see [`RecoveryProgressTable`]); this is best-effort and doesn't influence recovery.
+//!
+//! Before recovering chunks, the number of snapshot entries in each remaining chunk is loaded from the entry source
+//! if it supports this (see [`RecoveryPlan`]). The plan is reported via metrics and is used to recover
+//! the largest chunks first if configured.
+//!
+//! Heavy snapshot queries (loading chunk entries and filtering recovered chunks) can be sent to a dedicated
+//! connection pool with long statement timeouts and / or to a Postgres read replica (see [`RecoveryPools`]).
+//! The replica is only used if it contains all snapshot storage logs (see [`SnapshotReplica`]); otherwise,
+//! or if a replica query fails, queries fall back to the primary.
+//! Optionally, entries of entire chunks are loaded using binary `COPY`, with a fallback to a conventional query
+//! if `COPY` fails (e.g., because of insufficient permissions).
+//!
+//! Before recovering chunks, the disk space required for the remaining chunks is estimated based on the number
+//! of snapshot entries and compared with the space available for the tree (see [`DiskSpaceCheck`]).
+//!
+//! Recovery performs basic sanity checks to ensure that the tree won't end up containing garbage data.
+//! E.g., it's checked that the tree always recovers from the same snapshot; that leaf indices in the snapshot
+//! are neither duplicated nor skipped (see [`verify_leaf_indices()`] and [`ChunkLeafIndexStats`]); that no key
+//! is applied by two chunks (see [`ChunkBoundaries`]); that the tree root hash after recovery matches one
+//! in the Postgres snapshot etc.
+//! Optionally, the recovered tree is additionally verified by comparing entries sampled from each chunk
+//! with Postgres (see [`verify_recovered_tree()`]).
+//!
+//! After recovery is finalized, the recovered tree can be exported as a RocksDB checkpoint together with
+//! a JSON descriptor (see [`export_recovered_tree()`]), so that it can be distributed to other nodes.
+//! If the node is restarted before the export is complete, the export is restarted on the next launch.
+//! Conversely, an empty tree can be initialized by importing such an export (see [`import_exported_tree()`]).
+//! The imported tree is verified in the same way as a tree recovered from Postgres; if verification fails,
+//! imported data is removed, and the tree is recovered as usual (unless strict import is configured).
+//! The export is imported as a hard-linked checkpoint and verified before it replaces the empty tree, so an import
+//! can be interrupted at any point. Configuring the same path for the export and import turns the export
+//! into a local backup, from which the tree is restored if its data directory is lost.
+//!
+//! Recovery doesn't depend on the tree mode. A tree recovered and processed in the lightweight mode can be upgraded
+//! to the full mode on a later start (see [`upgrade_to_full()`]).
+//!
+//! Independently of recovery, the entire recovered tree can be checked against the Postgres snapshot
+//! instead of normal operation (see [`run_integrity_check()`]). The check is resumable and reports all missing,
+//! extra and mismatched tree entries.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt, mem, ops,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex as StdMutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use futures::{future, Future};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, watch};
+use vise::{EncodeLabelSet, EncodeLabelValue};
+use zksync_config::configs::database::MerkleTreeMode;
+use zksync_dal::{ConnectionPool, SqlxError, StorageProcessor};
+use zksync_health_check::{CheckHealth, Health, HealthStatus, HealthUpdater};
+use zksync_merkle_tree::{RocksDBWrapper, TreeEntry};
+use zksync_object_store::{ObjectStore, ObjectStoreError};
+use zksync_types::{
+    snapshots::{SnapshotRecoveryStatus, SnapshotStorageLogsChunk, SnapshotStorageLogsStorageKey},
+    web3::signing::keccak256,
+    L1BatchNumber, MiniblockNumber, H256, U256,
+};
+use zksync_utils::{time::seconds_since_epoch, u256_to_h256};
+
+use self::{
+    concurrency::{AdaptiveConcurrency, ConcurrencyLimits},
+    connection::access_storage_with_retries,
+    control::PauseGate,
+    decision::StartupHealthDetails,
+    diagnostics::diagnose_root_hash_mismatch,
+    disk_space::{DiskSpaceCheck, OsFsStats},
+    export::export_recovered_tree,
+    flush::{FlushInterval, FlushTracker},
+    format::RecoveryFormat,
+    import::import_exported_tree,
+    journal::ChunkJournalEntry,
+    latest_snapshot::LatestSnapshotCheck,
+    listeners::RecoveryEventFanOut,
+    memory::LoadedEntriesBudget,
+    progress_table::RecoveryProgressTable,
+    replica::SnapshotReplica,
+    snapshot_file::FileSnapshotSource,
+    summary::{ChunkStageTimings, ChunkTimingsCollector},
+    throttle::EntryThrottle,
+    upgrade::{check_upgrade_to_full, upgrade_to_full},
+    verification::{
+        verify_leaf_indices, verify_recovered_proofs, verify_recovered_tree, ChunkLeafIndexStats,
+    },
+    watchdog::{RecoveryWatchdog, WatchdogOptions},
+};
+use super::{
+    helpers::{
+        create_db, AsyncTree, AsyncTreeRecovery, GenericAsyncTree, RecoveryDbProfile,
+        RecoveryFailure, TreeDbParams, TreeTaint,
+    },
+    metrics::{ChunkRecoveryStage, RecoveryStage, RECOVERY_METRICS},
+    pruning::prune_and_compact,
+    MetadataCalculatorRecoveryConfig,
+};
+
+mod concurrency;
+mod connection;
+mod control;
+mod decision;
+mod diagnostics;
+mod disk_space;
+mod error;
+mod export;
+mod fingerprints;
+mod flush;
+mod format;
+mod import;
+mod inspect;
+mod integrity;
+mod journal;
+mod latest_snapshot;
+mod listeners;
+mod memory;
+mod overrides;
+mod plan;
+mod progress_table;
+mod replica;
+mod snapshot_file;
+mod summary;
+mod throttle;
+mod upgrade;
+mod verification;
+mod watchdog;
+
+pub use self::{
+    control::{RecoveryControl, RecoveryControlHandle},
+    decision::{StartupAction, StartupDecision},
+    disk_space::DiskSpaceEstimate,
+    error::{RecoveryError, RecoveryErrorKind},
+    fingerprints::{ChunkFingerprint, RecoveryFingerprintLog},
+    inspect::{
+        inspect_tree_db, reset_tree_db, IncompleteChunk, RecoveryInspection, TreeDbInspection,
+        TreeDbState,
+    },
+    integrity::{
+        DiscrepancyKind, EntryDiscrepancy, HandleIntegrityCheckEvent, IntegrityCheckPhase,
+        IntegrityCheckStats,
+    },
+    plan::{PlannedChunk, RecoveryPlan},
+    snapshot_file::{write_snapshot_file, SnapshotFileHeader},
+    summary::{ChunkTimingsSummary, SlowestChunk},
+    verification::{verify_proofs, LeafIndexStats},
+    watchdog::RecoveryStallReport,
+};
+pub(super) use self::{integrity::run_integrity_check, overrides::RecoveryOverrides};
+
+/// Handler of recovery life cycle events. Besides the built-in handler updating the tree health check,
+/// handlers can be registered from outside the module using [`MetadataCalculator::register_recovery_listener()`].
+/// Registered listeners are isolated from recovery: panics in listeners are caught and logged, and async events
+/// are awaited with a timeout. Events are only emitted during normal (i.e., not dry-run) recovery.
+///
+/// # Ordering guarantees
+///
+/// - [`Self::recovery_started()`] is called once before any other events, and [`Self::disk_space_checked()`]
+///   is called after it if the disk space check is enabled.
+/// - Chunk events may be called concurrently for different chunks. For a single chunk, [`Self::chunk_started()`]
+///   precedes [`Self::chunk_loaded()`], which precedes [`Self::chunk_recovered()`].
+/// - [`Self::chunk_recovered()`] is called exactly once per chunk recovered by the current process; chunks recovered
+///   before a restart are only accounted for in the arguments of [`Self::recovery_started()`].
+/// - [`Self::chunk_failed()`] is called at most once per chunk, instead of [`Self::chunk_recovered()`].
+/// - [`Self::recovery_paused()`] and [`Self::recovery_resumed()`] alternate, starting from the former, and may
+///   interleave with chunk events.
+/// - [`Self::finalize_stage_started()`] is called for each finalization sub-stage in order, after all chunk events.
+/// - Either [`Self::recovery_finished()`] or [`Self::recovery_failed()`] is called at most once, after all
+///   other events. Neither is called if recovery is interrupted.
+///
+/// [`MetadataCalculator::register_recovery_listener()`]: super::MetadataCalculator::register_recovery_listener
+#[async_trait]
+pub trait HandleRecoveryEvent: fmt::Debug + Send + Sync {
+    /// Called when recovery starts or is resumed. `recovered_chunk_count` is the number of chunks recovered
+    /// before the recovery was (re)started, and `recovered_entry_count` is the number of entries in the tree
+    /// at this point (which may include entries of partially recovered chunks).
+    fn recovery_started(
+        &self,
+        _chunk_count: usize,
+        _recovered_chunk_count: usize,
+        _recovered_entry_count: u64,
+    ) {
+        // Default implementation does nothing
+    }
+
+    /// Called after checking disk space required for recovery.
+    fn disk_space_checked(&self, _estimate: DiskSpaceEstimate) {
+        // Default implementation does nothing
+    }
+
+    /// Called when recovery of the specified chunk starts. [`ChunkDescriptor::entry_count`] is always `None`.
+    async fn chunk_started(&self, _chunk: &ChunkDescriptor) {
+        // Default implementation does nothing
+    }
+
+    /// Called when a chunk recovery attempt fails with a transient error and is going to be retried.
+    /// `attempt` is the 1-based number of the failed attempt.
+    async fn chunk_retried(&self, _attempt: usize) {
+        // Default implementation does nothing
+    }
+
+    /// Called when loading the chunk with the specified ID is finished, i.e., all its entries are queued
+    /// for applying to the tree. Not called for chunks interrupted by a stop signal or skipped by the loader.
+    async fn chunk_loaded(&self, _chunk_id: usize) {
+        // Default implementation does nothing
+    }
+
+    /// Called when the specified chunk is recovered. [`ChunkDescriptor::entry_count`] is always set to the number
+    /// of entries inserted into the tree for the chunk by the current process.
+    async fn chunk_recovered(&self, _chunk: &ChunkDescriptor) {
+        // Default implementation does nothing
+    }
+
+    /// Called when loading the specified chunk fails (after all retries, if any). Depending on the recovery options,
+    /// other chunks may continue to be recovered. [`ChunkDescriptor::entry_count`] is always `None`.
+    async fn chunk_failed(&self, _chunk: &ChunkDescriptor, _err: &anyhow::Error) {
+        // Default implementation does nothing
+    }
+
+    /// Called periodically while recovery is stalled, i.e., no chunks have finished loading for a while
+    /// (e.g., because all chunk tasks are stuck waiting on Postgres). Paused recovery is never considered stalled.
+    fn recovery_stalled(&self, _report: &RecoveryStallReport) {
+        // Default implementation does nothing
+    }
+
+    /// Called when recovery is paused via [`RecoveryControlHandle::pause()`]. Chunks in flight may still
+    /// be recovered while recovery is paused.
+    fn recovery_paused(&self) {
+        // Default implementation does nothing
+    }
+
+    /// Called when paused recovery is resumed.
+    fn recovery_resumed(&self) {
+        // Default implementation does nothing
+    }
+
+    /// Called when a sub-stage of finalizing the recovered tree starts. The previous sub-stage (if any)
+    /// is finished at this point.
+    fn finalize_stage_started(&self, _stage: RecoveryFinalizeStage) {
+        // Default implementation does nothing
+    }
+
+    /// Called when recovery successfully finishes, after the recovered tree is finalized.
+    fn recovery_finished(&self, _stats: RecoveryStats) {
+        // Default implementation does nothing
+    }
+
+    /// Called when recovery fails with an error other than [`RecoveryError::Interrupted`].
+    fn recovery_failed(&self, _err: &RecoveryError) {
+        // Default implementation does nothing
+    }
+}
+
+/// Information about a recovered chunk passed to [`HandleRecoveryEvent`] callbacks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkDescriptor {
+    /// 0-based index of the chunk. Chunk indices are stable across restarts.
+    pub index: usize,
+    /// Range of hashed keys covered by the chunk.
+    pub key_range: ops::RangeInclusive<H256>,
+    /// Number of entries inserted into the tree for the chunk, or `None` if it's not known yet.
+    pub entry_count: Option<u64>,
+}
+
+/// Statistics of a finished Merkle tree recovery passed to [`HandleRecoveryEvent::recovery_finished()`].
+/// Statistics only cover the work done by the current process, i.e., since recovery was started or resumed
+/// after a restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryStats {
+    /// Wall-clock duration of recovery.
+    pub duration: Duration,
+    /// Number of entries inserted into the tree.
+    pub entry_count: u64,
+    /// Total number of chunks in the snapshot, including chunks recovered before a restart.
+    pub chunk_count: usize,
+    /// Number of chunk recovery retries caused by transient errors.
+    pub retry_count: usize,
+    /// Total time spent extending the tree with loaded entries. The tree is extended by a single applier,
+    /// so this never exceeds `duration`.
+    pub extend_duration: Duration,
+    /// Total time chunk loaders spent waiting for the tree applier to accept loaded entries, summed over
+    /// all loaders. If this is small compared to `extend_duration`, loading chunks isn't serialized behind
+    /// extending the tree.
+    pub tree_wait_duration: Duration,
+    /// Root hash of the recovered tree.
+    pub root_hash: H256,
+    /// Summary of per-chunk timings for chunks recovered by the current process.
+    pub chunk_timings: ChunkTimingsSummary,
+}
+
+/// Report on preparing the Merkle tree for normal operation returned by [`GenericAsyncTree::ensure_ready()`].
+/// The report is logged and attached to the health check details by the metadata calculator.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum RecoveryReport {
+    /// The tree didn't need recovery from Postgres: it was ready, is built from genesis, or was imported
+    /// from an export.
+    NotNeeded,
+    /// The tree was recovered from a snapshot.
+    Recovered {
+        l1_batch: L1BatchNumber,
+        miniblock: MiniblockNumber,
+        /// Total number of chunks in the snapshot, including chunks recovered before a restart.
+        chunks: usize,
+        /// Number of entries in the recovered tree.
+        entries: u64,
+        /// Wall-clock duration of recovery since it was started or resumed after a restart.
+        #[serde(rename = "duration_secs", serialize_with = "serialize_duration_secs")]
+        duration: Duration,
+        root_hash: H256,
+        /// Summary of per-chunk timings for chunks recovered since recovery was started or resumed.
+        chunk_timings: ChunkTimingsSummary,
+    },
+    /// Recovery was interrupted by a stop signal before completion.
+    Interrupted {
+        recovered_chunks: usize,
+        total_chunks: usize,
+    },
+}
+
+impl RecoveryReport {
+    /// Creates a report on interrupted recovery based on the last published recovery progress (if any).
+    fn interrupted(status: Option<&RecoveryStatus>) -> Self {
+        Self::Interrupted {
+            recovered_chunks: status.map_or(0, |status| status.recovered_chunk_count),
+            total_chunks: status.map_or(0, |status| status.chunk_count),
+        }
+    }
+
+    /// Attaches this report to the current health details under the `recovery_report` key.
+    pub(crate) async fn attach_to_health(&self, health_updater: &HealthUpdater) {
+        let health = health_updater.subscribe().check_health().await;
+        let mut details = match health.details() {
+            Some(serde_json::Value::Object(details)) => details.clone(),
+            _ => serde_json::Map::new(),
+        };
+        let report = serde_json::to_value(self).expect("failed serializing recovery report");
+        details.insert("recovery_report".to_owned(), report);
+        health_updater.update(Health::from(health.status()).with_details(details));
+    }
+}
+
+/// Outcome of [`AsyncTreeRecovery::recover_or_supersede()`].
+#[derive(Debug)]
+enum RecoveryOutcome {
+    /// The tree was fully recovered.
+    Recovered(AsyncTree, RecoveryReport),
+    /// Recovery was aborted because Postgres contains a newer snapshot. The partially recovered tree
+    /// is returned so that it can be reset.
+    Superseded {
+        tree: AsyncTreeRecovery,
+        latest_l1_batch: L1BatchNumber,
+    },
+}
+
+fn serialize_duration_secs<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Sub-stage of finalizing the recovered tree passed to [`HandleRecoveryEvent::finalize_stage_started()`].
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    EncodeLabelValue,
+    EncodeLabelSet,
+)]
+#[serde(rename_all = "snake_case")]
+#[metrics(label = "stage", rename_all = "snake_case")]
+pub enum RecoveryFinalizeStage {
+    /// Checking that leaf indices in the snapshot are consistent with the number of snapshot entries
+    /// and with the recovered tree.
+    CheckLeafIndices,
+    /// Computing the root hash of the recovered tree and comparing it with the snapshot root hash.
+    ComputeRootHash,
+    /// Pruning stale keys accumulated during recovery from RocksDB.
+    FlushDb,
+    /// Manually compacting RocksDB. Only performed if the tree RocksDB is tuned for bulk loading
+    /// with auto-compaction disabled.
+    CompactDb,
+    /// Clearing the recovery journal and marking recovery as complete in the tree manifest.
+    WriteManifest,
+}
+
+/// Mode of the tree recovery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecoveryMode {
+    /// Recovery of the production tree.
+    Normal,
+    /// Recovery of a temporary tree that is discarded after checking its root hash.
+    DryRun,
+}
+
+impl RecoveryMode {
+    fn health_mode(self) -> &'static str {
+        match self {
+            Self::Normal => "recovery",
+            Self::DryRun => "recovery_dry_run",
+        }
+    }
+}
+
+/// Information about a Merkle tree during its snapshot recovery.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct RecoveryMerkleTreeInfo {
+    mode: &'static str, // "recovery" or "recovery_dry_run" to distinguish from `MerkleTreeInfo`
+    chunk_count: usize,
+    recovered_chunk_count: usize,
+    /// UNIX timestamp (in seconds) when recovery was started or resumed after a restart.
+    started_at: u64,
+    /// Moving average of the number of entries inserted into the tree per second.
+    entries_per_second: Option<f64>,
+    /// Estimated time remaining until recovery completes, based on `entries_per_second`.
+    estimated_time_remaining_secs: Option<f64>,
+    /// Total number of entries inserted into the tree, including ones inserted before recovery was resumed
+    /// after a restart.
+    inserted_entry_count: u64,
+    /// Number of entries inserted into the tree per second over a sliding window ending at the last recovered chunk.
+    recent_entries_per_second: Option<f64>,
+    /// Estimated disk space required for recovery and the available disk space.
+    disk_space: Option<DiskSpaceEstimate>,
+}
+
+/// Progress of the Merkle tree recovery published for the Merkle tree API and other components
+/// (see [`MetadataCalculator::recovery_status()`](super::MetadataCalculator::recovery_status())).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryStatus {
+    /// L1 batch of the snapshot the tree is recovered from.
+    pub snapshot_l1_batch: L1BatchNumber,
+    pub chunk_count: usize,
+    pub recovered_chunk_count: usize,
+    /// Total number of entries in the snapshot.
+    pub total_entry_count: u64,
+    /// Number of entries inserted into the tree since recovery was started or resumed after a restart.
+    pub processed_entry_count: u64,
+    /// Moving average of the number of entries inserted into the tree per second.
+    pub entries_per_second: Option<f64>,
+    /// Estimated time remaining until recovery completes, based on `entries_per_second`.
+    pub estimated_time_remaining_secs: Option<f64>,
+}
+
+/// Information about a failed Merkle tree recovery reported via the health check.
+#[derive(Debug, Serialize)]
+struct RecoveryFailureInfo<'a> {
+    #[serde(flatten)]
+    tree_info: RecoveryMerkleTreeInfo,
+    error_kind: RecoveryErrorKind,
+    /// Failed chunks; empty unless the error kind is `chunks_failed`.
+    failed_chunks: &'a [FailedChunk],
+}
+
+/// Information about a finished Merkle tree recovery reported via the health check until the tree switches
+/// to normal operation.
+#[derive(Debug, Serialize)]
+struct RecoveryCompletedInfo {
+    mode: &'static str,
+    /// UNIX timestamp (in seconds) when recovery was started or resumed after a restart.
+    started_at: u64,
+    /// UNIX timestamp (in seconds) when recovery has finished.
+    completed_at: u64,
+    duration_secs: f64,
+    chunk_count: usize,
+    entry_count: u64,
+    retry_count: usize,
+    extend_duration_secs: f64,
+    tree_wait_duration_secs: f64,
+    root_hash: H256,
+    chunk_timings: ChunkTimingsSummary,
+}
+
+/// Information about a Merkle tree recovery in progress with some failed chunks reported via the health check.
+#[derive(Debug, Serialize)]
+struct AffectedRecoveryInfo<'a> {
+    #[serde(flatten)]
+    tree_info: RecoveryMerkleTreeInfo,
+    failed_chunks: &'a [FailedChunk],
+}
+
+/// Information about a paused Merkle tree recovery reported via the health check.
+#[derive(Debug, Serialize)]
+struct PausedRecoveryInfo<'a> {
+    #[serde(flatten)]
+    tree_info: RecoveryMerkleTreeInfo,
+    /// Always set to `true`, so that paused recovery is easy to distinguish.
+    paused: bool,
+    /// UNIX timestamp (in seconds) when recovery was paused.
+    paused_at: u64,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    failed_chunks: &'a [FailedChunk],
+}
+
+/// Information about a Merkle tree recovery being finalized reported via the health check.
+#[derive(Debug, Serialize)]
+struct FinalizingRecoveryInfo {
+    #[serde(flatten)]
+    tree_info: RecoveryMerkleTreeInfo,
+    finalize_stage: RecoveryFinalizeStage,
+}
+
+/// Information about a Merkle tree recovery that has failed before recovering chunks (e.g., because
+/// the snapshot is missing from Postgres) reported via the health check.
+#[derive(Debug, Serialize)]
+struct RecoveryStartFailureInfo {
+    mode: &'static str,
+    error_kind: RecoveryErrorKind,
+}
+
+/// Information about a Merkle tree whose recovery has previously failed reported via the health check.
+#[derive(Debug, Serialize)]
+struct PreviousRecoveryFailureInfo {
+    mode: &'static str, // "recovery_failed"
+    error_kind: RecoveryErrorKind,
+    message: &'static str,
+    recovery_failure: RecoveryFailure,
+}
+
+/// Information about a Merkle tree waiting for the snapshot to be fully applied to Postgres reported
+/// via the health check.
+#[derive(Debug, Serialize)]
+struct WaitingForSnapshotInfo {
+    mode: &'static str, // "waiting_for_snapshot"
+    l1_batch_number: L1BatchNumber,
+    last_finished_chunk_id: Option<u64>,
+    total_chunk_count: u64,
+}
+
+/// Information about a Merkle tree stopping after recovery as configured reported via the health check.
+#[derive(Debug, Serialize)]
+struct RecoveryCompleteInfo<'a> {
+    mode: &'static str, // "recovery_complete"
+    l1_batch_number: L1BatchNumber,
+    root_hash: H256,
+    recovery_report: &'a RecoveryReport,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tainted: Option<TreeTaint>,
+}
+
+/// Information about a tainted Merkle tree refused for normal operation reported via the health check.
+#[derive(Debug, Serialize)]
+struct TaintedTreeInfo {
+    mode: &'static str, // "tainted"
+    tainted: TreeTaint,
+}
+
+/// Recovery throughput tracked by [`RecoveryHealthUpdater`].
+#[derive(Debug)]
+struct RecoveryThroughput {
+    started_at: Instant,
+    last_update: Instant,
+    remaining_entry_count: u64,
+    entries_per_second: Option<f64>,
+    /// Timestamps and entry counts of chunks recovered within the sliding window.
+    recent_chunks: VecDeque<(Instant, u64)>,
+    recent_entries_per_second: Option<f64>,
+}
+
+impl RecoveryThroughput {
+    /// Smoothing factor for the exponential moving average of the throughput.
+    const SMOOTHING_FACTOR: f64 = 0.2;
+    /// Duration of the sliding window for `recent_entries_per_second`.
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    fn new(remaining_entry_count: u64) -> Self {
+        let now = Instant::now();
+        Self {
+            started_at: now,
+            last_update: now,
+            remaining_entry_count,
+            entries_per_second: None,
+            recent_chunks: VecDeque::new(),
+            recent_entries_per_second: None,
+        }
+    }
+
+    fn observe_chunk(&mut self, entry_count: u64, now: Instant) {
+        let elapsed = now.duration_since(self.last_update).as_secs_f64();
+        self.last_update = now;
+        self.remaining_entry_count = self.remaining_entry_count.saturating_sub(entry_count);
+        if elapsed > 0.0 {
+            let rate = entry_count as f64 / elapsed;
+            self.entries_per_second = Some(match self.entries_per_second {
+                Some(avg_rate) => avg_rate + Self::SMOOTHING_FACTOR * (rate - avg_rate),
+                None => rate,
+            });
+        }
+        self.observe_recent_chunk(entry_count, now);
+    }
+
+    /// Updates the throughput over the sliding window ending at `now`. If recovery was started or resumed
+    /// less than the window duration ago, the window is shortened accordingly.
+    fn observe_recent_chunk(&mut self, entry_count: u64, now: Instant) {
+        self.recent_chunks.push_back((now, entry_count));
+        let window_start = now
+            .checked_sub(Self::WINDOW)
+            .map_or(self.started_at, |start| start.max(self.started_at));
+        while let Some(&(timestamp, _)) = self.recent_chunks.front() {
+            if timestamp > window_start {
+                break;
+            }
+            self.recent_chunks.pop_front();
+        }
+
+        let window_secs = now.saturating_duration_since(window_start).as_secs_f64();
+        if window_secs > 0.0 {
+            let recent_entry_count: u64 = self.recent_chunks.iter().map(|&(_, count)| count).sum();
+            self.recent_entries_per_second = Some(recent_entry_count as f64 / window_secs);
+        }
+    }
+
+    fn estimated_time_remaining_secs(&self) -> Option<f64> {
+        if self.remaining_entry_count == 0 {
+            return Some(0.0);
+        }
+        let rate = self.entries_per_second.filter(|&rate| rate > 0.0)?;
+        Some(self.remaining_entry_count as f64 / rate)
+    }
+}
+
+/// Throttle for health updates on recovered chunks, so that health is updated at most once per the specified interval.
+#[derive(Debug)]
+struct HealthUpdateThrottle {
+    interval: Duration,
+    last_update: Option<Instant>,
+}
+
+impl HealthUpdateThrottle {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_update: None,
+        }
+    }
+
+    /// Checks whether health should be updated at `now`. If `force` is set, health is always updated.
+    fn should_update(&mut self, now: Instant, force: bool) -> bool {
+        let should_update = force
+            || self.last_update.map_or(true, |last_update| {
+                now.duration_since(last_update) >= self.interval
+            });
+        if should_update {
+            self.last_update = Some(now);
+        }
+        should_update
+    }
+}
+
+/// [`HealthUpdater`]-based [`HandleRecoveryEvent`] implementation. Optionally, also publishes [`RecoveryStatus`]
+/// for the Merkle tree API. Health updates on recovered chunks are throttled (see [`Self::with_health_update_interval()`]),
+/// while the recovery status is published on each recovered chunk.
+#[derive(Debug)]
+struct RecoveryHealthUpdater<'a> {
+    inner: &'a HealthUpdater,
+    mode: RecoveryMode,
+    total_entry_count: u64,
+    chunk_count: AtomicUsize,
+    started_at: AtomicU64,
+    recovered_chunk_count: AtomicUsize,
+    processed_entry_count: AtomicU64,
+    inserted_entry_count: AtomicU64,
+    throughput: StdMutex<RecoveryThroughput>,
+    disk_space: StdMutex<Option<DiskSpaceEstimate>>,
+    failed_chunks: StdMutex<Vec<FailedChunk>>,
+    /// UNIX timestamp (in seconds) when recovery was paused, or `None` if it isn't paused.
+    paused_at: StdMutex<Option<u64>>,
+    health_throttle: StdMutex<HealthUpdateThrottle>,
+    status_sender: Option<(&'a watch::Sender<Option<RecoveryStatus>>, L1BatchNumber)>,
+    /// Whether the tree remains [`HealthStatus::Recovering`] after recovery is finished, so that it's reported
+    /// as ready only once it catches up with Postgres.
+    catch_up_before_ready: bool,
+}
+
+impl<'a> RecoveryHealthUpdater<'a> {
+    fn new(inner: &'a HealthUpdater, mode: RecoveryMode, total_entry_count: u64) -> Self {
+        Self {
+            inner,
+            mode,
+            total_entry_count,
+            chunk_count: AtomicUsize::new(0),
+            started_at: AtomicU64::new(seconds_since_epoch()),
+            recovered_chunk_count: AtomicUsize::new(0),
+            processed_entry_count: AtomicU64::new(0),
+            inserted_entry_count: AtomicU64::new(0),
+            throughput: StdMutex::new(RecoveryThroughput::new(total_entry_count)),
+            disk_space: StdMutex::new(None),
+            failed_chunks: StdMutex::default(),
+            paused_at: StdMutex::new(None),
+            health_throttle: StdMutex::new(HealthUpdateThrottle::new(Duration::ZERO)),
+            status_sender: None,
+            catch_up_before_ready: false,
+        }
+    }
+
+    /// Sets the minimum interval between health updates on recovered chunks. The update for the last chunk
+    /// is never throttled. By default, health is updated on each recovered chunk.
+    fn with_health_update_interval(mut self, interval: Duration) -> Self {
+        *self
+            .health_throttle
+            .get_mut()
+            .expect("health throttle mutex poisoned") = HealthUpdateThrottle::new(interval);
+        self
+    }
+
+    /// Publishes recovery progress for the snapshot with the specified L1 batch to `sender`.
+    fn with_status_sender(
+        mut self,
+        sender: &'a watch::Sender<Option<RecoveryStatus>>,
+        snapshot_l1_batch: L1BatchNumber,
+    ) -> Self {
+        self.status_sender = Some((sender, snapshot_l1_batch));
+        self
+    }
+
+    /// Keeps the health status [`HealthStatus::Recovering`] after recovery is finished if `catch_up_before_ready`
+    /// is set (see [`MetadataCalculatorRecoveryConfig::catch_up_before_ready`]).
+    fn with_catch_up_before_ready(mut self, catch_up_before_ready: bool) -> Self {
+        self.catch_up_before_ready = catch_up_before_ready;
+        self
+    }
+
+    fn chunk_count(&self) -> usize {
+        self.chunk_count.load(Ordering::SeqCst)
+    }
+
+    fn disk_space(&self) -> Option<DiskSpaceEstimate> {
+        *self.disk_space.lock().expect("disk space mutex poisoned")
+    }
+
+    fn paused_at(&self) -> Option<u64> {
+        *self.paused_at.lock().expect("paused at mutex poisoned")
+    }
+
+    /// Returns health for the recovery in progress. The health status is always [`HealthStatus::Recovering`];
+    /// it's switched to [`HealthStatus::Ready`] only after the recovered tree is finalized (or has caught up
+    /// with Postgres, if [`Self::with_catch_up_before_ready()`] is set). If some chunks
+    /// have failed, failed chunks are included into health details; if recovery is paused, this is marked
+    /// in health details as well.
+    fn progress_health(&self, tree_info: RecoveryMerkleTreeInfo) -> Health {
+        let failed_chunks = self
+            .failed_chunks
+            .lock()
+            .expect("failed chunks mutex poisoned");
+        let health = Health::from(HealthStatus::Recovering);
+        if let Some(paused_at) = self.paused_at() {
+            health.with_details(PausedRecoveryInfo {
+                tree_info,
+                paused: true,
+                paused_at,
+                failed_chunks: &failed_chunks,
+            })
+        } else if failed_chunks.is_empty() {
+            health.with_details(tree_info)
+        } else {
+            health.with_details(AffectedRecoveryInfo {
+                tree_info,
+                failed_chunks: &failed_chunks,
+            })
+        }
+    }
+
+    fn chunk_recovered_at(&self, chunk: &ChunkDescriptor, now: Instant) {
+        let entry_count = chunk.entry_count.unwrap_or(0);
+        let recovered_chunk_count = self.recovered_chunk_count.fetch_add(1, Ordering::SeqCst) + 1;
+        self.processed_entry_count
+            .fetch_add(entry_count, Ordering::SeqCst);
+        let inserted_entry_count = self
+            .inserted_entry_count
+            .fetch_add(entry_count, Ordering::SeqCst)
+            + entry_count;
+        RECOVERY_METRICS
+            .recovered_chunk_count
+            .set(recovered_chunk_count);
+        RECOVERY_METRICS
+            .remaining_chunks
+            .set(self.chunk_count().saturating_sub(recovered_chunk_count));
+        RECOVERY_METRICS.inserted_entries.inc_by(entry_count);
+        let (entries_per_second, estimated_time_remaining_secs, recent_entries_per_second) = {
+            let mut throughput = self.throughput.lock().expect("throughput mutex poisoned");
+            throughput.observe_chunk(entry_count, now);
+            (
+                throughput.entries_per_second,
+                throughput.estimated_time_remaining_secs(),
+                throughput.recent_entries_per_second,
+            )
+        };
+        if let Some(rate) = recent_entries_per_second {
+            RECOVERY_METRICS.entries_per_second.set(rate);
+        }
+
+        let is_last_chunk = recovered_chunk_count >= self.chunk_count();
+        let should_update_health = self
+            .health_throttle
+            .lock()
+            .expect("health throttle mutex poisoned")
+            .should_update(now, is_last_chunk);
+        if should_update_health {
+            let tree_info = RecoveryMerkleTreeInfo {
+                mode: self.mode.health_mode(),
+                chunk_count: self.chunk_count(),
+                recovered_chunk_count,
+                started_at: self.started_at.load(Ordering::SeqCst),
+                entries_per_second,
+                estimated_time_remaining_secs,
+                inserted_entry_count,
+                recent_entries_per_second,
+                disk_space: self.disk_space(),
+            };
+            self.inner.update(self.progress_health(tree_info));
+        }
+        self.publish_status(
+            recovered_chunk_count,
+            entries_per_second,
+            estimated_time_remaining_secs,
+        );
+    }
+
+    /// Returns information about the recovery in progress as of the last recovered chunk.
+    fn current_tree_info(&self) -> RecoveryMerkleTreeInfo {
+        let throughput = self.throughput.lock().expect("throughput mutex poisoned");
+        RecoveryMerkleTreeInfo {
+            mode: self.mode.health_mode(),
+            chunk_count: self.chunk_count(),
+            recovered_chunk_count: self.recovered_chunk_count.load(Ordering::SeqCst),
+            started_at: self.started_at.load(Ordering::SeqCst),
+            entries_per_second: throughput.entries_per_second,
+            estimated_time_remaining_secs: throughput.estimated_time_remaining_secs(),
+            inserted_entry_count: self.inserted_entry_count.load(Ordering::SeqCst),
+            recent_entries_per_second: throughput.recent_entries_per_second,
+            disk_space: self.disk_space(),
+        }
+    }
+
+    fn publish_status(
+        &self,
+        recovered_chunk_count: usize,
+        entries_per_second: Option<f64>,
+        estimated_time_remaining_secs: Option<f64>,
+    ) {
+        let Some((sender, snapshot_l1_batch)) = self.status_sender else {
+            return;
+        };
+        sender.send_replace(Some(RecoveryStatus {
+            snapshot_l1_batch,
+            chunk_count: self.chunk_count(),
+            recovered_chunk_count,
+            total_entry_count: self.total_entry_count,
+            processed_entry_count: self.processed_entry_count.load(Ordering::SeqCst),
+            entries_per_second,
+            estimated_time_remaining_secs,
+        }));
+    }
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for RecoveryHealthUpdater<'_> {
+    fn recovery_started(
+        &self,
+        chunk_count: usize,
+        recovered_chunk_count: usize,
+        recovered_entry_count: u64,
+    ) {
+        self.chunk_count.store(chunk_count, Ordering::SeqCst);
+        let started_at = seconds_since_epoch();
+        self.started_at.store(started_at, Ordering::SeqCst);
+        self.recovered_chunk_count
+            .store(recovered_chunk_count, Ordering::SeqCst);
+        self.processed_entry_count.store(0, Ordering::SeqCst);
+        self.inserted_entry_count
+            .store(recovered_entry_count, Ordering::SeqCst);
+        self.failed_chunks
+            .lock()
+            .expect("failed chunks mutex poisoned")
+            .clear();
+        *self.paused_at.lock().expect("paused at mutex poisoned") = None;
+        let remaining_entry_count = self.total_entry_count.saturating_sub(recovered_entry_count);
+        *self.throughput.lock().expect("throughput mutex poisoned") =
+            RecoveryThroughput::new(remaining_entry_count);
+        // Metrics and health are updated immediately, so that progress persisted before a restart is reported
+        // before any new chunks are recovered.
+        RECOVERY_METRICS.chunk_count.set(chunk_count);
+        RECOVERY_METRICS
+            .recovered_chunk_count
+            .set(recovered_chunk_count);
+        RECOVERY_METRICS
+            .remaining_chunks
+            .set(chunk_count.saturating_sub(recovered_chunk_count));
+        // The counter is monotonic, so it can only catch up with entries inserted before a restart.
+        let reported_entry_count = RECOVERY_METRICS.inserted_entries.get();
+        RECOVERY_METRICS
+            .inserted_entries
+            .inc_by(recovered_entry_count.saturating_sub(reported_entry_count));
+
+        let tree_info = RecoveryMerkleTreeInfo {
+            mode: self.mode.health_mode(),
+            chunk_count,
+            recovered_chunk_count,
+            started_at,
+            entries_per_second: None,
+            estimated_time_remaining_secs: None,
+            inserted_entry_count: recovered_entry_count,
+            recent_entries_per_second: None,
+            disk_space: self.disk_space(),
+        };
+        self.inner.update(self.progress_health(tree_info));
+        self.publish_status(recovered_chunk_count, None, None);
+    }
+
+    fn disk_space_checked(&self, estimate: DiskSpaceEstimate) {
+        *self.disk_space.lock().expect("disk space mutex poisoned") = Some(estimate);
+        let tree_info = RecoveryMerkleTreeInfo {
+            mode: self.mode.health_mode(),
+            chunk_count: self.chunk_count(),
+            recovered_chunk_count: self.recovered_chunk_count.load(Ordering::SeqCst),
+            started_at: self.started_at.load(Ordering::SeqCst),
+            entries_per_second: None,
+            estimated_time_remaining_secs: None,
+            inserted_entry_count: self.inserted_entry_count.load(Ordering::SeqCst),
+            recent_entries_per_second: None,
+            disk_space: self.disk_space(),
+        };
+        self.inner.update(self.progress_health(tree_info));
+    }
+
+    async fn chunk_recovered(&self, chunk: &ChunkDescriptor) {
+        self.chunk_recovered_at(chunk, Instant::now());
+    }
+
+    async fn chunk_failed(&self, chunk: &ChunkDescriptor, err: &anyhow::Error) {
+        self.failed_chunks
+            .lock()
+            .expect("failed chunks mutex poisoned")
+            .push(FailedChunk::new(chunk.index, chunk.key_range.clone(), err));
+
+        let (entries_per_second, recent_entries_per_second) = {
+            let throughput = self.throughput.lock().expect("throughput mutex poisoned");
+            (
+                throughput.entries_per_second,
+                throughput.recent_entries_per_second,
+            )
+        };
+        let tree_info = RecoveryMerkleTreeInfo {
+            mode: self.mode.health_mode(),
+            chunk_count: self.chunk_count(),
+            recovered_chunk_count: self.recovered_chunk_count.load(Ordering::SeqCst),
+            started_at: self.started_at.load(Ordering::SeqCst),
+            entries_per_second,
+            estimated_time_remaining_secs: None,
+            inserted_entry_count: self.inserted_entry_count.load(Ordering::SeqCst),
+            recent_entries_per_second,
+            disk_space: self.disk_space(),
+        };
+        self.inner.update(self.progress_health(tree_info));
+    }
+
+    fn recovery_paused(&self) {
+        *self.paused_at.lock().expect("paused at mutex poisoned") = Some(seconds_since_epoch());
+        self.inner
+            .update(self.progress_health(self.current_tree_info()));
+    }
+
+    fn recovery_resumed(&self) {
+        *self.paused_at.lock().expect("paused at mutex poisoned") = None;
+        self.inner
+            .update(self.progress_health(self.current_tree_info()));
+    }
+
+    fn finalize_stage_started(&self, stage: RecoveryFinalizeStage) {
+        let throughput = self.throughput.lock().expect("throughput mutex poisoned");
+        let tree_info = RecoveryMerkleTreeInfo {
+            mode: self.mode.health_mode(),
+            chunk_count: self.chunk_count(),
+            recovered_chunk_count: self.recovered_chunk_count.load(Ordering::SeqCst),
+            started_at: self.started_at.load(Ordering::SeqCst),
+            entries_per_second: throughput.entries_per_second,
+            estimated_time_remaining_secs: None,
+            inserted_entry_count: self.inserted_entry_count.load(Ordering::SeqCst),
+            recent_entries_per_second: throughput.recent_entries_per_second,
+            disk_space: self.disk_space(),
+        };
+        let health = Health::from(HealthStatus::Recovering).with_details(FinalizingRecoveryInfo {
+            tree_info,
+            finalize_stage: stage,
+        });
+        self.inner.update(health);
+    }
+
+    fn recovery_finished(&self, stats: RecoveryStats) {
+        let status = if self.catch_up_before_ready {
+            HealthStatus::Recovering
+        } else {
+            HealthStatus::Ready
+        };
+        let health = Health::from(status).with_details(RecoveryCompletedInfo {
+            mode: self.mode.health_mode(),
+            started_at: self.started_at.load(Ordering::SeqCst),
+            completed_at: seconds_since_epoch(),
+            duration_secs: stats.duration.as_secs_f64(),
+            chunk_count: stats.chunk_count,
+            entry_count: stats.entry_count,
+            retry_count: stats.retry_count,
+            extend_duration_secs: stats.extend_duration.as_secs_f64(),
+            tree_wait_duration_secs: stats.tree_wait_duration.as_secs_f64(),
+            root_hash: stats.root_hash,
+            chunk_timings: stats.chunk_timings,
+        });
+        self.inner.update(health);
+    }
+
+    fn recovery_failed(&self, err: &RecoveryError) {
+        let throughput = self.throughput.lock().expect("throughput mutex poisoned");
+        let tree_info = RecoveryMerkleTreeInfo {
+            mode: self.mode.health_mode(),
+            chunk_count: self.chunk_count(),
+            recovered_chunk_count: self.recovered_chunk_count.load(Ordering::SeqCst),
+            started_at: self.started_at.load(Ordering::SeqCst),
+            entries_per_second: throughput.entries_per_second,
+            estimated_time_remaining_secs: None,
+            inserted_entry_count: self.inserted_entry_count.load(Ordering::SeqCst),
+            recent_entries_per_second: throughput.recent_entries_per_second,
+            disk_space: self.disk_space(),
+        };
+        let failed_chunks = match err {
+            RecoveryError::ChunksFailed(failed_chunks) => failed_chunks.chunks.as_slice(),
+            _ => &[],
+        };
+        let health = Health::from(HealthStatus::NotReady).with_details(RecoveryFailureInfo {
+            tree_info,
+            error_kind: err.kind(),
+            failed_chunks,
+        });
+        self.inner.update(health);
+    }
+}
+
+/// Tracks sub-stages of finalizing the recovered tree, reporting them via metrics, logs and recovery events.
+#[derive(Debug)]
+struct FinalizeProgress<'a> {
+    events: &'a dyn HandleRecoveryEvent,
+    current_stage: Option<(RecoveryFinalizeStage, Instant)>,
+}
+
+impl<'a> FinalizeProgress<'a> {
+    fn new(events: &'a dyn HandleRecoveryEvent) -> Self {
+        Self {
+            events,
+            current_stage: None,
+        }
+    }
+
+    /// Finishes the current stage (if any) and starts the specified one.
+    fn start_stage(&mut self, stage: RecoveryFinalizeStage) {
+        self.finish();
+        tracing::info!("Started finalization stage {stage:?}");
+        self.events.finalize_stage_started(stage);
+        self.current_stage = Some((stage, Instant::now()));
+    }
+
+    fn finish(&mut self) {
+        if let Some((stage, started_at)) = self.current_stage.take() {
+            let elapsed = started_at.elapsed();
+            RECOVERY_METRICS.finalize_latency[&stage].observe(elapsed);
+            tracing::info!("Finished finalization stage {stage:?} in {elapsed:?}");
+        }
+    }
+}
+
+/// Parameters of the Postgres snapshot the Merkle tree is recovered from. Can be used by external tooling
+/// to compute the same chunk count as recovery.
+///
+/// # Examples
+///
+/// ```
+/// use zksync_core::metadata_calculator::{MetadataCalculatorRecoveryConfig, SnapshotParameters};
+/// use zksync_types::{snapshots::SnapshotRecoveryStatus, L1BatchNumber, MiniblockNumber, H256};
+///
+/// let snapshot_recovery = SnapshotRecoveryStatus {
+///     l1_batch_number: L1BatchNumber(42),
+///     l1_batch_root_hash: H256::repeat_byte(1),
+///     miniblock_number: MiniblockNumber(100),
+///     miniblock_root_hash: H256::repeat_byte(2),
+///     last_finished_chunk_id: None,
+///     total_chunk_count: 10,
+/// };
+/// let snapshot = SnapshotParameters::with_log_count(&snapshot_recovery, 1_000_001)?;
+/// assert_eq!(snapshot.miniblock(), MiniblockNumber(100));
+/// assert_eq!(snapshot.expected_root_hash(), H256::repeat_byte(1));
+/// assert_eq!(snapshot.log_count(), 1_000_001);
+///
+/// let config = MetadataCalculatorRecoveryConfig {
+///     desired_chunk_size: 200_000,
+///     ..MetadataCalculatorRecoveryConfig::default()
+/// };
+/// assert_eq!(snapshot.chunk_count(&config), 6);
+/// # Ok::<_, zksync_core::metadata_calculator::RecoveryError>(())
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotParameters {
+    l1_batch_number: L1BatchNumber,
+    miniblock: MiniblockNumber,
+    expected_root_hash: H256,
+    log_count: u64,
+}
+
+impl SnapshotParameters {
+    /// Loads snapshot parameters. If `replica` is supplied and is synced with the primary, the number
+    /// of snapshot storage logs is taken from the replica.
+    async fn new(
+        pool: &ConnectionPool,
+        replica: Option<&SnapshotReplica<'_>>,
+        snapshot_recovery: &SnapshotRecoveryStatus,
+    ) -> anyhow::Result<Self> {
+        let miniblock = snapshot_recovery.miniblock_number;
+        Self::validate(pool, snapshot_recovery).await?;
+
+        let replica_log_count = match replica {
+            Some(replica) => replica.log_count().await,
+            None => None,
+        };
+        let log_count = if let Some(log_count) = replica_log_count {
+            log_count
+        } else {
+            let mut storage = pool.access_storage().await?;
+            storage
+                .storage_logs_dal()
+                .count_miniblock_storage_logs(miniblock)
+                .await
+                .with_context(|| {
+                    format!("Failed getting number of logs for miniblock #{miniblock}")
+                })?
+        };
+        Ok(Self::with_log_count(snapshot_recovery, log_count)?)
+    }
+
+    /// Creates parameters with the specified number of snapshot storage logs. A snapshot without logs
+    /// (e.g., produced by a broken snapshot creator, or with pruned storage logs) is rejected, since it cannot be
+    /// split into chunks, and the tree recovered from it would be empty.
+    pub fn with_log_count(
+        snapshot_recovery: &SnapshotRecoveryStatus,
+        log_count: u64,
+    ) -> Result<Self, RecoveryError> {
+        let miniblock = snapshot_recovery.miniblock_number;
+        if log_count == 0 {
+            return Err(RecoveryError::InvalidSnapshotParameters {
+                l1_batch_number: snapshot_recovery.l1_batch_number,
+                details: format!(
+                    "snapshot miniblock #{miniblock} has no storage logs in Postgres; the snapshot is broken, \
+                     or its storage logs were pruned"
+                ),
+            });
+        }
+        Ok(Self {
+            l1_batch_number: snapshot_recovery.l1_batch_number,
+            miniblock,
+            expected_root_hash: snapshot_recovery.l1_batch_root_hash,
+            log_count,
+        })
+    }
+
+    /// Re-reads the expected root hash and the number of snapshot storage logs from Postgres and checks that
+    /// they haven't changed since these parameters were loaded. Recovery can take hours, and if Postgres
+    /// is rolled back or the snapshot L1 batch is re-executed in the meantime, the recovered tree would be
+    /// checked against stale data.
+    async fn check_unchanged(&self, pool: &ConnectionPool) -> Result<(), RecoveryError> {
+        let l1_batch_number = self.l1_batch_number;
+        let changed = |details: String| RecoveryError::SnapshotChanged {
+            l1_batch_number,
+            details,
+        };
+
+        let mut storage = pool.access_storage().await?;
+        let snapshots = storage
+            .snapshot_recovery_dal()
+            .get_applied_snapshot_statuses()
+            .await
+            .context("Failed getting snapshot recovery info")?;
+        let snapshot_recovery = snapshots
+            .into_iter()
+            .find(|snapshot_recovery| snapshot_recovery.l1_batch_number == l1_batch_number);
+        // If the tree is recovered to a configured L1 batch rather than the Postgres snapshot, the expected root hash
+        // is taken from L1 batch metadata.
+        let root_hash = if let Some(snapshot_recovery) = snapshot_recovery {
+            if snapshot_recovery.miniblock_number != self.miniblock {
+                return Err(changed(format!(
+                    "snapshot miniblock has changed from #{} to #{}",
+                    self.miniblock, snapshot_recovery.miniblock_number
+                )));
+            }
+            Some(snapshot_recovery.l1_batch_root_hash)
+        } else {
+            storage
+                .blocks_dal()
+                .get_l1_batch_state_root(l1_batch_number)
+                .await
+                .with_context(|| {
+                    format!("Failed getting root hash for L1 batch #{l1_batch_number}")
+                })?
+        };
+        let Some(root_hash) = root_hash else {
+            return Err(changed(
+                "snapshot recovery info and L1 batch metadata have disappeared from Postgres"
+                    .to_owned(),
+            ));
+        };
+        if root_hash != self.expected_root_hash {
+            return Err(changed(format!(
+                "expected root hash has changed from {:?} to {root_hash:?}",
+                self.expected_root_hash
+            )));
+        }
+
+        let miniblock = self.miniblock;
+        let log_count = storage
+            .storage_logs_dal()
+            .count_miniblock_storage_logs(miniblock)
+            .await
+            .with_context(|| format!("Failed getting number of logs for miniblock #{miniblock}"))?;
+        if log_count != self.log_count {
+            return Err(changed(format!(
+                "number of storage logs for snapshot miniblock #{miniblock} has changed from {} to {log_count}",
+                self.log_count
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks invariants of the snapshot recovery information: the expected root hash must be non-zero,
+    /// and the snapshot miniblock must be present in Postgres and be the last miniblock of the snapshot L1 batch.
+    /// Otherwise, the tree would be recovered from logs of a wrong miniblock, or the root hash check
+    /// after recovery would be meaningless.
+    async fn validate(
+        pool: &ConnectionPool,
+        snapshot_recovery: &SnapshotRecoveryStatus,
+    ) -> anyhow::Result<()> {
+        let l1_batch_number = snapshot_recovery.l1_batch_number;
+        let miniblock = snapshot_recovery.miniblock_number;
+        let invalid = |details: String| RecoveryError::InvalidSnapshotParameters {
+            l1_batch_number,
+            details,
+        };
+
+        if snapshot_recovery.l1_batch_root_hash == H256::zero() {
+            return Err(invalid("expected root hash is zero".to_owned()).into());
+        }
+
+        let mut storage = pool.access_storage().await?;
+        let header = storage
+            .blocks_dal()
+            .get_miniblock_header(miniblock)
+            .await
+            .with_context(|| format!("Failed getting header for miniblock #{miniblock}"))?;
+        if header.is_none() {
+            return Err(invalid(format!(
+                "snapshot miniblock #{miniblock} is not present in Postgres"
+            ))
+            .into());
+        }
+
+        let miniblock_l1_batch = storage
+            .blocks_web3_dal()
+            .get_l1_batch_number_of_miniblock(miniblock)
+            .await
+            .with_context(|| format!("Failed getting L1 batch for miniblock #{miniblock}"))?;
+        if miniblock_l1_batch != Some(l1_batch_number) {
+            let actual = miniblock_l1_batch.map_or_else(
+                || "no L1 batch".to_owned(),
+                |number| format!("L1 batch #{number}"),
+            );
+            return Err(invalid(format!(
+                "snapshot miniblock #{miniblock} belongs to {actual} rather than to the snapshot L1 batch"
+            ))
+            .into());
+        }
+
+        let (_, last_miniblock) = storage
+            .blocks_dal()
+            .get_miniblock_range_of_l1_batch(l1_batch_number)
+            .await
+            .with_context(|| format!("Failed getting miniblocks for L1 batch #{l1_batch_number}"))?
+            .context("miniblock range is empty, although it contains the snapshot miniblock")?;
+        if last_miniblock != miniblock {
+            return Err(invalid(format!(
+                "snapshot miniblock #{miniblock} is not the last miniblock in the snapshot L1 batch \
+                 (the last one is #{last_miniblock})"
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Returns the snapshot miniblock.
+    pub fn miniblock(&self) -> MiniblockNumber {
+        self.miniblock
+    }
+
+    /// Returns the root hash the recovered tree is expected to have.
+    pub fn expected_root_hash(&self) -> H256 {
+        self.expected_root_hash
+    }
+
+    /// Returns the number of snapshot storage logs.
+    pub fn log_count(&self) -> u64 {
+        self.log_count
+    }
+
+    /// Returns the number of chunks recovery from Postgres splits the snapshot into with the specified `config`.
+    /// If recovery has already started, it uses the desired chunk size persisted in the tree instead
+    /// of the configured one. When recovering from snapshot chunks in the object store, the number of chunks
+    /// is taken from the snapshot instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the desired chunk size in `config` is zero.
+    pub fn chunk_count(&self, config: &MetadataCalculatorRecoveryConfig) -> usize {
+        assert!(
+            config.desired_chunk_size > 0,
+            "desired chunk size must be positive"
+        );
+        self.chunk_count_for_size(config.desired_chunk_size)
+    }
+
+    /// Returns the number of recovery chunks. Since the snapshot has at least one storage log
+    /// (see [`Self::with_log_count()`]), there is always at least one chunk.
+    fn chunk_count_for_size(&self, desired_chunk_size: u64) -> usize {
+        zksync_utils::ceil_div(self.log_count, desired_chunk_size) as usize
+    }
+}
+
+/// Splits the hashed key space into `count` equal-width contiguous ranges in the same way as recovery does
+/// for snapshot chunks in the object store. Recovery from Postgres uses ranges weighted by the number of snapshot
+/// entries instead, which fall back to these ranges if the snapshot is too small.
+///
+/// # Panics
+///
+/// Panics if `count` is zero.
+///
+/// # Examples
+///
+/// ```
+/// use zksync_core::metadata_calculator::recovery_chunk_ranges;
+/// use zksync_types::H256;
+///
+/// let ranges: Vec<_> = recovery_chunk_ranges(2).collect();
+/// let mut middle_key = H256::zero();
+/// middle_key.0[0] = 0x80;
+/// assert_eq!(ranges[0].start(), &H256::zero());
+/// assert_eq!(ranges[1].start(), &middle_key);
+/// assert_eq!(ranges[1].end(), &H256::repeat_byte(0xff));
+/// ```
+pub fn recovery_chunk_ranges(count: usize) -> impl Iterator<Item = ops::RangeInclusive<H256>> {
+    AsyncTreeRecovery::hashed_key_ranges(count)
+}
+
+/// Recovers the Merkle tree stored in `db` from the Postgres snapshot described by `snapshot_recovery`, e.g. to embed
+/// recovery into another binary. The database must be empty or contain a tree recovering from the same snapshot.
+///
+/// If recovery is interrupted and restarted, it must be resumed from the same snapshot and with the same chunk count
+/// in `options`; otherwise, recovered chunks may be skipped or mixed with entries of another snapshot.
+/// Unlike [`MetadataCalculator`](super::MetadataCalculator), this function doesn't persist recovery parameters
+/// in the tree, doesn't import tree exports and doesn't prune the tree after recovery.
+pub async fn recover_tree<'a>(
+    db: RocksDBWrapper,
+    mode: MerkleTreeMode,
+    snapshot_recovery: &SnapshotRecoveryStatus,
+    options: RecoveryOptions<'a>,
+    pool: &'a ConnectionPool,
+    stop_receiver: &watch::Receiver<bool>,
+) -> Result<RecoveryReport, RecoveryError> {
+    let snapshot = SnapshotParameters::new(pool, None, snapshot_recovery).await?;
+    let recovered_version = snapshot_recovery.l1_batch_number.0.into();
+    let tree = AsyncTreeRecovery::new(db, recovered_version, mode);
+    let (_, report) = tree.recover(snapshot, options, pool, stop_receiver).await?;
+    Ok(report)
+}
+
+/// Plan of splitting the snapshot into chunks for recovery. Since recovery progress is tracked per chunk,
+/// the plan must not change once recovery has started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct ChunkPlan {
+    /// Number of snapshot storage logs.
+    log_count: u64,
+    /// Number of chunks the snapshot is split into.
+    chunk_count: usize,
+}
+
+/// Fingerprint of the snapshot the tree is recovered from and of its chunk plan. The fingerprint is persisted
+/// in the tree manifest when recovery starts and is checked on each resume, so that a Postgres restore,
+/// a re-executed snapshot L1 batch or a chunk plan drift is detected before recovering more chunks. Components
+/// are persisted together with their hash, so that a mismatch can be attributed to specific components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct SnapshotFingerprint {
+    miniblock: MiniblockNumber,
+    expected_root_hash: H256,
+    log_count: u64,
+    chunk_count: usize,
+    /// Hash of all other fields.
+    hash: H256,
+}
+
+impl SnapshotFingerprint {
+    fn new(snapshot: &SnapshotParameters, chunk_count: usize) -> Self {
+        let mut this = Self {
+            miniblock: snapshot.miniblock,
+            expected_root_hash: snapshot.expected_root_hash,
+            log_count: snapshot.log_count,
+            chunk_count,
+            hash: H256::zero(),
+        };
+        this.hash = this.compute_hash();
+        this
+    }
+
+    fn compute_hash(&self) -> H256 {
+        let mut buffer = Vec::with_capacity(52);
+        buffer.extend_from_slice(&self.miniblock.0.to_be_bytes());
+        buffer.extend_from_slice(self.expected_root_hash.as_bytes());
+        buffer.extend_from_slice(&self.log_count.to_be_bytes());
+        buffer.extend_from_slice(&(self.chunk_count as u64).to_be_bytes());
+        H256(keccak256(&buffer))
+    }
+
+    /// Returns names of the components differing between this and the `other` fingerprint.
+    fn differing_components(&self, other: &Self) -> Vec<&'static str> {
+        let components = [
+            ("miniblock", self.miniblock != other.miniblock),
+            (
+                "expected_root_hash",
+                self.expected_root_hash != other.expected_root_hash,
+            ),
+            ("log_count", self.log_count != other.log_count),
+            ("chunk_count", self.chunk_count != other.chunk_count),
+        ];
+        components
+            .into_iter()
+            .filter_map(|(name, differs)| differs.then_some(name))
+            .collect()
+    }
+}
+
+/// Identity of the Postgres data the tree is recovered from: the snapshot L1 batch for nodes recovered
+/// from a snapshot, or the genesis L1 batch otherwise. The root hash of the batch depends on the chain ID
+/// and the genesis state, so it changes if Postgres is re-initialized for another chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct PostgresGenesis {
+    l1_batch_number: L1BatchNumber,
+    root_hash: H256,
+}
+
+impl PostgresGenesis {
+    /// Loads genesis information from Postgres for the tree recovered to `recovered_version` (if known).
+    /// Returns `None` if Postgres isn't initialized yet.
+    async fn load(
+        pool: &ConnectionPool,
+        recovered_version: Option<u64>,
+    ) -> anyhow::Result<Option<Self>> {
+        if let Some(snapshot_recovery) = get_snapshot_recovery(pool, recovered_version).await? {
+            return Ok(Some(Self {
+                l1_batch_number: snapshot_recovery.l1_batch_number,
+                root_hash: snapshot_recovery.l1_batch_root_hash,
+            }));
+        }
+
+        let mut storage = pool.access_storage().await?;
+        let genesis_root_hash = storage
+            .blocks_dal()
+            .get_l1_batch_state_root(L1BatchNumber(0))
+            .await
+            .context("Failed getting genesis root hash")?;
+        Ok(genesis_root_hash.map(|root_hash| Self {
+            l1_batch_number: L1BatchNumber(0),
+            root_hash,
+        }))
+    }
+}
+
+/// Options for tree recovery. Options are usually derived from [`MetadataCalculatorRecoveryConfig`]
+/// by `GenericAsyncTree::ensure_ready()`; when recovery is driven directly (e.g., using [`recover_tree()`]),
+/// options can be created with [`Self::new()`] and adjusted using builder methods.
+///
+/// # Examples
+///
+/// ```
+/// # use std::sync::Arc;
+/// use zksync_core::metadata_calculator::{HandleRecoveryEvent, RecoveryOptions};
+///
+/// #[derive(Debug)]
+/// struct Listener;
+///
+/// impl HandleRecoveryEvent for Listener {}
+///
+/// let options = RecoveryOptions::new(16)
+///     .concurrency(4)
+///     .max_chunk_attempts(3)
+///     .events(Arc::new(Listener));
+/// ```
+#[derive(Debug)]
+pub struct RecoveryOptions<'a> {
+    mode: RecoveryMode,
+    chunk_count: usize,
+    concurrency_limit: ConcurrencyLimits,
+    /// Recovery parameters changed at runtime. The maximum concurrency is changed using
+    /// [`AdaptiveConcurrency::set_max()`], and the background write rate limit using
+    /// [`AsyncTreeRecovery::set_background_write_rate_limit()`].
+    overrides: RecoveryOverrides,
+    max_chunk_attempts: usize,
+    /// Delays between retries of a failed chunk.
+    chunk_retry_delays: ChunkRetryDelays,
+    /// If set, recovery fails on the first chunk error. Otherwise, remaining chunks are still recovered,
+    /// and errors of all failed chunks are combined (see [`FailedChunks`]).
+    fail_fast: bool,
+    /// If set, chunks are applied to the tree in sub-chunks with the specified number of entries,
+    /// so that chunk recovery can be resumed mid-way after a restart.
+    sub_chunk_size: Option<usize>,
+    /// If set and the entry source supports it, chunk entries are loaded and applied to the tree in batches
+    /// with the specified number of entries (see [`RecoveryEntrySource::load_entries_batch()`]) instead
+    /// of being loaded at once. Takes precedence over `sub_chunk_size`.
+    streaming_batch_size: Option<usize>,
+    /// If set, soft cap (in bytes) on the total size of loaded entries not yet applied to the tree
+    /// (see [`LoadedEntriesBudget`]).
+    loaded_entries_soft_cap: Option<usize>,
+    /// Interval between manual flushes of the tree RocksDB memtables (see [`FlushTracker`]).
+    flush_interval: FlushInterval,
+    /// If set, limits the rate at which entries are loaded and applied to the tree (see [`EntryThrottle`]).
+    max_entries_per_second: Option<usize>,
+    /// If set, no new chunks are started once this duration has elapsed since recovery was started, and recovery
+    /// fails with [`RecoveryError::DeadlineExceeded`] after chunks in flight are recovered.
+    max_duration: Option<Duration>,
+    /// If set, no new chunks are started once a newer snapshot appears in Postgres, and recovery fails
+    /// with [`RecoveryError::SnapshotSuperseded`] after chunks in flight are recovered.
+    latest_snapshot_check: Option<LatestSnapshotCheck<'a>>,
+    /// Whether to recover chunks with the largest estimated number of entries first.
+    prioritize_large_chunks: bool,
+    /// If set, disk space required for recovery is checked before recovering chunks.
+    disk_space_check: Option<DiskSpaceCheck<'a>>,
+    /// If set, the recovered tree is verified by comparing the specified number of entries sampled from each chunk
+    /// with Postgres.
+    verification_samples_per_chunk: Option<usize>,
+    /// If set, Merkle proofs for the specified number of sampled entries are verified after recovery is finalized.
+    proof_verification_samples: Option<usize>,
+    /// If set, a root hash mismatch is diagnosed reporting up to the specified number of diverging keys per chunk
+    /// (see [`diagnose_root_hash_mismatch()`]).
+    mismatch_diagnostic_keys_per_chunk: Option<usize>,
+    /// If set, a watchdog reports recovery stalls (see [`RecoveryWatchdog`]).
+    watchdog: Option<WatchdogOptions>,
+    /// If set, the tree root hash is recorded in this log after each recovered chunk.
+    fingerprint_log: Option<RecoveryFingerprintLog>,
+    /// If set, recovered chunks and the recovery summary are recorded in Postgres (see [`RecoveryProgressTable`]).
+    progress_table: Option<RecoveryProgressTable<'a>>,
+    /// Maximum number of chunks processed at once when filtering out recovered chunks.
+    chunk_filter_batch_size: usize,
+    /// Whether to always check ends of recovered chunks when filtering them (see [`AsyncTreeRecovery::filter_chunks()`]).
+    /// Ends are checked regardless of this option if tree writes may have been lost.
+    check_chunk_ends: bool,
+    /// Whether to repair recovered chunks with tree entries mismatching Postgres when filtering them
+    /// (see [`AsyncTreeRecovery::repair_chunk()`]) instead of failing recovery.
+    repair_mismatched_chunks: bool,
+    /// **Unsafe.** Whether to finalize recovery despite a root hash mismatch, marking the tree as tainted
+    /// (see [`TreeTaint`]) instead of failing recovery.
+    skip_root_hash_check: bool,
+    /// If set, recovered chunks are filtered using this read replica (falling back to the primary
+    /// if the replica lags behind or fails).
+    replica: Option<&'a SnapshotReplica<'a>>,
+    /// Total duration of retrying to acquire a Postgres connection (see [`access_storage_with_retries()`]).
+    connection_retry_timeout: Duration,
+    /// Timeout for a single attempt to acquire a Postgres connection.
+    connection_acquire_timeout: Duration,
+    /// Source of snapshot entries. If not set, entries are loaded from Postgres by [`AsyncTreeRecovery::recover()`].
+    entry_source: Option<Box<dyn RecoveryEntrySource + 'a>>,
+    events: Arc<dyn HandleRecoveryEvent + 'a>,
+}
+
+impl<'a> RecoveryOptions<'a> {
+    /// Creates options for recovering the tree in `chunk_count` chunks. Chunks are recovered one at a time
+    /// with the default number of attempts and Postgres connection timeouts, and recovery events are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_count` is zero.
+    pub fn new(chunk_count: usize) -> Self {
+        assert!(
+            chunk_count > 0,
+            "Number of recovery chunks must be positive"
+        );
+        let config = MetadataCalculatorRecoveryConfig::default();
+        Self {
+            mode: RecoveryMode::Normal,
+            chunk_count,
+            concurrency_limit: ConcurrencyLimits::fixed(1),
+            overrides: RecoveryOverrides::default(),
+            max_chunk_attempts: config.max_chunk_attempts,
+            chunk_retry_delays: ChunkRetryDelays::new(&config),
+            fail_fast: false,
+            sub_chunk_size: None,
+            streaming_batch_size: None,
+            loaded_entries_soft_cap: None,
+            flush_interval: FlushInterval::default(),
+            max_entries_per_second: None,
+            max_duration: None,
+            latest_snapshot_check: None,
+            prioritize_large_chunks: false,
+            disk_space_check: None,
+            verification_samples_per_chunk: None,
+            proof_verification_samples: None,
+            mismatch_diagnostic_keys_per_chunk: None,
+            watchdog: None,
+            fingerprint_log: None,
+            progress_table: None,
+            chunk_filter_batch_size: config.chunk_filter_batch_size,
+            check_chunk_ends: false,
+            repair_mismatched_chunks: false,
+            skip_root_hash_check: false,
+            replica: None,
+            connection_retry_timeout: config.connection_retry_timeout,
+            connection_acquire_timeout: config.connection_acquire_timeout,
+            entry_source: None,
+            events: Arc::new(NoopRecoveryEvents),
+        }
+    }
+
+    /// Sets the fixed number of concurrently recovered chunks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `concurrency` is zero.
+    #[must_use]
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        assert!(concurrency > 0, "Recovery concurrency must be positive");
+        self.concurrency_limit = ConcurrencyLimits::fixed(concurrency);
+        self
+    }
+
+    /// Sets the maximum number of attempts to recover a single chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `attempts` is zero.
+    #[must_use]
+    pub fn max_chunk_attempts(mut self, attempts: usize) -> Self {
+        assert!(attempts > 0, "Number of chunk attempts must be positive");
+        self.max_chunk_attempts = attempts;
+        self
+    }
+
+    /// Sets whether recovery should fail on the first chunk error. By default, remaining chunks are still recovered,
+    /// and errors of all failed chunks are reported together.
+    #[must_use]
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Applies chunks to the tree in sub-chunks with the specified number of entries, so that chunk recovery
+    /// can be resumed mid-way after a restart.
+    #[must_use]
+    pub fn sub_chunk_size(mut self, size: usize) -> Self {
+        self.sub_chunk_size = Some(size);
+        self
+    }
+
+    /// Limits the rate at which snapshot entries are loaded and applied to the tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is zero.
+    #[must_use]
+    pub fn max_entries_per_second(mut self, limit: usize) -> Self {
+        assert!(limit > 0, "Entry rate limit must be positive");
+        self.max_entries_per_second = Some(limit);
+        self
+    }
+
+    /// Sets the maximum duration of recovery. Once it has elapsed, no new chunks are started, and recovery fails
+    /// with [`RecoveryError::DeadlineExceeded`] after chunks in flight are recovered.
+    #[must_use]
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    /// Sets the channel used to pause and resume recovery (see [`RecoveryControlHandle`]).
+    #[must_use]
+    pub fn control(mut self, control: watch::Receiver<RecoveryControl>) -> Self {
+        self.overrides.control = Some(control);
+        self
+    }
+
+    /// Sets the handler of recovery events. The handler may be shared with the caller, e.g. to inspect
+    /// recovery progress.
+    #[must_use]
+    pub fn events(mut self, events: Arc<dyn HandleRecoveryEvent + 'a>) -> Self {
+        self.events = events;
+        self
+    }
+
+    fn entry_source(&self) -> &dyn RecoveryEntrySource {
+        self.entry_source
+            .as_deref()
+            .expect("entry source is set in `AsyncTreeRecovery::recover()`")
+    }
+}
+
+/// [`HandleRecoveryEvent`] implementation ignoring all events.
+#[derive(Debug)]
+struct NoopRecoveryEvents;
+
+impl HandleRecoveryEvent for NoopRecoveryEvents {}
+
+/// Source of snapshot entries for tree recovery.
+#[async_trait]
+trait RecoveryEntrySource: fmt::Debug + Send + Sync {
+    /// Returns hashed key ranges for `chunk_count` recovery chunks. Chunks must only depend
+    /// on the immutable snapshot data, so that they are defined in the same way across recovery restarts.
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>>;
+
+    /// Returns the (possibly estimated) number of entries in each of `key_chunks`. Returns `None` if the source
+    /// doesn't support estimation.
+    async fn estimate_entry_counts(
+        &self,
+        _key_chunks: &[ops::RangeInclusive<H256>],
+    ) -> anyhow::Result<Option<Vec<u64>>> {
+        Ok(None)
+    }
+
+    /// Loads entries for the chunk with the specified ID and hashed key range. Entries must be sorted by key.
+    /// Returns `None` if loading was interrupted by a stop signal.
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>>;
+
+    /// Returns whether the source supports loading entries in batches (see [`Self::load_entries_batch()`]).
+    fn supports_batches(&self) -> bool {
+        false
+    }
+
+    /// Loads up to `limit` entries for the chunk with the specified ID and hashed key range with hashed keys
+    /// strictly greater than `after_key` (if specified). Entries must be sorted by the hashed key (which is different
+    /// from sorting by the tree key). Returns `None` if loading was interrupted by a stop signal.
+    async fn load_entries_batch(
+        &self,
+        _chunk_id: usize,
+        _key_chunk: &ops::RangeInclusive<H256>,
+        _after_key: Option<H256>,
+        _limit: usize,
+        _stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        anyhow::bail!("entry source doesn't support loading entries in batches")
+    }
+}
+
+/// Loads snapshot entries from Postgres. If a read replica is supplied, chunk entries are loaded from it
+/// unless it lags behind the snapshot or a replica query fails.
+#[derive(Debug)]
+struct PostgresEntrySource<'a> {
+    pool: &'a ConnectionPool,
+    replica: Option<&'a SnapshotReplica<'a>>,
+    snapshot_miniblock: MiniblockNumber,
+    /// Total duration of retrying to acquire a connection for loading chunk entries.
+    connection_retry_timeout: Duration,
+    /// Timeout for a single attempt to acquire a connection for loading chunk entries.
+    connection_acquire_timeout: Duration,
+    /// Whether to load entries of entire chunks using binary `COPY` (see [`Self::copy_entries_from()`]).
+    use_copy: bool,
+}
+
+#[async_trait]
+impl RecoveryEntrySource for PostgresEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        let mut storage = self.pool.access_storage().await?;
+        AsyncTreeRecovery::key_ranges(&mut storage, self.snapshot_miniblock, chunk_count).await
+    }
+
+    async fn estimate_entry_counts(
+        &self,
+        key_chunks: &[ops::RangeInclusive<H256>],
+    ) -> anyhow::Result<Option<Vec<u64>>> {
+        let mut storage = self.pool.access_storage().await?;
+        let latency = RECOVERY_METRICS.latency[&RecoveryStage::CountChunkEntries].start();
+        let entry_counts = storage
+            .storage_logs_dal()
+            .count_storage_logs_in_ranges(self.snapshot_miniblock, key_chunks)
+            .await
+            .context("Failed counting entries in chunks")?;
+        let latency = latency.observe();
+        tracing::debug!(
+            "Counted entries in {} chunks for miniblock #{} in {latency:?}",
+            key_chunks.len(),
+            self.snapshot_miniblock
+        );
+        Ok(Some(entry_counts))
+    }
+
+    async fn load_entries(
+        &self,
+        _chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        if let Some(replica_pool) = self.replica_pool().await {
+            match self
+                .load_entries_from(replica_pool, key_chunk, stop_receiver)
+                .await
+            {
+                Ok(entries) => return Ok(entries),
+                Err(err) => self.fall_back("loading chunk entries", &err),
+            }
+        }
+        self.load_entries_from(self.pool, key_chunk, stop_receiver)
+            .await
+    }
+
+    fn supports_batches(&self) -> bool {
+        true
+    }
+
+    async fn load_entries_batch(
+        &self,
+        _chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        after_key: Option<H256>,
+        limit: usize,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        if let Some(replica_pool) = self.replica_pool().await {
+            match self
+                .load_entries_batch_from(replica_pool, key_chunk, after_key, limit, stop_receiver)
+                .await
+            {
+                Ok(entries) => return Ok(entries),
+                Err(err) => self.fall_back("loading batch of chunk entries", &err),
+            }
+        }
+        self.load_entries_batch_from(self.pool, key_chunk, after_key, limit, stop_receiver)
+            .await
+    }
+}
+
+impl PostgresEntrySource<'_> {
+    /// Returns the replica pool if the replica is supplied and is synced with the primary.
+    async fn replica_pool(&self) -> Option<&ConnectionPool> {
+        match self.replica {
+            Some(replica) => replica.pool().await,
+            None => None,
+        }
+    }
+
+    fn fall_back(&self, operation: &str, err: &anyhow::Error) {
+        if let Some(replica) = self.replica {
+            replica.fall_back(operation, err);
+        }
+    }
+
+    async fn load_entries_from(
+        &self,
+        pool: &ConnectionPool,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        if self.use_copy {
+            match self.copy_entries_from(pool, key_chunk, stop_receiver).await {
+                Ok(entries) => return Ok(entries),
+                Err(err) => {
+                    tracing::warn!(
+                        "Failed loading entries for chunk {key_chunk:?} using COPY, falling back to query: {err:#}"
+                    );
+                    RECOVERY_METRICS.copy_fallbacks.inc();
+                }
+            }
+        }
+        self.query_entries_from(pool, key_chunk, stop_receiver)
+            .await
+    }
+
+    /// Loads entries for the chunk using binary `COPY`, which is faster than a query for large chunks,
+    /// but may be unavailable because of insufficient permissions.
+    async fn copy_entries_from(
+        &self,
+        pool: &ConnectionPool,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        let connection = self.connection(pool, key_chunk, stop_receiver).await?;
+        let Some((mut storage, backend_pid)) = connection else {
+            return Ok(None);
+        };
+        let snapshot_miniblock = self.snapshot_miniblock;
+        let entries = storage
+            .storage_logs_dal()
+            .copy_tree_entries_for_miniblock(snapshot_miniblock, key_chunk.clone());
+        let entries = run_until_stopped(entries, stop_receiver).await;
+        let Some(entries) = entries else {
+            Self::cancel_query(pool, storage, backend_pid).await;
+            return Ok(None);
+        };
+        let entries = match entries {
+            Ok(entries) => entries,
+            Err(err) => {
+                // The connection may be left in the middle of `COPY` output, so it's not returned to the pool.
+                if let Err(close_err) = storage.close().await {
+                    tracing::warn!(
+                        "Failed closing Postgres connection after failed COPY: {close_err}"
+                    );
+                }
+                return Err(anyhow::Error::from(err).context(format!(
+                    "Failed copying entries for chunk {key_chunk:?} in snapshot for miniblock #{snapshot_miniblock}"
+                )));
+            }
+        };
+        let entries = convert_entries(entries, |entry| TreeEntry {
+            key: entry.key,
+            value: entry.value,
+            leaf_index: entry.leaf_index,
+        });
+        Ok(Some(entries.await?))
+    }
+
+    async fn query_entries_from(
+        &self,
+        pool: &ConnectionPool,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        let connection = self.connection(pool, key_chunk, stop_receiver).await?;
+        let Some((mut storage, backend_pid)) = connection else {
+            return Ok(None);
+        };
+        let snapshot_miniblock = self.snapshot_miniblock;
+        let entries = storage
+            .storage_logs_dal()
+            .get_tree_entries_for_miniblock(snapshot_miniblock, key_chunk.clone());
+        // The query future must be dropped before cancelling the query since it borrows the connection.
+        let entries = run_until_stopped(entries, stop_receiver).await;
+        let Some(entries) = entries else {
+            Self::cancel_query(pool, storage, backend_pid).await;
+            return Ok(None);
+        };
+        let entries = entries.with_context(|| {
+            format!("Failed getting entries for chunk {key_chunk:?} in snapshot for miniblock #{snapshot_miniblock}")
+        })?;
+        let entries = convert_entries(entries, |entry| TreeEntry {
+            key: entry.key,
+            value: entry.value,
+            leaf_index: entry.leaf_index,
+        });
+        Ok(Some(entries.await?))
+    }
+
+    async fn load_entries_batch_from(
+        &self,
+        pool: &ConnectionPool,
+        key_chunk: &ops::RangeInclusive<H256>,
+        after_key: Option<H256>,
+        limit: usize,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        let connection = self.connection(pool, key_chunk, stop_receiver).await?;
+        let Some((mut storage, backend_pid)) = connection else {
+            return Ok(None);
+        };
+        let snapshot_miniblock = self.snapshot_miniblock;
+        let entries = storage
+            .storage_logs_dal()
+            .get_tree_entries_for_miniblock_paged(
+                snapshot_miniblock,
+                key_chunk.clone(),
+                after_key,
+                limit,
+            );
+        let entries = run_until_stopped(entries, stop_receiver).await;
+        let Some(entries) = entries else {
+            Self::cancel_query(pool, storage, backend_pid).await;
+            return Ok(None);
+        };
+        let entries = entries.with_context(|| {
+            format!(
+                "Failed getting batch of entries for chunk {key_chunk:?} after key {after_key:?} in snapshot \
+                 for miniblock #{snapshot_miniblock}"
+            )
+        })?;
+        let entries = entries.into_iter().map(|entry| TreeEntry {
+            key: entry.key,
+            value: entry.value,
+            leaf_index: entry.leaf_index,
+        });
+        Ok(Some(entries.collect()))
+    }
+
+    /// Acquires a connection for loading entries together with its Postgres backend PID, which is used to cancel
+    /// the query if it's interrupted by a stop signal. Failures to acquire a connection are retried
+    /// (see [`access_storage_with_retries()`]). Returns `None` if a stop signal was received
+    /// while acquiring the connection.
+    async fn connection<'p>(
+        &self,
+        pool: &'p ConnectionPool,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<(StorageProcessor<'p>, i32)>> {
+        let acquire_connection_latency =
+            RECOVERY_METRICS.chunk_latency[&ChunkRecoveryStage::AcquireConnection].start();
+        let target = format!("chunk {key_chunk:?}");
+        let storage = access_storage_with_retries(
+            pool,
+            &target,
+            self.connection_retry_timeout,
+            self.connection_acquire_timeout,
+            stop_receiver,
+        )
+        .await?;
+        let Some(mut storage) = storage else {
+            return Ok(None);
+        };
+        acquire_connection_latency.observe();
+        let backend_pid = storage
+            .system_dal()
+            .get_backend_pid()
+            .await
+            .context("Failed getting Postgres backend PID")?;
+        Ok(Some((storage, backend_pid)))
+    }
+
+    /// Cancels a query interrupted by a stop signal. Just dropping the query future is not enough: Postgres
+    /// would continue executing the query, and the connection would remain busy until then. Hence, the connection
+    /// is closed instead of being returned to the pool, and the query is cancelled using another connection.
+    /// Errors are logged rather than returned since they don't influence recovery.
+    async fn cancel_query(pool: &ConnectionPool, storage: StorageProcessor<'_>, backend_pid: i32) {
+        if let Err(err) = storage.close().await {
+            tracing::warn!("Failed closing Postgres connection with interrupted query: {err}");
+        }
+        let cancel_result = async {
+            let mut storage = pool.access_storage().await?;
+            let cancelled = storage
+                .system_dal()
+                .cancel_backend_query(backend_pid)
+                .await?;
+            anyhow::Ok(cancelled)
+        };
+        match cancel_result.await {
+            Ok(cancelled) => tracing::info!(
+                "Cancelled interrupted query for Postgres backend {backend_pid} (success: {cancelled})"
+            ),
+            Err(err) => tracing::warn!(
+                "Failed cancelling interrupted query for Postgres backend {backend_pid}: {err:#}"
+            ),
+        }
+    }
+}
+
+/// Cursor over snapshot entries with hashed keys in a certain range, which loads entries from Postgres in pages
+/// using keyset pagination on the hashed key. Unlike a single query for the entire range, loading a page
+/// takes bounded time, so it doesn't run into statement timeouts for large ranges. A connection is supplied
+/// for each page, so that the cursor doesn't hold a connection between pages.
+#[derive(Debug)]
+struct SnapshotEntryPages {
+    snapshot_miniblock: MiniblockNumber,
+    key_range: ops::RangeInclusive<H256>,
+    /// Hashed key of the last loaded entry.
+    after_key: Option<H256>,
+    page_size: usize,
+    is_finished: bool,
+}
+
+impl SnapshotEntryPages {
+    fn new(
+        snapshot_miniblock: MiniblockNumber,
+        key_range: ops::RangeInclusive<H256>,
+        page_size: usize,
+    ) -> Self {
+        Self {
+            snapshot_miniblock,
+            key_range,
+            after_key: None,
+            page_size: page_size.max(1),
+            is_finished: false,
+        }
+    }
+
+    /// Makes the cursor start after the specified hashed key (e.g., the last key processed before a restart).
+    fn resume_after(mut self, after_key: Option<H256>) -> Self {
+        self.after_key = after_key;
+        self
+    }
+
+    /// Loads the next page of entries sorted by the hashed key. Returns `None` once all entries in the range
+    /// are loaded.
+    async fn next_page(
+        &mut self,
+        storage: &mut StorageProcessor<'_>,
+    ) -> Result<Option<Vec<TreeEntry>>, SqlxError> {
+        if self.is_finished {
+            return Ok(None);
+        }
+        let page = storage
+            .storage_logs_dal()
+            .get_tree_entries_for_miniblock_paged(
+                self.snapshot_miniblock,
+                self.key_range.clone(),
+                self.after_key,
+                self.page_size,
+            )
+            .await?;
+        self.is_finished = page.len() < self.page_size;
+        let Some(last_entry) = page.last() else {
+            return Ok(None);
+        };
+        self.after_key = Some(hashed_key(&last_entry.key));
+
+        let page = page.into_iter().map(|entry| TreeEntry {
+            key: entry.key,
+            value: entry.value,
+            leaf_index: entry.leaf_index,
+        });
+        Ok(Some(page.collect()))
+    }
+}
+
+/// Loads snapshot entries from storage log chunks in the object store. Chunk key ranges are defined
+/// by the snapshot creator; they split the hashed key space into equal-width ranges.
+#[derive(Debug)]
+struct ObjectStoreEntrySource<'a> {
+    object_store: &'a dyn ObjectStore,
+    l1_batch_number: L1BatchNumber,
+    chunk_count: usize,
+}
+
+#[async_trait]
+impl RecoveryEntrySource for ObjectStoreEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        anyhow::ensure!(
+            chunk_count == self.chunk_count,
+            "Requested {chunk_count} chunks, but snapshot for L1 batch #{} has {} storage log chunks \
+             in the object store",
+            self.l1_batch_number,
+            self.chunk_count
+        );
+        Ok(AsyncTreeRecovery::hashed_key_ranges(chunk_count).collect())
+    }
+
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        let storage_key = SnapshotStorageLogsStorageKey {
+            l1_batch_number: self.l1_batch_number,
+            chunk_id: chunk_id as u64,
+        };
+        let chunk = self
+            .object_store
+            .get::<SnapshotStorageLogsChunk>(storage_key);
+        let Some(chunk) = run_until_stopped(chunk, stop_receiver).await else {
+            return Ok(None);
+        };
+        let chunk = chunk.with_context(|| {
+            format!("Failed getting storage logs chunk {storage_key:?} from object store")
+        })?;
+
+        let mut entries = Vec::with_capacity(chunk.storage_logs.len());
+        for log in chunk.storage_logs {
+            let hashed_key = log.key.hashed_key();
+            if !key_chunk.contains(&hashed_key) {
+                return Err(RecoveryError::CorruptedSnapshot {
+                    key: hashed_key,
+                    details: format!(
+                        "storage logs chunk {storage_key:?} in object store contains the key \
+                         outside of the chunk range {key_chunk:?}"
+                    ),
+                }
+                .into());
+            }
+            entries.push(TreeEntry {
+                key: log.key.hashed_key_u256(),
+                value: log.value,
+                leaf_index: log.enumeration_index,
+            });
+        }
+        entries.sort_unstable_by_key(|entry| entry.key);
+        Ok(Some(entries))
+    }
+}
+
+/// Kind of [`RecoveryEntrySource`] used for recovery. Persisted in the tree manifest, since chunks are defined
+/// differently for different sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecoveryEntrySourceKind {
+    Postgres,
+    ObjectStore,
+    File,
+}
+
+impl RecoveryEntrySourceKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Postgres => "postgres",
+            Self::ObjectStore => "object_store",
+            Self::File => "file",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Postgres => "Postgres",
+            Self::ObjectStore => "snapshot object store",
+            Self::File => "snapshot file",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "postgres" => Some(Self::Postgres),
+            "object_store" => Some(Self::ObjectStore),
+            "file" => Some(Self::File),
+            _ => None,
+        }
+    }
+}
+
+/// Dedicated Postgres connection pools used for heavy snapshot queries during recovery.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecoveryPools<'a> {
+    /// Pool used to load snapshot chunks instead of the main pool.
+    pub recovery: Option<&'a ConnectionPool>,
+    /// Read replica preferred for snapshot queries (see [`SnapshotReplica`]).
+    pub replica: Option<&'a ConnectionPool>,
+}
+
+/// Run-wide inputs of [`GenericAsyncTree::ensure_ready()`].
+#[derive(Debug)]
+pub struct EnsureReadyContext<'a> {
+    /// Main Postgres connection pool.
+    pub pool: &'a ConnectionPool,
+    /// Dedicated connection pools used for heavy snapshot queries instead of the main pool.
+    pub pools: RecoveryPools<'a>,
+    /// Object store with snapshot chunks, if the snapshot is stored there.
+    pub snapshot_object_store: Option<&'a dyn ObjectStore>,
+    pub stop_receiver: &'a watch::Receiver<bool>,
+    pub health_updater: &'a HealthUpdater,
+    /// Sender to publish recovery progress to.
+    pub recovery_status: &'a watch::Sender<Option<RecoveryStatus>>,
+    /// Listeners recovery events are forwarded to.
+    pub recovery_listeners: Vec<Box<dyn HandleRecoveryEvent>>,
+    /// Overrides for recovery parameters changed at runtime.
+    pub overrides: RecoveryOverrides,
+}
+
+impl GenericAsyncTree {
+    /// Ensures that the tree is ready for the normal operation, recovering it from a Postgres snapshot
+    /// if necessary. Returns the tree together with a report on recovery. If recovery was stopped before completion,
+    /// no tree is returned, and the report is [`RecoveryReport::Interrupted`].
+    pub async fn ensure_ready(
+        self,
+        config: &MetadataCalculatorRecoveryConfig,
+        context: EnsureReadyContext<'_>,
+    ) -> Result<(Option<AsyncTree>, RecoveryReport), RecoveryError> {
+        let health_updater = context.health_updater;
+        let recovery_status = context.recovery_status;
+        let result = self.ensure_ready_inner(config, context).await;
+        if let Err(RecoveryError::PreviouslyFailed(failure)) = &result {
+            let details = PreviousRecoveryFailureInfo {
+                mode: "recovery_failed",
+                error_kind: RecoveryErrorKind::PreviouslyFailed,
+                message: "manual intervention required: inspect the Merkle tree and reset it \
+                          to restart recovery",
+                recovery_failure: *failure,
+            };
+            health_updater.update(Health::from(HealthStatus::NotReady).with_details(details));
+        } else if let Err(err) = &result {
+            // Other errors either don't relate to recovery, or are reported by `RecoveryHealthUpdater`.
+            let error_kind = err.kind();
+            if matches!(
+                error_kind,
+                RecoveryErrorKind::SnapshotMissing
+                    | RecoveryErrorKind::SnapshotIncomplete
+                    | RecoveryErrorKind::InvalidSnapshotParameters
+            ) {
+                let health =
+                    Health::from(HealthStatus::NotReady).with_details(RecoveryStartFailureInfo {
+                        mode: RecoveryMode::Normal.health_mode(),
+                        error_kind,
+                    });
+                health_updater.update(health);
+            }
+        }
+        match result {
+            Ok((tree, report)) => Ok((Some(tree), report)),
+            Err(RecoveryError::Interrupted) => {
+                let report = RecoveryReport::interrupted(recovery_status.borrow().as_ref());
+                Ok((None, report))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn ensure_ready_inner(
+        mut self,
+        config: &MetadataCalculatorRecoveryConfig,
+        context: EnsureReadyContext<'_>,
+    ) -> Result<(AsyncTree, RecoveryReport), RecoveryError> {
+        let EnsureReadyContext {
+            pool,
+            pools,
+            snapshot_object_store,
+            stop_receiver,
+            health_updater,
+            recovery_status,
+            recovery_listeners,
+            overrides,
+        } = context;
+        // Fail fast instead of repeating recovery that has already failed; this doesn't require Postgres.
+        if let Some(failure) = self.recovery_failure().await? {
+            return Err(RecoveryError::PreviouslyFailed(failure));
+        }
+        wait_for_snapshot(config, pool, stop_receiver, health_updater).await?;
+        self = self.ensure_same_genesis(config, pool).await?;
+        self = self.reset_if_unusable(config, pool).await?;
+        let chunk_pool = pools.recovery.unwrap_or(pool);
+        let state = self.state();
+        // Gather all inputs before branching, so that the decision can be logged.
+        let (target, snapshot_l1_batch) = if matches!(self, Self::Ready(_)) {
+            // The recovery target is irrelevant for a ready tree, so only the Postgres snapshot is reported.
+            (None, get_snapshot_l1_batch(pool).await?)
+        } else {
+            let recovered_version = match &self {
+                Self::Recovering(tree) => Some(tree.recovered_version()),
+                _ => None,
+            };
+            let target = get_recovery_target(config, pool, recovered_version).await?;
+            let snapshot_l1_batch = target
+                .as_ref()
+                .map(|target| target.snapshot_recovery.l1_batch_number);
+            (target, snapshot_l1_batch)
+        };
+        let decision = StartupDecision::new(state, snapshot_l1_batch, config);
+        decision.log();
+        // Publish the tree state before doing any potentially long work (e.g., recovering chunks or pruning the tree).
+        let details = StartupHealthDetails {
+            state,
+            decision: &decision,
+        };
+        health_updater.update(Health::from(HealthStatus::NotReady).with_details(details));
+        let snapshot_file = match &config.recovery_file {
+            Some(path) if !matches!(self, Self::Ready(_)) => {
+                let snapshot_file = FileSnapshotSource::open(path).await?;
+                tracing::info!(
+                    "Using snapshot file `{}` for Merkle tree recovery: {:?}",
+                    path.display(),
+                    snapshot_file.header()
+                );
+                Some(snapshot_file)
+            }
+            _ => None,
+        };
+        if config.dry_run && !matches!(self, Self::Ready(_)) {
+            if let Some(target) = &target {
+                dry_run_recovery(
+                    config,
+                    &target.snapshot_recovery,
+                    pool,
+                    pools,
+                    target.object_store(snapshot_object_store),
+                    snapshot_file.as_ref(),
+                    overrides.clone(),
+                    stop_receiver,
+                    health_updater,
+                )
+                .await?;
+                if config.stop_after_dry_run {
+                    tracing::info!("Stopping Merkle tree after dry-run recovery as configured");
+                    return Err(RecoveryError::Interrupted);
+                }
+            }
+        }
+
+        let (mut tree, mut target) = match self {
+            Self::Ready(mut tree) => {
+                resume_export(&tree, config, pool, health_updater).await?;
+                upgrade_to_full(&mut tree, config, pool).await?;
+                prune_if_configured(&tree, config, false, stop_receiver).await?;
+                return Ok((tree, RecoveryReport::NotNeeded));
+            }
+            Self::Recovering(tree) => {
+                let target = target.ok_or_else(|| {
+                    RecoveryError::SnapshotMissing(
+                        "Merkle tree is recovering, but Postgres doesn't contain snapshot recovery information"
+                            .to_owned(),
+                    )
+                })?;
+                let l1_batch = target.snapshot_recovery.l1_batch_number;
+                let recovered_version = tree.recovered_version();
+                if u64::from(l1_batch.0) != recovered_version {
+                    let err = if config.target_l1_batch.is_some() {
+                        anyhow::anyhow!(
+                            "Merkle tree is being recovered to L1 batch #{recovered_version}, which differs from \
+                             the configured target L1 batch #{l1_batch}; update the config, or enable `allow_tree_reset` \
+                             in the config to remove the tree and recover it from scratch"
+                        )
+                    } else {
+                        anyhow::anyhow!(
+                            "Snapshot L1 batch in Postgres ({l1_batch}) differs from the recovered Merkle tree version \
+                             ({recovered_version})"
+                        )
+                    };
+                    return Err(err.into());
+                }
+                (tree, target)
+            }
+            Self::Empty { db, mode } => {
+                if let Some(target) = target {
+                    if let Some(import_path) = &config.import_path {
+                        let snapshot_recovery = &target.snapshot_recovery;
+                        let imported =
+                            import_exported_tree(db.path(), mode, import_path, snapshot_recovery)
+                                .await;
+                        match imported {
+                            Ok(staged) => {
+                                let tree = staged.install(db).await?;
+                                return Ok((tree, RecoveryReport::NotNeeded));
+                            }
+                            Err(err) if config.strict_import => return Err(err.into()),
+                            Err(err) => tracing::warn!(
+                                "{err:#}; proceeding with Merkle tree recovery from Postgres"
+                            ),
+                        }
+                    }
+                    let l1_batch = target.snapshot_recovery.l1_batch_number;
+                    let mut tree = AsyncTreeRecovery::new(db, l1_batch.0.into(), mode);
+                    if let Some(genesis) =
+                        PostgresGenesis::load(pool, Some(l1_batch.0.into())).await?
+                    {
+                        tree.check_postgres_genesis(genesis).await?;
+                    }
+                    (tree, target)
+                } else {
+                    // Start the tree from scratch. The genesis block will be filled in `TreeUpdater::loop_updating_tree()`.
+                    return Ok((AsyncTree::new(db, mode), RecoveryReport::NotNeeded));
+                }
+            }
+        };
+
+        tree.check_mode().await?;
+        tree.check_format().await?;
+        if let Some(profile) = recovery_db_profile(config) {
+            tracing::info!(
+                "Tuning Merkle tree RocksDB for bulk loading during recovery: {profile:?}"
+            );
+            tree.use_db_profile(profile).await?;
+        }
+        if config.relaxed_durability {
+            tracing::info!(
+                "Disabling write-ahead log of Merkle tree RocksDB during recovery; writes will be flushed \
+                 when recovery is finalized"
+            );
+            tree.relax_durability().await?;
+        }
+        if let Some(rate_limit) = config.background_write_rate_limit {
+            tracing::info!(
+                "Limiting background writes of Merkle tree RocksDB during recovery to {rate_limit} bytes/s"
+            );
+            tree.set_background_write_rate_limit(Some(rate_limit))
+                .await?;
+        }
+
+        // If the snapshot is superseded during recovery, the tree is reset and recovery is restarted
+        // for the newer snapshot.
+        let (tree, report, snapshot) = loop {
+            let snapshot_recovery = &target.snapshot_recovery;
+            let replica = pools.replica.map(|replica_pool| {
+                SnapshotReplica::new(chunk_pool, replica_pool, snapshot_recovery.miniblock_number)
+            });
+            let snapshot = match &snapshot_file {
+                Some(snapshot_file) => {
+                    snapshot_file
+                        .snapshot_parameters(pool, snapshot_recovery)
+                        .await?
+                }
+                None => SnapshotParameters::new(pool, replica.as_ref(), snapshot_recovery).await?,
+            };
+            tracing::debug!("Obtained snapshot parameters: {snapshot:?}");
+            let (mut chunk_count, mut entry_source) = tree
+                .entry_source(
+                    config,
+                    &snapshot,
+                    snapshot_recovery,
+                    chunk_pool,
+                    replica.as_ref(),
+                    target.object_store(snapshot_object_store),
+                    snapshot_file.as_ref(),
+                )
+                .await?;
+            let plan = ChunkPlan {
+                log_count: snapshot.log_count,
+                chunk_count,
+            };
+            let mut check_result = tree.check_chunk_plan(plan).await;
+            if check_result.is_ok() {
+                let fingerprint = SnapshotFingerprint::new(&snapshot, chunk_count);
+                check_result = tree.check_snapshot_fingerprint(fingerprint).await;
+            }
+            if let Err(err) = check_result {
+                if !config.force_replan {
+                    return Err(err.into());
+                }
+                tracing::warn!(
+                    "{err:#}; wiping Merkle tree and restarting recovery from scratch as configured"
+                );
+                tree = tree.reset().await?;
+                tree.check_mode().await?;
+                tree.check_format().await?;
+                (chunk_count, entry_source) = tree
+                    .entry_source(
+                        config,
+                        &snapshot,
+                        snapshot_recovery,
+                        chunk_pool,
+                        replica.as_ref(),
+                        target.object_store(snapshot_object_store),
+                        snapshot_file.as_ref(),
+                    )
+                    .await?;
+                let plan = ChunkPlan {
+                    log_count: snapshot.log_count,
+                    chunk_count,
+                };
+                tree.check_chunk_plan(plan).await?;
+                let fingerprint = SnapshotFingerprint::new(&snapshot, chunk_count);
+                tree.check_snapshot_fingerprint(fingerprint).await?;
+            }
+            if let Some(thread_count) = config.hashing_threads {
+                tracing::info!(
+                    "Using {thread_count} threads to hash Merkle tree nodes during recovery"
+                );
+                tree.use_dedicated_thread_pool(thread_count)?;
+            }
+
+            let health_events = RecoveryHealthUpdater::new(
+                health_updater,
+                RecoveryMode::Normal,
+                snapshot.log_count,
+            )
+            .with_health_update_interval(config.health_update_interval)
+            .with_status_sender(recovery_status, snapshot_recovery.l1_batch_number)
+            .with_catch_up_before_ready(config.catch_up_before_ready);
+            let recovery_options = RecoveryOptions {
+                mode: RecoveryMode::Normal,
+                chunk_count,
+                concurrency_limit: concurrency_limit(config, chunk_pool)?,
+                overrides: overrides.clone(),
+                max_chunk_attempts: config.max_chunk_attempts,
+                chunk_retry_delays: ChunkRetryDelays::new(config),
+                fail_fast: false,
+                sub_chunk_size: config.sub_chunk_size,
+                streaming_batch_size: config.streaming_batch_size,
+                loaded_entries_soft_cap: config.loaded_entries_soft_cap,
+                flush_interval: flush_interval(config),
+                max_entries_per_second: config.max_entries_per_second,
+                max_duration: config.max_recovery_duration,
+                latest_snapshot_check: (config.prefer_latest_snapshot
+                    && config.target_l1_batch.is_none())
+                .then(|| {
+                    LatestSnapshotCheck::new(
+                        pool,
+                        tree.recovered_version(),
+                        config.latest_snapshot_check_interval,
+                    )
+                }),
+                prioritize_large_chunks: config.prioritize_large_chunks,
+                disk_space_check: disk_space_check(config),
+                verification_samples_per_chunk: config.verification_samples_per_chunk,
+                proof_verification_samples: config.proof_verification_samples,
+                mismatch_diagnostic_keys_per_chunk: config.mismatch_diagnostic_keys_per_chunk,
+                watchdog: watchdog_options(config),
+                fingerprint_log: config
+                    .fingerprint_log
+                    .then(|| RecoveryFingerprintLog::for_tree(tree.db_path())),
+                progress_table: Some(RecoveryProgressTable::new(
+                    pool,
+                    snapshot_recovery.l1_batch_number,
+                    chunk_count,
+                )),
+                chunk_filter_batch_size: config.chunk_filter_batch_size,
+                check_chunk_ends: config.check_chunk_ends,
+                repair_mismatched_chunks: config.repair_mismatched_chunks,
+                skip_root_hash_check: config.experimental_skip_root_hash_check,
+                replica: replica.as_ref(),
+                connection_retry_timeout: config.connection_retry_timeout,
+                connection_acquire_timeout: config.connection_acquire_timeout,
+                entry_source: Some(entry_source),
+                events: Arc::new(RecoveryEventFanOut::new(
+                    Box::new(health_events),
+                    &recovery_listeners,
+                )),
+            };
+            let outcome = tree
+                .recover_or_supersede(snapshot, recovery_options, pool, stop_receiver)
+                .await?;
+            let (superseded_tree, latest_l1_batch) = match outcome {
+                RecoveryOutcome::Recovered(tree, report) => break (tree, report, snapshot),
+                RecoveryOutcome::Superseded {
+                    tree,
+                    latest_l1_batch,
+                } => (tree, latest_l1_batch),
+            };
+            tracing::warn!(
+                "Aborted Merkle tree recovery to L1 batch #{} since Postgres contains a newer snapshot \
+                 for L1 batch #{latest_l1_batch}; wiping the tree and restarting recovery from the newer snapshot \
+                 as configured",
+                superseded_tree.recovered_version()
+            );
+            wait_for_snapshot(config, pool, stop_receiver, health_updater).await?;
+            target = get_recovery_target(config, pool, None).await?.ok_or_else(|| {
+                RecoveryError::SnapshotMissing(
+                    "Snapshot recovery information disappeared from Postgres during Merkle tree recovery"
+                        .to_owned(),
+                )
+            })?;
+            let l1_batch = target.snapshot_recovery.l1_batch_number;
+            tree = superseded_tree.reset_to_version(l1_batch.0.into()).await?;
+            if let Some(genesis) = PostgresGenesis::load(pool, Some(l1_batch.0.into())).await? {
+                tree.check_postgres_genesis(genesis).await?;
+            }
+            tree.check_mode().await?;
+            tree.check_format().await?;
+        };
+        if let Some(export_path) = &config.export_path {
+            export_recovered_tree(&tree, snapshot.miniblock, export_path, health_updater).await?;
+        }
+        // Compact RocksDB even if there's nothing to prune, to reclaim space taken by nodes overwritten during recovery.
+        prune_if_configured(&tree, config, true, stop_receiver).await?;
+        Ok((tree, report))
+    }
+
+    /// Checks that a recovering tree was started for the current Postgres genesis. If the genesis has changed
+    /// and the config allows it, wipes the tree so that it's initialized from scratch.
+    async fn ensure_same_genesis(
+        self,
+        config: &MetadataCalculatorRecoveryConfig,
+        pool: &ConnectionPool,
+    ) -> anyhow::Result<Self> {
+        let mut tree = match self {
+            Self::Recovering(tree) => tree,
+            other => return Ok(other),
+        };
+        let Some(genesis) = PostgresGenesis::load(pool, Some(tree.recovered_version())).await?
+        else {
+            tracing::warn!(
+                "Postgres doesn't contain genesis or snapshot recovery information; cannot check that \
+                 the Merkle tree is recovered for the same Postgres genesis"
+            );
+            return Ok(Self::Recovering(tree));
+        };
+
+        match tree.check_postgres_genesis(genesis).await {
+            Ok(()) => Ok(Self::Recovering(tree)),
+            Err(err) if config.allow_tree_reset_on_regenesis => {
+                tracing::warn!("{err:#}; wiping Merkle tree as configured");
+                Self::Recovering(tree).reset().await
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Removes the tree if it cannot be used as is and the config allows it, so that the tree is initialized
+    /// from scratch. This is the case if the tree is being recovered to an L1 batch different from the recovery target,
+    /// or if it was processed in the lightweight mode and cannot be upgraded to the full mode. If the config
+    /// prefers the latest snapshot, a tree being recovered to an older snapshot is removed as well.
+    async fn reset_if_unusable(
+        self,
+        config: &MetadataCalculatorRecoveryConfig,
+        pool: &ConnectionPool,
+    ) -> anyhow::Result<Self> {
+        let prefers_latest_snapshot =
+            config.prefer_latest_snapshot && config.target_l1_batch.is_none();
+        if !config.allow_tree_reset && !prefers_latest_snapshot {
+            return Ok(self);
+        }
+        match self {
+            Self::Recovering(tree) => {
+                // If the latest snapshot is preferred, the tree must not stick to the snapshot it's recovered to.
+                let recovered_version =
+                    (!prefers_latest_snapshot).then(|| tree.recovered_version());
+                let Some(target) = get_recovery_target(config, pool, recovered_version).await?
+                else {
+                    return Ok(Self::Recovering(tree));
+                };
+                let l1_batch = target.snapshot_recovery.l1_batch_number;
+                let recovered_version = tree.recovered_version();
+                if u64::from(l1_batch.0) == recovered_version {
+                    return Ok(Self::Recovering(tree));
+                }
+                if !config.allow_tree_reset {
+                    if u64::from(l1_batch.0) < recovered_version {
+                        return Ok(Self::Recovering(tree));
+                    }
+                    tracing::warn!(
+                        "Merkle tree is being recovered to L1 batch #{recovered_version}, but Postgres contains \
+                         a newer snapshot for L1 batch #{l1_batch}; removing the tree to recover it from the newer \
+                         snapshot as configured"
+                    );
+                    return Self::Recovering(tree).reset().await;
+                }
+                tracing::warn!(
+                    "Merkle tree is being recovered to L1 batch #{recovered_version}, which differs from the recovery \
+                     target L1 batch #{l1_batch}; removing the tree as configured"
+                );
+                Self::Recovering(tree).reset().await
+            }
+            Self::Ready(tree) if config.allow_tree_reset => {
+                if let Err(err) = check_upgrade_to_full(&tree, config) {
+                    tracing::warn!("{err:#}; removing Merkle tree as configured");
+                    return Self::Ready(tree).reset().await;
+                }
+                Ok(Self::Ready(tree))
+            }
+            other => Ok(other),
+        }
+    }
+}
+
+impl AsyncTreeRecovery {
+    /// Custom tag in the tree manifest storing the desired chunk size used for recovery.
+    const CHUNK_SIZE_TAG: &'static str = "recovery.desired_chunk_size";
+    /// Custom tag in the tree manifest storing the kind of the entry source used for recovery.
+    const ENTRY_SOURCE_TAG: &'static str = "recovery.entry_source";
+    /// Custom tag in the tree manifest storing the chunk plan used for recovery.
+    const CHUNK_PLAN_TAG: &'static str = "recovery.chunk_plan";
+    /// Custom tag in the tree manifest storing Postgres genesis for which recovery was started.
+    const POSTGRES_GENESIS_TAG: &'static str = "recovery.postgres_genesis";
+    /// Custom tag in the tree manifest storing the tree mode with which recovery was started.
+    const MODE_TAG: &'static str = "recovery.mode";
+    /// Custom tag in the tree manifest storing the format with which recovery was started.
+    const FORMAT_TAG: &'static str = "recovery.format";
+    /// Custom tag in the tree manifest storing the fingerprint of the snapshot from which recovery was started.
+    const SNAPSHOT_FINGERPRINT_TAG: &'static str = "recovery.snapshot_fingerprint";
+
+    /// Returns the entry source for recovery together with the number of chunks to recover. The snapshot file
+    /// or (if the file is not supplied) the snapshot object store is used if it's supplied, unless recovery
+    /// was started with another source.
+    #[allow(clippy::too_many_arguments)]
+    async fn entry_source<'a>(
+        &mut self,
+        config: &MetadataCalculatorRecoveryConfig,
+        snapshot: &SnapshotParameters,
+        snapshot_recovery: &SnapshotRecoveryStatus,
+        pool: &'a ConnectionPool,
+        replica: Option<&'a SnapshotReplica<'a>>,
+        snapshot_object_store: Option<&'a dyn ObjectStore>,
+        snapshot_file: Option<&FileSnapshotSource>,
+    ) -> anyhow::Result<(usize, Box<dyn RecoveryEntrySource + 'a>)> {
+        let external_kind = if snapshot_file.is_some() {
+            Some(RecoveryEntrySourceKind::File)
+        } else {
+            snapshot_object_store.map(|_| RecoveryEntrySourceKind::ObjectStore)
+        };
+        let source_kind = self.entry_source_kind(external_kind).await?;
+        Ok(match (source_kind, snapshot_object_store) {
+            (RecoveryEntrySourceKind::File, _) => {
+                let snapshot_file = snapshot_file.context(
+                    "Merkle tree recovery was started using a snapshot file, but no snapshot file is configured",
+                )?;
+                let desired_chunk_size = self.desired_chunk_size(config.desired_chunk_size).await?;
+                let source: Box<dyn RecoveryEntrySource + 'a> = Box::new(snapshot_file.clone());
+                (snapshot.chunk_count_for_size(desired_chunk_size), source)
+            }
+            (RecoveryEntrySourceKind::Postgres, _) => {
+                let desired_chunk_size = self.desired_chunk_size(config.desired_chunk_size).await?;
+                let source: Box<dyn RecoveryEntrySource + 'a> = Box::new(PostgresEntrySource {
+                    pool,
+                    replica,
+                    snapshot_miniblock: snapshot.miniblock,
+                    connection_retry_timeout: config.connection_retry_timeout,
+                    connection_acquire_timeout: config.connection_acquire_timeout,
+                    use_copy: config.use_copy,
+                });
+                (snapshot.chunk_count_for_size(desired_chunk_size), source)
+            }
+            (RecoveryEntrySourceKind::ObjectStore, Some(object_store)) => {
+                let chunk_count = usize::try_from(snapshot_recovery.total_chunk_count)
+                    .context("Snapshot chunk count doesn't fit into usize")?;
+                anyhow::ensure!(
+                    chunk_count > 0,
+                    "Snapshot for L1 batch #{} has no storage log chunks",
+                    snapshot_recovery.l1_batch_number
+                );
+                let source: Box<dyn RecoveryEntrySource + 'a> = Box::new(ObjectStoreEntrySource {
+                    object_store,
+                    l1_batch_number: snapshot_recovery.l1_batch_number,
+                    chunk_count,
+                });
+                (chunk_count, source)
+            }
+            (RecoveryEntrySourceKind::ObjectStore, None) => {
+                anyhow::bail!(
+                    "Merkle tree recovery was started using snapshot chunks from the object store, \
+                     but no snapshot object store is supplied"
+                );
+            }
+        })
+    }
+
+    /// Returns the kind of the entry source for recovery. Chunks are defined differently for different sources,
+    /// so the source is persisted in the tree manifest when recovery starts, similarly to the desired chunk size.
+    /// If `external_kind` is specified, it's used for new recovery instead of Postgres.
+    async fn entry_source_kind(
+        &mut self,
+        external_kind: Option<RecoveryEntrySourceKind>,
+    ) -> anyhow::Result<RecoveryEntrySourceKind> {
+        let tags = self.custom_tags().await;
+        if let Some(persisted_kind) = tags.get(Self::ENTRY_SOURCE_TAG) {
+            let persisted_kind =
+                RecoveryEntrySourceKind::parse(persisted_kind).with_context(|| {
+                    format!("Malformed recovery entry source persisted in Merkle tree: {persisted_kind:?}")
+                })?;
+            if let Some(external_kind) = external_kind.filter(|&kind| kind != persisted_kind) {
+                tracing::warn!(
+                    "Merkle tree recovery was started using {}; continuing to use it despite the supplied {}",
+                    persisted_kind.description(),
+                    external_kind.description()
+                );
+            }
+            return Ok(persisted_kind);
+        }
+
+        // If the chunk size is persisted, recovery was started before the entry source was persisted,
+        // i.e., using Postgres.
+        let kind = match external_kind {
+            Some(kind) if !tags.contains_key(Self::CHUNK_SIZE_TAG) => kind,
+            _ => RecoveryEntrySourceKind::Postgres,
+        };
+        self.update_custom_tags(move |tags| {
+            tags.insert(Self::ENTRY_SOURCE_TAG.to_owned(), kind.as_str().to_owned());
+        })
+        .await;
+        Ok(kind)
+    }
+
+    /// Returns the desired chunk size for recovery. Chunks must be the same for the entire recovery
+    /// (i.e., not changed after a node restart), so the chunk size is persisted in the tree manifest
+    /// when recovery starts. If the persisted value differs from the configured one, the persisted value wins.
+    async fn desired_chunk_size(&mut self, configured_chunk_size: u64) -> anyhow::Result<u64> {
+        let tags = self.custom_tags().await;
+        if let Some(persisted_chunk_size) = tags.get(Self::CHUNK_SIZE_TAG) {
+            let persisted_chunk_size: u64 = persisted_chunk_size.parse().with_context(|| {
+                format!("Malformed recovery chunk size persisted in Merkle tree: {persisted_chunk_size:?}")
+            })?;
+            if persisted_chunk_size != configured_chunk_size {
+                tracing::warn!(
+                    "Recovery chunk size persisted in Merkle tree ({persisted_chunk_size}) differs from \
+                     the configured value ({configured_chunk_size}); using the persisted value"
+                );
+            }
+            return Ok(persisted_chunk_size);
+        }
+
+        anyhow::ensure!(
+            configured_chunk_size > 0,
+            "Recovery chunk size is misconfigured to be 0; please update it to positive value"
+        );
+        self.update_custom_tags(move |tags| {
+            tags.insert(
+                Self::CHUNK_SIZE_TAG.to_owned(),
+                configured_chunk_size.to_string(),
+            );
+        })
+        .await;
+        Ok(configured_chunk_size)
+    }
+
+    /// Checks that the chunk plan persisted in the tree manifest matches the provided `plan`. If no plan is persisted
+    /// (i.e., recovery has just started, or it was started before the plan was persisted), persists `plan`.
+    async fn check_chunk_plan(&mut self, plan: ChunkPlan) -> anyhow::Result<()> {
+        let tags = self.custom_tags().await;
+        if let Some(persisted_plan) = tags.get(Self::CHUNK_PLAN_TAG) {
+            let persisted_plan: ChunkPlan =
+                serde_json::from_str(persisted_plan).with_context(|| {
+                    format!("Malformed recovery chunk plan persisted in Merkle tree: {persisted_plan:?}")
+                })?;
+            anyhow::ensure!(
+                persisted_plan == plan,
+                "Merkle tree recovery was started with chunk plan {persisted_plan:?}, which differs from the current plan \
+                 {plan:?} (e.g., because the number of snapshot storage logs in Postgres has changed); recovered chunks \
+                 cannot be matched with the current plan. Check that Postgres contains the same snapshot data, or enable \
+                 `force_replan` in the Merkle tree recovery config to wipe the tree and restart recovery from scratch"
+            );
+            return Ok(());
+        }
+
+        let plan =
+            serde_json::to_string(&plan).context("failed serializing recovery chunk plan")?;
+        self.update_custom_tags(move |tags| {
+            tags.insert(Self::CHUNK_PLAN_TAG.to_owned(), plan);
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Checks that the snapshot fingerprint persisted in the tree manifest matches the provided `fingerprint`.
+    /// If no fingerprint is persisted (i.e., recovery has just started, or it was started before the fingerprint
+    /// was persisted), persists `fingerprint`.
+    async fn check_snapshot_fingerprint(
+        &mut self,
+        fingerprint: SnapshotFingerprint,
+    ) -> anyhow::Result<()> {
+        let tags = self.custom_tags().await;
+        if let Some(persisted) = tags.get(Self::SNAPSHOT_FINGERPRINT_TAG) {
+            let persisted: SnapshotFingerprint =
+                serde_json::from_str(persisted).with_context(|| {
+                    format!(
+                        "Malformed snapshot fingerprint persisted in Merkle tree: {persisted:?}"
+                    )
+                })?;
+            let differing_components = persisted.differing_components(&fingerprint);
+            anyhow::ensure!(
+                differing_components.is_empty(),
+                "Merkle tree recovery was started for snapshot with fingerprint {persisted:?}, which differs from \
+                 the current snapshot fingerprint {fingerprint:?} in {}; e.g., Postgres was restored from a backup, \
+                 or the snapshot L1 batch was re-executed. Check that Postgres contains the same snapshot data, \
+                 or enable `force_replan` in the Merkle tree recovery config to wipe the tree and restart recovery \
+                 from scratch",
+                differing_components.join(", ")
+            );
+            let persisted_hash = persisted.compute_hash();
+            anyhow::ensure!(
+                persisted.hash == persisted_hash,
+                "Snapshot fingerprint persisted in Merkle tree {persisted:?} is corrupted: its hash differs from \
+                 the hash of its components {persisted_hash:?}. Remove the tree to restart recovery from scratch"
+            );
+            return Ok(());
+        }
+
+        let fingerprint = serde_json::to_string(&fingerprint)
+            .context("failed serializing snapshot fingerprint")?;
+        self.update_custom_tags(move |tags| {
+            tags.insert(Self::SNAPSHOT_FINGERPRINT_TAG.to_owned(), fingerprint);
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Checks that Postgres genesis persisted in the tree manifest matches the provided `genesis`. If no genesis
+    /// is persisted (i.e., recovery has just started, or it was started before the genesis was persisted),
+    /// persists `genesis`.
+    async fn check_postgres_genesis(&mut self, genesis: PostgresGenesis) -> anyhow::Result<()> {
+        let tags = self.custom_tags().await;
+        if let Some(persisted_genesis) = tags.get(Self::POSTGRES_GENESIS_TAG) {
+            let persisted_genesis: PostgresGenesis = serde_json::from_str(persisted_genesis)
+                .with_context(|| {
+                    format!("Malformed Postgres genesis persisted in Merkle tree: {persisted_genesis:?}")
+                })?;
+            anyhow::ensure!(
+                persisted_genesis == genesis,
+                "Merkle tree recovery was started for Postgres genesis {persisted_genesis:?}, which differs from \
+                 the current Postgres genesis {genesis:?} (e.g., because Postgres was re-initialized). Remove the tree, \
+                 or enable `allow_tree_reset_on_regenesis` in the Merkle tree recovery config to wipe the tree \
+                 and restart recovery from scratch"
+            );
+            return Ok(());
+        }
+
+        let genesis =
+            serde_json::to_string(&genesis).context("failed serializing Postgres genesis")?;
+        self.update_custom_tags(move |tags| {
+            tags.insert(Self::POSTGRES_GENESIS_TAG.to_owned(), genesis);
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Checks that the tree mode persisted in the tree manifest matches the configured mode. If no mode is persisted
+    /// (i.e., recovery has just started, or it was started before the mode was persisted), persists the configured mode.
+    async fn check_mode(&mut self) -> anyhow::Result<()> {
+        let mode = self.mode;
+        let tags = self.custom_tags().await;
+        if let Some(persisted_mode) = tags.get(Self::MODE_TAG) {
+            let persisted_mode: MerkleTreeMode = serde_json::from_str(persisted_mode)
+                .with_context(|| {
+                    format!(
+                        "Malformed Merkle tree mode persisted in Merkle tree: {persisted_mode:?}"
+                    )
+                })?;
+            anyhow::ensure!(
+                persisted_mode == mode,
+                "Merkle tree recovery was started in the {persisted_mode:?} mode, but the tree is configured to run \
+                 in the {mode:?} mode; resuming recovery would produce a tree mixing both modes. Revert the Merkle tree \
+                 mode in the config to {persisted_mode:?}, or remove the tree to restart recovery from scratch"
+            );
+            return Ok(());
+        }
+
+        let mode = serde_json::to_string(&mode).context("failed serializing Merkle tree mode")?;
+        self.update_custom_tags(move |tags| {
+            tags.insert(Self::MODE_TAG.to_owned(), mode);
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Checks that the recovery format persisted in the tree manifest is compatible with this binary
+    /// (see [`RecoveryFormat`]). If no format is persisted (i.e., recovery has just started, or it was started
+    /// before the format was persisted), or the persisted format was migrated, persists the current format.
+    async fn check_format(&mut self) -> anyhow::Result<()> {
+        let tags = self.custom_tags().await;
+        if let Some(persisted_format) = tags.get(Self::FORMAT_TAG) {
+            if !RecoveryFormat::check_resume(persisted_format)? {
+                return Ok(());
+            }
+        }
+
+        let format = serde_json::to_string(&RecoveryFormat::current())
+            .context("failed serializing recovery format")?;
+        self.update_custom_tags(move |tags| {
+            tags.insert(Self::FORMAT_TAG.to_owned(), format);
+        })
+        .await;
+        Ok(())
+    }
+
+    /// Recovers the tree from the snapshot, returning it together with a [`RecoveryReport::Recovered`] report.
+    /// Returns [`RecoveryError::Interrupted`] if a stop signal was received before recovery completed.
+    /// If `options` don't specify an entry source, entries are loaded from `pool`.
+    ///
+    /// Prefer [`GenericAsyncTree::ensure_ready()`], which derives options from the config and persists
+    /// recovery parameters in the tree. When calling this method directly, the caller is responsible
+    /// for upholding the following invariants across recovery restarts:
+    ///
+    /// - Recovery must be resumed from the same snapshot (i.e., `snapshot` must be the same, and the snapshot
+    ///   storage logs in Postgres must not change). Otherwise, the recovered tree will mix entries
+    ///   from different snapshots, and recovery will fail on the root hash check.
+    /// - The chunk plan must be stable: `options` must specify the same chunk count and entry source. Chunks
+    ///   recovered before a restart are detected by their key ranges, so changing chunks may lead to chunks
+    ///   being skipped or recovered twice.
+    pub async fn recover<'a>(
+        self,
+        snapshot: SnapshotParameters,
+        mut options: RecoveryOptions<'a>,
+        pool: &'a ConnectionPool,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> Result<(AsyncTree, RecoveryReport), RecoveryError> {
+        if options.entry_source.is_none() {
+            options.entry_source = Some(Box::new(PostgresEntrySource {
+                pool,
+                replica: options.replica,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: options.connection_retry_timeout,
+                connection_acquire_timeout: options.connection_acquire_timeout,
+                use_copy: false,
+            }));
+        }
+        let recovered_version = self.recovered_version();
+        let result = self
+            .recover_inner(snapshot, &options, pool, stop_receiver)
+            .await;
+        let result = result.and_then(|outcome| match outcome {
+            RecoveryOutcome::Recovered(tree, report) => Ok((tree, report)),
+            RecoveryOutcome::Superseded {
+                latest_l1_batch, ..
+            } => Err(RecoveryError::SnapshotSuperseded {
+                recovered_version,
+                latest_l1_batch,
+            }),
+        });
+        if let Err(err) = &result {
+            if !matches!(err, RecoveryError::Interrupted) {
+                options.events.recovery_failed(err);
+            }
+        }
+        result
+    }
+
+    /// Same as [`Self::recover()`], but returns the partially recovered tree if recovery is aborted because
+    /// the snapshot is superseded, so that the tree can be reset. `options` must specify the entry source.
+    async fn recover_or_supersede<'a>(
+        self,
+        snapshot: SnapshotParameters,
+        options: RecoveryOptions<'a>,
+        pool: &'a ConnectionPool,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> Result<RecoveryOutcome, RecoveryError> {
+        let result = self
+            .recover_inner(snapshot, &options, pool, stop_receiver)
+            .await;
+        if let Err(err) = &result {
+            if !matches!(err, RecoveryError::Interrupted) {
+                options.events.recovery_failed(err);
+            }
+        }
+        result
+    }
+
+    async fn recover_inner(
+        mut self,
+        snapshot: SnapshotParameters,
+        options: &RecoveryOptions<'_>,
+        pool: &ConnectionPool,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> Result<RecoveryOutcome, RecoveryError> {
+        let started_at = Instant::now();
+        let chunk_count = options.chunk_count;
+        tracing::info!(
+            "Recovering Merkle tree from snapshot in {chunk_count} chunks with concurrency limits {:?} \
+             (mode: {:?}, entry source: {:?})",
+            options.concurrency_limit,
+            options.mode,
+            options.entry_source()
+        );
+
+        let chunks = options.entry_source().key_chunks(chunk_count).await?;
+        let remaining_chunks = self
+            .filter_remaining_chunks(pool, snapshot.miniblock, &chunks, options, stop_receiver)
+            .await?;
+        let Some(mut remaining_chunks) = remaining_chunks else {
+            return Err(RecoveryError::Interrupted);
+        };
+        // If all chunks are recovered in one go, leaf indices of all snapshot entries are tracked by the tree applier.
+        let recovers_all_chunks = remaining_chunks.len() == chunk_count;
+        if let Some(progress_table) = &options.progress_table {
+            progress_table.reconcile(&chunks, &remaining_chunks).await;
+        }
+        let mut plan = RecoveryPlan::load(options.entry_source(), &remaining_chunks).await?;
+        if let Some(plan) = &plan {
+            plan.report_metrics();
+            tracing::info!(
+                "Loaded recovery plan: {} entries in {} remaining chunks, largest chunk: {:?}",
+                plan.total_entry_count(),
+                plan.chunks().len(),
+                plan.largest_chunk()
+            );
+        }
+        if options.prioritize_large_chunks {
+            if let Some(plan) = &mut plan {
+                plan.prioritize_large_chunks();
+                remaining_chunks = plan.key_chunks();
+            } else {
+                tracing::info!(
+                    "Entry source doesn't support estimating chunk sizes; chunks are recovered in the default order"
+                );
+            }
+        }
+        let recovered_entry_count = self.leaf_count().await;
+        options.events.recovery_started(
+            chunk_count,
+            chunk_count - remaining_chunks.len(),
+            recovered_entry_count,
+        );
+        tracing::info!(
+            "Filtered recovered key chunks; {} / {chunk_count} chunks remaining, {recovered_entry_count} entries \
+             are already in the tree",
+            remaining_chunks.len()
+        );
+        if let Some(fingerprint_log) = &options.fingerprint_log {
+            if remaining_chunks.len() == chunk_count {
+                // Recovery starts from scratch, so fingerprints left from an unrelated recovery are irrelevant.
+                fingerprint_log.truncate().await?;
+            }
+        }
+
+        if let Some(disk_space_check) = &options.disk_space_check {
+            let remaining_entry_count = snapshot.log_count.saturating_sub(recovered_entry_count);
+            let estimate = disk_space_check.run(self.db_path(), remaining_entry_count)?;
+            options.events.disk_space_checked(estimate);
+        }
+
+        // Loaded entries waiting to be applied to the tree in addition to ones held by chunk loaders. Since loaders
+        // wait for the queue to free up while holding a concurrency permit, a small capacity is enough to keep
+        // the tree busy without increasing memory usage much.
+        const LOADED_ENTRIES_QUEUE_CAPACITY: usize = 1;
+
+        let mut tree = self;
+        let concurrency = AdaptiveConcurrency::new(options.concurrency_limit);
+        let watchdog = RecoveryWatchdog::new();
+        let estimated_chunk_entry_count = snapshot.log_count / chunk_count.max(1) as u64;
+        let budget =
+            LoadedEntriesBudget::new(options.loaded_entries_soft_cap, estimated_chunk_entry_count);
+        let throttle =
+            EntryThrottle::new(options.max_entries_per_second, estimated_chunk_entry_count);
+        let pause_gate = PauseGate::new(options.overrides.control.clone());
+        let deadline = options.max_duration.map(|duration| started_at + duration);
+        // Number of chunks not started because the deadline was exceeded.
+        let skipped_chunk_count = AtomicUsize::new(0);
+        let (entries_sender, entries_receiver) = mpsc::channel(LOADED_ENTRIES_QUEUE_CAPACITY);
+        let entries_sender = LoadedEntriesSender::new(entries_sender);
+        let tree_wait_duration = entries_sender.tree_wait_duration.clone();
+        // Chunk loaders borrow the recovery state (options, the concurrency limiter, the budget etc.), so they are
+        // polled by the recovery task rather than spawned. Loaders are I/O-bound; CPU-bound processing of loaded
+        // entries is offloaded to blocking threads, so that it doesn't stall other loaders. Dropping loaders
+        // (e.g., on the first chunk error in the fail-fast mode) cancels them.
+        let load_tasks: Vec<_> = remaining_chunks
+            .iter()
+            .cloned()
+            .map(|(chunk_id, chunk)| {
+                let entries_sender = entries_sender.clone();
+                let concurrency = &concurrency;
+                let watchdog = &watchdog;
+                let budget = &budget;
+                let throttle = &throttle;
+                let pause_gate = &pause_gate;
+                let skipped_chunk_count = &skipped_chunk_count;
+                async move {
+                    let _permit = loop {
+                        if !pause_gate.wait_until_resumed(stop_receiver).await {
+                            return anyhow::Ok(ChunkLoadOutcome::Interrupted);
+                        }
+                        let permit = concurrency.acquire().await?;
+                        if !pause_gate.is_paused() {
+                            break permit;
+                        }
+                        // Recovery was paused while waiting for the permit; it's released so that the chunk
+                        // isn't started while recovery is paused.
+                    };
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        skipped_chunk_count.fetch_add(1, Ordering::Relaxed);
+                        return anyhow::Ok(ChunkLoadOutcome::Skipped);
+                    }
+                    if let Some(check) = &options.latest_snapshot_check {
+                        if check.check().await.is_some() {
+                            return anyhow::Ok(ChunkLoadOutcome::Skipped);
+                        }
+                    }
+                    let _watchdog_guard = watchdog.track_chunk(chunk_id, chunk.clone());
+                    let descriptor = ChunkDescriptor {
+                        index: chunk_id,
+                        key_range: chunk.clone(),
+                        entry_count: None,
+                    };
+                    options.events.chunk_started(&descriptor).await;
+                    let context = ChunkLoaderContext {
+                        concurrency,
+                        budget,
+                        throttle,
+                        stop_receiver,
+                        entries_sender: &entries_sender,
+                    };
+                    let outcome = Self::load_key_chunk_with_retries(
+                        chunk_id,
+                        chunk.clone(),
+                        context,
+                        options,
+                    )
+                    .await;
+                    if let Err(err) = &outcome {
+                        options.events.chunk_failed(&descriptor, err).await;
+                    }
+                    let outcome = outcome?;
+                    if let ChunkLoadOutcome::Loaded { .. } = outcome {
+                        options.events.chunk_loaded(chunk_id).await;
+                    }
+                    anyhow::Ok(outcome)
+                }
+            })
+            .collect();
+        // Chunk loaders must hold the only senders, so that the tree applier stops once all loaders are finished.
+        drop(entries_sender);
+        let load_chunks = async {
+            if options.fail_fast {
+                let outcomes = future::try_join_all(load_tasks).await?;
+                return Ok(outcomes.into_iter().map(ChunkLoadOutcome::retries).sum());
+            }
+            // Chunks are loaded to completion even if some of them fail, so that successfully loaded chunks
+            // are applied to the tree and are skipped after a restart.
+            let results = future::join_all(load_tasks).await;
+            let retry_count = results
+                .iter()
+                .filter_map(|result| result.as_ref().ok())
+                .map(|outcome| outcome.retries())
+                .sum::<usize>();
+            let failed_chunks = remaining_chunks.iter().zip(results).filter_map(
+                |((chunk_id, key_range), result)| {
+                    let err = result.err()?;
+                    Some(FailedChunk::new(*chunk_id, key_range.clone(), &err))
+                },
+            );
+            let failed_chunks = FailedChunks {
+                chunk_count: remaining_chunks.len(),
+                chunks: failed_chunks.collect(),
+            };
+            if failed_chunks.chunks.is_empty() {
+                return Ok(retry_count);
+            }
+            Err(anyhow::Error::from(RecoveryError::ChunksFailed(
+                failed_chunks,
+            )))
+        };
+        let apply_entries = async {
+            let result = tree
+                .apply_loaded_entries(entries_receiver, options, &budget, &throttle, stop_receiver)
+                .await;
+            // Entries remaining in the queue are dropped once the applier stops, so they won't be released.
+            budget.close();
+            result
+        };
+        // The tree applier must finish even if loading chunks fails, so that all loaded entries are applied.
+        let pipeline = future::join(load_chunks, apply_entries);
+        let pipeline = async {
+            let Some(override_receiver) = options.overrides.concurrency.clone() else {
+                return pipeline.await;
+            };
+            // Overrides are followed until the pipeline finishes.
+            tokio::pin!(pipeline);
+            tokio::select! {
+                output = &mut pipeline => output,
+                () = concurrency.follow_max_overrides(override_receiver) => pipeline.await,
+            }
+        };
+        let pipeline = async {
+            tokio::pin!(pipeline);
+            let report_transitions =
+                pause_gate.report_transitions(options.events.as_ref(), &watchdog);
+            tokio::select! {
+                output = &mut pipeline => output,
+                () = report_transitions => pipeline.await,
+            }
+        };
+        let (load_result, apply_result) = if let Some(watchdog_options) = options.watchdog {
+            // The watchdog is dropped once the pipeline finishes. If it exits on a stop signal,
+            // the pipeline is still driven to completion so that loaded entries are applied.
+            tokio::pin!(pipeline);
+            let run_watchdog =
+                watchdog.run(watchdog_options, options.events.as_ref(), stop_receiver);
+            tokio::select! {
+                output = &mut pipeline => output,
+                () = run_watchdog => pipeline.await,
+            }
+        } else {
+            pipeline.await
+        };
+        // Chunks recovered before an error or a stop signal are recorded as well.
+        if let Some(progress_table) = &options.progress_table {
+            progress_table.flush().await;
+        }
+        let (entry_count, extend_duration, leaf_index_stats, chunk_timings) = apply_result?;
+        let retry_count = load_result?;
+
+        if *stop_receiver.borrow() {
+            return Err(RecoveryError::Interrupted);
+        }
+        let latest_l1_batch = options
+            .latest_snapshot_check
+            .as_ref()
+            .and_then(LatestSnapshotCheck::superseded_by);
+        if let Some(latest_l1_batch) = latest_l1_batch {
+            // The tree is going to be reset, so there's no need to flush it.
+            return Ok(RecoveryOutcome::Superseded {
+                tree,
+                latest_l1_batch,
+            });
+        }
+        let skipped_chunk_count = skipped_chunk_count.load(Ordering::Relaxed);
+        if skipped_chunk_count > 0 {
+            // Recovered chunks must survive a restart even if the write-ahead log is disabled.
+            tree.flush_db().await?;
+            let err = RecoveryError::DeadlineExceeded {
+                recovered: chunk_count - skipped_chunk_count,
+                total: chunk_count,
+                elapsed: started_at.elapsed(),
+            };
+            tracing::warn!("{err}");
+            return Err(err);
+        }
+
+        let finalize_latency = RECOVERY_METRICS.latency[&RecoveryStage::Finalize].start();
+        let mut finalize_progress = FinalizeProgress::new(options.events.as_ref());
+        finalize_progress.start_stage(RecoveryFinalizeStage::CheckLeafIndices);
+        // Checks below compare the tree against Postgres, so Postgres must still contain the same snapshot.
+        snapshot.check_unchanged(pool).await?;
+        leaf_index_stats.verify(snapshot.miniblock, snapshot.log_count, recovers_all_chunks)?;
+        let mut storage = pool.access_storage().await?;
+        verify_leaf_indices(
+            &mut tree,
+            &mut storage,
+            snapshot.miniblock,
+            snapshot.log_count,
+        )
+        .await?;
+        drop(storage);
+        finalize_progress.start_stage(RecoveryFinalizeStage::ComputeRootHash);
+        let actual_root_hash = tree.root_hash().await;
+        if actual_root_hash != snapshot.expected_root_hash {
+            let mut diagnostics = None;
+            if let Some(keys_per_chunk) = options.mismatch_diagnostic_keys_per_chunk {
+                let mut storage = pool.access_storage().await?;
+                let report = diagnose_root_hash_mismatch(
+                    &mut tree,
+                    &mut storage,
+                    snapshot.miniblock,
+                    snapshot.expected_root_hash,
+                    actual_root_hash,
+                    &chunks,
+                    keys_per_chunk,
+                )
+                .await
+                .context("Failed diagnosing root hash mismatch")?;
+                diagnostics = Some(report.to_string());
+            }
+            let err = RecoveryError::RootHashMismatch {
+                expected: snapshot.expected_root_hash,
+                actual: actual_root_hash,
+                diagnostics,
+            };
+            if !options.skip_root_hash_check {
+                if matches!(options.mode, RecoveryMode::Normal) {
+                    // Retrying recovery would fail in the same way, so the failure marker is persisted for the tree
+                    // to be inspected and reset manually. The tree is intentionally *not* finalized: a finalized tree
+                    // would be treated as ready and could serve proofs for the wrong state.
+                    tracing::error!(
+                        "{err}. Merkle tree is kept for inspection; recovery won't be retried until the tree is reset"
+                    );
+                    tree.mark_recovery_failed(RecoveryFailure {
+                        expected_root_hash: snapshot.expected_root_hash,
+                        actual_root_hash,
+                        failed_at: seconds_since_epoch(),
+                    })
+                    .await?;
+                }
+                return Err(err);
+            }
+            tracing::error!(
+                "!!! {err}. Root hash check is skipped as configured; the recovered tree is marked as TAINTED \
+                 and MUST NOT be used to produce L1 batch metadata or Merkle proofs. Use it only to debug \
+                 the snapshot !!!"
+            );
+            tree.mark_tainted(TreeTaint {
+                expected_root_hash: snapshot.expected_root_hash,
+                actual_root_hash,
+            })
+            .await?;
+        }
+        finalize_progress.start_stage(RecoveryFinalizeStage::FlushDb);
+        tree.prune_stale_keys().await;
+        if tree.uses_db_profile() {
+            finalize_progress.start_stage(RecoveryFinalizeStage::CompactDb);
+            let reclaimed_bytes = tree.compact_db().await;
+            tracing::info!(
+                "Compacted Merkle tree RocksDB after bulk loading, reclaiming {reclaimed_bytes}B"
+            );
+        }
+        finalize_progress.start_stage(RecoveryFinalizeStage::WriteManifest);
+        let mut tree = tree.finalize().await;
+        if let Some(fingerprint_log) = &options.fingerprint_log {
+            fingerprint_log.archive().await?;
+        }
+        finalize_progress.finish();
+        let finalize_latency = finalize_latency.observe();
+        tracing::info!("Finalized tree recovery in {finalize_latency:?}");
+
+        if let Some(samples_per_chunk) = options.verification_samples_per_chunk {
+            let mut storage = pool.access_storage().await?;
+            verify_recovered_tree(
+                &tree,
+                &mut storage,
+                snapshot.miniblock,
+                &chunks,
+                samples_per_chunk,
+                &mut StdRng::from_entropy(),
+            )
+            .await
+            .context("Sampled verification of the recovered tree failed")?;
+        }
+        if let Some(sample_count) = options.proof_verification_samples {
+            verify_recovered_proofs(&mut tree, sample_count, &mut StdRng::from_entropy())
+                .await
+                .context("Verifying Merkle proofs of the recovered tree failed")?;
+        }
+        let stats = RecoveryStats {
+            duration: started_at.elapsed(),
+            entry_count,
+            chunk_count,
+            retry_count,
+            extend_duration,
+            tree_wait_duration: Duration::from_nanos(tree_wait_duration.load(Ordering::Relaxed)),
+            root_hash: tree.root_hash(),
+            chunk_timings: chunk_timings.summary(),
+        };
+        RECOVERY_METRICS.recovered_entry_count.set(entry_count);
+        RECOVERY_METRICS.duration.set(stats.duration);
+        tracing::info!("{}", chunk_timings.report(retry_count));
+        tracing::info!("Finished tree recovery ({stats:?}); resuming normal tree operation");
+        options.events.recovery_finished(stats);
+        if let Some(progress_table) = &options.progress_table {
+            progress_table.finish().await;
+        }
+        let report = RecoveryReport::Recovered {
+            l1_batch: tree.next_l1_batch_number() - 1,
+            miniblock: snapshot.miniblock,
+            chunks: chunk_count,
+            entries: snapshot.log_count,
+            duration: stats.duration,
+            root_hash: stats.root_hash,
+            chunk_timings: stats.chunk_timings,
+        };
+        Ok(RecoveryOutcome::Recovered(tree, report))
+    }
+
+    /// Loads the coarse histogram of hashed keys for the snapshot miniblock (see [`Self::weighted_key_ranges()`]).
+    async fn load_key_histogram(
+        storage: &mut StorageProcessor<'_>,
+        snapshot_miniblock: MiniblockNumber,
+    ) -> anyhow::Result<Vec<u64>> {
+        let histogram_latency = RECOVERY_METRICS.latency[&RecoveryStage::LoadKeyHistogram].start();
+        let histogram = storage
+            .storage_logs_dal()
+            .get_hashed_key_histogram_for_miniblock(snapshot_miniblock)
+            .await
+            .context("Failed getting hashed key histogram")?;
+        let histogram_latency = histogram_latency.observe();
+        tracing::debug!(
+            "Loaded hashed key histogram for miniblock #{snapshot_miniblock} in {histogram_latency:?}"
+        );
+        Ok(histogram)
+    }
+
+    /// Splits the hashed key space into `chunk_count` chunks with approximately equal number of entries
+    /// in the snapshot. Chunks only depend on the immutable snapshot data, so they are defined in the same way
+    /// across recovery restarts.
+    async fn key_ranges(
+        storage: &mut StorageProcessor<'_>,
+        snapshot_miniblock: MiniblockNumber,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        let histogram = Self::load_key_histogram(storage, snapshot_miniblock).await?;
+        Ok(Self::weighted_key_ranges(&histogram, chunk_count))
+    }
+
+    /// Splits the hashed key space into `chunk_count` contiguous ranges so that each range contains
+    /// approximately the same number of entries according to `histogram`. The histogram must contain
+    /// entry counts for each 2-byte big-endian key prefix, as returned by the DAL. Falls back to
+    /// equal-width ranges if the histogram is empty or too coarse for the requested number of chunks.
+    fn weighted_key_ranges(
+        histogram: &[u64],
+        chunk_count: usize,
+    ) -> Vec<ops::RangeInclusive<H256>> {
+        const BUCKET_COUNT: usize = 1 << 16;
+
+        assert!(chunk_count > 0);
+        assert_eq!(
+            histogram.len(),
+            BUCKET_COUNT,
+            "unexpected key histogram length"
+        );
+        let total_count: u64 = histogram.iter().sum();
+        if total_count == 0 || chunk_count > BUCKET_COUNT {
+            return Self::hashed_key_ranges(chunk_count).collect();
+        }
+
+        let cumulative_counts: Vec<_> = histogram
+            .iter()
+            .scan(0_u64, |acc, &count| {
+                *acc += count;
+                Some(*acc)
+            })
+            .collect();
+        // Indices of the last (inclusive) histogram bucket for each chunk.
+        let mut chunk_ends = Vec::with_capacity(chunk_count);
+        for i in 1..chunk_count {
+            let target_count = (u128::from(total_count) * i as u128 / chunk_count as u128) as u64;
+            let mut end = cumulative_counts.partition_point(|&count| count < target_count);
+            // Each chunk must contain at least one bucket, and there must be enough buckets left
+            // for the remaining chunks.
+            if let Some(&prev_end) = chunk_ends.last() {
+                end = end.max(prev_end + 1);
+            }
+            end = end.min(BUCKET_COUNT - 1 - (chunk_count - i));
+            chunk_ends.push(end);
+        }
+        chunk_ends.push(BUCKET_COUNT - 1);
+
+        let mut start_bucket = 0;
+        chunk_ends
+            .into_iter()
+            .map(|end_bucket| {
+                let start = Self::bucket_bound(start_bucket, 0);
+                let end = Self::bucket_bound(end_bucket, 0xff);
+                start_bucket = end_bucket + 1;
+                start..=end
+            })
+            .collect()
+    }
+
+    /// Returns the hashed key with the 2-byte `bucket` prefix and all other bytes set to `filler`.
+    fn bucket_bound(bucket: usize, filler: u8) -> H256 {
+        let mut key = H256::repeat_byte(filler);
+        key.0[..2].copy_from_slice(&(bucket as u16).to_be_bytes());
+        key
+    }
+
+    /// Splits the hashed key space into `count` equal-width contiguous ranges.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is zero. Callers must guard against this; for recovery, the number of chunks is positive
+    /// since snapshots without storage logs are rejected when loading [`SnapshotParameters`].
+    fn hashed_key_ranges(count: usize) -> impl Iterator<Item = ops::RangeInclusive<H256>> {
+        assert!(count > 0, "cannot split hashed key space into 0 ranges");
+        let mut stride = U256::MAX / count;
+        let stride_minus_one = if stride < U256::MAX {
+            stride += U256::one();
+            stride - 1
+        } else {
+            stride // `stride` is really 1 << 256 == U256::MAX + 1
+        };
+
+        (0..count).map(move |i| {
+            let start = stride * i;
+            let (mut end, is_overflow) = stride_minus_one.overflowing_add(start);
+            if is_overflow {
+                end = U256::MAX;
+            }
+            u256_to_h256(start)..=u256_to_h256(end)
+        })
+    }
+
+    /// Filters out recovered chunks (see [`Self::filter_chunks()`]) using the read replica if it's supplied
+    /// and is synced with the primary. Falls back to the primary `pool` otherwise, or if filtering using
+    /// the replica fails. Returns `None` if a stop signal was received while acquiring a connection.
+    async fn filter_remaining_chunks(
+        &mut self,
+        pool: &ConnectionPool,
+        snapshot_miniblock: MiniblockNumber,
+        key_chunks: &[ops::RangeInclusive<H256>],
+        options: &RecoveryOptions<'_>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<(usize, ops::RangeInclusive<H256>)>>> {
+        let batch_size = options.chunk_filter_batch_size;
+        let check_chunk_ends = if self.may_have_lost_writes() {
+            tracing::warn!(
+                "Merkle tree recovery was interrupted before writes were flushed to disk; checking both starts \
+                 and ends of recovered chunks"
+            );
+            true
+        } else {
+            if options.check_chunk_ends {
+                tracing::info!("Checking both starts and ends of recovered chunks as configured");
+            }
+            options.check_chunk_ends
+        };
+        let replica_pool = match options.replica {
+            Some(replica) => replica.pool().await.map(|pool| (replica, pool)),
+            None => None,
+        };
+        if let Some((replica, replica_pool)) = replica_pool {
+            let filtered = async {
+                let mut storage = replica_pool.access_storage().await?;
+                self.filter_chunks(
+                    &mut storage,
+                    snapshot_miniblock,
+                    key_chunks,
+                    batch_size,
+                    check_chunk_ends,
+                    options.repair_mismatched_chunks,
+                )
+                .await
+            };
+            match filtered.await {
+                Ok(remaining_chunks) => return Ok(Some(remaining_chunks)),
+                Err(err) => replica.fall_back("filtering recovered chunks", &err),
+            }
+        }
+
+        let storage = access_storage_with_retries(
+            pool,
+            "filtering recovered chunks",
+            options.connection_retry_timeout,
+            options.connection_acquire_timeout,
+            stop_receiver,
+        )
+        .await?;
+        let Some(mut storage) = storage else {
+            return Ok(None);
+        };
+        let remaining_chunks = self
+            .filter_chunks(
+                &mut storage,
+                snapshot_miniblock,
+                key_chunks,
+                batch_size,
+                check_chunk_ends,
+                options.repair_mismatched_chunks,
+            )
+            .await?;
+        Ok(Some(remaining_chunks))
+    }
+
+    /// Filters out `key_chunks` for which recovery was successfully performed. Returns remaining chunks
+    /// together with their IDs (i.e., indices in `key_chunks`). A chunk with the first key present in the tree
+    /// is still returned if it has an entry in the recovery journal, i.e., it was only partially recovered.
+    ///
+    /// The first key of each chunk is always loaded from Postgres, regardless of the entry source. This is valid
+    /// because the snapshot is fully applied to Postgres before tree recovery starts, so the first entry
+    /// in a chunk range is the same as the first entry in the corresponding object store chunk, and it can be
+    /// loaded much cheaper than the entire object store chunk.
+    ///
+    /// Chunks are processed sequentially in batches of `batch_size` chunks, so that neither the Postgres query
+    /// nor the tree lookup grows unbounded with the number of chunks.
+    ///
+    /// If `check_chunk_ends` is set, the last key of each chunk with the first key present in the tree is checked
+    /// as well (see [`Self::check_chunk_ends()`]). This is necessary if tree writes may have been lost.
+    ///
+    /// If a checked tree entry mismatches Postgres, filtering fails unless `repair_mismatched_chunks` is set,
+    /// in which case the chunk is repaired (see [`Self::repair_chunk()`]).
+    async fn filter_chunks(
+        &mut self,
+        storage: &mut StorageProcessor<'_>,
+        snapshot_miniblock: MiniblockNumber,
+        key_chunks: &[ops::RangeInclusive<H256>],
+        batch_size: usize,
+        check_chunk_ends: bool,
+        repair_mismatched_chunks: bool,
+    ) -> anyhow::Result<Vec<(usize, ops::RangeInclusive<H256>)>> {
+        let batch_size = batch_size.max(1);
+        let filter_latency = RECOVERY_METRICS.latency[&RecoveryStage::FilterChunks].start();
+        RECOVERY_METRICS.filtered_chunk_count.set(0);
+        let mut output = vec![];
+        for (batch_idx, batch) in key_chunks.chunks(batch_size).enumerate() {
+            let first_chunk_id = batch_idx * batch_size;
+            let remaining_chunks = self
+                .filter_chunks_batch(
+                    storage,
+                    snapshot_miniblock,
+                    batch,
+                    check_chunk_ends,
+                    repair_mismatched_chunks,
+                )
+                .await?;
+            let remaining_chunks = remaining_chunks
+                .into_iter()
+                .map(|(i, chunk)| (first_chunk_id + i, chunk));
+            output.extend(remaining_chunks);
+
+            let filtered_chunk_count = first_chunk_id + batch.len();
+            RECOVERY_METRICS
+                .filtered_chunk_count
+                .set(filtered_chunk_count);
+            if key_chunks.len() > batch_size {
+                tracing::debug!(
+                    "Filtered {filtered_chunk_count} / {} chunks; {} chunks remaining so far",
+                    key_chunks.len(),
+                    output.len()
+                );
+            }
+        }
+        let filter_latency = filter_latency.observe();
+        tracing::debug!("Filtered {} chunks in {filter_latency:?}", key_chunks.len());
+        Ok(output)
+    }
+
+    /// Filters a single batch of chunks for [`Self::filter_chunks()`]. Returned chunk IDs are indices in `key_chunks`.
+    async fn filter_chunks_batch(
+        &mut self,
+        storage: &mut StorageProcessor<'_>,
+        snapshot_miniblock: MiniblockNumber,
+        key_chunks: &[ops::RangeInclusive<H256>],
+        check_chunk_ends: bool,
+        repair_mismatched_chunks: bool,
+    ) -> anyhow::Result<Vec<(usize, ops::RangeInclusive<H256>)>> {
+        let chunk_starts_latency =
+            RECOVERY_METRICS.latency[&RecoveryStage::LoadChunkStarts].start();
+        let chunk_starts = storage
+            .storage_logs_dal()
+            .get_chunk_starts_for_miniblock(snapshot_miniblock, key_chunks)
+            .await
+            .context("Failed getting chunk starts")?;
+        let chunk_starts_latency = chunk_starts_latency.observe();
+        tracing::debug!(
+            "Loaded start entries for {} chunks in {chunk_starts_latency:?}",
+            key_chunks.len()
+        );
+
+        let existing_starts = chunk_starts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &start)| Some((i, start?)));
+        let start_keys = existing_starts
+            .clone()
+            .map(|(_, start_entry)| start_entry.key)
+            .collect();
+        let tree_entries = self.entries(start_keys).await.with_context(|| {
+            let key_range = key_chunks
+                .first()
+                .zip(key_chunks.last())
+                .map(|(first, last)| *first.start()..=*last.end());
+            format!(
+                "Failed reading start entries of key chunks in range {key_range:?} from the tree"
+            )
+        })?;
+
+        let mut output = vec![];
+        let mut recovered_chunk_ids = vec![];
+        for (tree_entry, (i, db_entry)) in tree_entries.into_iter().zip(existing_starts) {
+            if tree_entry.is_empty() {
+                output.push((i, key_chunks[i].clone()));
+                continue;
+            }
+            if tree_entry.value != db_entry.value || tree_entry.leaf_index != db_entry.leaf_index {
+                anyhow::ensure!(
+                    repair_mismatched_chunks,
+                    "Mismatch between entry for key {:0>64x} in Postgres snapshot for miniblock #{snapshot_miniblock} \
+                     ({db_entry:?}) and tree ({tree_entry:?}); the recovery procedure may be corrupted",
+                    db_entry.key
+                );
+                tracing::warn!(
+                    "Mismatch between start entry for key {:0>64x} in Postgres snapshot for miniblock \
+                     #{snapshot_miniblock} ({db_entry:?}) and tree ({tree_entry:?}); repairing chunk {:?}",
+                    db_entry.key,
+                    key_chunks[i]
+                );
+                self.repair_chunk(storage, snapshot_miniblock, &key_chunks[i])
+                    .await?;
+            }
+            if self.chunk_journal_entry(&key_chunks[i]).await?.is_some() {
+                output.push((i, key_chunks[i].clone()));
+            } else {
+                recovered_chunk_ids.push(i);
+            }
+        }
+
+        if check_chunk_ends && !recovered_chunk_ids.is_empty() {
+            let incomplete_chunk_ids = self
+                .check_chunk_ends(
+                    storage,
+                    snapshot_miniblock,
+                    key_chunks,
+                    &recovered_chunk_ids,
+                    repair_mismatched_chunks,
+                )
+                .await?;
+            output.extend(
+                incomplete_chunk_ids
+                    .into_iter()
+                    .map(|i| (i, key_chunks[i].clone())),
+            );
+            output.sort_unstable_by_key(|(i, _)| *i);
+        }
+        Ok(output)
+    }
+
+    /// Checks that the last keys of chunks with the specified IDs (indices in `key_chunks`) are present in the tree.
+    /// These chunks are considered recovered based on their first keys, but if tree writes may have been lost,
+    /// a chunk may be only partially persisted. Since tree writes are persisted in order, a chunk is complete
+    /// if its last key is present in the tree.
+    ///
+    /// Returns IDs of incomplete chunks. These chunks are marked as partially recovered in the recovery journal,
+    /// so that their already applied entries are not applied again. Chunks with a mismatched end entry are repaired
+    /// if `repair_mismatched_chunks` is set.
+    async fn check_chunk_ends(
+        &mut self,
+        storage: &mut StorageProcessor<'_>,
+        snapshot_miniblock: MiniblockNumber,
+        key_chunks: &[ops::RangeInclusive<H256>],
+        chunk_ids: &[usize],
+        repair_mismatched_chunks: bool,
+    ) -> anyhow::Result<Vec<usize>> {
+        let checked_chunks: Vec<_> = chunk_ids.iter().map(|&i| key_chunks[i].clone()).collect();
+        let chunk_ends = storage
+            .storage_logs_dal()
+            .get_chunk_ends_for_miniblock(snapshot_miniblock, &checked_chunks)
+            .await
+            .context("Failed getting chunk ends")?;
+        let chunk_ends = chunk_ids
+            .iter()
+            .zip(chunk_ends)
+            .map(|(&i, end)| {
+                end.with_context(|| {
+                    format!(
+                        "Chunk {:?} has a start entry, but no end entry in Postgres",
+                        key_chunks[i]
+                    )
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let end_keys = chunk_ends.iter().map(|entry| entry.key).collect();
+        let tree_entries = self
+            .entries(end_keys)
+            .await
+            .context("Failed reading end entries of key chunks from the tree")?;
+        let mut incomplete_chunk_ids = vec![];
+        for ((&i, db_entry), tree_entry) in chunk_ids.iter().zip(&chunk_ends).zip(tree_entries) {
+            if tree_entry.is_empty() {
+                tracing::info!(
+                    "Chunk {:?} is only partially persisted in the tree; it will be recovered again",
+                    key_chunks[i]
+                );
+                let journal_entry = ChunkJournalEntry {
+                    recovered_version: self.recovered_version(),
+                    last_applied_key: None,
+                };
+                let journal_key = ChunkJournalEntry::journal_key(&key_chunks[i]);
+                self.set_journal_entry(journal_key, Some(journal_entry.serialize()))
+                    .await;
+                incomplete_chunk_ids.push(i);
+                continue;
+            }
+            if tree_entry.value != db_entry.value || tree_entry.leaf_index != db_entry.leaf_index {
+                anyhow::ensure!(
+                    repair_mismatched_chunks,
+                    "Mismatch between entry for key {:0>64x} in Postgres snapshot for miniblock #{snapshot_miniblock} \
+                     ({db_entry:?}) and tree ({tree_entry:?}); the recovery procedure may be corrupted",
+                    db_entry.key
+                );
+                tracing::warn!(
+                    "Mismatch between end entry for key {:0>64x} in Postgres snapshot for miniblock \
+                     #{snapshot_miniblock} ({db_entry:?}) and tree ({tree_entry:?}); repairing chunk {:?}",
+                    db_entry.key,
+                    key_chunks[i]
+                );
+                self.repair_chunk(storage, snapshot_miniblock, &key_chunks[i])
+                    .await?;
+            }
+        }
+        Ok(incomplete_chunk_ids)
+    }
+
+    /// Repairs a recovered chunk with a tree entry mismatching Postgres. All chunk entries are loaded from Postgres,
+    /// and the tree entries differing from them are overwritten. Repairs cannot lead to an incorrect tree since
+    /// the root hash of the recovered tree is still checked against the snapshot.
+    async fn repair_chunk(
+        &mut self,
+        storage: &mut StorageProcessor<'_>,
+        snapshot_miniblock: MiniblockNumber,
+        key_chunk: &ops::RangeInclusive<H256>,
+    ) -> anyhow::Result<()> {
+        let db_entries = storage
+            .storage_logs_dal()
+            .get_tree_entries_for_miniblock(snapshot_miniblock, key_chunk.clone())
+            .await
+            .with_context(|| {
+                format!("Failed getting entries for repaired chunk {key_chunk:?} in snapshot for miniblock #{snapshot_miniblock}")
+            })?;
+        let keys = db_entries.iter().map(|entry| entry.key).collect();
+        let tree_entries = self.entries(keys).await.with_context(|| {
+            format!("Failed reading entries for repaired chunk {key_chunk:?} from the tree")
+        })?;
+        let repaired_entries: Vec<_> = db_entries
+            .into_iter()
+            .zip(tree_entries)
+            .filter(|(db_entry, tree_entry)| {
+                tree_entry.value != db_entry.value || tree_entry.leaf_index != db_entry.leaf_index
+            })
+            .map(|(db_entry, _)| TreeEntry {
+                key: db_entry.key,
+                value: db_entry.value,
+                leaf_index: db_entry.leaf_index,
+            })
+            .collect();
+
+        tracing::warn!(
+            "Repaired chunk {key_chunk:?} by overwriting {} tree entries with entries from Postgres",
+            repaired_entries.len()
+        );
+        self.extend(repaired_entries).await?;
+        RECOVERY_METRICS.repaired_chunks.inc();
+        Ok(())
+    }
+
+    /// Returns the recovery journal entry for the specified chunk. Entries written for another recovered
+    /// tree version (i.e., another snapshot L1 batch) are ignored.
+    async fn chunk_journal_entry(
+        &mut self,
+        key_chunk: &ops::RangeInclusive<H256>,
+    ) -> anyhow::Result<Option<ChunkJournalEntry>> {
+        let journal_key = ChunkJournalEntry::journal_key(key_chunk);
+        let Some(raw_entry) = self.journal_entry(journal_key).await else {
+            return Ok(None);
+        };
+        let entry = ChunkJournalEntry::deserialize(&raw_entry).with_context(|| {
+            format!("Failed deserializing recovery journal entry for chunk {key_chunk:?}")
+        })?;
+
+        let recovered_version = self.recovered_version();
+        if entry.recovered_version != recovered_version {
+            tracing::warn!(
+                "Ignoring recovery journal entry for chunk {key_chunk:?} written for tree version {}; \
+                 the tree is recovered for version {recovered_version}",
+                entry.recovered_version
+            );
+            return Ok(None);
+        }
+        Ok(Some(entry))
+    }
+
+    /// Filters out `entries` already present in the tree. Used when resuming recovery of a partially applied chunk.
+    async fn filter_applied_entries(
+        &mut self,
+        key_chunk: &ops::RangeInclusive<H256>,
+        entries: Vec<TreeEntry>,
+    ) -> anyhow::Result<Vec<TreeEntry>> {
+        let keys = entries.iter().map(|entry| entry.key).collect();
+        let tree_entries = self.entries(keys).await.with_context(|| {
+            format!("Failed reading applied entries for chunk {key_chunk:?} from the tree")
+        })?;
+        let entries = entries.into_iter().zip(tree_entries);
+        let entries =
+            entries.filter_map(|(entry, tree_entry)| tree_entry.is_empty().then_some(entry));
+        Ok(entries.collect())
+    }
+
+    /// Applies the background write rate limit received from `receiver` if it has changed since it was last applied.
+    /// Since the tree RocksDB is reopened to apply the limit, this must only be called between applying entries.
+    async fn follow_rate_limit_override(
+        &mut self,
+        receiver: &mut watch::Receiver<usize>,
+    ) -> anyhow::Result<()> {
+        if !receiver.borrow().has_changed() {
+            return Ok(());
+        }
+        let rate_limit = *receiver.borrow_and_update();
+        let rate_limit = (rate_limit > 0).then_some(rate_limit);
+        tracing::info!(
+            "Changing background write rate limit of Merkle tree RocksDB to {rate_limit:?} bytes/s"
+        );
+        self.set_background_write_rate_limit(rate_limit).await
+    }
+
+    /// Applies entries received from chunk loaders to the tree in the order of arrival until all loaders
+    /// are finished. This is the only place where the tree is modified during recovery, so it doesn't need
+    /// to be locked; chunks are loaded concurrently with applying previously loaded chunks.
+    /// Returns the total number of entries inserted into the tree for recovered chunks, the total time
+    /// spent extending the tree, leaf index stats and timings for the applied chunks.
+    async fn apply_loaded_entries(
+        &mut self,
+        mut receiver: mpsc::Receiver<LoadedEntries>,
+        options: &RecoveryOptions<'_>,
+        budget: &LoadedEntriesBudget,
+        throttle: &EntryThrottle,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<(u64, Duration, ChunkLeafIndexStats, ChunkTimingsCollector)> {
+        let mut streamed_chunks = HashMap::new();
+        let mut chunk_boundaries = ChunkBoundaries::default();
+        let mut leaf_index_stats = ChunkLeafIndexStats::default();
+        let mut chunk_timings = ChunkTimingsCollector::default();
+        let mut total_entry_count = 0_u64;
+        let mut total_extend_duration = Duration::ZERO;
+        let mut rate_limit_override = options.overrides.background_write_rate_limit.clone();
+        let mut flush_tracker = FlushTracker::new(options.flush_interval);
+        loop {
+            let wait_latency =
+                RECOVERY_METRICS.chunk_latency[&ChunkRecoveryStage::WaitForEntries].start();
+            let Some(loaded) = receiver.recv().await else {
+                break; // All chunk loaders are finished
+            };
+            wait_latency.observe();
+            RECOVERY_METRICS.loaded_entries_queue_depth.dec_by(1);
+            if let Some(override_receiver) = &mut rate_limit_override {
+                self.follow_rate_limit_override(override_receiver).await?;
+            }
+
+            let tree_wait_duration = loaded.loaded_at.elapsed();
+            let LoadedEntries {
+                chunk_id,
+                key_chunk,
+                chunk_started_at,
+                acquire_duration,
+                load_duration,
+                throttled_entry_count,
+                entries,
+                kind,
+                ..
+            } = loaded;
+            chunk_boundaries.record(chunk_id, &key_chunk, &entries)?;
+            // A retried chunk loader sends chunk entries from the start.
+            let is_chunk_start = matches!(
+                kind,
+                LoadedEntriesKind::Chunk | LoadedEntriesKind::Batch { is_first: true, .. }
+            );
+            leaf_index_stats.observe(chunk_id, &key_chunk, &entries, is_chunk_start);
+            let loaded_bytes = LoadedEntriesBudget::entries_bytes(&entries);
+            let loaded_entry_count = entries.len();
+            let extend_tree_latency =
+                RECOVERY_METRICS.chunk_latency[&ChunkRecoveryStage::ExtendTree].start();
+            let extend_started_at = Instant::now();
+            // Number of inserted entries and the total time spent extending the tree for a recovered chunk.
+            let recovered_chunk_stats = async {
+                anyhow::Ok(match kind {
+                    LoadedEntriesKind::Chunk => {
+                        let entry_count = self
+                            .apply_chunk(&key_chunk, entries, options.sub_chunk_size)
+                            .await?;
+                        Some((entry_count, extend_started_at.elapsed()))
+                    }
+                    LoadedEntriesKind::Batch { is_first, is_last } => {
+                        if is_first {
+                            // If the chunk loader was retried, this resets the chunk state.
+                            let state = StreamedChunkState::new(self, &key_chunk).await?;
+                            streamed_chunks.insert(chunk_id, state);
+                        }
+                        let state = streamed_chunks.get_mut(&chunk_id).with_context(|| {
+                            format!("Received batch of entries for chunk {key_chunk:?} before its first batch")
+                        })?;
+                        self.apply_entries_batch(state, &key_chunk, entries, is_last)
+                            .await?;
+                        state.extend_duration += extend_started_at.elapsed();
+                        is_last.then(|| {
+                            let state = streamed_chunks.remove(&chunk_id).unwrap();
+                            (state.entry_count, state.extend_duration)
+                        })
+                    }
+                })
+            };
+            let recovered_chunk_stats = match recovered_chunk_stats.await {
+                Ok(stats) => stats,
+                Err(err) => {
+                    // The tree may be inconsistent after the error (e.g., if it has panicked), so recovery
+                    // cannot continue; the failed chunk is reported to make the error actionable.
+                    let err = err.context(format!(
+                        "Failed applying entries of chunk #{chunk_id} {key_chunk:?} to Merkle tree"
+                    ));
+                    let descriptor = ChunkDescriptor {
+                        index: chunk_id,
+                        key_range: key_chunk,
+                        entry_count: None,
+                    };
+                    options.events.chunk_failed(&descriptor, &err).await;
+                    return Err(err);
+                }
+            };
+            let extend_tree_latency = extend_tree_latency.observe();
+            total_extend_duration += extend_tree_latency;
+            budget.release_loaded(loaded_bytes);
+            let stage_timings = ChunkStageTimings {
+                acquire: acquire_duration,
+                load: load_duration,
+                wait: tree_wait_duration,
+                extend: extend_tree_latency,
+                byte_count: loaded_bytes as u64,
+            };
+            chunk_timings.observe_entries(chunk_id, is_chunk_start, stage_timings);
+            // If recovery is throttled, sleeps until the entries not covered by the chunk loader are paid off.
+            throttle
+                .settle(throttled_entry_count, loaded_entry_count, stop_receiver)
+                .await;
+
+            flush_tracker.observe_applied_bytes(loaded_bytes);
+            if recovered_chunk_stats.is_some() {
+                flush_tracker.observe_recovered_chunk();
+            }
+            if flush_tracker.take_flush() {
+                // The tree is only modified here, so flushing is serialized with extending the tree.
+                let flush_latency =
+                    RECOVERY_METRICS.chunk_latency[&ChunkRecoveryStage::Flush].start();
+                self.flush_db().await?;
+                let flush_latency = flush_latency.observe();
+                tracing::debug!("Flushed Merkle tree RocksDB memtables in {flush_latency:?}");
+            }
+
+            if let Some((entry_count, extend_duration)) = recovered_chunk_stats {
+                tracing::debug!(
+                    "Extended Merkle tree with {entry_count} entries for chunk #{chunk_id} {key_chunk:?} \
+                     in {extend_tree_latency:?}"
+                );
+                RECOVERY_METRICS.chunk_entries.observe(entry_count);
+                let chunk_duration = chunk_started_at.elapsed();
+                RECOVERY_METRICS.chunk_duration.observe(chunk_duration);
+                chunk_timings.chunk_recovered(chunk_id, &key_chunk, entry_count, chunk_duration);
+                RECOVERY_METRICS.observe_chunk_throughput(
+                    ChunkRecoveryStage::ExtendTree,
+                    entry_count,
+                    extend_duration,
+                );
+                total_entry_count += entry_count as u64;
+                if let Some(fingerprint_log) = &options.fingerprint_log {
+                    let fingerprint = ChunkFingerprint::new(
+                        self.recovered_version(),
+                        chunk_id,
+                        &key_chunk,
+                        entry_count as u64,
+                        self.root_hash().await,
+                    );
+                    fingerprint_log
+                        .append(&fingerprint)
+                        .await
+                        .with_context(|| {
+                            format!(
+                                "Failed recording fingerprint for chunk #{chunk_id} {key_chunk:?}"
+                            )
+                        })?;
+                }
+                let descriptor = ChunkDescriptor {
+                    index: chunk_id,
+                    key_range: key_chunk,
+                    entry_count: Some(entry_count as u64),
+                };
+                if let Some(progress_table) = &options.progress_table {
+                    progress_table.chunk_recovered(&descriptor).await;
+                }
+                options.events.chunk_recovered(&descriptor).await;
+            }
+        }
+        Ok((
+            total_entry_count,
+            total_extend_duration,
+            leaf_index_stats,
+            chunk_timings,
+        ))
+    }
+
+    /// Applies all entries of a chunk (sorted by key) to the tree, optionally in sub-chunks of the specified size.
+    /// Returns the number of inserted entries, which may be less than the number of supplied entries
+    /// if the chunk is resumed from the recovery journal.
+    async fn apply_chunk(
+        &mut self,
+        key_chunk: &ops::RangeInclusive<H256>,
+        mut all_entries: Vec<TreeEntry>,
+        sub_chunk_size: Option<usize>,
+    ) -> anyhow::Result<usize> {
+        let entry_count = all_entries.len();
+        let journal_entry = self.chunk_journal_entry(key_chunk).await?;
+        if let Some(last_applied_key) = journal_entry.and_then(|entry| entry.last_applied_key) {
+            let applied_count = all_entries.partition_point(|entry| entry.key <= last_applied_key);
+            tracing::info!(
+                "Resuming recovery of chunk {key_chunk:?} from the recovery journal; \
+                 {applied_count} / {entry_count} entries are already applied"
+            );
+            all_entries.drain(..applied_count);
+        }
+        if journal_entry.is_some() {
+            // A sub-chunk may have been applied without updating the journal (e.g., if the node was stopped
+            // in between), or the chunk may have been partially applied in streaming mode, in which entries
+            // are applied in a different order. Applied entries must not be applied again.
+            all_entries = self.filter_applied_entries(key_chunk, all_entries).await?;
+        }
+
+        let inserted_entry_count = all_entries.len();
+        let sub_chunk_size = sub_chunk_size.unwrap_or(usize::MAX).max(1);
+        let sub_chunk_count = all_entries.len().div_ceil(sub_chunk_size);
+        // Journaling is only necessary if the chunk is applied non-atomically, i.e., in multiple sub-chunks.
+        let uses_journal = sub_chunk_count > 1;
+        let journal_key = ChunkJournalEntry::journal_key(key_chunk);
+        let recovered_version = self.recovered_version();
+        if uses_journal && journal_entry.is_none() {
+            // Must be persisted before applying the first sub-chunk; otherwise, `filter_chunks()` would consider
+            // the chunk recovered after a restart.
+            let entry = ChunkJournalEntry {
+                recovered_version,
+                last_applied_key: None,
+            };
+            self.set_journal_entry(journal_key.clone(), Some(entry.serialize()))
+                .await;
+        }
+
+        let mut remaining_entries = all_entries;
+        for i in 0..sub_chunk_count {
+            let tail = remaining_entries.split_off(sub_chunk_size.min(remaining_entries.len()));
+            let sub_chunk = mem::replace(&mut remaining_entries, tail);
+            let last_applied_key = sub_chunk.last().map(|entry| entry.key);
+            self.extend(sub_chunk).await?;
+
+            if uses_journal && i + 1 < sub_chunk_count {
+                let entry = ChunkJournalEntry {
+                    recovered_version,
+                    last_applied_key,
+                };
+                self.set_journal_entry(journal_key.clone(), Some(entry.serialize()))
+                    .await;
+            }
+        }
+        if uses_journal || journal_entry.is_some() {
+            self.set_journal_entry(journal_key, None).await;
+        }
+        Ok(inserted_entry_count)
+    }
+
+    /// Applies a batch of streamed chunk entries to the tree. Since a streamed chunk is applied non-atomically,
+    /// its progress is tracked in the recovery journal in the same way as for sub-chunks.
+    async fn apply_entries_batch(
+        &mut self,
+        state: &mut StreamedChunkState,
+        key_chunk: &ops::RangeInclusive<H256>,
+        mut batch: Vec<TreeEntry>,
+        is_last: bool,
+    ) -> anyhow::Result<()> {
+        if state.is_resumed {
+            batch = self.filter_applied_entries(key_chunk, batch).await?;
+        }
+        state.entry_count += batch.len();
+        let journal_key = ChunkJournalEntry::journal_key(key_chunk);
+        if !is_last && !state.uses_journal {
+            // Must be persisted before applying the first batch; otherwise, `filter_chunks()` would consider
+            // the chunk recovered after a restart.
+            let entry = ChunkJournalEntry {
+                recovered_version: self.recovered_version(),
+                last_applied_key: None,
+            };
+            self.set_journal_entry(journal_key.clone(), Some(entry.serialize()))
+                .await;
+            state.uses_journal = true;
+        }
+        if !batch.is_empty() {
+            self.extend(batch).await?;
+        }
+        if is_last && state.uses_journal {
+            self.set_journal_entry(journal_key, None).await;
+        }
+        Ok(())
+    }
+
+    /// Loads a single chunk and sends its entries to the tree applier, retrying transient errors
+    /// with exponential backoff.
+    async fn load_key_chunk_with_retries(
+        chunk_id: usize,
+        key_chunk: ops::RangeInclusive<H256>,
+        context: ChunkLoaderContext<'_>,
+        options: &RecoveryOptions<'_>,
+    ) -> anyhow::Result<ChunkLoadOutcome> {
+        let max_attempts = options.max_chunk_attempts.max(1);
+        let mut attempt = 1;
+        let entry_source = options.entry_source();
+        let streaming_batch_size = options
+            .streaming_batch_size
+            .filter(|_| entry_source.supports_batches());
+        loop {
+            let load_result = if let Some(batch_size) = streaming_batch_size {
+                Self::load_key_chunk_streaming(
+                    chunk_id,
+                    &key_chunk,
+                    entry_source,
+                    batch_size,
+                    context,
+                )
+                .await
+            } else {
+                Self::load_key_chunk(chunk_id, &key_chunk, entry_source, context).await
+            };
+            let err = match load_result {
+                Ok(ChunkLoadOutcome::Loaded { .. }) => {
+                    return Ok(ChunkLoadOutcome::Loaded {
+                        retries: attempt - 1,
+                    });
+                }
+                Ok(outcome) => return Ok(outcome),
+                Err(err) => err,
+            };
+            if attempt >= max_attempts || !is_transient_error(&err) {
+                return Err(err);
+            }
+
+            let delay = options.chunk_retry_delays.delay(attempt);
+            tracing::warn!(
+                "Transient error recovering chunk {key_chunk:?} (attempt {attempt}/{max_attempts}); \
+                 retrying in {delay:?}: {err:#}"
+            );
+            RECOVERY_METRICS.chunk_retries.inc();
+            options.events.chunk_retried(attempt).await;
+
+            let mut stop_receiver = context.stop_receiver.clone();
+            tokio::time::timeout(delay, stop_receiver.changed())
+                .await
+                .ok();
+            if *stop_receiver.borrow() {
+                return Ok(ChunkLoadOutcome::Interrupted);
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Loads all entries of a single chunk and sends them to the tree applier. A stop signal prevents the chunk
+    /// from starting and interrupts loading its entries; once the entries are loaded, they are always sent
+    /// to the applier, so that the work isn't lost on shutdown. If the soft cap on loaded entries is exceeded,
+    /// loading waits until enough loaded entries are applied to the tree.
+    async fn load_key_chunk(
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        entry_source: &dyn RecoveryEntrySource,
+        context: ChunkLoaderContext<'_>,
+    ) -> anyhow::Result<ChunkLoadOutcome> {
+        let ChunkLoaderContext {
+            concurrency,
+            budget,
+            throttle,
+            stop_receiver,
+            entries_sender,
+        } = context;
+        if *stop_receiver.borrow() {
+            return Ok(ChunkLoadOutcome::Interrupted);
+        }
+        let reserve_started_at = Instant::now();
+        let _reservation = budget.reserve_chunk().await;
+        let throttled_entry_count = throttle.estimated_chunk_entry_count();
+        if !throttle.acquire(throttled_entry_count, stop_receiver).await {
+            tracing::info!("Stop signal received while throttling chunk {key_chunk:?}");
+            return Ok(ChunkLoadOutcome::Interrupted);
+        }
+
+        let chunk_started_at = Instant::now();
+        let entries_latency =
+            RECOVERY_METRICS.chunk_latency[&ChunkRecoveryStage::LoadEntries].start();
+        let Some(all_entries) = entry_source
+            .load_entries(chunk_id, key_chunk, stop_receiver)
+            .await?
+        else {
+            tracing::info!("Stop signal received while loading entries for chunk {key_chunk:?}");
+            return Ok(ChunkLoadOutcome::Interrupted);
+        };
+        let entries_latency = entries_latency.observe();
+        concurrency.observe_latency(entries_latency);
+        RECOVERY_METRICS.observe_chunk_throughput(
+            ChunkRecoveryStage::LoadEntries,
+            all_entries.len(),
+            entries_latency,
+        );
+        tracing::debug!(
+            "Loaded {} entries for chunk {key_chunk:?} in {entries_latency:?}",
+            all_entries.len()
+        );
+
+        // Entries are ordered by the tree key, so that sub-chunks are defined in the same way across restarts.
+        let all_entries = sort_and_check_entries(all_entries).await?;
+
+        let loaded = LoadedEntries {
+            chunk_id,
+            key_chunk: key_chunk.clone(),
+            chunk_started_at,
+            acquire_duration: chunk_started_at.duration_since(reserve_started_at),
+            load_duration: chunk_started_at.elapsed(),
+            loaded_at: Instant::now(),
+            throttled_entry_count,
+            entries: all_entries,
+            kind: LoadedEntriesKind::Chunk,
+        };
+        loaded.send(entries_sender, budget).await?;
+        Ok(ChunkLoadOutcome::Loaded { retries: 0 })
+    }
+
+    /// Loads entries of a single chunk from the source in batches, sending each batch to the tree applier
+    /// as soon as it's loaded, so that peak memory usage is bounded by the batch size times the recovery
+    /// concurrency. Batches are defined by keyset pagination on the hashed key; each batch starts after
+    /// the last key of the previous batch. Unlike with [`Self::load_key_chunk()`], a stop signal received
+    /// while loading a batch interrupts chunk recovery; recovery is resumed from the journal after a restart.
+    async fn load_key_chunk_streaming(
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        entry_source: &dyn RecoveryEntrySource,
+        batch_size: usize,
+        context: ChunkLoaderContext<'_>,
+    ) -> anyhow::Result<ChunkLoadOutcome> {
+        let ChunkLoaderContext {
+            concurrency,
+            budget,
+            throttle,
+            stop_receiver,
+            entries_sender,
+        } = context;
+        if *stop_receiver.borrow() {
+            return Ok(ChunkLoadOutcome::Interrupted);
+        }
+        let reserve_started_at = Instant::now();
+        let _reservation = budget.reserve_chunk().await;
+        let batch_size = batch_size.max(1);
+
+        let chunk_started_at = Instant::now();
+        let mut last_key = None::<U256>;
+        loop {
+            let batch_started_at = Instant::now();
+            if !throttle.acquire(batch_size, stop_receiver).await {
+                tracing::info!("Stop signal received while throttling chunk {key_chunk:?}");
+                return Ok(ChunkLoadOutcome::Interrupted);
+            }
+            let after_key = last_key.as_ref().map(hashed_key);
+            let entries_latency =
+                RECOVERY_METRICS.chunk_latency[&ChunkRecoveryStage::LoadEntries].start();
+            let batch = entry_source
+                .load_entries_batch(chunk_id, key_chunk, after_key, batch_size, stop_receiver)
+                .await?;
+            let Some(batch) = batch else {
+                tracing::info!(
+                    "Stop signal received while streaming entries for chunk {key_chunk:?}"
+                );
+                return Ok(ChunkLoadOutcome::Interrupted);
+            };
+            if last_key.is_none() {
+                let entries_latency = entries_latency.observe();
+                // Only the first batch is loaded independently of the tree applier (the following batches
+                // may be throttled by it), so only its latency is representative for concurrency control.
+                concurrency.observe_latency(entries_latency);
+            }
+
+            let is_first = last_key.is_none();
+            let is_last = batch.len() < batch_size;
+            ensure_distinct_keys(&batch)?;
+            last_key = batch.last().map(|entry| entry.key);
+
+            let acquire_duration = if is_first {
+                chunk_started_at.duration_since(reserve_started_at)
+            } else {
+                Duration::ZERO
+            };
+            let loaded = LoadedEntries {
+                chunk_id,
+                key_chunk: key_chunk.clone(),
+                chunk_started_at,
+                acquire_duration,
+                load_duration: batch_started_at.elapsed(),
+                loaded_at: Instant::now(),
+                throttled_entry_count: batch_size,
+                entries: batch,
+                kind: LoadedEntriesKind::Batch { is_first, is_last },
+            };
+            loaded.send(entries_sender, budget).await?;
+            if is_last {
+                return Ok(ChunkLoadOutcome::Loaded { retries: 0 });
+            }
+        }
+    }
+}
+
+/// Recovery state shared by chunk loaders (see [`AsyncTreeRecovery::load_key_chunk_with_retries()`]).
+#[derive(Debug, Clone, Copy)]
+struct ChunkLoaderContext<'a> {
+    concurrency: &'a AdaptiveConcurrency,
+    budget: &'a LoadedEntriesBudget,
+    throttle: &'a EntryThrottle,
+    stop_receiver: &'a watch::Receiver<bool>,
+    /// Sender owned by the chunk loader task.
+    entries_sender: &'a LoadedEntriesSender,
+}
+
+/// Outcome of loading a single chunk by a chunk loader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkLoadOutcome {
+    /// All chunk entries were loaded and sent to the tree applier.
+    Loaded {
+        /// Number of retries caused by transient errors.
+        retries: usize,
+    },
+    /// Loading was interrupted by a stop signal; the chunk must not be considered loaded.
+    Interrupted,
+    /// The chunk wasn't started because the recovery deadline was exceeded or the snapshot was superseded.
+    Skipped,
+}
+
+impl ChunkLoadOutcome {
+    fn retries(self) -> usize {
+        match self {
+            Self::Loaded { retries } => retries,
+            Self::Interrupted | Self::Skipped => 0,
+        }
+    }
+}
+
+/// Information about a chunk that failed to recover.
+#[derive(Debug, Clone, Serialize)]
+struct FailedChunk {
+    chunk_id: usize,
+    key_range: ops::RangeInclusive<H256>,
+    error_kind: RecoveryErrorKind,
+    /// Error message including the error context.
+    error: String,
+}
+
+/// Combined error for chunks that failed to recover. Chunks not mentioned in the error are recovered
+/// and will be skipped when recovery is resumed.
+#[derive(Debug)]
+pub struct FailedChunks {
+    /// Number of chunks that recovery was attempted for.
+    chunk_count: usize,
+    chunks: Vec<FailedChunk>,
+}
+
+impl FailedChunk {
+    fn new(chunk_id: usize, key_range: ops::RangeInclusive<H256>, err: &anyhow::Error) -> Self {
+        Self {
+            chunk_id,
+            key_range,
+            error_kind: RecoveryError::kind_of(err),
+            error: format!("{err:#}"),
+        }
+    }
+}
+
+impl fmt::Display for FailedChunks {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "failed recovering {} / {} chunks",
+            self.chunks.len(),
+            self.chunk_count
+        )?;
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            let separator = if i == 0 { ": " } else { "; " };
+            write!(
+                formatter,
+                "{separator}chunk #{} {:?}: {}",
+                chunk.chunk_id, chunk.key_range, chunk.error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FailedChunks {}
+
+/// Entries loaded by a chunk loader and sent to the tree applier (see [`AsyncTreeRecovery::apply_loaded_entries()`]).
+#[derive(Debug)]
+struct LoadedEntries {
+    chunk_id: usize,
+    key_chunk: ops::RangeInclusive<H256>,
+    /// Time when the loader has started loading the chunk.
+    chunk_started_at: Instant,
+    /// Time the loader has spent waiting for the loaded entries budget before loading the chunk. Only set
+    /// for the first batch of streamed entries.
+    acquire_duration: Duration,
+    /// Time spent loading these entries.
+    load_duration: Duration,
+    /// Time when the entries were loaded and started waiting for the tree applier.
+    loaded_at: Instant,
+    /// Number of entries acquired from [`EntryThrottle`] by the loader for these entries.
+    throttled_entry_count: usize,
+    entries: Vec<TreeEntry>,
+    kind: LoadedEntriesKind,
+}
+
+impl LoadedEntries {
+    async fn send(
+        self,
+        sender: &LoadedEntriesSender,
+        budget: &LoadedEntriesBudget,
+    ) -> anyhow::Result<()> {
+        let loaded_bytes = LoadedEntriesBudget::entries_bytes(&self.entries);
+        budget.track_loaded(loaded_bytes);
+        RECOVERY_METRICS.loaded_entries_queue_depth.inc_by(1);
+        let wait_latency = RECOVERY_METRICS.tree_wait.start();
+        let queue_guard = RECOVERY_METRICS.tree_wait_queue_depth.inc_guard(1);
+        let send_result = sender.inner.send(self).await;
+        drop(queue_guard);
+        let wait_latency = wait_latency.observe();
+        sender
+            .tree_wait_duration
+            .fetch_add(wait_latency.as_nanos() as u64, Ordering::Relaxed);
+        if send_result.is_err() {
+            RECOVERY_METRICS.loaded_entries_queue_depth.dec_by(1);
+            budget.release_loaded(loaded_bytes);
+            anyhow::bail!("tree applier has stopped");
+        }
+        Ok(())
+    }
+}
+
+/// Sender of [`LoadedEntries`] to the tree applier tracking the time chunk loaders spend waiting for the applier.
+#[derive(Debug, Clone)]
+struct LoadedEntriesSender {
+    inner: mpsc::Sender<LoadedEntries>,
+    /// Total wait time in nanoseconds, shared among all clones of the sender.
+    tree_wait_duration: Arc<AtomicU64>,
+}
+
+impl LoadedEntriesSender {
+    fn new(inner: mpsc::Sender<LoadedEntries>) -> Self {
+        Self {
+            inner,
+            tree_wait_duration: Arc::default(),
+        }
+    }
+}
+
+/// Kind of [`LoadedEntries`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoadedEntriesKind {
+    /// All entries of a chunk sorted by key.
+    Chunk,
+    /// Batch of streamed chunk entries sorted by hashed key.
+    Batch { is_first: bool, is_last: bool },
+}
+
+/// State of a chunk with streamed entries maintained by the tree applier.
+#[derive(Debug)]
+struct StreamedChunkState {
+    /// Whether the chunk is resumed from the recovery journal; if so, some of its entries may be already applied.
+    is_resumed: bool,
+    /// Whether the chunk progress is recorded in the recovery journal.
+    uses_journal: bool,
+    /// Number of entries inserted into the tree so far.
+    entry_count: usize,
+    /// Total time spent extending the tree with the chunk entries so far.
+    extend_duration: Duration,
+}
+
+impl StreamedChunkState {
+    async fn new(
+        tree: &mut AsyncTreeRecovery,
+        key_chunk: &ops::RangeInclusive<H256>,
+    ) -> anyhow::Result<Self> {
+        let is_resumed = tree.chunk_journal_entry(key_chunk).await?.is_some();
+        if is_resumed {
+            tracing::info!(
+                "Resuming streaming recovery of chunk {key_chunk:?} from the recovery journal"
+            );
+        }
+        Ok(Self {
+            is_resumed,
+            uses_journal: is_resumed,
+            entry_count: 0,
+            extend_duration: Duration::ZERO,
+        })
+    }
+}
+
+/// Boundaries of entries applied to the tree for a chunk.
+#[derive(Debug)]
+struct ChunkBoundary {
+    key_chunk: ops::RangeInclusive<H256>,
+    /// Minimum hashed key of the applied entries.
+    first_key: H256,
+    /// Maximum hashed key of the applied entries.
+    last_key: H256,
+}
+
+/// Tracks boundaries of entries applied to the tree for each chunk, so that the tree applier can check that
+/// no key is applied by two chunks (e.g., because of overlapping chunk ranges). [`ensure_distinct_keys()`] only checks
+/// entries within a single chunk. Since chunk IDs are ordered in the same way as chunk ranges, it's sufficient
+/// to compare boundaries of a chunk with the boundaries of its nearest tracked neighbors.
+///
+/// Only chunks applied since recovery was (re)started are tracked.
+#[derive(Debug, Default)]
+struct ChunkBoundaries {
+    by_chunk_id: BTreeMap<usize, ChunkBoundary>,
+}
+
+impl ChunkBoundaries {
+    /// Records `entries` applied for a chunk (all entries or a streamed batch) and checks that they don't overlap
+    /// with entries applied for the neighboring chunks.
+    fn record(
+        &mut self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        entries: &[TreeEntry],
+    ) -> anyhow::Result<()> {
+        let mut hashed_keys = entries.iter().map(|entry| hashed_key(&entry.key));
+        let Some(key) = hashed_keys.next() else {
+            return Ok(());
+        };
+        let (first_key, last_key) = match self.by_chunk_id.get(&chunk_id) {
+            Some(boundary) => (boundary.first_key.min(key), boundary.last_key.max(key)),
+            None => (key, key),
+        };
+        let (first_key, last_key) = hashed_keys
+            .fold((first_key, last_key), |(first, last), key| {
+                (first.min(key), last.max(key))
+            });
+
+        if let Some((prev_id, prev)) = self.by_chunk_id.range(..chunk_id).next_back() {
+            anyhow::ensure!(
+                prev.last_key < first_key,
+                "Chunks #{prev_id} {:?} and #{chunk_id} {key_chunk:?} overlap: key {:?} applied by chunk #{prev_id} \
+                 is not less than key {first_key:?} applied by chunk #{chunk_id}",
+                prev.key_chunk,
+                prev.last_key
+            );
+        }
+        if let Some((next_id, next)) = self.by_chunk_id.range(chunk_id + 1..).next() {
+            anyhow::ensure!(
+                last_key < next.first_key,
+                "Chunks #{chunk_id} {key_chunk:?} and #{next_id} {:?} overlap: key {last_key:?} applied by \
+                 chunk #{chunk_id} is not less than key {:?} applied by chunk #{next_id}",
+                next.key_chunk,
+                next.first_key
+            );
+        }
+
+        self.by_chunk_id.insert(
+            chunk_id,
+            ChunkBoundary {
+                key_chunk: key_chunk.clone(),
+                first_key,
+                last_key,
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Checks that keys of sorted `entries` are distinct. Otherwise, we may end up writing non-final values
+/// to the tree, since we don't enforce any ordering on entries besides by the hashed key.
+fn ensure_distinct_keys(entries: &[TreeEntry]) -> anyhow::Result<()> {
+    for window in entries.windows(2) {
+        let [prev_entry, next_entry] = window else {
+            unreachable!();
+        };
+        if prev_entry.key == next_entry.key {
+            return Err(RecoveryError::CorruptedSnapshot {
+                key: hashed_key(&prev_entry.key),
+                details: format!("entries {prev_entry:?} and {next_entry:?} have the same key"),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Sorts chunk entries by the tree key and checks that keys are distinct (see [`ensure_distinct_keys()`]).
+/// Chunks may contain hundreds of thousands of entries, so this is performed on a blocking thread in order
+/// not to stall the async runtime shared by chunk loaders and the tree applier.
+async fn sort_and_check_entries(mut entries: Vec<TreeEntry>) -> anyhow::Result<Vec<TreeEntry>> {
+    tokio::task::spawn_blocking(move || {
+        entries.sort_unstable_by_key(|entry| entry.key);
+        ensure_distinct_keys(&entries)?;
+        Ok(entries)
+    })
+    .await
+    .context("panicked sorting chunk entries")?
+}
+
+/// Converts entries of an entire chunk loaded from Postgres into tree entries on a blocking thread
+/// (see [`sort_and_check_entries()`] for the rationale).
+async fn convert_entries<T: Send + 'static>(
+    entries: Vec<T>,
+    convert: fn(T) -> TreeEntry,
+) -> anyhow::Result<Vec<TreeEntry>> {
+    tokio::task::spawn_blocking(move || entries.into_iter().map(convert).collect())
+        .await
+        .context("panicked converting chunk entries")
+}
+
+/// Converts a tree key to the corresponding hashed key.
+pub(super) fn hashed_key(key: &U256) -> H256 {
+    let mut bytes = [0_u8; 32];
+    key.to_little_endian(&mut bytes);
+    H256(bytes)
+}
+
+/// Verifies the Postgres snapshot by recovering a temporary tree, without touching the production tree DB.
+/// Returns [`RecoveryError::Interrupted`] if the dry run was interrupted by a stop signal.
+/// Snapshot chunks are loaded using dedicated `pools` if they are supplied.
+#[allow(clippy::too_many_arguments)]
+async fn dry_run_recovery(
+    config: &MetadataCalculatorRecoveryConfig,
+    snapshot_recovery: &SnapshotRecoveryStatus,
+    pool: &ConnectionPool,
+    pools: RecoveryPools<'_>,
+    snapshot_object_store: Option<&dyn ObjectStore>,
+    snapshot_file: Option<&FileSnapshotSource>,
+    overrides: RecoveryOverrides,
+    stop_receiver: &watch::Receiver<bool>,
+    health_updater: &HealthUpdater,
+) -> Result<(), RecoveryError> {
+    let l1_batch = snapshot_recovery.l1_batch_number;
+    tracing::info!("Starting dry-run Merkle tree recovery with snapshot L1 batch #{l1_batch}");
+
+    let temp_dir = tempfile::TempDir::new()
+        .context("Failed creating temporary directory for dry-run Merkle tree recovery")?;
+    let params = TreeDbParams {
+        block_cache_capacity: 128 << 20, // 128 MiB
+        memtable_capacity: 256 << 20,    // 256 MiB
+        total_write_buffer_size: None,
+        max_open_files: None,
+        stalled_writes_timeout: Duration::from_secs(30),
+        multi_get_chunk_size: 500,
+    };
+    let db = create_db(
+        temp_dir.path().to_owned(),
+        params,
+        recovery_db_profile(config),
+    )
+    .await;
+    let mut tree = AsyncTreeRecovery::new(db, l1_batch.0.into(), MerkleTreeMode::Lightweight);
+    if let Some(rate_limit) = config.background_write_rate_limit {
+        // The temporary tree shares the disk with the production one, so it's throttled in the same way.
+        tree.set_background_write_rate_limit(Some(rate_limit))
+            .await?;
+    }
+
+    let chunk_pool = pools.recovery.unwrap_or(pool);
+    let replica = pools.replica.map(|replica_pool| {
+        SnapshotReplica::new(chunk_pool, replica_pool, snapshot_recovery.miniblock_number)
+    });
+    let snapshot = match snapshot_file {
+        Some(snapshot_file) => {
+            snapshot_file
+                .snapshot_parameters(pool, snapshot_recovery)
+                .await?
+        }
+        None => SnapshotParameters::new(pool, replica.as_ref(), snapshot_recovery).await?,
+    };
+    let (chunk_count, entry_source) = tree
+        .entry_source(
+            config,
+            &snapshot,
+            snapshot_recovery,
+            chunk_pool,
+            replica.as_ref(),
+            snapshot_object_store,
+            snapshot_file,
+        )
+        .await?;
+    let recovery_options = RecoveryOptions {
+        mode: RecoveryMode::DryRun,
+        chunk_count,
+        concurrency_limit: concurrency_limit(config, chunk_pool)?,
+        overrides,
+        max_chunk_attempts: config.max_chunk_attempts,
+        chunk_retry_delays: ChunkRetryDelays::new(config),
+        fail_fast: false,
+        sub_chunk_size: config.sub_chunk_size,
+        streaming_batch_size: config.streaming_batch_size,
+        loaded_entries_soft_cap: config.loaded_entries_soft_cap,
+        flush_interval: flush_interval(config),
+        max_entries_per_second: config.max_entries_per_second,
+        max_duration: config.max_recovery_duration,
+        latest_snapshot_check: None,
+        prioritize_large_chunks: config.prioritize_large_chunks,
+        disk_space_check: disk_space_check(config),
+        verification_samples_per_chunk: config.verification_samples_per_chunk,
+        proof_verification_samples: config.proof_verification_samples,
+        mismatch_diagnostic_keys_per_chunk: config.mismatch_diagnostic_keys_per_chunk,
+        watchdog: watchdog_options(config),
+        fingerprint_log: None,
+        progress_table: None,
+        chunk_filter_batch_size: config.chunk_filter_batch_size,
+        check_chunk_ends: config.check_chunk_ends,
+        repair_mismatched_chunks: config.repair_mismatched_chunks,
+        // The dry run is meant to verify the snapshot, so the root hash is always checked.
+        skip_root_hash_check: false,
+        replica: replica.as_ref(),
+        connection_retry_timeout: config.connection_retry_timeout,
+        connection_acquire_timeout: config.connection_acquire_timeout,
+        entry_source: Some(entry_source),
+        events: Arc::new(
+            RecoveryHealthUpdater::new(health_updater, RecoveryMode::DryRun, snapshot.log_count)
+                .with_health_update_interval(config.health_update_interval),
+        ),
+    };
+    let tree = match tree
+        .recover(snapshot, recovery_options, pool, stop_receiver)
+        .await
+    {
+        Ok((tree, _)) => tree,
+        Err(RecoveryError::Interrupted) => {
+            tracing::info!("Dry-run Merkle tree recovery was interrupted");
+            return Err(RecoveryError::Interrupted);
+        }
+        Err(err) => {
+            tracing::error!("Dry-run Merkle tree recovery failed: {err:#}");
+            return Err(err);
+        }
+    };
+    tracing::info!(
+        "Dry-run Merkle tree recovery verified snapshot for L1 batch #{l1_batch}; root hash: {:?}",
+        tree.root_hash()
+    );
+    drop(tree);
+    tokio::task::spawn_blocking(move || temp_dir.close())
+        .await
+        .context("panicked removing temporary directory")?
+        .context("Failed removing temporary directory for dry-run Merkle tree recovery")?;
+    Ok(())
+}
+
+/// Prunes old tree versions and compacts the tree RocksDB if pruning is enabled in `config`. Since pruning progress
+/// is persisted, this resumes pruning interrupted by a stop signal.
+async fn prune_if_configured(
+    tree: &AsyncTree,
+    config: &MetadataCalculatorRecoveryConfig,
+    force_compaction: bool,
+    stop_receiver: &watch::Receiver<bool>,
+) -> Result<(), RecoveryError> {
+    let Some(past_versions_to_keep) = config.pruning_retained_versions else {
+        return Ok(());
+    };
+    let stats = prune_and_compact(
+        tree.reader(),
+        past_versions_to_keep,
+        force_compaction,
+        stop_receiver,
+    )
+    .await;
+    stats.map(drop).ok_or(RecoveryError::Interrupted)
+}
+
+/// Exports the tree if export is configured, but wasn't completed after recovery (e.g., because the node
+/// was restarted after finalizing recovery). The export is only possible while the tree is at the recovered
+/// L1 batch; once the tree has processed more batches, the export is skipped with a warning.
+async fn resume_export(
+    tree: &AsyncTree,
+    config: &MetadataCalculatorRecoveryConfig,
+    pool: &ConnectionPool,
+    health_updater: &HealthUpdater,
+) -> anyhow::Result<()> {
+    let Some(export_path) = &config.export_path else {
+        return Ok(());
+    };
+    if export_path.exists() {
+        return Ok(());
+    }
+    let Some(target) =
+        get_recovery_target(config, pool, ready_tree_recovered_version(tree)).await?
+    else {
+        return Ok(()); // the tree wasn't recovered from a snapshot
+    };
+
+    let l1_batch = target.snapshot_recovery.l1_batch_number;
+    if tree.next_l1_batch_number() != l1_batch + 1 {
+        tracing::warn!(
+            "Merkle tree export to `{}` wasn't completed after recovery, but the tree has progressed beyond \
+             the recovered L1 batch #{l1_batch}; skipping the export",
+            export_path.display()
+        );
+        return Ok(());
+    }
+    tracing::info!(
+        "Resuming export of the Merkle tree recovered to L1 batch #{l1_batch} to `{}`",
+        export_path.display()
+    );
+    let miniblock = target.snapshot_recovery.miniblock_number;
+    export_recovered_tree(tree, miniblock, export_path, health_updater).await?;
+    Ok(())
+}
+
+/// Checks the tree prepared by [`GenericAsyncTree::ensure_ready()`] before stopping as configured
+/// (see [`MetadataCalculatorRecoveryConfig::stop_after_recovery`]). The tree must be at the snapshot L1 batch
+/// and have the snapshot root hash. On success, health is switched to [`HealthStatus::ShutDown`].
+pub(super) async fn finish_recovery_run(
+    tree: &AsyncTree,
+    report: &RecoveryReport,
+    config: &MetadataCalculatorRecoveryConfig,
+    pool: &ConnectionPool,
+    health_updater: &HealthUpdater,
+) -> Result<(), RecoveryError> {
+    let target = get_recovery_target(config, pool, ready_tree_recovered_version(tree))
+        .await?
+        .ok_or_else(|| {
+        RecoveryError::SnapshotMissing(
+            "Merkle tree is configured to stop after recovery, but Postgres doesn't contain a snapshot to recover from"
+                .to_owned(),
+        )
+    })?;
+    let snapshot_recovery = &target.snapshot_recovery;
+    let l1_batch = snapshot_recovery.l1_batch_number;
+    let next_l1_batch = tree.next_l1_batch_number();
+    if next_l1_batch != l1_batch + 1 {
+        let err = anyhow::anyhow!(
+            "Merkle tree is configured to stop after recovery to L1 batch #{l1_batch}, but the next L1 batch \
+             for the tree is #{next_l1_batch}"
+        );
+        return Err(err.into());
+    }
+    let root_hash = tree.root_hash();
+    let taint = tree.taint()?;
+    if root_hash != snapshot_recovery.l1_batch_root_hash {
+        // A tainted tree was knowingly finalized with this mismatch; stopping allows to inspect it.
+        let is_tainted_by_mismatch = taint.map_or(false, |taint| {
+            taint.expected_root_hash == snapshot_recovery.l1_batch_root_hash
+                && taint.actual_root_hash == root_hash
+        });
+        if !is_tainted_by_mismatch {
+            return Err(RecoveryError::RootHashMismatch {
+                expected: snapshot_recovery.l1_batch_root_hash,
+                actual: root_hash,
+                diagnostics: None,
+            });
+        }
+        tracing::error!(
+            "Merkle tree recovered to L1 batch #{l1_batch} is TAINTED: its root hash {root_hash:?} differs from \
+             the snapshot root hash {:?}",
+            snapshot_recovery.l1_batch_root_hash
+        );
+    }
+
+    tracing::info!(
+        "Merkle tree is recovered to L1 batch #{l1_batch} with root hash {root_hash:?}; stopping as configured"
+    );
+    let health = Health::from(HealthStatus::ShutDown).with_details(RecoveryCompleteInfo {
+        mode: "recovery_complete",
+        l1_batch_number: l1_batch,
+        root_hash,
+        recovery_report: report,
+        tainted: taint,
+    });
+    health_updater.update(health);
+    Ok(())
+}
+
+/// Checks whether the tree prepared by [`GenericAsyncTree::ensure_ready()`] is tainted (see [`TreeTaint`]).
+/// A tainted tree must not be used for normal operation (which includes serving Merkle proofs) unless
+/// [`MetadataCalculatorRecoveryConfig::experimental_allow_tainted_tree`] is set; otherwise, health is switched
+/// to [`HealthStatus::NotReady`] with the taint in details, and an error is returned.
+pub(super) fn check_tree_taint(
+    tree: &AsyncTree,
+    config: &MetadataCalculatorRecoveryConfig,
+    health_updater: &HealthUpdater,
+) -> anyhow::Result<Option<TreeTaint>> {
+    let Some(taint) = tree.taint()? else {
+        return Ok(None);
+    };
+    let TreeTaint {
+        expected_root_hash,
+        actual_root_hash,
+    } = taint;
+    if !config.experimental_allow_tainted_tree {
+        let health = Health::from(HealthStatus::NotReady).with_details(TaintedTreeInfo {
+            mode: "tainted",
+            tainted: taint,
+        });
+        health_updater.update(health);
+        anyhow::bail!(
+            "Merkle tree is tainted: its recovery was finalized despite the root hash mismatch (expected \
+             {expected_root_hash:?}, got {actual_root_hash:?}) because `experimental_skip_root_hash_check` was set. \
+             Refusing to use the tree for normal operation; remove the tree to recover it from scratch, or set \
+             `experimental_allow_tainted_tree` in the Merkle tree recovery config to use it anyway (UNSAFE)"
+        );
+    }
+    tracing::error!(
+        "!!! Using TAINTED Merkle tree (expected root hash {expected_root_hash:?}, got {actual_root_hash:?}) \
+         for normal operation as configured. L1 batch metadata and Merkle proofs produced by the tree \
+         MUST NOT be trusted !!!"
+    );
+    Ok(Some(taint))
+}
+
+/// Returns the disk space check performed before recovery, or `None` if the check is disabled.
+fn disk_space_check(config: &MetadataCalculatorRecoveryConfig) -> Option<DiskSpaceCheck<'static>> {
+    (config.estimated_bytes_per_entry > 0).then_some(DiskSpaceCheck {
+        bytes_per_entry: config.estimated_bytes_per_entry,
+        margin_bytes: config.disk_space_margin as u64,
+        strict: config.strict_disk_space_check,
+        fs_stats: &OsFsStats,
+    })
+}
+
+/// Returns the RocksDB tuning profile for recovery, or `None` if recovery should use the normal RocksDB options.
+fn recovery_db_profile(config: &MetadataCalculatorRecoveryConfig) -> Option<RecoveryDbProfile> {
+    Some(RecoveryDbProfile {
+        memtable_capacity: config.bulk_load_memtable_capacity?,
+        max_write_buffer_number: config.bulk_load_max_write_buffers,
+        block_cache_capacity: config.bulk_load_block_cache_capacity,
+        total_write_buffer_size: config.bulk_load_total_write_buffer_size,
+        max_open_files: config.bulk_load_max_open_files,
+    })
+}
+
+/// Returns limits on the number of concurrently recovered chunks. The maximum concurrency defaults to the pool size;
+/// an explicitly configured value must not exceed it, since each concurrently recovered chunk may hold a connection.
+/// Concurrency is adaptive only if the minimum concurrency is configured.
+fn flush_interval(config: &MetadataCalculatorRecoveryConfig) -> FlushInterval {
+    FlushInterval {
+        chunks: config.flush_interval_chunks,
+        bytes: config.flush_interval_bytes,
+    }
+}
+
+fn watchdog_options(config: &MetadataCalculatorRecoveryConfig) -> Option<WatchdogOptions> {
+    if config.stall_check_interval.is_zero() {
+        return None;
+    }
+    Some(WatchdogOptions {
+        check_interval: config.stall_check_interval,
+        stall_threshold: config.stall_threshold,
+    })
+}
+
+fn concurrency_limit(
+    config: &MetadataCalculatorRecoveryConfig,
+    pool: &ConnectionPool,
+) -> anyhow::Result<ConcurrencyLimits> {
+    let pool_size = pool.max_size() as usize;
+    let max_concurrency = config.concurrency.unwrap_or(pool_size);
+    anyhow::ensure!(
+        max_concurrency > 0,
+        "Recovery concurrency is misconfigured to be 0; please update it to positive value"
+    );
+    anyhow::ensure!(
+        max_concurrency <= pool_size,
+        "Recovery concurrency ({max_concurrency}) exceeds the size of the recovery connection pool ({pool_size})"
+    );
+
+    let Some(min_concurrency) = config.min_concurrency else {
+        return Ok(ConcurrencyLimits::fixed(max_concurrency).with_ceiling(pool_size));
+    };
+    anyhow::ensure!(
+        min_concurrency > 0 && min_concurrency <= max_concurrency,
+        "Minimum recovery concurrency ({min_concurrency}) must be positive and not exceed the maximum concurrency \
+         ({max_concurrency})"
+    );
+    Ok(ConcurrencyLimits {
+        min: min_concurrency,
+        max: max_concurrency,
+        latency_threshold: config.slow_chunk_threshold,
+        ceiling: pool_size,
+    })
+}
+
+/// Checks whether the error is transient, i.e., the failed operation can be retried. Only Postgres errors
+/// related to connectivity, statement timeouts and serialization failures, and object store errors
+/// not related to (de)serialization or missing objects are considered transient.
+fn is_transient_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|err| {
+        if let Some(err) = err.downcast_ref::<ObjectStoreError>() {
+            return matches!(err, ObjectStoreError::Other(_));
+        }
+        let Some(err) = err.downcast_ref::<SqlxError>() else {
+            return false;
+        };
+        match err {
+            SqlxError::Io(_) | SqlxError::PoolTimedOut => true,
+            SqlxError::Database(err) => err.code().map_or(false, |code| {
+                // Connection exceptions (class 08), `query_canceled` (e.g., because of a statement timeout),
+                // serialization failures and deadlocks.
+                code.starts_with("08") || matches!(code.as_ref(), "57014" | "40001" | "40P01")
+            }),
+            _ => false,
+        }
+    })
+}
+
+/// Capped exponential backoff for retrying failed chunks.
+#[derive(Debug, Clone, Copy)]
+struct ChunkRetryDelays {
+    initial: Duration,
+    max: Duration,
+}
+
+impl Default for ChunkRetryDelays {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ChunkRetryDelays {
+    fn new(config: &MetadataCalculatorRecoveryConfig) -> Self {
+        Self {
+            initial: config.chunk_retry_initial_delay,
+            max: config.chunk_retry_max_delay,
+        }
+    }
+
+    /// Returns the delay before retrying chunk recovery after a failed `attempt` (1-based). The delay grows
+    /// exponentially and is randomized to spread retries of concurrently recovered chunks.
+    fn delay(&self, attempt: usize) -> Duration {
+        let exponent = u32::try_from(attempt - 1).unwrap_or(u32::MAX).min(16);
+        let delay = self.initial.saturating_mul(1 << exponent).min(self.max);
+        // Full jitter in the upper half of the delay interval.
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+/// Drives `future` to completion unless a stop signal is received first, in which case the future is dropped
+/// and `None` is returned.
+async fn run_until_stopped<T>(
+    future: impl Future<Output = T>,
+    stop_receiver: &watch::Receiver<bool>,
+) -> Option<T> {
+    tokio::select! {
+        output = future => Some(output),
+        () = wait_for_stop(stop_receiver.clone()) => None,
+    }
+}
+
+/// Resolves once a stop signal is received. If the stop signal sender is dropped, never resolves.
+pub(super) async fn wait_for_stop(mut stop_receiver: watch::Receiver<bool>) {
+    while !*stop_receiver.borrow_and_update() {
+        if stop_receiver.changed().await.is_err() {
+            future::pending::<()>().await;
+        }
+    }
+}
+
+/// L1 batch the tree is recovered to.
+#[derive(Debug)]
+struct RecoveryTarget {
+    snapshot_recovery: SnapshotRecoveryStatus,
+    /// Whether the target is the snapshot Postgres was recovered from. If not set, the target is pinned
+    /// in the config, and its data is only available in Postgres.
+    is_postgres_snapshot: bool,
+}
+
+impl RecoveryTarget {
+    /// Returns the snapshot object store to recover from, if any. The object store can only be used
+    /// for the snapshot Postgres was recovered from.
+    fn object_store<'a>(
+        &self,
+        snapshot_object_store: Option<&'a dyn ObjectStore>,
+    ) -> Option<&'a dyn ObjectStore> {
+        if !self.is_postgres_snapshot && snapshot_object_store.is_some() {
+            tracing::warn!(
+                "Snapshot object store is ignored since Merkle tree is recovered to the configured L1 batch #{}, \
+                 which differs from the snapshot L1 batch",
+                self.snapshot_recovery.l1_batch_number
+            );
+            return None;
+        }
+        snapshot_object_store
+    }
+}
+
+/// Returns the L1 batch the tree should be recovered to. If the target L1 batch is not configured, this is
+/// the snapshot the node was recovered from (if any; see [`get_snapshot_recovery()`] for how `recovered_version`
+/// is used). Otherwise, snapshot information for the target L1 batch
+/// is assembled from Postgres; it's checked that Postgres contains metadata for the batch, and that storage logs
+/// for its last miniblock contain the full state.
+async fn get_recovery_target(
+    config: &MetadataCalculatorRecoveryConfig,
+    pool: &ConnectionPool,
+    recovered_version: Option<u64>,
+) -> anyhow::Result<Option<RecoveryTarget>> {
+    let snapshot_recovery = get_snapshot_recovery(pool, recovered_version).await?;
+    let Some(target_l1_batch) = config.target_l1_batch else {
+        return Ok(snapshot_recovery.map(|snapshot_recovery| RecoveryTarget {
+            snapshot_recovery,
+            is_postgres_snapshot: true,
+        }));
+    };
+    if let Some(snapshot_recovery) = snapshot_recovery {
+        if snapshot_recovery.l1_batch_number == target_l1_batch {
+            return Ok(Some(RecoveryTarget {
+                snapshot_recovery,
+                is_postgres_snapshot: true,
+            }));
+        }
+    }
+
+    let mut storage = pool.access_storage().await?;
+    let root_hash = storage
+        .blocks_dal()
+        .get_l1_batch_state_root(target_l1_batch)
+        .await
+        .with_context(|| format!("Failed getting root hash for L1 batch #{target_l1_batch}"))?
+        .ok_or_else(|| {
+            RecoveryError::SnapshotMissing(format!(
+                "Postgres doesn't contain metadata for the target recovery L1 batch #{target_l1_batch}"
+            ))
+        })?;
+    let (_, last_miniblock) = storage
+        .blocks_dal()
+        .get_miniblock_range_of_l1_batch(target_l1_batch)
+        .await
+        .with_context(|| format!("Failed getting miniblocks for L1 batch #{target_l1_batch}"))?
+        .ok_or_else(|| {
+            RecoveryError::SnapshotMissing(format!(
+                "Postgres doesn't contain miniblocks for the target recovery L1 batch #{target_l1_batch}"
+            ))
+        })?;
+    let log_count = storage
+        .storage_logs_dal()
+        .count_miniblock_storage_logs(last_miniblock)
+        .await
+        .with_context(|| {
+            format!("Failed getting number of logs for miniblock #{last_miniblock}")
+        })?;
+    if log_count == 0 {
+        return Err(RecoveryError::SnapshotMissing(format!(
+            "Postgres doesn't contain storage logs for miniblock #{last_miniblock}, the last miniblock \
+             in the target recovery L1 batch #{target_l1_batch}"
+        ))
+        .into());
+    }
+    // Chunks are loaded from storage logs of the target miniblock only, so these logs must cover the entire state.
+    // This only holds for the genesis L1 batch; otherwise, the tree would miss all keys not updated in the miniblock.
+    let has_earlier_logs = storage
+        .storage_logs_dal()
+        .has_storage_logs_before_miniblock(last_miniblock)
+        .await
+        .with_context(|| {
+            format!("Failed checking storage logs before miniblock #{last_miniblock}")
+        })?;
+    if has_earlier_logs {
+        return Err(RecoveryError::InvalidSnapshotParameters {
+            l1_batch_number: target_l1_batch,
+            details: format!(
+                "storage logs for miniblock #{last_miniblock} don't contain the full state since earlier \
+                 miniblocks have storage logs as well; only the snapshot L1 batch or the genesis L1 batch \
+                 can be used as the recovery target"
+            ),
+        }
+        .into());
+    }
+
+    tracing::info!(
+        "Using configured target L1 batch #{target_l1_batch} (last miniblock: #{last_miniblock}, \
+         root hash: {root_hash:?}) for Merkle tree recovery"
+    );
+    Ok(Some(RecoveryTarget {
+        snapshot_recovery: SnapshotRecoveryStatus {
+            l1_batch_number: target_l1_batch,
+            l1_batch_root_hash: root_hash,
+            miniblock_number: last_miniblock,
+            miniblock_root_hash: H256::zero(), // not used by the tree
+            last_finished_chunk_id: None,
+            total_chunk_count: 0,
+        },
+        is_postgres_snapshot: false,
+    }))
+}
+
+/// Waits until storage logs of the snapshot the node is recovered from are fully applied to Postgres. On a fresh
+/// node, the snapshot may be applied concurrently with the tree startup; recovering the tree from a partially applied
+/// snapshot would fail or produce a wrong tree. Also waits until there's a snapshot with L1 batch metadata
+/// (see [`select_snapshot()`]). Returns immediately if the node isn't recovered from a snapshot.
+/// Returns [`RecoveryError::Interrupted`] if a stop signal is received while waiting.
+async fn wait_for_snapshot(
+    config: &MetadataCalculatorRecoveryConfig,
+    pool: &ConnectionPool,
+    stop_receiver: &watch::Receiver<bool>,
+    health_updater: &HealthUpdater,
+) -> Result<(), RecoveryError> {
+    let mut is_first_check = true;
+    loop {
+        let mut storage = pool.access_storage().await?;
+        let is_complete = storage
+            .snapshot_recovery_dal()
+            .is_storage_logs_recovery_complete()
+            .await
+            .context("Failed checking whether snapshot is fully applied to Postgres")?;
+        let snapshot_recovery = match is_complete {
+            None => return Ok(()),
+            Some(true) => {
+                let snapshots = storage
+                    .snapshot_recovery_dal()
+                    .get_applied_snapshot_statuses()
+                    .await
+                    .context("Failed getting snapshot recovery info")?;
+                if select_snapshot(&snapshots, None).is_some() {
+                    if !is_first_check {
+                        tracing::info!(
+                            "Snapshot is fully applied to Postgres; proceeding with Merkle tree startup"
+                        );
+                    }
+                    return Ok(());
+                }
+                let snapshot_recovery = snapshots
+                    .into_iter()
+                    .next()
+                    .context("snapshot recovery info disappeared from Postgres")?;
+                if is_first_check {
+                    tracing::info!(
+                        "Snapshot for L1 batch #{} lacks L1 batch metadata, and Postgres doesn't contain an older \
+                         snapshot with metadata; waiting for the metadata to be computed",
+                        snapshot_recovery.l1_batch_number
+                    );
+                    is_first_check = false;
+                }
+                snapshot_recovery
+            }
+            Some(false) => {
+                let snapshot_recovery = storage
+                    .snapshot_recovery_dal()
+                    .get_applied_snapshot_status()
+                    .await
+                    .context("Failed getting snapshot recovery info")?
+                    .context("snapshot recovery info disappeared from Postgres")?;
+                if is_first_check {
+                    tracing::info!(
+                        "Snapshot for L1 batch #{} is not fully applied to Postgres yet (last finished chunk: {:?}, \
+                         total chunk count: {}); waiting for it to be applied",
+                        snapshot_recovery.l1_batch_number,
+                        snapshot_recovery.last_finished_chunk_id,
+                        snapshot_recovery.total_chunk_count
+                    );
+                    is_first_check = false;
+                }
+                snapshot_recovery
+            }
+        };
+        drop(storage);
+        let health = Health::from(HealthStatus::NotReady).with_details(WaitingForSnapshotInfo {
+            mode: "waiting_for_snapshot",
+            l1_batch_number: snapshot_recovery.l1_batch_number,
+            last_finished_chunk_id: snapshot_recovery.last_finished_chunk_id,
+            total_chunk_count: snapshot_recovery.total_chunk_count,
+        });
+        health_updater.update(health);
+
+        let sleep = tokio::time::sleep(config.snapshot_poll_interval);
+        if run_until_stopped(sleep, stop_receiver).await.is_none() {
+            tracing::info!(
+                "Stop signal received while waiting for snapshot to be applied to Postgres"
+            );
+            return Err(RecoveryError::Interrupted);
+        }
+    }
+}
+
+/// Returns the L1 batch of the newest snapshot with L1 batch metadata Postgres was recovered from
+/// (see [`select_snapshot()`]), without checking whether it's fully applied.
+async fn get_snapshot_l1_batch(pool: &ConnectionPool) -> anyhow::Result<Option<L1BatchNumber>> {
+    let mut storage = pool.access_storage().await?;
+    let snapshots = storage
+        .snapshot_recovery_dal()
+        .get_applied_snapshot_statuses()
+        .await
+        .context("Failed getting snapshot recovery info")?;
+    Ok(
+        select_snapshot(&snapshots, None)
+            .map(|snapshot_recovery| snapshot_recovery.l1_batch_number),
+    )
+}
+
+/// Returns the version a ready tree was recovered to, assuming that it hasn't processed any L1 batches since.
+fn ready_tree_recovered_version(tree: &AsyncTree) -> Option<u64> {
+    let next_l1_batch = tree.next_l1_batch_number();
+    next_l1_batch.0.checked_sub(1).map(u64::from)
+}
+
+/// Checks whether the snapshot contains metadata for its L1 batch. Metadata may be missing if it hasn't been computed
+/// or backfilled for the snapshot L1 batch yet; in this case, the expected root hash is zero.
+fn has_l1_batch_metadata(snapshot_recovery: &SnapshotRecoveryStatus) -> bool {
+    snapshot_recovery.l1_batch_root_hash != H256::zero()
+}
+
+/// Selects the snapshot to recover the tree from among `snapshots` applied to Postgres, which are ordered newest first.
+/// Snapshots without L1 batch metadata (see [`has_l1_batch_metadata()`]) are skipped, so that the tree can fall back
+/// to an older snapshot. If `recovered_version` is specified and there's a snapshot with metadata for it,
+/// this snapshot is selected, so that resumed recovery uses the same snapshot after metadata for a newer snapshot
+/// becomes available.
+fn select_snapshot(
+    snapshots: &[SnapshotRecoveryStatus],
+    recovered_version: Option<u64>,
+) -> Option<&SnapshotRecoveryStatus> {
+    let mut candidates = snapshots
+        .iter()
+        .filter(|snapshot| has_l1_batch_metadata(snapshot));
+    if let Some(recovered_version) = recovered_version {
+        let recovered_snapshot = candidates
+            .clone()
+            .find(|snapshot| u64::from(snapshot.l1_batch_number.0) == recovered_version);
+        if recovered_snapshot.is_some() {
+            return recovered_snapshot;
+        }
+    }
+    candidates.next()
+}
+
+/// Returns information about the snapshot the node was recovered from, or `None` if the node wasn't recovered
+/// from a snapshot. If Postgres contains several snapshots, the snapshot is selected using [`select_snapshot()`].
+/// Returns an error if the snapshot exists, but isn't fully applied to Postgres yet; the tree must not start
+/// recovery from such a snapshot since it would recover from incomplete data.
+async fn get_snapshot_recovery(
+    pool: &ConnectionPool,
+    recovered_version: Option<u64>,
+) -> anyhow::Result<Option<SnapshotRecoveryStatus>> {
+    let mut storage = pool.access_storage().await?;
+    let snapshots = storage
+        .snapshot_recovery_dal()
+        .get_applied_snapshot_statuses()
+        .await
+        .context("Failed getting snapshot recovery info")?;
+    drop(storage);
+    let Some(newest_snapshot) = snapshots.first() else {
+        return Ok(None);
+    };
+    let newest_l1_batch = newest_snapshot.l1_batch_number;
+    let Some(snapshot_recovery) = select_snapshot(&snapshots, recovered_version) else {
+        return Err(RecoveryError::SnapshotMissing(format!(
+            "snapshot for L1 batch #{newest_l1_batch} lacks L1 batch metadata (its expected root hash is zero), \
+             and Postgres doesn't contain an older snapshot with metadata"
+        ))
+        .into());
+    };
+    let snapshot_l1_batch = snapshot_recovery.l1_batch_number;
+    if snapshot_l1_batch != newest_l1_batch {
+        if has_l1_batch_metadata(newest_snapshot) {
+            tracing::info!(
+                "Using snapshot for L1 batch #{snapshot_l1_batch} the Merkle tree is recovered to, although \
+                 Postgres contains a newer snapshot for L1 batch #{newest_l1_batch}"
+            );
+        } else {
+            tracing::warn!(
+                "Snapshot for L1 batch #{newest_l1_batch} lacks L1 batch metadata (its expected root hash is zero); \
+                 falling back to the snapshot for L1 batch #{snapshot_l1_batch}"
+            );
+        }
+    }
+    let snapshot_recovery = snapshot_recovery.clone();
+
+    let is_fully_applied = snapshot_recovery
+        .last_finished_chunk_id
+        .map_or(false, |chunk_id| {
+            chunk_id + 1 >= snapshot_recovery.total_chunk_count
+        });
+    if !is_fully_applied {
+        return Err(RecoveryError::SnapshotIncomplete {
+            l1_batch_number: snapshot_recovery.l1_batch_number,
+            last_finished_chunk_id: snapshot_recovery.last_finished_chunk_id,
+            total_chunk_count: snapshot_recovery.total_chunk_count,
+        }
+        .into());
+    }
+    Ok(Some(snapshot_recovery))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,132 @@
+//! Recovery parameters that can be changed at runtime.
+
+use std::{future, path::Path};
+
+use anyhow::Context as _;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::watch,
+};
+
+use super::RecoveryControl;
+use crate::metadata_calculator::MetadataCalculatorRecoveryConfig;
+
+const BYTES_IN_MEGABYTE: usize = 1 << 20;
+
+/// Recovery parameters that can be changed while recovery is in progress. Parameters are changed to the values
+/// received from the corresponding channels; if a channel is not supplied, the configured value is used throughout
+/// recovery.
+#[derive(Debug, Clone, Default)]
+pub struct RecoveryOverrides {
+    /// Maximum number of concurrently recovered chunks. The value is capped by the size of the connection pool
+    /// used to load chunks.
+    pub concurrency: Option<watch::Receiver<usize>>,
+    /// Limit on the rate of background writes of the tree RocksDB in bytes per second; 0 removes the limit.
+    /// The tree RocksDB is reopened to apply a new limit before applying the next loaded chunk (or batch
+    /// of chunk entries) to the tree.
+    pub background_write_rate_limit: Option<watch::Receiver<usize>>,
+    /// Commands pausing and resuming recovery (see [`RecoveryControlHandle`](super::RecoveryControlHandle)).
+    /// While recovery is paused, no new chunks are started.
+    pub control: Option<watch::Receiver<RecoveryControl>>,
+}
+
+impl RecoveryOverrides {
+    /// Creates overrides for parameters with an override file specified in `config`. Override values are read
+    /// from the files by the returned reloader.
+    pub(crate) fn from_config(
+        config: &MetadataCalculatorRecoveryConfig,
+    ) -> (Self, OverridesReloader<'_>) {
+        let mut overrides = Self::default();
+        let mut files = vec![];
+        if let Some(path) = &config.concurrency_override_path {
+            let (sender, receiver) = watch::channel(0);
+            overrides.concurrency = Some(receiver);
+            files.push(OverrideFile {
+                name: "maximum recovery concurrency",
+                path,
+                scale: 1,
+                sender,
+            });
+        }
+        if let Some(path) = &config.background_write_rate_limit_override_path {
+            let (sender, receiver) = watch::channel(0);
+            overrides.background_write_rate_limit = Some(receiver);
+            files.push(OverrideFile {
+                name: "background write rate limit (MB/s)",
+                path,
+                scale: BYTES_IN_MEGABYTE,
+                sender,
+            });
+        }
+        (overrides, OverridesReloader { files })
+    }
+}
+
+/// File containing an override for a recovery parameter.
+#[derive(Debug)]
+struct OverrideFile<'a> {
+    /// Human-readable parameter name used in logs.
+    name: &'static str,
+    path: &'a Path,
+    /// Multiplier converting the value in the file to the units of the parameter (e.g., megabytes to bytes).
+    scale: usize,
+    sender: watch::Sender<usize>,
+}
+
+impl OverrideFile<'_> {
+    /// Reads the override from the file and sends it to the channel. Returns the value read from the file.
+    fn reload(&self) -> anyhow::Result<usize> {
+        let path = self.path;
+        let contents =
+            std::fs::read_to_string(path).with_context(|| format!("failed reading {path:?}"))?;
+        let contents = contents.trim();
+        let value = contents
+            .parse::<usize>()
+            .with_context(|| format!("invalid {}: {contents:?}", self.name))?;
+        let scaled_value = value
+            .checked_mul(self.scale)
+            .with_context(|| format!("{} is too large: {value}", self.name))?;
+        self.sender.send_replace(scaled_value);
+        Ok(value)
+    }
+}
+
+/// Reloads [`RecoveryOverrides`] from override files each time the process receives `SIGHUP`.
+#[derive(Debug)]
+pub(crate) struct OverridesReloader<'a> {
+    files: Vec<OverrideFile<'a>>,
+}
+
+impl OverridesReloader<'_> {
+    /// Reads overrides from the files each time the process receives `SIGHUP` and sends them to the corresponding
+    /// channels. Invalid file contents are logged and ignored. Only returns on an error setting up the signal handler;
+    /// if there are no override files, never returns.
+    pub async fn run(self) -> anyhow::Result<()> {
+        if self.files.is_empty() {
+            future::pending::<()>().await;
+        }
+
+        let mut hangups =
+            signal(SignalKind::hangup()).context("failed setting up SIGHUP handler")?;
+        for file in &self.files {
+            tracing::info!(
+                "Recovery parameter `{}` can be changed by writing it to {:?} and sending SIGHUP to the process",
+                file.name,
+                file.path
+            );
+        }
+        while hangups.recv().await.is_some() {
+            for file in &self.files {
+                match file.reload() {
+                    Ok(value) => {
+                        tracing::info!("Received {} {value} from {:?}", file.name, file.path);
+                    }
+                    Err(err) => {
+                        tracing::warn!("Failed reloading {} on SIGHUP: {err:#}", file.name);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
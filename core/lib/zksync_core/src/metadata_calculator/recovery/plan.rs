@@ -0,0 +1,124 @@
+//! Recovery plan: the number of snapshot entries in each chunk remaining to be recovered.
+
+use std::{cmp, ops};
+
+use zksync_types::H256;
+
+use super::RecoveryEntrySource;
+use crate::metadata_calculator::metrics::RECOVERY_METRICS;
+
+/// Chunk in a [`RecoveryPlan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedChunk {
+    /// 0-based index of the chunk. Chunk indices are stable across restarts.
+    pub index: usize,
+    /// Range of hashed keys covered by the chunk.
+    pub key_range: ops::RangeInclusive<H256>,
+    /// Number of snapshot entries in the chunk.
+    pub entry_count: u64,
+}
+
+/// Number of snapshot entries in each chunk remaining to be recovered, as reported by the entry source
+/// (see [`RecoveryEntrySource::estimate_entry_counts()`]). For Postgres, entry counts are exact. The plan is used
+/// to prioritize chunks and is reported via metrics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryPlan {
+    chunks: Vec<PlannedChunk>,
+}
+
+impl RecoveryPlan {
+    /// Loads the plan for the specified chunks. Returns `None` if the entry source doesn't support
+    /// counting chunk entries.
+    pub(super) async fn load(
+        entry_source: &dyn RecoveryEntrySource,
+        chunks: &[(usize, ops::RangeInclusive<H256>)],
+    ) -> anyhow::Result<Option<Self>> {
+        let key_chunks: Vec<_> = chunks.iter().map(|(_, chunk)| chunk.clone()).collect();
+        let Some(entry_counts) = entry_source.estimate_entry_counts(&key_chunks).await? else {
+            return Ok(None);
+        };
+        anyhow::ensure!(
+            entry_counts.len() == chunks.len(),
+            "Entry source returned {} entry counts for {} chunks",
+            entry_counts.len(),
+            chunks.len()
+        );
+
+        let chunks = chunks.iter().cloned().zip(entry_counts);
+        let chunks = chunks.map(|((index, key_range), entry_count)| PlannedChunk {
+            index,
+            key_range,
+            entry_count,
+        });
+        Ok(Some(Self {
+            chunks: chunks.collect(),
+        }))
+    }
+
+    /// Returns planned chunks in the recovery order.
+    pub fn chunks(&self) -> &[PlannedChunk] {
+        &self.chunks
+    }
+
+    /// Returns the total number of entries in the planned chunks.
+    pub fn total_entry_count(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.entry_count).sum()
+    }
+
+    /// Returns the planned chunk with the largest number of entries.
+    pub fn largest_chunk(&self) -> Option<&PlannedChunk> {
+        self.chunks.iter().max_by_key(|chunk| chunk.entry_count)
+    }
+
+    /// Sorts chunks by the descending number of entries, so that the largest chunks are recovered first
+    /// and don't dominate the recovery tail. Chunks with equal entry counts retain their relative order.
+    pub(super) fn prioritize_large_chunks(&mut self) {
+        self.chunks
+            .sort_by_key(|chunk| cmp::Reverse(chunk.entry_count));
+    }
+
+    /// Returns chunk indices and key ranges in the recovery order.
+    pub(super) fn key_chunks(&self) -> Vec<(usize, ops::RangeInclusive<H256>)> {
+        let chunks = self.chunks.iter();
+        chunks
+            .map(|chunk| (chunk.index, chunk.key_range.clone()))
+            .collect()
+    }
+
+    pub(super) fn report_metrics(&self) {
+        RECOVERY_METRICS
+            .planned_entries
+            .set(self.total_entry_count());
+        for chunk in &self.chunks {
+            RECOVERY_METRICS
+                .planned_chunk_entries
+                .observe(chunk.entry_count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prioritizing_large_chunks() {
+        let entry_counts = [10, 30, 20, 30, 0];
+        let chunks = entry_counts.into_iter().enumerate();
+        let chunks = chunks.map(|(index, entry_count)| PlannedChunk {
+            index,
+            key_range: H256::repeat_byte(index as u8)..=H256::repeat_byte(index as u8),
+            entry_count,
+        });
+        let mut plan = RecoveryPlan {
+            chunks: chunks.collect(),
+        };
+        assert_eq!(plan.total_entry_count(), 90);
+        assert_eq!(plan.largest_chunk().unwrap().entry_count, 30);
+
+        plan.prioritize_large_chunks();
+        let indices: Vec<_> = plan.key_chunks().into_iter().map(|(idx, _)| idx).collect();
+        assert_eq!(indices, [1, 3, 2, 0, 4]);
+        assert_eq!(plan.total_entry_count(), 90);
+    }
+}
@@ -0,0 +1,157 @@
+//! Recording Merkle tree recovery progress in Postgres.
+//!
+//! Recovered chunks are recorded in the `tree_recovery_progress` table, and the recovery summary
+//! in the `tree_recovery_summary` table, so that recovery can be monitored using SQL. Recording is best-effort:
+//! errors are logged and don't influence recovery, which relies on the tree data only. To limit the load on Postgres,
+//! recovered chunks are buffered and written in batches. When recovery is resumed, the table is reconciled
+//! with the chunks actually recovered by the tree.
+
+use std::{
+    mem, ops,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use zksync_dal::{tree_recovery_progress_dal::RecoveredTreeChunk, ConnectionPool};
+use zksync_types::{L1BatchNumber, H256};
+
+use super::ChunkDescriptor;
+
+#[derive(Debug)]
+struct PendingChunks {
+    chunks: Vec<RecoveredTreeChunk>,
+    last_flushed_at: Instant,
+}
+
+/// Records recovery progress in Postgres (see the module docs).
+#[derive(Debug)]
+pub(super) struct RecoveryProgressTable<'a> {
+    pool: &'a ConnectionPool,
+    l1_batch_number: L1BatchNumber,
+    chunk_count: usize,
+    pending: Mutex<PendingChunks>,
+}
+
+impl<'a> RecoveryProgressTable<'a> {
+    /// Maximum number of buffered recovered chunks.
+    const MAX_PENDING_CHUNKS: usize = 32;
+    /// Maximum interval between writing buffered recovered chunks.
+    const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+    pub fn new(
+        pool: &'a ConnectionPool,
+        l1_batch_number: L1BatchNumber,
+        chunk_count: usize,
+    ) -> Self {
+        Self {
+            pool,
+            l1_batch_number,
+            chunk_count,
+            pending: Mutex::new(PendingChunks {
+                chunks: vec![],
+                last_flushed_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Reconciles the table with the chunks recovered by the tree, i.e., `chunks` not present in `remaining_chunks`.
+    pub async fn reconcile(
+        &self,
+        chunks: &[ops::RangeInclusive<H256>],
+        remaining_chunks: &[(usize, ops::RangeInclusive<H256>)],
+    ) {
+        let mut is_recovered = vec![true; chunks.len()];
+        for &(chunk_id, _) in remaining_chunks {
+            is_recovered[chunk_id] = false;
+        }
+        let recovered_chunks: Vec<_> = chunks
+            .iter()
+            .enumerate()
+            .filter(|&(chunk_id, _)| is_recovered[chunk_id])
+            .map(|(chunk_id, key_range)| (chunk_id as u64, key_range.clone()))
+            .collect();
+
+        let result = async {
+            let mut storage = self.pool.access_storage().await?;
+            storage
+                .tree_recovery_progress_dal()
+                .reconcile_recovered_chunks(
+                    self.l1_batch_number,
+                    self.chunk_count as u64,
+                    &recovered_chunks,
+                )
+                .await?;
+            anyhow::Ok(())
+        };
+        if let Err(err) = result.await {
+            tracing::warn!("Failed reconciling Merkle tree recovery progress in Postgres: {err:#}");
+        }
+    }
+
+    /// Buffers a recovered chunk, writing buffered chunks to Postgres if necessary.
+    pub async fn chunk_recovered(&self, chunk: &ChunkDescriptor) {
+        let chunks_to_write = {
+            let mut pending = self.pending.lock().expect("pending chunks are poisoned");
+            pending.chunks.push(RecoveredTreeChunk {
+                chunk_id: chunk.index as u64,
+                key_range: chunk.key_range.clone(),
+                entry_count: chunk.entry_count,
+            });
+            let should_flush = pending.chunks.len() >= Self::MAX_PENDING_CHUNKS
+                || pending.last_flushed_at.elapsed() >= Self::FLUSH_INTERVAL;
+            should_flush.then(|| Self::take_pending(&mut pending))
+        };
+        if let Some(chunks) = chunks_to_write {
+            self.write_chunks(&chunks).await;
+        }
+    }
+
+    fn take_pending(pending: &mut PendingChunks) -> Vec<RecoveredTreeChunk> {
+        pending.last_flushed_at = Instant::now();
+        mem::take(&mut pending.chunks)
+    }
+
+    /// Writes all buffered recovered chunks to Postgres.
+    pub async fn flush(&self) {
+        let chunks = {
+            let mut pending = self.pending.lock().expect("pending chunks are poisoned");
+            Self::take_pending(&mut pending)
+        };
+        if !chunks.is_empty() {
+            self.write_chunks(&chunks).await;
+        }
+    }
+
+    async fn write_chunks(&self, chunks: &[RecoveredTreeChunk]) {
+        let result = async {
+            let mut storage = self.pool.access_storage().await?;
+            storage
+                .tree_recovery_progress_dal()
+                .insert_recovered_chunks(self.l1_batch_number, self.chunk_count as u64, chunks)
+                .await?;
+            anyhow::Ok(())
+        };
+        if let Err(err) = result.await {
+            tracing::warn!(
+                "Failed recording {} recovered chunks in Postgres: {err:#}",
+                chunks.len()
+            );
+        }
+    }
+
+    /// Marks recovery as finished in Postgres.
+    pub async fn finish(&self) {
+        self.flush().await;
+        let result = async {
+            let mut storage = self.pool.access_storage().await?;
+            storage
+                .tree_recovery_progress_dal()
+                .mark_recovery_finished(self.l1_batch_number)
+                .await?;
+            anyhow::Ok(())
+        };
+        if let Err(err) = result.await {
+            tracing::warn!("Failed marking Merkle tree recovery as finished in Postgres: {err:#}");
+        }
+    }
+}
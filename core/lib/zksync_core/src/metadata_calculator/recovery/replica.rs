@@ -0,0 +1,135 @@
+//! Postgres read replica used for heavy snapshot queries during Merkle tree recovery.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Context as _;
+use tokio::sync::OnceCell;
+use zksync_dal::ConnectionPool;
+use zksync_types::MiniblockNumber;
+
+use crate::metadata_calculator::metrics::RECOVERY_METRICS;
+
+/// Result of checking whether a [`SnapshotReplica`] is synced with the primary.
+#[derive(Debug, Clone, Copy)]
+struct SyncCheck {
+    is_synced: bool,
+    /// Number of snapshot storage logs in the replica.
+    replica_log_count: u64,
+}
+
+/// Postgres read replica used to load snapshot data during recovery. Since snapshot storage logs are immutable
+/// once written, a replica containing all of them returns the same data as the primary.
+///
+/// Whether the replica is synced is checked once, on first use, by comparing the number of snapshot storage logs
+/// in the replica and in the primary. If the replica misses some logs (i.e., it lags behind the snapshot miniblock),
+/// it's not used, and all queries go to the primary. Failed replica queries fall back to the primary as well.
+#[derive(Debug)]
+pub(super) struct SnapshotReplica<'a> {
+    primary: &'a ConnectionPool,
+    pool: &'a ConnectionPool,
+    snapshot_miniblock: MiniblockNumber,
+    sync_check: OnceCell<Option<SyncCheck>>,
+    fallback_count: AtomicUsize,
+}
+
+impl<'a> SnapshotReplica<'a> {
+    pub fn new(
+        primary: &'a ConnectionPool,
+        pool: &'a ConnectionPool,
+        snapshot_miniblock: MiniblockNumber,
+    ) -> Self {
+        Self {
+            primary,
+            pool,
+            snapshot_miniblock,
+            sync_check: OnceCell::new(),
+            fallback_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the replica pool if the replica is synced with the primary, or `None` otherwise. In the latter case,
+    /// the caller should use the primary; this is recorded as a fallback.
+    pub async fn pool(&self) -> Option<&'a ConnectionPool> {
+        if self.sync_check().await.is_some() {
+            Some(self.pool)
+        } else {
+            self.record_fallback();
+            None
+        }
+    }
+
+    /// Returns the number of snapshot storage logs in the replica if it's synced with the primary.
+    pub async fn log_count(&self) -> Option<u64> {
+        self.sync_check()
+            .await
+            .map(|sync_check| sync_check.replica_log_count)
+    }
+
+    /// Records a fallback to the primary after a replica query has failed.
+    pub fn fall_back(&self, operation: &str, err: &anyhow::Error) {
+        tracing::warn!(
+            "Failed {operation} using Postgres replica, falling back to the primary: {err:#}"
+        );
+        self.record_fallback();
+    }
+
+    /// Returns the number of times a query fell back to the primary.
+    pub fn fallback_count(&self) -> usize {
+        self.fallback_count.load(Ordering::Relaxed)
+    }
+
+    fn record_fallback(&self) {
+        self.fallback_count.fetch_add(1, Ordering::Relaxed);
+        RECOVERY_METRICS.replica_fallbacks.inc();
+    }
+
+    async fn sync_check(&self) -> Option<SyncCheck> {
+        let sync_check = self.sync_check.get_or_init(|| async {
+            match self.check_sync().await {
+                Ok(sync_check) => Some(sync_check),
+                Err(err) => {
+                    tracing::warn!("Failed checking whether Postgres replica is synced: {err:#}");
+                    None
+                }
+            }
+        });
+        sync_check.await.filter(|sync_check| sync_check.is_synced)
+    }
+
+    async fn check_sync(&self) -> anyhow::Result<SyncCheck> {
+        let replica_log_count = Self::count_logs(self.pool, self.snapshot_miniblock)
+            .await
+            .context("failed counting snapshot storage logs in replica")?;
+        let primary_log_count = Self::count_logs(self.primary, self.snapshot_miniblock)
+            .await
+            .context("failed counting snapshot storage logs in primary")?;
+
+        let is_synced = replica_log_count >= primary_log_count;
+        if is_synced {
+            tracing::info!(
+                "Postgres replica contains all {primary_log_count} storage logs for snapshot miniblock #{}; \
+                 using it to load snapshot data",
+                self.snapshot_miniblock
+            );
+        } else {
+            tracing::warn!(
+                "Postgres replica lags behind the snapshot: it contains {replica_log_count} / {primary_log_count} \
+                 storage logs for miniblock #{}; using the primary to load snapshot data",
+                self.snapshot_miniblock
+            );
+        }
+        Ok(SyncCheck {
+            is_synced,
+            replica_log_count,
+        })
+    }
+
+    async fn count_logs(pool: &ConnectionPool, miniblock: MiniblockNumber) -> anyhow::Result<u64> {
+        let mut storage = pool.access_storage().await?;
+        let count = storage
+            .storage_logs_dal()
+            .count_miniblock_storage_logs(miniblock)
+            .await?;
+        Ok(count)
+    }
+}
@@ -0,0 +1,497 @@
+//! Recovering the Merkle tree from a local snapshot file, for deployments where snapshot entries cannot be loaded
+//! from Postgres by the tree node (e.g., air-gapped ones). The file is produced by [`write_snapshot_file()`]
+//! on a machine with access to Postgres.
+//!
+//! # File format
+//!
+//! A snapshot file consists of:
+//!
+//! 1. Magic bytes `ZKTREESN`.
+//! 2. Format version as a little-endian `u32` (currently, 1).
+//! 3. Header length in bytes as a little-endian `u32`, followed by the JSON-encoded [`SnapshotFileHeader`].
+//! 4. `log_count` fixed-size records strictly ordered by the hashed key. Each record consists of the hashed key
+//!    (32 bytes), the value (32 bytes) and the leaf index (little-endian `u64`).
+//!
+//! Since records have a fixed size and are sorted, records for a chunk are located using binary search
+//! without reading the entire file.
+
+use std::{
+    fs,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    ops,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use zksync_dal::ConnectionPool;
+use zksync_merkle_tree::TreeEntry;
+use zksync_types::{snapshots::SnapshotRecoveryStatus, L1BatchNumber, MiniblockNumber, H256, U256};
+use zksync_utils::u256_to_h256;
+
+use super::{run_until_stopped, RecoveryEntrySource, RecoveryError, SnapshotParameters};
+use crate::metadata_calculator::helpers::AsyncTreeRecovery;
+
+const MAGIC: &[u8; 8] = b"ZKTREESN";
+const FORMAT_VERSION: u32 = 1;
+/// Size of the magic bytes, format version and header length.
+const PREFIX_SIZE: u64 = 16;
+const RECORD_SIZE: u64 = 72;
+
+/// Header of a snapshot file describing the snapshot stored in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotFileHeader {
+    pub l1_batch_number: L1BatchNumber,
+    pub miniblock_number: MiniblockNumber,
+    /// Number of records in the file.
+    pub log_count: u64,
+    /// Expected root hash of the tree recovered from the file.
+    pub root_hash: H256,
+}
+
+/// Loads snapshot entries from a snapshot file. The file is opened anew for each operation, so that chunks
+/// can be loaded concurrently.
+#[derive(Debug, Clone)]
+pub(super) struct FileSnapshotSource {
+    path: PathBuf,
+    header: SnapshotFileHeader,
+    records_offset: u64,
+}
+
+impl FileSnapshotSource {
+    /// Opens the snapshot file and checks its header.
+    pub async fn open(path: &Path) -> anyhow::Result<Self> {
+        let path = path.to_owned();
+        tokio::task::spawn_blocking(move || Self::open_sync(path))
+            .await
+            .unwrap()
+    }
+
+    fn open_sync(path: PathBuf) -> anyhow::Result<Self> {
+        let mut file = fs::File::open(&path)
+            .with_context(|| format!("failed opening snapshot file `{}`", path.display()))?;
+        let mut prefix = [0_u8; PREFIX_SIZE as usize];
+        file.read_exact(&mut prefix)
+            .with_context(|| format!("failed reading snapshot file `{}`", path.display()))?;
+        anyhow::ensure!(
+            prefix[..8] == MAGIC[..],
+            "`{}` is not a Merkle tree snapshot file",
+            path.display()
+        );
+        let version = u32::from_le_bytes(prefix[8..12].try_into().unwrap());
+        anyhow::ensure!(
+            version == FORMAT_VERSION,
+            "snapshot file `{}` has unsupported format version {version} (expected {FORMAT_VERSION})",
+            path.display()
+        );
+        let header_len = u32::from_le_bytes(prefix[12..].try_into().unwrap());
+        let mut header = vec![0_u8; header_len as usize];
+        file.read_exact(&mut header).with_context(|| {
+            format!(
+                "failed reading header of snapshot file `{}`",
+                path.display()
+            )
+        })?;
+        let header: SnapshotFileHeader = serde_json::from_slice(&header).with_context(|| {
+            format!(
+                "failed deserializing header of snapshot file `{}`",
+                path.display()
+            )
+        })?;
+
+        let records_offset = PREFIX_SIZE + u64::from(header_len);
+        let file_len = file
+            .metadata()
+            .with_context(|| format!("failed getting metadata of `{}`", path.display()))?
+            .len();
+        let expected_len = header
+            .log_count
+            .checked_mul(RECORD_SIZE)
+            .and_then(|len| len.checked_add(records_offset));
+        anyhow::ensure!(
+            expected_len == Some(file_len),
+            "snapshot file `{}` is truncated or corrupted: it has {file_len} bytes, while its header \
+             declares {} records",
+            path.display(),
+            header.log_count
+        );
+        Ok(Self {
+            path,
+            header,
+            records_offset,
+        })
+    }
+
+    pub fn header(&self) -> &SnapshotFileHeader {
+        &self.header
+    }
+
+    /// Checks that the file contains the snapshot the tree is recovered from and returns snapshot parameters
+    /// based on the file header.
+    pub async fn snapshot_parameters(
+        &self,
+        pool: &ConnectionPool,
+        snapshot_recovery: &SnapshotRecoveryStatus,
+    ) -> anyhow::Result<SnapshotParameters> {
+        let header = &self.header;
+        let l1_batch_number = snapshot_recovery.l1_batch_number;
+        if header.l1_batch_number != l1_batch_number
+            || header.miniblock_number != snapshot_recovery.miniblock_number
+        {
+            return Err(RecoveryError::InvalidSnapshotParameters {
+                l1_batch_number,
+                details: format!(
+                    "snapshot file `{}` contains snapshot for L1 batch #{} (miniblock #{}), while the tree \
+                     is recovered to miniblock #{}",
+                    self.path.display(),
+                    header.l1_batch_number,
+                    header.miniblock_number,
+                    snapshot_recovery.miniblock_number
+                ),
+            }
+            .into());
+        }
+        if header.root_hash != snapshot_recovery.l1_batch_root_hash {
+            return Err(RecoveryError::InvalidSnapshotParameters {
+                l1_batch_number,
+                details: format!(
+                    "snapshot file `{}` has root hash {:?}, while the snapshot in Postgres has root hash {:?}",
+                    self.path.display(),
+                    header.root_hash,
+                    snapshot_recovery.l1_batch_root_hash
+                ),
+            }
+            .into());
+        }
+        SnapshotParameters::validate(pool, snapshot_recovery).await?;
+        Ok(SnapshotParameters::with_log_count(
+            snapshot_recovery,
+            header.log_count,
+        )?)
+    }
+
+    fn open_file(&self) -> anyhow::Result<fs::File> {
+        fs::File::open(&self.path)
+            .with_context(|| format!("failed opening snapshot file `{}`", self.path.display()))
+    }
+
+    fn read_key(&self, file: &mut fs::File, index: u64) -> io::Result<H256> {
+        file.seek(SeekFrom::Start(self.records_offset + index * RECORD_SIZE))?;
+        let mut key = H256::zero();
+        file.read_exact(&mut key.0)?;
+        Ok(key)
+    }
+
+    /// Returns the index of the first record with the hashed key greater or equal to `key`.
+    fn lower_bound(&self, file: &mut fs::File, key: H256) -> io::Result<u64> {
+        let (mut start, mut end) = (0, self.header.log_count);
+        while start < end {
+            let mid = start + (end - start) / 2;
+            if self.read_key(file, mid)? < key {
+                start = mid + 1;
+            } else {
+                end = mid;
+            }
+        }
+        Ok(start)
+    }
+
+    /// Returns the range of record indices with hashed keys in `key_chunk`.
+    fn locate(
+        &self,
+        file: &mut fs::File,
+        key_chunk: &ops::RangeInclusive<H256>,
+    ) -> io::Result<ops::Range<u64>> {
+        let start = self.lower_bound(file, *key_chunk.start())?;
+        let end_key = U256::from_big_endian(&key_chunk.end().0);
+        let end = if end_key == U256::MAX {
+            self.header.log_count
+        } else {
+            self.lower_bound(file, u256_to_h256(end_key + 1))?
+        };
+        Ok(start..end.max(start))
+    }
+
+    fn count_entries_sync(
+        &self,
+        key_chunks: &[ops::RangeInclusive<H256>],
+    ) -> anyhow::Result<Vec<u64>> {
+        let mut file = self.open_file()?;
+        key_chunks
+            .iter()
+            .map(|key_chunk| {
+                let range = self.locate(&mut file, key_chunk)?;
+                Ok(range.end - range.start)
+            })
+            .collect::<io::Result<_>>()
+            .with_context(|| format!("failed reading snapshot file `{}`", self.path.display()))
+    }
+
+    fn load_entries_sync(
+        &self,
+        key_chunk: &ops::RangeInclusive<H256>,
+    ) -> anyhow::Result<Vec<TreeEntry>> {
+        let mut file = self.open_file()?;
+        let range = self
+            .locate(&mut file, key_chunk)
+            .with_context(|| format!("failed reading snapshot file `{}`", self.path.display()))?;
+        file.seek(SeekFrom::Start(
+            self.records_offset + range.start * RECORD_SIZE,
+        ))?;
+        let mut reader = BufReader::new(file);
+
+        let mut entries = Vec::with_capacity((range.end - range.start) as usize);
+        let mut prev_key = None;
+        let mut record = [0_u8; RECORD_SIZE as usize];
+        for _ in range {
+            reader.read_exact(&mut record).with_context(|| {
+                format!("failed reading snapshot file `{}`", self.path.display())
+            })?;
+            let hashed_key = H256::from_slice(&record[..32]);
+            if prev_key.is_some_and(|prev_key| prev_key >= hashed_key) {
+                return Err(RecoveryError::CorruptedSnapshot {
+                    key: hashed_key,
+                    details: format!(
+                        "snapshot file `{}` contains duplicate or unordered keys",
+                        self.path.display()
+                    ),
+                }
+                .into());
+            }
+            prev_key = Some(hashed_key);
+            entries.push(TreeEntry {
+                key: U256::from_big_endian(&hashed_key.0),
+                value: H256::from_slice(&record[32..64]),
+                leaf_index: u64::from_le_bytes(record[64..].try_into().unwrap()),
+            });
+        }
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl RecoveryEntrySource for FileSnapshotSource {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        Ok(AsyncTreeRecovery::hashed_key_ranges(chunk_count).collect())
+    }
+
+    async fn estimate_entry_counts(
+        &self,
+        key_chunks: &[ops::RangeInclusive<H256>],
+    ) -> anyhow::Result<Option<Vec<u64>>> {
+        let this = self.clone();
+        let key_chunks = key_chunks.to_vec();
+        let entry_counts =
+            tokio::task::spawn_blocking(move || this.count_entries_sync(&key_chunks))
+                .await
+                .unwrap()?;
+        Ok(Some(entry_counts))
+    }
+
+    async fn load_entries(
+        &self,
+        _chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        let this = self.clone();
+        let key_chunk = key_chunk.clone();
+        let entries = tokio::task::spawn_blocking(move || this.load_entries_sync(&key_chunk));
+        let Some(entries) = run_until_stopped(entries, stop_receiver).await else {
+            return Ok(None);
+        };
+        entries.unwrap().map(Some)
+    }
+}
+
+/// Writes the snapshot described by `snapshot_recovery` from Postgres to a snapshot file at `path`, so that
+/// the Merkle tree can be recovered from the file on a machine without access to this Postgres instance
+/// (see `recovery_file` in the Merkle tree recovery config). Entries are loaded from Postgres in `chunk_count`
+/// chunks to bound memory usage. The file is written to a temporary sibling path and renamed once it's complete,
+/// so that an interrupted write never leaves a partially written file at `path`.
+pub async fn write_snapshot_file(
+    pool: &ConnectionPool,
+    snapshot_recovery: &SnapshotRecoveryStatus,
+    path: &Path,
+    chunk_count: usize,
+) -> anyhow::Result<SnapshotFileHeader> {
+    let miniblock = snapshot_recovery.miniblock_number;
+    let mut storage = pool.access_storage().await?;
+    let log_count = storage
+        .storage_logs_dal()
+        .count_miniblock_storage_logs(miniblock)
+        .await
+        .with_context(|| format!("Failed getting number of logs for miniblock #{miniblock}"))?;
+    let header = SnapshotFileHeader {
+        l1_batch_number: snapshot_recovery.l1_batch_number,
+        miniblock_number: miniblock,
+        log_count,
+        root_hash: snapshot_recovery.l1_batch_root_hash,
+    };
+
+    let tmp_path = path.with_extension("tmp");
+    let mut writer = {
+        let tmp_path = tmp_path.clone();
+        tokio::task::spawn_blocking(move || create_snapshot_file(&tmp_path, &header))
+            .await
+            .unwrap()?
+    };
+    let mut written_count = 0;
+    for key_chunk in AsyncTreeRecovery::hashed_key_ranges(chunk_count) {
+        let entries = storage
+            .storage_logs_dal()
+            .get_tree_entries_for_miniblock(miniblock, key_chunk.clone())
+            .await
+            .with_context(|| {
+                format!("Failed getting entries for chunk {key_chunk:?} in miniblock #{miniblock}")
+            })?;
+        written_count += entries.len() as u64;
+        writer = tokio::task::spawn_blocking(move || {
+            for entry in entries {
+                writer.write_all(&u256_to_h256(entry.key).0)?;
+                writer.write_all(&entry.value.0)?;
+                writer.write_all(&entry.leaf_index.to_le_bytes())?;
+            }
+            io::Result::Ok(writer)
+        })
+        .await
+        .unwrap()
+        .with_context(|| format!("failed writing `{}`", tmp_path.display()))?;
+    }
+    drop(storage);
+    anyhow::ensure!(
+        written_count == log_count,
+        "Number of written entries ({written_count}) differs from the number of storage logs \
+         for miniblock #{miniblock} ({log_count}); was Postgres modified concurrently?"
+    );
+
+    let path = path.to_owned();
+    tokio::task::spawn_blocking(move || {
+        let file = writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)
+            .with_context(|| format!("failed flushing `{}`", tmp_path.display()))?;
+        file.sync_all()
+            .with_context(|| format!("failed syncing `{}`", tmp_path.display()))?;
+        fs::rename(&tmp_path, &path).with_context(|| {
+            format!(
+                "failed renaming `{}` to `{}`",
+                tmp_path.display(),
+                path.display()
+            )
+        })
+    })
+    .await
+    .unwrap()?;
+    tracing::info!(
+        "Wrote snapshot for L1 batch #{} ({log_count} entries) to snapshot file",
+        header.l1_batch_number
+    );
+    Ok(header)
+}
+
+fn create_snapshot_file(
+    path: &Path,
+    header: &SnapshotFileHeader,
+) -> anyhow::Result<BufWriter<fs::File>> {
+    let header = serde_json::to_vec(header).context("failed serializing snapshot file header")?;
+    let header_len = u32::try_from(header.len()).context("snapshot file header is too large")?;
+    let file =
+        fs::File::create(path).with_context(|| format!("failed creating `{}`", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    let write_prefix = |writer: &mut BufWriter<fs::File>| -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&header_len.to_le_bytes())?;
+        writer.write_all(&header)
+    };
+    write_prefix(&mut writer).with_context(|| format!("failed writing `{}`", path.display()))?;
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn write_test_file(path: &Path, entries: &[(H256, u64)]) -> FileSnapshotSource {
+        let header = SnapshotFileHeader {
+            l1_batch_number: L1BatchNumber(1),
+            miniblock_number: MiniblockNumber(1),
+            log_count: entries.len() as u64,
+            root_hash: H256::repeat_byte(1),
+        };
+        let mut writer = create_snapshot_file(path, &header).unwrap();
+        for &(key, leaf_index) in entries {
+            writer.write_all(&key.0).unwrap();
+            writer.write_all(&H256::repeat_byte(0xaa).0).unwrap();
+            writer.write_all(&leaf_index.to_le_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+        FileSnapshotSource::open_sync(path.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn loading_chunks_from_snapshot_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("snapshot.bin");
+        let entries: Vec<_> = (0_u8..=255)
+            .step_by(5)
+            .map(|byte| (H256::repeat_byte(byte), u64::from(byte) + 1))
+            .collect();
+        let source = write_test_file(&path, &entries);
+        assert_eq!(source.header().log_count, entries.len() as u64);
+
+        let key_chunks: Vec<_> = AsyncTreeRecovery::hashed_key_ranges(4).collect();
+        let counts = source.count_entries_sync(&key_chunks).unwrap();
+        assert_eq!(counts.iter().sum::<u64>(), entries.len() as u64);
+
+        let mut loaded_count = 0;
+        for (key_chunk, count) in key_chunks.iter().zip(counts) {
+            let chunk_entries = source.load_entries_sync(key_chunk).unwrap();
+            assert_eq!(chunk_entries.len() as u64, count);
+            for entry in &chunk_entries {
+                assert!(key_chunk.contains(&u256_to_h256(entry.key)));
+                assert_eq!(entry.value, H256::repeat_byte(0xaa));
+            }
+            loaded_count += chunk_entries.len();
+        }
+        assert_eq!(loaded_count, entries.len());
+    }
+
+    #[test]
+    fn duplicate_keys_in_snapshot_file_are_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("snapshot.bin");
+        let key = H256::repeat_byte(0x42);
+        let source = write_test_file(&path, &[(key, 1), (key, 2)]);
+        let full_range = H256::zero()..=H256::repeat_byte(0xff);
+        let err = source.load_entries_sync(&full_range).unwrap_err();
+        let err = err.downcast::<RecoveryError>().unwrap();
+        assert!(
+            matches!(err, RecoveryError::CorruptedSnapshot { key: err_key, .. } if err_key == key),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn truncated_snapshot_file_is_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("snapshot.bin");
+        write_test_file(
+            &path,
+            &[(H256::repeat_byte(1), 1), (H256::repeat_byte(2), 2)],
+        );
+        let contents = fs::read(&path).unwrap();
+        fs::write(&path, &contents[..contents.len() - 1]).unwrap();
+
+        let err = FileSnapshotSource::open_sync(path).unwrap_err();
+        assert!(format!("{err}").contains("truncated"), "{err}");
+    }
+}
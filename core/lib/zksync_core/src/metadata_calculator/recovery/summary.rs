@@ -0,0 +1,409 @@
+//! Summary of per-chunk timings logged at the end of Merkle tree recovery.
+//!
+//! Timings are collected by the tree applier for each recovered chunk. To keep memory usage bounded
+//! regardless of the number of chunks, only the slowest chunks are retained together with aggregated stats;
+//! the p95 chunk duration is estimated using a histogram with exponential buckets.
+
+use std::{collections::HashMap, fmt, ops, time::Duration};
+
+use serde::Serialize;
+use zksync_types::H256;
+
+use super::serialize_duration_secs;
+
+/// Timings of loading and applying entries for a chunk, broken down by recovery stage. For chunks with
+/// streamed entries, timings are summed over all batches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct ChunkStageTimings {
+    /// Time spent waiting for the loaded entries budget before loading the chunk.
+    pub acquire: Duration,
+    /// Time spent loading (and sorting) chunk entries.
+    pub load: Duration,
+    /// Time loaded entries spent waiting for the tree applier.
+    pub wait: Duration,
+    /// Time spent extending the tree with chunk entries.
+    pub extend: Duration,
+    /// Size of loaded entries in bytes.
+    pub byte_count: u64,
+}
+
+impl ops::AddAssign for ChunkStageTimings {
+    fn add_assign(&mut self, rhs: Self) {
+        self.acquire += rhs.acquire;
+        self.load += rhs.load;
+        self.wait += rhs.wait;
+        self.extend += rhs.extend;
+        self.byte_count += rhs.byte_count;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RecoveredChunkTimings {
+    chunk_id: usize,
+    key_range: ops::RangeInclusive<H256>,
+    entry_count: usize,
+    /// Wall-clock duration of recovering the chunk, from acquiring the loaded entries budget
+    /// until its last entries were applied to the tree.
+    duration: Duration,
+    stages: ChunkStageTimings,
+}
+
+/// Histogram of chunk durations with [`Self::BUCKETS_PER_DOUBLING`] exponential buckets per doubling
+/// starting from [`Self::MIN_DURATION`].
+#[derive(Debug)]
+struct DurationHistogram {
+    buckets: Vec<usize>,
+}
+
+impl Default for DurationHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; Self::BUCKET_COUNT],
+        }
+    }
+}
+
+impl DurationHistogram {
+    const MIN_DURATION: Duration = Duration::from_millis(1);
+    const BUCKETS_PER_DOUBLING: usize = 4;
+    /// Covers durations up to ~4 hours; longer durations are put into the last bucket.
+    const BUCKET_COUNT: usize = 24 * Self::BUCKETS_PER_DOUBLING;
+
+    fn bucket_index(duration: Duration) -> usize {
+        let ratio = duration.as_secs_f64() / Self::MIN_DURATION.as_secs_f64();
+        if ratio <= 1.0 {
+            return 0;
+        }
+        let index = (ratio.log2() * Self::BUCKETS_PER_DOUBLING as f64).ceil() as usize;
+        index.min(Self::BUCKET_COUNT - 1)
+    }
+
+    fn bucket_upper_bound(index: usize) -> Duration {
+        let exp = index as f64 / Self::BUCKETS_PER_DOUBLING as f64;
+        Self::MIN_DURATION.mul_f64(exp.exp2())
+    }
+
+    fn observe(&mut self, duration: Duration) {
+        self.buckets[Self::bucket_index(duration)] += 1;
+    }
+
+    /// Returns the upper bound of the bucket containing the specified quantile.
+    fn quantile(&self, quantile: f64) -> Option<Duration> {
+        let total_count: usize = self.buckets.iter().sum();
+        if total_count == 0 {
+            return None;
+        }
+        let rank = ((total_count as f64 * quantile).ceil() as usize).max(1);
+        let mut cumulative_count = 0;
+        for (index, &count) in self.buckets.iter().enumerate() {
+            cumulative_count += count;
+            if cumulative_count >= rank {
+                return Some(Self::bucket_upper_bound(index));
+            }
+        }
+        unreachable!("rank doesn't exceed the total count")
+    }
+}
+
+/// Collects per-chunk timings in the tree applier.
+#[derive(Debug, Default)]
+pub(super) struct ChunkTimingsCollector {
+    /// Timings for chunks with streamed entries that are not fully applied yet.
+    pending: HashMap<usize, ChunkStageTimings>,
+    /// Slowest recovered chunks sorted by descending duration.
+    slowest_chunks: Vec<RecoveredChunkTimings>,
+    chunk_count: usize,
+    total_duration: Duration,
+    byte_count: u64,
+    histogram: DurationHistogram,
+}
+
+impl ChunkTimingsCollector {
+    /// Number of slowest chunks retained for the summary.
+    const SLOWEST_CHUNK_COUNT: usize = 10;
+    const QUANTILE: f64 = 0.95;
+
+    /// Observes timings for entries of a chunk applied to the tree. If `is_chunk_start` is set (e.g.,
+    /// because the chunk loader was retried), previously observed timings for the chunk are discarded.
+    pub fn observe_entries(
+        &mut self,
+        chunk_id: usize,
+        is_chunk_start: bool,
+        stages: ChunkStageTimings,
+    ) {
+        if is_chunk_start {
+            self.pending.insert(chunk_id, stages);
+        } else {
+            *self.pending.entry(chunk_id).or_default() += stages;
+        }
+    }
+
+    /// Records a recovered chunk with the timings observed for it. `load_duration` is the time elapsed
+    /// since the chunk loader has started loading the chunk (i.e., after acquiring the loaded entries budget).
+    pub fn chunk_recovered(
+        &mut self,
+        chunk_id: usize,
+        key_range: &ops::RangeInclusive<H256>,
+        entry_count: usize,
+        load_duration: Duration,
+    ) {
+        let stages = self.pending.remove(&chunk_id).unwrap_or_default();
+        let duration = stages.acquire + load_duration;
+        self.chunk_count += 1;
+        self.total_duration += duration;
+        self.byte_count += stages.byte_count;
+        self.histogram.observe(duration);
+
+        let pos = self
+            .slowest_chunks
+            .partition_point(|chunk| chunk.duration >= duration);
+        if pos < Self::SLOWEST_CHUNK_COUNT {
+            self.slowest_chunks.insert(
+                pos,
+                RecoveredChunkTimings {
+                    chunk_id,
+                    key_range: key_range.clone(),
+                    entry_count,
+                    duration,
+                    stages,
+                },
+            );
+            self.slowest_chunks.truncate(Self::SLOWEST_CHUNK_COUNT);
+        }
+    }
+
+    /// Returns an abbreviated summary of collected timings.
+    pub fn summary(&self) -> ChunkTimingsSummary {
+        let slowest_chunk = self.slowest_chunks.first();
+        let max_duration = slowest_chunk.map_or(Duration::ZERO, |chunk| chunk.duration);
+        let avg_duration = if self.chunk_count == 0 {
+            Duration::ZERO
+        } else {
+            self.total_duration / self.chunk_count as u32
+        };
+        // The bucket upper bound may exceed the actual durations, so it's capped by the maximum duration.
+        let p95_duration = self
+            .histogram
+            .quantile(Self::QUANTILE)
+            .map_or(Duration::ZERO, |duration| duration.min(max_duration));
+        ChunkTimingsSummary {
+            chunk_count: self.chunk_count,
+            avg_duration,
+            p95_duration,
+            byte_count: self.byte_count,
+            slowest_chunk: slowest_chunk.map(|chunk| SlowestChunk {
+                index: chunk.chunk_id,
+                duration: chunk.duration,
+            }),
+        }
+    }
+
+    /// Returns the full multi-line summary of collected timings, including the slowest chunks.
+    pub fn report(&self, retry_count: usize) -> ChunkTimingsReport<'_> {
+        ChunkTimingsReport {
+            collector: self,
+            retry_count,
+        }
+    }
+}
+
+/// Abbreviated summary of per-chunk timings for chunks recovered by the current process. Attached to
+/// [`RecoveryStats`](super::RecoveryStats) and [`RecoveryReport`](super::RecoveryReport).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ChunkTimingsSummary {
+    /// Number of chunks recovered by the current process.
+    pub chunk_count: usize,
+    /// Average wall-clock duration of recovering a chunk.
+    #[serde(
+        rename = "avg_duration_secs",
+        serialize_with = "serialize_duration_secs"
+    )]
+    pub avg_duration: Duration,
+    /// Approximate 95th percentile of chunk durations.
+    #[serde(
+        rename = "p95_duration_secs",
+        serialize_with = "serialize_duration_secs"
+    )]
+    pub p95_duration: Duration,
+    /// Total size of loaded chunk entries in bytes.
+    pub byte_count: u64,
+    /// Slowest recovered chunk, or `None` if no chunks were recovered.
+    pub slowest_chunk: Option<SlowestChunk>,
+}
+
+/// Slowest recovered chunk in [`ChunkTimingsSummary`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SlowestChunk {
+    /// 0-based index of the chunk.
+    pub index: usize,
+    /// Wall-clock duration of recovering the chunk.
+    #[serde(rename = "duration_secs", serialize_with = "serialize_duration_secs")]
+    pub duration: Duration,
+}
+
+impl fmt::Display for ChunkTimingsSummary {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "recovered {} chunks ({}B loaded); chunk duration avg: {:?}, p95: {:?}",
+            self.chunk_count, self.byte_count, self.avg_duration, self.p95_duration
+        )?;
+        if let Some(chunk) = &self.slowest_chunk {
+            write!(
+                formatter,
+                ", max: {:?} (chunk #{})",
+                chunk.duration, chunk.index
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Multi-line summary of per-chunk timings logged at the end of recovery.
+#[derive(Debug)]
+pub(super) struct ChunkTimingsReport<'a> {
+    collector: &'a ChunkTimingsCollector,
+    retry_count: usize,
+}
+
+impl fmt::Display for ChunkTimingsReport<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let collector = self.collector;
+        write!(
+            formatter,
+            "Merkle tree recovery {}; {} chunk retries",
+            collector.summary(),
+            self.retry_count
+        )?;
+        if collector.slowest_chunks.is_empty() {
+            return Ok(());
+        }
+        write!(
+            formatter,
+            "\nSlowest chunks (acquire / load / wait for tree / extend tree):"
+        )?;
+        for chunk in &collector.slowest_chunks {
+            let stages = &chunk.stages;
+            write!(
+                formatter,
+                "\n  #{} {:?}: {:?} ({:?} / {:?} / {:?} / {:?}), {} entries, {}B",
+                chunk.chunk_id,
+                chunk.key_range,
+                chunk.duration,
+                stages.acquire,
+                stages.load,
+                stages.wait,
+                stages.extend,
+                chunk.entry_count,
+                stages.byte_count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recover_chunk(collector: &mut ChunkTimingsCollector, chunk_id: usize, duration: Duration) {
+        let stages = ChunkStageTimings {
+            load: duration / 2,
+            extend: duration / 2,
+            byte_count: 100,
+            ..ChunkStageTimings::default()
+        };
+        collector.observe_entries(chunk_id, true, stages);
+        collector.chunk_recovered(
+            chunk_id,
+            &(H256::zero()..=H256::repeat_byte(0xff)),
+            10,
+            duration,
+        );
+    }
+
+    #[test]
+    fn histogram_quantiles() {
+        let mut histogram = DurationHistogram::default();
+        assert_eq!(histogram.quantile(0.95), None);
+        for millis in 1..=100 {
+            histogram.observe(Duration::from_millis(millis));
+        }
+        let p95 = histogram.quantile(0.95).unwrap();
+        assert!(p95 >= Duration::from_millis(95), "{p95:?}");
+        assert!(p95 < Duration::from_millis(120), "{p95:?}");
+
+        let mut histogram = DurationHistogram::default();
+        histogram.observe(Duration::ZERO);
+        histogram.observe(Duration::from_secs(1_000_000));
+        assert_eq!(
+            histogram.quantile(0.5).unwrap(),
+            DurationHistogram::MIN_DURATION
+        );
+        assert!(histogram.quantile(1.0).unwrap() > Duration::from_secs(3_600));
+    }
+
+    #[test]
+    fn collector_retains_slowest_chunks() {
+        let mut collector = ChunkTimingsCollector::default();
+        for chunk_id in 0..50 {
+            recover_chunk(
+                &mut collector,
+                chunk_id,
+                Duration::from_millis(chunk_id as u64 + 1),
+            );
+        }
+        assert_eq!(collector.slowest_chunks.len(), 10);
+        let slowest_ids: Vec<_> = collector
+            .slowest_chunks
+            .iter()
+            .map(|chunk| chunk.chunk_id)
+            .collect();
+        assert_eq!(slowest_ids, (40..50).rev().collect::<Vec<_>>());
+        assert!(collector.pending.is_empty());
+
+        let summary = collector.summary();
+        assert_eq!(summary.chunk_count, 50);
+        assert_eq!(summary.byte_count, 5_000);
+        assert_eq!(summary.avg_duration, Duration::from_micros(25_500));
+        assert!(summary.p95_duration <= Duration::from_millis(50));
+        assert_eq!(
+            summary.slowest_chunk,
+            Some(SlowestChunk {
+                index: 49,
+                duration: Duration::from_millis(50),
+            })
+        );
+
+        let report = collector.report(3).to_string();
+        assert!(report.contains("recovered 50 chunks"), "{report}");
+        assert!(report.contains("3 chunk retries"), "{report}");
+        assert_eq!(report.lines().count(), 12, "{report}");
+    }
+
+    #[test]
+    fn collector_resets_timings_for_retried_chunks() {
+        let mut collector = ChunkTimingsCollector::default();
+        let batch = ChunkStageTimings {
+            load: Duration::from_millis(10),
+            byte_count: 100,
+            ..ChunkStageTimings::default()
+        };
+        collector.observe_entries(0, true, batch);
+        collector.observe_entries(0, false, batch);
+        // The chunk loader was retried.
+        collector.observe_entries(0, true, batch);
+        collector.observe_entries(0, false, batch);
+        collector.chunk_recovered(
+            0,
+            &(H256::zero()..=H256::zero()),
+            5,
+            Duration::from_millis(30),
+        );
+
+        let stages = collector.slowest_chunks[0].stages;
+        assert_eq!(stages.load, Duration::from_millis(20));
+        assert_eq!(stages.byte_count, 200);
+        assert_eq!(collector.summary().byte_count, 200);
+    }
+}
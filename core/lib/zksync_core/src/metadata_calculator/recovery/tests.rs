@@ -0,0 +1,7500 @@
+//! Tests for metadata calculator snapshot recovery.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use assert_matches::assert_matches;
+use futures::FutureExt as _;
+use tempfile::TempDir;
+use test_casing::{test_casing, Product};
+use zksync_config::configs::{
+    chain::OperationsManagerConfig,
+    database::{MerkleTreeConfig, MerkleTreeRecoveryConfig},
+};
+use zksync_dal::tree_recovery_progress_dal::RecoveredTreeChunk;
+use zksync_health_check::{CheckHealth, ReactiveHealthCheck};
+use zksync_merkle_tree::{MerkleTreeColumnFamily, RocksDBWrapper};
+use zksync_object_store::ObjectStoreFactory;
+use zksync_types::{AccountTreeId, Address, L1BatchNumber, L2ChainId, StorageKey, StorageLog};
+use zksync_utils::h256_to_u256;
+
+use super::{disk_space::FsStatsProvider, integrity::IntegrityCheckOptions, *};
+use crate::{
+    genesis::{ensure_genesis_state, GenesisParams},
+    metadata_calculator::{
+        helpers::{wipe_path, L1BatchWithLogs},
+        tests::{extend_db_state, gen_storage_logs, run_calculator, setup_calculator},
+        MetadataCalculator, MetadataCalculatorConfig, MetadataCalculatorModeConfig,
+        TreeLookupError, TreeState,
+    },
+};
+
+impl<'a> RecoveryOptions<'a> {
+    /// Options recovering the tree in a single chunk with entries loaded from `entry_source`.
+    /// Tests override the options they exercise.
+    fn for_tests(
+        entry_source: impl RecoveryEntrySource + 'a,
+        events: impl HandleRecoveryEvent + 'a,
+    ) -> Self {
+        Self {
+            mode: RecoveryMode::Normal,
+            chunk_count: 1,
+            concurrency_limit: ConcurrencyLimits::fixed(1),
+            overrides: RecoveryOverrides::default(),
+            max_chunk_attempts: 1,
+            chunk_retry_delays: ChunkRetryDelays::default(),
+            fail_fast: true,
+            sub_chunk_size: None,
+            streaming_batch_size: None,
+            loaded_entries_soft_cap: None,
+            flush_interval: FlushInterval::default(),
+            max_entries_per_second: None,
+            max_duration: None,
+            latest_snapshot_check: None,
+            prioritize_large_chunks: false,
+            disk_space_check: None,
+            verification_samples_per_chunk: None,
+            proof_verification_samples: None,
+            mismatch_diagnostic_keys_per_chunk: None,
+            watchdog: None,
+            fingerprint_log: None,
+            progress_table: None,
+            chunk_filter_batch_size: 1_000,
+            check_chunk_ends: false,
+            repair_mismatched_chunks: false,
+            skip_root_hash_check: false,
+            replica: None,
+            connection_retry_timeout: Duration::from_secs(60),
+            connection_acquire_timeout: Duration::from_secs(30),
+            entry_source: Some(Box::new(entry_source)),
+            events: Arc::new(events),
+        }
+    }
+}
+
+#[test]
+fn calculating_hashed_key_ranges_with_single_chunk() {
+    let mut ranges = AsyncTreeRecovery::hashed_key_ranges(1);
+    let full_range = ranges.next().unwrap();
+    assert_eq!(full_range, H256::zero()..=H256([0xff; 32]));
+}
+
+#[test]
+fn calculating_hashed_key_ranges_for_256_chunks() {
+    let ranges = AsyncTreeRecovery::hashed_key_ranges(256);
+    let mut start = H256::zero();
+    let mut end = H256([0xff; 32]);
+
+    for (i, range) in ranges.enumerate() {
+        let i = u8::try_from(i).unwrap();
+        start.0[0] = i;
+        end.0[0] = i;
+        assert_eq!(range, start..=end);
+    }
+}
+
+#[test]
+fn classifying_transient_errors() {
+    let err = anyhow::Error::from(SqlxError::PoolTimedOut).context("Failed acquiring connection");
+    assert!(is_transient_error(&err));
+    let io_err = io::Error::new(io::ErrorKind::ConnectionReset, "connection reset");
+    let err = anyhow::Error::from(SqlxError::Io(io_err)).context("Failed getting entries");
+    assert!(is_transient_error(&err));
+
+    let err = anyhow::Error::from(SqlxError::RowNotFound).context("Failed getting entries");
+    assert!(!is_transient_error(&err));
+    let err = anyhow::anyhow!("node snapshot in Postgres is corrupted");
+    assert!(!is_transient_error(&err));
+
+    let err = anyhow::Error::from(ObjectStoreError::Other("network error".into()))
+        .context("Failed getting storage logs chunk");
+    assert!(is_transient_error(&err));
+    let err = anyhow::Error::from(ObjectStoreError::KeyNotFound("not found".into()));
+    assert!(!is_transient_error(&err));
+}
+
+#[test]
+fn calculating_retry_delays() {
+    let delays = ChunkRetryDelays::default();
+    let delay = delays.delay(1);
+    assert!(delay >= Duration::from_millis(250) && delay <= Duration::from_millis(500));
+    let delay = delays.delay(2);
+    assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_secs(1));
+    let delay = delays.delay(4);
+    assert!(delay >= Duration::from_secs(2) && delay <= Duration::from_secs(4));
+    for attempt in [10, 100, usize::MAX] {
+        let delay = delays.delay(attempt);
+        assert!(delay >= Duration::from_secs(15) && delay <= Duration::from_secs(30));
+    }
+
+    let delays = ChunkRetryDelays {
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(50),
+    };
+    let delay = delays.delay(1);
+    assert!(delay >= Duration::from_millis(5) && delay <= Duration::from_millis(10));
+    let delay = delays.delay(10);
+    assert!(delay >= Duration::from_millis(25) && delay <= Duration::from_millis(50));
+}
+
+#[tokio::test]
+async fn sorting_and_checking_chunk_entries() {
+    let entries = vec![
+        TreeEntry::new(U256::from(3), 3, H256::repeat_byte(3)),
+        TreeEntry::new(U256::from(1), 1, H256::repeat_byte(1)),
+        TreeEntry::new(U256::from(2), 2, H256::repeat_byte(2)),
+    ];
+    let entries = sort_and_check_entries(entries).await.unwrap();
+    let keys: Vec<_> = entries.iter().map(|entry| entry.key).collect();
+    assert_eq!(keys, [U256::from(1), U256::from(2), U256::from(3)]);
+
+    let entries = vec![
+        TreeEntry::new(U256::from(2), 2, H256::repeat_byte(2)),
+        TreeEntry::new(U256::from(1), 1, H256::repeat_byte(1)),
+        TreeEntry::new(U256::from(2), 3, H256::repeat_byte(3)),
+    ];
+    let err = sort_and_check_entries(entries).await.unwrap_err();
+    let err = RecoveryError::from(err);
+    assert_matches!(
+        err,
+        RecoveryError::CorruptedSnapshot { key, .. } if key == hashed_key(&U256::from(2))
+    );
+}
+
+fn assert_contiguous_ranges(ranges: &[ops::RangeInclusive<H256>], chunk_count: usize) {
+    assert_eq!(ranges.len(), chunk_count);
+    for window in ranges.windows(2) {
+        let [prev_range, range] = window else {
+            unreachable!();
+        };
+        assert!(range.start() <= range.end());
+        assert_eq!(
+            h256_to_u256(*range.start()),
+            h256_to_u256(*prev_range.end()) + 1
+        );
+    }
+    assert_eq!(*ranges.first().unwrap().start(), H256::zero());
+    assert_eq!(*ranges.last().unwrap().end(), H256([0xff; 32]));
+}
+
+fn count_entries(histogram: &[u64], range: &ops::RangeInclusive<H256>) -> u64 {
+    let bucket = |key: &H256| usize::from(u16::from_be_bytes([key.0[0], key.0[1]]));
+    histogram[bucket(range.start())..=bucket(range.end())]
+        .iter()
+        .sum()
+}
+
+#[test_casing(5, [1, 3, 7, 256, 1_000])]
+fn calculating_weighted_key_ranges_for_uniform_histogram(chunk_count: usize) {
+    let histogram = vec![10; 1 << 16];
+    let ranges = AsyncTreeRecovery::weighted_key_ranges(&histogram, chunk_count);
+    assert_contiguous_ranges(&ranges, chunk_count);
+
+    let expected_chunk_size = 10 * (1 << 16) / chunk_count as u64;
+    for range in &ranges {
+        let entry_count = count_entries(&histogram, range);
+        assert!(
+            entry_count.abs_diff(expected_chunk_size) <= 10,
+            "{range:?} has {entry_count} entries, expected ~{expected_chunk_size}"
+        );
+    }
+}
+
+#[test_casing(4, [2, 5, 16, 100])]
+fn calculating_weighted_key_ranges_for_skewed_histogram(chunk_count: usize) {
+    // Entries are concentrated in the first 1/16th of the key space.
+    let mut histogram = vec![1; 1 << 16];
+    for count in &mut histogram[..1 << 12] {
+        *count = 100;
+    }
+    let total_count: u64 = histogram.iter().sum();
+    let ranges = AsyncTreeRecovery::weighted_key_ranges(&histogram, chunk_count);
+    assert_contiguous_ranges(&ranges, chunk_count);
+
+    let expected_chunk_size = total_count / chunk_count as u64;
+    for range in &ranges {
+        let entry_count = count_entries(&histogram, range);
+        assert!(
+            entry_count.abs_diff(expected_chunk_size) <= 100,
+            "{range:?} has {entry_count} entries, expected ~{expected_chunk_size}"
+        );
+    }
+}
+
+#[test_casing(3, [1, 5, 256])]
+fn calculating_weighted_key_ranges_for_sparse_histogram(chunk_count: usize) {
+    let mut histogram = vec![0; 1 << 16];
+    histogram[0x1234] = 1_000;
+    let ranges = AsyncTreeRecovery::weighted_key_ranges(&histogram, chunk_count);
+    assert_contiguous_ranges(&ranges, chunk_count);
+
+    let non_empty_ranges: Vec<_> = ranges
+        .iter()
+        .filter(|range| count_entries(&histogram, range) > 0)
+        .collect();
+    assert_eq!(non_empty_ranges.len(), 1);
+}
+
+#[test]
+fn weighted_key_ranges_fall_back_to_equal_width_ranges() {
+    let histogram = vec![0; 1 << 16];
+    let ranges = AsyncTreeRecovery::weighted_key_ranges(&histogram, 10);
+    let expected_ranges: Vec<_> = AsyncTreeRecovery::hashed_key_ranges(10).collect();
+    assert_eq!(ranges, expected_ranges);
+
+    let chunk_count = (1 << 16) + 1;
+    let histogram = vec![1; 1 << 16];
+    let ranges = AsyncTreeRecovery::weighted_key_ranges(&histogram, chunk_count);
+    assert_contiguous_ranges(&ranges, chunk_count);
+}
+
+#[test_casing(5, [3, 7, 23, 100, 255])]
+fn calculating_hashed_key_ranges_for_arbitrary_chunks(chunk_count: usize) {
+    let ranges: Vec<_> = AsyncTreeRecovery::hashed_key_ranges(chunk_count).collect();
+    assert_eq!(ranges.len(), chunk_count);
+
+    for window in ranges.windows(2) {
+        let [prev_range, range] = window else {
+            unreachable!();
+        };
+        assert_eq!(
+            h256_to_u256(*range.start()),
+            h256_to_u256(*prev_range.end()) + 1
+        );
+    }
+    assert_eq!(*ranges.first().unwrap().start(), H256::zero());
+    assert_eq!(*ranges.last().unwrap().end(), H256([0xff; 32]));
+}
+
+#[test]
+fn calculating_chunk_count() {
+    let mut snapshot = SnapshotParameters {
+        l1_batch_number: L1BatchNumber(1),
+        miniblock: MiniblockNumber(1),
+        log_count: 160_000_000,
+        expected_root_hash: H256::zero(),
+    };
+    assert_eq!(snapshot.chunk_count_for_size(200_000), 800);
+
+    snapshot.log_count += 1;
+    assert_eq!(snapshot.chunk_count_for_size(200_000), 801);
+
+    snapshot.log_count = 100;
+    assert_eq!(snapshot.chunk_count_for_size(200_000), 1);
+    assert_eq!(snapshot.chunk_count_for_size(30), 4);
+}
+
+async fn create_test_db(path: PathBuf) -> RocksDBWrapper {
+    create_db(path, TreeDbParams::for_tests(), None).await
+}
+
+async fn create_tree_recovery(path: PathBuf, l1_batch: L1BatchNumber) -> AsyncTreeRecovery {
+    let db = create_test_db(path).await;
+    AsyncTreeRecovery::new(db, l1_batch.0.into(), MerkleTreeMode::Full)
+}
+
+#[tokio::test]
+async fn reading_entries_from_corrupted_tree_returns_error() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(db_path.clone(), L1BatchNumber(1)).await;
+    let entries: Vec<_> = (1_u64..=1_000)
+        .map(|i| TreeEntry::new(U256::from(i), i, H256::from_low_u64_be(i)))
+        .collect();
+    tree.extend(entries.clone()).await.unwrap();
+    let keys: Vec<_> = entries.iter().map(|entry| entry.key).collect();
+    assert_eq!(tree.entries(keys.clone()).await.unwrap(), entries);
+    drop(tree);
+
+    // Corrupt all tree nodes, leaving the tree manifest intact.
+    let db = create_test_db(db_path.clone()).await.into_inner();
+    let mut write_batch = db.new_write_batch();
+    for (key, _) in db.prefix_iterator_cf(MerkleTreeColumnFamily::Tree, &[]) {
+        if *key != [0] {
+            write_batch.put_cf(MerkleTreeColumnFamily::Tree, &key, b"garbage");
+        }
+    }
+    db.write(write_batch).unwrap();
+    drop(db);
+
+    let mut tree = create_tree_recovery(db_path, L1BatchNumber(1)).await;
+    let err = tree.entries(keys).await.unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("Failed reading tree entries"), "{err}");
+    // The tree must remain usable after an error.
+    assert_eq!(tree.recovered_version(), 1);
+    tree.entries(vec![U256::one()]).await.unwrap_err();
+}
+
+#[tokio::test]
+async fn inspecting_tree_db() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let db_path = temp_dir.path().join("recovery");
+    let err = inspect_tree_db(db_path.clone()).await.unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("read-only mode"), "{err}");
+
+    let mut tree = create_tree_recovery(db_path.clone(), L1BatchNumber(1)).await;
+    let entries: Vec<_> = (1_u64..=100)
+        .map(|i| TreeEntry::new(U256::from(i) << 128, i, H256::from_low_u64_be(i)))
+        .collect();
+    tree.extend(entries).await.unwrap();
+    let journal_entry = ChunkJournalEntry {
+        recovered_version: 1,
+        last_applied_key: Some(U256::from(50) << 128),
+    };
+    tree.set_journal_entry(H256::zero().0.to_vec(), Some(journal_entry.serialize()))
+        .await;
+    // Journal entries for another recovered version must be ignored.
+    let stale_journal_entry = ChunkJournalEntry {
+        recovered_version: 5,
+        last_applied_key: None,
+    };
+    tree.set_journal_entry(
+        H256::repeat_byte(0x80).0.to_vec(),
+        Some(stale_journal_entry.serialize()),
+    )
+    .await;
+    let root_hash = tree.root_hash().await;
+
+    // The tree can be inspected while it's open.
+    let inspection = inspect_tree_db(db_path.clone()).await.unwrap();
+    assert_eq!(inspection.path, db_path);
+    assert!(inspection.disk_usage_bytes > 0);
+    let TreeDbState::Recovering(recovery) = inspection.state else {
+        panic!("Unexpected tree state: {inspection:?}");
+    };
+    assert_eq!(recovery.recovered_version, 1);
+    assert_eq!(recovery.root_hash, root_hash);
+    assert_eq!(recovery.leaf_count, 100);
+    assert_eq!(recovery.least_key, Some(u256_to_h256(U256::one() << 128)));
+    assert_eq!(
+        recovery.greatest_key,
+        Some(u256_to_h256(U256::from(100) << 128))
+    );
+    assert!(recovery.key_span < 1e-9, "{recovery:?}");
+    assert_eq!(
+        recovery.incomplete_chunks,
+        [IncompleteChunk {
+            start_key: H256::zero(),
+            last_applied_key: Some(u256_to_h256(U256::from(50) << 128)),
+        }]
+    );
+    assert!(!recovery.may_have_lost_writes);
+    // Inspection must not modify the tree.
+    assert_eq!(tree.leaf_count().await, 100);
+    assert_eq!(tree.journal_entries().await.len(), 2);
+
+    let tree = tree.finalize().await;
+    let root_hash = tree.root_hash();
+    drop(tree);
+    let inspection = inspect_tree_db(db_path).await.unwrap();
+    assert_eq!(
+        inspection.state,
+        TreeDbState::Ready {
+            next_l1_batch_number: L1BatchNumber(2),
+            root_hash,
+            leaf_count: 100,
+        }
+    );
+}
+
+fn mock_snapshot_recovery(root_hash: H256) -> SnapshotRecoveryStatus {
+    SnapshotRecoveryStatus {
+        l1_batch_number: L1BatchNumber(1),
+        l1_batch_root_hash: root_hash,
+        miniblock_number: MiniblockNumber(1),
+        miniblock_root_hash: H256::zero(), // not used by the tree
+        last_finished_chunk_id: Some(0),
+        total_chunk_count: 1,
+    }
+}
+
+#[test_casing(3, [7, 1_000, 10_000])]
+#[tokio::test]
+async fn filtering_large_number_of_chunks(batch_size: usize) {
+    const CHUNK_COUNT: usize = 5_000;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    let mut storage = pool.access_storage().await.unwrap();
+    let all_entries = storage
+        .storage_logs_dal()
+        .get_tree_entries_for_miniblock(snapshot.miniblock, H256::zero()..=H256::repeat_byte(0xff))
+        .await
+        .unwrap();
+    let sorted_hashed_keys: Vec<_> = all_entries
+        .iter()
+        .map(|entry| hashed_key(&entry.key))
+        .collect();
+
+    // Recover the first half of chunks.
+    let key_chunks: Vec<_> = AsyncTreeRecovery::hashed_key_ranges(CHUNK_COUNT).collect();
+    let recovered_end = *key_chunks[CHUNK_COUNT / 2 - 1].end();
+    let recovered_entries = all_entries
+        .iter()
+        .filter(|entry| hashed_key(&entry.key) <= recovered_end)
+        .map(|entry| TreeEntry::new(entry.key, entry.leaf_index, entry.value));
+    let mut tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    tree.extend(recovered_entries.collect()).await.unwrap();
+
+    let remaining_chunks = tree
+        .filter_chunks(
+            &mut storage,
+            snapshot.miniblock,
+            &key_chunks,
+            batch_size,
+            false,
+            false,
+        )
+        .await
+        .unwrap();
+    // Empty chunks are considered recovered.
+    let expected_chunks: Vec<_> = key_chunks
+        .iter()
+        .cloned()
+        .enumerate()
+        .skip(CHUNK_COUNT / 2)
+        .filter(|(_, chunk)| {
+            let idx = sorted_hashed_keys.partition_point(|key| key < chunk.start());
+            sorted_hashed_keys
+                .get(idx)
+                .map_or(false, |key| chunk.contains(key))
+        })
+        .collect();
+    assert!(!expected_chunks.is_empty());
+    assert_eq!(remaining_chunks, expected_chunks);
+}
+
+#[tokio::test]
+async fn chunks_are_filtered_pessimistically_after_unsynced_writes() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = mock_snapshot_recovery(root_hash);
+    let mut storage = pool.access_storage().await.unwrap();
+    storage
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&snapshot_recovery)
+        .await
+        .unwrap();
+    let snapshot = SnapshotParameters::new(&pool, None, &snapshot_recovery)
+        .await
+        .unwrap();
+    let all_entries = storage
+        .storage_logs_dal()
+        .get_tree_entries_for_miniblock(snapshot.miniblock, H256::zero()..=H256::repeat_byte(0xff))
+        .await
+        .unwrap();
+
+    // Emulate a crash before the flush barrier: only a prefix of the chunk was persisted, and the chunk
+    // has no journal entry.
+    let db_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(db_path.clone(), L1BatchNumber(1)).await;
+    tree.relax_durability().await.unwrap();
+    let persisted_entries = all_entries[..all_entries.len() / 2]
+        .iter()
+        .map(|entry| TreeEntry::new(entry.key, entry.leaf_index, entry.value));
+    tree.extend(persisted_entries.collect()).await.unwrap();
+    drop(tree);
+
+    let mut tree = create_tree_recovery(db_path.clone(), L1BatchNumber(1)).await;
+    assert!(tree.may_have_lost_writes());
+    let key_chunks: Vec<_> = AsyncTreeRecovery::hashed_key_ranges(1).collect();
+    // Checking only chunk starts mistakes the chunk for a recovered one.
+    let remaining_chunks = tree
+        .filter_chunks(
+            &mut storage,
+            snapshot.miniblock,
+            &key_chunks,
+            10,
+            false,
+            false,
+        )
+        .await
+        .unwrap();
+    assert!(remaining_chunks.is_empty(), "{remaining_chunks:?}");
+
+    let remaining_chunks = tree
+        .filter_chunks(
+            &mut storage,
+            snapshot.miniblock,
+            &key_chunks,
+            10,
+            true,
+            false,
+        )
+        .await
+        .unwrap();
+    assert_eq!(remaining_chunks, [(0, key_chunks[0].clone())]);
+    let journal_entry = tree.chunk_journal_entry(&key_chunks[0]).await.unwrap();
+    let journal_entry = journal_entry.expect("no journal entry for incomplete chunk");
+    assert_eq!(journal_entry.last_applied_key, None);
+    drop(tree);
+    drop(storage);
+
+    let config = MetadataCalculatorRecoveryConfig {
+        relaxed_durability: true,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree = ensure_tree_ready(db_path.clone(), MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+    assert!(!db_path.join("UNSYNCED_RECOVERY").exists());
+}
+
+#[tokio::test]
+async fn chunks_with_truncated_tail_are_recovered_with_chunk_end_checks() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = mock_snapshot_recovery(root_hash);
+    let mut storage = pool.access_storage().await.unwrap();
+    storage
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&snapshot_recovery)
+        .await
+        .unwrap();
+    let all_entries = storage
+        .storage_logs_dal()
+        .get_tree_entries_for_miniblock(
+            snapshot_recovery.miniblock_number,
+            H256::zero()..=H256::repeat_byte(0xff),
+        )
+        .await
+        .unwrap();
+    drop(storage);
+
+    // Emulate a chunk with a truncated tail: its start is in the tree, but the tail is missing, and there is
+    // neither a journal entry nor a marker of lost writes.
+    let db_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(db_path.clone(), L1BatchNumber(1)).await;
+    let persisted_entries = all_entries[..all_entries.len() / 2]
+        .iter()
+        .map(|entry| TreeEntry::new(entry.key, entry.leaf_index, entry.value));
+    tree.extend(persisted_entries.collect()).await.unwrap();
+    assert!(!tree.may_have_lost_writes());
+    drop(tree);
+
+    // By default, the chunk is mistaken for a recovered one.
+    let config = MetadataCalculatorRecoveryConfig::default();
+    let err = ensure_tree_ready(db_path.clone(), MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap_err();
+    assert_matches!(err, RecoveryError::LeafIndexMismatch { .. });
+
+    let config = MetadataCalculatorRecoveryConfig {
+        check_chunk_ends: true,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree = ensure_tree_ready(db_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[tokio::test]
+async fn chunks_with_mismatched_start_are_repaired_if_configured() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = mock_snapshot_recovery(root_hash);
+    let mut storage = pool.access_storage().await.unwrap();
+    storage
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&snapshot_recovery)
+        .await
+        .unwrap();
+    let all_entries = storage
+        .storage_logs_dal()
+        .get_tree_entries_for_miniblock(
+            snapshot_recovery.miniblock_number,
+            H256::zero()..=H256::repeat_byte(0xff),
+        )
+        .await
+        .unwrap();
+    drop(storage);
+
+    // Emulate a recovered tree with a corrupted start entry of the first chunk.
+    let db_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(db_path.clone(), L1BatchNumber(1)).await;
+    let mut recovered_entries: Vec<_> = all_entries
+        .iter()
+        .map(|entry| TreeEntry::new(entry.key, entry.leaf_index, entry.value))
+        .collect();
+    recovered_entries[0].value = H256::repeat_byte(0xfe);
+    tree.extend(recovered_entries).await.unwrap();
+    drop(tree);
+
+    let config = MetadataCalculatorRecoveryConfig::default();
+    let err = ensure_tree_ready(db_path.clone(), MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap_err();
+    assert_matches!(err, RecoveryError::Other(_));
+    let err = format!("{err:#}");
+    assert!(err.contains("recovery procedure may be corrupted"), "{err}");
+
+    let config = MetadataCalculatorRecoveryConfig {
+        repair_mismatched_chunks: true,
+        skip_root_hash_check: false,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree = ensure_tree_ready(db_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[tokio::test]
+async fn recovery_with_low_background_write_rate_limit() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let mut storage = pool.access_storage().await.unwrap();
+    storage
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    drop(storage);
+
+    // Bulk loading is enabled, so that the tree is compacted when recovery is finalized.
+    let config = MetadataCalculatorRecoveryConfig {
+        bulk_load_memtable_capacity: Some(16 << 20),
+        background_write_rate_limit: Some(64 << 10), // 64 KiB/s
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let db_path = temp_dir.path().join("recovery");
+    let tree = ensure_tree_ready(db_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[test_casing(3, [(Some(1), None), (None, Some(1)), (Some(3), Some(4_096))])]
+#[tokio::test]
+async fn recovery_with_periodic_flushes(
+    flush_interval_chunks: Option<usize>,
+    flush_interval_bytes: Option<usize>,
+) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let mut storage = pool.access_storage().await.unwrap();
+    storage
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    drop(storage);
+
+    // Entries are streamed in small batches, so that byte-based flushes occur in the middle of chunks.
+    let config = MetadataCalculatorRecoveryConfig {
+        desired_chunk_size: 30,
+        streaming_batch_size: Some(7),
+        bulk_load_memtable_capacity: Some(16 << 20),
+        flush_interval_chunks,
+        flush_interval_bytes,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let db_path = temp_dir.path().join("recovery");
+    let tree = ensure_tree_ready(db_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+
+    let registry = vise::MetricsCollection::default().collect();
+    let mut buffer = String::new();
+    registry
+        .encode(&mut buffer, vise::Format::OpenMetricsForPrometheus)
+        .unwrap();
+    let series = "server_metadata_calculator_recovery_chunk_latency_seconds_count{stage=\"flush\"}";
+    let flush_count = buffer
+        .lines()
+        .find_map(|line| line.strip_prefix(series)?.trim().parse::<f64>().ok())
+        .unwrap_or_else(|| panic!("series `{series}` is missing:\n{buffer}"));
+    assert!(flush_count > 0.0, "{flush_count}");
+}
+
+#[tokio::test]
+async fn changing_background_write_rate_limit_during_recovery() {
+    const RATE_LIMIT: usize = 64 << 10; // 64 KiB/s
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let mut tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    tree.use_dedicated_thread_pool(2).unwrap();
+    let (override_sender, mut override_receiver) = watch::channel(0);
+    tree.follow_rate_limit_override(&mut override_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.background_write_rate_limit(), None);
+    override_sender.send_replace(RATE_LIMIT);
+    tree.follow_rate_limit_override(&mut override_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.background_write_rate_limit(), Some(RATE_LIMIT));
+    override_sender.send_replace(0);
+    tree.follow_rate_limit_override(&mut override_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.background_write_rate_limit(), None);
+
+    // The limit sent before recovery should be applied when applying the first chunk.
+    override_sender.send_replace(RATE_LIMIT);
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let recovery_options = RecoveryOptions {
+        chunk_count: 4,
+        overrides: RecoveryOverrides {
+            background_write_rate_limit: Some(override_receiver),
+            ..RecoveryOverrides::default()
+        },
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            RecoveryHealthUpdater::new(&health_updater, RecoveryMode::Normal, snapshot.log_count),
+        )
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[test_casing(3, [None, Some(2), Some(37)])]
+#[tokio::test]
+async fn basic_recovery_workflow(streaming_batch_size: Option<usize>) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    assert!(snapshot.log_count > 200);
+    assert_eq!(snapshot.miniblock, MiniblockNumber(1));
+    assert_eq!(snapshot.expected_root_hash, root_hash);
+
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    for chunk_count in [1, 4, 9, 16, 60, 256] {
+        println!("Recovering tree with {chunk_count} chunks");
+
+        let tree_path = temp_dir.path().join(format!("recovery-{chunk_count}"));
+        let tree = create_tree_recovery(tree_path, L1BatchNumber(1)).await;
+        let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+        let recovery_options = RecoveryOptions {
+            chunk_count,
+            streaming_batch_size,
+            ..RecoveryOptions::for_tests(
+                PostgresEntrySource {
+                    pool: &pool,
+                    replica: None,
+                    snapshot_miniblock: snapshot.miniblock,
+                    connection_retry_timeout: Duration::from_secs(60),
+                    connection_acquire_timeout: Duration::from_secs(30),
+                    use_copy: false,
+                },
+                RecoveryHealthUpdater::new(
+                    &health_updater,
+                    RecoveryMode::Normal,
+                    snapshot.log_count,
+                ),
+            )
+        };
+        let (tree, report) = tree
+            .recover(snapshot, recovery_options, &pool, &stop_receiver)
+            .await
+            .unwrap();
+
+        assert_eq!(tree.root_hash(), root_hash);
+        let RecoveryReport::Recovered {
+            l1_batch,
+            miniblock,
+            chunks,
+            entries,
+            duration,
+            root_hash: reported_root_hash,
+        } = report
+        else {
+            panic!("unexpected report: {report:?}");
+        };
+        assert_eq!(l1_batch, L1BatchNumber(1));
+        assert_eq!(miniblock, MiniblockNumber(1));
+        assert_eq!(chunks, chunk_count);
+        assert_eq!(entries, snapshot.log_count);
+        assert!(duration > Duration::ZERO);
+        assert_eq!(reported_root_hash, root_hash);
+        let health = health_check.check_health().await;
+        assert_matches!(health.status(), HealthStatus::Ready);
+        let details = health.details().unwrap();
+        assert_eq!(details["mode"], "recovery");
+        assert_eq!(details["chunk_count"], chunk_count);
+        assert_eq!(details["entry_count"], snapshot.log_count);
+        assert_eq!(details["retry_count"], 0);
+        assert_eq!(
+            details["root_hash"],
+            serde_json::to_value(root_hash).unwrap()
+        );
+        assert!(
+            details["duration_secs"].as_f64().unwrap() > 0.0,
+            "{details:?}"
+        );
+    }
+    assert_chunk_metrics_populated();
+}
+
+/// Checks that per-chunk recovery metrics are observed. Since metrics are global, this only checks
+/// that the corresponding histograms are non-empty.
+fn assert_chunk_metrics_populated() {
+    let registry = vise::MetricsCollection::default().collect();
+    let mut buffer = String::new();
+    registry
+        .encode(&mut buffer, vise::Format::OpenMetricsForPrometheus)
+        .unwrap();
+
+    let expected_series = [
+        "server_metadata_calculator_recovery_chunk_entries_count",
+        "server_metadata_calculator_recovery_chunk_duration_seconds_count",
+        "server_metadata_calculator_recovery_chunk_throughput_count{stage=\"extend_tree\"}",
+    ];
+    for series in expected_series {
+        let observation_count = buffer
+            .lines()
+            .find_map(|line| line.strip_prefix(series)?.trim().parse::<f64>().ok())
+            .unwrap_or_else(|| panic!("series `{series}` is missing:\n{buffer}"));
+        assert!(observation_count > 0.0, "{series} {observation_count}");
+    }
+}
+
+/// Wrapper around [`RecoveryHealthUpdater`] recording health details after each recovered chunk.
+#[derive(Debug)]
+struct HealthRecorder<'a> {
+    inner: RecoveryHealthUpdater<'a>,
+    health_check: ReactiveHealthCheck,
+    details: &'a StdMutex<Vec<serde_json::Value>>,
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for HealthRecorder<'_> {
+    fn recovery_started(
+        &self,
+        chunk_count: usize,
+        recovered_chunk_count: usize,
+        recovered_entry_count: u64,
+    ) {
+        self.inner
+            .recovery_started(chunk_count, recovered_chunk_count, recovered_entry_count);
+    }
+
+    fn disk_space_checked(&self, estimate: DiskSpaceEstimate) {
+        self.inner.disk_space_checked(estimate);
+    }
+
+    async fn chunk_recovered(&self, chunk: &ChunkDescriptor) {
+        self.inner.chunk_recovered(chunk).await;
+        let health = self.health_check.check_health().await;
+        assert_matches!(health.status(), HealthStatus::Recovering);
+        let details = health.details().expect("no health details").clone();
+        self.details.lock().unwrap().push(details);
+    }
+}
+
+#[tokio::test]
+async fn recovery_health_contains_eta() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let recorded_details = StdMutex::default();
+    let recorder = HealthRecorder {
+        inner: RecoveryHealthUpdater::new(
+            &health_updater,
+            RecoveryMode::Normal,
+            snapshot.log_count,
+        ),
+        health_check,
+        details: &recorded_details,
+    };
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: 8,
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            recorder,
+        )
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+
+    let recorded_details = recorded_details.into_inner().unwrap();
+    assert!(!recorded_details.is_empty());
+    let etas: Vec<_> = recorded_details
+        .iter()
+        .map(|details| {
+            assert_eq!(details["mode"], "recovery");
+            assert!(details["started_at"].as_u64().unwrap() > 0);
+            let entries_per_second = details["entries_per_second"].as_f64().unwrap();
+            assert!(entries_per_second.is_finite() && entries_per_second > 0.0);
+            details["estimated_time_remaining_secs"].as_f64().unwrap()
+        })
+        .collect();
+    assert!(
+        etas.iter().all(|eta| eta.is_finite() && *eta >= 0.0),
+        "{etas:?}"
+    );
+    assert_eq!(*etas.last().unwrap(), 0.0);
+    assert!(etas[0] > 0.0, "{etas:?}");
+
+    let inserted_entry_counts: Vec<_> = recorded_details
+        .iter()
+        .map(|details| {
+            let recent_entries_per_second = details["recent_entries_per_second"].as_f64().unwrap();
+            assert!(recent_entries_per_second.is_finite() && recent_entries_per_second > 0.0);
+            details["inserted_entry_count"].as_u64().unwrap()
+        })
+        .collect();
+    assert!(
+        inserted_entry_counts
+            .windows(2)
+            .all(|pair| pair[0] <= pair[1]),
+        "{inserted_entry_counts:?}"
+    );
+    assert_eq!(*inserted_entry_counts.last().unwrap(), snapshot.log_count);
+}
+
+#[test]
+fn recent_recovery_throughput_uses_sliding_window() {
+    let mut throughput = RecoveryThroughput::new(1_000);
+    let start = throughput.started_at;
+    assert_eq!(throughput.recent_entries_per_second, None);
+
+    // The window is shortened if recovery was started less than the window duration ago.
+    throughput.observe_chunk(10, start + Duration::from_secs(1));
+    assert_eq!(throughput.recent_entries_per_second, Some(10.0));
+    throughput.observe_chunk(20, start + Duration::from_secs(30));
+    assert_eq!(throughput.recent_entries_per_second, Some(1.0));
+
+    // Both previous chunks are outside the window.
+    throughput.observe_chunk(60, start + Duration::from_secs(100));
+    assert_eq!(throughput.recent_entries_per_second, Some(1.0));
+    assert_eq!(throughput.recent_chunks.len(), 1);
+    throughput.observe_chunk(120, start + Duration::from_secs(130));
+    assert_eq!(throughput.recent_entries_per_second, Some(3.0));
+}
+
+/// Wrapper around [`RecoveryHealthUpdater`] recording health on each finalization stage.
+#[derive(Debug)]
+struct FinalizeStageRecorder<'a> {
+    inner: RecoveryHealthUpdater<'a>,
+    health_check: ReactiveHealthCheck,
+    stages: &'a StdMutex<Vec<(RecoveryFinalizeStage, Health)>>,
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for FinalizeStageRecorder<'_> {
+    fn recovery_started(
+        &self,
+        chunk_count: usize,
+        recovered_chunk_count: usize,
+        recovered_entry_count: u64,
+    ) {
+        self.inner
+            .recovery_started(chunk_count, recovered_chunk_count, recovered_entry_count);
+    }
+
+    async fn chunk_recovered(&self, chunk: &ChunkDescriptor) {
+        self.inner.chunk_recovered(chunk).await;
+    }
+
+    fn finalize_stage_started(&self, stage: RecoveryFinalizeStage) {
+        self.inner.finalize_stage_started(stage);
+        let health = self
+            .health_check
+            .check_health()
+            .now_or_never()
+            .expect("health check is not ready");
+        self.stages.lock().unwrap().push((stage, health));
+    }
+
+    fn recovery_finished(&self, stats: RecoveryStats) {
+        self.inner.recovery_finished(stats);
+    }
+}
+
+#[test_casing(4, Product(([false, true], [false, true])))]
+#[tokio::test]
+async fn finalize_stages_are_reported_via_health(
+    use_db_profile: bool,
+    catch_up_before_ready: bool,
+) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let mut tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    if use_db_profile {
+        let profile = RecoveryDbProfile {
+            memtable_capacity: 32 << 20, // 32 MiB
+            max_write_buffer_number: 4,
+            block_cache_capacity: None,
+            total_write_buffer_size: None,
+            max_open_files: None,
+        };
+        tree.use_db_profile(profile).await.unwrap();
+        assert!(tree.uses_db_profile());
+    }
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let stages = StdMutex::default();
+    let recorder = FinalizeStageRecorder {
+        inner: RecoveryHealthUpdater::new(
+            &health_updater,
+            RecoveryMode::Normal,
+            snapshot.log_count,
+        )
+        .with_catch_up_before_ready(catch_up_before_ready),
+        health_check: health_updater.subscribe(),
+        stages: &stages,
+    };
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: 4,
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            recorder,
+        )
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+
+    let stages = stages.into_inner().unwrap();
+    let stage_names: Vec<_> = stages.iter().map(|(stage, _)| *stage).collect();
+    let mut expected_stage_names = vec![
+        RecoveryFinalizeStage::CheckLeafIndices,
+        RecoveryFinalizeStage::ComputeRootHash,
+        RecoveryFinalizeStage::FlushDb,
+        RecoveryFinalizeStage::WriteManifest,
+    ];
+    if use_db_profile {
+        // Auto-compaction is disabled during bulk loading, so the DB must be compacted on finalization.
+        expected_stage_names.insert(3, RecoveryFinalizeStage::CompactDb);
+    }
+    assert_eq!(stage_names, expected_stage_names);
+    for (stage, health) in &stages {
+        assert_matches!(health.status(), HealthStatus::Recovering);
+        let details = health.details().unwrap();
+        assert_eq!(
+            details["finalize_stage"],
+            serde_json::to_value(stage).unwrap()
+        );
+        assert_eq!(details["recovered_chunk_count"], 4);
+    }
+    assert_eq!(stages[2].1.details().unwrap()["finalize_stage"], "flush_db");
+
+    let health = health_check.check_health().await;
+    if catch_up_before_ready {
+        // The tree is only marked as ready once it catches up with Postgres.
+        assert_matches!(health.status(), HealthStatus::Recovering);
+    } else {
+        assert_matches!(health.status(), HealthStatus::Ready);
+    }
+    let details = health.details().unwrap();
+    assert_eq!(details["mode"], "recovery");
+    assert!(details.get("completed_at").is_some(), "{details:?}");
+}
+
+#[test_casing(3, [10, 100, 1_000])]
+#[tokio::test]
+async fn recovery_health_updates_are_throttled(chunk_count: usize) {
+    const CHUNK_DURATION: Duration = Duration::from_millis(10);
+    const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let health_events = RecoveryHealthUpdater::new(&health_updater, RecoveryMode::Normal, 1_000)
+        .with_health_update_interval(UPDATE_INTERVAL);
+    health_events.recovery_started(chunk_count, 0, 0);
+
+    // Use a fake clock advancing by `CHUNK_DURATION` on each recovered chunk.
+    let start = Instant::now();
+    let mut last_recovered_chunk_count = None;
+    let mut update_count = 0;
+    for index in 0..chunk_count {
+        let chunk = ChunkDescriptor {
+            index,
+            key_range: H256::zero()..=H256::zero(),
+            entry_count: Some(1),
+        };
+        let now = start + CHUNK_DURATION * (index as u32 + 1);
+        health_events.chunk_recovered_at(&chunk, now);
+
+        let health = health_check.check_health().await;
+        let recovered_chunk_count = health.details().unwrap()["recovered_chunk_count"].as_u64();
+        if recovered_chunk_count != last_recovered_chunk_count {
+            update_count += 1;
+            last_recovered_chunk_count = recovered_chunk_count;
+        }
+    }
+
+    // The last chunk must always be reported.
+    assert_eq!(last_recovered_chunk_count, Some(chunk_count as u64));
+    let total_duration = CHUNK_DURATION * chunk_count as u32;
+    let max_update_count = (total_duration.as_millis() / UPDATE_INTERVAL.as_millis()) as usize + 2;
+    assert!(
+        update_count <= max_update_count,
+        "{update_count} health updates for {chunk_count} chunks"
+    );
+}
+
+#[derive(Debug)]
+struct MockFsStats(u64);
+
+impl FsStatsProvider for MockFsStats {
+    fn available_space(&self, _path: &Path) -> anyhow::Result<u64> {
+        Ok(self.0)
+    }
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn disk_space_is_checked_before_recovery(strict: bool) {
+    const BYTES_PER_ENTRY: u64 = 1_000;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let recorded_details = StdMutex::default();
+    let recorder = HealthRecorder {
+        inner: RecoveryHealthUpdater::new(
+            &health_updater,
+            RecoveryMode::Normal,
+            snapshot.log_count,
+        ),
+        health_check,
+        details: &recorded_details,
+    };
+    // Available space is sufficient for the tree, but not for the margin.
+    let available_bytes = snapshot.log_count * BYTES_PER_ENTRY + 500;
+    let fs_stats = MockFsStats(available_bytes);
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: 8,
+        disk_space_check: Some(DiskSpaceCheck {
+            bytes_per_entry: BYTES_PER_ENTRY,
+            margin_bytes: 1_000,
+            strict,
+            fs_stats: &fs_stats,
+        }),
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            recorder,
+        )
+    };
+    let result = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await;
+
+    if strict {
+        let err = format!("{:#}", result.unwrap_err());
+        assert!(err.contains("disk space"), "{err}");
+        assert!(recorded_details.into_inner().unwrap().is_empty());
+        // No chunks should be recovered.
+        let mut tree = create_tree_recovery(tree_path, L1BatchNumber(1)).await;
+        let mut empty_tree =
+            create_tree_recovery(temp_dir.path().join("empty"), L1BatchNumber(1)).await;
+        assert_eq!(tree.root_hash().await, empty_tree.root_hash().await);
+    } else {
+        let (tree, _) = result.unwrap();
+        assert_eq!(tree.root_hash(), root_hash);
+
+        let recorded_details = recorded_details.into_inner().unwrap();
+        assert!(!recorded_details.is_empty());
+        for details in &recorded_details {
+            let disk_space = &details["disk_space"];
+            assert_eq!(
+                disk_space["required_bytes"].as_u64().unwrap(),
+                snapshot.log_count * BYTES_PER_ENTRY
+            );
+            assert_eq!(
+                disk_space["available_bytes"].as_u64().unwrap(),
+                available_bytes
+            );
+            assert_eq!(disk_space["margin_bytes"].as_u64().unwrap(), 1_000);
+        }
+    }
+}
+
+/// Recovery listener recording the number of recovered chunks.
+#[derive(Debug, Default)]
+struct RecoveredChunksListener {
+    chunk_count: Arc<AtomicUsize>,
+    recovered_chunk_count: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for RecoveredChunksListener {
+    fn recovery_started(
+        &self,
+        chunk_count: usize,
+        recovered_chunk_count: usize,
+        _recovered_entry_count: u64,
+    ) {
+        self.chunk_count.store(chunk_count, Ordering::SeqCst);
+        self.recovered_chunk_count
+            .store(recovered_chunk_count, Ordering::SeqCst);
+    }
+
+    async fn chunk_recovered(&self, _chunk: &ChunkDescriptor) {
+        self.recovered_chunk_count.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn ensure_ready_recovers_tree_from_snapshot() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let db = create_test_db(temp_dir.path().join("recovery")).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Empty { .. });
+
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let (recovery_status_sender, recovery_status) = watch::channel(None);
+    let listener = RecoveredChunksListener::default();
+    let (chunk_count, recovered_chunk_count) = (
+        listener.chunk_count.clone(),
+        listener.recovered_chunk_count.clone(),
+    );
+    let config = MetadataCalculatorRecoveryConfig::default();
+    let tree = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &recovery_status_sender,
+                recovery_listeners: vec![Box::new(listener)],
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+
+    let recovery_status = recovery_status
+        .borrow()
+        .clone()
+        .expect("no recovery status");
+    assert_eq!(recovery_status.snapshot_l1_batch, L1BatchNumber(1));
+    assert!(recovery_status.chunk_count > 0);
+    assert_eq!(
+        recovery_status.recovered_chunk_count,
+        recovery_status.chunk_count
+    );
+    assert_eq!(
+        recovery_status.processed_entry_count,
+        recovery_status.total_entry_count
+    );
+    assert_eq!(recovery_status.estimated_time_remaining_secs, Some(0.0));
+
+    let chunk_count = chunk_count.load(Ordering::SeqCst);
+    assert_eq!(chunk_count, recovery_status.chunk_count);
+    assert_eq!(recovered_chunk_count.load(Ordering::SeqCst), chunk_count);
+}
+
+/// Checks that recovery can be driven using the public API only, as it would be from another binary.
+#[tokio::test]
+async fn recovering_tree_with_externally_constructed_options() {
+    use crate::metadata_calculator::{recover_tree, RecoveryOptions, RecoveryReport};
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = mock_snapshot_recovery(root_hash);
+
+    let listener = Arc::new(RecoveredChunksListener::default());
+    let options = RecoveryOptions::new(4)
+        .concurrency(2)
+        .max_chunk_attempts(2)
+        .events(listener.clone());
+    let db = create_test_db(temp_dir.path().join("recovery")).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let report = recover_tree(
+        db,
+        MerkleTreeMode::Full,
+        &snapshot_recovery,
+        options,
+        &pool,
+        &stop_receiver,
+    )
+    .await
+    .unwrap();
+    assert_matches!(report, RecoveryReport::Recovered { chunks: 4, .. });
+    // The listener is shared with the recovery, so its state can be inspected afterwards.
+    assert_eq!(listener.chunk_count.load(Ordering::SeqCst), 4);
+    assert_eq!(listener.recovered_chunk_count.load(Ordering::SeqCst), 4);
+
+    let db = create_test_db(temp_dir.path().join("recovery")).await;
+    let tree = AsyncTree::new(db, MerkleTreeMode::Full);
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[tokio::test]
+async fn recovery_report_contains_chunk_timings_summary() {
+    use crate::metadata_calculator::{recover_tree, RecoveryOptions, RecoveryReport};
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = mock_snapshot_recovery(root_hash);
+
+    let db = create_test_db(temp_dir.path().join("recovery")).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let report = recover_tree(
+        db,
+        MerkleTreeMode::Full,
+        &snapshot_recovery,
+        RecoveryOptions::new(8).concurrency(3),
+        &pool,
+        &stop_receiver,
+    )
+    .await
+    .unwrap();
+
+    let RecoveryReport::Recovered { chunk_timings, .. } = report else {
+        panic!("unexpected report: {report:?}");
+    };
+    assert_eq!(chunk_timings.chunk_count, 8);
+    assert!(chunk_timings.byte_count > 0);
+    assert!(chunk_timings.avg_duration <= chunk_timings.slowest_chunk.unwrap().duration);
+    assert!(chunk_timings.p95_duration <= chunk_timings.slowest_chunk.unwrap().duration);
+    let summary = chunk_timings.to_string();
+    assert!(summary.contains("recovered 8 chunks"), "{summary}");
+
+    let report_json = serde_json::to_value(&report).unwrap();
+    assert_eq!(report_json["chunk_timings"]["chunk_count"], 8);
+}
+
+#[tokio::test]
+async fn throttled_recovery_respects_entry_rate_limit() {
+    use crate::metadata_calculator::{recover_tree, RecoveryOptions, RecoveryReport};
+
+    const MAX_ENTRIES_PER_SECOND: usize = 100;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = mock_snapshot_recovery(root_hash);
+
+    let db = create_test_db(temp_dir.path().join("recovery")).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let started_at = Instant::now();
+    let report = recover_tree(
+        db,
+        MerkleTreeMode::Full,
+        &snapshot_recovery,
+        RecoveryOptions::new(8)
+            .concurrency(2)
+            .max_entries_per_second(MAX_ENTRIES_PER_SECOND),
+        &pool,
+        &stop_receiver,
+    )
+    .await
+    .unwrap();
+    let elapsed = started_at.elapsed();
+
+    let RecoveryReport::Recovered { entries, .. } = report else {
+        panic!("unexpected report: {report:?}");
+    };
+    // Entries acquired within the burst tolerance and ones of the last chunk aren't waited for.
+    let expected_duration = Duration::from_secs_f64(entries as f64 / MAX_ENTRIES_PER_SECOND as f64);
+    let min_duration = expected_duration.saturating_sub(EntryThrottle::BURST_TOLERANCE) / 2;
+    assert!(
+        elapsed >= min_duration,
+        "elapsed: {elapsed:?}, expected: {expected_duration:?}"
+    );
+    assert!(
+        elapsed < expected_duration + Duration::from_secs(10),
+        "elapsed: {elapsed:?}, expected: {expected_duration:?}"
+    );
+}
+
+/// Extracts the tree from the output of [`GenericAsyncTree::ensure_ready()`], mapping interrupted recovery
+/// to [`RecoveryError::Interrupted`].
+fn into_tree(
+    (tree, report): (Option<AsyncTree>, RecoveryReport),
+) -> Result<AsyncTree, RecoveryError> {
+    tree.ok_or_else(|| {
+        assert_matches!(report, RecoveryReport::Interrupted { .. });
+        RecoveryError::Interrupted
+    })
+}
+
+async fn ensure_tree_ready(
+    db_path: PathBuf,
+    mode: MerkleTreeMode,
+    config: &MetadataCalculatorRecoveryConfig,
+    pool: &ConnectionPool,
+) -> Result<AsyncTree, RecoveryError> {
+    let db = create_test_db(db_path).await;
+    let tree = GenericAsyncTree::new(db, mode).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    tree.ensure_ready(
+        config,
+        EnsureReadyContext {
+            pool,
+            pools: RecoveryPools::default(),
+            snapshot_object_store: None,
+            stop_receiver: &stop_receiver,
+            health_updater: &health_updater,
+            recovery_status: &watch::channel(None).0,
+            recovery_listeners: Vec::new(),
+            overrides: RecoveryOverrides::default(),
+        },
+    )
+    .await
+    .and_then(into_tree)
+}
+
+#[tokio::test]
+async fn recovery_with_dedicated_hashing_threads() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let snapshot_root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(snapshot_root_hash))
+        .await
+        .unwrap();
+
+    let config = MetadataCalculatorRecoveryConfig {
+        hashing_threads: Some(4),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = ensure_tree_ready(tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), snapshot_root_hash);
+}
+
+#[tokio::test]
+async fn recovery_using_copy() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let snapshot_root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(snapshot_root_hash))
+        .await
+        .unwrap();
+
+    let config = MetadataCalculatorRecoveryConfig {
+        desired_chunk_size: 50,
+        use_copy: true,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = ensure_tree_ready(tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), snapshot_root_hash);
+}
+
+#[tokio::test]
+async fn recovery_with_fingerprint_log() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let snapshot_root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(snapshot_root_hash))
+        .await
+        .unwrap();
+
+    let config = MetadataCalculatorRecoveryConfig {
+        fingerprint_log: true,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree_path = temp_dir.path().join("recovery");
+    let fingerprint_log = RecoveryFingerprintLog::for_tree(&tree_path);
+    let tree = ensure_tree_ready(tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), snapshot_root_hash);
+
+    // The log must be archived after recovery is finalized.
+    assert!(!fingerprint_log.path().exists());
+    let fingerprints = fingerprint_log.read_archived().unwrap();
+    assert!(!fingerprints.is_empty());
+    let mut chunk_indices: Vec<_> = fingerprints.iter().map(|fp| fp.chunk_index).collect();
+    chunk_indices.sort_unstable();
+    assert_eq!(chunk_indices, (0..fingerprints.len()).collect::<Vec<_>>());
+    for fingerprint in &fingerprints {
+        assert_eq!(fingerprint.recovered_version, 1);
+        assert!(fingerprint.key_range_start <= fingerprint.key_range_end);
+    }
+    assert_eq!(fingerprints.last().unwrap().root_hash, snapshot_root_hash);
+
+    let dump: serde_json::Value =
+        serde_json::from_str(&fingerprint_log.dump_json().unwrap()).unwrap();
+    assert_eq!(dump["log"], serde_json::json!([]));
+    assert_eq!(
+        dump["archived_log"].as_array().unwrap().len(),
+        fingerprints.len()
+    );
+}
+
+#[tokio::test]
+async fn recovery_progress_is_recorded_in_postgres() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let snapshot_root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let mut storage = pool.access_storage().await.unwrap();
+    storage
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(snapshot_root_hash))
+        .await
+        .unwrap();
+    // Progress for an unrelated snapshot must be removed once recovery starts.
+    let stale_chunk = RecoveredTreeChunk {
+        chunk_id: 0,
+        key_range: H256::zero()..=H256::repeat_byte(0xff),
+        entry_count: Some(1),
+    };
+    storage
+        .tree_recovery_progress_dal()
+        .insert_recovered_chunks(L1BatchNumber(100), 1, &[stale_chunk])
+        .await
+        .unwrap();
+    drop(storage);
+
+    let config = MetadataCalculatorRecoveryConfig {
+        desired_chunk_size: 50,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = ensure_tree_ready(tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), snapshot_root_hash);
+    let leaf_count = tree.reader().info().await.leaf_count;
+
+    let mut storage = pool.access_storage().await.unwrap();
+    let mut dal = storage.tree_recovery_progress_dal();
+    assert_eq!(dal.get_summary(L1BatchNumber(100)).await.unwrap(), None);
+    let summary = dal.get_summary(L1BatchNumber(1)).await.unwrap().unwrap();
+    assert!(summary.is_finished, "{summary:?}");
+    assert!(summary.total_chunk_count > 1, "{summary:?}");
+    assert_eq!(summary.recovered_chunk_count, summary.total_chunk_count);
+    assert_eq!(summary.recovered_entry_count, leaf_count);
+
+    // Recorded chunks must match the chunk plan, i.e., cover the entire keyspace without gaps.
+    let chunks = dal.get_recovered_chunks(L1BatchNumber(1)).await.unwrap();
+    let chunk_ids: Vec<_> = chunks.iter().map(|chunk| chunk.chunk_id).collect();
+    assert_eq!(
+        chunk_ids,
+        (0..summary.total_chunk_count).collect::<Vec<_>>()
+    );
+    let key_ranges: Vec<_> = chunks.iter().map(|chunk| chunk.key_range.clone()).collect();
+    assert_contiguous_ranges(&key_ranges, chunks.len());
+    let entry_count: u64 = chunks.iter().map(|chunk| chunk.entry_count.unwrap()).sum();
+    assert_eq!(entry_count, leaf_count);
+}
+
+#[tokio::test]
+async fn lightweight_recovered_tree_is_upgraded_to_full_mode() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let snapshot_root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(snapshot_root_hash))
+        .await
+        .unwrap();
+
+    let config = MetadataCalculatorRecoveryConfig::default();
+    let full_tree_path = temp_dir.path().join("full");
+    let mut full_tree = ensure_tree_ready(full_tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    let lightweight_tree_path = temp_dir.path().join("lightweight");
+    let mut tree = ensure_tree_ready(
+        lightweight_tree_path.clone(),
+        MerkleTreeMode::Lightweight,
+        &config,
+        &pool,
+    )
+    .await
+    .unwrap();
+    assert_eq!(tree.root_hash(), full_tree.root_hash());
+    assert_eq!(tree.lightweight_since().unwrap(), None);
+
+    let mut storage = pool.access_storage().await.unwrap();
+    extend_db_state(&mut storage, gen_storage_logs(300..400, 3)).await;
+    let mut full_root_hashes = vec![];
+    for l1_batch_number in 2..=4 {
+        let l1_batch_number = L1BatchNumber(l1_batch_number);
+        let l1_batch = L1BatchWithLogs::new(&mut storage, l1_batch_number)
+            .await
+            .unwrap();
+        tree.process_l1_batch(l1_batch.storage_logs.clone()).await;
+        full_tree.process_l1_batch(l1_batch.storage_logs).await;
+        full_root_hashes.push(full_tree.root_hash());
+    }
+    drop(storage);
+    tree.save().await;
+    assert_eq!(tree.lightweight_since().unwrap(), Some(L1BatchNumber(2)));
+    assert_eq!(tree.root_hash(), full_tree.root_hash());
+    drop(tree);
+
+    // Without the backfill, the tree should continue in the full mode from the same spot.
+    let tree = ensure_tree_ready(
+        lightweight_tree_path.clone(),
+        MerkleTreeMode::Full,
+        &config,
+        &pool,
+    )
+    .await
+    .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(5));
+    assert_eq!(tree.root_hash(), full_tree.root_hash());
+    assert_eq!(tree.lightweight_since().unwrap(), Some(L1BatchNumber(2)));
+    drop(tree);
+
+    let config = MetadataCalculatorRecoveryConfig {
+        allow_lightweight_tree_upgrade: true,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let mut tree = ensure_tree_ready(lightweight_tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), snapshot_root_hash);
+    assert_eq!(tree.lightweight_since().unwrap(), None);
+
+    // Re-processing L1 batches in the full mode should produce the same root hashes as the natively full tree.
+    let mut storage = pool.access_storage().await.unwrap();
+    for (l1_batch_number, expected_root_hash) in (2..=4).zip(full_root_hashes) {
+        let l1_batch = L1BatchWithLogs::new(&mut storage, L1BatchNumber(l1_batch_number))
+            .await
+            .unwrap();
+        let metadata = tree.process_l1_batch(l1_batch.storage_logs).await;
+        assert!(metadata.witness.is_some());
+        assert_eq!(metadata.root_hash, expected_root_hash);
+    }
+}
+
+#[tokio::test]
+async fn ensure_ready_recovers_tree_to_configured_l1_batch() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let mut storage = pool.access_storage().await.unwrap();
+    storage
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    let genesis_root_hash = storage
+        .blocks_dal()
+        .get_l1_batch_state_root(L1BatchNumber(0))
+        .await
+        .unwrap()
+        .expect("no genesis root hash");
+    drop(storage);
+
+    let db = create_test_db(temp_dir.path().join("recovery")).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    // Recover to the genesis L1 batch rather than the latest snapshot L1 batch.
+    let config = MetadataCalculatorRecoveryConfig {
+        target_l1_batch: Some(L1BatchNumber(0)),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(1));
+    assert_eq!(tree.root_hash(), genesis_root_hash);
+}
+
+#[tokio::test]
+async fn ensure_ready_validates_configured_target_l1_batch() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+
+    // Postgres doesn't contain data for the target L1 batch.
+    let db = create_test_db(temp_dir.path().join("empty")).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let config = MetadataCalculatorRecoveryConfig {
+        target_l1_batch: Some(L1BatchNumber(5)),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let err = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap_err();
+    assert_matches!(err, RecoveryError::SnapshotMissing(_));
+
+    // The tree is already being recovered to another L1 batch.
+    let tree_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    tree.entry_source_kind(None).await.unwrap();
+    drop(tree);
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Recovering(_));
+    let config = MetadataCalculatorRecoveryConfig {
+        target_l1_batch: Some(L1BatchNumber(0)),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let err = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap_err();
+    assert_matches!(err, RecoveryError::Other(_));
+    let err = format!("{err:#}");
+    assert!(
+        err.contains("differs from the configured target L1 batch"),
+        "{err}"
+    );
+}
+
+#[tokio::test]
+async fn ensure_ready_rejects_target_l1_batch_without_full_state() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    // Add L1 batch #2 with metadata; its only miniblock contains just the storage logs written in it.
+    let mut storage = pool.access_storage().await.unwrap();
+    extend_db_state(&mut storage, gen_storage_logs(300..310, 1)).await;
+    drop(storage);
+    let (calculator, _) = setup_calculator(&temp_dir.path().join("init"), &pool).await;
+    run_calculator(calculator, pool.clone()).await;
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig {
+        target_l1_batch: Some(L1BatchNumber(2)),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let err = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap_err();
+    assert_matches!(
+        &err,
+        RecoveryError::InvalidSnapshotParameters {
+            l1_batch_number: L1BatchNumber(2),
+            ..
+        }
+    );
+    let err = err.to_string();
+    assert!(err.contains("don't contain the full state"), "{err}");
+
+    // The target is rejected before recovery is started.
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Empty { .. });
+}
+
+#[tokio::test]
+async fn tree_recovered_to_different_l1_batch_is_reset_if_allowed() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(5)).await;
+    tree.entry_source_kind(None).await.unwrap();
+    drop(tree);
+
+    let config = MetadataCalculatorRecoveryConfig {
+        allow_tree_reset: true,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree = ensure_tree_ready(tree_path.clone(), MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+    assert!(!wipe_path(&tree_path).exists());
+}
+
+/// Recovery listener replacing the snapshot in Postgres once the first chunk is started.
+#[derive(Debug)]
+struct SnapshotSwapper {
+    pool: ConnectionPool,
+    new_snapshot: StdMutex<Option<SnapshotRecoveryStatus>>,
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for SnapshotSwapper {
+    async fn chunk_started(&self, _chunk: &ChunkDescriptor) {
+        let new_snapshot = self.new_snapshot.lock().unwrap().take();
+        let Some(new_snapshot) = new_snapshot else {
+            return;
+        };
+        let mut storage = self.pool.access_storage().await.unwrap();
+        let mut transaction = storage.start_transaction().await.unwrap();
+        let mut dal = transaction.snapshot_recovery_dal();
+        dal.delete_applied_snapshot_status().await.unwrap();
+        dal.set_applied_snapshot_status(&new_snapshot)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn superseded_snapshot_is_replaced_during_recovery_if_configured() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let mut storage = pool.access_storage().await.unwrap();
+    let genesis_root_hash = storage
+        .blocks_dal()
+        .get_l1_batch_state_root(L1BatchNumber(0))
+        .await
+        .unwrap()
+        .expect("no genesis root hash");
+    // Start recovery from the genesis L1 batch, and replace the snapshot with a newer one during recovery.
+    let old_snapshot = SnapshotRecoveryStatus {
+        l1_batch_number: L1BatchNumber(0),
+        l1_batch_root_hash: genesis_root_hash,
+        miniblock_number: MiniblockNumber(0),
+        ..mock_snapshot_recovery(root_hash)
+    };
+    storage
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&old_snapshot)
+        .await
+        .unwrap();
+    drop(storage);
+
+    let db = create_test_db(temp_dir.path().join("recovery")).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let (recovery_status_sender, recovery_status) = watch::channel(None);
+    let listener = SnapshotSwapper {
+        pool: pool.clone(),
+        new_snapshot: StdMutex::new(Some(mock_snapshot_recovery(root_hash))),
+    };
+    let config = MetadataCalculatorRecoveryConfig {
+        desired_chunk_size: 5,
+        concurrency: Some(1),
+        prefer_latest_snapshot: true,
+        latest_snapshot_check_interval: Duration::ZERO,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &recovery_status_sender,
+                recovery_listeners: vec![Box::new(listener)],
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+    let recovery_status = recovery_status.borrow().clone().unwrap();
+    assert_eq!(recovery_status.snapshot_l1_batch, L1BatchNumber(1));
+}
+
+/// Recovery listener changing snapshot data in Postgres once all chunks are recovered, i.e. before
+/// the recovered tree is finalized.
+#[derive(Debug)]
+struct SnapshotMutator {
+    pool: ConnectionPool,
+    change_log_count: bool,
+    chunk_count: AtomicUsize,
+    recovered_chunk_count: AtomicUsize,
+}
+
+impl SnapshotMutator {
+    fn new(pool: ConnectionPool, change_log_count: bool) -> Self {
+        Self {
+            pool,
+            change_log_count,
+            chunk_count: AtomicUsize::new(0),
+            recovered_chunk_count: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for SnapshotMutator {
+    fn recovery_started(
+        &self,
+        chunk_count: usize,
+        recovered_chunk_count: usize,
+        _recovered_entry_count: u64,
+    ) {
+        self.chunk_count.store(chunk_count, Ordering::SeqCst);
+        self.recovered_chunk_count
+            .store(recovered_chunk_count, Ordering::SeqCst);
+    }
+
+    async fn chunk_recovered(&self, _chunk: &ChunkDescriptor) {
+        let recovered_chunk_count = self.recovered_chunk_count.fetch_add(1, Ordering::SeqCst) + 1;
+        if recovered_chunk_count < self.chunk_count.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let mut storage = self.pool.access_storage().await.unwrap();
+        if self.change_log_count {
+            let key = StorageKey::new(AccountTreeId::new(Address::repeat_byte(0xff)), H256::zero());
+            let log = StorageLog::new_write_log(key, H256::repeat_byte(0xff));
+            storage
+                .storage_logs_dal()
+                .insert_storage_logs(MiniblockNumber(1), &[(H256::zero(), vec![log])])
+                .await;
+        } else {
+            let mut snapshot_recovery = storage
+                .snapshot_recovery_dal()
+                .get_applied_snapshot_status()
+                .await
+                .unwrap()
+                .expect("no snapshot recovery");
+            // Emulate the snapshot L1 batch being re-executed after a Postgres rollback.
+            snapshot_recovery.l1_batch_root_hash = H256::repeat_byte(0xff);
+            storage
+                .snapshot_recovery_dal()
+                .set_applied_snapshot_status(&snapshot_recovery)
+                .await
+                .unwrap();
+        }
+    }
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn snapshot_changed_during_recovery_is_detected_before_finalization(change_log_count: bool) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+
+    let tree_path = temp_dir.path().join("recovery");
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let listener = SnapshotMutator::new(pool.clone(), change_log_count);
+    let config = MetadataCalculatorRecoveryConfig {
+        desired_chunk_size: 50,
+        concurrency: Some(1),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let err = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: vec![Box::new(listener)],
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap_err();
+    assert_eq!(err.kind(), RecoveryErrorKind::SnapshotChanged);
+    let err = err.to_string();
+    if change_log_count {
+        assert!(err.contains("number of storage logs"), "{err}");
+    } else {
+        assert!(err.contains("expected root hash has changed"), "{err}");
+    }
+    assert!(err.contains("rollback"), "{err}");
+
+    let health = health_check.check_health().await;
+    assert_matches!(health.status(), HealthStatus::NotReady);
+    assert_eq!(health.details().unwrap()["error_kind"], "snapshot_changed");
+    // The tree must not be finalized.
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Recovering(_));
+}
+
+#[tokio::test]
+async fn tree_recovered_to_older_snapshot_is_reset_if_latest_snapshot_is_preferred() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(0)).await;
+    tree.entry_source_kind(None).await.unwrap();
+    drop(tree);
+
+    let config = MetadataCalculatorRecoveryConfig {
+        prefer_latest_snapshot: true,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree = ensure_tree_ready(tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+/// Snapshot for the L1 batch following the one in [`mock_snapshot_recovery()`]. Data for the snapshot isn't present
+/// in Postgres; it's never used by the tree in tests.
+fn mock_newer_snapshot_recovery(root_hash: H256) -> SnapshotRecoveryStatus {
+    SnapshotRecoveryStatus {
+        l1_batch_number: L1BatchNumber(2),
+        l1_batch_root_hash: root_hash,
+        miniblock_number: MiniblockNumber(2),
+        miniblock_root_hash: H256::zero(),
+        last_finished_chunk_id: Some(0),
+        total_chunk_count: 1,
+    }
+}
+
+#[tokio::test]
+async fn recovery_falls_back_to_previous_snapshot_without_l1_batch_metadata() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+    // The newer snapshot lacks L1 batch metadata.
+    set_snapshot_recovery(&pool, &mock_newer_snapshot_recovery(H256::zero())).await;
+
+    let config = MetadataCalculatorRecoveryConfig::default();
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = ensure_tree_ready(tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[tokio::test]
+async fn resumed_recovery_uses_same_snapshot_after_newer_snapshot_gets_metadata() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+
+    // Emulate recovery started from the previous snapshot because the newer snapshot lacked metadata.
+    let tree_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    tree.entry_source_kind(None).await.unwrap();
+    drop(tree);
+    set_snapshot_recovery(&pool, &mock_newer_snapshot_recovery(H256::repeat_byte(2))).await;
+
+    let config = MetadataCalculatorRecoveryConfig::default();
+    let tree = ensure_tree_ready(tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+async fn create_tree_with_data(path: PathBuf) -> GenericAsyncTree {
+    let mut tree = create_tree_recovery(path.clone(), L1BatchNumber(1)).await;
+    tree.extend(vec![TreeEntry::new(U256::one(), 1, H256::repeat_byte(1))])
+        .await
+        .unwrap();
+    drop(tree);
+    let db = create_test_db(path).await;
+    GenericAsyncTree::new(db, MerkleTreeMode::Full).await
+}
+
+#[tokio::test]
+async fn resetting_tree_removes_its_data() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let tree_path = temp_dir.path().join("tree");
+    let tree = create_tree_with_data(tree_path.clone()).await;
+    assert_matches!(tree, GenericAsyncTree::Recovering(_));
+
+    let tree = tree.reset().await.unwrap();
+    assert_matches!(
+        tree,
+        GenericAsyncTree::Empty {
+            mode: MerkleTreeMode::Full,
+            ..
+        }
+    );
+    assert!(tree_path.is_dir());
+    assert!(!wipe_path(&tree_path).exists());
+    drop(tree);
+
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Empty { .. });
+    // Resetting an empty tree is a no-op.
+    let tree = tree.reset().await.unwrap();
+    assert_matches!(tree, GenericAsyncTree::Empty { .. });
+}
+
+#[tokio::test]
+async fn interrupted_tree_reset_is_finished_on_startup() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let tree_path = temp_dir.path().join("tree");
+    let tree = create_tree_with_data(tree_path.clone()).await;
+    drop(tree);
+
+    // Emulate the node crashing after the tree directory was renamed, but before it was removed.
+    let wipe_path = wipe_path(&tree_path);
+    fs::rename(&tree_path, &wipe_path).unwrap();
+
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Empty { .. });
+    assert!(!wipe_path.exists());
+
+    drop(tree);
+
+    // Emulate the node crashing while removing the renamed directory; the tree must not be affected by
+    // the remaining data.
+    let tree = create_tree_with_data(tree_path.clone()).await;
+    drop(tree);
+    fs::create_dir(&wipe_path).unwrap();
+    fs::write(wipe_path.join("CURRENT"), "garbage").unwrap();
+
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Recovering(_));
+    assert!(!wipe_path.exists());
+    let tree = tree.reset().await.unwrap();
+    assert_matches!(tree, GenericAsyncTree::Empty { .. });
+}
+
+async fn prepare_object_store_snapshot(
+    pool: &ConnectionPool,
+    snapshot_recovery: &SnapshotRecoveryStatus,
+) -> Box<dyn ObjectStore> {
+    let object_store = ObjectStoreFactory::mock().create_store().await;
+    let mut storage = pool.access_storage().await.unwrap();
+    let chunk_count = snapshot_recovery.total_chunk_count as usize;
+    for (chunk_id, key_range) in AsyncTreeRecovery::hashed_key_ranges(chunk_count).enumerate() {
+        let storage_logs = storage
+            .snapshots_creator_dal()
+            .get_storage_logs_chunk(snapshot_recovery.miniblock_number, key_range)
+            .await
+            .unwrap();
+        let storage_key = SnapshotStorageLogsStorageKey {
+            l1_batch_number: snapshot_recovery.l1_batch_number,
+            chunk_id: chunk_id as u64,
+        };
+        let chunk = SnapshotStorageLogsChunk { storage_logs };
+        object_store.put(storage_key, &chunk).await.unwrap();
+    }
+    object_store
+}
+
+#[test_casing(3, [1, 4, 16])]
+#[tokio::test]
+async fn ensure_ready_recovers_tree_from_object_store(chunk_count: u64) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = SnapshotRecoveryStatus {
+        last_finished_chunk_id: Some(chunk_count - 1),
+        total_chunk_count: chunk_count,
+        ..mock_snapshot_recovery(root_hash)
+    };
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&snapshot_recovery)
+        .await
+        .unwrap();
+    let object_store = prepare_object_store_snapshot(&pool, &snapshot_recovery).await;
+
+    let db = create_test_db(temp_dir.path().join("recovery")).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig::default();
+    let tree = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: Some(object_store.as_ref()),
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[tokio::test]
+async fn recovery_from_object_store_cannot_be_resumed_without_object_store() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+
+    let mut tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let kind = tree
+        .entry_source_kind(Some(RecoveryEntrySourceKind::ObjectStore))
+        .await
+        .unwrap();
+    assert_eq!(kind, RecoveryEntrySourceKind::ObjectStore);
+    // Emulate a restart without the object store.
+    let kind = tree.entry_source_kind(None).await.unwrap();
+    assert_eq!(kind, RecoveryEntrySourceKind::ObjectStore);
+
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    let config = MetadataCalculatorRecoveryConfig::default();
+    let err = tree
+        .entry_source(
+            &config,
+            &snapshot,
+            &mock_snapshot_recovery(root_hash),
+            &pool,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("no snapshot object store"), "{err}");
+}
+
+#[tokio::test]
+async fn recovering_tree_from_snapshot_file() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = mock_snapshot_recovery(root_hash);
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&snapshot_recovery)
+        .await
+        .unwrap();
+    let file_path = temp_dir.path().join("snapshot.bin");
+    let header = write_snapshot_file(&pool, &snapshot_recovery, &file_path, 3)
+        .await
+        .unwrap();
+    assert_eq!(header.l1_batch_number, L1BatchNumber(1));
+    assert_eq!(header.root_hash, root_hash);
+
+    let tree_path = temp_dir.path().join("recovery");
+    let config = MetadataCalculatorRecoveryConfig {
+        desired_chunk_size: 50,
+        recovery_file: Some(file_path),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree = ensure_tree_ready(tree_path.clone(), MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[tokio::test]
+async fn snapshot_file_for_different_snapshot_is_rejected() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = mock_snapshot_recovery(root_hash);
+    let file_path = temp_dir.path().join("snapshot.bin");
+    let wrong_snapshot = SnapshotRecoveryStatus {
+        l1_batch_root_hash: H256::repeat_byte(0x23),
+        ..snapshot_recovery.clone()
+    };
+    write_snapshot_file(&pool, &wrong_snapshot, &file_path, 1)
+        .await
+        .unwrap();
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&snapshot_recovery)
+        .await
+        .unwrap();
+
+    let config = MetadataCalculatorRecoveryConfig {
+        recovery_file: Some(file_path),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree_path = temp_dir.path().join("recovery");
+    let err = ensure_tree_ready(tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), RecoveryErrorKind::InvalidSnapshotParameters);
+}
+
+#[tokio::test]
+async fn object_store_chunk_with_out_of_range_key_is_rejected() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = SnapshotRecoveryStatus {
+        last_finished_chunk_id: Some(1),
+        total_chunk_count: 2,
+        ..mock_snapshot_recovery(root_hash)
+    };
+    let object_store = prepare_object_store_snapshot(&pool, &snapshot_recovery).await;
+    let entry_source = ObjectStoreEntrySource {
+        object_store: object_store.as_ref(),
+        l1_batch_number: snapshot_recovery.l1_batch_number,
+        chunk_count: 2,
+    };
+    let key_chunks = entry_source.key_chunks(2).await.unwrap();
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let entries = entry_source
+        .load_entries(0, &key_chunks[0], &stop_receiver)
+        .await
+        .unwrap()
+        .expect("loading entries was interrupted");
+    assert!(!entries.is_empty());
+    assert!(entries
+        .windows(2)
+        .all(|window| window[0].key < window[1].key));
+
+    // Chunk 1 is requested with the key range of chunk 0.
+    let err = entry_source
+        .load_entries(1, &key_chunks[0], &stop_receiver)
+        .await
+        .unwrap_err();
+    assert_matches!(
+        RecoveryError::from(err),
+        RecoveryError::CorruptedSnapshot { key, .. } if !key_chunks[0].contains(&key)
+    );
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn recovery_with_dry_run(stop_after_dry_run: bool) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig {
+        dry_run: true,
+        stop_after_dry_run,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let result = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree);
+
+    if stop_after_dry_run {
+        assert_matches!(result, Err(RecoveryError::Interrupted));
+        let health = health_check.check_health().await;
+        assert_eq!(health.details().unwrap()["mode"], "recovery_dry_run");
+        // The production tree must not be touched.
+        let db = create_test_db(tree_path).await;
+        let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+        assert_matches!(tree, GenericAsyncTree::Empty { .. });
+    } else {
+        let tree = result.unwrap();
+        assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+        assert_eq!(tree.root_hash(), root_hash);
+        let health = health_check.check_health().await;
+        assert_eq!(health.details().unwrap()["mode"], "recovery");
+    }
+}
+
+#[tokio::test]
+async fn recovery_detects_skipped_leaf_index() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+
+    // Add 2 initial writes and a snapshot storage log for the second one, so that the leaf index
+    // of the first initial write is skipped in the snapshot.
+    let mut storage = pool.access_storage().await.unwrap();
+    let account = AccountTreeId::new(Address::repeat_byte(0xaa));
+    let keys = [
+        StorageKey::new(account, H256::repeat_byte(1)),
+        StorageKey::new(account, H256::repeat_byte(2)),
+    ];
+    storage
+        .storage_logs_dedup_dal()
+        .insert_initial_writes(L1BatchNumber(1), &keys)
+        .await;
+    let log = StorageLog::new_write_log(keys[1], H256::repeat_byte(0xff));
+    storage
+        .storage_logs_dal()
+        .append_storage_logs(MiniblockNumber(1), &[(H256::zero(), vec![log])])
+        .await;
+    storage
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    let log_count = storage
+        .storage_logs_dal()
+        .count_miniblock_storage_logs(MiniblockNumber(1))
+        .await
+        .unwrap();
+    drop(storage);
+
+    let tree_path = temp_dir.path().join("recovery");
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let err = tree
+        .ensure_ready(
+            &MetadataCalculatorRecoveryConfig::default(),
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap_err();
+
+    // The skipped leaf index is detected using leaf index stats collected for recovered chunks.
+    assert_matches!(
+        &err,
+        RecoveryError::ChunkLeafIndexMismatch { miniblock, .. } if *miniblock == MiniblockNumber(1)
+    );
+    assert_eq!(err.kind(), RecoveryErrorKind::LeafIndexMismatch);
+    let err = err.to_string();
+    assert!(
+        err.contains(&format!(
+            "leaf indices outside 1..={log_count} in 1 chunk(s)"
+        )),
+        "{err}"
+    );
+    assert!(
+        err.contains(&format!(
+            "maximum leaf index is {} rather than {log_count}",
+            log_count + 1
+        )),
+        "{err}"
+    );
+}
+
+async fn assert_invalid_snapshot_parameters(
+    pool: &ConnectionPool,
+    snapshot_recovery: &SnapshotRecoveryStatus,
+    expected_details: &str,
+) {
+    let err = SnapshotParameters::new(pool, None, snapshot_recovery)
+        .await
+        .unwrap_err();
+    let err = RecoveryError::from(err);
+    assert_eq!(err.kind(), RecoveryErrorKind::InvalidSnapshotParameters);
+    let err = err.to_string();
+    assert!(err.contains(expected_details), "{err}");
+}
+
+/// Inserts a miniblock with the specified number, copying other header fields from miniblock #1.
+async fn insert_miniblock_copy(pool: &ConnectionPool, number: MiniblockNumber) {
+    let mut storage = pool.access_storage().await.unwrap();
+    let mut header = storage
+        .blocks_dal()
+        .get_miniblock_header(MiniblockNumber(1))
+        .await
+        .unwrap()
+        .expect("no miniblock #1");
+    header.number = number;
+    header.hash = H256::from_low_u64_be(number.0.into());
+    storage
+        .blocks_dal()
+        .insert_miniblock(&header)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn snapshot_parameters_with_zero_root_hash_are_rejected() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    prepare_recovery_snapshot(&pool, &temp_dir).await;
+
+    let snapshot_recovery = mock_snapshot_recovery(H256::zero());
+    assert_invalid_snapshot_parameters(&pool, &snapshot_recovery, "expected root hash is zero")
+        .await;
+}
+
+#[tokio::test]
+async fn snapshot_parameters_with_missing_miniblock_are_rejected() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+
+    let snapshot_recovery = SnapshotRecoveryStatus {
+        miniblock_number: MiniblockNumber(5),
+        ..mock_snapshot_recovery(root_hash)
+    };
+    assert_invalid_snapshot_parameters(
+        &pool,
+        &snapshot_recovery,
+        "snapshot miniblock #5 is not present in Postgres",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn snapshot_parameters_with_miniblock_from_another_batch_are_rejected() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let mut storage = pool.access_storage().await.unwrap();
+    extend_db_state(&mut storage, gen_storage_logs(300..310, 1)).await;
+    drop(storage);
+
+    let snapshot_recovery = SnapshotRecoveryStatus {
+        miniblock_number: MiniblockNumber(2),
+        ..mock_snapshot_recovery(root_hash)
+    };
+    assert_invalid_snapshot_parameters(
+        &pool,
+        &snapshot_recovery,
+        "snapshot miniblock #2 belongs to L1 batch #2",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn snapshot_parameters_with_pending_miniblock_are_rejected() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    insert_miniblock_copy(&pool, MiniblockNumber(2)).await;
+
+    let snapshot_recovery = SnapshotRecoveryStatus {
+        miniblock_number: MiniblockNumber(2),
+        ..mock_snapshot_recovery(root_hash)
+    };
+    assert_invalid_snapshot_parameters(
+        &pool,
+        &snapshot_recovery,
+        "snapshot miniblock #2 belongs to no L1 batch",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn snapshot_parameters_with_non_last_miniblock_are_rejected() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    // Doctor the DB so that miniblock #2 is the last miniblock in L1 batch #1.
+    insert_miniblock_copy(&pool, MiniblockNumber(2)).await;
+    pool.access_storage()
+        .await
+        .unwrap()
+        .blocks_dal()
+        .mark_miniblocks_as_executed_in_l1_batch(L1BatchNumber(1))
+        .await
+        .unwrap();
+
+    let snapshot_recovery = mock_snapshot_recovery(root_hash);
+    assert_invalid_snapshot_parameters(
+        &pool,
+        &snapshot_recovery,
+        "snapshot miniblock #1 is not the last miniblock in the snapshot L1 batch (the last one is #2)",
+    )
+    .await;
+}
+
+/// Default desired recovery chunk size.
+const DESIRED_CHUNK_SIZE: u64 = 200_000;
+
+#[test]
+fn snapshot_parameters_with_zero_log_count_are_rejected() {
+    let snapshot_recovery = mock_snapshot_recovery(H256::repeat_byte(1));
+    let err = SnapshotParameters::with_log_count(&snapshot_recovery, 0).unwrap_err();
+    assert_eq!(err.kind(), RecoveryErrorKind::InvalidSnapshotParameters);
+    let err = err.to_string();
+    assert!(
+        err.contains("snapshot miniblock #1 has no storage logs in Postgres"),
+        "{err}"
+    );
+}
+
+#[test_casing(4, [(1, 1), (DESIRED_CHUNK_SIZE - 1, 1), (DESIRED_CHUNK_SIZE, 1), (DESIRED_CHUNK_SIZE + 1, 2)])]
+#[test]
+fn chunk_count_for_snapshot_parameters(log_count: u64, expected_chunk_count: usize) {
+    let snapshot_recovery = mock_snapshot_recovery(H256::repeat_byte(1));
+    let snapshot = SnapshotParameters::with_log_count(&snapshot_recovery, log_count).unwrap();
+    let config = MetadataCalculatorRecoveryConfig::default();
+    assert_eq!(config.desired_chunk_size, DESIRED_CHUNK_SIZE);
+    let chunk_count = snapshot.chunk_count_for_size(config.desired_chunk_size);
+    assert_eq!(chunk_count, expected_chunk_count);
+    let ranges: Vec<_> = AsyncTreeRecovery::hashed_key_ranges(chunk_count).collect();
+    assert_contiguous_ranges(&ranges, chunk_count);
+}
+
+#[tokio::test]
+async fn dry_run_recovery_detects_root_hash_mismatch() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    prepare_recovery_snapshot(&pool, &temp_dir).await;
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&mock_snapshot_recovery(H256::repeat_byte(1)))
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig {
+        dry_run: true,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let err = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap_err();
+    assert_matches!(
+        err,
+        RecoveryError::RootHashMismatch { expected, .. } if expected == H256::repeat_byte(1)
+    );
+    let health = health_check.check_health().await;
+    assert_matches!(health.status(), HealthStatus::NotReady);
+    let details = health.details().unwrap();
+    assert_eq!(details["mode"], "recovery_dry_run");
+    assert_eq!(details["error_kind"], "root_hash_mismatch");
+
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Empty { .. });
+}
+
+#[tokio::test]
+async fn ensure_ready_without_snapshot() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+
+    let db = create_test_db(temp_dir.path().join("tree")).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig::default();
+    let tree = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap();
+    assert!(tree.is_empty());
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(0));
+}
+
+/// Polls `health_check` until it reports that the tree waits for the snapshot to be applied.
+async fn wait_for_snapshot_health(health_check: &ReactiveHealthCheck) -> Health {
+    loop {
+        let health = health_check.check_health().await;
+        let is_waiting = health
+            .details()
+            .map_or(false, |details| details["mode"] == "waiting_for_snapshot");
+        if is_waiting {
+            return health;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+#[tokio::test]
+async fn ensure_ready_waits_for_partially_applied_snapshot() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = SnapshotRecoveryStatus {
+        last_finished_chunk_id: Some(1),
+        total_chunk_count: 3,
+        ..mock_snapshot_recovery(root_hash)
+    };
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&snapshot_recovery)
+        .await
+        .unwrap();
+
+    let db = create_test_db(temp_dir.path().join("recovery")).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig {
+        snapshot_poll_interval: Duration::from_millis(10),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let recovery_task = tree.ensure_ready(
+        &config,
+        EnsureReadyContext {
+            pool: &pool,
+            pools: RecoveryPools::default(),
+            snapshot_object_store: None,
+            stop_receiver: &stop_receiver,
+            health_updater: &health_updater,
+            recovery_status: &watch::channel(None).0,
+            recovery_listeners: Vec::new(),
+            overrides: RecoveryOverrides::default(),
+        },
+    );
+    let stop_task = async {
+        let health = wait_for_snapshot_health(&health_check).await;
+        assert_matches!(health.status(), HealthStatus::NotReady);
+        let details = health.details().unwrap();
+        assert_eq!(details["mode"], "waiting_for_snapshot");
+        assert_eq!(details["last_finished_chunk_id"], 1);
+        assert_eq!(details["total_chunk_count"], 3);
+        stop_sender.send_replace(true);
+    };
+    let (result, ()) = tokio::join!(recovery_task, stop_task);
+    let (tree, report) = result.unwrap();
+    assert!(tree.is_none());
+    assert_eq!(
+        report,
+        RecoveryReport::Interrupted {
+            recovered_chunks: 0,
+            total_chunks: 0,
+        }
+    );
+}
+
+#[tokio::test]
+async fn ensure_ready_recovers_tree_after_snapshot_is_applied() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = SnapshotRecoveryStatus {
+        last_finished_chunk_id: None,
+        ..mock_snapshot_recovery(root_hash)
+    };
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&snapshot_recovery)
+        .await
+        .unwrap();
+
+    let db = create_test_db(temp_dir.path().join("recovery")).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig {
+        snapshot_poll_interval: Duration::from_millis(10),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let recovery_task = tree.ensure_ready(
+        &config,
+        EnsureReadyContext {
+            pool: &pool,
+            pools: RecoveryPools::default(),
+            snapshot_object_store: None,
+            stop_receiver: &stop_receiver,
+            health_updater: &health_updater,
+            recovery_status: &watch::channel(None).0,
+            recovery_listeners: Vec::new(),
+            overrides: RecoveryOverrides::default(),
+        },
+    );
+    // Simulate the snapshot applier finishing the last chunk after a delay.
+    let applier_task = async {
+        wait_for_snapshot_health(&health_check).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let snapshot_recovery = SnapshotRecoveryStatus {
+            last_finished_chunk_id: Some(0),
+            ..snapshot_recovery
+        };
+        pool.access_storage()
+            .await
+            .unwrap()
+            .snapshot_recovery_dal()
+            .set_applied_snapshot_status(&snapshot_recovery)
+            .await
+            .unwrap();
+    };
+    let (result, ()) = tokio::join!(recovery_task, applier_task);
+    let tree = into_tree(result.unwrap()).unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+}
+
+async fn prepare_recovery_snapshot(pool: &ConnectionPool, temp_dir: &TempDir) -> H256 {
+    prepare_recovery_snapshot_for_chain(pool, temp_dir, L2ChainId::from(270)).await
+}
+
+async fn prepare_recovery_snapshot_for_chain(
+    pool: &ConnectionPool,
+    temp_dir: &TempDir,
+    chain_id: L2ChainId,
+) -> H256 {
+    let mut storage = pool.access_storage().await.unwrap();
+    ensure_genesis_state(&mut storage, chain_id, &GenesisParams::mock())
+        .await
+        .unwrap();
+    let mut logs = gen_storage_logs(100..300, 1).pop().unwrap();
+
+    // Add all logs from the genesis L1 batch to `logs` so that they cover all state keys.
+    let genesis_logs = storage
+        .storage_logs_dal()
+        .get_touched_slots_for_l1_batch(L1BatchNumber(0))
+        .await;
+    let genesis_logs = genesis_logs
+        .into_iter()
+        .map(|(key, value)| StorageLog::new_write_log(key, value));
+    logs.extend(genesis_logs);
+    extend_db_state(&mut storage, vec![logs]).await;
+    drop(storage);
+
+    // Ensure that metadata for L1 batch #1 is present in the DB.
+    let (calculator, _) = setup_calculator(&temp_dir.path().join("init"), pool).await;
+    run_calculator(calculator, pool.clone()).await
+}
+
+#[derive(Debug)]
+struct TestEventListener {
+    expected_recovered_chunks: usize,
+    stop_at_chunk: Option<usize>,
+    stop_sender: watch::Sender<bool>,
+}
+
+impl TestEventListener {
+    fn new(stop_sender: watch::Sender<bool>) -> Self {
+        Self {
+            expected_recovered_chunks: 0,
+            stop_at_chunk: None,
+            stop_sender,
+        }
+    }
+
+    fn expect_recovered_chunks(mut self, count: usize) -> Self {
+        self.expected_recovered_chunks = count;
+        self
+    }
+
+    /// Sends a stop signal once the chunk with the specified index is recovered.
+    fn stop_at_chunk(mut self, index: usize) -> Self {
+        self.stop_at_chunk = Some(index);
+        self
+    }
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for TestEventListener {
+    fn recovery_started(
+        &self,
+        _chunk_count: usize,
+        recovered_chunk_count: usize,
+        _recovered_entry_count: u64,
+    ) {
+        assert_eq!(recovered_chunk_count, self.expected_recovered_chunks);
+    }
+
+    async fn chunk_recovered(&self, chunk: &ChunkDescriptor) {
+        assert!(chunk.entry_count.is_some(), "{chunk:?}");
+        if self.stop_at_chunk == Some(chunk.index) {
+            self.stop_sender.send_replace(true);
+        }
+    }
+}
+
+/// Wrapper around [`RecoveryHealthUpdater`] and [`TestEventListener`] recording health right after
+/// recovery is started, i.e., before any new chunks are recovered.
+#[derive(Debug)]
+struct StartedHealthRecorder<'a> {
+    inner: RecoveryHealthUpdater<'a>,
+    listener: TestEventListener,
+    health_check: ReactiveHealthCheck,
+    started_health: &'a StdMutex<Option<Health>>,
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for StartedHealthRecorder<'_> {
+    fn recovery_started(
+        &self,
+        chunk_count: usize,
+        recovered_chunk_count: usize,
+        recovered_entry_count: u64,
+    ) {
+        self.inner
+            .recovery_started(chunk_count, recovered_chunk_count, recovered_entry_count);
+        self.listener
+            .recovery_started(chunk_count, recovered_chunk_count, recovered_entry_count);
+        let health = self
+            .health_check
+            .check_health()
+            .now_or_never()
+            .expect("health check is not ready");
+        *self.started_health.lock().unwrap() = Some(health);
+    }
+
+    async fn chunk_recovered(&self, chunk: &ChunkDescriptor) {
+        self.inner.chunk_recovered(chunk).await;
+        self.listener.chunk_recovered(chunk).await;
+    }
+}
+
+#[tokio::test]
+async fn chunk_size_is_persisted_across_restarts() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let desired_chunk_size = tree.desired_chunk_size(50).await.unwrap();
+    assert_eq!(desired_chunk_size, 50);
+    let chunk_count = snapshot.chunk_count_for_size(desired_chunk_size);
+    assert!(chunk_count > 2);
+
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count,
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            TestEventListener::new(stop_sender).stop_at_chunk(1),
+        )
+    };
+    let result = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await;
+    assert_matches!(result, Err(RecoveryError::Interrupted));
+
+    // Emulate a restart with a changed config value; the persisted chunk size must be used.
+    let mut tree = create_tree_recovery(tree_path, L1BatchNumber(1)).await;
+    let desired_chunk_size = tree.desired_chunk_size(30).await.unwrap();
+    assert_eq!(desired_chunk_size, 50);
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: snapshot.chunk_count_for_size(desired_chunk_size),
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            TestEventListener::new(stop_sender).expect_recovered_chunks(2),
+        )
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn chunk_plan_is_validated_on_resume(force_replan: bool) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = mock_snapshot_recovery(root_hash);
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&snapshot_recovery)
+        .await
+        .unwrap();
+    let snapshot = SnapshotParameters::new(&pool, None, &snapshot_recovery)
+        .await
+        .unwrap();
+
+    // Partially recover the tree with the plan for a different number of snapshot logs.
+    let tree_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let desired_chunk_size = tree.desired_chunk_size(50).await.unwrap();
+    let stale_plan = ChunkPlan {
+        log_count: snapshot.log_count + 100,
+        chunk_count: snapshot.chunk_count_for_size(desired_chunk_size) + 2,
+    };
+    tree.check_chunk_plan(stale_plan).await.unwrap();
+    // The persisted plan should be accepted.
+    tree.check_chunk_plan(stale_plan).await.unwrap();
+
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: stale_plan.chunk_count,
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            TestEventListener::new(stop_sender).stop_at_chunk(1),
+        )
+    };
+    let result = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await;
+    assert_matches!(result, Err(RecoveryError::Interrupted));
+
+    // Emulate a restart with the changed number of snapshot logs.
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Recovering(_));
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig {
+        desired_chunk_size: 50,
+        force_replan,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let result = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree);
+
+    if force_replan {
+        let tree = result.unwrap();
+        assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+        assert_eq!(tree.root_hash(), root_hash);
+    } else {
+        let err = format!("{:#}", result.unwrap_err());
+        assert!(err.contains("differs from the current plan"), "{err}");
+        assert!(err.contains("force_replan"), "{err}");
+    }
+}
+
+#[test_casing(2, ["garbage", r#"{"hasher":"blake2s256","entry_encoding":0}"#])]
+#[tokio::test]
+async fn resuming_recovery_with_incompatible_format_fails_early(persisted_format: &str) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+
+    // Emulate recovery started by a binary with another recovery format.
+    let tree_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let persisted_format = persisted_format.to_owned();
+    tree.update_custom_tags(move |tags| {
+        tags.insert(AsyncTreeRecovery::FORMAT_TAG.to_owned(), persisted_format);
+    })
+    .await;
+    drop(tree);
+
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let err = tree
+        .ensure_ready(
+            &MetadataCalculatorRecoveryConfig::default(),
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("recovery format"), "{err}");
+    assert!(err.contains("remove the tree"), "{err}");
+
+    // The persisted format must not be overwritten.
+    let db = create_test_db(tree_path).await;
+    let GenericAsyncTree::Recovering(mut tree) =
+        GenericAsyncTree::new(db, MerkleTreeMode::Full).await
+    else {
+        panic!("tree is not recovering");
+    };
+    let tags = tree.custom_tags().await;
+    assert_ne!(
+        tags[AsyncTreeRecovery::FORMAT_TAG],
+        serde_json::to_string(&RecoveryFormat::current()).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn tree_state_is_published_on_start() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+
+    let tree_path = temp_dir.path().join("recovery");
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Lightweight).await;
+    let mode = MerkleTreeMode::Lightweight;
+    assert_eq!(tree.state(), TreeState::Empty { mode });
+
+    // Start recovery and immediately interrupt it.
+    let (_stop_sender, stop_receiver) = watch::channel(true);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig::default();
+    let result = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await;
+    let (tree, report) = result.unwrap();
+    assert!(tree.is_none());
+    assert_eq!(
+        report,
+        RecoveryReport::Interrupted {
+            recovered_chunks: 0,
+            total_chunks: 0,
+        }
+    );
+
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Lightweight).await;
+    let expected_state = TreeState::Recovering {
+        recovered_version: 1,
+        mode,
+    };
+    assert_eq!(tree.state(), expected_state);
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    tree.ensure_ready(
+        &config,
+        EnsureReadyContext {
+            pool: &pool,
+            pools: RecoveryPools::default(),
+            snapshot_object_store: None,
+            stop_receiver: &stop_receiver,
+            health_updater: &health_updater,
+            recovery_status: &watch::channel(None).0,
+            recovery_listeners: Vec::new(),
+            overrides: RecoveryOverrides::default(),
+        },
+    )
+    .await
+    .and_then(into_tree)
+    .unwrap();
+
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Lightweight).await;
+    let expected_state = TreeState::Ready {
+        next_l1_batch_number: L1BatchNumber(2),
+        mode,
+    };
+    assert_eq!(tree.state(), expected_state);
+    assert_eq!(
+        expected_state.to_string(),
+        "ready with next L1 batch #2 (Lightweight mode)"
+    );
+    tree.ensure_ready(
+        &config,
+        EnsureReadyContext {
+            pool: &pool,
+            pools: RecoveryPools::default(),
+            snapshot_object_store: None,
+            stop_receiver: &stop_receiver,
+            health_updater: &health_updater,
+            recovery_status: &watch::channel(None).0,
+            recovery_listeners: Vec::new(),
+            overrides: RecoveryOverrides::default(),
+        },
+    )
+    .await
+    .and_then(into_tree)
+    .unwrap();
+
+    // For a ready tree, the published state is not overwritten during `ensure_ready()`.
+    let health = health_check.check_health().await;
+    assert_matches!(health.status(), HealthStatus::NotReady);
+    let details = health.details().expect("no health details").clone();
+    assert_eq!(
+        details["decision"]["action"],
+        serde_json::json!({ "kind": "resume_operation" })
+    );
+    let published_state: TreeState = serde_json::from_value(details).unwrap();
+    assert_eq!(published_state, expected_state);
+}
+
+async fn set_snapshot_recovery(pool: &ConnectionPool, snapshot_recovery: &SnapshotRecoveryStatus) {
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(snapshot_recovery)
+        .await
+        .unwrap();
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn postgres_regenesis_is_detected_on_resume(allow_tree_reset_on_regenesis: bool) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+
+    // Start recovery and immediately interrupt it, so that Postgres genesis is persisted in the tree.
+    let tree_path = temp_dir.path().join("recovery");
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Empty { .. });
+    let (_stop_sender, stop_receiver) = watch::channel(true);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig {
+        allow_tree_reset_on_regenesis,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let result = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree);
+    assert_matches!(result, Err(RecoveryError::Interrupted));
+
+    // Re-initialize Postgres for another chain.
+    let new_pool = ConnectionPool::test_pool().await;
+    let new_temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let new_root_hash =
+        prepare_recovery_snapshot_for_chain(&new_pool, &new_temp_dir, L2ChainId::from(271)).await;
+    assert_ne!(new_root_hash, root_hash);
+    set_snapshot_recovery(&new_pool, &mock_snapshot_recovery(new_root_hash)).await;
+
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Recovering(_));
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let result = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &new_pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree);
+
+    if allow_tree_reset_on_regenesis {
+        let tree = result.unwrap();
+        assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+        assert_eq!(tree.root_hash(), new_root_hash);
+    } else {
+        let err = format!("{:#}", result.unwrap_err());
+        assert!(
+            err.contains("differs from the current Postgres genesis"),
+            "{err}"
+        );
+        assert!(err.contains("allow_tree_reset_on_regenesis"), "{err}");
+    }
+}
+
+#[tokio::test]
+async fn postgres_genesis_is_loaded_without_snapshot_recovery() {
+    let pool = ConnectionPool::test_pool().await;
+    assert_eq!(PostgresGenesis::load(&pool, None).await.unwrap(), None);
+
+    let mut storage = pool.access_storage().await.unwrap();
+    ensure_genesis_state(&mut storage, L2ChainId::from(270), &GenesisParams::mock())
+        .await
+        .unwrap();
+    drop(storage);
+    let genesis = PostgresGenesis::load(&pool, None).await.unwrap().unwrap();
+    assert_eq!(genesis.l1_batch_number, L1BatchNumber(0));
+
+    let other_pool = ConnectionPool::test_pool().await;
+    let mut storage = other_pool.access_storage().await.unwrap();
+    ensure_genesis_state(&mut storage, L2ChainId::from(271), &GenesisParams::mock())
+        .await
+        .unwrap();
+    drop(storage);
+    let other_genesis = PostgresGenesis::load(&other_pool, None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(other_genesis.l1_batch_number, L1BatchNumber(0));
+    assert_ne!(other_genesis.root_hash, genesis.root_hash);
+}
+
+#[tokio::test]
+async fn recovery_with_sampled_verification() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: 5,
+        verification_samples_per_chunk: Some(10),
+        proof_verification_samples: Some(10),
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            TestEventListener::new(stop_sender),
+        )
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[tokio::test]
+async fn sampled_verification_detects_divergent_chunk() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let mut storage = pool.access_storage().await.unwrap();
+    let key_chunks = AsyncTreeRecovery::key_ranges(&mut storage, snapshot.miniblock, 4)
+        .await
+        .unwrap();
+    let mut tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    for (chunk_id, key_chunk) in key_chunks.iter().enumerate() {
+        let entries = storage
+            .storage_logs_dal()
+            .get_tree_entries_for_miniblock(snapshot.miniblock, key_chunk.clone())
+            .await
+            .unwrap();
+        let entries = entries.into_iter().map(|entry| {
+            // Corrupt all entries in chunk #1.
+            let value = if chunk_id == 1 {
+                H256::repeat_byte(0xff)
+            } else {
+                entry.value
+            };
+            TreeEntry::new(entry.key, entry.leaf_index, value)
+        });
+        tree.extend(entries.collect()).await.unwrap();
+    }
+    let tree = tree.finalize().await;
+
+    let mut rng = StdRng::seed_from_u64(123);
+    let err = verify_recovered_tree(
+        &tree,
+        &mut storage,
+        snapshot.miniblock,
+        &key_chunks,
+        10,
+        &mut rng,
+    )
+    .await
+    .unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("chunk 1 of 4"), "{err}");
+    assert!(!err.contains("chunk 0 of 4"), "{err}");
+    assert!(!err.contains("chunk 2 of 4"), "{err}");
+    assert!(!err.contains("chunk 3 of 4"), "{err}");
+
+    // Other chunks should pass verification.
+    verify_recovered_tree(
+        &tree,
+        &mut storage,
+        snapshot.miniblock,
+        &key_chunks[2..],
+        10,
+        &mut rng,
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn proof_verification_detects_corrupted_leaf() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let mut storage = pool.access_storage().await.unwrap();
+    let key_chunks = AsyncTreeRecovery::key_ranges(&mut storage, snapshot.miniblock, 1)
+        .await
+        .unwrap();
+    let entries = storage
+        .storage_logs_dal()
+        .get_tree_entries_for_miniblock(snapshot.miniblock, key_chunks[0].clone())
+        .await
+        .unwrap();
+    drop(storage);
+    let entries = entries
+        .into_iter()
+        .map(|entry| TreeEntry::new(entry.key, entry.leaf_index, entry.value));
+    let mut tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    tree.extend(entries.collect()).await.unwrap();
+    let mut tree = tree.finalize().await;
+    assert_eq!(tree.root_hash(), root_hash);
+
+    let mut rng = StdRng::seed_from_u64(123);
+    verify_recovered_proofs(&mut tree, 10, &mut rng)
+        .await
+        .unwrap();
+
+    let page = tree
+        .entries_range(H256::zero()..=H256::repeat_byte(0xff), 5)
+        .await;
+    let keys = page.entries.iter().map(|entry| entry.key).collect();
+    let mut entries = tree.entries_with_proofs(keys).await.unwrap();
+    assert_eq!(entries.len(), 5);
+    verify_proofs(root_hash, &entries).unwrap();
+
+    // Corrupt a single leaf.
+    entries[2].base.value = H256::repeat_byte(0xff);
+    let err = verify_proofs(root_hash, &entries).unwrap_err().to_string();
+    assert!(err.starts_with("1 of 5 Merkle proofs are invalid"), "{err}");
+    let corrupted_key = format!("{:0>64x}", entries[2].base.key);
+    assert!(err.contains(&corrupted_key), "{err}");
+}
+
+#[derive(Debug)]
+struct IntegrityCheckRecorder<'a> {
+    started_with: &'a StdMutex<Option<(IntegrityCheckPhase, IntegrityCheckStats)>>,
+    discrepancies: &'a StdMutex<Vec<EntryDiscrepancy>>,
+    stop_at_phase: Option<(IntegrityCheckPhase, watch::Sender<bool>)>,
+}
+
+impl HandleIntegrityCheckEvent for IntegrityCheckRecorder<'_> {
+    fn check_started(
+        &mut self,
+        _snapshot_miniblock: MiniblockNumber,
+        phase: IntegrityCheckPhase,
+        stats: IntegrityCheckStats,
+    ) {
+        *self.started_with.lock().unwrap() = Some((phase, stats));
+    }
+
+    fn discrepancy_found(&mut self, discrepancy: &EntryDiscrepancy) {
+        self.discrepancies.lock().unwrap().push(discrepancy.clone());
+    }
+
+    fn page_verified(
+        &mut self,
+        phase: IntegrityCheckPhase,
+        _last_key: H256,
+        _stats: IntegrityCheckStats,
+    ) {
+        if let Some((stop_phase, stop_sender)) = &self.stop_at_phase {
+            if *stop_phase == phase {
+                stop_sender.send_replace(true);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn integrity_check_for_recovered_tree() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot_recovery = mock_snapshot_recovery(root_hash);
+    pool.access_storage()
+        .await
+        .unwrap()
+        .snapshot_recovery_dal()
+        .set_applied_snapshot_status(&snapshot_recovery)
+        .await
+        .unwrap();
+    let snapshot = SnapshotParameters::new(&pool, None, &snapshot_recovery)
+        .await
+        .unwrap();
+
+    let config = MetadataCalculatorRecoveryConfig {
+        verify_integrity: true,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree_path = temp_dir.path().join("recovery");
+    let mut tree = ensure_tree_ready(tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    run_integrity_check(
+        &mut tree,
+        &config,
+        &pool,
+        &stop_receiver,
+        &health_updater,
+        Vec::new(),
+    )
+    .await
+    .unwrap();
+
+    let health = health_check.check_health().await;
+    let details = health.details().unwrap();
+    assert_eq!(details["mode"], "integrity_check");
+    assert_eq!(details["is_finished"], true);
+    assert_eq!(details["verified_tree_entries"], snapshot.log_count);
+    assert_eq!(details["verified_postgres_entries"], 0);
+    assert_eq!(details["missing_entry_count"], 0);
+    // Progress should be cleared once the check is finished.
+    let tags = tree.custom_tags();
+    assert!(!tags.contains_key("integrity_check.progress"), "{tags:?}");
+}
+
+/// Recovers a tree from the Postgres snapshot with the first entry corrupted, the last 2 entries skipped,
+/// and an extra entry inserted. Entries are ordered by hashed key, so missing entries are only found at the end
+/// of the Postgres pass of the integrity check. Returns the tree together with the expected discrepancies sorted by hashed key.
+async fn create_corrupted_tree(
+    pool: &ConnectionPool,
+    snapshot_miniblock: MiniblockNumber,
+    db_path: PathBuf,
+) -> (AsyncTree, Vec<EntryDiscrepancy>) {
+    let mut storage = pool.access_storage().await.unwrap();
+    let entries = storage
+        .storage_logs_dal()
+        .get_tree_entries_for_miniblock(snapshot_miniblock, H256::zero()..=H256::repeat_byte(0xff))
+        .await
+        .unwrap();
+    drop(storage);
+    let mut entries: Vec<_> = entries
+        .into_iter()
+        .map(|entry| TreeEntry::new(entry.key, entry.leaf_index, entry.value))
+        .collect();
+    let max_leaf_index = entries.iter().map(|entry| entry.leaf_index).max().unwrap();
+
+    let corrupted_entry = entries[0].with_value(H256::repeat_byte(0xff));
+    let extra_entry = TreeEntry::new(U256::one(), max_leaf_index + 1, H256::repeat_byte(1));
+    assert!(entries.iter().all(|entry| entry.key != extra_entry.key));
+    let mut expected_discrepancies = vec![
+        EntryDiscrepancy {
+            kind: DiscrepancyKind::Mismatched,
+            hashed_key: hashed_key(&corrupted_entry.key),
+            postgres_entry: Some(entries[0]),
+            tree_entry: Some(corrupted_entry),
+        },
+        EntryDiscrepancy {
+            kind: DiscrepancyKind::Extra,
+            hashed_key: hashed_key(&extra_entry.key),
+            postgres_entry: None,
+            tree_entry: Some(extra_entry),
+        },
+    ];
+    let missing_entries = entries.drain(entries.len() - 2..);
+    for missing_entry in missing_entries {
+        expected_discrepancies.push(EntryDiscrepancy {
+            kind: DiscrepancyKind::Missing,
+            hashed_key: hashed_key(&missing_entry.key),
+            postgres_entry: Some(missing_entry),
+            tree_entry: None,
+        });
+    }
+    expected_discrepancies.sort_unstable_by_key(|discrepancy| discrepancy.hashed_key);
+    entries[0] = corrupted_entry;
+    entries.push(extra_entry);
+
+    let mut tree = create_tree_recovery(db_path, L1BatchNumber(1)).await;
+    tree.extend(entries).await.unwrap();
+    (tree.finalize().await, expected_discrepancies)
+}
+
+#[tokio::test]
+async fn integrity_check_detects_discrepancies() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    let (mut tree, expected_discrepancies) =
+        create_corrupted_tree(&pool, snapshot.miniblock, temp_dir.path().join("recovery")).await;
+
+    let started_with = StdMutex::default();
+    let discrepancies = StdMutex::default();
+    let options = IntegrityCheckOptions {
+        page_size: 10,
+        events: Box::new(IntegrityCheckRecorder {
+            started_with: &started_with,
+            discrepancies: &discrepancies,
+            stop_at_phase: None,
+        }),
+    };
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let stats = tree
+        .verify_against_snapshot(&pool, snapshot.miniblock, options, &stop_receiver)
+        .await
+        .unwrap()
+        .expect("integrity check was interrupted");
+
+    assert_eq!(stats.verified_tree_entries, snapshot.log_count - 1);
+    assert!(stats.verified_postgres_entries > 0);
+    assert_eq!(stats.missing_entry_count, 2);
+    assert_eq!(stats.extra_entry_count, 1);
+    assert_eq!(stats.mismatched_entry_count, 1);
+    assert_eq!(
+        *started_with.lock().unwrap(),
+        Some((IntegrityCheckPhase::Tree, IntegrityCheckStats::default()))
+    );
+    let mut discrepancies = discrepancies.into_inner().unwrap();
+    discrepancies.sort_unstable_by_key(|discrepancy| discrepancy.hashed_key);
+    assert_eq!(discrepancies, expected_discrepancies);
+}
+
+#[test_casing(2, [IntegrityCheckPhase::Tree, IntegrityCheckPhase::Postgres])]
+#[tokio::test]
+async fn integrity_check_is_resumed_after_interruption(stop_phase: IntegrityCheckPhase) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    let tree_path = temp_dir.path().join("recovery");
+    let (mut tree, expected_discrepancies) =
+        create_corrupted_tree(&pool, snapshot.miniblock, tree_path.clone()).await;
+
+    let started_with = StdMutex::default();
+    let discrepancies = StdMutex::default();
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let options = IntegrityCheckOptions {
+        page_size: 10,
+        events: Box::new(IntegrityCheckRecorder {
+            started_with: &started_with,
+            discrepancies: &discrepancies,
+            stop_at_phase: Some((stop_phase, stop_sender)),
+        }),
+    };
+    let stats = tree
+        .verify_against_snapshot(&pool, snapshot.miniblock, options, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(stats, None);
+    drop(tree);
+
+    // Check that progress is persisted.
+    let db = create_test_db(tree_path).await;
+    let mut tree = AsyncTree::new(db, MerkleTreeMode::Full);
+    let started_with = StdMutex::default();
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let options = IntegrityCheckOptions {
+        page_size: 10,
+        events: Box::new(IntegrityCheckRecorder {
+            started_with: &started_with,
+            discrepancies: &discrepancies,
+            stop_at_phase: None,
+        }),
+    };
+    let stats = tree
+        .verify_against_snapshot(&pool, snapshot.miniblock, options, &stop_receiver)
+        .await
+        .unwrap()
+        .expect("integrity check was interrupted");
+
+    let (resumed_phase, resumed_stats) = started_with.into_inner().unwrap().unwrap();
+    assert_eq!(resumed_phase, stop_phase);
+    match stop_phase {
+        IntegrityCheckPhase::Tree => assert_eq!(resumed_stats.verified_tree_entries, 10),
+        IntegrityCheckPhase::Postgres => {
+            assert_eq!(resumed_stats.verified_tree_entries, snapshot.log_count - 1);
+            assert!(resumed_stats.verified_postgres_entries > 0);
+        }
+    }
+    assert_eq!(stats.verified_tree_entries, snapshot.log_count - 1);
+    assert_eq!(stats.discrepancy_count(), 4);
+    // Discrepancies must be reported exactly once across both runs.
+    let mut discrepancies = discrepancies.into_inner().unwrap();
+    discrepancies.sort_unstable_by_key(|discrepancy| discrepancy.hashed_key);
+    assert_eq!(discrepancies, expected_discrepancies);
+}
+
+#[tokio::test]
+async fn root_hash_mismatch_diagnostics_identify_divergent_chunks() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let mut storage = pool.access_storage().await.unwrap();
+    let key_chunks = AsyncTreeRecovery::key_ranges(&mut storage, snapshot.miniblock, 4)
+        .await
+        .unwrap();
+    let tree_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    for (chunk_id, key_chunk) in key_chunks.iter().enumerate() {
+        let entries = storage
+            .storage_logs_dal()
+            .get_tree_entries_for_miniblock(snapshot.miniblock, key_chunk.clone())
+            .await
+            .unwrap();
+        let entries = entries.into_iter().enumerate().filter_map(|(i, entry)| {
+            // Corrupt all entries in chunk #1 and skip the first entry in chunk #3.
+            let value = match chunk_id {
+                1 => H256::repeat_byte(0xff),
+                3 if i == 0 => return None,
+                _ => entry.value,
+            };
+            Some(TreeEntry::new(entry.key, entry.leaf_index, value))
+        });
+        tree.extend(entries.collect()).await.unwrap();
+    }
+    let actual_root_hash = tree.root_hash().await;
+    assert_ne!(actual_root_hash, root_hash);
+
+    let report = diagnose_root_hash_mismatch(
+        &mut tree,
+        &mut storage,
+        snapshot.miniblock,
+        root_hash,
+        actual_root_hash,
+        &key_chunks,
+        3,
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.chunk_count, 4);
+    assert_eq!(report.unassigned_tree_entry_count, 0);
+    let divergent_chunk_ids: Vec<_> = report
+        .divergent_chunks
+        .iter()
+        .map(|chunk| chunk.chunk_id)
+        .collect();
+    assert_eq!(divergent_chunk_ids, [1, 3]);
+
+    let corrupted_chunk = &report.divergent_chunks[0];
+    assert_eq!(corrupted_chunk.key_range, key_chunks[1]);
+    assert_eq!(
+        corrupted_chunk.postgres.entry_count,
+        corrupted_chunk.tree.entry_count
+    );
+    assert_eq!(corrupted_chunk.divergent_keys.len(), 3);
+    for key in &corrupted_chunk.divergent_keys {
+        assert!(key_chunks[1].contains(&key.hashed_key));
+        let tree_entry = key.tree.unwrap();
+        assert_eq!(tree_entry.value, H256::repeat_byte(0xff));
+        assert_eq!(key.postgres.unwrap().leaf_index, tree_entry.leaf_index);
+    }
+
+    let truncated_chunk = &report.divergent_chunks[1];
+    assert_eq!(
+        truncated_chunk.postgres.entry_count,
+        truncated_chunk.tree.entry_count + 1
+    );
+    assert_matches!(
+        truncated_chunk.divergent_keys.as_slice(),
+        [key] if key.postgres.is_some() && key.tree.is_none()
+    );
+
+    let report_path = diagnostics::RootHashMismatchReport::path(&tree_path);
+    let persisted_report = fs::read(&report_path).unwrap();
+    let persisted_report: diagnostics::RootHashMismatchReport =
+        serde_json::from_slice(&persisted_report).unwrap();
+    assert_eq!(persisted_report, report);
+}
+
+#[tokio::test]
+async fn root_hash_mismatch_is_diagnosed_during_recovery() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let wrong_root_hash = H256::repeat_byte(1);
+    let snapshot_recovery = mock_snapshot_recovery(wrong_root_hash);
+    set_snapshot_recovery(&pool, &snapshot_recovery).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &snapshot_recovery)
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: 5,
+        mismatch_diagnostic_keys_per_chunk: Some(10),
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            TestEventListener::new(stop_sender),
+        )
+    };
+    let err = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap_err();
+    // Since all entries are recovered correctly, the mismatch isn't attributed to any chunk.
+    assert_matches!(
+        &err,
+        RecoveryError::RootHashMismatch { diagnostics: Some(diagnostics), .. }
+            if diagnostics.contains("0 of 5 chunks diverge")
+    );
+
+    let report_path = diagnostics::RootHashMismatchReport::path(&tree_path);
+    assert!(report_path.exists());
+}
+
+#[tokio::test]
+async fn recovery_fails_fast_after_root_hash_mismatch() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let wrong_root_hash = H256::repeat_byte(1);
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(wrong_root_hash)).await;
+
+    let tree_path = temp_dir.path().join("recovery");
+    let config = MetadataCalculatorRecoveryConfig::default();
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let err = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap_err();
+    assert_matches!(err, RecoveryError::RootHashMismatch { .. });
+
+    // The tree is kept with the failure marker, so that it can be inspected; it must not be finalized.
+    let inspection = inspect_tree_db(tree_path.clone()).await.unwrap();
+    let failure = inspection
+        .recovery_failure
+        .expect("no recovery failure marker");
+    assert_eq!(failure.expected_root_hash, wrong_root_hash);
+    assert_eq!(failure.actual_root_hash, root_hash);
+    assert_matches!(inspection.state, TreeDbState::Recovering(_));
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Recovering(_));
+    drop(tree);
+
+    // On restart, recovery fails immediately without being started.
+    let listener = RecoveredChunksListener::default();
+    let chunk_count = listener.chunk_count.clone();
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let err = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: vec![Box::new(listener)],
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap_err();
+    assert_matches!(err, RecoveryError::PreviouslyFailed(persisted) if persisted == failure);
+    assert!(
+        err.to_string().contains("Manual intervention required"),
+        "{err}"
+    );
+    assert_eq!(chunk_count.load(Ordering::SeqCst), 0);
+    let health = health_check.check_health().await;
+    assert_matches!(health.status(), HealthStatus::NotReady);
+    let details = health.details().unwrap();
+    assert_eq!(details["mode"], "recovery_failed");
+    assert_eq!(details["error_kind"], "previously_failed");
+    assert!(
+        details["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("manual intervention required"),
+        "{details:?}"
+    );
+
+    // Once the snapshot is fixed, the tree can be reset to restart recovery.
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+    reset_tree_db(tree_path.clone()).await.unwrap();
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Empty { .. });
+    let tree = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+    assert_eq!(tree.recovery_failure().unwrap(), None);
+}
+
+async fn assert_exported_tree(export_path: &Path, tree: &AsyncTree, root_hash: H256) {
+    let descriptor = export::TreeExportDescriptor::load(export_path)
+        .await
+        .unwrap();
+    let tree_info = tree.reader().info().await;
+    assert_eq!(
+        descriptor,
+        export::TreeExportDescriptor {
+            l1_batch_number: L1BatchNumber(1),
+            miniblock_number: MiniblockNumber(1),
+            root_hash,
+            entry_count: tree_info.leaf_count,
+        }
+    );
+    assert!(!export_path.with_extension("partial").exists());
+
+    let exported_db = create_test_db(export_path.join("db")).await;
+    let exported_tree = AsyncTree::new(exported_db, MerkleTreeMode::Full);
+    assert_eq!(exported_tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(exported_tree.root_hash(), root_hash);
+}
+
+#[tokio::test]
+async fn tree_api_reader_is_not_available_after_root_hash_mismatch() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(H256::repeat_byte(1))).await;
+
+    let merkle_tree_config = MerkleTreeConfig {
+        path: temp_dir
+            .path()
+            .join("recovery")
+            .to_str()
+            .unwrap()
+            .to_owned(),
+        ..MerkleTreeConfig::default()
+    };
+    let operation_config = OperationsManagerConfig {
+        delay_interval: 50, // ms
+    };
+    let calculator_config = MetadataCalculatorConfig::for_main_node(
+        &merkle_tree_config,
+        &operation_config,
+        MetadataCalculatorModeConfig::Lightweight,
+    );
+
+    // Both the initial run and the restart must fail without the tree being available via the API.
+    for expect_previous_failure in [false, true] {
+        let calculator = MetadataCalculator::new(&calculator_config).await;
+        // This is the reader handle used by the tree API server.
+        let tree_reader = calculator.tree_reader();
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        let err = calculator
+            .run(pool.clone(), stop_receiver)
+            .await
+            .unwrap_err();
+        let err = err.downcast::<RecoveryError>().unwrap();
+        if expect_previous_failure {
+            assert_matches!(err, RecoveryError::PreviouslyFailed(_));
+        } else {
+            assert_matches!(err, RecoveryError::RootHashMismatch { .. });
+        }
+
+        // Neither the (wrong) recovered tree nor the degraded reader backed by the snapshot should be served.
+        assert!(tree_reader.borrow().is_none());
+    }
+}
+#[tokio::test]
+async fn recovered_tree_is_exported() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+
+    let export_path = temp_dir.path().join("export");
+    // Emulate a partial export left after an interrupted export.
+    let partial_export_path = export_path.with_extension("partial");
+    fs::create_dir_all(partial_export_path.join("db")).unwrap();
+    fs::write(partial_export_path.join("db/garbage"), b"garbage").unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig {
+        export_path: Some(export_path.clone()),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+    assert_exported_tree(&export_path, &tree, root_hash).await;
+
+    let health = health_check.check_health().await;
+    let details = health.details().unwrap();
+    assert_eq!(details["mode"], "recovery_export");
+    assert_eq!(details["stage"], "completed");
+
+    // Exporting the same tree again is a no-op.
+    export_recovered_tree(&tree, MiniblockNumber(1), &export_path, &health_updater)
+        .await
+        .unwrap();
+    drop(tree);
+
+    // Emulate the node being restarted before the export was completed.
+    fs::remove_dir_all(&export_path).unwrap();
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Ready(_));
+    let tree = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap();
+    assert_exported_tree(&export_path, &tree, root_hash).await;
+}
+
+#[tokio::test]
+async fn export_fails_on_mismatched_existing_export() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+
+    let export_path = temp_dir.path().join("export");
+    fs::create_dir(&export_path).unwrap();
+    let other_descriptor = serde_json::json!({
+        "l1_batch_number": 1,
+        "miniblock_number": 1,
+        "root_hash": H256::repeat_byte(1),
+        "entry_count": 1,
+    });
+    fs::write(
+        export::TreeExportDescriptor::path(&export_path),
+        serde_json::to_vec(&other_descriptor).unwrap(),
+    )
+    .unwrap();
+
+    let db = create_test_db(temp_dir.path().join("recovery")).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig {
+        export_path: Some(export_path.clone()),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let err = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .and_then(into_tree)
+        .unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("differs from the recovered tree"), "{err}");
+}
+
+/// Recovers a tree and exports it. Returns the export path.
+async fn prepare_exported_tree(pool: &ConnectionPool, temp_dir: &TempDir) -> PathBuf {
+    let export_path = temp_dir.path().join("export");
+    let db = create_test_db(temp_dir.path().join("exported")).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig {
+        export_path: Some(export_path.clone()),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    tree.ensure_ready(
+        &config,
+        EnsureReadyContext {
+            pool,
+            pools: RecoveryPools::default(),
+            snapshot_object_store: None,
+            stop_receiver: &stop_receiver,
+            health_updater: &health_updater,
+            recovery_status: &watch::channel(None).0,
+            recovery_listeners: Vec::new(),
+            overrides: RecoveryOverrides::default(),
+        },
+    )
+    .await
+    .and_then(into_tree)
+    .unwrap();
+    export_path
+}
+
+async fn import_tree(
+    pool: &ConnectionPool,
+    tree_path: PathBuf,
+    import_path: PathBuf,
+    strict_import: bool,
+) -> Result<AsyncTree, RecoveryError> {
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Empty { .. });
+    // Recovery from Postgres is immediately interrupted, so the tree can only be returned if it's imported.
+    let (_stop_sender, stop_receiver) = watch::channel(true);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let config = MetadataCalculatorRecoveryConfig {
+        import_path: Some(import_path),
+        strict_import,
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    tree.ensure_ready(
+        &config,
+        EnsureReadyContext {
+            pool,
+            pools: RecoveryPools::default(),
+            snapshot_object_store: None,
+            stop_receiver: &stop_receiver,
+            health_updater: &health_updater,
+            recovery_status: &watch::channel(None).0,
+            recovery_listeners: Vec::new(),
+            overrides: RecoveryOverrides::default(),
+        },
+    )
+    .await
+    .and_then(into_tree)
+}
+
+#[tokio::test]
+async fn importing_exported_tree() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+    let export_path = prepare_exported_tree(&pool, &temp_dir).await;
+
+    let tree_path = temp_dir.path().join("imported");
+    let tree = import_tree(&pool, tree_path.clone(), export_path, true)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+    drop(tree);
+
+    let db = create_test_db(tree_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    assert_matches!(tree, GenericAsyncTree::Ready(_));
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn importing_tree_with_mismatched_descriptor(strict_import: bool) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+    let export_path = prepare_exported_tree(&pool, &temp_dir).await;
+
+    let descriptor_path = export::TreeExportDescriptor::path(&export_path);
+    let mut descriptor: serde_json::Value =
+        serde_json::from_slice(&fs::read(&descriptor_path).unwrap()).unwrap();
+    descriptor["l1_batch_number"] = 2.into();
+    fs::write(&descriptor_path, serde_json::to_vec(&descriptor).unwrap()).unwrap();
+
+    let tree_path = temp_dir.path().join("imported");
+    let result = import_tree(&pool, tree_path, export_path, strict_import).await;
+    if strict_import {
+        let err = format!("{:#}", result.unwrap_err());
+        assert!(
+            err.contains("doesn't match the snapshot L1 batch #1"),
+            "{err}"
+        );
+    } else {
+        // The tree falls back to recovery from Postgres, which is interrupted.
+        assert_matches!(result, Err(RecoveryError::Interrupted));
+    }
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn importing_tree_with_corrupted_payload(strict_import: bool) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+    let export_path = prepare_exported_tree(&pool, &temp_dir).await;
+    // Remove tree nodes from the exported RocksDB, but leave the descriptor intact.
+    RocksDBWrapper::new(&export_path.join("db")).clear();
+
+    let tree_path = temp_dir.path().join("imported");
+    let result = import_tree(&pool, tree_path.clone(), export_path, strict_import).await;
+    if strict_import {
+        let err = format!("{:#}", result.unwrap_err());
+        assert!(err.contains("failed verification"), "{err}");
+        assert!(err.contains("Imported Merkle tree is empty"), "{err}");
+        // Imported data must be removed.
+        assert!(!import::import_staging_path(&tree_path).exists());
+        let db = create_test_db(tree_path).await;
+        let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+        assert_matches!(tree, GenericAsyncTree::Empty { .. });
+    } else {
+        assert_matches!(result, Err(RecoveryError::Interrupted));
+        // The tree has started recovery from Postgres after the import failed.
+        let db = create_test_db(tree_path).await;
+        let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+        assert_matches!(tree, GenericAsyncTree::Recovering(_));
+    }
+}
+
+#[tokio::test]
+async fn interrupted_import_is_restarted() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+    let export_path = prepare_exported_tree(&pool, &temp_dir).await;
+
+    // Emulate a staging directory left after an interrupted import.
+    let tree_path = temp_dir.path().join("imported");
+    let staging_path = import::import_staging_path(&tree_path);
+    fs::create_dir_all(&staging_path).unwrap();
+    fs::write(staging_path.join("garbage"), b"garbage").unwrap();
+
+    let tree = import_tree(&pool, tree_path, export_path, true)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+    assert!(!staging_path.exists());
+}
+
+#[tokio::test]
+async fn restoring_tree_from_local_backup() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+
+    let backup_path = temp_dir.path().join("backup");
+    let config = MetadataCalculatorRecoveryConfig {
+        export_path: Some(backup_path.clone()),
+        import_path: Some(backup_path.clone()),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = ensure_tree_ready(tree_path.clone(), MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_exported_tree(&backup_path, &tree, root_hash).await;
+    drop(tree);
+
+    // Emulate losing the tree data directory.
+    fs::remove_dir_all(&tree_path).unwrap();
+    let tree = import_tree(&pool, tree_path.clone(), backup_path.clone(), true)
+        .await
+        .unwrap();
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+    // The backup must remain intact after the restore.
+    assert_exported_tree(&backup_path, &tree, root_hash).await;
+}
+
+#[test_casing(3, [5, 7, 8])]
+#[tokio::test]
+async fn recovery_fault_tolerance(chunk_count: usize) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count,
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            TestEventListener::new(stop_sender).stop_at_chunk(0),
+        )
+    };
+    let result = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await;
+    assert_matches!(result, Err(RecoveryError::Interrupted));
+
+    // Emulate a restart and recover 2 more chunks (#1 and #2).
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    assert_ne!(tree.root_hash().await, root_hash);
+    let recovered_entry_count = tree.leaf_count().await;
+    assert!(recovered_entry_count > 0);
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let started_health = StdMutex::default();
+    let events = StartedHealthRecorder {
+        inner: RecoveryHealthUpdater::new(
+            &health_updater,
+            RecoveryMode::Normal,
+            snapshot.log_count,
+        ),
+        listener: TestEventListener::new(stop_sender)
+            .expect_recovered_chunks(1)
+            .stop_at_chunk(2),
+        health_check,
+        started_health: &started_health,
+    };
+    let recovery_options = RecoveryOptions {
+        chunk_count,
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            events,
+        )
+    };
+    let result = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await;
+    assert_matches!(result, Err(RecoveryError::Interrupted));
+
+    // Progress persisted before the restart must be reported before any new chunks are recovered.
+    let started_health = started_health.into_inner().unwrap().unwrap();
+    assert_matches!(started_health.status(), HealthStatus::Recovering);
+    let details = started_health.details().unwrap();
+    assert_eq!(details["chunk_count"], chunk_count);
+    assert_eq!(details["recovered_chunk_count"], 1);
+    assert_eq!(details["inserted_entry_count"], recovered_entry_count);
+
+    // Emulate another restart and recover remaining chunks.
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    assert_ne!(tree.root_hash().await, root_hash);
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count,
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            TestEventListener::new(stop_sender).expect_recovered_chunks(3),
+        )
+    };
+    let (tree, report) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+    // The report covers all chunks, including ones recovered before restarts.
+    assert_matches!(
+        report,
+        RecoveryReport::Recovered { l1_batch, miniblock, chunks, entries, root_hash: reported_root_hash, .. }
+            if l1_batch == L1BatchNumber(1)
+                && miniblock == MiniblockNumber(1)
+                && chunks == chunk_count
+                && entries == snapshot.log_count
+                && reported_root_hash == root_hash
+    );
+}
+
+#[tokio::test]
+async fn recovery_fault_tolerance_with_changed_tree_mode() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+    let config = MetadataCalculatorRecoveryConfig {
+        desired_chunk_size: 50,
+        concurrency: Some(1),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+
+    // Start recovery in the full mode and interrupt it after the first chunk.
+    let tree_path = temp_dir.path().join("recovery");
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let listener = TestEventListener::new(stop_sender).stop_at_chunk(0);
+    let (tree, _) = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: vec![Box::new(listener)],
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .unwrap();
+    assert!(tree.is_none());
+
+    // Emulate a restart with the tree mode switched to lightweight.
+    let err = ensure_tree_ready(
+        tree_path.clone(),
+        MerkleTreeMode::Lightweight,
+        &config,
+        &pool,
+    )
+    .await
+    .unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("started in the Full mode"), "{err}");
+    assert!(
+        err.contains("configured to run in the Lightweight mode"),
+        "{err}"
+    );
+
+    // Reverting the mode allows to finish recovery.
+    let tree = ensure_tree_ready(tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.mode(), MerkleTreeMode::Full);
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[test_casing(4, ["miniblock", "expected_root_hash", "log_count", "hash"])]
+#[tokio::test]
+async fn recovery_fault_tolerance_with_corrupted_snapshot_fingerprint(component: &str) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+    let config = MetadataCalculatorRecoveryConfig {
+        desired_chunk_size: 50,
+        concurrency: Some(1),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+
+    // Start recovery and interrupt it after the first chunk.
+    let tree_path = temp_dir.path().join("recovery");
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let listener = TestEventListener::new(stop_sender).stop_at_chunk(0);
+    let (tree, _) = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: vec![Box::new(listener)],
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .unwrap();
+    assert!(tree.is_none());
+
+    // Corrupt the persisted fingerprint.
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let tags = tree.custom_tags().await;
+    let persisted_fingerprint = tags[AsyncTreeRecovery::SNAPSHOT_FINGERPRINT_TAG].clone();
+    let mut fingerprint: serde_json::Value = serde_json::from_str(&persisted_fingerprint).unwrap();
+    fingerprint[component] = match component {
+        "miniblock" | "log_count" => serde_json::json!(1_000),
+        _ => serde_json::to_value(H256::repeat_byte(0xff)).unwrap(),
+    };
+    let fingerprint = fingerprint.to_string();
+    tree.update_custom_tags(move |tags| {
+        tags.insert(
+            AsyncTreeRecovery::SNAPSHOT_FINGERPRINT_TAG.to_owned(),
+            fingerprint,
+        );
+    })
+    .await;
+    drop(tree);
+
+    // Emulate a restart; recovery must fail before recovering any chunks.
+    let err = ensure_tree_ready(tree_path.clone(), MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap_err();
+    let err = format!("{err:#}");
+    if component == "hash" {
+        assert!(err.contains("is corrupted"), "{err}");
+    } else {
+        assert!(
+            err.contains("differs from the current snapshot fingerprint"),
+            "{err}"
+        );
+        assert!(err.contains(&format!(" in {component};")), "{err}");
+    }
+
+    // Restoring the fingerprint allows to finish recovery.
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    tree.update_custom_tags(move |tags| {
+        tags.insert(
+            AsyncTreeRecovery::SNAPSHOT_FINGERPRINT_TAG.to_owned(),
+            persisted_fingerprint,
+        );
+    })
+    .await;
+    drop(tree);
+    let tree = ensure_tree_ready(tree_path, MerkleTreeMode::Full, &config, &pool)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[tokio::test]
+async fn ensure_ready_reports_recovery_outcome() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+    let log_count = pool
+        .access_storage()
+        .await
+        .unwrap()
+        .storage_logs_dal()
+        .count_miniblock_storage_logs(MiniblockNumber(1))
+        .await
+        .unwrap();
+    let config = MetadataCalculatorRecoveryConfig {
+        desired_chunk_size: 50,
+        concurrency: Some(1),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let expected_chunk_count = zksync_utils::ceil_div(log_count, 50) as usize;
+    assert!(expected_chunk_count > 2);
+
+    // Interrupt recovery once the first chunk is recovered.
+    let tree_path = temp_dir.path().join("recovery");
+    let db = create_test_db(tree_path.clone()).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let listener = TestEventListener::new(stop_sender).stop_at_chunk(0);
+    let (tree, report) = tree
+        .ensure_ready(
+            &config,
+            EnsureReadyContext {
+                pool: &pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: vec![Box::new(listener)],
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .unwrap();
+    assert!(tree.is_none());
+    let RecoveryReport::Interrupted {
+        recovered_chunks,
+        total_chunks,
+    } = report
+    else {
+        panic!("unexpected report: {report:?}");
+    };
+    assert_eq!(total_chunks, expected_chunk_count);
+    assert!(
+        recovered_chunks > 0 && recovered_chunks < total_chunks,
+        "{recovered_chunks}"
+    );
+
+    // Resume and finish recovery.
+    let (tree, report) = ensure_tree_ready_with_report(tree_path.clone(), &config, &pool).await;
+    assert_eq!(tree.unwrap().root_hash(), root_hash);
+    assert_matches!(
+        report,
+        RecoveryReport::Recovered { chunks, entries, root_hash: reported_root_hash, .. }
+            if chunks == expected_chunk_count && entries == log_count && reported_root_hash == root_hash
+    );
+
+    // The recovered tree doesn't need recovery.
+    let (tree, report) = ensure_tree_ready_with_report(tree_path, &config, &pool).await;
+    assert_eq!(tree.unwrap().root_hash(), root_hash);
+    assert_eq!(report, RecoveryReport::NotNeeded);
+}
+
+async fn ensure_tree_ready_with_report(
+    db_path: PathBuf,
+    config: &MetadataCalculatorRecoveryConfig,
+    pool: &ConnectionPool,
+) -> (Option<AsyncTree>, RecoveryReport) {
+    let db = create_test_db(db_path).await;
+    let tree = GenericAsyncTree::new(db, MerkleTreeMode::Full).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let output = tree
+        .ensure_ready(
+            config,
+            EnsureReadyContext {
+                pool,
+                pools: RecoveryPools::default(),
+                snapshot_object_store: None,
+                stop_receiver: &stop_receiver,
+                health_updater: &health_updater,
+                recovery_status: &watch::channel(None).0,
+                recovery_listeners: Vec::new(),
+                overrides: RecoveryOverrides::default(),
+            },
+        )
+        .await
+        .unwrap();
+
+    output.1.attach_to_health(&health_updater).await;
+    let health = health_check.check_health().await;
+    let reported = &health.details().unwrap()["recovery_report"];
+    assert_eq!(*reported, serde_json::to_value(&output.1).unwrap());
+    output
+}
+
+#[tokio::test]
+async fn calculator_stops_after_recovery_as_configured() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+    // Add an L1 batch after the snapshot; it must not be processed by the tree.
+    let mut storage = pool.access_storage().await.unwrap();
+    extend_db_state(&mut storage, gen_storage_logs(300..350, 1)).await;
+    drop(storage);
+
+    let tree_path = temp_dir.path().join("recovery");
+    let merkle_tree_config = MerkleTreeConfig {
+        path: tree_path.to_str().unwrap().to_owned(),
+        recovery: MerkleTreeRecoveryConfig {
+            stop_after_recovery: true,
+            ..MerkleTreeRecoveryConfig::default()
+        },
+        ..MerkleTreeConfig::default()
+    };
+    let operation_config = OperationsManagerConfig {
+        delay_interval: 50, // ms
+    };
+    let calculator_config = MetadataCalculatorConfig::for_main_node(
+        &merkle_tree_config,
+        &operation_config,
+        MetadataCalculatorModeConfig::Lightweight,
+    );
+    let calculator = MetadataCalculator::new(&calculator_config).await;
+    let health_check = calculator.tree_health_check();
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let calculator_task = calculator.run(pool.clone(), stop_receiver);
+    tokio::time::timeout(Duration::from_secs(30), calculator_task)
+        .await
+        .expect("metadata calculator didn't stop after recovery")
+        .unwrap();
+
+    let health = health_check.check_health().await;
+    assert_matches!(health.status(), HealthStatus::ShutDown);
+    let details = health.details().unwrap();
+    assert_eq!(details["mode"], "recovery_complete");
+    assert_eq!(details["l1_batch_number"], 1);
+    assert_eq!(
+        details["root_hash"],
+        serde_json::to_value(root_hash).unwrap()
+    );
+    assert_eq!(details["recovery_report"]["outcome"], "recovered");
+
+    let tree = AsyncTree::new(create_test_db(tree_path).await, MerkleTreeMode::Lightweight);
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+#[tokio::test]
+async fn tree_reader_switches_from_postgres_to_recovered_tree() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+
+    let merkle_tree_config = MerkleTreeConfig {
+        path: temp_dir
+            .path()
+            .join("recovery")
+            .to_str()
+            .unwrap()
+            .to_owned(),
+        ..MerkleTreeConfig::default()
+    };
+    let operation_config = OperationsManagerConfig {
+        delay_interval: 50, // ms
+    };
+    let calculator_config = MetadataCalculatorConfig::for_main_node(
+        &merkle_tree_config,
+        &operation_config,
+        MetadataCalculatorModeConfig::Lightweight,
+    );
+    let calculator = MetadataCalculator::new(&calculator_config).await;
+    let mut tree_reader = calculator.tree_reader();
+    // Keep the tree recovering until degraded lookups are checked.
+    let recovery_control = calculator.recovery_control();
+    recovery_control.pause();
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let calculator_task = tokio::spawn(calculator.run(pool.clone(), stop_receiver));
+
+    let handle = tree_reader.wait_for(Option::is_some).await.unwrap().clone();
+    let handle = handle.unwrap();
+    assert!(handle.is_degraded());
+    assert!(handle.ready().is_none());
+
+    let logs = gen_storage_logs(100..300, 1).pop().unwrap();
+    let mut keys: Vec<_> = logs
+        .iter()
+        .take(20)
+        .map(|log| log.key.hashed_key_u256())
+        .collect();
+    keys.push(U256::MAX); // missing key
+    let degraded_entries = handle
+        .entries(L1BatchNumber(1), keys.clone())
+        .await
+        .unwrap();
+    assert_eq!(degraded_entries.len(), keys.len());
+    for (entry, log) in degraded_entries.iter().zip(&logs) {
+        assert_eq!(entry.value, log.value);
+        assert_ne!(entry.leaf_index, 0);
+    }
+    let missing_entry = degraded_entries.last().unwrap();
+    assert_eq!(missing_entry.value, H256::zero());
+    assert_eq!(missing_entry.leaf_index, 0);
+
+    let err = handle
+        .entries(L1BatchNumber(0), keys.clone())
+        .await
+        .unwrap_err();
+    assert_matches!(
+        err,
+        TreeLookupError::NotSnapshotL1Batch {
+            requested: L1BatchNumber(0),
+            snapshot_l1_batch: L1BatchNumber(1),
+        }
+    );
+
+    recovery_control.resume();
+    let handle = tree_reader
+        .wait_for(|handle| handle.as_ref().is_some_and(|handle| !handle.is_degraded()))
+        .await
+        .unwrap()
+        .clone();
+    let handle = handle.unwrap();
+    let entries = handle.entries(L1BatchNumber(1), keys).await.unwrap();
+    assert_eq!(entries, degraded_entries);
+
+    stop_sender.send_replace(true);
+    tokio::time::timeout(Duration::from_secs(30), calculator_task)
+        .await
+        .expect("metadata calculator didn't stop")
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn recovery_status_is_published_for_other_components() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+
+    let merkle_tree_config = MerkleTreeConfig {
+        path: temp_dir
+            .path()
+            .join("recovery")
+            .to_str()
+            .unwrap()
+            .to_owned(),
+        ..MerkleTreeConfig::default()
+    };
+    let operation_config = OperationsManagerConfig {
+        delay_interval: 50, // ms
+    };
+    let calculator_config = MetadataCalculatorConfig::for_main_node(
+        &merkle_tree_config,
+        &operation_config,
+        MetadataCalculatorModeConfig::Lightweight,
+    );
+    let calculator = MetadataCalculator::new(&calculator_config).await;
+    let mut recovery_status = calculator.recovery_status();
+    assert_eq!(*recovery_status.borrow_and_update(), None);
+    let mut tree_reader = calculator.tree_reader();
+    // Pause recovery so that the initial progress can be observed.
+    let recovery_control = calculator.recovery_control();
+    recovery_control.pause();
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let calculator_task = tokio::spawn(calculator.run(pool.clone(), stop_receiver));
+
+    let status = recovery_status
+        .wait_for(Option::is_some)
+        .await
+        .unwrap()
+        .clone()
+        .unwrap();
+    assert_eq!(status.snapshot_l1_batch, L1BatchNumber(1));
+    assert!(status.chunk_count > 0);
+    assert_eq!(status.recovered_chunk_count, 0);
+
+    recovery_control.resume();
+    recovery_status.wait_for(Option::is_none).await.unwrap();
+    // The status is reset only after the recovered tree is finalized.
+    tree_reader
+        .wait_for(|handle| handle.as_ref().is_some_and(|handle| !handle.is_degraded()))
+        .await
+        .unwrap();
+    assert_eq!(*recovery_status.borrow(), None);
+
+    stop_sender.send_replace(true);
+    tokio::time::timeout(Duration::from_secs(30), calculator_task)
+        .await
+        .expect("metadata calculator didn't stop")
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn recovered_tree_is_ready_only_after_catching_up() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(root_hash)).await;
+    // Add L1 batches #2..=4 after the snapshot; the tree needs to catch up with them after recovery.
+    let mut storage = pool.access_storage().await.unwrap();
+    extend_db_state(&mut storage, gen_storage_logs(300..360, 3)).await;
+    drop(storage);
+
+    let merkle_tree_config = MerkleTreeConfig {
+        path: temp_dir
+            .path()
+            .join("recovery")
+            .to_str()
+            .unwrap()
+            .to_owned(),
+        max_l1_batches_per_iter: 1,
+        tree_ready_lag_batches: Some(1),
+        recovery: MerkleTreeRecoveryConfig {
+            catch_up_before_ready: true,
+            ..MerkleTreeRecoveryConfig::default()
+        },
+        ..MerkleTreeConfig::default()
+    };
+    let operation_config = OperationsManagerConfig {
+        delay_interval: 50, // ms
+    };
+    let calculator_config = MetadataCalculatorConfig::for_main_node(
+        &merkle_tree_config,
+        &operation_config,
+        MetadataCalculatorModeConfig::Lightweight,
+    );
+    let mut calculator = MetadataCalculator::new(&calculator_config).await;
+    let health_check = calculator.tree_health_check();
+    let mut recovery_status = calculator.recovery_status();
+    let (delay_sender, mut delay_receiver) = tokio::sync::mpsc::unbounded_channel();
+    calculator.delayer.delay_notifier = delay_sender;
+    let recovery_control = calculator.recovery_control();
+    recovery_control.pause();
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let calculator_task = tokio::spawn(calculator.run(pool.clone(), stop_receiver));
+
+    recovery_status.wait_for(Option::is_some).await.unwrap();
+    let health = health_check.check_health().await;
+    assert_matches!(health.status(), HealthStatus::Recovering);
+    assert_eq!(health.details().unwrap()["mode"], "recovery");
+
+    recovery_control.resume();
+    // The updater processes L1 batches one by one until it's idle.
+    let (next_l1_batch, _) = tokio::time::timeout(Duration::from_secs(30), delay_receiver.recv())
+        .await
+        .expect("metadata calculator timed out catching up")
+        .unwrap();
+    assert_eq!(next_l1_batch, L1BatchNumber(5));
+    let health = health_check.check_health().await;
+    assert_matches!(health.status(), HealthStatus::Ready);
+    let details = health.details().unwrap();
+    assert_eq!(details["mode"], "lightweight");
+    assert_eq!(details["next_l1_batch_number"], 5);
+    assert!(details.get("catching_up").is_none(), "{details:?}");
+
+    stop_sender.send_replace(true);
+    tokio::time::timeout(Duration::from_secs(30), calculator_task)
+        .await
+        .expect("metadata calculator didn't stop")
+        .unwrap()
+        .unwrap();
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn tree_recovered_with_skipped_root_hash_check_is_tainted(allow_tainted_tree: bool) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let wrong_root_hash = H256::repeat_byte(1);
+    set_snapshot_recovery(&pool, &mock_snapshot_recovery(wrong_root_hash)).await;
+
+    let tree_path = temp_dir.path().join("recovery");
+    let merkle_tree_config = MerkleTreeConfig {
+        path: tree_path.to_str().unwrap().to_owned(),
+        recovery: MerkleTreeRecoveryConfig {
+            experimental_skip_root_hash_check: true,
+            experimental_allow_tainted_tree: allow_tainted_tree,
+            ..MerkleTreeRecoveryConfig::default()
+        },
+        ..MerkleTreeConfig::default()
+    };
+    let operation_config = OperationsManagerConfig {
+        delay_interval: 50, // ms
+    };
+    let calculator_config = MetadataCalculatorConfig::for_main_node(
+        &merkle_tree_config,
+        &operation_config,
+        MetadataCalculatorModeConfig::Lightweight,
+    );
+    let mut calculator = MetadataCalculator::new(&calculator_config).await;
+    let health_check = calculator.tree_health_check();
+    let tree_reader = calculator.tree_reader();
+    let (delay_sender, mut delay_receiver) = tokio::sync::mpsc::unbounded_channel();
+    calculator.delayer.delay_notifier = delay_sender;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let calculator_task = tokio::spawn(calculator.run(pool.clone(), stop_receiver));
+
+    if allow_tainted_tree {
+        tokio::time::timeout(Duration::from_secs(30), delay_receiver.recv())
+            .await
+            .expect("metadata calculator timed out")
+            .unwrap();
+        let handle = tree_reader.borrow().clone().unwrap();
+        assert!(handle.ready().is_some());
+        let health = health_check.check_health().await;
+        assert_matches!(health.status(), HealthStatus::Affected);
+        let details = health.details().unwrap();
+        assert_eq!(details["mode"], "lightweight");
+        assert_eq!(
+            details["tainted"]["expected_root_hash"],
+            serde_json::to_value(wrong_root_hash).unwrap()
+        );
+
+        stop_sender.send_replace(true);
+        tokio::time::timeout(Duration::from_secs(30), calculator_task)
+            .await
+            .expect("metadata calculator didn't stop")
+            .unwrap()
+            .unwrap();
+    } else {
+        let err = tokio::time::timeout(Duration::from_secs(30), calculator_task)
+            .await
+            .expect("metadata calculator didn't stop")
+            .unwrap()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Merkle tree is tainted"), "{err}");
+        // The tainted tree must not be exposed to proof-serving components.
+        let handle = tree_reader.borrow().clone().unwrap();
+        assert!(handle.is_degraded());
+        let health = health_check.check_health().await;
+        assert_matches!(health.status(), HealthStatus::NotReady);
+        let details = health.details().unwrap();
+        assert_eq!(details["mode"], "tainted");
+        assert_eq!(
+            details["tainted"]["expected_root_hash"],
+            serde_json::to_value(wrong_root_hash).unwrap()
+        );
+    }
+
+    // The taint is persisted in the recovered tree.
+    let db = create_test_db(tree_path).await;
+    let tree = AsyncTree::new(db, MerkleTreeMode::Lightweight);
+    let taint = tree.taint().unwrap().expect("tree is not tainted");
+    assert_eq!(taint.expected_root_hash, wrong_root_hash);
+    assert_eq!(taint.actual_root_hash, tree.root_hash());
+}
+
+#[derive(Debug, Default)]
+struct ConcurrencyTracker {
+    loading_chunk_count: AtomicUsize,
+    max_loading_chunk_count: AtomicUsize,
+    recovered_chunk_count: AtomicUsize,
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for &ConcurrencyTracker {
+    async fn chunk_started(&self, _chunk: &ChunkDescriptor) {
+        let loading_chunk_count = self.loading_chunk_count.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_loading_chunk_count
+            .fetch_max(loading_chunk_count, Ordering::SeqCst);
+    }
+
+    async fn chunk_loaded(&self, _chunk_id: usize) {
+        self.loading_chunk_count.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    async fn chunk_recovered(&self, _chunk: &ChunkDescriptor) {
+        self.recovered_chunk_count.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn chunks_are_loaded_sequentially_with_unit_concurrency() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let config = MetadataCalculatorRecoveryConfig {
+        concurrency: Some(1),
+        ..MetadataCalculatorRecoveryConfig::default()
+    };
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let tracker = ConcurrencyTracker::default();
+    let recovery_options = RecoveryOptions {
+        chunk_count: 4,
+        concurrency_limit: concurrency_limit(&config, &pool).unwrap(),
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            &tracker,
+        )
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+
+    assert_eq!(tracker.recovered_chunk_count.into_inner(), 4);
+    assert_eq!(tracker.max_loading_chunk_count.into_inner(), 1);
+}
+
+/// Event handler checking that the next chunk is loaded while the previous one is being applied to the tree.
+#[derive(Debug)]
+struct PipelineTracker {
+    chunk_count: usize,
+    started_chunk_count: watch::Sender<usize>,
+    recovered_chunk_count: AtomicUsize,
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for &PipelineTracker {
+    async fn chunk_started(&self, _chunk: &ChunkDescriptor) {
+        self.started_chunk_count.send_modify(|count| *count += 1);
+    }
+
+    async fn chunk_recovered(&self, _chunk: &ChunkDescriptor) {
+        let recovered_chunk_count = self.recovered_chunk_count.fetch_add(1, Ordering::SeqCst) + 1;
+        if recovered_chunk_count == self.chunk_count {
+            return;
+        }
+        // Without pipelining, the next chunk would only start after the current one is recovered.
+        let mut started_chunk_count = self.started_chunk_count.subscribe();
+        let next_chunk_started =
+            started_chunk_count.wait_for(|&count| count > recovered_chunk_count);
+        tokio::time::timeout(Duration::from_secs(10), next_chunk_started)
+            .await
+            .expect("next chunk wasn't started while applying the previous one")
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn next_chunk_is_loaded_while_previous_chunk_is_applied() {
+    const CHUNK_COUNT: usize = 4;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let tracker = PipelineTracker {
+        chunk_count: CHUNK_COUNT,
+        started_chunk_count: watch::channel(0).0,
+        recovered_chunk_count: AtomicUsize::new(0),
+    };
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            &tracker,
+        )
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+    assert_eq!(
+        tracker.recovered_chunk_count.load(Ordering::SeqCst),
+        CHUNK_COUNT
+    );
+}
+
+/// Event handler emulating a slow tree applier and recording the maximum number of loaded chunks
+/// waiting for the applier.
+#[derive(Debug, Default)]
+struct TreeWaitTracker {
+    max_tree_wait_queue_depth: AtomicUsize,
+    stats: StdMutex<Option<RecoveryStats>>,
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for &TreeWaitTracker {
+    async fn chunk_recovered(&self, _chunk: &ChunkDescriptor) {
+        // The applier is blocked while this handler runs, so loaded chunks accumulate waiting for it.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let queue_depth = RECOVERY_METRICS.tree_wait_queue_depth.get();
+        self.max_tree_wait_queue_depth
+            .fetch_max(queue_depth, Ordering::SeqCst);
+    }
+
+    fn recovery_finished(&self, stats: RecoveryStats) {
+        *self.stats.lock().unwrap() = Some(stats);
+    }
+}
+
+#[tokio::test]
+async fn slow_tree_applier_is_reflected_in_wait_metrics() {
+    const CHUNK_COUNT: usize = 8;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let tracker = TreeWaitTracker::default();
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        concurrency_limit: ConcurrencyLimits::fixed(4),
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            &tracker,
+        )
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+
+    let max_queue_depth = tracker.max_tree_wait_queue_depth.into_inner();
+    assert!(max_queue_depth > 0, "no chunks waited for the tree applier");
+    let stats = tracker
+        .stats
+        .into_inner()
+        .unwrap()
+        .expect("no recovery stats");
+    assert!(stats.tree_wait_duration > Duration::ZERO, "{stats:?}");
+    assert!(stats.extend_duration <= stats.duration, "{stats:?}");
+}
+
+/// Entry source recording the maximum number of chunks loaded concurrently.
+#[derive(Debug)]
+struct InFlightTrackingEntrySource<'a> {
+    inner: PostgresEntrySource<'a>,
+    in_flight_count: AtomicUsize,
+    max_in_flight_count: AtomicUsize,
+}
+
+#[async_trait]
+impl RecoveryEntrySource for &InFlightTrackingEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        self.inner.key_chunks(chunk_count).await
+    }
+
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        let in_flight_count = self.in_flight_count.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight_count
+            .fetch_max(in_flight_count, Ordering::SeqCst);
+        // Give other chunk tasks a chance to start loading entries.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let result = self
+            .inner
+            .load_entries(chunk_id, key_chunk, stop_receiver)
+            .await;
+        self.in_flight_count.fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+}
+
+#[tokio::test]
+async fn loading_chunks_is_serialized_with_tiny_memory_cap() {
+    const CHUNK_COUNT: usize = 4;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let entry_source = InFlightTrackingEntrySource {
+        inner: PostgresEntrySource {
+            pool: &pool,
+            replica: None,
+            snapshot_miniblock: snapshot.miniblock,
+            connection_retry_timeout: Duration::from_secs(60),
+            connection_acquire_timeout: Duration::from_secs(30),
+            use_copy: false,
+        },
+        in_flight_count: AtomicUsize::new(0),
+        max_in_flight_count: AtomicUsize::new(0),
+    };
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        concurrency_limit: ConcurrencyLimits::fixed(CHUNK_COUNT),
+        loaded_entries_soft_cap: Some(1),
+        ..RecoveryOptions::for_tests(&entry_source, TestEventListener::new(stop_sender))
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+    assert_eq!(entry_source.max_in_flight_count.into_inner(), 1);
+    assert_eq!(RECOVERY_METRICS.loaded_entries_bytes.get(), 0);
+}
+
+/// Entry source recording the number of chunks loaded concurrently when each chunk starts loading.
+/// Once the specified number of chunks has started, the maximum recovery concurrency is overridden.
+#[derive(Debug)]
+struct ConcurrencyOverridingEntrySource<'a> {
+    inner: InFlightTrackingEntrySource<'a>,
+    override_after_chunks: usize,
+    concurrency_override: (watch::Sender<usize>, usize),
+    in_flight_counts: StdMutex<Vec<usize>>,
+}
+
+#[async_trait]
+impl RecoveryEntrySource for &ConcurrencyOverridingEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        self.inner.inner.key_chunks(chunk_count).await
+    }
+
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        let in_flight_count = self.inner.in_flight_count.load(Ordering::SeqCst) + 1;
+        let started_chunk_count = {
+            let mut in_flight_counts = self.in_flight_counts.lock().unwrap();
+            in_flight_counts.push(in_flight_count);
+            in_flight_counts.len()
+        };
+        if started_chunk_count == self.override_after_chunks {
+            let (sender, max_concurrency) = &self.concurrency_override;
+            sender.send_replace(*max_concurrency);
+        }
+        (&self.inner)
+            .load_entries(chunk_id, key_chunk, stop_receiver)
+            .await
+    }
+}
+
+#[tokio::test]
+async fn changing_concurrency_during_recovery() {
+    const CHUNK_COUNT: usize = 16;
+    const INITIAL_CONCURRENCY: usize = 4;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let (override_sender, override_receiver) = watch::channel(INITIAL_CONCURRENCY);
+    let entry_source = ConcurrencyOverridingEntrySource {
+        inner: InFlightTrackingEntrySource {
+            inner: PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            in_flight_count: AtomicUsize::new(0),
+            max_in_flight_count: AtomicUsize::new(0),
+        },
+        override_after_chunks: INITIAL_CONCURRENCY,
+        concurrency_override: (override_sender, 1),
+        in_flight_counts: StdMutex::default(),
+    };
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        concurrency_limit: ConcurrencyLimits::fixed(INITIAL_CONCURRENCY),
+        overrides: RecoveryOverrides {
+            concurrency: Some(override_receiver),
+            ..RecoveryOverrides::default()
+        },
+        ..RecoveryOptions::for_tests(&entry_source, TestEventListener::new(stop_sender))
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+
+    let in_flight_counts = entry_source.in_flight_counts.into_inner().unwrap();
+    assert_eq!(in_flight_counts.len(), CHUNK_COUNT);
+    let (before_override, after_override) = in_flight_counts.split_at(INITIAL_CONCURRENCY);
+    assert_eq!(
+        before_override.iter().max(),
+        Some(&INITIAL_CONCURRENCY),
+        "{in_flight_counts:?}"
+    );
+    assert!(
+        after_override.iter().all(|&count| count == 1),
+        "{in_flight_counts:?}"
+    );
+}
+
+/// Entry source and event handler checking that chunks are loaded while the tree applier is busy. All chunks
+/// except for the first loaded one are held until the first chunk is applied to the tree.
+#[derive(Debug)]
+struct ApplierOverlapTracker<'a> {
+    inner: PostgresEntrySource<'a>,
+    started_load_count: AtomicUsize,
+    in_flight_count: watch::Sender<usize>,
+    recovered_chunk_count: AtomicUsize,
+    first_chunk_recovered: watch::Sender<bool>,
+    max_in_flight_count_while_applying: AtomicUsize,
+}
+
+impl<'a> ApplierOverlapTracker<'a> {
+    fn new(inner: PostgresEntrySource<'a>) -> Self {
+        Self {
+            inner,
+            started_load_count: AtomicUsize::new(0),
+            in_flight_count: watch::channel(0).0,
+            recovered_chunk_count: AtomicUsize::new(0),
+            first_chunk_recovered: watch::channel(false).0,
+            max_in_flight_count_while_applying: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl RecoveryEntrySource for &ApplierOverlapTracker<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        self.inner.key_chunks(chunk_count).await
+    }
+
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        self.in_flight_count.send_modify(|count| *count += 1);
+        if self.started_load_count.fetch_add(1, Ordering::SeqCst) > 0 {
+            let mut first_chunk_recovered = self.first_chunk_recovered.subscribe();
+            first_chunk_recovered
+                .wait_for(|&recovered| recovered)
+                .await
+                .unwrap();
+        }
+        let result = self
+            .inner
+            .load_entries(chunk_id, key_chunk, stop_receiver)
+            .await;
+        self.in_flight_count.send_modify(|count| *count -= 1);
+        result
+    }
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for &ApplierOverlapTracker<'_> {
+    async fn chunk_recovered(&self, _chunk: &ChunkDescriptor) {
+        // The tree applier is blocked until the event is handled.
+        let recovered_chunk_count = self.recovered_chunk_count.fetch_add(1, Ordering::SeqCst) + 1;
+        if recovered_chunk_count == 1 {
+            // If loaders were serialized behind the applier, no other chunks would be loaded at this point.
+            let mut in_flight_count = self.in_flight_count.subscribe();
+            let other_chunks_loading = in_flight_count.wait_for(|&count| count > 1);
+            tokio::time::timeout(Duration::from_secs(10), other_chunks_loading)
+                .await
+                .expect("chunks aren't loaded while the tree applier is busy")
+                .unwrap();
+        }
+        let in_flight_count = *self.in_flight_count.borrow();
+        self.max_in_flight_count_while_applying
+            .fetch_max(in_flight_count, Ordering::SeqCst);
+        self.first_chunk_recovered.send_replace(true);
+    }
+}
+
+#[tokio::test]
+async fn loading_chunks_is_not_serialized_behind_tree_applier() {
+    const CHUNK_COUNT: usize = 8;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let tracker = ApplierOverlapTracker::new(PostgresEntrySource {
+        pool: &pool,
+        replica: None,
+        snapshot_miniblock: snapshot.miniblock,
+        connection_retry_timeout: Duration::from_secs(60),
+        connection_acquire_timeout: Duration::from_secs(30),
+        use_copy: false,
+    });
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        concurrency_limit: ConcurrencyLimits::fixed(CHUNK_COUNT),
+        ..RecoveryOptions::for_tests(&tracker, &tracker)
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+    assert_eq!(
+        tracker.recovered_chunk_count.load(Ordering::SeqCst),
+        CHUNK_COUNT
+    );
+    let max_in_flight_count = tracker
+        .max_in_flight_count_while_applying
+        .load(Ordering::SeqCst);
+    assert!(max_in_flight_count > 1, "{max_in_flight_count}");
+}
+
+#[tokio::test]
+async fn validating_recovery_concurrency() {
+    let pool = ConnectionPool::test_pool().await;
+    let pool_size = pool.max_size() as usize;
+
+    let mut config = MetadataCalculatorRecoveryConfig::default();
+    let limits = concurrency_limit(&config, &pool).unwrap();
+    assert_eq!(limits, ConcurrencyLimits::fixed(pool_size));
+    config.concurrency = Some(pool_size);
+    let limits = concurrency_limit(&config, &pool).unwrap();
+    assert_eq!(limits, ConcurrencyLimits::fixed(pool_size));
+    config.concurrency = Some(3);
+    let limits = concurrency_limit(&config, &pool).unwrap();
+    assert_eq!(limits, ConcurrencyLimits::fixed(3));
+
+    config.concurrency = Some(pool_size + 1);
+    let err = concurrency_limit(&config, &pool).unwrap_err().to_string();
+    assert!(err.contains("exceeds the size"), "{err}");
+    config.concurrency = Some(0);
+    concurrency_limit(&config, &pool).unwrap_err();
+
+    config.concurrency = Some(8);
+    config.min_concurrency = Some(2);
+    let limits = concurrency_limit(&config, &pool).unwrap();
+    assert_eq!((limits.min, limits.max), (2, 8));
+    assert_eq!(limits.latency_threshold, config.slow_chunk_threshold);
+    config.min_concurrency = Some(9);
+    concurrency_limit(&config, &pool).unwrap_err();
+}
+
+async fn assert_recovery_journal_is_empty(
+    tree_path: PathBuf,
+    key_chunk: &ops::RangeInclusive<H256>,
+) {
+    let db = create_test_db(tree_path).await;
+    let journal_key = ChunkJournalEntry::journal_key(key_chunk);
+    assert_eq!(db.recovery_journal_entry(&journal_key), None);
+}
+
+#[test_casing(3, [1, 7, 50])]
+#[tokio::test]
+async fn recovery_in_sub_chunks(sub_chunk_size: usize) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let entry_source = PostgresEntrySource {
+        pool: &pool,
+        replica: None,
+        snapshot_miniblock: snapshot.miniblock,
+        connection_retry_timeout: Duration::from_secs(60),
+        connection_acquire_timeout: Duration::from_secs(30),
+        use_copy: false,
+    };
+    let key_chunks = entry_source.key_chunks(1).await.unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        sub_chunk_size: Some(sub_chunk_size),
+        ..RecoveryOptions::for_tests(entry_source, TestEventListener::new(stop_sender))
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+
+    drop(tree);
+    assert_recovery_journal_is_empty(tree_path, &key_chunks[0]).await;
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn recovery_is_resumed_from_journal(unjournaled_sub_chunk: bool) {
+    const SUB_CHUNK_SIZE: usize = 50;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    let entry_source = PostgresEntrySource {
+        pool: &pool,
+        replica: None,
+        snapshot_miniblock: snapshot.miniblock,
+        connection_retry_timeout: Duration::from_secs(60),
+        connection_acquire_timeout: Duration::from_secs(30),
+        use_copy: false,
+    };
+    let key_chunks = entry_source.key_chunks(1).await.unwrap();
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let mut entries = entry_source
+        .load_entries(0, &key_chunks[0], &stop_receiver)
+        .await
+        .unwrap()
+        .expect("loading entries was interrupted");
+    entries.sort_unstable_by_key(|entry| entry.key);
+    assert!(entries.len() > 3 * SUB_CHUNK_SIZE);
+
+    // Emulate a node stopped after applying 1 (or 2, if the last sub-chunk was not recorded
+    // in the journal) sub-chunks.
+    let tree_path = temp_dir.path().join("recovery");
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let applied_count = if unjournaled_sub_chunk {
+        2 * SUB_CHUNK_SIZE
+    } else {
+        SUB_CHUNK_SIZE
+    };
+    tree.extend(entries[..applied_count].to_vec())
+        .await
+        .unwrap();
+    let journal_entry = ChunkJournalEntry {
+        recovered_version: 1,
+        last_applied_key: Some(entries[SUB_CHUNK_SIZE - 1].key),
+    };
+    let journal_key = ChunkJournalEntry::journal_key(&key_chunks[0]);
+    tree.set_journal_entry(journal_key, Some(journal_entry.serialize()))
+        .await;
+    drop(tree);
+
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    assert_eq!(
+        tree.chunk_journal_entry(&key_chunks[0]).await.unwrap(),
+        Some(journal_entry)
+    );
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    // The chunk must not be considered recovered despite its first key being present in the tree.
+    let recovery_options = RecoveryOptions {
+        sub_chunk_size: Some(SUB_CHUNK_SIZE),
+        ..RecoveryOptions::for_tests(entry_source, TestEventListener::new(stop_sender))
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+    assert_eq!(tree.reader().info().await.leaf_count, entries.len() as u64);
+
+    drop(tree);
+    assert_recovery_journal_is_empty(tree_path, &key_chunks[0]).await;
+}
+
+/// Entry source emulating a stop signal received while loading a batch of entries after the specified number
+/// of batches is loaded.
+#[derive(Debug)]
+struct BatchStoppingEntrySource<'a> {
+    inner: PostgresEntrySource<'a>,
+    stop_sender: watch::Sender<bool>,
+    batches_before_stop: usize,
+    loaded_batches: AtomicUsize,
+}
+
+#[async_trait]
+impl RecoveryEntrySource for BatchStoppingEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        self.inner.key_chunks(chunk_count).await
+    }
+
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        self.inner
+            .load_entries(chunk_id, key_chunk, stop_receiver)
+            .await
+    }
+
+    fn supports_batches(&self) -> bool {
+        true
+    }
+
+    async fn load_entries_batch(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        after_key: Option<H256>,
+        limit: usize,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        if self.loaded_batches.fetch_add(1, Ordering::SeqCst) >= self.batches_before_stop {
+            self.stop_sender.send_replace(true);
+            return Ok(None);
+        }
+        self.inner
+            .load_entries_batch(chunk_id, key_chunk, after_key, limit, stop_receiver)
+            .await
+    }
+}
+
+#[test_casing(4, [1, 7, 20, 1_000])]
+#[tokio::test]
+async fn loading_snapshot_entries_in_pages(page_size: usize) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    let entry_source = PostgresEntrySource {
+        pool: &pool,
+        replica: None,
+        snapshot_miniblock: snapshot.miniblock,
+        connection_retry_timeout: Duration::from_secs(60),
+        connection_acquire_timeout: Duration::from_secs(30),
+        use_copy: false,
+    };
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+
+    for key_chunk in entry_source.key_chunks(3).await.unwrap() {
+        // Postgres entries are sorted by the hashed key.
+        let expected_entries = entry_source
+            .load_entries(0, &key_chunk, &stop_receiver)
+            .await
+            .unwrap()
+            .expect("loading entries was interrupted");
+
+        let mut pages = SnapshotEntryPages::new(snapshot.miniblock, key_chunk.clone(), page_size);
+        let mut storage = pool.access_storage().await.unwrap();
+        let mut entries = vec![];
+        while let Some(page) = pages.next_page(&mut storage).await.unwrap() {
+            assert!(!page.is_empty() && page.len() <= page_size);
+            entries.extend(page);
+        }
+        assert_eq!(entries, expected_entries);
+        // The cursor remains exhausted.
+        assert!(pages.next_page(&mut storage).await.unwrap().is_none());
+
+        if let [.., second_to_last, last] = expected_entries.as_slice() {
+            let mut pages =
+                SnapshotEntryPages::new(snapshot.miniblock, key_chunk.clone(), page_size)
+                    .resume_after(Some(hashed_key(&second_to_last.key)));
+            let page = pages.next_page(&mut storage).await.unwrap();
+            assert_eq!(page, Some(vec![*last]));
+            let mut pages = SnapshotEntryPages::new(snapshot.miniblock, key_chunk, page_size)
+                .resume_after(Some(hashed_key(&last.key)));
+            assert_eq!(pages.next_page(&mut storage).await.unwrap(), None);
+        }
+    }
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn streaming_recovery_is_resumed_after_interruption(resume_streaming: bool) {
+    const BATCH_SIZE: usize = 20;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    let entry_source = PostgresEntrySource {
+        pool: &pool,
+        replica: None,
+        snapshot_miniblock: snapshot.miniblock,
+        connection_retry_timeout: Duration::from_secs(60),
+        connection_acquire_timeout: Duration::from_secs(30),
+        use_copy: false,
+    };
+    let key_chunks = entry_source.key_chunks(1).await.unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        streaming_batch_size: Some(BATCH_SIZE),
+        ..RecoveryOptions::for_tests(
+            BatchStoppingEntrySource {
+                inner: PostgresEntrySource {
+                    pool: &pool,
+                    replica: None,
+                    snapshot_miniblock: snapshot.miniblock,
+                    connection_retry_timeout: Duration::from_secs(60),
+                    connection_acquire_timeout: Duration::from_secs(30),
+                    use_copy: false,
+                },
+                stop_sender,
+                batches_before_stop: 3,
+                loaded_batches: AtomicUsize::new(0),
+            },
+            RecoveryHealthUpdater::new(&health_updater, RecoveryMode::Normal, snapshot.log_count),
+        )
+    };
+    let recovery_result = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await;
+    assert_matches!(recovery_result, Err(RecoveryError::Interrupted));
+
+    // Check that the chunk was partially applied, and its progress is recorded in the journal.
+    let mut tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let journal_entry = tree.chunk_journal_entry(&key_chunks[0]).await.unwrap();
+    assert_matches!(
+        journal_entry,
+        Some(ChunkJournalEntry {
+            recovered_version: 1,
+            last_applied_key: None,
+        })
+    );
+    let applied_count = 3 * BATCH_SIZE;
+    let all_entries = entry_source
+        .load_entries(0, &key_chunks[0], &stop_receiver)
+        .await
+        .unwrap()
+        .expect("loading entries was interrupted");
+    let tree_entries = tree
+        .entries(all_entries.iter().map(|entry| entry.key).collect())
+        .await
+        .unwrap();
+    let present_count = tree_entries
+        .iter()
+        .filter(|entry| !entry.is_empty())
+        .count();
+    assert_eq!(present_count, applied_count);
+
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    // The chunk must not be considered recovered despite its first key being present in the tree.
+    let recovery_options = RecoveryOptions {
+        sub_chunk_size: Some(BATCH_SIZE),
+        streaming_batch_size: resume_streaming.then_some(BATCH_SIZE),
+        ..RecoveryOptions::for_tests(entry_source, TestEventListener::new(stop_sender))
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+    assert_eq!(
+        tree.reader().info().await.leaf_count,
+        all_entries.len() as u64
+    );
+
+    drop(tree);
+    assert_recovery_journal_is_empty(tree_path, &key_chunks[0]).await;
+}
+
+/// Entry source emulating a stop signal received after loading entries or while loading them
+/// is artificially delayed.
+#[derive(Debug)]
+struct StoppingEntrySource<'a> {
+    inner: PostgresEntrySource<'a>,
+    stop_sender: watch::Sender<bool>,
+    delay_loading: bool,
+}
+
+#[async_trait]
+impl RecoveryEntrySource for StoppingEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        self.inner.key_chunks(chunk_count).await
+    }
+
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        if self.delay_loading {
+            self.stop_sender.send_replace(true);
+            let delay = tokio::time::sleep(Duration::from_secs(3_600));
+            if run_until_stopped(delay, stop_receiver).await.is_none() {
+                return Ok(None);
+            }
+        }
+        let entries = self
+            .inner
+            .load_entries(chunk_id, key_chunk, stop_receiver)
+            .await?;
+        self.stop_sender.send_replace(true);
+        Ok(entries)
+    }
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn stop_signal_during_chunk_recovery(delay_loading: bool) {
+    const CHUNK_COUNT: usize = 5;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let tracker = ConcurrencyTracker::default();
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        ..RecoveryOptions::for_tests(
+            StoppingEntrySource {
+                inner: PostgresEntrySource {
+                    pool: &pool,
+                    replica: None,
+                    snapshot_miniblock: snapshot.miniblock,
+                    connection_retry_timeout: Duration::from_secs(60),
+                    connection_acquire_timeout: Duration::from_secs(30),
+                    use_copy: false,
+                },
+                stop_sender,
+                delay_loading,
+            },
+            &tracker,
+        )
+    };
+    let recovery = tree.recover(snapshot, recovery_options, &pool, &stop_receiver);
+    let recovered_tree = tokio::time::timeout(Duration::from_secs(10), recovery)
+        .await
+        .expect("Recovery wasn't promptly stopped");
+    assert_matches!(recovered_tree, Err(RecoveryError::Interrupted));
+    let expected_recovered_chunks = if delay_loading { 0 } else { 1 };
+    assert_eq!(
+        tracker.recovered_chunk_count.load(Ordering::SeqCst),
+        expected_recovered_chunks
+    );
+
+    // Emulate a restart. The chunk with entries loaded before the stop signal must be recovered.
+    let tree = create_tree_recovery(tree_path, L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            TestEventListener::new(stop_sender).expect_recovered_chunks(expected_recovered_chunks),
+        )
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+/// Entry source emulating a stop signal received while loading a chunk. If `fail_transiently` is set, loading
+/// fails with a transient error, so that the stop signal is received before the chunk is retried.
+#[derive(Debug)]
+struct InterruptingEntrySource<'a> {
+    inner: StoppingEntrySource<'a>,
+    fail_transiently: bool,
+}
+
+#[async_trait]
+impl RecoveryEntrySource for InterruptingEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        self.inner.key_chunks(chunk_count).await
+    }
+
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        if self.fail_transiently {
+            self.inner.stop_sender.send_replace(true);
+            let err = anyhow::Error::from(SqlxError::PoolTimedOut);
+            return Err(err.context("emulated transient error"));
+        }
+        self.inner
+            .load_entries(chunk_id, key_chunk, stop_receiver)
+            .await
+    }
+}
+
+#[derive(Debug, Default)]
+struct LoadedChunksRecorder(StdMutex<Vec<usize>>);
+
+#[async_trait]
+impl HandleRecoveryEvent for &LoadedChunksRecorder {
+    async fn chunk_loaded(&self, chunk_id: usize) {
+        self.0.lock().unwrap().push(chunk_id);
+    }
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn interrupted_chunks_are_not_reported_as_loaded(fail_transiently: bool) {
+    const CHUNK_COUNT: usize = 5;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = create_tree_recovery(tree_path, L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recorder = LoadedChunksRecorder::default();
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        max_chunk_attempts: 3,
+        ..RecoveryOptions::for_tests(
+            InterruptingEntrySource {
+                inner: StoppingEntrySource {
+                    inner: PostgresEntrySource {
+                        pool: &pool,
+                        replica: None,
+                        snapshot_miniblock: snapshot.miniblock,
+                        connection_retry_timeout: Duration::from_secs(60),
+                        connection_acquire_timeout: Duration::from_secs(30),
+                        use_copy: false,
+                    },
+                    stop_sender,
+                    delay_loading: true,
+                },
+                fail_transiently,
+            },
+            &recorder,
+        )
+    };
+    let recovery = tree.recover(snapshot, recovery_options, &pool, &stop_receiver);
+    let recovered_tree = tokio::time::timeout(Duration::from_secs(10), recovery)
+        .await
+        .expect("Recovery wasn't promptly stopped");
+    assert_matches!(recovered_tree, Err(RecoveryError::Interrupted));
+    let loaded_chunk_ids = recorder.0.into_inner().unwrap();
+    assert!(loaded_chunk_ids.is_empty(), "{loaded_chunk_ids:?}");
+}
+
+/// Entry source emulating slow loading of chunks from Postgres.
+#[derive(Debug)]
+struct SlowEntrySource<'a> {
+    inner: PostgresEntrySource<'a>,
+    delay: Duration,
+}
+
+#[async_trait]
+impl RecoveryEntrySource for SlowEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        self.inner.key_chunks(chunk_count).await
+    }
+
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        tokio::time::sleep(self.delay).await;
+        self.inner
+            .load_entries(chunk_id, key_chunk, stop_receiver)
+            .await
+    }
+}
+
+#[tokio::test]
+async fn recovery_deadline_stops_starting_chunks_and_is_resumed() {
+    const CHUNK_COUNT: usize = 8;
+    const LOAD_DELAY: Duration = Duration::from_millis(100);
+    const MAX_DURATION: Duration = Duration::from_millis(250);
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let mut recovery_options = RecoveryOptions::new(CHUNK_COUNT)
+        .max_duration(MAX_DURATION)
+        .events(Arc::new(RecoveryHealthUpdater::new(
+            &health_updater,
+            RecoveryMode::Normal,
+            snapshot.log_count,
+        )));
+    recovery_options.entry_source = Some(Box::new(SlowEntrySource {
+        inner: PostgresEntrySource {
+            pool: &pool,
+            replica: None,
+            snapshot_miniblock: snapshot.miniblock,
+            connection_retry_timeout: Duration::from_secs(60),
+            connection_acquire_timeout: Duration::from_secs(30),
+            use_copy: false,
+        },
+        delay: LOAD_DELAY,
+    }));
+    let err = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap_err();
+
+    let RecoveryError::DeadlineExceeded {
+        recovered,
+        total,
+        elapsed,
+    } = err
+    else {
+        panic!("unexpected error: {err:?}");
+    };
+    assert_eq!(total, CHUNK_COUNT);
+    assert!(recovered > 0 && recovered < CHUNK_COUNT, "{recovered}");
+    assert!(elapsed >= MAX_DURATION, "{elapsed:?}");
+
+    // The chunk in flight when the deadline was exceeded must be recovered.
+    let health = health_check.check_health().await;
+    assert_matches!(health.status(), HealthStatus::NotReady);
+    let details = health.details().unwrap();
+    assert_eq!(details["error_kind"], "deadline_exceeded");
+    assert_eq!(details["recovered_chunk_count"], recovered);
+
+    // Emulate a restart. Chunks recovered before the deadline must be skipped.
+    let tree = create_tree_recovery(tree_path, L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let mut recovery_options = RecoveryOptions::new(CHUNK_COUNT).events(Arc::new(
+        TestEventListener::new(stop_sender).expect_recovered_chunks(recovered),
+    ));
+    recovery_options.entry_source = Some(Box::new(PostgresEntrySource {
+        pool: &pool,
+        replica: None,
+        snapshot_miniblock: snapshot.miniblock,
+        connection_retry_timeout: Duration::from_secs(60),
+        connection_acquire_timeout: Duration::from_secs(30),
+        use_copy: false,
+    }));
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+/// Waits until the health details satisfy `predicate`, returning the details.
+async fn wait_for_health_details(
+    health_check: &ReactiveHealthCheck,
+    predicate: impl Fn(&serde_json::Value) -> bool,
+) -> serde_json::Value {
+    loop {
+        let health = health_check.check_health().await;
+        if let Some(details) = health.details().filter(|&details| predicate(details)) {
+            return details.clone();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn paused_recovery_is_resumed_and_completed() {
+    const CHUNK_COUNT: usize = 8;
+    const LOAD_DELAY: Duration = Duration::from_millis(50);
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let control = RecoveryControlHandle::default();
+    let mut recovery_options = RecoveryOptions::new(CHUNK_COUNT)
+        .control(control.subscribe())
+        .events(Arc::new(RecoveryHealthUpdater::new(
+            &health_updater,
+            RecoveryMode::Normal,
+            snapshot.log_count,
+        )));
+    recovery_options.entry_source = Some(Box::new(SlowEntrySource {
+        inner: PostgresEntrySource {
+            pool: &pool,
+            replica: None,
+            snapshot_miniblock: snapshot.miniblock,
+            connection_retry_timeout: Duration::from_secs(60),
+            connection_acquire_timeout: Duration::from_secs(30),
+            use_copy: false,
+        },
+        delay: LOAD_DELAY,
+    }));
+    let recovery = tree.recover(snapshot, recovery_options, &pool, &stop_receiver);
+
+    let control_recovery = async {
+        wait_for_health_details(&health_check, |details| {
+            details["recovered_chunk_count"].as_u64() >= Some(2)
+        })
+        .await;
+        control.pause();
+        let details =
+            wait_for_health_details(&health_check, |details| details["paused"] == true).await;
+        assert!(details["paused_at"].as_u64().unwrap() > 0, "{details:?}");
+
+        // Let chunks in flight be recovered; after that, no new chunks should be started.
+        tokio::time::sleep(LOAD_DELAY * 4).await;
+        let details = health_check.check_health().await.details().unwrap().clone();
+        assert_eq!(details["paused"], true, "{details:?}");
+        let paused_chunk_count = details["recovered_chunk_count"].as_u64().unwrap();
+        assert!(paused_chunk_count < CHUNK_COUNT as u64, "{details:?}");
+        tokio::time::sleep(LOAD_DELAY * 4).await;
+        let details = health_check.check_health().await.details().unwrap().clone();
+        assert_eq!(details["recovered_chunk_count"], paused_chunk_count);
+
+        control.resume();
+        wait_for_health_details(&health_check, |details| details.get("paused").is_none()).await;
+    };
+    let (recovery_result, ()) = tokio::join!(recovery, control_recovery);
+    let (tree, _) = recovery_result.unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+    let health = health_check.check_health().await;
+    assert_matches!(health.status(), HealthStatus::Ready);
+}
+
+#[tokio::test]
+async fn loading_recovery_plan_from_postgres() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    let entry_source = PostgresEntrySource {
+        pool: &pool,
+        replica: None,
+        snapshot_miniblock: snapshot.miniblock,
+        connection_retry_timeout: Duration::from_secs(60),
+        connection_acquire_timeout: Duration::from_secs(30),
+        use_copy: false,
+    };
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+
+    let key_chunks = entry_source.key_chunks(5).await.unwrap();
+    let chunks: Vec<_> = key_chunks.into_iter().enumerate().collect();
+    let plan = RecoveryPlan::load(&entry_source, &chunks)
+        .await
+        .unwrap()
+        .expect("Postgres must support recovery plans");
+    assert_eq!(plan.chunks().len(), 5);
+    assert_eq!(plan.total_entry_count(), snapshot.log_count);
+    for (planned_chunk, (index, key_range)) in plan.chunks().iter().zip(&chunks) {
+        assert_eq!(planned_chunk.index, *index);
+        assert_eq!(planned_chunk.key_range, *key_range);
+        let entries = entry_source
+            .load_entries(*index, key_range, &stop_receiver)
+            .await
+            .unwrap()
+            .expect("loading entries was interrupted");
+        assert_eq!(planned_chunk.entry_count, entries.len() as u64);
+    }
+}
+
+/// Entry source with fake estimates of chunk entry counts.
+#[derive(Debug)]
+struct EstimatingEntrySource<'a> {
+    inner: PostgresEntrySource<'a>,
+    entry_counts: Vec<u64>,
+}
+
+#[async_trait]
+impl RecoveryEntrySource for EstimatingEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        self.inner.key_chunks(chunk_count).await
+    }
+
+    async fn estimate_entry_counts(
+        &self,
+        key_chunks: &[ops::RangeInclusive<H256>],
+    ) -> anyhow::Result<Option<Vec<u64>>> {
+        // All chunks are expected to be estimated since the tree is initially empty.
+        assert_eq!(key_chunks.len(), self.entry_counts.len());
+        Ok(Some(self.entry_counts.clone()))
+    }
+
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        self.inner
+            .load_entries(chunk_id, key_chunk, stop_receiver)
+            .await
+    }
+}
+
+#[derive(Debug, Default)]
+struct ChunkOrderRecorder(StdMutex<Vec<usize>>);
+
+#[async_trait]
+impl HandleRecoveryEvent for &ChunkOrderRecorder {
+    async fn chunk_started(&self, chunk: &ChunkDescriptor) {
+        self.0.lock().unwrap().push(chunk.index);
+    }
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn large_chunks_are_recovered_first(prioritize_large_chunks: bool) {
+    const ENTRY_COUNTS: [u64; 6] = [5, 30, 10, 30, 0, 20];
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let recorder = ChunkOrderRecorder::default();
+    let recovery_options = RecoveryOptions {
+        chunk_count: ENTRY_COUNTS.len(),
+        prioritize_large_chunks,
+        ..RecoveryOptions::for_tests(
+            EstimatingEntrySource {
+                inner: PostgresEntrySource {
+                    pool: &pool,
+                    replica: None,
+                    snapshot_miniblock: snapshot.miniblock,
+                    connection_retry_timeout: Duration::from_secs(60),
+                    connection_acquire_timeout: Duration::from_secs(30),
+                    use_copy: false,
+                },
+                entry_counts: ENTRY_COUNTS.to_vec(),
+            },
+            &recorder,
+        )
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+
+    let chunk_order = recorder.0.into_inner().unwrap();
+    if prioritize_large_chunks {
+        // Chunks with equal estimates must retain their relative order.
+        assert_eq!(chunk_order, [1, 3, 5, 2, 0, 4]);
+    } else {
+        assert_eq!(chunk_order, [0, 1, 2, 3, 4, 5]);
+    }
+}
+
+/// Entry source failing to load entries for the specified chunks.
+#[derive(Debug)]
+struct FailingEntrySource<'a> {
+    inner: PostgresEntrySource<'a>,
+    failing_chunk_ids: Vec<usize>,
+}
+
+#[async_trait]
+impl RecoveryEntrySource for FailingEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        self.inner.key_chunks(chunk_count).await
+    }
+
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        if self.failing_chunk_ids.contains(&chunk_id) {
+            anyhow::bail!("emulated error loading chunk #{chunk_id}");
+        }
+        self.inner
+            .load_entries(chunk_id, key_chunk, stop_receiver)
+            .await
+    }
+}
+
+#[test_casing(2, [false, true])]
+#[tokio::test]
+async fn chunk_errors_are_aggregated(fail_fast: bool) {
+    const CHUNK_COUNT: usize = 5;
+    const FAILING_CHUNK_IDS: [usize; 2] = [1, 3];
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree_path = temp_dir.path().join("recovery");
+    let tree = create_tree_recovery(tree_path.clone(), L1BatchNumber(1)).await;
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        fail_fast,
+        ..RecoveryOptions::for_tests(
+            FailingEntrySource {
+                inner: PostgresEntrySource {
+                    pool: &pool,
+                    replica: None,
+                    snapshot_miniblock: snapshot.miniblock,
+                    connection_retry_timeout: Duration::from_secs(60),
+                    connection_acquire_timeout: Duration::from_secs(30),
+                    use_copy: false,
+                },
+                failing_chunk_ids: FAILING_CHUNK_IDS.to_vec(),
+            },
+            RecoveryHealthUpdater::new(&health_updater, RecoveryMode::Normal, snapshot.log_count),
+        )
+    };
+    let err = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap_err();
+    if fail_fast {
+        assert_matches!(err, RecoveryError::Other(_));
+        let err = format!("{err:#}");
+        assert!(err.contains("emulated error loading chunk #1"), "{err}");
+        return;
+    }
+
+    assert_matches!(err, RecoveryError::ChunksFailed(_));
+    let err = format!("{err:#}");
+    assert!(err.contains("failed recovering 2 / 5 chunks"), "{err}");
+    for chunk_id in FAILING_CHUNK_IDS {
+        assert!(err.contains(&format!("chunk #{chunk_id} ")), "{err}");
+        assert!(
+            err.contains(&format!("emulated error loading chunk #{chunk_id}")),
+            "{err}"
+        );
+    }
+    let health = health_check.check_health().await;
+    assert_matches!(health.status(), HealthStatus::NotReady);
+    let details = health.details().unwrap();
+    assert_eq!(details["recovered_chunk_count"], 3);
+    assert_eq!(details["error_kind"], "chunks_failed");
+    let failed_chunks = details["failed_chunks"].as_array().unwrap();
+    let failed_chunk_ids: Vec<_> = failed_chunks
+        .iter()
+        .map(|chunk| chunk["chunk_id"].as_u64().unwrap() as usize)
+        .collect();
+    assert_eq!(failed_chunk_ids, FAILING_CHUNK_IDS);
+    for chunk in failed_chunks {
+        assert_eq!(chunk["error_kind"], "other");
+    }
+
+    // Emulate a restart. Successfully recovered chunks must be skipped.
+    let tree = create_tree_recovery(tree_path, L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        fail_fast,
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            TestEventListener::new(stop_sender)
+                .expect_recovered_chunks(CHUNK_COUNT - FAILING_CHUNK_IDS.len()),
+        )
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap();
+    assert_eq!(tree.root_hash(), root_hash);
+}
+
+/// Entry source emulating chunk loaders that never finish and ignore stop signals, so that they can only be
+/// finished by cancellation. The loader started last fails once all concurrency permits are taken.
+#[derive(Debug)]
+struct HangingEntrySource<'a> {
+    inner: PostgresEntrySource<'a>,
+    concurrency: usize,
+    started_loaders: AtomicUsize,
+    cancelled_loaders: AtomicUsize,
+}
+
+/// Counts cancelled chunk loaders on drop.
+struct CancellationGuard<'a>(&'a AtomicUsize);
+
+impl Drop for CancellationGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[async_trait]
+impl RecoveryEntrySource for &HangingEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        self.inner.key_chunks(chunk_count).await
+    }
+
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        _key_chunk: &ops::RangeInclusive<H256>,
+        _stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        let started_loaders = self.started_loaders.fetch_add(1, Ordering::SeqCst) + 1;
+        if started_loaders == self.concurrency {
+            anyhow::bail!("emulated error loading chunk #{chunk_id}");
+        }
+        let _guard = CancellationGuard(&self.cancelled_loaders);
+        future::pending::<()>().await;
+        unreachable!()
+    }
+}
+
+#[tokio::test]
+async fn chunk_error_cancels_in_flight_chunk_loaders_in_fail_fast_mode() {
+    const CHUNK_COUNT: usize = 5;
+    const CONCURRENCY: usize = 3;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let entry_source = HangingEntrySource {
+        inner: PostgresEntrySource {
+            pool: &pool,
+            replica: None,
+            snapshot_miniblock: snapshot.miniblock,
+            connection_retry_timeout: Duration::from_secs(60),
+            connection_acquire_timeout: Duration::from_secs(30),
+            use_copy: false,
+        },
+        concurrency: CONCURRENCY,
+        started_loaders: AtomicUsize::new(0),
+        cancelled_loaders: AtomicUsize::new(0),
+    };
+    let tracker = ConcurrencyTracker::default();
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        concurrency_limit: ConcurrencyLimits::fixed(CONCURRENCY),
+        ..RecoveryOptions::for_tests(&entry_source, &tracker)
+    };
+    let err = tokio::time::timeout(
+        Duration::from_secs(10),
+        tree.recover(snapshot, recovery_options, &pool, &stop_receiver),
+    )
+    .await
+    .expect("in-flight chunk loaders were not cancelled")
+    .unwrap_err();
+    assert_matches!(err, RecoveryError::Other(_));
+    let err = format!("{err:#}");
+    assert!(err.contains("emulated error loading chunk"), "{err}");
+
+    // Loaders that have started before the error are cancelled; other chunks are never started.
+    assert_eq!(
+        entry_source.started_loaders.load(Ordering::SeqCst),
+        CONCURRENCY
+    );
+    assert_eq!(
+        entry_source.cancelled_loaders.load(Ordering::SeqCst),
+        CONCURRENCY - 1
+    );
+    assert_eq!(tracker.recovered_chunk_count.load(Ordering::SeqCst), 0);
+}
+
+fn tree_entry_with_hashed_key(hashed_key: H256) -> TreeEntry {
+    TreeEntry::new(
+        U256::from_little_endian(hashed_key.as_bytes()),
+        1,
+        H256::zero(),
+    )
+}
+
+#[test]
+fn chunk_boundaries_detect_overlapping_chunks() {
+    let key_chunks: Vec<_> = AsyncTreeRecovery::hashed_key_ranges(4).collect();
+    let entries = |bytes: &[u8]| -> Vec<_> {
+        bytes
+            .iter()
+            .map(|&byte| tree_entry_with_hashed_key(H256::repeat_byte(byte)))
+            .collect()
+    };
+
+    let mut boundaries = ChunkBoundaries::default();
+    boundaries
+        .record(2, &key_chunks[2], &entries(&[0x90, 0x80]))
+        .unwrap();
+    boundaries
+        .record(0, &key_chunks[0], &entries(&[0x10]))
+        .unwrap();
+    boundaries.record(0, &key_chunks[0], &[]).unwrap();
+    // Streamed batches of the same chunk extend its boundaries.
+    boundaries
+        .record(0, &key_chunks[0], &entries(&[0x20, 0x30]))
+        .unwrap();
+    boundaries
+        .record(3, &key_chunks[3], &entries(&[0xc0]))
+        .unwrap();
+
+    let err = boundaries
+        .record(1, &key_chunks[1], &entries(&[0x50, 0x30]))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Chunks #0 "), "{err}");
+    assert!(err.contains(&format!("{:?}", key_chunks[0])), "{err}");
+    assert!(err.contains(&format!("#1 {:?}", key_chunks[1])), "{err}");
+
+    let err = boundaries
+        .record(1, &key_chunks[1], &entries(&[0x50, 0x80]))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains(&format!("#2 {:?}", key_chunks[2])), "{err}");
+
+    boundaries
+        .record(1, &key_chunks[1], &entries(&[0x50, 0x60]))
+        .unwrap();
+    let err = boundaries
+        .record(3, &key_chunks[3], &entries(&[0x90]))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Chunks #2 "), "{err}");
+}
+
+/// Entry source with the second chunk overlapping the first one.
+#[derive(Debug)]
+struct OverlappingEntrySource<'a> {
+    inner: PostgresEntrySource<'a>,
+}
+
+#[async_trait]
+impl RecoveryEntrySource for OverlappingEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        let mut chunks: Vec<_> = AsyncTreeRecovery::hashed_key_ranges(chunk_count).collect();
+        chunks[1] = *chunks[0].start()..=*chunks[1].end();
+        Ok(chunks)
+    }
+
+    async fn load_entries(
+        &self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        self.inner
+            .load_entries(chunk_id, key_chunk, stop_receiver)
+            .await
+    }
+}
+
+#[tokio::test]
+async fn recovery_fails_on_overlapping_chunks() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (_health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: 2,
+        ..RecoveryOptions::for_tests(
+            OverlappingEntrySource {
+                inner: PostgresEntrySource {
+                    pool: &pool,
+                    replica: None,
+                    snapshot_miniblock: snapshot.miniblock,
+                    connection_retry_timeout: Duration::from_secs(60),
+                    connection_acquire_timeout: Duration::from_secs(30),
+                    use_copy: false,
+                },
+            },
+            RecoveryHealthUpdater::new(&health_updater, RecoveryMode::Normal, snapshot.log_count),
+        )
+    };
+    let err = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap_err();
+    assert_matches!(err, RecoveryError::Other(_));
+    let err = format!("{err:#}");
+    let key_chunks: Vec<_> = AsyncTreeRecovery::hashed_key_ranges(2).collect();
+    let overlapping_chunk = *key_chunks[0].start()..=*key_chunks[1].end();
+    assert!(err.contains(&format!("#0 {:?}", key_chunks[0])), "{err}");
+    assert!(err.contains(&format!("#1 {overlapping_chunk:?}")), "{err}");
+}
+
+/// Wrapper around [`RecoveryHealthUpdater`] recording health after each failed chunk.
+#[derive(Debug)]
+struct ChunkFailureRecorder<'a> {
+    inner: RecoveryHealthUpdater<'a>,
+    health_check: ReactiveHealthCheck,
+    failures: &'a StdMutex<Vec<(ChunkDescriptor, Health)>>,
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for ChunkFailureRecorder<'_> {
+    fn recovery_started(
+        &self,
+        chunk_count: usize,
+        recovered_chunk_count: usize,
+        recovered_entry_count: u64,
+    ) {
+        self.inner
+            .recovery_started(chunk_count, recovered_chunk_count, recovered_entry_count);
+    }
+
+    async fn chunk_recovered(&self, chunk: &ChunkDescriptor) {
+        self.inner.chunk_recovered(chunk).await;
+    }
+
+    async fn chunk_failed(&self, chunk: &ChunkDescriptor, err: &anyhow::Error) {
+        self.inner.chunk_failed(chunk, err).await;
+        let health = self.health_check.check_health().await;
+        self.failures.lock().unwrap().push((chunk.clone(), health));
+    }
+
+    fn recovery_failed(&self, err: &RecoveryError) {
+        self.inner.recovery_failed(err);
+    }
+}
+
+#[tokio::test]
+async fn chunk_failures_are_reported_via_health() {
+    const CHUNK_COUNT: usize = 4;
+    const FAILING_CHUNK_ID: usize = 1;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (health_check, health_updater) = ReactiveHealthCheck::new("tree");
+    let failures = StdMutex::default();
+    let recorder = ChunkFailureRecorder {
+        inner: RecoveryHealthUpdater::new(
+            &health_updater,
+            RecoveryMode::Normal,
+            snapshot.log_count,
+        ),
+        health_check: health_updater.subscribe(),
+        failures: &failures,
+    };
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        fail_fast: false,
+        ..RecoveryOptions::for_tests(
+            FailingEntrySource {
+                inner: PostgresEntrySource {
+                    pool: &pool,
+                    replica: None,
+                    snapshot_miniblock: snapshot.miniblock,
+                    connection_retry_timeout: Duration::from_secs(60),
+                    connection_acquire_timeout: Duration::from_secs(30),
+                    use_copy: false,
+                },
+                failing_chunk_ids: vec![FAILING_CHUNK_ID],
+            },
+            recorder,
+        )
+    };
+    let err = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap_err();
+    assert_matches!(err, RecoveryError::ChunksFailed(_));
+
+    let failures = failures.into_inner().unwrap();
+    assert_eq!(failures.len(), 1);
+    let (failed_chunk, health) = &failures[0];
+    assert_eq!(failed_chunk.index, FAILING_CHUNK_ID);
+    assert_eq!(failed_chunk.entry_count, None);
+    assert_matches!(health.status(), HealthStatus::Recovering);
+    let details = health.details().unwrap();
+    assert_eq!(details["mode"], "recovery");
+    let reported_chunks = details["failed_chunks"].as_array().unwrap();
+    assert_eq!(reported_chunks.len(), 1);
+    assert_eq!(reported_chunks[0]["chunk_id"], FAILING_CHUNK_ID);
+    assert_eq!(
+        reported_chunks[0]["key_range"],
+        serde_json::to_value(&failed_chunk.key_range).unwrap()
+    );
+    let error = reported_chunks[0]["error"].as_str().unwrap();
+    assert!(
+        error.contains(&format!("emulated error loading chunk #{FAILING_CHUNK_ID}")),
+        "{error}"
+    );
+
+    // After all chunks are processed, recovery is reported as failed.
+    let health = health_check.check_health().await;
+    assert_matches!(health.status(), HealthStatus::NotReady);
+    let details = health.details().unwrap();
+    assert_eq!(details["recovered_chunk_count"], CHUNK_COUNT - 1);
+    assert_eq!(details["error_kind"], "chunks_failed");
+}
+
+/// Entry source emulating a locked-up Postgres: loading entries blocks until a stop signal is received.
+#[derive(Debug)]
+struct BlockedEntrySource<'a>(PostgresEntrySource<'a>);
+
+#[async_trait]
+impl RecoveryEntrySource for BlockedEntrySource<'_> {
+    async fn key_chunks(
+        &self,
+        chunk_count: usize,
+    ) -> anyhow::Result<Vec<ops::RangeInclusive<H256>>> {
+        self.0.key_chunks(chunk_count).await
+    }
+
+    async fn load_entries(
+        &self,
+        _chunk_id: usize,
+        _key_chunk: &ops::RangeInclusive<H256>,
+        stop_receiver: &watch::Receiver<bool>,
+    ) -> anyhow::Result<Option<Vec<TreeEntry>>> {
+        wait_for_stop(stop_receiver.clone()).await;
+        Ok(None)
+    }
+}
+
+/// Records stall reports and sends a stop signal after the first one.
+#[derive(Debug)]
+struct StallRecorder {
+    reports: StdMutex<Vec<RecoveryStallReport>>,
+    stop_sender: watch::Sender<bool>,
+}
+
+#[async_trait]
+impl HandleRecoveryEvent for &StallRecorder {
+    fn recovery_stalled(&self, report: &RecoveryStallReport) {
+        self.reports.lock().unwrap().push(report.clone());
+        self.stop_sender.send_replace(true);
+    }
+}
+
+#[tokio::test]
+async fn watchdog_reports_stalled_recovery() {
+    const CHUNK_COUNT: usize = 4;
+    const CONCURRENCY: usize = 2;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recorder = StallRecorder {
+        reports: StdMutex::default(),
+        stop_sender,
+    };
+    let recovery_options = RecoveryOptions {
+        chunk_count: CHUNK_COUNT,
+        concurrency_limit: ConcurrencyLimits::fixed(CONCURRENCY),
+        watchdog: Some(WatchdogOptions {
+            check_interval: Duration::from_millis(10),
+            stall_threshold: Duration::from_millis(50),
+        }),
+        ..RecoveryOptions::for_tests(
+            BlockedEntrySource(PostgresEntrySource {
+                pool: &pool,
+                replica: None,
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            }),
+            &recorder,
+        )
+    };
+    let err = tokio::time::timeout(
+        Duration::from_secs(10),
+        tree.recover(snapshot, recovery_options, &pool, &stop_receiver),
+    )
+    .await
+    .expect("recovery is not stopped after a stall report")
+    .unwrap_err();
+    assert_matches!(err, RecoveryError::Interrupted);
+
+    let reports = recorder.reports.into_inner().unwrap();
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert!(
+        report.stalled_for >= Duration::from_millis(50),
+        "{report:?}"
+    );
+    assert_eq!(report.in_flight_chunk_count, CONCURRENCY);
+    let (chunk, elapsed) = report.longest_running_chunk.as_ref().unwrap();
+    assert!(chunk.index < CONCURRENCY, "{report:?}");
+    assert!(*elapsed <= report.stalled_for, "{report:?}");
+}
+
+async fn recover_tree_with_replica(
+    pool: &ConnectionPool,
+    replica: &SnapshotReplica<'_>,
+    root_hash: H256,
+    tree_path: PathBuf,
+    streaming_batch_size: Option<usize>,
+) -> (SnapshotParameters, AsyncTree) {
+    let snapshot = SnapshotParameters::new(pool, Some(replica), &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+    let tree = create_tree_recovery(tree_path, L1BatchNumber(1)).await;
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let recovery_options = RecoveryOptions {
+        chunk_count: 4,
+        streaming_batch_size,
+        replica: Some(replica),
+        ..RecoveryOptions::for_tests(
+            PostgresEntrySource {
+                pool,
+                replica: Some(replica),
+                snapshot_miniblock: snapshot.miniblock,
+                connection_retry_timeout: Duration::from_secs(60),
+                connection_acquire_timeout: Duration::from_secs(30),
+                use_copy: false,
+            },
+            TestEventListener::new(stop_sender),
+        )
+    };
+    let (tree, _) = tree
+        .recover(snapshot, recovery_options, pool, &stop_receiver)
+        .await
+        .unwrap();
+    (snapshot, tree)
+}
+
+#[test_casing(2, [None, Some(37)])]
+#[tokio::test]
+async fn recovery_using_synced_replica(streaming_batch_size: Option<usize>) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let replica_pool = ConnectionPool::test_pool().await;
+    let replica_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let replica_root_hash = prepare_recovery_snapshot(&replica_pool, &replica_dir).await;
+    assert_eq!(replica_root_hash, root_hash);
+
+    let replica = SnapshotReplica::new(&pool, &replica_pool, MiniblockNumber(1));
+    let tree_path = temp_dir.path().join("recovery");
+    let (snapshot, tree) =
+        recover_tree_with_replica(&pool, &replica, root_hash, tree_path, streaming_batch_size)
+            .await;
+
+    assert_eq!(tree.root_hash(), root_hash);
+    assert_eq!(replica.log_count().await, Some(snapshot.log_count));
+    assert_eq!(replica.fallback_count(), 0);
+}
+
+#[test_casing(2, [None, Some(37)])]
+#[tokio::test]
+async fn recovery_falls_back_to_primary_if_replica_lags(streaming_batch_size: Option<usize>) {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    // The replica only contains the genesis state, i.e., it misses all snapshot storage logs.
+    let replica_pool = ConnectionPool::test_pool().await;
+    let mut storage = replica_pool.access_storage().await.unwrap();
+    ensure_genesis_state(&mut storage, L2ChainId::from(270), &GenesisParams::mock())
+        .await
+        .unwrap();
+    drop(storage);
+
+    let replica = SnapshotReplica::new(&pool, &replica_pool, MiniblockNumber(1));
+    let tree_path = temp_dir.path().join("recovery");
+    let (snapshot, tree) =
+        recover_tree_with_replica(&pool, &replica, root_hash, tree_path, streaming_batch_size)
+            .await;
+
+    assert_eq!(tree.root_hash(), root_hash);
+    assert!(snapshot.log_count > 200);
+    assert_eq!(replica.log_count().await, None);
+    // Filtering chunks and loading each of 4 chunks fall back to the primary.
+    assert!(
+        replica.fallback_count() >= 5,
+        "{}",
+        replica.fallback_count()
+    );
+}
+
+/// Recovery listener recording indices of failed chunks.
+#[derive(Debug, Default)]
+struct FailedChunksListener(StdMutex<Vec<usize>>);
+
+#[async_trait]
+impl HandleRecoveryEvent for FailedChunksListener {
+    async fn chunk_failed(&self, chunk: &ChunkDescriptor, _err: &anyhow::Error) {
+        self.0.lock().unwrap().push(chunk.index);
+    }
+}
+
+#[tokio::test]
+async fn tree_panic_is_reported_with_chunk_range() {
+    const CHUNK_COUNT: usize = 4;
+    const PANICKING_CHUNK_ID: usize = 2;
+
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let root_hash = prepare_recovery_snapshot(&pool, &temp_dir).await;
+    let snapshot = SnapshotParameters::new(&pool, None, &mock_snapshot_recovery(root_hash))
+        .await
+        .unwrap();
+
+    let mut storage = pool.access_storage().await.unwrap();
+    let key_chunks = AsyncTreeRecovery::key_ranges(&mut storage, snapshot.miniblock, CHUNK_COUNT)
+        .await
+        .unwrap();
+    let chunk_entries = storage
+        .storage_logs_dal()
+        .get_tree_entries_for_miniblock(snapshot.miniblock, key_chunks[PANICKING_CHUNK_ID].clone())
+        .await
+        .unwrap();
+    drop(storage);
+
+    let mut tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    tree.panic_on_key(chunk_entries[0].key);
+    let listener = Arc::new(FailedChunksListener::default());
+    let recovery_options = RecoveryOptions::new(CHUNK_COUNT).events(listener.clone());
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let err = tree
+        .recover(snapshot, recovery_options, &pool, &stop_receiver)
+        .await
+        .unwrap_err();
+    assert_matches!(err, RecoveryError::Other(_));
+    let err = format!("{err:#}");
+    let expected_chunk = format!(
+        "chunk #{PANICKING_CHUNK_ID} {:?}",
+        key_chunks[PANICKING_CHUNK_ID]
+    );
+    assert!(err.contains(&expected_chunk), "{err}");
+    assert!(err.contains("Merkle tree panicked"), "{err}");
+    assert!(err.contains("Test panic on key"), "{err}");
+
+    // Loaders of the following chunks may fail as well once the tree applier stops.
+    let failed_chunks = listener.0.lock().unwrap();
+    assert_eq!(failed_chunks.first(), Some(&PANICKING_CHUNK_ID));
+    assert!(
+        failed_chunks.iter().all(|&id| id >= PANICKING_CHUNK_ID),
+        "{failed_chunks:?}"
+    );
+}
+
+#[tokio::test]
+async fn tree_recovery_cannot_be_extended_after_panic() {
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let mut tree = create_tree_recovery(temp_dir.path().join("recovery"), L1BatchNumber(1)).await;
+    tree.panic_on_key(U256::from(2));
+    let entries: Vec<_> = (1_u64..=3)
+        .map(|i| TreeEntry::new(U256::from(i), i, H256::from_low_u64_be(i)))
+        .collect();
+    let err = tree.extend(entries).await.unwrap_err();
+    let err = format!("{err:#}");
+    assert!(
+        err.contains("Merkle tree panicked extending with 3 entries"),
+        "{err}"
+    );
+    assert!(err.contains("Test panic on key"), "{err}");
+
+    // The tree may be partially updated, so it must refuse further updates instead of panicking.
+    let entries = vec![TreeEntry::new(U256::from(4), 4, H256::repeat_byte(4))];
+    let err = tree.extend(entries).await.unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("cannot be extended further"), "{err}");
+}
@@ -0,0 +1,182 @@
+//! Throttling Merkle tree recovery to a configured number of snapshot entries per second.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::watch;
+
+use super::wait_for_stop;
+use crate::metadata_calculator::metrics::RECOVERY_METRICS;
+
+#[derive(Debug)]
+struct ThrottleState {
+    /// Time at which all acquired entries are paid off at the configured rate.
+    paid_until: Instant,
+    /// Total number of entries applied to the tree since recovery was started or resumed.
+    applied_entry_count: u64,
+}
+
+/// Token bucket limiting the rate at which snapshot entries are loaded and applied to the tree. Since the number
+/// of chunk entries isn't known before they are loaded, a chunk loader acquires tokens for the estimated number
+/// of entries before querying them, and the tree applier settles the difference with the actual number
+/// of entries after extending the tree.
+///
+/// Acquisitions are queued in the order of arrival. An acquisition only waits until the previously acquired entries
+/// are paid off (minus the burst tolerance), so that a chunk larger than the burst tolerance isn't blocked forever.
+/// If the limit is not set, the throttle is a no-op.
+#[derive(Debug)]
+pub(super) struct EntryThrottle {
+    max_entries_per_second: Option<usize>,
+    estimated_chunk_entry_count: usize,
+    started_at: Instant,
+    state: Mutex<ThrottleState>,
+}
+
+impl EntryThrottle {
+    /// Time for which entries can be acquired ahead of the configured rate.
+    pub const BURST_TOLERANCE: Duration = Duration::from_secs(1);
+
+    pub fn new(max_entries_per_second: Option<usize>, estimated_chunk_entry_count: u64) -> Self {
+        let estimated_chunk_entry_count = usize::try_from(estimated_chunk_entry_count)
+            .unwrap_or(usize::MAX)
+            .max(1);
+        RECOVERY_METRICS.throttled_entries_per_second.set(0.0);
+        let now = Instant::now();
+        Self {
+            max_entries_per_second: max_entries_per_second.filter(|&limit| limit > 0),
+            estimated_chunk_entry_count,
+            started_at: now,
+            state: Mutex::new(ThrottleState {
+                paid_until: now,
+                applied_entry_count: 0,
+            }),
+        }
+    }
+
+    /// Returns the number of entries a chunk loader should acquire before loading all entries of a chunk.
+    pub fn estimated_chunk_entry_count(&self) -> usize {
+        self.estimated_chunk_entry_count
+    }
+
+    fn entries_duration(limit: usize, entry_count: usize) -> Duration {
+        Duration::from_secs_f64(entry_count as f64 / limit as f64)
+    }
+
+    /// Acquires the specified number of entries, waiting if necessary. Returns `false` if the wait was
+    /// interrupted by a stop signal.
+    pub async fn acquire(&self, entry_count: usize, stop_receiver: &watch::Receiver<bool>) -> bool {
+        let Some(limit) = self.max_entries_per_second else {
+            return true;
+        };
+        let delay = {
+            let mut state = self.state.lock().expect("throttle state is poisoned");
+            let now = Instant::now();
+            let paid_until = state.paid_until.max(now);
+            state.paid_until = paid_until + Self::entries_duration(limit, entry_count);
+            paid_until.saturating_duration_since(now + Self::BURST_TOLERANCE)
+        };
+        Self::sleep(delay, stop_receiver).await
+    }
+
+    /// Settles the number of entries `acquired` by a chunk loader with the `actual` number of entries applied
+    /// to the tree. Unused entries are returned to the bucket; if more entries were applied than acquired,
+    /// waits until the excess is paid off or a stop signal is received.
+    pub async fn settle(
+        &self,
+        acquired: usize,
+        actual: usize,
+        stop_receiver: &watch::Receiver<bool>,
+    ) {
+        let Some(limit) = self.max_entries_per_second else {
+            return;
+        };
+        {
+            let mut state = self.state.lock().expect("throttle state is poisoned");
+            state.applied_entry_count += actual as u64;
+            let elapsed = self.started_at.elapsed().as_secs_f64();
+            if elapsed > 0.0 {
+                let rate = state.applied_entry_count as f64 / elapsed;
+                RECOVERY_METRICS.throttled_entries_per_second.set(rate);
+            }
+            if actual <= acquired {
+                let refund = Self::entries_duration(limit, acquired - actual);
+                state.paid_until = state
+                    .paid_until
+                    .checked_sub(refund)
+                    .unwrap_or(state.paid_until);
+                return;
+            }
+        }
+        self.acquire(actual - acquired, stop_receiver).await;
+    }
+
+    async fn sleep(delay: Duration, stop_receiver: &watch::Receiver<bool>) -> bool {
+        if delay.is_zero() {
+            return true;
+        }
+        let stop = wait_for_stop(stop_receiver.clone());
+        tokio::time::timeout(delay, stop).await.is_err()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unlimited_throttle_never_waits() {
+        let throttle = EntryThrottle::new(None, 1_000_000);
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        let started_at = Instant::now();
+        for _ in 0..10 {
+            assert!(throttle.acquire(1_000_000, &stop_receiver).await);
+            throttle.settle(1_000_000, 2_000_000, &stop_receiver).await;
+        }
+        assert!(started_at.elapsed() < EntryThrottle::BURST_TOLERANCE);
+    }
+
+    #[tokio::test]
+    async fn throttle_limits_entry_rate() {
+        let throttle = EntryThrottle::new(Some(1_000), 100);
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        let started_at = Instant::now();
+        // The first 1,000 entries are within the burst tolerance; the last acquisition should wait ~400ms.
+        for _ in 0..15 {
+            assert!(throttle.acquire(100, &stop_receiver).await);
+        }
+        let elapsed = started_at.elapsed();
+        assert!(elapsed >= Duration::from_millis(350), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn throttle_refunds_unused_entries() {
+        let throttle = EntryThrottle::new(Some(1_000), 100);
+        let (_stop_sender, stop_receiver) = watch::channel(false);
+        let started_at = Instant::now();
+        for _ in 0..20 {
+            assert!(throttle.acquire(100, &stop_receiver).await);
+            throttle.settle(100, 10, &stop_receiver).await;
+        }
+        assert!(started_at.elapsed() < EntryThrottle::BURST_TOLERANCE);
+    }
+
+    #[tokio::test]
+    async fn throttle_wait_is_interrupted_by_stop_signal() {
+        let throttle = EntryThrottle::new(Some(1), 100);
+        let (stop_sender, stop_receiver) = watch::channel(false);
+        assert!(throttle.acquire(100, &stop_receiver).await);
+
+        let started_at = Instant::now();
+        let acquire = throttle.acquire(100, &stop_receiver);
+        let stop = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            stop_sender.send_replace(true);
+        };
+        let (acquired, ()) = tokio::join!(acquire, stop);
+        assert!(!acquired);
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+    }
+}
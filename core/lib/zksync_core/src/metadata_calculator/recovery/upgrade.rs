@@ -0,0 +1,126 @@
+//! Upgrading a Merkle tree processed in the lightweight mode to the full mode.
+//!
+//! Tree data doesn't depend on the tree mode; the full mode additionally produces witness inputs
+//! and commitments for each processed L1 batch. Thus, a tree (e.g., recovered from a snapshot) processed
+//! in the lightweight mode can be switched to the full mode at any time; by default, the tree just continues
+//! processing L1 batches in the full mode, and witness inputs for L1 batches processed in the lightweight mode
+//! are never produced. The tree records the first L1 batch processed in the lightweight mode in its manifest,
+//! so that these L1 batches can be backfilled if configured. In this case, the tree is reverted to
+//! the preceding L1 batch, and the remaining L1 batches are re-processed by the tree updater in the full mode
+//! batch by batch. The updater persists its progress and honors the stop signal, so the backfill is resumed
+//! after a node restart. The backfill requires the tree version for the preceding L1 batch, so it's impossible
+//! if this version was pruned.
+
+use anyhow::Context as _;
+use zksync_config::configs::database::MerkleTreeMode;
+use zksync_dal::ConnectionPool;
+use zksync_types::{L1BatchNumber, H256};
+
+use crate::metadata_calculator::{helpers::AsyncTree, MetadataCalculatorRecoveryConfig};
+
+/// Backfills L1 batches processed by the tree in the lightweight mode if the calculator runs in the full mode
+/// and the backfill is enabled in `config`. Returns an error if the tree cannot be upgraded because the required
+/// tree version was pruned.
+pub(super) async fn upgrade_to_full(
+    tree: &mut AsyncTree,
+    config: &MetadataCalculatorRecoveryConfig,
+    pool: &ConnectionPool,
+) -> anyhow::Result<()> {
+    let Some(upgrade) = check_upgrade_to_full(tree, config)? else {
+        if tree.mode() == MerkleTreeMode::Full {
+            if let Some(lightweight_since) = tree.lightweight_since()? {
+                let next_l1_batch = tree.next_l1_batch_number();
+                tracing::warn!(
+                    "Merkle tree has processed L1 batches starting from #{lightweight_since} in the lightweight \
+                     mode; continuing in the full mode from L1 batch #{next_l1_batch}. Witness inputs and commitments \
+                     won't be produced for L1 batches #{lightweight_since}..#{next_l1_batch}; to backfill them, \
+                     enable `allow_lightweight_tree_upgrade` in the config"
+                );
+            }
+        }
+        return Ok(());
+    };
+    let TreeUpgrade {
+        lightweight_since,
+        last_l1_batch_to_keep,
+        tree_root_hash,
+    } = upgrade;
+    let next_l1_batch = tree.next_l1_batch_number();
+    let mut storage = pool.access_storage().await?;
+    let postgres_root_hash = storage
+        .blocks_dal()
+        .get_l1_batch_state_root(last_l1_batch_to_keep)
+        .await
+        .with_context(|| {
+            format!("Failed getting root hash for L1 batch #{last_l1_batch_to_keep}")
+        })?;
+    drop(storage);
+    // Postgres may not contain the L1 batch if it's the snapshot L1 batch the tree was recovered to.
+    if let Some(postgres_root_hash) = postgres_root_hash {
+        anyhow::ensure!(
+            tree_root_hash == postgres_root_hash,
+            "Merkle tree root hash for L1 batch #{last_l1_batch_to_keep} ({tree_root_hash:?}) differs \
+             from the one in Postgres ({postgres_root_hash:?}); cannot upgrade the tree to the full mode"
+        );
+    }
+
+    tracing::info!(
+        "Upgrading Merkle tree processed in the lightweight mode since L1 batch #{lightweight_since} \
+         to the full mode; reverting the tree to L1 batch #{last_l1_batch_to_keep}. L1 batches \
+         #{lightweight_since}..#{next_l1_batch} will be re-processed to backfill witness inputs and commitments"
+    );
+    tree.revert_logs(last_l1_batch_to_keep);
+    tree.clear_lightweight_since();
+    tree.save().await;
+    Ok(())
+}
+
+/// Parameters of upgrading the tree to the full mode.
+#[derive(Debug)]
+pub(super) struct TreeUpgrade {
+    lightweight_since: L1BatchNumber,
+    last_l1_batch_to_keep: L1BatchNumber,
+    tree_root_hash: H256,
+}
+
+/// Checks whether the tree needs to be upgraded to the full mode, and whether it can be upgraded based on the tree data
+/// alone. Returns `None` if no upgrade is required or the backfill isn't enabled in `config`.
+pub(super) fn check_upgrade_to_full(
+    tree: &AsyncTree,
+    config: &MetadataCalculatorRecoveryConfig,
+) -> anyhow::Result<Option<TreeUpgrade>> {
+    if tree.mode() != MerkleTreeMode::Full {
+        return Ok(None);
+    }
+    let Some(lightweight_since) = tree.lightweight_since()? else {
+        return Ok(None);
+    };
+    if !config.allow_lightweight_tree_upgrade {
+        return Ok(None);
+    }
+
+    let last_l1_batch_to_keep = lightweight_since
+        .0
+        .checked_sub(1)
+        .map(L1BatchNumber)
+        .context("genesis L1 batch cannot be processed in the lightweight mode")?;
+    // Check this before anything else; otherwise, the tree would be reverted to a partially pruned version.
+    anyhow::ensure!(
+        tree.is_l1_batch_retained(last_l1_batch_to_keep),
+        "Merkle tree cannot backfill L1 batches #{lightweight_since}..#{next_l1_batch} processed in the lightweight \
+         mode: its version for L1 batch #{last_l1_batch_to_keep} was pruned. Disable \
+         `allow_lightweight_tree_upgrade` in the config to continue in the full mode without the backfill, \
+         or enable `allow_tree_reset` so that the tree is rebuilt in the full mode",
+        next_l1_batch = tree.next_l1_batch_number()
+    );
+    let tree_root_hash = tree
+        .l1_batch_root_hash(last_l1_batch_to_keep)
+        .with_context(|| {
+            format!("Merkle tree doesn't contain root hash for L1 batch #{last_l1_batch_to_keep}")
+        })?;
+    Ok(Some(TreeUpgrade {
+        lightweight_since,
+        last_l1_batch_to_keep,
+        tree_root_hash,
+    }))
+}
@@ -0,0 +1,589 @@
+//! Sampled verification of the recovered Merkle tree against Postgres, checks of snapshot leaf indices,
+//! and verification of Merkle proofs produced by the recovered tree.
+
+use std::{collections::BTreeMap, fmt, ops};
+
+use anyhow::Context as _;
+use rand::Rng;
+use zksync_crypto::hasher::blake2::Blake2Hasher;
+use zksync_dal::StorageProcessor;
+use zksync_merkle_tree::{Key, TreeEntry, TreeEntryWithProof};
+use zksync_types::{MiniblockNumber, H256, U256};
+use zksync_utils::{h256_to_u256, u256_to_h256};
+
+use super::RecoveryError;
+use crate::metadata_calculator::{
+    helpers::{AsyncTree, AsyncTreeRecovery},
+    metrics::{RecoveryStage, RECOVERY_METRICS},
+};
+
+/// Mismatch between a sampled entry in Postgres and the recovered tree.
+#[derive(Debug)]
+struct EntryMismatch {
+    chunk_id: usize,
+    postgres_entry: TreeEntry,
+    tree_entry: TreeEntry,
+}
+
+/// Wrapper to format mismatches in an error message.
+struct MismatchesReport<'a> {
+    mismatches: &'a [EntryMismatch],
+    chunk_count: usize,
+}
+
+impl fmt::Display for MismatchesReport<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, mismatch) in self.mismatches.iter().enumerate() {
+            if i > 0 {
+                formatter.write_str("; ")?;
+            }
+            write!(
+                formatter,
+                "chunk {} of {}: key {:0>64x} (Postgres: {:?}, tree: {:?})",
+                mismatch.chunk_id,
+                self.chunk_count,
+                mismatch.postgres_entry.key,
+                mismatch.postgres_entry,
+                mismatch.tree_entry
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns a uniformly distributed random key in the specified range.
+fn random_key(rng: &mut impl Rng, range: &ops::RangeInclusive<H256>) -> H256 {
+    let start = h256_to_u256(*range.start());
+    let span = h256_to_u256(*range.end()) - start;
+    let random = U256::from_big_endian(&rng.gen::<[u8; 32]>());
+    let offset = if span == U256::MAX {
+        random
+    } else {
+        random % (span + 1)
+    };
+    u256_to_h256(start + offset)
+}
+
+/// Verifies the recovered `tree` by sampling `samples_per_chunk` entries in each of `key_chunks` from Postgres
+/// and comparing them with the corresponding tree entries. Each sample is the first entry in the snapshot
+/// following a random key in the chunk range.
+pub(super) async fn verify_recovered_tree(
+    tree: &AsyncTree,
+    storage: &mut StorageProcessor<'_>,
+    snapshot_miniblock: MiniblockNumber,
+    key_chunks: &[ops::RangeInclusive<H256>],
+    samples_per_chunk: usize,
+    rng: &mut impl Rng,
+) -> anyhow::Result<()> {
+    let verification_latency = RECOVERY_METRICS.latency[&RecoveryStage::Verify].start();
+    let (chunk_ids, sampled_ranges): (Vec<_>, Vec<_>) = key_chunks
+        .iter()
+        .enumerate()
+        .flat_map(|(chunk_id, chunk)| {
+            let sampled_ranges: Vec<_> = (0..samples_per_chunk)
+                .map(|_| random_key(rng, chunk)..=*chunk.end())
+                .collect();
+            sampled_ranges
+                .into_iter()
+                .map(move |range| (chunk_id, range))
+        })
+        .unzip();
+
+    let postgres_entries = storage
+        .storage_logs_dal()
+        .get_chunk_starts_for_miniblock(snapshot_miniblock, &sampled_ranges)
+        .await
+        .context("Failed getting sampled entries from Postgres")?;
+    let (chunk_ids, postgres_entries): (Vec<_>, Vec<_>) = chunk_ids
+        .into_iter()
+        .zip(postgres_entries)
+        .filter_map(|(chunk_id, entry)| {
+            let entry = entry?;
+            Some((
+                chunk_id,
+                TreeEntry::new(entry.key, entry.leaf_index, entry.value),
+            ))
+        })
+        .unzip();
+
+    let l1_batch_number = tree.next_l1_batch_number() - 1;
+    let keys = postgres_entries.iter().map(|entry| entry.key).collect();
+    let tree_entries = tree
+        .reader()
+        .entries_with_proofs(l1_batch_number, keys)
+        .await
+        .context("Failed getting sampled entries from the recovered tree")?;
+
+    let sampled_entry_count = postgres_entries.len();
+    let mismatches: Vec<_> = chunk_ids
+        .into_iter()
+        .zip(postgres_entries)
+        .zip(tree_entries)
+        .filter_map(|((chunk_id, postgres_entry), tree_entry)| {
+            let tree_entry = tree_entry.base;
+            (tree_entry != postgres_entry).then_some(EntryMismatch {
+                chunk_id,
+                postgres_entry,
+                tree_entry,
+            })
+        })
+        .collect();
+    let verification_latency = verification_latency.observe();
+    RECOVERY_METRICS
+        .verified_entry_count
+        .set(sampled_entry_count);
+    RECOVERY_METRICS
+        .mismatched_entry_count
+        .set(mismatches.len());
+
+    let chunk_count = key_chunks.len();
+    if mismatches.is_empty() {
+        tracing::info!(
+            "Verified {sampled_entry_count} entries sampled from {chunk_count} chunks of the recovered Merkle tree \
+             against Postgres in {verification_latency:?}"
+        );
+        return Ok(());
+    }
+
+    let report = MismatchesReport {
+        mismatches: &mismatches,
+        chunk_count,
+    };
+    tracing::error!(
+        "Found {} mismatches among {sampled_entry_count} entries sampled from {chunk_count} chunks \
+         of the recovered Merkle tree",
+        mismatches.len()
+    );
+    anyhow::bail!(
+        "Recovered Merkle tree doesn't match Postgres snapshot for miniblock #{snapshot_miniblock}; \
+         mismatched entries: {report}"
+    );
+}
+
+/// Leaf index statistics for a Postgres snapshot and the Merkle tree recovered from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeafIndexStats {
+    /// Number of storage logs in the snapshot.
+    pub log_count: u64,
+    /// Number of distinct leaf indices of snapshot storage logs.
+    pub distinct_leaf_index_count: u64,
+    /// Maximum leaf index of snapshot storage logs.
+    pub max_leaf_index: u64,
+    /// Number of leaves in the recovered tree.
+    pub leaf_count: u64,
+}
+
+impl fmt::Display for LeafIndexStats {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{} storage logs with {} distinct leaf indices (max leaf index: {}); recovered tree has {} leaves \
+             and will assign leaf index {} next",
+            self.log_count,
+            self.distinct_leaf_index_count,
+            self.max_leaf_index,
+            self.leaf_count,
+            self.next_leaf_index()
+        )
+    }
+}
+
+impl LeafIndexStats {
+    /// Returns the leaf index the recovered tree will assign to the next inserted key.
+    fn next_leaf_index(&self) -> u64 {
+        self.leaf_count + 1
+    }
+
+    /// Checks that leaf indices of snapshot storage logs are `1..=log_count` (i.e., there are no duplicated
+    /// or skipped indices), and that the tree contains all snapshot entries and will continue the leaf index
+    /// sequence.
+    fn is_consistent(&self) -> bool {
+        self.leaf_count == self.log_count
+            && self.distinct_leaf_index_count == self.log_count
+            && self.next_leaf_index() == self.max_leaf_index + 1
+    }
+}
+
+/// Checks leaf indices of the snapshot for `snapshot_miniblock` against the `tree` recovered from it.
+/// Duplicated or skipped leaf indices don't necessarily affect the root hash check, but they would break
+/// witness generation for L1 batches processed after recovery.
+pub(super) async fn verify_leaf_indices(
+    tree: &mut AsyncTreeRecovery,
+    storage: &mut StorageProcessor<'_>,
+    snapshot_miniblock: MiniblockNumber,
+    log_count: u64,
+) -> Result<(), RecoveryError> {
+    let distinct_leaf_index_count = storage
+        .storage_logs_dal()
+        .count_distinct_leaf_indices_for_miniblock(snapshot_miniblock)
+        .await
+        .context("Failed counting distinct leaf indices in snapshot")?;
+    let max_leaf_index = storage
+        .storage_logs_dal()
+        .max_leaf_index_for_miniblock(snapshot_miniblock)
+        .await
+        .context("Failed getting max leaf index in snapshot")?;
+    let stats = LeafIndexStats {
+        log_count,
+        distinct_leaf_index_count,
+        max_leaf_index,
+        leaf_count: tree.leaf_count().await,
+    };
+
+    if !stats.is_consistent() {
+        return Err(RecoveryError::LeafIndexMismatch {
+            miniblock: snapshot_miniblock,
+            stats,
+        });
+    }
+    tracing::info!("Checked leaf indices in snapshot for miniblock #{snapshot_miniblock}: {stats}");
+    Ok(())
+}
+
+/// Maximum number of chunks listed in a [`ChunkLeafIndexStats::verify()`] error.
+const MAX_REPORTED_CHUNKS: usize = 10;
+
+/// Leaf indices of entries loaded for a single chunk.
+#[derive(Debug)]
+struct ChunkLeafIndices {
+    key_chunk: ops::RangeInclusive<H256>,
+    /// Minimum and maximum leaf index; `None` if the chunk is empty.
+    min_max: Option<(u64, u64)>,
+    entry_count: u64,
+}
+
+impl fmt::Display for ChunkLeafIndices {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{:?} with {} entries",
+            self.key_chunk, self.entry_count
+        )?;
+        if let Some((min, max)) = self.min_max {
+            write!(formatter, " (leaf indices {min}..={max})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Per-chunk leaf index statistics collected by the tree applier during recovery. Unlike [`verify_leaf_indices()`],
+/// which checks aggregated leaf indices in Postgres, these stats allow to attribute anomalous leaf indices
+/// to specific chunks. Only chunks recovered since recovery was (re)started are tracked.
+#[derive(Debug, Default)]
+pub(super) struct ChunkLeafIndexStats {
+    by_chunk_id: BTreeMap<usize, ChunkLeafIndices>,
+}
+
+impl ChunkLeafIndexStats {
+    /// Records leaf indices of `entries` loaded for a chunk (all chunk entries or a streamed batch). If `reset` is set,
+    /// previously recorded indices for the chunk are discarded (e.g., because the chunk loader was retried).
+    pub fn observe(
+        &mut self,
+        chunk_id: usize,
+        key_chunk: &ops::RangeInclusive<H256>,
+        entries: &[TreeEntry],
+        reset: bool,
+    ) {
+        let indices = self
+            .by_chunk_id
+            .entry(chunk_id)
+            .or_insert_with(|| ChunkLeafIndices {
+                key_chunk: key_chunk.clone(),
+                min_max: None,
+                entry_count: 0,
+            });
+        if reset {
+            indices.min_max = None;
+            indices.entry_count = 0;
+        }
+        for entry in entries {
+            let leaf_index = entry.leaf_index;
+            indices.min_max = Some(match indices.min_max {
+                Some((min, max)) => (min.min(leaf_index), max.max(leaf_index)),
+                None => (leaf_index, leaf_index),
+            });
+        }
+        indices.entry_count += entries.len() as u64;
+    }
+
+    /// Checks that recorded leaf indices are in the range `1..=log_count`. If `is_complete` is set (i.e., all chunks
+    /// were recovered since recovery was started), additionally checks that the minimum leaf index is 1,
+    /// the maximum leaf index is `log_count`, and that chunks contain `log_count` entries in total.
+    pub fn verify(
+        &self,
+        snapshot_miniblock: MiniblockNumber,
+        log_count: u64,
+        is_complete: bool,
+    ) -> Result<(), RecoveryError> {
+        let mut problems = vec![];
+        let out_of_range_chunks: Vec<_> = self
+            .by_chunk_id
+            .iter()
+            .filter(|(_, indices)| {
+                indices
+                    .min_max
+                    .is_some_and(|(min, max)| min == 0 || max > log_count)
+            })
+            .collect();
+        if !out_of_range_chunks.is_empty() {
+            let reported_chunks = out_of_range_chunks
+                .iter()
+                .take(MAX_REPORTED_CHUNKS)
+                .map(|(chunk_id, indices)| format!("chunk #{chunk_id} {indices}"));
+            problems.push(format!(
+                "leaf indices outside 1..={log_count} in {} chunk(s): {}",
+                out_of_range_chunks.len(),
+                reported_chunks.collect::<Vec<_>>().join(", ")
+            ));
+        }
+
+        if is_complete {
+            let min_chunk = self
+                .by_chunk_id
+                .iter()
+                .filter_map(|(chunk_id, indices)| Some((indices.min_max?.0, chunk_id, indices)))
+                .min_by_key(|(min, ..)| *min);
+            let max_chunk = self
+                .by_chunk_id
+                .iter()
+                .filter_map(|(chunk_id, indices)| Some((indices.min_max?.1, chunk_id, indices)))
+                .max_by_key(|(max, ..)| *max);
+            match min_chunk {
+                Some((min, chunk_id, indices)) if min != 1 => problems.push(format!(
+                    "minimum leaf index is {min} rather than 1 (chunk #{chunk_id} {indices})"
+                )),
+                _ => { /* OK, or there are no entries, which is checked below */ }
+            }
+            match max_chunk {
+                Some((max, chunk_id, indices)) if max != log_count => problems.push(format!(
+                    "maximum leaf index is {max} rather than {log_count} (chunk #{chunk_id} {indices})"
+                )),
+                _ => { /* OK, or there are no entries, which is checked below */ }
+            }
+            let total_entry_count: u64 = self
+                .by_chunk_id
+                .values()
+                .map(|indices| indices.entry_count)
+                .sum();
+            if total_entry_count != log_count {
+                problems.push(format!(
+                    "chunks contain {total_entry_count} entries in total rather than {log_count}"
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(RecoveryError::ChunkLeafIndexMismatch {
+                miniblock: snapshot_miniblock,
+                details: problems.join("; "),
+            })
+        }
+    }
+}
+
+/// Maximum number of entries with invalid proofs listed in a [`verify_proofs()`] error.
+const MAX_REPORTED_INVALID_PROOFS: usize = 10;
+
+/// Verifies Merkle proofs of `entries` (e.g., obtained from the Merkle tree API) against the trusted tree `root_hash`.
+///
+/// # Errors
+///
+/// Returns an error listing entries with invalid proofs.
+pub fn verify_proofs(root_hash: H256, entries: &[TreeEntryWithProof]) -> anyhow::Result<()> {
+    let invalid_proofs: Vec<_> = entries
+        .iter()
+        .filter_map(|entry| {
+            let err = entry.try_verify(&Blake2Hasher, root_hash).err()?;
+            Some(format!("key {:0>64x}: {err}", entry.base.key))
+        })
+        .collect();
+    if invalid_proofs.is_empty() {
+        return Ok(());
+    }
+
+    let reported_proofs = &invalid_proofs[..invalid_proofs.len().min(MAX_REPORTED_INVALID_PROOFS)];
+    anyhow::bail!(
+        "{} of {} Merkle proofs are invalid for root hash {root_hash:?}: {}",
+        invalid_proofs.len(),
+        entries.len(),
+        reported_proofs.join("; ")
+    );
+}
+
+/// Verifies Merkle proofs for `sample_count` entries of the recovered `tree` against its root hash. Each sample
+/// is the first tree entry following a random key; if there is no such entry, the proof of absence
+/// for the random key is verified instead.
+pub(super) async fn verify_recovered_proofs(
+    tree: &mut AsyncTree,
+    sample_count: usize,
+    rng: &mut impl Rng,
+) -> anyhow::Result<()> {
+    let verification_latency = RECOVERY_METRICS.latency[&RecoveryStage::VerifyProofs].start();
+    let full_range = H256::zero()..=H256::repeat_byte(0xff);
+    let mut keys = Vec::with_capacity(sample_count);
+    for _ in 0..sample_count {
+        let random_key = random_key(rng, &full_range);
+        let page = tree.entries_range(random_key..=*full_range.end(), 1).await;
+        let key: Key = page
+            .entries
+            .first()
+            .map_or_else(|| h256_to_u256(random_key), |entry| entry.key);
+        keys.push(key);
+    }
+    keys.sort_unstable();
+    keys.dedup();
+
+    let entries = tree
+        .entries_with_proofs(keys)
+        .await
+        .context("Failed getting sampled entries with proofs from the recovered tree")?;
+    verify_proofs(tree.root_hash(), &entries)?;
+    let verification_latency = verification_latency.observe();
+    tracing::info!(
+        "Verified Merkle proofs for {} entries sampled from the recovered tree in {verification_latency:?}",
+        entries.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata_calculator::recovery::RecoveryErrorKind;
+
+    #[test]
+    fn checking_leaf_index_stats() {
+        let stats = LeafIndexStats {
+            log_count: 100,
+            distinct_leaf_index_count: 100,
+            max_leaf_index: 100,
+            leaf_count: 100,
+        };
+        assert!(stats.is_consistent());
+        assert_eq!(stats.next_leaf_index(), 101);
+
+        let duplicated_index = LeafIndexStats {
+            distinct_leaf_index_count: 99,
+            ..stats
+        };
+        assert!(!duplicated_index.is_consistent());
+        let skipped_index = LeafIndexStats {
+            max_leaf_index: 101,
+            ..stats
+        };
+        assert!(!skipped_index.is_consistent());
+        let missing_leaf = LeafIndexStats {
+            leaf_count: 99,
+            ..stats
+        };
+        assert!(!missing_leaf.is_consistent());
+    }
+
+    fn entries_with_leaf_indices(leaf_indices: ops::RangeInclusive<u64>) -> Vec<TreeEntry> {
+        leaf_indices
+            .map(|i| TreeEntry::new(U256::from(i), i, H256::zero()))
+            .collect()
+    }
+
+    #[test]
+    fn checking_chunk_leaf_index_stats() {
+        let key_chunks: Vec<_> = AsyncTreeRecovery::hashed_key_ranges(3).collect();
+        let mut stats = ChunkLeafIndexStats::default();
+        stats.observe(0, &key_chunks[0], &entries_with_leaf_indices(1..=10), true);
+        // Retried streamed chunk.
+        stats.observe(2, &key_chunks[2], &entries_with_leaf_indices(1..=5), true);
+        stats.observe(2, &key_chunks[2], &entries_with_leaf_indices(21..=25), true);
+        stats.observe(
+            2,
+            &key_chunks[2],
+            &entries_with_leaf_indices(26..=30),
+            false,
+        );
+        stats.verify(MiniblockNumber(1), 30, false).unwrap();
+        stats.verify(MiniblockNumber(1), 30, true).unwrap_err();
+
+        stats.observe(1, &key_chunks[1], &entries_with_leaf_indices(11..=20), true);
+        stats.verify(MiniblockNumber(1), 30, true).unwrap();
+        stats.observe(1, &key_chunks[1], &[], true);
+        stats.observe(1, &key_chunks[1], &[], false);
+        let err = stats
+            .verify(MiniblockNumber(1), 30, true)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("chunks contain 20 entries in total rather than 30"),
+            "{err}"
+        );
+        assert!(!err.contains("leaf index is"), "{err}");
+    }
+
+    #[test]
+    fn anomalous_chunk_leaf_indices_are_reported() {
+        let key_chunks: Vec<_> = AsyncTreeRecovery::hashed_key_ranges(2).collect();
+        let mut stats = ChunkLeafIndexStats::default();
+        stats.observe(0, &key_chunks[0], &entries_with_leaf_indices(0..=9), true);
+        stats.observe(1, &key_chunks[1], &entries_with_leaf_indices(10..=21), true);
+
+        let err = stats.verify(MiniblockNumber(1), 20, false).unwrap_err();
+        assert_eq!(err.kind(), RecoveryErrorKind::LeafIndexMismatch);
+        let err = err.to_string();
+        assert!(err.contains("miniblock #1"), "{err}");
+        assert!(
+            err.contains("leaf indices outside 1..=20 in 2 chunk(s)"),
+            "{err}"
+        );
+        assert!(
+            err.contains(&format!(
+                "chunk #0 {:?} with 10 entries (leaf indices 0..=9)",
+                key_chunks[0]
+            )),
+            "{err}"
+        );
+        assert!(
+            err.contains(&format!(
+                "chunk #1 {:?} with 12 entries (leaf indices 10..=21)",
+                key_chunks[1]
+            )),
+            "{err}"
+        );
+
+        let err = stats
+            .verify(MiniblockNumber(1), 20, true)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("minimum leaf index is 0 rather than 1 (chunk #0 "),
+            "{err}"
+        );
+        assert!(
+            err.contains("maximum leaf index is 21 rather than 20 (chunk #1 "),
+            "{err}"
+        );
+        assert!(
+            err.contains("chunks contain 22 entries in total rather than 20"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn leaf_index_stats_in_error_message() {
+        let err = RecoveryError::LeafIndexMismatch {
+            miniblock: MiniblockNumber(1),
+            stats: LeafIndexStats {
+                log_count: 100,
+                distinct_leaf_index_count: 99,
+                max_leaf_index: 100,
+                leaf_count: 100,
+            },
+        };
+        let err = err.to_string();
+        assert!(err.contains("miniblock #1"), "{err}");
+        assert!(
+            err.contains("100 storage logs with 99 distinct leaf indices (max leaf index: 100)"),
+            "{err}"
+        );
+        assert!(err.contains("assign leaf index 101 next"), "{err}");
+    }
+}
@@ -0,0 +1,230 @@
+//! Watchdog reporting stalled Merkle tree recovery.
+
+use std::{
+    collections::HashMap,
+    ops,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::watch;
+use zksync_types::H256;
+
+use super::{wait_for_stop, ChunkDescriptor, HandleRecoveryEvent};
+use crate::metadata_calculator::metrics::RECOVERY_METRICS;
+
+/// Options for [`RecoveryWatchdog`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct WatchdogOptions {
+    /// Interval between watchdog checks.
+    pub check_interval: Duration,
+    /// Recovery is considered stalled if no chunk has finished loading within this threshold.
+    pub stall_threshold: Duration,
+}
+
+/// Report on stalled Merkle tree recovery passed to [`HandleRecoveryEvent::recovery_stalled()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryStallReport {
+    /// Time elapsed since a chunk has last finished loading, or since recovery was started or resumed
+    /// if no chunks have finished yet.
+    pub stalled_for: Duration,
+    /// Number of chunks that are currently being loaded.
+    pub in_flight_chunk_count: usize,
+    /// Longest-running in-flight chunk together with the time elapsed since it was started.
+    /// [`ChunkDescriptor::entry_count`] is always `None`.
+    pub longest_running_chunk: Option<(ChunkDescriptor, Duration)>,
+}
+
+#[derive(Debug)]
+struct WatchdogState {
+    /// In-flight chunks keyed by the chunk index.
+    in_flight_chunks: HashMap<usize, (ops::RangeInclusive<H256>, Instant)>,
+    last_progress: Instant,
+    /// Whether recovery is paused; paused recovery is never considered stalled.
+    is_paused: bool,
+}
+
+/// Watchdog tracking in-flight recovery chunks. If no chunk has finished loading within the stall threshold
+/// (e.g., because all chunk tasks are stuck waiting on Postgres), the watchdog logs a warning with
+/// the longest-running chunk, updates the `stalled` metric and notifies recovery event handlers.
+#[derive(Debug)]
+pub(super) struct RecoveryWatchdog {
+    state: Mutex<WatchdogState>,
+}
+
+impl RecoveryWatchdog {
+    pub fn new() -> Self {
+        RECOVERY_METRICS.stalled.set(Duration::ZERO);
+        Self {
+            state: Mutex::new(WatchdogState {
+                in_flight_chunks: HashMap::new(),
+                last_progress: Instant::now(),
+                is_paused: false,
+            }),
+        }
+    }
+
+    /// Tracks the specified chunk as in-flight until the returned guard is dropped. Dropping the guard
+    /// counts as recovery progress.
+    pub fn track_chunk(
+        &self,
+        chunk_id: usize,
+        key_range: ops::RangeInclusive<H256>,
+    ) -> ChunkGuard<'_> {
+        let mut state = self.state.lock().expect("watchdog state is poisoned");
+        state
+            .in_flight_chunks
+            .insert(chunk_id, (key_range, Instant::now()));
+        ChunkGuard {
+            watchdog: self,
+            chunk_id,
+        }
+    }
+
+    fn finish_chunk(&self, chunk_id: usize) {
+        let mut state = self.state.lock().expect("watchdog state is poisoned");
+        state.in_flight_chunks.remove(&chunk_id);
+        state.last_progress = Instant::now();
+    }
+
+    /// Marks recovery as paused or resumed. The stall timer is restarted on resume.
+    pub fn set_paused(&self, is_paused: bool) {
+        let mut state = self.state.lock().expect("watchdog state is poisoned");
+        if state.is_paused && !is_paused {
+            state.last_progress = Instant::now();
+        }
+        state.is_paused = is_paused;
+    }
+
+    /// Checks whether recovery is stalled at `now`, returning a report if it is.
+    fn check(&self, now: Instant, stall_threshold: Duration) -> Option<RecoveryStallReport> {
+        let state = self.state.lock().expect("watchdog state is poisoned");
+        if state.is_paused {
+            return None;
+        }
+        let stalled_for = now.saturating_duration_since(state.last_progress);
+        if stalled_for < stall_threshold {
+            return None;
+        }
+
+        let longest_running_chunk = state
+            .in_flight_chunks
+            .iter()
+            .min_by_key(|(&index, (_, started_at))| (*started_at, index))
+            .map(|(&index, (key_range, started_at))| {
+                let chunk = ChunkDescriptor {
+                    index,
+                    key_range: key_range.clone(),
+                    entry_count: None,
+                };
+                (chunk, now.saturating_duration_since(*started_at))
+            });
+        Some(RecoveryStallReport {
+            stalled_for,
+            in_flight_chunk_count: state.in_flight_chunks.len(),
+            longest_running_chunk,
+        })
+    }
+
+    /// Periodically checks whether recovery is stalled until a stop signal is received. The returned future
+    /// is expected to be dropped once recovery finishes.
+    pub async fn run(
+        &self,
+        options: WatchdogOptions,
+        events: &dyn HandleRecoveryEvent,
+        stop_receiver: &watch::Receiver<bool>,
+    ) {
+        loop {
+            let stop = wait_for_stop(stop_receiver.clone());
+            if tokio::time::timeout(options.check_interval, stop)
+                .await
+                .is_ok()
+            {
+                tracing::info!("Stop signal received, shutting down recovery watchdog");
+                return;
+            }
+
+            let Some(report) = self.check(Instant::now(), options.stall_threshold) else {
+                RECOVERY_METRICS.stalled.set(Duration::ZERO);
+                continue;
+            };
+            RECOVERY_METRICS.stalled.set(report.stalled_for);
+            if let Some((chunk, elapsed)) = &report.longest_running_chunk {
+                tracing::warn!(
+                    "Tree recovery is stalled: no chunks finished in {:?}; {} chunks are in flight, the longest-running \
+                     chunk #{} ({:?}) is running for {elapsed:?}",
+                    report.stalled_for,
+                    report.in_flight_chunk_count,
+                    chunk.index,
+                    chunk.key_range
+                );
+            } else {
+                tracing::warn!(
+                    "Tree recovery is stalled: no chunks finished in {:?}, and no chunks are in flight",
+                    report.stalled_for
+                );
+            }
+            events.recovery_stalled(&report);
+        }
+    }
+}
+
+/// Guard returned by [`RecoveryWatchdog::track_chunk()`].
+#[derive(Debug)]
+pub(super) struct ChunkGuard<'a> {
+    watchdog: &'a RecoveryWatchdog,
+    chunk_id: usize,
+}
+
+impl Drop for ChunkGuard<'_> {
+    fn drop(&mut self) {
+        self.watchdog.finish_chunk(self.chunk_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog_reports_longest_running_chunk() {
+        const THRESHOLD: Duration = Duration::from_secs(10);
+
+        let watchdog = RecoveryWatchdog::new();
+        let start = watchdog.state.lock().unwrap().last_progress;
+        assert_eq!(watchdog.check(start + THRESHOLD / 2, THRESHOLD), None);
+
+        let first_guard = watchdog.track_chunk(0, H256::zero()..=H256::repeat_byte(0x7f));
+        let _second_guard =
+            watchdog.track_chunk(1, H256::repeat_byte(0x80)..=H256::repeat_byte(0xff));
+        let report = watchdog.check(start + THRESHOLD * 2, THRESHOLD).unwrap();
+        assert!(report.stalled_for >= THRESHOLD * 2);
+        assert_eq!(report.in_flight_chunk_count, 2);
+        let (chunk, elapsed) = report.longest_running_chunk.unwrap();
+        assert_eq!(chunk.index, 0);
+        assert!(elapsed <= report.stalled_for);
+
+        drop(first_guard);
+        let now = Instant::now();
+        assert_eq!(watchdog.check(now, THRESHOLD), None);
+        let report = watchdog.check(now + THRESHOLD, THRESHOLD).unwrap();
+        assert_eq!(report.in_flight_chunk_count, 1);
+        assert_eq!(report.longest_running_chunk.unwrap().0.index, 1);
+    }
+
+    #[test]
+    fn watchdog_ignores_paused_recovery() {
+        const THRESHOLD: Duration = Duration::from_secs(10);
+
+        let watchdog = RecoveryWatchdog::new();
+        let start = watchdog.state.lock().unwrap().last_progress;
+        watchdog.set_paused(true);
+        assert_eq!(watchdog.check(start + THRESHOLD * 2, THRESHOLD), None);
+
+        watchdog.set_paused(false);
+        let resumed_at = watchdog.state.lock().unwrap().last_progress;
+        assert!(resumed_at >= start);
+        assert_eq!(watchdog.check(resumed_at + THRESHOLD / 2, THRESHOLD), None);
+        assert!(watchdog.check(resumed_at + THRESHOLD, THRESHOLD).is_some());
+    }
+}
@@ -7,8 +7,12 @@ use std::{future::Future, ops, panic, path::Path, time::Duration};
 use assert_matches::assert_matches;
 use itertools::Itertools;
 use tempfile::TempDir;
+use test_casing::test_casing;
 use tokio::sync::{mpsc, watch};
-use zksync_config::configs::{chain::OperationsManagerConfig, database::MerkleTreeConfig};
+use zksync_config::configs::{
+    chain::OperationsManagerConfig,
+    database::{MerkleTreeConfig, MerkleTreeMode},
+};
 use zksync_contracts::BaseSystemContracts;
 use zksync_dal::{ConnectionPool, StorageProcessor};
 use zksync_health_check::{CheckHealth, HealthStatus};
@@ -23,8 +27,9 @@ use zksync_types::{
 use zksync_utils::u32_to_h256;
 
 use super::{
-    GenericAsyncTree, L1BatchWithLogs, MetadataCalculator, MetadataCalculatorConfig,
-    MetadataCalculatorModeConfig,
+    helpers::TreeTaint, updater::TreeReadiness, ChunkTimingsSummary, GenericAsyncTree,
+    L1BatchWithLogs, MerkleTreeInfo, MetadataCalculator, MetadataCalculatorConfig,
+    MetadataCalculatorModeConfig, RecoveryReport, TreePruningStats, TreeState,
 };
 use crate::genesis::{ensure_genesis_state, GenesisParams};
 
@@ -63,6 +68,8 @@ async fn basic_workflow() {
     let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
 
     let (calculator, object_store) = setup_calculator(temp_dir.path(), &pool).await;
+    let mode = MerkleTreeMode::Full;
+    assert_eq!(calculator.tree_state(), TreeState::Empty { mode });
     reset_db_state(&pool, 1).await;
     let merkle_tree_hash = run_calculator(calculator, pool.clone()).await;
 
@@ -82,6 +89,11 @@ async fn basic_workflow() {
         panic!("Unexpected tree state: {:?}", calculator.tree);
     };
     assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(2));
+    let expected_state = TreeState::Ready {
+        next_l1_batch_number: L1BatchNumber(2),
+        mode,
+    };
+    assert_eq!(calculator.tree_state(), expected_state);
 }
 
 async fn expected_tree_hash(pool: &ConnectionPool) -> H256 {
@@ -149,6 +161,146 @@ async fn status_receiver_has_correct_states() {
     );
 }
 
+fn mock_tree_info(next_l1_batch_number: u32) -> MerkleTreeInfo {
+    MerkleTreeInfo {
+        mode: MerkleTreeMode::Full,
+        root_hash: H256::zero(),
+        next_l1_batch_number: L1BatchNumber(next_l1_batch_number),
+        leaf_count: 0,
+    }
+}
+
+#[test]
+fn tree_readiness_during_catch_up() {
+    let mut readiness = TreeReadiness::new(Some(2));
+    let last_sealed_l1_batch = L1BatchNumber(10);
+    // The tree is recovered to L1 batch #3 and catches up with Postgres processing 2 batches at a time.
+    for (next_l1_batch, expected_batches_behind) in [(4, 7), (6, 5), (8, 3)] {
+        let health = readiness.health(mock_tree_info(next_l1_batch), last_sealed_l1_batch);
+        assert_matches!(health.status(), HealthStatus::NotReady);
+        let details = health.details().unwrap();
+        assert_eq!(details["batches_behind"], expected_batches_behind);
+        assert_eq!(details["next_l1_batch_number"], next_l1_batch);
+    }
+
+    let health = readiness.health(mock_tree_info(10), last_sealed_l1_batch);
+    assert_matches!(health.status(), HealthStatus::Ready);
+    let details = health.details().unwrap();
+    assert!(details.get("batches_behind").is_none(), "{details:?}");
+
+    // Falling behind after catching up doesn't influence readiness.
+    let health = readiness.health(mock_tree_info(11), L1BatchNumber(100));
+    assert_matches!(health.status(), HealthStatus::Ready);
+}
+
+#[test]
+fn tree_readiness_without_lag() {
+    let mut readiness = TreeReadiness::new(None);
+    let health = readiness.health(mock_tree_info(1), L1BatchNumber(1_000));
+    assert_matches!(health.status(), HealthStatus::Ready);
+}
+
+#[test]
+fn tree_readiness_with_taint() {
+    let taint = TreeTaint {
+        expected_root_hash: H256::repeat_byte(1),
+        actual_root_hash: H256::repeat_byte(2),
+    };
+    let mut readiness = TreeReadiness::new(Some(2)).with_taint(Some(taint));
+    let health = readiness.health(mock_tree_info(4), L1BatchNumber(10));
+    assert_matches!(health.status(), HealthStatus::NotReady);
+    let details = health.details().unwrap();
+    assert_eq!(details["tainted"], serde_json::to_value(taint).unwrap());
+
+    // A caught-up tainted tree is operational, but is never reported as healthy.
+    let health = readiness.health(mock_tree_info(10), L1BatchNumber(10));
+    assert_matches!(health.status(), HealthStatus::Affected);
+    let details = health.details().unwrap();
+    assert_eq!(details["mode"], "full");
+    assert_eq!(details["tainted"], serde_json::to_value(taint).unwrap());
+}
+
+fn mock_recovery_report() -> RecoveryReport {
+    RecoveryReport::Recovered {
+        l1_batch: L1BatchNumber(3),
+        miniblock: MiniblockNumber(42),
+        chunks: 10,
+        entries: 1_000,
+        duration: Duration::from_secs(60),
+        root_hash: H256::zero(),
+        chunk_timings: ChunkTimingsSummary::default(),
+    }
+}
+
+#[test_casing(2, [Some(2), None])]
+fn tree_readiness_during_catch_up_after_recovery(ready_lag_batches: Option<u32>) {
+    let mut readiness = TreeReadiness::after_recovery(ready_lag_batches, mock_recovery_report());
+    let last_sealed_l1_batch = L1BatchNumber(10);
+    let allowed_lag = ready_lag_batches.unwrap_or(0);
+    for next_l1_batch in (4..=11).step_by(2) {
+        let batches_behind = 11 - next_l1_batch;
+        let health = readiness.health(mock_tree_info(next_l1_batch), last_sealed_l1_batch);
+        let details = health.details().unwrap();
+        if batches_behind > allowed_lag {
+            // The tree stays recovering, but is distinguishable from the recovery phase by `catching_up`.
+            assert_matches!(health.status(), HealthStatus::Recovering);
+            assert_eq!(details["catching_up"], true);
+            assert_eq!(details["batches_behind"], batches_behind);
+            assert_eq!(details["mode"], "full");
+            assert_eq!(details["recovery_report"]["outcome"], "recovered");
+        } else {
+            assert_matches!(health.status(), HealthStatus::Ready);
+            assert!(details.get("catching_up").is_none(), "{details:?}");
+            assert!(details.get("recovery_report").is_none(), "{details:?}");
+        }
+    }
+
+    let health = readiness.health(mock_tree_info(11), last_sealed_l1_batch);
+    assert_matches!(health.status(), HealthStatus::Ready);
+    // Falling behind after catching up doesn't influence readiness.
+    let health = readiness.health(mock_tree_info(11), L1BatchNumber(100));
+    assert_matches!(health.status(), HealthStatus::Ready);
+}
+
+#[tokio::test]
+async fn calculator_with_ready_lag() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let (mut merkle_tree_config, operation_config) = create_config(temp_dir.path());
+    merkle_tree_config.max_l1_batches_per_iter = 1;
+    merkle_tree_config.tree_ready_lag_batches = Some(2);
+    let mut calculator = setup_calculator_with_options(
+        &merkle_tree_config,
+        &operation_config,
+        &pool,
+        MetadataCalculatorModeConfig::Lightweight,
+    )
+    .await;
+    let tree_health_check = calculator.tree_health_check();
+    reset_db_state(&pool, 5).await;
+
+    let (stop_sx, stop_rx) = watch::channel(false);
+    let (delay_sx, mut delay_rx) = mpsc::unbounded_channel();
+    calculator.delayer.delay_notifier = delay_sx;
+    let calculator_handle = tokio::spawn(calculator.run(pool, stop_rx));
+    // Wait until the calculator has processed all L1 batches one by one.
+    let (next_l1_batch, _) = tokio::time::timeout(RUN_TIMEOUT, delay_rx.recv())
+        .await
+        .expect("metadata calculator timed out processing L1 batches")
+        .unwrap();
+    assert_eq!(next_l1_batch, L1BatchNumber(6));
+
+    let health = tree_health_check.check_health().await;
+    assert_matches!(health.status(), HealthStatus::Ready);
+    assert_eq!(health.details().unwrap()["next_l1_batch_number"], 6);
+
+    stop_sx.send_replace(true);
+    run_with_timeout(RUN_TIMEOUT, calculator_handle)
+        .await
+        .unwrap()
+        .unwrap();
+}
+
 #[tokio::test]
 async fn multi_l1_batch_workflow() {
     let pool = ConnectionPool::test_pool().await;
@@ -235,6 +387,129 @@ async fn running_metadata_calculator_with_additional_blocks() {
     assert_eq!(root_hash_for_full_tree, updated_root_hash);
 }
 
+#[tokio::test]
+async fn upgrading_lightweight_tree_to_full_mode() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    // Process the first L1 batches in the full mode, so that the tree is upgraded only partially.
+    let (calculator, _) = setup_calculator(temp_dir.path(), &pool).await;
+    reset_db_state(&pool, 5).await;
+    run_calculator(calculator, pool.clone()).await;
+    let new_logs = gen_storage_logs(100..200, 5);
+    extend_db_state(&mut pool.access_storage().await.unwrap(), new_logs).await;
+    let calculator = setup_lightweight_calculator(temp_dir.path(), &pool).await;
+    let root_hash = run_calculator(calculator, pool.clone()).await;
+
+    // By default, the full calculator should continue from the same spot without backfilling L1 batches.
+    let (mut merkle_tree_config, operation_config) = create_config(temp_dir.path());
+    let store_factory = &ObjectStoreFactory::mock();
+    let object_store = store_factory.create_store().await;
+    let mode = MetadataCalculatorModeConfig::Full {
+        store_factory: Some(store_factory),
+    };
+    let calculator =
+        setup_calculator_with_options(&merkle_tree_config, &operation_config, &pool, mode).await;
+    assert_eq!(run_calculator(calculator, pool.clone()).await, root_hash);
+    assert!(object_store
+        .get::<PrepareBasicCircuitsJob>(L1BatchNumber(6))
+        .await
+        .is_err());
+
+    merkle_tree_config.recovery.allow_lightweight_tree_upgrade = true;
+    let mode = MetadataCalculatorModeConfig::Full {
+        store_factory: Some(store_factory),
+    };
+    let calculator =
+        setup_calculator_with_options(&merkle_tree_config, &operation_config, &pool, mode).await;
+    assert_eq!(run_calculator(calculator, pool.clone()).await, root_hash);
+
+    // Compare witness inputs produced by the upgraded tree with ones produced by a tree processed in the full mode.
+    let full_temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let (calculator, full_object_store) = setup_calculator(full_temp_dir.path(), &pool).await;
+    assert_eq!(run_calculator(calculator, pool.clone()).await, root_hash);
+    for l1_batch_number in 6..=10 {
+        let l1_batch_number = L1BatchNumber(l1_batch_number);
+        let job: PrepareBasicCircuitsJob = object_store.get(l1_batch_number).await.unwrap();
+        let expected_job: PrepareBasicCircuitsJob =
+            full_object_store.get(l1_batch_number).await.unwrap();
+        assert_eq!(
+            job.next_enumeration_index(),
+            expected_job.next_enumeration_index()
+        );
+        let merkle_paths: Vec<_> = job.into_merkle_paths().collect();
+        let expected_merkle_paths: Vec<_> = expected_job.into_merkle_paths().collect();
+        assert_eq!(merkle_paths, expected_merkle_paths);
+    }
+    // L1 batches processed in the full mode before switching to the lightweight mode shouldn't be re-processed.
+    assert!(object_store
+        .get::<PrepareBasicCircuitsJob>(L1BatchNumber(5))
+        .await
+        .is_err());
+
+    // The upgraded tree shouldn't be upgraded again on restart.
+    let calculator = setup_calculator_with_options(
+        &merkle_tree_config,
+        &operation_config,
+        &pool,
+        MetadataCalculatorModeConfig::Full {
+            store_factory: Some(&ObjectStoreFactory::mock()),
+        },
+    )
+    .await;
+    let GenericAsyncTree::Ready(tree) = &calculator.tree else {
+        panic!("Unexpected tree state: {:?}", calculator.tree);
+    };
+    assert_eq!(tree.lightweight_since().unwrap(), None);
+    assert_eq!(tree.next_l1_batch_number(), L1BatchNumber(11));
+}
+
+#[tokio::test]
+async fn backfilling_lightweight_tree_with_pruned_versions() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let (calculator, _) = setup_calculator(temp_dir.path(), &pool).await;
+    reset_db_state(&pool, 5).await;
+    run_calculator(calculator, pool.clone()).await;
+    let new_logs = gen_storage_logs(100..200, 5);
+    extend_db_state(&mut pool.access_storage().await.unwrap(), new_logs).await;
+    let calculator = setup_lightweight_calculator(temp_dir.path(), &pool).await;
+    let pruner = calculator.tree_pruner();
+    let root_hash = run_calculator(calculator, pool.clone()).await;
+
+    // Prune the tree version for the last L1 batch processed in the full mode.
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let stats = pruner
+        .prune_and_compact(2, &stop_receiver)
+        .await
+        .unwrap()
+        .expect("pruning was interrupted");
+    assert!(stats.pruned_key_count > 0, "{stats:?}");
+    drop(pruner); // releases RocksDB
+
+    let (mut merkle_tree_config, operation_config) = create_config(temp_dir.path());
+    merkle_tree_config.recovery.allow_lightweight_tree_upgrade = true;
+    let store_factory = &ObjectStoreFactory::mock();
+    let mode = MetadataCalculatorModeConfig::Full {
+        store_factory: Some(store_factory),
+    };
+    let calculator =
+        setup_calculator_with_options(&merkle_tree_config, &operation_config, &pool, mode).await;
+    let err = run_with_timeout(RUN_TIMEOUT, calculator.run(pool.clone(), stop_receiver))
+        .await
+        .unwrap_err();
+    let err = format!("{err:#}");
+    assert!(err.contains("L1 batch #5 was pruned"), "{err}");
+
+    // Without the backfill, the tree should continue in the full mode.
+    merkle_tree_config.recovery.allow_lightweight_tree_upgrade = false;
+    let mode = MetadataCalculatorModeConfig::Full {
+        store_factory: Some(store_factory),
+    };
+    let calculator =
+        setup_calculator_with_options(&merkle_tree_config, &operation_config, &pool, mode).await;
+    assert_eq!(run_calculator(calculator, pool).await, root_hash);
+}
+
 #[tokio::test]
 async fn shutting_down_calculator() {
     let pool = ConnectionPool::test_pool().await;
@@ -262,6 +537,59 @@ async fn shutting_down_calculator() {
         .unwrap();
 }
 
+#[tokio::test]
+async fn pruning_tree_versions() {
+    let pool = ConnectionPool::test_pool().await;
+    let temp_dir = TempDir::new().expect("failed get temporary directory for RocksDB");
+    let calculator = setup_lightweight_calculator(temp_dir.path(), &pool).await;
+    let pruner = calculator.tree_pruner();
+    reset_db_state(&pool, 10).await;
+    let root_hash = run_calculator(calculator, pool.clone()).await;
+
+    let (_stop_sender, stop_receiver) = watch::channel(false);
+    let stats = pruner
+        .prune_and_compact(5, &stop_receiver)
+        .await
+        .unwrap()
+        .expect("pruning was interrupted");
+    assert!(stats.pruned_key_count > 0, "{stats:?}");
+    let stats = pruner
+        .prune_and_compact(5, &stop_receiver)
+        .await
+        .unwrap()
+        .expect("pruning was interrupted");
+    assert_eq!(stats, TreePruningStats::default());
+    drop(pruner); // releases RocksDB
+
+    // Pruning configured for the calculator should be performed on start.
+    let (mut merkle_tree_config, operation_config) = create_config(temp_dir.path());
+    merkle_tree_config.recovery.pruning_retained_versions = Some(2);
+    let mode = MetadataCalculatorModeConfig::Lightweight;
+    let calculator =
+        setup_calculator_with_options(&merkle_tree_config, &operation_config, &pool, mode).await;
+    let pruner = calculator.tree_pruner();
+    assert_eq!(run_calculator(calculator, pool.clone()).await, root_hash);
+    let stats = pruner
+        .prune_and_compact(2, &stop_receiver)
+        .await
+        .unwrap()
+        .expect("pruning was interrupted");
+    assert_eq!(stats.pruned_key_count, 0);
+    drop(pruner);
+
+    let calculator = setup_lightweight_calculator(temp_dir.path(), &pool).await;
+    let GenericAsyncTree::Ready(tree) = &calculator.tree else {
+        panic!("Unexpected tree state: {:?}", calculator.tree);
+    };
+    assert_eq!(tree.root_hash(), root_hash);
+    for l1_batch_number in 8..=10 {
+        tree.reader()
+            .verify_consistency(L1BatchNumber(l1_batch_number))
+            .await
+            .unwrap();
+    }
+}
+
 async fn test_postgres_backup_recovery(
     sleep_between_batches: bool,
     insert_batch_without_metadata: bool,
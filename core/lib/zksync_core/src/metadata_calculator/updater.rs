@@ -4,26 +4,134 @@ use std::{ops, time::Instant};
 
 use anyhow::Context as _;
 use futures::{future, FutureExt};
+use serde::Serialize;
 use tokio::sync::watch;
 use zksync_commitment_utils::{bootloader_initial_content_commitment, events_queue_commitment};
 use zksync_config::configs::database::MerkleTreeMode;
 use zksync_dal::{ConnectionPool, StorageProcessor};
-use zksync_health_check::HealthUpdater;
+use zksync_health_check::{Health, HealthStatus, HealthUpdater};
 use zksync_merkle_tree::domain::TreeMetadata;
 use zksync_object_store::ObjectStore;
 use zksync_types::{block::L1BatchHeader, writes::InitialStorageWrite, L1BatchNumber, H256, U256};
 
 use super::{
-    helpers::{AsyncTree, Delayer, L1BatchWithLogs},
+    helpers::{AsyncTree, Delayer, L1BatchWithLogs, MerkleTreeInfo, TreeTaint},
     metrics::{TreeUpdateStage, METRICS},
-    MetadataCalculator,
+    MetadataCalculator, RecoveryReport,
 };
 use crate::utils::wait_for_l1_batch;
 
+/// Information about a Merkle tree catching up with Postgres reported via the health check.
+#[derive(Debug, Serialize)]
+struct CatchingUpInfo<'a> {
+    #[serde(flatten)]
+    tree_info: MerkleTreeInfo,
+    /// Always set to `true`, so that catch-up is easy to distinguish from recovery and normal operation.
+    catching_up: bool,
+    batches_behind: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recovery_report: Option<&'a RecoveryReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tainted: Option<TreeTaint>,
+}
+
+/// Information about a tainted Merkle tree used for normal operation reported via the health check.
+#[derive(Debug, Serialize)]
+struct TaintedTreeInfo {
+    #[serde(flatten)]
+    tree_info: MerkleTreeInfo,
+    tainted: TreeTaint,
+}
+
+/// Tracks whether the tree has caught up with Postgres closely enough to be reported as ready.
+#[derive(Debug)]
+pub(super) struct TreeReadiness {
+    ready_lag_batches: Option<u32>,
+    is_caught_up: bool,
+    /// Report on the recovery preceding catch-up. If set, the tree is reported as [`HealthStatus::Recovering`]
+    /// rather than [`HealthStatus::NotReady`] while catching up.
+    recovery_report: Option<RecoveryReport>,
+    /// Taint of the tree. A tainted tree is reported as [`HealthStatus::Affected`] rather than
+    /// [`HealthStatus::Ready`] once it's caught up.
+    taint: Option<TreeTaint>,
+}
+
+impl TreeReadiness {
+    /// Creates readiness tracking. If `ready_lag_batches` is `None`, the tree is always reported as ready.
+    pub fn new(ready_lag_batches: Option<u32>) -> Self {
+        Self {
+            ready_lag_batches,
+            is_caught_up: ready_lag_batches.is_none(),
+            recovery_report: None,
+            taint: None,
+        }
+    }
+
+    /// Creates readiness tracking for a tree that was just recovered from a snapshot. Until the tree catches up
+    /// to within `ready_lag_batches` (or fully, if the lag is `None`), it's reported as recovering
+    /// with the recovery `report` attached to health details.
+    pub fn after_recovery(ready_lag_batches: Option<u32>, report: RecoveryReport) -> Self {
+        Self {
+            ready_lag_batches: Some(ready_lag_batches.unwrap_or(0)),
+            is_caught_up: false,
+            recovery_report: Some(report),
+            taint: None,
+        }
+    }
+
+    /// Reports the tree `taint` (if any) via the health check.
+    pub fn with_taint(mut self, taint: Option<TreeTaint>) -> Self {
+        self.taint = taint;
+        self
+    }
+
+    /// Returns the tree health given the last sealed L1 batch in Postgres. Once the tree has caught up
+    /// to within the configured lag, it's always reported as ready.
+    pub fn health(
+        &mut self,
+        tree_info: MerkleTreeInfo,
+        last_sealed_l1_batch: L1BatchNumber,
+    ) -> Health {
+        let batches_behind =
+            (last_sealed_l1_batch.0 + 1).saturating_sub(tree_info.next_l1_batch_number.0);
+        METRICS.tree_lag_batches.set(batches_behind.into());
+
+        if !self.is_caught_up {
+            let ready_lag_batches = self.ready_lag_batches.unwrap_or(u32::MAX);
+            if batches_behind > ready_lag_batches {
+                let status = if self.recovery_report.is_some() {
+                    HealthStatus::Recovering
+                } else {
+                    HealthStatus::NotReady
+                };
+                return Health::from(status).with_details(CatchingUpInfo {
+                    tree_info,
+                    catching_up: true,
+                    batches_behind: batches_behind.into(),
+                    recovery_report: self.recovery_report.as_ref(),
+                    tainted: self.taint,
+                });
+            }
+            tracing::info!(
+                "Merkle tree has caught up with Postgres ({batches_behind} L1 batches behind, \
+                 allowed lag is {ready_lag_batches}); marking it as ready"
+            );
+            self.is_caught_up = true;
+            self.recovery_report = None;
+        }
+        match self.taint {
+            Some(tainted) => Health::from(HealthStatus::Affected)
+                .with_details(TaintedTreeInfo { tree_info, tainted }),
+            None => tree_info.into(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(super) struct TreeUpdater {
     tree: AsyncTree,
     max_l1_batches_per_iter: usize,
+    readiness: TreeReadiness,
     object_store: Option<Box<dyn ObjectStore>>,
 }
 
@@ -31,11 +139,13 @@ impl TreeUpdater {
     pub fn new(
         tree: AsyncTree,
         max_l1_batches_per_iter: usize,
+        readiness: TreeReadiness,
         object_store: Option<Box<dyn ObjectStore>>,
     ) -> Self {
         Self {
             tree,
             max_l1_batches_per_iter,
+            readiness,
             object_store,
         }
     }
@@ -234,11 +344,12 @@ impl TreeUpdater {
         )
     }
 
+    /// Processes the next L1 batches, if any. Returns the last sealed L1 batch in Postgres.
     async fn step(
         &mut self,
         mut storage: StorageProcessor<'_>,
         next_l1_batch_to_seal: &mut L1BatchNumber,
-    ) {
+    ) -> L1BatchNumber {
         let last_sealed_l1_batch = storage
             .blocks_dal()
             .get_sealed_l1_batch_number()
@@ -258,6 +369,7 @@ impl TreeUpdater {
                 .process_multiple_batches(&mut storage, l1_batch_numbers)
                 .await;
         }
+        last_sealed_l1_batch
     }
 
     /// The processing loop for this updater.
@@ -305,7 +417,7 @@ impl TreeUpdater {
             max_batches_per_iter = self.max_l1_batches_per_iter
         );
         let tree_info = tree.reader().info().await;
-        health_updater.update(tree_info.into());
+        health_updater.update(self.readiness.health(tree_info, current_db_batch));
 
         // It may be the case that we don't have any L1 batches with metadata in Postgres, e.g. after
         // recovering from a snapshot. We cannot wait for such a batch to appear (*this* is the component
@@ -333,7 +445,7 @@ impl TreeUpdater {
                 tracing::info!("Truncated Merkle tree to L1 batch #{next_l1_batch_to_seal}");
 
                 let tree_info = tree.reader().info().await;
-                health_updater.update(tree_info.into());
+                health_updater.update(self.readiness.health(tree_info, current_db_batch));
             }
         }
 
@@ -345,7 +457,7 @@ impl TreeUpdater {
             let storage = pool.access_storage_tagged("metadata_calculator").await?;
 
             let snapshot = *next_l1_batch_to_seal;
-            self.step(storage, &mut next_l1_batch_to_seal).await;
+            let last_sealed_l1_batch = self.step(storage, &mut next_l1_batch_to_seal).await;
             let delay = if snapshot == *next_l1_batch_to_seal {
                 tracing::trace!(
                     "Metadata calculator (next L1 batch: #{next_l1_batch_to_seal}) \
@@ -354,7 +466,8 @@ impl TreeUpdater {
                 delayer.wait(&self.tree).left_future()
             } else {
                 let tree_info = self.tree.reader().info().await;
-                health_updater.update(tree_info.into());
+                let health = self.readiness.health(tree_info, last_sealed_l1_batch);
+                health_updater.update(health);
 
                 tracing::trace!(
                     "Metadata calculator (next L1 batch: #{next_l1_batch_to_seal}) made progress from #{snapshot}"